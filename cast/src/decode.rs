@@ -0,0 +1,421 @@
+//! Decoding of calldata into a tree of calls
+//!
+//! Nested calldata, like the calls of a multicall, is decoded recursively, wrappers that don't
+//! encode their calls as ABI `bytes`, like Gnosis Safe's `multiSend` and the commands of
+//! Uniswap's universal router, are unwrapped explicitly.
+use ethers_core::{
+    abi::{self, Function, Token},
+    types::{Address, U256},
+};
+use eyre::{Context, Result};
+use foundry_utils::get_func;
+use std::{collections::BTreeMap, convert::TryInto, fmt};
+
+/// Well-known functions whose calldata wraps other calls
+const WRAPPERS: &[&str] = &[
+    // Multicall3
+    "aggregate((address target,bytes callData)[] calls)",
+    "tryAggregate(bool requireSuccess,(address target,bytes callData)[] calls)",
+    "blockAndAggregate((address target,bytes callData)[] calls)",
+    "tryBlockAndAggregate(bool requireSuccess,(address target,bytes callData)[] calls)",
+    "aggregate3((address target,bool allowFailure,bytes callData)[] calls)",
+    "aggregate3Value((address target,bool allowFailure,uint256 value,bytes callData)[] calls)",
+    // Uniswap V3 periphery and other self multicalls
+    "multicall(bytes[] data)",
+    "multicall(uint256 deadline,bytes[] data)",
+    // Gnosis Safe
+    "execTransaction(address to,uint256 value,bytes data,uint8 operation,uint256 safeTxGas,uint256 baseGas,uint256 gasPrice,address gasToken,address refundReceiver,bytes signatures)",
+    "multiSend(bytes transactions)",
+    // Uniswap universal router
+    "execute(bytes commands,bytes[] inputs)",
+    "execute(bytes commands,bytes[] inputs,uint256 deadline)",
+];
+
+/// The commands of Uniswap's universal router by their type, with the ABI encoded inputs of each
+/// command as the parameters
+const ROUTER_COMMANDS: &[(u8, &str)] = &[
+    (0x00, "V3_SWAP_EXACT_IN(address recipient,uint256 amountIn,uint256 amountOutMin,bytes path,bool payerIsUser)"),
+    (0x01, "V3_SWAP_EXACT_OUT(address recipient,uint256 amountOut,uint256 amountInMax,bytes path,bool payerIsUser)"),
+    (0x02, "PERMIT2_TRANSFER_FROM(address token,address recipient,uint160 amount)"),
+    (0x03, "PERMIT2_PERMIT_BATCH(((address,uint160,uint48,uint48)[],address,uint256) permitBatch,bytes signature)"),
+    (0x04, "SWEEP(address token,address recipient,uint256 amountMin)"),
+    (0x05, "TRANSFER(address token,address recipient,uint256 value)"),
+    (0x06, "PAY_PORTION(address token,address recipient,uint256 bips)"),
+    (0x08, "V2_SWAP_EXACT_IN(address recipient,uint256 amountIn,uint256 amountOutMin,address[] path,bool payerIsUser)"),
+    (0x09, "V2_SWAP_EXACT_OUT(address recipient,uint256 amountOut,uint256 amountInMax,address[] path,bool payerIsUser)"),
+    (0x0a, "PERMIT2_PERMIT(((address,uint160,uint48,uint48),address,uint256) permitSingle,bytes signature)"),
+    (0x0b, "WRAP_ETH(address recipient,uint256 amountMin)"),
+    (0x0c, "UNWRAP_WETH(address recipient,uint256 amountMin)"),
+    (0x0d, "PERMIT2_TRANSFER_FROM_BATCH((address,address,uint160,address)[] batchDetails)"),
+    (0x0e, "BALANCE_CHECK_ERC20(address owner,address token,uint256 minBalance)"),
+];
+
+/// The bits of a universal router command that select its type
+const COMMAND_TYPE_MASK: u8 = 0x3f;
+/// The bit of a universal router command that allows it to revert
+const ALLOW_REVERT_FLAG: u8 = 0x80;
+
+/// Decodes calldata of known functions and of the well-known wrappers
+#[derive(Debug, Clone)]
+pub struct CalldataDecoder {
+    functions: BTreeMap<[u8; 4], Function>,
+    commands: BTreeMap<u8, Function>,
+}
+
+impl CalldataDecoder {
+    /// Returns a decoder of the `functions` and of the well-known wrappers, the `functions` take
+    /// precedence over a wrapper with the same selector
+    pub fn new(functions: impl IntoIterator<Item = Function>) -> Self {
+        let functions = WRAPPERS
+            .iter()
+            .map(|sig| get_func(sig).expect("valid signature"))
+            .chain(functions)
+            .map(|func| (func.short_signature(), func))
+            .collect();
+        let commands = ROUTER_COMMANDS
+            .iter()
+            .map(|(command, sig)| (*command, get_func(sig).expect("valid signature")))
+            .collect();
+        Self { functions, commands }
+    }
+
+    /// Decodes the calldata, which starts with the selector of a known function
+    pub fn decode(&self, calldata: &[u8]) -> Result<DecodedCall> {
+        if calldata.len() < 4 {
+            eyre::bail!("calldata is shorter than a selector")
+        }
+        let selector: [u8; 4] = calldata[..4].try_into().expect("4 bytes");
+        let func = self
+            .functions
+            .get(&selector)
+            .ok_or_else(|| eyre::eyre!("unknown selector 0x{}", hex::encode(selector)))?;
+        let tokens = func
+            .decode_input(&calldata[4..])
+            .wrap_err_with(|| format!("failed to decode the calldata of {}", signature(func)))?;
+
+        let params = match (func.name.as_str(), tokens.as_slice()) {
+            ("multiSend", [Token::Bytes(transactions)]) => {
+                vec![("transactions".to_string(), self.multi_send(transactions)?)]
+            }
+            ("execute", [Token::Bytes(commands), Token::Array(inputs), ..]) => {
+                let mut params =
+                    vec![("commands".to_string(), self.router_commands(commands, inputs)?)];
+                params.extend(
+                    func.inputs
+                        .iter()
+                        .zip(&tokens)
+                        .skip(2)
+                        .map(|(param, token)| (param.name.clone(), self.value(token.clone()))),
+                );
+                params
+            }
+            _ => func
+                .inputs
+                .iter()
+                .zip(&tokens)
+                .map(|(param, token)| (param.name.clone(), self.value(token.clone())))
+                .collect(),
+        };
+        Ok(DecodedCall { signature: signature(func), params })
+    }
+
+    /// Decodes the token, `bytes` that are calldata of a known function are decoded as a call
+    fn value(&self, token: Token) -> DecodedValue {
+        match token {
+            Token::Bytes(bytes) => match self.decode(&bytes) {
+                Ok(call) => DecodedValue::Call(Box::new(call)),
+                Err(_) => DecodedValue::Token(Token::Bytes(bytes)),
+            },
+            Token::Array(tokens) | Token::FixedArray(tokens) => {
+                DecodedValue::Array(tokens.into_iter().map(|token| self.value(token)).collect())
+            }
+            Token::Tuple(tokens) => {
+                DecodedValue::Tuple(tokens.into_iter().map(|token| self.value(token)).collect())
+            }
+            token => DecodedValue::Token(token),
+        }
+    }
+
+    /// Decodes the transactions of Gnosis Safe's `multiSend`, which are packed as
+    /// `operation (1 byte), to (20 bytes), value (32 bytes), data length (32 bytes), data`
+    fn multi_send(&self, mut transactions: &[u8]) -> Result<DecodedValue> {
+        let mut decoded = Vec::new();
+        while !transactions.is_empty() {
+            if transactions.len() < 85 {
+                eyre::bail!("truncated multiSend transaction")
+            }
+            let len = U256::from_big_endian(&transactions[53..85]);
+            if len > U256::from(transactions.len() - 85) {
+                eyre::bail!("truncated multiSend transaction data")
+            }
+            let end = 85 + len.as_usize();
+            decoded.push(DecodedValue::Tuple(vec![
+                DecodedValue::Token(Token::Uint(transactions[0].into())),
+                DecodedValue::Token(Token::Address(Address::from_slice(&transactions[1..21]))),
+                DecodedValue::Token(Token::Uint(transactions[21..53].into())),
+                self.value(Token::Bytes(transactions[85..end].to_vec())),
+            ]));
+            transactions = &transactions[end..];
+        }
+        Ok(DecodedValue::Array(decoded))
+    }
+
+    /// Decodes the commands of Uniswap's universal router, each command byte selects the type of
+    /// the command whose parameters are ABI encoded in the input of the same index
+    fn router_commands(&self, commands: &[u8], inputs: &[Token]) -> Result<DecodedValue> {
+        if commands.len() != inputs.len() {
+            eyre::bail!("{} commands but {} inputs", commands.len(), inputs.len())
+        }
+        let decoded = commands
+            .iter()
+            .zip(inputs)
+            .map(|(command, input)| {
+                let input = match input {
+                    Token::Bytes(input) => input,
+                    _ => return DecodedValue::Token(input.clone()),
+                };
+                let func = match self.commands.get(&(command & COMMAND_TYPE_MASK)) {
+                    Some(func) => func,
+                    None => return DecodedValue::Token(Token::Bytes(input.clone())),
+                };
+                let types = func.inputs.iter().map(|param| param.kind.clone()).collect::<Vec<_>>();
+                let tokens = match abi::decode(&types, input) {
+                    Ok(tokens) => tokens,
+                    Err(_) => return DecodedValue::Token(Token::Bytes(input.clone())),
+                };
+                let mut signature = signature(func);
+                if command & ALLOW_REVERT_FLAG != 0 {
+                    signature.push_str(" [allow revert]");
+                }
+                let params = func
+                    .inputs
+                    .iter()
+                    .zip(tokens)
+                    .map(|(param, token)| (param.name.clone(), self.value(token)))
+                    .collect();
+                DecodedValue::Call(Box::new(DecodedCall { signature, params }))
+            })
+            .collect();
+        Ok(DecodedValue::Array(decoded))
+    }
+}
+
+/// Returns the signature of the function without its outputs, like `transfer(address,uint256)`
+fn signature(func: &Function) -> String {
+    let types = func.inputs.iter().map(|param| param.kind.to_string()).collect::<Vec<_>>();
+    format!("{}({})", func.name, types.join(","))
+}
+
+/// A decoded call, which is displayed as an indented tree of its parameters
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedCall {
+    /// The signature of the function, like `transfer(address,uint256)`
+    pub signature: String,
+    /// The names of the parameters, which may be empty, and their values
+    pub params: Vec<(String, DecodedValue)>,
+}
+
+/// The value of a parameter of a decoded call
+#[derive(Debug, Clone, PartialEq)]
+pub enum DecodedValue {
+    Token(Token),
+    /// Calldata of a known function
+    Call(Box<DecodedCall>),
+    Array(Vec<DecodedValue>),
+    Tuple(Vec<DecodedValue>),
+}
+
+impl DecodedCall {
+    fn write(&self, f: &mut fmt::Formatter<'_>, indent: usize) -> fmt::Result {
+        writeln!(f, "{}", self.signature)?;
+        for (idx, (name, value)) in self.params.iter().enumerate() {
+            let label = if name.is_empty() { idx.to_string() } else { name.clone() };
+            value.write(f, &label, indent + 1)?;
+        }
+        Ok(())
+    }
+}
+
+impl DecodedValue {
+    fn write(&self, f: &mut fmt::Formatter<'_>, label: &str, indent: usize) -> fmt::Result {
+        let pad = "  ".repeat(indent);
+        match self {
+            DecodedValue::Token(token) => {
+                writeln!(f, "{}{}: {}", pad, label, foundry_utils::format_token(token))
+            }
+            DecodedValue::Call(call) => {
+                write!(f, "{}{}: ", pad, label)?;
+                call.write(f, indent)
+            }
+            DecodedValue::Array(values) | DecodedValue::Tuple(values) => {
+                let (open, close) = match self {
+                    DecodedValue::Array(_) => ("[", "]"),
+                    _ => ("(", ")"),
+                };
+                if values.is_empty() {
+                    return writeln!(f, "{}{}: {}{}", pad, label, open, close)
+                }
+                writeln!(f, "{}{}: {}", pad, label, open)?;
+                for (idx, value) in values.iter().enumerate() {
+                    value.write(f, &idx.to_string(), indent + 1)?;
+                }
+                writeln!(f, "{}{}", pad, close)
+            }
+        }
+    }
+}
+
+impl fmt::Display for DecodedCall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transfer() -> Function {
+        get_func("transfer(address to,uint256 amount)").unwrap()
+    }
+
+    fn transfer_call(amount: u64) -> Vec<u8> {
+        transfer()
+            .encode_input(&[
+                Token::Address(Address::from_low_u64_be(2)),
+                Token::Uint(amount.into()),
+            ])
+            .unwrap()
+    }
+
+    #[test]
+    fn unwraps_multicall3() {
+        let aggregate3 = get_func("aggregate3((address,bool,bytes)[] calls)").unwrap();
+        let calldata = aggregate3
+            .encode_input(&[Token::Array(vec![
+                Token::Tuple(vec![
+                    Token::Address(Address::from_low_u64_be(1)),
+                    Token::Bool(false),
+                    Token::Bytes(transfer_call(7)),
+                ]),
+                Token::Tuple(vec![
+                    Token::Address(Address::from_low_u64_be(1)),
+                    Token::Bool(true),
+                    Token::Bytes(vec![0xde, 0xad]),
+                ]),
+            ])])
+            .unwrap();
+
+        let decoded = CalldataDecoder::new([transfer()]).decode(&calldata).unwrap();
+        assert_eq!(
+            decoded.to_string(),
+            "\
+aggregate3((address,bool,bytes)[])
+  calls: [
+    0: (
+      0: 0x0000000000000000000000000000000000000001
+      1: false
+      2: transfer(address,uint256)
+        to: 0x0000000000000000000000000000000000000002
+        amount: 7
+    )
+    1: (
+      0: 0x0000000000000000000000000000000000000001
+      1: true
+      2: 0xdead
+    )
+  ]
+"
+        );
+
+        // unknown calls are kept as bytes
+        let decoded = CalldataDecoder::new([]).decode(&calldata).unwrap();
+        let call = match &decoded.params[0].1 {
+            DecodedValue::Array(calls) => calls[0].clone(),
+            value => panic!("unexpected value {:?}", value),
+        };
+        assert_eq!(
+            call,
+            DecodedValue::Tuple(vec![
+                DecodedValue::Token(Token::Address(Address::from_low_u64_be(1))),
+                DecodedValue::Token(Token::Bool(false)),
+                DecodedValue::Token(Token::Bytes(transfer_call(7))),
+            ])
+        );
+    }
+
+    #[test]
+    fn unwraps_safe_multi_send() {
+        let mut transactions = Vec::new();
+        for amount in [1u64, 2] {
+            let data = transfer_call(amount);
+            transactions.push(0u8);
+            transactions.extend_from_slice(Address::from_low_u64_be(3).as_bytes());
+            transactions.extend_from_slice(&[0u8; 32]);
+            let mut len = [0u8; 32];
+            U256::from(data.len()).to_big_endian(&mut len);
+            transactions.extend_from_slice(&len);
+            transactions.extend_from_slice(&data);
+        }
+        let multi_send = get_func("multiSend(bytes transactions)").unwrap();
+        let calldata = multi_send.encode_input(&[Token::Bytes(transactions)]).unwrap();
+
+        let decoder = CalldataDecoder::new([transfer()]);
+        let decoded = decoder.decode(&calldata).unwrap();
+        let transactions = match &decoded.params[0].1 {
+            DecodedValue::Array(transactions) => transactions.clone(),
+            value => panic!("unexpected value {:?}", value),
+        };
+        assert_eq!(transactions.len(), 2);
+        assert_eq!(
+            transactions[1],
+            DecodedValue::Tuple(vec![
+                DecodedValue::Token(Token::Uint(0.into())),
+                DecodedValue::Token(Token::Address(Address::from_low_u64_be(3))),
+                DecodedValue::Token(Token::Uint(0.into())),
+                DecodedValue::Call(Box::new(decoder.decode(&transfer_call(2)).unwrap())),
+            ])
+        );
+
+        // a truncated transaction is an error
+        let truncated = multi_send.encode_input(&[Token::Bytes(vec![0u8; 10])]).unwrap();
+        assert!(decoder.decode(&truncated).is_err());
+    }
+
+    #[test]
+    fn unwraps_universal_router_commands() {
+        let wrap =
+            abi::encode(&[Token::Address(Address::from_low_u64_be(1)), Token::Uint(5.into())]);
+        let unknown = vec![0xab];
+        let execute = get_func("execute(bytes,bytes[],uint256)").unwrap();
+        let calldata = execute
+            .encode_input(&[
+                Token::Bytes(vec![0x0b | ALLOW_REVERT_FLAG, 0x3f]),
+                Token::Array(vec![Token::Bytes(wrap), Token::Bytes(unknown)]),
+                Token::Uint(100.into()),
+            ])
+            .unwrap();
+
+        let decoded = CalldataDecoder::new([]).decode(&calldata).unwrap();
+        assert_eq!(
+            decoded.to_string(),
+            "\
+execute(bytes,bytes[],uint256)
+  commands: [
+    0: WRAP_ETH(address,uint256) [allow revert]
+      recipient: 0x0000000000000000000000000000000000000001
+      amountMin: 5
+    1: 0xab
+  ]
+  deadline: 100
+"
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_selectors() {
+        let err = CalldataDecoder::new([]).decode(&transfer_call(1)).unwrap_err();
+        assert_eq!(err.to_string(), "unknown selector 0xa9059cbb");
+        assert!(CalldataDecoder::new([]).decode(&[0xa9]).is_err());
+    }
+}
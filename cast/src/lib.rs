@@ -5,9 +5,15 @@ use chrono::NaiveDateTime;
 use ethers_core::{
     abi::{
         token::{LenientTokenizer, Tokenizer},
-        Abi, AbiParser, Token,
+        Abi, Token,
+    },
+    types::{
+        transaction::{
+            eip2718::TypedTransaction,
+            eip2930::{AccessList, Eip2930TransactionRequest},
+        },
+        Chain, *,
     },
-    types::{transaction::eip2718::TypedTransaction, Chain, *},
     utils::{self, keccak256},
 };
 
@@ -17,7 +23,14 @@ use eyre::{Context, Result};
 use rustc_hex::{FromHexIter, ToHex};
 use std::str::FromStr;
 
-use foundry_utils::{encode_args, get_func, get_func_etherscan, to_table};
+use foundry_utils::{decode_log, encode_args, get_func, get_func_etherscan, to_table};
+
+pub mod decode;
+pub use decode::{CalldataDecoder, DecodedCall, DecodedValue};
+pub mod storage;
+pub use storage::{StorageChange, StorageLayout, StorageValue};
+pub mod tx;
+pub use tx::decode_raw_transaction;
 
 // TODO: CastContract with common contract initializers? Same for CastProviders?
 
@@ -133,7 +146,7 @@ where
     /// let gas = U256::from_str("200000").unwrap();
     /// let value = U256::from_str("1").unwrap();
     /// let nonce = U256::from_str("1").unwrap();
-    /// let data = cast.send(from, to, Some((sig, args)), Some(gas), Some(value), Some(nonce), Chain::Mainnet, None).await?;
+    /// let data = cast.send(from, to, Some((sig, args)), Some(gas), Some(value), Some(nonce), None, Chain::Mainnet, None).await?;
     /// println!("{}", *data);
     /// # Ok(())
     /// # }
@@ -147,16 +160,85 @@ where
         gas: Option<U256>,
         value: Option<U256>,
         nonce: Option<U256>,
+        access_list: Option<AccessList>,
         chain: Chain,
         etherscan_api_key: Option<String>,
     ) -> Result<PendingTransaction<'_, M::Provider>> {
-        let (tx, _) =
+        let (mut tx, _) =
             self.build_tx(from, to, args, gas, value, nonce, chain, etherscan_api_key).await?;
+        if let Some(access_list) = access_list {
+            tx = with_access_list(tx, access_list);
+        }
         let res = self.provider.send_transaction(tx, None).await?;
 
         Ok::<_, eyre::Error>(res)
     }
 
+    /// Builds a transaction with every field populated, the chain id, the nonce, the fees and the
+    /// gas limit from simulating it, so that it can be signed offline, like on an air-gapped
+    /// machine, and published with [`Cast::publish`]
+    ///
+    /// ```no_run
+    /// use cast::Cast;
+    /// use ethers_core::types::{Address, Chain};
+    /// use ethers_providers::{Provider, Http};
+    /// use std::{str::FromStr, convert::TryFrom};
+    ///
+    /// # async fn foo() -> eyre::Result<()> {
+    /// let provider = Provider::<Http>::try_from("http://localhost:8545")?;
+    /// let cast = Cast::new(provider);
+    /// let from = Address::from_str("0xB3C95ff08316fb2F2e3E52Ee82F8e7b605Aa1304")?;
+    /// let to = Address::from_str("0x7a250d5630B4cF539739dF2C5dAcb4c659F2488D")?;
+    /// let args = vec!["0x0000000000000000000000000000000000000001".to_owned(), "1".to_owned()];
+    /// let tx = cast
+    ///     .unsigned_tx(from, to, Some(("transfer(address,uint256)", args)), None, None, None, None, Chain::Mainnet, None)
+    ///     .await?;
+    /// println!("{}", serde_json::to_string_pretty(&tx)?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[allow(clippy::too_many_arguments)]
+    pub async fn unsigned_tx<F: Into<NameOrAddress>, T: Into<NameOrAddress>>(
+        &self,
+        from: F,
+        to: T,
+        args: Option<(&str, Vec<String>)>,
+        gas: Option<U256>,
+        value: Option<U256>,
+        nonce: Option<U256>,
+        access_list: Option<AccessList>,
+        chain: Chain,
+        etherscan_api_key: Option<String>,
+    ) -> Result<TypedTransaction> {
+        let (mut tx, _) =
+            self.build_tx(from, to, args, gas, value, nonce, chain, etherscan_api_key).await?;
+        if let Some(access_list) = access_list {
+            tx = with_access_list(tx, access_list);
+        }
+        let from = *tx.from().expect("from is set");
+
+        tx.set_chain_id(self.provider.get_chainid().await?.as_u64());
+        if tx.nonce().is_none() {
+            let nonce = self
+                .provider
+                .get_transaction_count(from, Some(BlockNumber::Pending.into()))
+                .await?;
+            tx.set_nonce(nonce);
+        }
+        if let TypedTransaction::Eip1559(inner) = &mut tx {
+            let (max_fee, priority_fee) = self.provider.estimate_eip1559_fees(None).await?;
+            inner.max_fee_per_gas.get_or_insert(max_fee);
+            inner.max_priority_fee_per_gas.get_or_insert(priority_fee);
+        } else if tx.gas_price().is_none() {
+            tx.set_gas_price(self.provider.get_gas_price().await?);
+        }
+        if tx.gas().is_none() {
+            let gas = self.provider.estimate_gas(&tx).await?;
+            tx.set_gas(gas);
+        }
+        Ok(tx)
+    }
+
     /// Publishes a raw transaction to the network
     ///
     /// ```no_run
@@ -437,6 +519,29 @@ where
         Ok(self.provider.get_gas_price().await?)
     }
 
+    /// Decodes the storage of the contract at `who` with its storage `layout`, the `keys` are
+    /// looked up in the mappings whose key type they can be parsed as
+    pub async fn storage_layout<T: Into<NameOrAddress> + Send + Sync>(
+        &self,
+        who: T,
+        layout: &StorageLayout,
+        keys: &[String],
+        block: Option<BlockId>,
+    ) -> Result<Vec<StorageValue>> {
+        let who = who.into();
+        let mut slots = std::collections::BTreeMap::new();
+        loop {
+            let (values, missing) = layout.decode(keys, &slots)?;
+            if missing.is_empty() {
+                return Ok(values)
+            }
+            for slot in missing {
+                let value = self.provider.get_storage_at(who.clone(), slot, block).await?;
+                slots.insert(slot, value);
+            }
+        }
+    }
+
     /// ```no_run
     /// use cast::Cast;
     /// use ethers_providers::{Provider, Http};
@@ -522,6 +627,94 @@ where
             if to_json { serde_json::to_string(&transaction)? } else { to_table(transaction) };
         Ok(transaction)
     }
+
+    /// Queries the logs matching the `filter`, the logs matching an event of the `abis` are
+    /// decoded
+    ///
+    /// ```no_run
+    /// use cast::Cast;
+    /// use ethers_core::types::{Address, Filter};
+    /// use ethers_providers::{Provider, Http};
+    /// use std::{str::FromStr, convert::TryFrom};
+    ///
+    /// # async fn foo() -> eyre::Result<()> {
+    /// let provider = Provider::<Http>::try_from("http://localhost:8545")?;
+    /// let cast = Cast::new(provider);
+    /// let addr = Address::from_str("0x6B175474E89094C44Da98b954EedeAC495271d0F")?;
+    /// let filter = Filter::new().address(addr).event("Transfer(address,address,uint256)");
+    /// let logs = cast.logs(&filter, &[], false).await?;
+    /// println!("{}", logs);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn logs(&self, filter: &Filter, abis: &[Abi], to_json: bool) -> Result<String> {
+        let logs = self.provider.get_logs(filter).await?;
+        let logs = logs
+            .iter()
+            .map(|log| SimpleCast::format_log(log, abis, to_json))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(logs.join("\n"))
+    }
+
+    /// Shows the receipt of a transaction, the logs matching an event of the `abis` are decoded
+    /// into `decodedLogs`
+    ///
+    /// ```no_run
+    /// use cast::Cast;
+    /// use ethers_providers::{Provider, Http};
+    /// use std::convert::TryFrom;
+    ///
+    /// # async fn foo() -> eyre::Result<()> {
+    /// let provider = Provider::<Http>::try_from("http://localhost:8545")?;
+    /// let cast = Cast::new(provider);
+    /// let tx_hash = "0xf8d1713ea15a81482958fb7ddf884baee8d3bcc478c5f2f604e008dc788ee4fc";
+    /// let receipt = cast.receipt(tx_hash.to_string(), None, false, &[]).await?;
+    /// println!("{}", receipt);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn receipt(
+        &self,
+        tx_hash: String,
+        field: Option<String>,
+        to_json: bool,
+        abis: &[Abi],
+    ) -> Result<String> {
+        let receipt = self
+            .provider
+            .get_transaction_receipt(H256::from_str(&tx_hash)?)
+            .await?
+            .ok_or_else(|| eyre::eyre!("receipt of transaction {:?} not found", tx_hash))?;
+        SimpleCast::format_receipt(&receipt, field, to_json, abis, None)
+    }
+
+    /// Waits until the transaction is included in a block that has `confirmations` confirmations,
+    /// counting the block itself, and returns its receipt
+    ///
+    /// ```no_run
+    /// use cast::Cast;
+    /// use ethers_providers::{Provider, Http};
+    /// use std::{convert::TryFrom, str::FromStr};
+    ///
+    /// # async fn foo() -> eyre::Result<()> {
+    /// let provider = Provider::<Http>::try_from("http://localhost:8545")?;
+    /// let cast = Cast::new(provider);
+    /// let tx_hash = "0xf8d1713ea15a81482958fb7ddf884baee8d3bcc478c5f2f604e008dc788ee4fc".parse()?;
+    /// let receipt = cast.wait_for_receipt(tx_hash, 3).await?;
+    /// println!("{:?}", receipt.block_number);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn wait_for_receipt(
+        &self,
+        tx_hash: H256,
+        confirmations: usize,
+    ) -> Result<TransactionReceipt> {
+        PendingTransaction::new(tx_hash, self.provider.provider())
+            .confirmations(confirmations.max(1))
+            .await?
+            .ok_or_else(|| eyre::eyre!("transaction {:?} not found", tx_hash))
+    }
 }
 
 pub struct InterfaceSource {
@@ -536,6 +729,144 @@ pub enum InterfacePath {
 
 pub struct SimpleCast;
 impl SimpleCast {
+    /// Formats the log, if it matches an event of the `abis` its decoded form is added as
+    /// `decoded`
+    ///
+    /// ```
+    /// use cast::SimpleCast as Cast;
+    /// use ethers_core::{abi::parse_abi, types::Log};
+    ///
+    /// fn main() -> eyre::Result<()> {
+    ///     let abi = parse_abi(&["event Ping()"])?;
+    ///     let log = Log { topics: vec![abi.event("Ping")?.signature()], ..Default::default() };
+    ///     assert!(Cast::format_log(&log, &[abi], false)?.contains("Ping()"));
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn format_log(log: &Log, abis: &[Abi], to_json: bool) -> Result<String> {
+        let mut value = serde_json::to_value(log)?;
+        if let Some(decoded) = abis.iter().find_map(|abi| decode_log(abi, log)) {
+            value["decoded"] = serde_json::Value::String(decoded);
+        }
+        Ok(if to_json { serde_json::to_string(&value)? } else { to_table(value) })
+    }
+
+    /// Formats the receipt as a table or as JSON, the logs matching an event of the `abis` are
+    /// decoded into `decodedLogs` and the `revert_reason` of a failed transaction is added as
+    /// `revertReason`
+    pub fn format_receipt(
+        receipt: &TransactionReceipt,
+        field: Option<String>,
+        to_json: bool,
+        abis: &[Abi],
+        revert_reason: Option<String>,
+    ) -> Result<String> {
+        let mut receipt_value = serde_json::to_value(receipt)?;
+        if !abis.is_empty() {
+            let decoded = receipt
+                .logs
+                .iter()
+                .map(|log| {
+                    abis.iter()
+                        .find_map(|abi| decode_log(abi, log))
+                        .map_or(serde_json::Value::Null, serde_json::Value::String)
+                })
+                .collect();
+            receipt_value["decodedLogs"] = serde_json::Value::Array(decoded);
+        }
+        if let Some(reason) = revert_reason {
+            receipt_value["revertReason"] = serde_json::Value::String(reason);
+        }
+
+        let receipt_value = if let Some(ref field) = field {
+            receipt_value
+                .get(field)
+                .cloned()
+                .ok_or_else(|| eyre::eyre!("field {} not found", field))?
+        } else {
+            receipt_value
+        };
+
+        let receipt_value =
+            if to_json { serde_json::to_string(&receipt_value)? } else { to_table(receipt_value) };
+        Ok(receipt_value)
+    }
+
+    /// Decodes a signed raw transaction and formats it like [`Cast::transaction`], its calldata is
+    /// decoded if the function signature `sig` is given
+    pub fn format_raw_transaction(raw: &str, sig: Option<&str>, to_json: bool) -> Result<String> {
+        let raw = hex::decode(raw.trim().strip_prefix("0x").unwrap_or_else(|| raw.trim()))?;
+        let tx = decode_raw_transaction(&raw)?;
+        let mut tx_value = serde_json::to_value(&tx)?;
+        if let Some(sig) = sig {
+            let decoded = Self::abi_decode(sig, &hex::encode(&tx.input), true)?;
+            tx_value["decodedInput"] = foundry_utils::format_tokens(&decoded).collect();
+        }
+        Ok(if to_json { serde_json::to_string(&tx_value)? } else { to_table(tx_value) })
+    }
+
+    /// Encodes a JSON value of nested arrays of `0x` prefixed hex strings as RLP
+    ///
+    /// ```
+    /// use cast::SimpleCast as Cast;
+    ///
+    /// assert_eq!(Cast::to_rlp(r#"["0xaa", ["0x"]]"#).unwrap(), "0xc481aac180");
+    /// ```
+    pub fn to_rlp(value: &str) -> Result<String> {
+        fn append(stream: &mut utils::rlp::RlpStream, value: &serde_json::Value) -> Result<()> {
+            match value {
+                serde_json::Value::Array(items) => {
+                    stream.begin_list(items.len());
+                    for item in items {
+                        append(stream, item)?;
+                    }
+                }
+                serde_json::Value::String(hex) => {
+                    let bytes = hex::decode(hex.strip_prefix("0x").unwrap_or(hex))
+                        .wrap_err_with(|| format!("invalid hex string `{}`", hex))?;
+                    stream.append(&bytes);
+                }
+                value => eyre::bail!("expected an array or a hex string, found `{}`", value),
+            }
+            Ok(())
+        }
+
+        let value: serde_json::Value =
+            serde_json::from_str(value).wrap_err("expected a JSON array or a JSON string")?;
+        let mut stream = utils::rlp::RlpStream::new();
+        append(&mut stream, &value)?;
+        Ok(format!("0x{}", hex::encode(stream.out())))
+    }
+
+    /// Decodes RLP into a JSON value of nested arrays of `0x` prefixed hex strings
+    ///
+    /// ```
+    /// use cast::SimpleCast as Cast;
+    ///
+    /// assert_eq!(Cast::from_rlp("0xc481aac180").unwrap(), r#"["0xaa",["0x"]]"#);
+    /// ```
+    pub fn from_rlp(rlp: &str) -> Result<String> {
+        fn decode(rlp: &utils::rlp::Rlp<'_>) -> Result<serde_json::Value> {
+            Ok(if rlp.is_list() {
+                serde_json::Value::Array(
+                    rlp.iter().map(|item| decode(&item)).collect::<Result<_>>()?,
+                )
+            } else {
+                serde_json::Value::String(format!("0x{}", hex::encode(rlp.data()?)))
+            })
+        }
+
+        let bytes = hex::decode(rlp.trim().strip_prefix("0x").unwrap_or_else(|| rlp.trim()))?;
+        let rlp = utils::rlp::Rlp::new(&bytes);
+        let value = decode(&rlp)?;
+        let info = rlp.payload_info()?;
+        if info.header_len + info.value_len != bytes.len() {
+            eyre::bail!("trailing bytes after the RLP item")
+        }
+        Ok(serde_json::to_string(&value)?)
+    }
+
     /// Converts UTF-8 text input to hex
     ///
     /// ```
@@ -548,9 +879,9 @@ impl SimpleCast {
         let s: String = s.as_bytes().to_hex();
         format!("0x{}", s)
     }
-    /// Generates an interface in solidity from either a local file ABI or a verified contract on
-    /// Etherscan. It returns a vector of [`InterfaceSource`] structs that contain the source of the
-    /// interface and their name.
+    /// Generates an interface in solidity from either a local file ABI, or artifact, or a verified
+    /// contract on Etherscan. It returns a vector of [`InterfaceSource`] structs that contain the
+    /// source of the interface and their name.
     /// ```no_run
     /// use cast::SimpleCast as Cast;
     /// use cast::InterfacePath;
@@ -564,47 +895,52 @@ impl SimpleCast {
     pub async fn generate_interface(
         address_or_path: InterfacePath,
     ) -> Result<Vec<InterfaceSource>> {
-        let (contract_abis, contract_names): (Vec<Abi>, Vec<String>) = match address_or_path {
-            InterfacePath::Local(path) => {
-                let file = std::fs::read_to_string(&path).wrap_err("unable to read abi file")?;
-                (
-                    vec![serde_json::from_str(&file)
-                        .wrap_err("unable to parse json ABI from file")?],
-                    vec!["Interface".to_owned()],
-                )
-            }
-            InterfacePath::Etherscan { address, chain, api_key } => {
-                let client = Client::new(chain, api_key)?;
-
-                // get the source
-                let contract_source = match client.contract_source_code(address).await {
-                    Ok(src) => src,
-                    Err(err) => {
-                        let msg = err.to_string();
-                        if msg.contains("Invalid API Key") {
-                            eyre::bail!("Invalid Etherscan API key. Did you set it correctly? You may be using an API key for another Etherscan API chain (e.g. Ethereum API key for Polygonscan).")
-                        } else {
-                            eyre::bail!(err)
+        let (contract_abis, contract_names): (Vec<serde_json::Value>, Vec<String>) =
+            match address_or_path {
+                InterfacePath::Local(path) => {
+                    let file =
+                        std::fs::read_to_string(&path).wrap_err("unable to read abi file")?;
+                    (
+                        vec![serde_json::from_str(&file)
+                            .wrap_err("unable to parse json ABI from file")?],
+                        vec!["Interface".to_owned()],
+                    )
+                }
+                InterfacePath::Etherscan { address, chain, api_key } => {
+                    let client = Client::new(chain, api_key)?;
+
+                    // get the source
+                    let contract_source = match client.contract_source_code(address).await {
+                        Ok(src) => src,
+                        Err(err) => {
+                            let msg = err.to_string();
+                            if msg.contains("Invalid API Key") {
+                                eyre::bail!("Invalid Etherscan API key. Did you set it correctly? You may be using an API key for another Etherscan API chain (e.g. Ethereum API key for Polygonscan).")
+                            } else {
+                                eyre::bail!(err)
+                            }
                         }
+                    };
+
+                    if contract_source
+                        .items
+                        .iter()
+                        .any(|item| item.abi == "Contract source code not verified")
+                    {
+                        eyre::bail!("Contract source code at {:?} on {} not verified. Maybe you have selected the wrong chain?", address, chain)
                     }
-                };
 
-                if contract_source
-                    .items
-                    .iter()
-                    .any(|item| item.abi == "Contract source code not verified")
-                {
-                    eyre::bail!("Contract source code at {:?} on {} not verified. Maybe you have selected the wrong chain?", address, chain)
+                    contract_source
+                        .items
+                        .iter()
+                        .map(|item| {
+                            Ok((serde_json::from_str(&item.abi)?, item.contract_name.clone()))
+                        })
+                        .collect::<Result<Vec<_>>>()?
+                        .into_iter()
+                        .unzip()
                 }
-
-                let contract_source_names = contract_source
-                    .items
-                    .iter()
-                    .map(|item| item.contract_name.clone())
-                    .collect::<Vec<String>>();
-                (contract_source.abis()?, contract_source_names)
-            }
-        };
+            };
         contract_abis
             .iter()
             .zip(&contract_names)
@@ -644,11 +980,12 @@ impl SimpleCast {
     /// fn main() -> eyre::Result<()> {
     ///     assert_eq!(U256::from_dec_str("424242")?, Cast::to_dec("0x67932")?);
     ///     assert_eq!(U256::from_dec_str("1234")?, Cast::to_dec("0x4d2")?);
+    ///     assert_eq!(U256::from_dec_str("1234")?, Cast::to_dec("4d2")?);
     ///
     ///     Ok(())
     /// }
     pub fn to_dec(hex: &str) -> Result<U256> {
-        Ok(U256::from_str(hex)?)
+        Ok(U256::from_str(strip_0x(hex.trim()))?)
     }
 
     /// Returns maximum I256 value
@@ -758,6 +1095,39 @@ impl SimpleCast {
         foundry_utils::abi_decode(sig, calldata, input)
     }
 
+    /// Returns the selector of the function signature
+    ///
+    /// ```
+    /// use cast::SimpleCast as Cast;
+    ///
+    /// assert_eq!(Cast::get_selector("transfer(address,uint256)").unwrap(), "0xa9059cbb");
+    /// assert_eq!(
+    ///     Cast::get_selector("function transfer(address to, uint256 amount) returns (bool)").unwrap(),
+    ///     "0xa9059cbb"
+    /// );
+    /// ```
+    pub fn get_selector(sig: &str) -> Result<String> {
+        Ok(format!("0x{}", hex::encode(foundry_utils::parse_signature(sig)?.short_signature())))
+    }
+
+    /// Returns the topic of the event signature
+    ///
+    /// ```
+    /// use cast::SimpleCast as Cast;
+    ///
+    /// assert_eq!(
+    ///     Cast::get_event_topic("Transfer(address indexed from, address indexed to, uint256 value)").unwrap(),
+    ///     "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef"
+    /// );
+    /// ```
+    pub fn get_event_topic(sig: &str) -> Result<String> {
+        let sig = sig.trim();
+        let sig =
+            if sig.starts_with("event ") { sig.to_string() } else { format!("event {}", sig) };
+        let event = ethers_core::abi::AbiParser::default().parse_event(&sig)?;
+        Ok(format!("{:?}", event.signature()))
+    }
+
     /// Performs ABI encoding based off of the function signature. Does not include
     /// the function selector in the result.
     ///
@@ -769,11 +1139,15 @@ impl SimpleCast {
     ///         "0x0000000000000000000000000000000000000000000000000000000000000001",
     ///         Cast::abi_encode("f(uint a)", &["1"]).unwrap().as_str()
     ///     );
+    ///     assert_eq!(
+    ///         "0x00000000000000000000000000000000000000000000000000000000000000010000000000000000000000000000000000000000000000000000000000000001",
+    ///         Cast::abi_encode("f((uint256,bool))", &["(1,true)"]).unwrap().as_str()
+    ///     );
     /// #    Ok(())
     /// # }
     /// ```
     pub fn abi_encode(sig: &str, args: &[impl AsRef<str>]) -> Result<String> {
-        let func = AbiParser::default().parse_function(sig.as_ref())?;
+        let func = get_func(sig.as_ref())?;
         let calldata = encode_args(&func, args)?.to_hex::<String>();
         let encoded = &calldata[8..];
         Ok(format!("0x{}", encoded))
@@ -848,23 +1222,28 @@ impl SimpleCast {
         })
     }
 
-    /// Converts an eth amount into wei
+    /// Converts an eth amount into wei, the amount is parsed as a decimal string so that no
+    /// precision is lost
     ///
     /// ```
     /// use cast::SimpleCast as Cast;
     ///
     /// fn main() -> eyre::Result<()> {
-    ///     assert_eq!(Cast::to_wei(1.into(), "".to_string())?, "1000000000000000000");
-    ///     assert_eq!(Cast::to_wei(100.into(), "gwei".to_string())?, "100000000000");
-    ///     assert_eq!(Cast::to_wei(100.into(), "eth".to_string())?, "100000000000000000000");
-    ///     assert_eq!(Cast::to_wei(1000.into(), "ether".to_string())?, "1000000000000000000000");
+    ///     assert_eq!(Cast::to_wei("1", "".to_string())?, "1000000000000000000");
+    ///     assert_eq!(Cast::to_wei("100", "gwei".to_string())?, "100000000000");
+    ///     assert_eq!(Cast::to_wei("100", "eth".to_string())?, "100000000000000000000");
+    ///     assert_eq!(Cast::to_wei("1000", "ether".to_string())?, "1000000000000000000000");
+    ///     assert_eq!(Cast::to_wei("0.1", "ether".to_string())?, "100000000000000000");
+    ///     assert_eq!(Cast::to_wei("123456789.123456789123456789", "ether".to_string())?, "123456789123456789123456789");
+    ///     assert_eq!(Cast::to_wei("42", "wei".to_string())?, "42");
     ///
     ///     Ok(())
     /// }
     /// ```
-    pub fn to_wei(value: f64, unit: String) -> Result<String> {
-        let value = value.to_string();
+    pub fn to_wei(value: &str, unit: String) -> Result<String> {
+        let value = value.trim();
         Ok(match &unit[..] {
+            "wei" => ethers_core::utils::parse_units(value, 0),
             "gwei" => ethers_core::utils::parse_units(value, 9),
             "eth" | "ether" => ethers_core::utils::parse_units(value, 18),
             _ => ethers_core::utils::parse_units(value, 18),
@@ -889,6 +1268,7 @@ impl SimpleCast {
     /// ```
     pub fn from_wei(value: U256, unit: String) -> Result<String> {
         Ok(match &unit[..] {
+            "wei" => return Ok(value.to_string()),
             "gwei" => ethers_core::utils::format_units(value, 9),
             "eth" | "ether" => ethers_core::utils::format_units(value, 18),
             _ => ethers_core::utils::format_units(value, 18),
@@ -942,7 +1322,8 @@ impl SimpleCast {
         Ok(format!("{:?}", H256::from_str(&padded)?))
     }
 
-    /// Keccak-256 hashes arbitrary data
+    /// Keccak-256 hashes arbitrary data, `0x` prefixed hex data is hashed as bytes and anything
+    /// else as utf-8 text
     ///
     /// ```
     /// use cast::SimpleCast as Cast;
@@ -950,12 +1331,18 @@ impl SimpleCast {
     /// fn main() -> eyre::Result<()> {
     ///     assert_eq!(Cast::keccak("foo")?, "0x41b1a0649752af1b28b3dc29a1556eee781e4a4c3a1f7f53f90fa834de098c4d");
     ///     assert_eq!(Cast::keccak("123abc")?, "0xb1f1c74a1ba56f07a892ea1110a39349d40f66ca01d245e704621033cb7046a4");
+    ///     assert_eq!(Cast::keccak("0x")?, "0xc5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470");
+    ///     assert_eq!(Cast::keccak("0x666f6f")?, Cast::keccak("foo")?);
     ///
     ///     Ok(())
     /// }
     /// ```
     pub fn keccak(data: &str) -> Result<String> {
-        let hash: String = keccak256(data.as_bytes()).to_hex();
+        let hash: String = match data.strip_prefix("0x").map(hex::decode) {
+            Some(Ok(bytes)) => keccak256(bytes),
+            _ => keccak256(data.as_bytes()),
+        }
+        .to_hex();
         Ok(format!("0x{}", hash))
     }
 
@@ -1010,7 +1397,7 @@ impl SimpleCast {
     /// # }
     /// ```
     pub fn calldata(sig: impl AsRef<str>, args: &[impl AsRef<str>]) -> Result<String> {
-        let func = AbiParser::default().parse_function(sig.as_ref())?;
+        let func = get_func(sig.as_ref())?;
         let calldata = encode_args(&func, args)?;
         Ok(format!("0x{}", calldata.to_hex::<String>()))
     }
@@ -1052,9 +1439,25 @@ fn strip_0x(s: &str) -> &str {
     s.strip_prefix("0x").unwrap_or(s)
 }
 
+/// Sets the access list of the `tx`, legacy transactions are turned into EIP-2930 transactions
+fn with_access_list(tx: TypedTransaction, access_list: AccessList) -> TypedTransaction {
+    match tx {
+        TypedTransaction::Legacy(tx) => Eip2930TransactionRequest::new(tx, access_list).into(),
+        TypedTransaction::Eip2930(mut tx) => {
+            tx.access_list = access_list;
+            tx.into()
+        }
+        TypedTransaction::Eip1559(tx) => tx.access_list(access_list).into(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::SimpleCast as Cast;
+    use super::{with_access_list, SimpleCast as Cast};
+    use ethers_core::types::{
+        transaction::{eip2718::TypedTransaction, eip2930::AccessListItem},
+        Address, TransactionReceipt, TransactionRequest,
+    };
 
     #[test]
     fn calldata_uint() {
@@ -1071,4 +1474,31 @@ mod tests {
             Cast::calldata("bar(bool)", &["false"]).unwrap().as_str()
         );
     }
+
+    #[test]
+    fn receipt_with_revert_reason() {
+        let receipt = TransactionReceipt { status: Some(0u64.into()), ..Default::default() };
+        let formatted = Cast::format_receipt(
+            &receipt,
+            Some("revertReason".to_string()),
+            true,
+            &[],
+            Some("Ownable: caller is not the owner".to_string()),
+        )
+        .unwrap();
+        assert_eq!(formatted, r#""Ownable: caller is not the owner""#);
+
+        let formatted = Cast::format_receipt(&receipt, None, true, &[], None).unwrap();
+        assert!(!formatted.contains("revertReason"));
+    }
+
+    #[test]
+    fn legacy_tx_with_access_list() {
+        let access_list = vec![AccessListItem { address: Address::zero(), storage_keys: vec![] }];
+        let tx = with_access_list(TransactionRequest::new().into(), access_list.clone().into());
+        match tx {
+            TypedTransaction::Eip2930(tx) => assert_eq!(tx.access_list.0, access_list),
+            tx => panic!("expected an EIP-2930 transaction, got {:?}", tx),
+        }
+    }
 }
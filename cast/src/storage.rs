@@ -0,0 +1,528 @@
+//! Decoding of a contract's storage with the solc `storageLayout` of the contract
+//!
+//! The layout is walked once per round of storage reads: a walk decodes the variables with the
+//! slots read so far and collects the slots it's missing, like the elements of a dynamic array
+//! whose length wasn't read yet, which are read before the next walk.
+use ethers_core::{
+    abi::{
+        self,
+        param_type::Reader,
+        token::{LenientTokenizer, Tokenizer},
+        ParamType, Token,
+    },
+    types::{Address, H256, I256, U256},
+    utils::keccak256,
+};
+use eyre::{Context, Result};
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+/// The maximum number of elements of an array that are decoded
+pub const MAX_ARRAY_ELEMENTS: usize = 64;
+
+/// The solc `storageLayout` of a contract
+#[derive(Debug, Clone, Deserialize)]
+pub struct StorageLayout {
+    storage: Vec<StorageVariable>,
+    #[serde(default)]
+    types: BTreeMap<String, StorageType>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct StorageVariable {
+    label: String,
+    slot: String,
+    offset: usize,
+    #[serde(rename = "type")]
+    ty: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StorageType {
+    encoding: String,
+    label: String,
+    number_of_bytes: String,
+    key: Option<String>,
+    value: Option<String>,
+    base: Option<String>,
+    members: Option<Vec<StorageVariable>>,
+}
+
+/// The decoded value of a variable, or of a member or an element of a variable
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StorageValue {
+    /// The path of the value, like `balances[0x…].amount`
+    pub label: String,
+    /// The solidity type, like `uint256`
+    pub ty: String,
+    pub slot: H256,
+    pub offset: usize,
+    pub value: String,
+}
+
+impl fmt::Display for StorageValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:<40} {:<24} {}", self.label, self.ty, self.value)
+    }
+}
+
+/// A value that differs between two blocks, `None` if it only exists at one of them, like an
+/// element of a dynamic array that grew
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StorageChange {
+    pub label: String,
+    pub ty: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+impl fmt::Display for StorageChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:<40} {:<24} {} -> {}",
+            self.label,
+            self.ty,
+            self.old.as_deref().unwrap_or("<none>"),
+            self.new.as_deref().unwrap_or("<none>")
+        )
+    }
+}
+
+impl StorageLayout {
+    /// Reads the layout from a forge artifact built with the `storageLayout` extra output, or
+    /// from the `storageLayout` itself
+    pub fn from_json(json: &str) -> Result<Self> {
+        let mut value: serde_json::Value =
+            serde_json::from_str(json).wrap_err("invalid storage layout JSON")?;
+        if let Some(layout) = value.get_mut("storageLayout").map(serde_json::Value::take) {
+            value = layout;
+        }
+        if value.get("storage").is_none() {
+            eyre::bail!(
+                "no storage layout found, build the contract with `extra_output = [\"storageLayout\"]`"
+            )
+        }
+        Ok(serde_json::from_value(value)?)
+    }
+
+    /// Decodes all variables with the `slots` read so far and returns the values and the slots
+    /// that still need to be read
+    ///
+    /// The `keys` are looked up in every mapping whose key type they can be parsed as.
+    pub fn decode(
+        &self,
+        keys: &[String],
+        slots: &BTreeMap<H256, H256>,
+    ) -> Result<(Vec<StorageValue>, BTreeSet<H256>)> {
+        let mut walker =
+            Walker { layout: self, keys, slots, values: Vec::new(), missing: BTreeSet::new() };
+        for var in &self.storage {
+            let slot = U256::from_dec_str(&var.slot)
+                .wrap_err_with(|| format!("invalid slot of `{}`", var.label))?;
+            walker.walk(&var.label, &var.ty, slot, var.offset)?;
+        }
+        Ok((walker.values, walker.missing))
+    }
+}
+
+/// Returns the values that differ between the `old` and the `new` values, in the order of the
+/// `new` values followed by the values that only exist in the `old` values
+pub fn diff(old: &[StorageValue], new: &[StorageValue]) -> Vec<StorageChange> {
+    let old_values = old.iter().map(|value| (&value.label, value)).collect::<BTreeMap<_, _>>();
+    let new_labels = new.iter().map(|value| &value.label).collect::<BTreeSet<_>>();
+    let mut changes = new
+        .iter()
+        .filter_map(|new| {
+            let old = old_values.get(&new.label).map(|old| old.value.clone());
+            (old.as_ref() != Some(&new.value)).then(|| StorageChange {
+                label: new.label.clone(),
+                ty: new.ty.clone(),
+                old,
+                new: Some(new.value.clone()),
+            })
+        })
+        .collect::<Vec<_>>();
+    changes.extend(old.iter().filter(|old| !new_labels.contains(&old.label)).map(|old| {
+        StorageChange {
+            label: old.label.clone(),
+            ty: old.ty.clone(),
+            old: Some(old.value.clone()),
+            new: None,
+        }
+    }));
+    changes
+}
+
+struct Walker<'a> {
+    layout: &'a StorageLayout,
+    keys: &'a [String],
+    slots: &'a BTreeMap<H256, H256>,
+    values: Vec<StorageValue>,
+    missing: BTreeSet<H256>,
+}
+
+impl<'a> Walker<'a> {
+    fn ty(&self, id: &str) -> Result<&'a StorageType> {
+        self.layout.types.get(id).ok_or_else(|| eyre::eyre!("unknown storage type `{}`", id))
+    }
+
+    /// Returns the word at the slot, or zero if it wasn't read yet
+    fn word(&mut self, slot: U256) -> [u8; 32] {
+        let slot = H256::from(u256_bytes(slot));
+        match self.slots.get(&slot) {
+            Some(word) => word.0,
+            None => {
+                self.missing.insert(slot);
+                [0; 32]
+            }
+        }
+    }
+
+    fn walk(&mut self, label: &str, id: &str, slot: U256, offset: usize) -> Result<()> {
+        let ty = self.ty(id)?;
+        match ty.encoding.as_str() {
+            "mapping" => {
+                let key_ty = self.ty(ty.key.as_deref().unwrap_or_default())?;
+                let value_id = ty.value.as_deref().unwrap_or_default();
+                let keys = self.keys;
+                for key in keys {
+                    if let Some(key_bytes) = mapping_key(&key_ty.label, key) {
+                        let mut preimage = key_bytes;
+                        preimage.extend_from_slice(&u256_bytes(slot));
+                        let value_slot = U256::from_big_endian(&keccak256(preimage));
+                        self.walk(&format!("{}[{}]", label, key), value_id, value_slot, 0)?;
+                    }
+                }
+            }
+            "dynamic_array" => {
+                let len = U256::from_big_endian(&self.word(slot));
+                let base = ty.base.as_deref().unwrap_or_default();
+                let start = U256::from_big_endian(&keccak256(u256_bytes(slot)));
+                self.walk_elements(label, base, start, len)?;
+            }
+            "bytes" => {
+                let word = self.word(slot);
+                let value = if word[31] & 1 == 0 {
+                    // short values are stored in the slot with twice their length
+                    word[..(word[31] as usize / 2).min(31)].to_vec()
+                } else {
+                    let len = (U256::from_big_endian(&word) - 1) / 2;
+                    let len =
+                        if len > U256::from(u32::MAX) { u32::MAX as usize } else { len.as_usize() };
+                    let start = U256::from_big_endian(&keccak256(u256_bytes(slot)));
+                    let mut value = Vec::with_capacity(len);
+                    for idx in 0..(len + 31) / 32 {
+                        value.extend_from_slice(&self.word(start + idx));
+                    }
+                    value.truncate(len);
+                    value
+                };
+                let value = if ty.label == "string" {
+                    format!("{:?}", String::from_utf8_lossy(&value))
+                } else {
+                    format!("0x{}", hex::encode(value))
+                };
+                self.push(label, ty, slot, offset, value);
+            }
+            _ => {
+                if let Some(members) = &ty.members {
+                    for member in members {
+                        let member_slot = U256::from_dec_str(&member.slot)
+                            .wrap_err_with(|| format!("invalid slot of `{}`", member.label))?;
+                        self.walk(
+                            &format!("{}.{}", label, member.label),
+                            &member.ty,
+                            slot + member_slot,
+                            member.offset,
+                        )?;
+                    }
+                } else if let Some(base) = &ty.base {
+                    let len = ty
+                        .label
+                        .rsplit('[')
+                        .next()
+                        .and_then(|len| len.trim_end_matches(']').parse::<u64>().ok())
+                        .ok_or_else(|| eyre::eyre!("invalid array type `{}`", ty.label))?;
+                    self.walk_elements(label, base, slot, len.into())?;
+                } else {
+                    let size = ty.number_of_bytes.parse::<usize>().unwrap_or(32).min(32);
+                    let word = self.word(slot);
+                    let end = 32usize.saturating_sub(offset);
+                    let bytes = &word[end.saturating_sub(size)..end];
+                    let value = format_value(&ty.label, bytes);
+                    self.push(label, ty, slot, offset, value);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Walks the elements of an array that start at the `start` slot, elements of at most 16
+    /// bytes are packed into a slot
+    fn walk_elements(&mut self, label: &str, base: &str, start: U256, len: U256) -> Result<()> {
+        let size = self.ty(base)?.number_of_bytes.parse::<usize>().unwrap_or(32);
+        let shown =
+            if len > U256::from(MAX_ARRAY_ELEMENTS) { MAX_ARRAY_ELEMENTS } else { len.as_usize() };
+        for idx in 0..shown {
+            let (slot, offset) = if size <= 16 {
+                let per_slot = 32 / size;
+                (start + idx / per_slot, (idx % per_slot) * size)
+            } else {
+                (start + idx * ((size + 31) / 32), 0)
+            };
+            self.walk(&format!("{}[{}]", label, idx), base, slot, offset)?;
+        }
+        if len > U256::from(shown) {
+            self.values.push(StorageValue {
+                label: format!("{}[{}..]", label, shown),
+                ty: String::new(),
+                slot: H256::from(u256_bytes(start)),
+                offset: 0,
+                value: format!("{} more elements", len - shown),
+            });
+        }
+        Ok(())
+    }
+
+    fn push(&mut self, label: &str, ty: &StorageType, slot: U256, offset: usize, value: String) {
+        self.values.push(StorageValue {
+            label: label.to_string(),
+            ty: ty.label.clone(),
+            slot: H256::from(u256_bytes(slot)),
+            offset,
+            value,
+        });
+    }
+}
+
+fn u256_bytes(value: U256) -> [u8; 32] {
+    let mut bytes = [0; 32];
+    value.to_big_endian(&mut bytes);
+    bytes
+}
+
+/// Returns the ABI type of a value type, contracts are addresses and enums are `uint8`
+fn param_type(label: &str) -> Option<ParamType> {
+    if label.starts_with("contract ") || label.starts_with("address") {
+        Some(ParamType::Address)
+    } else if label.starts_with("enum ") {
+        Some(ParamType::Uint(8))
+    } else {
+        Reader::read(label).ok()
+    }
+}
+
+/// Encodes the key like solidity does to derive the slot of a mapping's value, `None` if the key
+/// can't be parsed as the key type
+fn mapping_key(ty: &str, key: &str) -> Option<Vec<u8>> {
+    match ty {
+        "string" => return Some(key.as_bytes().to_vec()),
+        "bytes" => return hex::decode(key.strip_prefix("0x").unwrap_or(key)).ok(),
+        _ => {}
+    }
+    let token = LenientTokenizer::tokenize(&param_type(ty)?, key).ok()?;
+    // signed keys are sign extended, which `tokenize` already did
+    Some(abi::encode(&[token]))
+}
+
+/// Formats the bytes of a value type
+fn format_value(ty: &str, bytes: &[u8]) -> String {
+    let token = match param_type(ty) {
+        Some(ParamType::Bool) => Token::Bool(bytes.iter().any(|byte| *byte != 0)),
+        Some(ParamType::Address) => Token::Address(Address::from_slice(&bytes[bytes.len() - 20..])),
+        Some(ParamType::Uint(_)) => Token::Uint(U256::from_big_endian(bytes)),
+        Some(ParamType::Int(_)) => {
+            let fill = if bytes.first().map_or(false, |byte| byte & 0x80 != 0) { 0xff } else { 0 };
+            let mut word = [fill; 32];
+            word[32 - bytes.len()..].copy_from_slice(bytes);
+            return I256::from_raw(U256::from_big_endian(&word)).to_string()
+        }
+        _ => return format!("0x{}", hex::encode(bytes)),
+    };
+    foundry_utils::format_token(&token)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The layout of
+    ///
+    /// ```solidity
+    /// contract Vault {
+    ///     struct Position { uint128 amount; bool open; }
+    ///     address owner;
+    ///     bool paused;
+    ///     int8 delta;
+    ///     string name;
+    ///     uint64[] checkpoints;
+    ///     mapping(address => Position) positions;
+    /// }
+    /// ```
+    const LAYOUT: &str = r#"{
+        "storageLayout": {
+            "storage": [
+                { "label": "owner", "offset": 0, "slot": "0", "type": "t_address", "contract": "Vault" },
+                { "label": "paused", "offset": 20, "slot": "0", "type": "t_bool", "contract": "Vault" },
+                { "label": "delta", "offset": 21, "slot": "0", "type": "t_int8", "contract": "Vault" },
+                { "label": "name", "offset": 0, "slot": "1", "type": "t_string_storage", "contract": "Vault" },
+                { "label": "checkpoints", "offset": 0, "slot": "2", "type": "t_array(t_uint64)dyn_storage", "contract": "Vault" },
+                { "label": "positions", "offset": 0, "slot": "3", "type": "t_mapping(t_address,t_struct(Position)10_storage)", "contract": "Vault" }
+            ],
+            "types": {
+                "t_address": { "encoding": "inplace", "label": "address", "numberOfBytes": "20" },
+                "t_bool": { "encoding": "inplace", "label": "bool", "numberOfBytes": "1" },
+                "t_int8": { "encoding": "inplace", "label": "int8", "numberOfBytes": "1" },
+                "t_uint64": { "encoding": "inplace", "label": "uint64", "numberOfBytes": "8" },
+                "t_uint128": { "encoding": "inplace", "label": "uint128", "numberOfBytes": "16" },
+                "t_string_storage": { "encoding": "bytes", "label": "string", "numberOfBytes": "32" },
+                "t_array(t_uint64)dyn_storage": { "base": "t_uint64", "encoding": "dynamic_array", "label": "uint64[]", "numberOfBytes": "32" },
+                "t_mapping(t_address,t_struct(Position)10_storage)": { "encoding": "mapping", "key": "t_address", "label": "mapping(address => struct Vault.Position)", "numberOfBytes": "32", "value": "t_struct(Position)10_storage" },
+                "t_struct(Position)10_storage": {
+                    "encoding": "inplace", "label": "struct Vault.Position", "numberOfBytes": "32",
+                    "members": [
+                        { "label": "amount", "offset": 0, "slot": "0", "type": "t_uint128", "contract": "Vault" },
+                        { "label": "open", "offset": 16, "slot": "0", "type": "t_bool", "contract": "Vault" }
+                    ]
+                }
+            }
+        }
+    }"#;
+
+    fn slot(n: u64) -> H256 {
+        H256::from_low_u64_be(n)
+    }
+
+    fn keccak_slot(preimage: &[u8], idx: u64) -> H256 {
+        H256::from(u256_bytes(U256::from_big_endian(&keccak256(preimage)) + idx))
+    }
+
+    /// Decodes the layout like `Cast::storage_layout` does, reading the missing slots from the
+    /// `storage` until nothing is missing
+    fn decode(storage: &BTreeMap<H256, H256>, keys: &[String]) -> Vec<StorageValue> {
+        let layout = StorageLayout::from_json(LAYOUT).unwrap();
+        let mut slots = BTreeMap::new();
+        loop {
+            let (values, missing) = layout.decode(keys, &slots).unwrap();
+            if missing.is_empty() {
+                return values
+            }
+            for slot in missing {
+                slots.insert(slot, storage.get(&slot).copied().unwrap_or_default());
+            }
+        }
+    }
+
+    fn storage() -> BTreeMap<H256, H256> {
+        let owner = Address::from_low_u64_be(0xbeef);
+        let mut storage = BTreeMap::new();
+
+        // owner, paused = true and delta = -2 are packed into slot 0
+        let mut word = [0u8; 32];
+        word[12..].copy_from_slice(owner.as_bytes());
+        word[11] = 1;
+        word[10] = 0xfe;
+        storage.insert(slot(0), H256(word));
+
+        // a short string
+        let mut word = [0u8; 32];
+        word[..5].copy_from_slice(b"vault");
+        word[31] = 10;
+        storage.insert(slot(1), H256(word));
+
+        // three packed uint64 checkpoints
+        storage.insert(slot(2), H256::from_low_u64_be(3));
+        let mut word = [0u8; 32];
+        word[24..].copy_from_slice(&10u64.to_be_bytes());
+        word[16..24].copy_from_slice(&20u64.to_be_bytes());
+        word[8..16].copy_from_slice(&30u64.to_be_bytes());
+        storage.insert(keccak_slot(slot(2).as_bytes(), 0), H256(word));
+
+        // the position of the owner
+        let mut preimage = H256::from(owner).as_bytes().to_vec();
+        preimage.extend_from_slice(slot(3).as_bytes());
+        let mut word = [0u8; 32];
+        word[16..].copy_from_slice(&U256::from(500).low_u128().to_be_bytes());
+        word[15] = 1;
+        storage.insert(H256(keccak256(preimage)), H256(word));
+        storage
+    }
+
+    #[test]
+    fn decodes_storage_layout() {
+        let owner = format!("{:?}", Address::from_low_u64_be(0xbeef));
+        let values = decode(&storage(), &[owner.clone(), "not a key".to_string()]);
+        let values = values
+            .iter()
+            .map(|value| (value.label.as_str(), value.value.as_str()))
+            .collect::<Vec<_>>();
+        let position = format!("positions[{}]", owner);
+        assert_eq!(
+            values,
+            vec![
+                ("owner", owner.as_str()),
+                ("paused", "true"),
+                ("delta", "-2"),
+                ("name", "\"vault\""),
+                ("checkpoints[0]", "10"),
+                ("checkpoints[1]", "20"),
+                ("checkpoints[2]", "30"),
+                (format!("{}.amount", position).as_str(), "500"),
+                (format!("{}.open", position).as_str(), "true"),
+            ]
+        );
+    }
+
+    #[test]
+    fn decodes_long_strings() {
+        let mut storage = storage();
+        let name = "a name that doesn't fit into a single storage slot";
+        storage.insert(slot(1), H256::from_low_u64_be(name.len() as u64 * 2 + 1));
+        for (idx, chunk) in name.as_bytes().chunks(32).enumerate() {
+            let mut word = [0u8; 32];
+            word[..chunk.len()].copy_from_slice(chunk);
+            storage.insert(keccak_slot(slot(1).as_bytes(), idx as u64), H256(word));
+        }
+        let values = decode(&storage, &[]);
+        assert_eq!(values[3].value, format!("{:?}", name));
+    }
+
+    #[test]
+    fn diffs_values() {
+        let old = decode(&storage(), &[]);
+        let mut storage = storage();
+        storage.insert(slot(2), H256::from_low_u64_be(2));
+        storage.insert(slot(1), H256::zero());
+        let new = decode(&storage, &[]);
+
+        let changes = diff(&old, &new);
+        assert_eq!(
+            changes,
+            vec![
+                StorageChange {
+                    label: "name".to_string(),
+                    ty: "string".to_string(),
+                    old: Some("\"vault\"".to_string()),
+                    new: Some("\"\"".to_string()),
+                },
+                StorageChange {
+                    label: "checkpoints[2]".to_string(),
+                    ty: "uint64".to_string(),
+                    old: Some("30".to_string()),
+                    new: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn requires_storage_layout() {
+        let err = StorageLayout::from_json(r#"{"abi": []}"#).unwrap_err();
+        assert!(err.to_string().contains("extra_output"));
+    }
+}
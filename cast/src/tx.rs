@@ -0,0 +1,160 @@
+//! Decoding of signed raw transactions
+use ethers_core::{
+    types::{
+        transaction::eip2930::{AccessList, AccessListItem},
+        Address, Signature, Transaction, H256, U256, U64,
+    },
+    utils::{
+        keccak256,
+        rlp::{Rlp, RlpStream},
+    },
+};
+use eyre::Result;
+
+/// Decodes a signed legacy, EIP-2930 or EIP-1559 transaction envelope and recovers its sender
+///
+/// The chain id of legacy transactions is only set if they're EIP-155 replay protected.
+pub fn decode_raw_transaction(raw: &[u8]) -> Result<Transaction> {
+    let (ty, payload) = match raw.first() {
+        Some(&ty) if ty <= 0x7f => (ty, &raw[1..]),
+        Some(_) => (0, raw),
+        None => eyre::bail!("empty transaction"),
+    };
+    let rlp = Rlp::new(payload);
+    let signed_fields =
+        rlp.item_count()?.checked_sub(3).ok_or_else(|| eyre::eyre!("transaction is not signed"))?;
+
+    let mut tx = Transaction { hash: H256::from(keccak256(raw)), ..Default::default() };
+    // `gas` is the index of the gas limit, which is followed by `to`, `value` and `data`
+    let gas = match ty {
+        0 => {
+            tx.nonce = rlp.val_at(0)?;
+            tx.gas_price = Some(rlp.val_at(1)?);
+            2
+        }
+        1 => {
+            tx.nonce = rlp.val_at(1)?;
+            tx.gas_price = Some(rlp.val_at(2)?);
+            3
+        }
+        2 => {
+            tx.nonce = rlp.val_at(1)?;
+            tx.max_priority_fee_per_gas = Some(rlp.val_at(2)?);
+            tx.max_fee_per_gas = Some(rlp.val_at(3)?);
+            4
+        }
+        _ => eyre::bail!("unsupported transaction type {}", ty),
+    };
+    tx.gas = rlp.val_at(gas)?;
+    let to = rlp.at(gas + 1)?;
+    tx.to = if to.is_empty() { None } else { Some(to.as_val::<Address>()?) };
+    tx.value = rlp.val_at(gas + 2)?;
+    tx.input = rlp.val_at::<Vec<u8>>(gas + 3)?.into();
+
+    let v: u64 = rlp.val_at(signed_fields)?;
+    let signature =
+        Signature { r: rlp.val_at(signed_fields + 1)?, s: rlp.val_at(signed_fields + 2)?, v };
+    let mut stream = RlpStream::new();
+    let sighash = if ty == 0 {
+        if v >= 35 {
+            // EIP-155 replay protection
+            let chain_id = (v - 35) / 2;
+            tx.chain_id = Some(chain_id.into());
+            stream.begin_list(9);
+            for idx in 0..6 {
+                stream.append_raw(rlp.at(idx)?.as_raw(), 1);
+            }
+            stream.append(&chain_id).append(&0u8).append(&0u8);
+        } else {
+            stream.begin_list(6);
+            for idx in 0..6 {
+                stream.append_raw(rlp.at(idx)?.as_raw(), 1);
+            }
+        }
+        keccak256(stream.out())
+    } else {
+        tx.transaction_type = Some(U64::from(ty));
+        tx.chain_id = Some(rlp.val_at(0)?);
+        tx.access_list = Some(decode_access_list(&rlp.at(gas + 4)?)?);
+        stream.begin_list(signed_fields);
+        for idx in 0..signed_fields {
+            stream.append_raw(rlp.at(idx)?.as_raw(), 1);
+        }
+        let mut encoded = vec![ty];
+        encoded.extend_from_slice(&stream.out());
+        keccak256(encoded)
+    };
+
+    tx.from = signature.recover(H256::from(sighash))?;
+    tx.v = v.into();
+    tx.r = signature.r;
+    tx.s = signature.s;
+    Ok(tx)
+}
+
+fn decode_access_list(rlp: &Rlp<'_>) -> Result<AccessList> {
+    let items = rlp
+        .iter()
+        .map(|item| Ok(AccessListItem { address: item.val_at(0)?, storage_keys: item.list_at(1)? }))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(AccessList(items))
+}
+
+/// The fee per gas paid by the transaction in a block with the `base_fee`
+pub fn effective_gas_price(tx: &Transaction, base_fee: U256) -> U256 {
+    match (tx.max_fee_per_gas, tx.max_priority_fee_per_gas) {
+        (Some(max_fee), Some(max_priority_fee)) => {
+            max_fee.min(base_fee.saturating_add(max_priority_fee))
+        }
+        _ => tx.gas_price.unwrap_or_default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers_core::types::{transaction::eip2718::TypedTransaction, Eip1559TransactionRequest};
+    use ethers_signers::{LocalWallet, Signer};
+
+    #[test]
+    fn decodes_eip155_transaction() {
+        // the example of EIP-155
+        let raw = hex::decode("f86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a76400008025a028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83").unwrap();
+        let tx = decode_raw_transaction(&raw).unwrap();
+        assert_eq!(tx.from, "0x9d8A62f656a8d1615C1294fd71e9CFb3E4855A4F".parse().unwrap());
+        assert_eq!(tx.to, Some("0x3535353535353535353535353535353535353535".parse().unwrap()));
+        assert_eq!(tx.chain_id, Some(1.into()));
+        assert_eq!(tx.gas_price, Some(20_000_000_000u64.into()));
+        assert_eq!(tx.value, U256::exp10(18));
+        assert_eq!(tx.transaction_type, None);
+    }
+
+    #[tokio::test]
+    async fn decodes_eip1559_transaction() {
+        let wallet = "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318"
+            .parse::<LocalWallet>()
+            .unwrap()
+            .with_chain_id(5u64);
+        let request: TypedTransaction = Eip1559TransactionRequest::new()
+            .to(Address::repeat_byte(0x35))
+            .nonce(3)
+            .gas(50_000)
+            .max_fee_per_gas(100)
+            .max_priority_fee_per_gas(2)
+            .data(vec![0xde, 0xad])
+            .chain_id(5)
+            .into();
+        let signature = wallet.sign_transaction(&request).await.unwrap();
+        let raw = request.rlp_signed(&signature);
+
+        let tx = decode_raw_transaction(&raw).unwrap();
+        assert_eq!(tx.from, wallet.address());
+        assert_eq!(tx.transaction_type, Some(2.into()));
+        assert_eq!(tx.chain_id, Some(5.into()));
+        assert_eq!(tx.nonce, 3.into());
+        assert_eq!(tx.input.as_ref(), &[0xde, 0xad]);
+        assert_eq!(tx.access_list, Some(AccessList(Vec::new())));
+        assert_eq!(effective_gas_price(&tx, 90.into()), 92.into());
+        assert_eq!(effective_gas_price(&tx, 99.into()), 100.into());
+    }
+}
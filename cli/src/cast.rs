@@ -1,63 +1,97 @@
 pub mod cmd;
-
+pub mod exit_code;
+pub mod explorer;
+pub mod hooks;
+pub mod ipfs;
+pub mod shell;
 mod utils;
 
-use cast::{Cast, SimpleCast};
+use cast::{CalldataDecoder, Cast, SimpleCast, StorageLayout};
 
 mod opts;
-use cast::InterfacePath;
 use ethers::{
     core::{
+        abi::{parse_abi, Abi},
         rand::thread_rng,
         types::{BlockId, BlockNumber::Latest},
     },
-    providers::{Middleware, Provider},
+    providers::{Middleware, Provider, Ws},
     signers::{LocalWallet, Signer},
-    types::{Address, Chain, NameOrAddress, Signature, U256},
+    types::{
+        transaction::{eip2718::TypedTransaction, eip2930::AccessList},
+        Address, Chain, Filter, Log, NameOrAddress, Signature, H256, U256,
+    },
 };
 use opts::{
-    cast::{Opts, Subcommands, WalletSubcommands},
-    EthereumOpts, WalletType,
+    cast::{MerkleSubcommands, Opts, Subcommands, WalletSubcommands},
+    wallet::{validate_account_name, with_wallet, WalletSigner},
 };
 use rayon::prelude::*;
 use regex::RegexSet;
 use rustc_hex::ToHex;
 use std::{
+    collections::BTreeSet,
     convert::TryFrom,
-    io::{self, Write},
+    io::{self, Read, Write},
     path::Path,
     str::FromStr,
-    time::Instant,
+    time::{Duration, Instant},
 };
 
 use clap::{IntoApp, Parser};
 use clap_complete::generate;
 
-use crate::utils::read_secret;
+use crate::{
+    cmd::Cmd,
+    exit_code::ExitCode,
+    shell::{sh_println, sh_status},
+    utils::read_secret,
+};
 use eyre::WrapErr;
+use foundry_utils::{
+    eip712::TypedData,
+    merkle::{self, AirdropClaim, MerkleTree},
+};
+use futures::{Stream, StreamExt};
 
 #[tokio::main]
 async fn main() -> eyre::Result<()> {
     color_eyre::install()?;
+    exit_code::install_panic_hook();
 
-    let opts = Opts::parse();
+    let opts = match Opts::try_parse() {
+        Ok(opts) => opts,
+        Err(err) => {
+            // the help and version are printed by clap too, but aren't errors
+            let _ = err.print();
+            if err.use_stderr() { ExitCode::ConfigError } else { ExitCode::Success }.exit()
+        }
+    };
+    opts.shell.install(shell::OutputMode::Human);
+    if let Err(err) = run(opts).await {
+        exit_code::exit_with_error(err)
+    }
+    Ok(())
+}
+
+async fn run(opts: Opts) -> eyre::Result<()> {
     match opts.sub {
         Subcommands::MaxInt => {
-            println!("{}", SimpleCast::max_int()?);
+            sh_println!("{}", SimpleCast::max_int()?);
         }
         Subcommands::MinInt => {
-            println!("{}", SimpleCast::min_int()?);
+            sh_println!("{}", SimpleCast::min_int()?);
         }
         Subcommands::MaxUint => {
-            println!("{}", SimpleCast::max_uint()?);
+            sh_println!("{}", SimpleCast::max_uint()?);
         }
         Subcommands::FromUtf8 { text } => {
             let val = unwrap_or_stdin(text)?;
-            println!("{}", SimpleCast::from_utf8(&val));
+            sh_println!("{}", SimpleCast::from_utf8(&val));
         }
         Subcommands::ToHex { decimal } => {
             let val = unwrap_or_stdin(decimal)?;
-            println!("{}", SimpleCast::hex(U256::from_dec_str(&val)?));
+            sh_println!("{}", SimpleCast::hex(U256::from_dec_str(val.trim())?));
         }
         Subcommands::ToHexdata { input } => {
             let val = unwrap_or_stdin(input)?;
@@ -78,52 +112,60 @@ async fn main() -> eyre::Result<()> {
                     output
                 }
             };
-            println!("0x{}", output);
+            sh_println!("0x{}", output);
         }
         Subcommands::ToCheckSumAddress { address } => {
             let val = unwrap_or_stdin(address)?;
-            println!("{}", SimpleCast::checksum_address(&val)?);
+            sh_println!("{}", SimpleCast::checksum_address(&val)?);
         }
         Subcommands::ToAscii { hexdata } => {
             let val = unwrap_or_stdin(hexdata)?;
-            println!("{}", SimpleCast::ascii(&val)?);
+            sh_println!("{}", SimpleCast::ascii(&val)?);
         }
         Subcommands::ToBytes32 { bytes } => {
             let val = unwrap_or_stdin(bytes)?;
-            println!("{}", SimpleCast::bytes32(&val)?);
+            sh_println!("{}", SimpleCast::bytes32(&val)?);
+        }
+        Subcommands::ToRlp { value } => {
+            let val = unwrap_or_stdin(value)?;
+            sh_println!("{}", SimpleCast::to_rlp(&val)?);
+        }
+        Subcommands::FromRlp { rlp } => {
+            let val = unwrap_or_stdin(rlp)?;
+            sh_println!("{}", SimpleCast::from_rlp(&val)?);
         }
         Subcommands::ToDec { hexvalue } => {
             let val = unwrap_or_stdin(hexvalue)?;
-            println!("{}", SimpleCast::to_dec(&val)?);
+            sh_println!("{}", SimpleCast::to_dec(&val)?);
         }
         Subcommands::ToFix { decimals, value } => {
             let val = unwrap_or_stdin(value)?;
-            println!(
+            sh_println!(
                 "{}",
                 SimpleCast::to_fix(unwrap_or_stdin(decimals)?, U256::from_dec_str(&val)?)?
             );
         }
         Subcommands::ToUint256 { value } => {
             let val = unwrap_or_stdin(value)?;
-            println!("{}", SimpleCast::to_uint256(&val)?);
+            sh_println!("{}", SimpleCast::to_uint256(&val)?);
         }
         Subcommands::ToUnit { value, unit } => {
             let val = unwrap_or_stdin(value)?;
-            println!("{}", SimpleCast::to_unit(val, unit.unwrap_or_else(|| String::from("wei")))?);
+            sh_println!(
+                "{}",
+                SimpleCast::to_unit(val, unit.unwrap_or_else(|| String::from("wei")))?
+            );
         }
         Subcommands::ToWei { value, unit } => {
             let val = unwrap_or_stdin(value)?;
-            println!(
+            sh_println!(
                 "{}",
-                SimpleCast::to_wei(
-                    val.parse::<f64>()?,
-                    unit.unwrap_or_else(|| String::from("eth"))
-                )?
+                SimpleCast::to_wei(&val, unit.unwrap_or_else(|| String::from("eth")))?
             );
         }
         Subcommands::FromWei { value, unit } => {
             let val = unwrap_or_stdin(value)?;
-            println!(
+            sh_println!(
                 "{}",
                 SimpleCast::from_wei(
                     U256::from_dec_str(&val)?,
@@ -133,19 +175,19 @@ async fn main() -> eyre::Result<()> {
         }
         Subcommands::Block { rpc_url, block, full, field, to_json } => {
             let provider = Provider::try_from(rpc_url)?;
-            println!("{}", Cast::new(provider).block(block, full, field, to_json).await?);
+            sh_println!("{}", Cast::new(provider).block(block, full, field, to_json).await?);
         }
         Subcommands::BlockNumber { rpc_url } => {
             let provider = Provider::try_from(rpc_url)?;
-            println!("{}", Cast::new(provider).block_number().await?);
+            sh_println!("{}", Cast::new(provider).block_number().await?);
         }
         Subcommands::Call { eth, address, sig, args } => {
             let provider = Provider::try_from(eth.rpc_url()?)?;
-            println!(
+            sh_println!(
                 "{}",
                 Cast::new(provider)
                     .call(
-                        eth.sender().await,
+                        eth.sender().await?,
                         address,
                         (&sig, args),
                         eth.chain,
@@ -155,79 +197,121 @@ async fn main() -> eyre::Result<()> {
             );
         }
         Subcommands::Calldata { sig, args } => {
-            println!("{}", SimpleCast::calldata(sig, &args)?);
+            sh_println!("{}", SimpleCast::calldata(sig, &args)?);
         }
         Subcommands::Chain { rpc_url } => {
             let provider = Provider::try_from(rpc_url)?;
-            println!("{}", Cast::new(provider).chain().await?);
+            sh_println!("{}", Cast::new(provider).chain().await?);
         }
         Subcommands::ChainId { rpc_url } => {
             let provider = Provider::try_from(rpc_url)?;
-            println!("{}", Cast::new(provider).chain_id().await?);
+            sh_println!("{}", Cast::new(provider).chain_id().await?);
         }
         Subcommands::Code { block, who, rpc_url } => {
             let provider = Provider::try_from(rpc_url)?;
-            println!("{}", Cast::new(provider).code(who, block).await?);
+            sh_println!("{}", Cast::new(provider).code(who, block).await?);
         }
         Subcommands::Namehash { name } => {
-            println!("{}", SimpleCast::namehash(&name)?);
+            sh_println!("{}", SimpleCast::namehash(&name)?);
         }
         Subcommands::Tx { rpc_url, hash, field, to_json } => {
             let provider = Provider::try_from(rpc_url)?;
-            println!("{}", Cast::new(&provider).transaction(hash, field, to_json).await?)
+            sh_println!("{}", Cast::new(&provider).transaction(hash, field, to_json).await?)
         }
-        Subcommands::SendTx { eth, to, sig, cast_async, args, gas, value, nonce } => {
+        Subcommands::DecodeTx { raw_tx, sig, to_json } => {
+            let raw_tx = unwrap_or_stdin(raw_tx)?;
+            sh_println!("{}", SimpleCast::format_raw_transaction(&raw_tx, sig.as_deref(), to_json)?)
+        }
+        Subcommands::Receipt {
+            hash,
+            field,
+            to_json,
+            confirmations,
+            timeout,
+            abi,
+            etherscan,
+            chain,
+            etherscan_api_key,
+            rpc_url,
+        } => {
+            let provider = Provider::try_from(rpc_url.as_str())?;
+            let cast = Cast::new(&provider);
+            let tx_hash = H256::from_str(&hash)?;
+            let receipt = match timeout {
+                Some(timeout) => tokio::time::timeout(
+                    Duration::from_secs(timeout),
+                    cast.wait_for_receipt(tx_hash, confirmations),
+                )
+                .await
+                .map_err(|_| {
+                    eyre::eyre!(
+                        "transaction {:?} wasn't confirmed within {} seconds",
+                        tx_hash,
+                        timeout
+                    )
+                })??,
+                None => cast.wait_for_receipt(tx_hash, confirmations).await?,
+            };
+
+            let mut abis = Vec::new();
+            if let Some(path) = abi {
+                abis.push(read_abi(&path)?);
+            }
+            if etherscan {
+                let api_key = etherscan_api_key.ok_or_else(|| eyre::eyre!("No Etherscan API Key is set. Consider using the ETHERSCAN_API_KEY env var, or the --etherscan-api-key CLI argument."))?;
+                let emitters = receipt.logs.iter().map(|log| log.address).collect::<BTreeSet<_>>();
+                for address in emitters {
+                    // unverified contracts are skipped, their logs are not decoded
+                    if let Ok(abi) =
+                        foundry_utils::fetch_abi_etherscan(address, chain.inner, api_key.clone())
+                            .await
+                    {
+                        abis.push(abi);
+                    }
+                }
+            }
+
+            // failed transactions are simulated on a fork of their parent block for the reason
+            let revert_reason = if receipt.status == Some(0u64.into()) {
+                let tx = provider
+                    .get_transaction(tx_hash)
+                    .await?
+                    .ok_or_else(|| eyre::eyre!("transaction {:?} not found", tx_hash))?;
+                // the fork backend runs its own runtime, which can't be nested in this one
+                let simulated =
+                    std::thread::spawn(move || cmd::replay::simulate_revert_reason(&rpc_url, &tx))
+                        .join()
+                        .expect("failed to simulate the transaction");
+                Some(simulated.unwrap_or_else(|err| format!("unknown, {}", err)))
+            } else {
+                None
+            };
+            sh_println!(
+                "{}",
+                SimpleCast::format_receipt(&receipt, field, to_json, &abis, revert_reason)?
+            )
+        }
+        Subcommands::SendTx { eth, to, sig, cast_async, args, gas, value, nonce, access_list } => {
             let provider = Provider::try_from(eth.rpc_url()?)?;
             let chain_id = Cast::new(&provider).chain_id().await?;
 
             if let Some(signer) = eth.signer_with(chain_id, provider.clone()).await? {
-                match signer {
-                    WalletType::Ledger(signer) => {
-                        cast_send(
-                            &signer,
-                            signer.address(),
-                            to,
-                            (sig, args),
-                            gas,
-                            value,
-                            nonce,
-                            eth.chain,
-                            eth.etherscan_api_key,
-                            cast_async,
-                        )
-                        .await?;
-                    }
-                    WalletType::Local(signer) => {
-                        cast_send(
-                            &signer,
-                            signer.address(),
-                            to,
-                            (sig, args),
-                            gas,
-                            value,
-                            nonce,
-                            eth.chain,
-                            eth.etherscan_api_key,
-                            cast_async,
-                        )
-                        .await?;
-                    }
-                    WalletType::Trezor(signer) => {
-                        cast_send(
-                            &signer,
-                            signer.address(),
-                            to,
-                            (sig, args),
-                            gas,
-                            value,
-                            nonce,
-                            eth.chain,
-                            eth.etherscan_api_key,
-                            cast_async,
-                        )
-                        .await?;
-                    }
-                }
+                with_wallet!(signer, |signer| {
+                    cast_send(
+                        &signer,
+                        signer.address(),
+                        to,
+                        (sig, args),
+                        gas,
+                        value,
+                        nonce,
+                        access_list,
+                        eth.chain,
+                        eth.etherscan_api_key,
+                        cast_async,
+                    )
+                    .await?
+                });
             } else {
                 let from = eth.from.expect("No ETH_FROM or signer specified");
                 cast_send(
@@ -238,6 +322,7 @@ async fn main() -> eyre::Result<()> {
                     gas,
                     value,
                     nonce,
+                    access_list,
                     eth.chain,
                     eth.etherscan_api_key,
                     cast_async,
@@ -245,24 +330,47 @@ async fn main() -> eyre::Result<()> {
                 .await?;
             }
         }
+        Subcommands::MakeTx { to, sig, args, gas, value, nonce, access_list, rlp, eth } => {
+            let provider = Provider::try_from(eth.rpc_url()?)?;
+            let from = eth.sender().await?;
+            let params = if !sig.is_empty() { Some((sig.as_str(), args)) } else { None };
+            let tx = Cast::new(&provider)
+                .unsigned_tx(
+                    from,
+                    to,
+                    params,
+                    gas,
+                    value,
+                    nonce,
+                    access_list,
+                    eth.chain,
+                    eth.etherscan_api_key,
+                )
+                .await?;
+            if rlp {
+                sh_println!("0x{}", hex::encode(tx.rlp()));
+            } else {
+                sh_println!("{}", serde_json::to_string_pretty(&tx)?);
+            }
+        }
         Subcommands::PublishTx { eth, raw_tx, cast_async } => {
             let provider = Provider::try_from(eth.rpc_url()?)?;
             let cast = Cast::new(&provider);
-            let pending_tx = cast.publish(raw_tx).await?;
+            let pending_tx = cast.publish(unwrap_or_stdin(raw_tx)?).await?;
             let tx_hash = *pending_tx;
 
             if cast_async {
-                println!("{:?}", pending_tx);
+                sh_println!("{:?}", pending_tx);
             } else {
                 let receipt =
                     pending_tx.await?.ok_or_else(|| eyre::eyre!("tx {} not found", tx_hash))?;
-                println!("Receipt: {:?}", receipt);
+                sh_println!("Receipt: {:?}", receipt);
             }
         }
         Subcommands::Estimate { eth, to, sig, args, value } => {
             let provider = Provider::try_from(eth.rpc_url()?)?;
             let cast = Cast::new(&provider);
-            let from = eth.sender().await;
+            let from = eth.sender().await?;
             let gas = cast
                 .estimate(
                     from,
@@ -273,28 +381,52 @@ async fn main() -> eyre::Result<()> {
                     eth.etherscan_api_key,
                 )
                 .await?;
-            println!("{}", gas);
+            sh_println!("{}", gas);
         }
         Subcommands::CalldataDecode { sig, calldata } => {
             let tokens = SimpleCast::abi_decode(&sig, &calldata, true)?;
             let tokens = foundry_utils::format_tokens(&tokens);
-            tokens.for_each(|t| println!("{}", t));
+            tokens.for_each(|t| sh_println!("{}", t));
+        }
+        Subcommands::DecodeCalldata { sig, calldata } => {
+            let path = Path::new(&sig);
+            let functions = if path.is_file() {
+                read_abi(path)?.functions().cloned().collect()
+            } else {
+                vec![foundry_utils::get_func(&sig)?]
+            };
+            let calldata = hex::decode(calldata.strip_prefix("0x").unwrap_or(&calldata))?;
+            let decoded = CalldataDecoder::new(functions).decode(&calldata)?;
+            sh_println!("{}", decoded.to_string().trim_end());
         }
         Subcommands::AbiDecode { sig, calldata, input } => {
             let tokens = SimpleCast::abi_decode(&sig, &calldata, input)?;
             let tokens = foundry_utils::format_tokens(&tokens);
-            tokens.for_each(|t| println!("{}", t));
+            tokens.for_each(|t| sh_println!("{}", t));
         }
         Subcommands::AbiEncode { sig, args } => {
-            println!("{}", SimpleCast::abi_encode(&sig, &args)?);
+            sh_println!("{}", SimpleCast::abi_encode(&sig, &args)?);
         }
         Subcommands::FourByte { selector } => {
             let sigs = foundry_utils::fourbyte(&selector).await?;
-            sigs.iter().for_each(|sig| println!("{}", sig.0));
+            sigs.iter().for_each(|sig| sh_println!("{}", sig.0));
+        }
+        Subcommands::FourByteEvent { topic } => {
+            let topic = unwrap_or_stdin(topic)?;
+            let sigs = foundry_utils::fourbyte_event(&topic).await?;
+            sigs.iter().for_each(|sig| sh_println!("{}", sig.0));
+        }
+        Subcommands::Sig { sig } => {
+            let sig = unwrap_or_stdin(sig)?;
+            sh_println!("{}", SimpleCast::get_selector(&sig)?);
+        }
+        Subcommands::SigEvent { sig } => {
+            let sig = unwrap_or_stdin(sig)?;
+            sh_println!("{}", SimpleCast::get_event_topic(&sig)?);
         }
         Subcommands::FourByteDecode { calldata, id } => {
             let sigs = foundry_utils::fourbyte_possible_sigs(&calldata, id).await?;
-            sigs.iter().enumerate().for_each(|(i, sig)| println!("{}) \"{}\"", i + 1, sig));
+            sigs.iter().enumerate().for_each(|(i, sig)| sh_println!("{}) \"{}\"", i + 1, sig));
 
             let sig = match sigs.len() {
                 0 => Err(eyre::eyre!("No signatures found")),
@@ -312,76 +444,146 @@ async fn main() -> eyre::Result<()> {
             let tokens = SimpleCast::abi_decode(sig, &calldata, true)?;
             let tokens = foundry_utils::format_tokens(&tokens);
 
-            tokens.for_each(|t| println!("{}", t));
+            tokens.for_each(|t| sh_println!("{}", t));
+        }
+        Subcommands::Logs {
+            event,
+            topics,
+            address,
+            from_block,
+            to_block,
+            abi,
+            follow,
+            to_json,
+            rpc_url,
+        } => {
+            let mut abis = Vec::new();
+            if let Some(path) = abi {
+                abis.push(read_abi(&path)?);
+            }
+            let mut filter = Filter::new();
+            if let Some(event) = event {
+                if event.starts_with("0x") {
+                    filter = filter.topic0(H256::from_str(&event)?);
+                } else {
+                    let sig = format!("event {}", event.trim_start_matches("event "));
+                    let event_abi = parse_abi(&[sig.as_str()])?;
+                    let event = event_abi.events().next().expect("parsed an event");
+                    filter = filter.topic0(event.signature());
+                    abis.push(event_abi);
+                }
+            }
+            for (idx, topic) in topics.into_iter().enumerate() {
+                filter = match idx {
+                    0 => filter.topic1(topic),
+                    1 => filter.topic2(topic),
+                    2 => filter.topic3(topic),
+                    _ => eyre::bail!("logs have at most 3 indexed topics"),
+                };
+            }
+            if let Some(block) = from_block {
+                filter = filter.from_block(block);
+            }
+            if let Some(block) = to_block {
+                filter = filter.to_block(block);
+            }
+            // without a start block only new logs are followed
+            let history = !follow || from_block.is_some();
+
+            if rpc_url.starts_with("ws") {
+                let provider = Provider::<Ws>::connect(rpc_url.as_str()).await?;
+                let filter = with_address(&provider, filter, address).await?;
+                if history {
+                    sh_println!("{}", Cast::new(&provider).logs(&filter, &abis, to_json).await?);
+                }
+                if follow {
+                    print_logs(provider.subscribe_logs(&filter).await?, &abis, to_json).await?;
+                }
+            } else {
+                let provider = Provider::try_from(rpc_url)?;
+                let filter = with_address(&provider, filter, address).await?;
+                if history {
+                    sh_println!("{}", Cast::new(&provider).logs(&filter, &abis, to_json).await?);
+                }
+                if follow {
+                    print_logs(provider.watch(&filter).await?, &abis, to_json).await?;
+                }
+            }
+        }
+        Subcommands::Pending { to, to_json, rpc_url } => {
+            if rpc_url.starts_with("ws") {
+                let provider = Provider::<Ws>::connect(rpc_url.as_str()).await?;
+                let stream = provider.subscribe_pending_txs().await?;
+                print_pending(&provider, stream, &to, to_json).await?;
+            } else {
+                let provider = Provider::try_from(rpc_url)?;
+                let stream = provider.watch_pending_transactions().await?;
+                print_pending(&provider, stream, &to, to_json).await?;
+            }
         }
         Subcommands::Age { block, rpc_url } => {
             let provider = Provider::try_from(rpc_url)?;
-            println!(
+            sh_println!(
                 "{}",
                 Cast::new(provider).age(block.unwrap_or(BlockId::Number(Latest))).await?
             );
         }
         Subcommands::Balance { block, who, rpc_url } => {
             let provider = Provider::try_from(rpc_url)?;
-            println!("{}", Cast::new(provider).balance(who, block).await?);
+            sh_println!("{}", Cast::new(provider).balance(who, block).await?);
         }
         Subcommands::BaseFee { block, rpc_url } => {
             let provider = Provider::try_from(rpc_url)?;
-            println!(
+            sh_println!(
                 "{}",
                 Cast::new(provider).base_fee(block.unwrap_or(BlockId::Number(Latest))).await?
             );
         }
         Subcommands::GasPrice { rpc_url } => {
             let provider = Provider::try_from(rpc_url)?;
-            println!("{}", Cast::new(provider).gas_price().await?);
+            sh_println!("{}", Cast::new(provider).gas_price().await?);
         }
         Subcommands::Keccak { data } => {
-            println!("{}", SimpleCast::keccak(&data)?);
+            let data = unwrap_or_stdin(data)?;
+            sh_println!("{}", SimpleCast::keccak(&data)?);
         }
 
         Subcommands::Interface {
             path_or_address,
+            name,
             pragma,
             chain,
             output_location,
             etherscan_api_key,
         } => {
-            let interfaces = if Path::new(&path_or_address).exists() {
-                SimpleCast::generate_interface(InterfacePath::Local(path_or_address)).await?
+            let (contract_name, abi) = if Path::new(&path_or_address).exists() {
+                let abi = std::fs::read_to_string(&path_or_address)
+                    .wrap_err("unable to read abi file")?;
+                let abi: serde_json::Value =
+                    serde_json::from_str(&abi).wrap_err("unable to parse json ABI from file")?;
+                ("Interface".to_string(), abi)
             } else {
-                let api_key = match etherscan_api_key {
-                    Some(inner) => inner,
-                    _ => eyre::bail!("No Etherscan API Key is set. Consider using the ETHERSCAN_API_KEY env var, or the -e CLI argument.")
-                };
-                SimpleCast::generate_interface(InterfacePath::Etherscan {
-                    chain: chain.inner,
-                    api_key,
-                    address: path_or_address
-                        .parse::<Address>()
-                        .wrap_err("Invalid address provided. Did you make a typo?")?,
-                })
-                .await?
+                let address = path_or_address
+                    .parse::<Address>()
+                    .wrap_err("Invalid address provided. Did you make a typo?")?;
+                let config = foundry_config::Config::try_load()?;
+                let explorer =
+                    explorer::explorer(&config, chain.inner as u64, None, None, etherscan_api_key)?;
+                explorer.contract_abi(address).await?
             };
-
-            // put it all together
-            let pragma = format!("pragma solidity {};", pragma);
-            let interfaces = interfaces
-                .iter()
-                .map(|iface| iface.source.to_string())
-                .collect::<Vec<_>>()
-                .join("\n");
-            let res = format!("{}\n\n{}", pragma, interfaces);
+            let interface =
+                foundry_utils::abi_to_solidity(&abi, name.as_deref().unwrap_or(&contract_name))?;
+            let res = format!("pragma solidity {};\n\n{}", pragma, interface);
 
             // print or write to file
             match output_location {
                 Some(loc) => {
                     std::fs::create_dir_all(&loc.parent().unwrap())?;
                     std::fs::write(&loc, res)?;
-                    println!("Saved interface at {}", loc.display());
+                    sh_status!("Saved interface at {}", loc.display());
                 }
                 None => {
-                    println!("{}", res);
+                    sh_println!("{}", res);
                 }
             }
         }
@@ -397,7 +599,7 @@ async fn main() -> eyre::Result<()> {
                     name, who
                 );
             }
-            println!("{:?}", address);
+            sh_println!("{:?}", address);
         }
         Subcommands::LookupAddress { who, rpc_url, verify } => {
             let provider = Provider::try_from(rpc_url)?;
@@ -411,32 +613,115 @@ async fn main() -> eyre::Result<()> {
                     name, who
                 );
             }
-            println!("{}", name);
+            sh_println!("{}", name);
         }
-        Subcommands::Storage { address, slot, rpc_url, block } => {
+        Subcommands::Storage { address, slot, layout, keys, diff, rpc_url, block } => {
             let provider = Provider::try_from(rpc_url)?;
-            let value = provider.get_storage_at(address, slot, block).await?;
-            println!("{:?}", value);
+            match (slot, layout) {
+                (Some(slot), _) => {
+                    let value = provider.get_storage_at(address, slot, block).await?;
+                    sh_println!("{:?}", value);
+                }
+                (None, Some(layout)) => {
+                    let layout = std::fs::read_to_string(&layout)
+                        .wrap_err_with(|| format!("failed to read {}", layout.display()))?;
+                    let layout = StorageLayout::from_json(&layout)?;
+                    let cast = Cast::new(provider);
+                    let values =
+                        cast.storage_layout(address.clone(), &layout, &keys, block).await?;
+                    if let Some(diff) = diff {
+                        let old = cast.storage_layout(address, &layout, &keys, Some(diff)).await?;
+                        for change in cast::storage::diff(&old, &values) {
+                            sh_println!("{}", change);
+                        }
+                    } else {
+                        for value in values {
+                            sh_println!("{}", value);
+                        }
+                    }
+                }
+                (None, None) => unreachable!("clap requires a slot or a layout"),
+            }
         }
         Subcommands::Nonce { block, who, rpc_url } => {
             let provider = Provider::try_from(rpc_url)?;
-            println!("{}", Cast::new(provider).nonce(who, block).await?);
+            sh_println!("{}", Cast::new(provider).nonce(who, block).await?);
+        }
+        Subcommands::ComputeAddress { address, nonce, salt, init_code, rpc_url } => {
+            let computed = match salt {
+                Some(salt) => {
+                    let init_code = init_code
+                        .ok_or_else(|| eyre::eyre!("CREATE2 addresses require the --init-code"))?;
+                    let init_code = hex::decode(init_code.trim().trim_start_matches("0x"))?;
+                    foundry_utils::create2_address(
+                        address.unwrap_or(foundry_utils::DEFAULT_CREATE2_DEPLOYER),
+                        salt,
+                        &init_code,
+                    )
+                }
+                None => {
+                    let address = address
+                        .ok_or_else(|| eyre::eyre!("CREATE addresses require the deployer"))?;
+                    let nonce = match nonce {
+                        Some(nonce) => nonce,
+                        None => {
+                            let rpc_url = rpc_url.ok_or_else(|| {
+                                eyre::eyre!("pass the --nonce or an --rpc-url to fetch it")
+                            })?;
+                            Cast::new(Provider::try_from(rpc_url)?).nonce(address, None).await?
+                        }
+                    };
+                    ethers::utils::get_contract_address(address, nonce)
+                }
+            };
+            sh_println!("{}", SimpleCast::checksum_address(&computed)?);
         }
         Subcommands::EtherscanSource { chain, address, etherscan_api_key } => {
-            println!(
+            sh_println!(
                 "{}",
                 SimpleCast::etherscan_source(chain.inner, address, etherscan_api_key).await?
             );
         }
+        Subcommands::Run(cmd) => {
+            // the replay drives its own runtime, which can't be started from within this one
+            std::thread::spawn(move || cmd.run())
+                .join()
+                .map_err(|_| eyre::eyre!("failed to run the transaction"))??;
+        }
+        Subcommands::Eip712 { data, sign, wallet } => {
+            let data = unwrap_or_stdin(data)?;
+            let json =
+                if Path::new(&data).is_file() { std::fs::read_to_string(&data)? } else { data };
+            let typed_data = TypedData::from_json(&json)?;
+            let digest = typed_data.digest()?;
+            sh_println!(
+                "Domain separator: 0x{}",
+                typed_data.domain_separator()?.to_hex::<String>()
+            );
+            sh_println!("Struct hash: 0x{}", typed_data.struct_hash()?.to_hex::<String>());
+            sh_println!("Digest: 0x{}", digest.to_hex::<String>());
+            if sign {
+                let sig = wallet.signer(0).await?.sign_hash(H256::from(digest))?;
+                sh_println!("Signature: 0x{}", sig);
+            }
+        }
         Subcommands::Wallet { command } => match command {
-            WalletSubcommands::New { path, password, unsafe_password } => {
-                let mut rng = thread_rng();
+            WalletSubcommands::New { path, password, unsafe_password, starts_with } => {
+                let wallet = match starts_with {
+                    Some(prefix) => vanity_wallet(Some(prefix), None)?,
+                    None => LocalWallet::new(&mut thread_rng()),
+                };
+                let address = SimpleCast::checksum_address(&wallet.address())?;
 
                 match path {
                     Some(path) => {
                         let password = read_secret(password, unsafe_password)?;
-                        let (key, uuid) = LocalWallet::new_keystore(&path, &mut rng, password)?;
-                        let address = SimpleCast::checksum_address(&key.address())?;
+                        let uuid = eth_keystore::encrypt_key(
+                            &path,
+                            &mut thread_rng(),
+                            wallet.signer().to_bytes(),
+                            password,
+                        )?;
                         let filepath = format!(
                             "{}/{}",
                             dunce::canonicalize(path)?
@@ -445,54 +730,25 @@ async fn main() -> eyre::Result<()> {
                                 .expect("failed to canonicalize file path"),
                             uuid
                         );
-                        println!(
+                        sh_println!(
                             "Successfully created new keypair at `{}`.\nAddress: {}.",
-                            filepath, address
+                            filepath,
+                            address
                         );
                     }
                     None => {
-                        let wallet = LocalWallet::new(&mut rng);
-                        println!(
+                        sh_println!(
                             "Successfully created new keypair.\nAddress: {}.\nPrivate Key: {}.",
-                            SimpleCast::checksum_address(&wallet.address())?,
+                            address,
                             hex::encode(wallet.signer().to_bytes()),
                         );
                     }
                 }
             }
             WalletSubcommands::Vanity { starts_with, ends_with } => {
-                let mut regexs = vec![];
-                if let Some(prefix) = starts_with {
-                    let pad_width = prefix.len() + prefix.len() % 2;
-                    hex::decode(format!("{:0>width$}", prefix, width = pad_width))
-                        .expect("invalid prefix hex provided");
-                    regexs.push(format!(r"^{}", prefix));
-                }
-                if let Some(suffix) = ends_with {
-                    let pad_width = suffix.len() + suffix.len() % 2;
-                    hex::decode(format!("{:0>width$}", suffix, width = pad_width))
-                        .expect("invalid suffix hex provided");
-                    regexs.push(format!(r"{}$", suffix));
-                }
-
-                assert!(
-                    regexs.iter().map(|p| p.len() - 1).sum::<usize>() <= 40,
-                    "vanity patterns length exceeded. cannot be more than 40 characters",
-                );
-
-                let regex = RegexSet::new(regexs)?;
-
-                println!("Starting to generate vanity address...");
                 let timer = Instant::now();
-                let wallet = std::iter::repeat_with(move || LocalWallet::new(&mut thread_rng()))
-                    .par_bridge()
-                    .find_any(|wallet| {
-                        let addr = hex::encode(wallet.address().to_fixed_bytes());
-                        regex.matches(&addr).into_iter().count() == regex.patterns().len()
-                    })
-                    .expect("failed to generate vanity wallet");
-
-                println!(
+                let wallet = vanity_wallet(starts_with, ends_with)?;
+                sh_println!(
                     "Successfully created new keypair in {} seconds.\nAddress: {}.\nPrivate Key: {}.",
                     timer.elapsed().as_secs(),
                     SimpleCast::checksum_address(&wallet.address())?,
@@ -500,61 +756,186 @@ async fn main() -> eyre::Result<()> {
                 );
             }
             WalletSubcommands::Address { wallet } => {
-                // TODO: Figure out better way to get wallet only.
-                let wallet = EthereumOpts {
-                    wallet,
-                    from: None,
-                    rpc_url: Some("http://localhost:8545".to_string()),
-                    flashbots: false,
-                    chain: Chain::Mainnet,
-                    etherscan_api_key: None,
+                let addr = wallet.signer(0).await?.address();
+                sh_println!("Address: {}", SimpleCast::checksum_address(&addr)?);
+            }
+            WalletSubcommands::Sign { message, hex, no_prefix, wallet } => {
+                let signer = wallet.signer(0).await?;
+                let sig = if no_prefix {
+                    signer.sign_hash(H256::from_str(&message)?)?
+                } else if hex {
+                    signer.sign_message(hex::decode(message.trim_start_matches("0x"))?).await?
+                } else {
+                    signer.sign_message(&message).await?
+                };
+                sh_println!("Signature: 0x{}", sig);
+            }
+            WalletSubcommands::SignTx { tx, wallet } => {
+                let tx = match tx {
+                    Some(tx) => tx,
+                    None => {
+                        let mut tx = String::new();
+                        io::stdin().read_to_string(&mut tx)?;
+                        tx
+                    }
+                };
+                let json = if tx.trim_start().starts_with('{') {
+                    tx
+                } else {
+                    std::fs::read_to_string(&tx)
+                        .wrap_err_with(|| format!("failed to read the transaction from {}", tx))?
+                };
+                let tx: TypedTransaction =
+                    serde_json::from_str(&json).wrap_err("invalid transaction JSON")?;
+                let chain_id = tx.chain_id().ok_or_else(|| {
+                    eyre::eyre!("the transaction has no chain id, build it with `cast mktx`")
+                })?;
+                let signer = wallet.signer(chain_id.as_u64()).await?;
+                if let Some(from) = tx.from() {
+                    if *from != signer.address() {
+                        eyre::bail!(
+                            "the transaction is sent from {:?}, but the wallet's address is {:?}",
+                            from,
+                            signer.address()
+                        )
+                    }
+                }
+                let signature = signer.sign_transaction(&tx).await?;
+                sh_println!("0x{}", hex::encode(tx.rlp_signed(&signature)));
+            }
+            WalletSubcommands::Import { name, unsafe_password, wallet } => {
+                validate_account_name(&name)?;
+                let dir = foundry_config::Config::foundry_keystores_dir()
+                    .ok_or_else(|| eyre::eyre!("could not find the home directory"))?;
+                let target = dir.join(&name);
+                if target.exists() {
+                    eyre::bail!("account `{}` already exists at {}", name, target.display())
                 }
-                .signer(0.into())
-                .await?
-                .unwrap();
-
-                let addr = match wallet {
-                    WalletType::Ledger(signer) => signer.address(),
-                    WalletType::Local(signer) => signer.address(),
-                    WalletType::Trezor(signer) => signer.address(),
+                let (key, address) = match wallet.signer(0).await? {
+                    WalletSigner::Local(wallet) => (wallet.signer().to_bytes(), wallet.address()),
+                    _ => eyre::bail!("only private keys, mnemonics and keystores can be imported"),
                 };
-                println!("Address: {}", SimpleCast::checksum_address(&addr)?);
+                let password = read_secret(unsafe_password.is_none(), unsafe_password)?;
+                std::fs::create_dir_all(&dir)?;
+                let uuid = eth_keystore::encrypt_key(&dir, &mut thread_rng(), key, password)?;
+                std::fs::rename(dir.join(uuid), &target)?;
+                sh_println!(
+                    "Imported account `{}` with address {} to {}",
+                    name,
+                    SimpleCast::checksum_address(&address)?,
+                    target.display()
+                );
             }
-            WalletSubcommands::Sign { message, wallet } => {
-                // TODO: Figure out better way to get wallet only.
-                let wallet = EthereumOpts {
-                    wallet,
-                    from: None,
-                    rpc_url: Some("http://localhost:8545".to_string()),
-                    flashbots: false,
-                    chain: Chain::Mainnet,
-                    etherscan_api_key: None,
+            WalletSubcommands::List => {
+                let dir = foundry_config::Config::foundry_keystores_dir()
+                    .ok_or_else(|| eyre::eyre!("could not find the home directory"))?;
+                let mut names = match std::fs::read_dir(&dir) {
+                    Ok(entries) => entries
+                        .filter_map(Result::ok)
+                        .filter(|entry| entry.path().is_file())
+                        .map(|entry| entry.file_name().to_string_lossy().to_string())
+                        .collect::<Vec<_>>(),
+                    Err(_) => Vec::new(),
+                };
+                names.sort();
+                if names.is_empty() {
+                    sh_println!("No accounts found in {}", dir.display());
+                }
+                for name in names {
+                    sh_println!("{}", name);
                 }
-                .signer(0.into())
-                .await?
-                .unwrap();
-
-                let sig = match wallet {
-                    WalletType::Ledger(wallet) => wallet.signer().sign_message(&message).await?,
-                    WalletType::Local(wallet) => wallet.signer().sign_message(&message).await?,
-                    WalletType::Trezor(wallet) => wallet.signer().sign_message(&message).await?,
+            }
+            WalletSubcommands::Recover { message, signature, hex, no_prefix } => {
+                let signature = Signature::from_str(&signature)?;
+                let address = if no_prefix {
+                    signature.recover(H256::from_str(&message)?)?
+                } else if hex {
+                    signature.recover(hex::decode(message.trim_start_matches("0x"))?)?
+                } else {
+                    signature.recover(message)?
                 };
-                println!("Signature: 0x{}", sig);
+                sh_println!("{}", SimpleCast::checksum_address(&address)?);
             }
             WalletSubcommands::Verify { message, signature, address } => {
                 let pubkey = Address::from_str(&address).expect("invalid pubkey provided");
                 let signature = Signature::from_str(&signature)?;
                 match signature.verify(message, pubkey) {
                     Ok(_) => {
-                        println!("Validation success. Address {} signed this message.", address)
+                        sh_println!("Validation success. Address {} signed this message.", address)
                     }
-                    Err(_) => println!(
+                    Err(_) => sh_println!(
                         "Validation failed. Address {} did not sign this message.",
                         address
                     ),
                 }
             }
         },
+        Subcommands::Merkle { command } => match command {
+            MerkleSubcommands::Tree { csv } => {
+                let claims = read_airdrop_claims(&csv)?;
+                let tree = MerkleTree::new(claims.iter().map(AirdropClaim::leaf).collect())?;
+                if shell::is_json() {
+                    let claims = claims
+                        .iter()
+                        .enumerate()
+                        .map(|(idx, claim)| {
+                            serde_json::json!({
+                                "account": claim.account,
+                                "amount": claim.amount.to_string(),
+                                "leaf": tree.leaves()[idx],
+                                "proof": tree.proof(idx),
+                            })
+                        })
+                        .collect::<Vec<_>>();
+                    shell::print_json(&serde_json::json!({
+                        "root": tree.root(),
+                        "claims": claims,
+                    }))?;
+                } else {
+                    sh_println!("Root: {:?}", tree.root());
+                    for (idx, claim) in claims.iter().enumerate() {
+                        let proof = tree.proof(idx).unwrap_or_default();
+                        sh_println!(
+                            "{} {}: [{}]",
+                            SimpleCast::checksum_address(&claim.account)?,
+                            claim.amount,
+                            proof
+                                .iter()
+                                .map(|node| format!("{:?}", node))
+                                .collect::<Vec<_>>()
+                                .join(",")
+                        );
+                    }
+                }
+            }
+            MerkleSubcommands::Proof { csv, account } => {
+                let claims = read_airdrop_claims(&csv)?;
+                let idx = claims
+                    .iter()
+                    .position(|claim| claim.account == account)
+                    .ok_or_else(|| eyre::eyre!("no claim of {:?} in {}", account, csv.display()))?;
+                let tree = MerkleTree::new(claims.iter().map(AirdropClaim::leaf).collect())?;
+                let proof = tree.proof(idx).unwrap_or_default();
+                if shell::is_json() {
+                    shell::print_json(&proof)?;
+                } else {
+                    for node in proof {
+                        sh_println!("{:?}", node);
+                    }
+                }
+            }
+            MerkleSubcommands::Verify { root, account, amount, proof } => {
+                let leaf = AirdropClaim { account, amount }.leaf();
+                if merkle::verify(root, leaf, &proof) {
+                    sh_println!("Valid proof, the claim is part of the tree.");
+                } else {
+                    eyre::bail!(
+                        "invalid proof, the claim is not part of the tree with root {:?}",
+                        root
+                    )
+                }
+            }
+        },
         Subcommands::Completions { shell } => {
             generate(shell, &mut Opts::into_app(), "cast", &mut std::io::stdout())
         }
@@ -562,6 +943,128 @@ async fn main() -> eyre::Result<()> {
     Ok(())
 }
 
+/// Generates wallets until the hex address starts and ends with the given patterns
+fn vanity_wallet(
+    starts_with: Option<String>,
+    ends_with: Option<String>,
+) -> eyre::Result<LocalWallet> {
+    let mut regexs = vec![];
+    if let Some(prefix) = starts_with {
+        let pad_width = prefix.len() + prefix.len() % 2;
+        hex::decode(format!("{:0>width$}", prefix, width = pad_width))
+            .wrap_err("invalid prefix hex provided")?;
+        regexs.push(format!(r"^{}", prefix));
+    }
+    if let Some(suffix) = ends_with {
+        let pad_width = suffix.len() + suffix.len() % 2;
+        hex::decode(format!("{:0>width$}", suffix, width = pad_width))
+            .wrap_err("invalid suffix hex provided")?;
+        regexs.push(format!(r"{}$", suffix));
+    }
+
+    if regexs.iter().map(|p| p.len() - 1).sum::<usize>() > 40 {
+        eyre::bail!("vanity patterns length exceeded. cannot be more than 40 characters")
+    }
+
+    let regex = RegexSet::new(regexs)?;
+
+    sh_status!("Starting to generate vanity address...");
+    Ok(std::iter::repeat_with(move || LocalWallet::new(&mut thread_rng()))
+        .par_bridge()
+        .find_any(|wallet| {
+            let addr = hex::encode(wallet.address().to_fixed_bytes());
+            regex.matches(&addr).into_iter().count() == regex.patterns().len()
+        })
+        .expect("failed to generate vanity wallet"))
+}
+
+/// Restricts the filter to the logs of the address, resolving ENS names
+async fn with_address<M: Middleware>(
+    provider: &M,
+    filter: Filter,
+    address: Option<NameOrAddress>,
+) -> eyre::Result<Filter>
+where
+    M::Error: 'static,
+{
+    Ok(match address {
+        Some(NameOrAddress::Address(address)) => filter.address(address),
+        Some(NameOrAddress::Name(name)) => filter.address(provider.resolve_name(&name).await?),
+        None => filter,
+    })
+}
+
+/// Prints the logs of the stream as they arrive
+async fn print_logs(
+    stream: impl Stream<Item = Log>,
+    abis: &[Abi],
+    to_json: bool,
+) -> eyre::Result<()> {
+    let mut stream = Box::pin(stream);
+    while let Some(log) = stream.next().await {
+        sh_println!("{}", SimpleCast::format_log(&log, abis, to_json)?);
+    }
+    Ok(())
+}
+
+/// Prints the hashes of the pending transactions of the stream as they arrive, or the full
+/// transactions if they are filtered by recipient or printed as JSON
+async fn print_pending<M: Middleware>(
+    provider: &M,
+    stream: impl Stream<Item = H256>,
+    to: &[Address],
+    to_json: bool,
+) -> eyre::Result<()>
+where
+    M::Error: 'static,
+{
+    let mut stream = Box::pin(stream);
+    while let Some(hash) = stream.next().await {
+        if to.is_empty() && !to_json {
+            sh_println!("{:?}", hash);
+            continue
+        }
+        // the transaction may have been dropped or mined in the meantime
+        let tx = match provider.get_transaction(hash).await? {
+            Some(tx) => tx,
+            None => continue,
+        };
+        if !to.is_empty() && !tx.to.map_or(false, |addr| to.contains(&addr)) {
+            continue
+        }
+        if to_json {
+            sh_println!("{}", serde_json::to_string(&tx)?);
+        } else {
+            sh_println!("{:?}", hash);
+        }
+    }
+    Ok(())
+}
+
+/// Reads a JSON ABI, or the ABI of a forge artifact
+fn read_abi(path: &Path) -> eyre::Result<Abi> {
+    let content: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(path)?)
+        .wrap_err_with(|| format!("failed to read the ABI at {}", path.display()))?;
+    let abi = match content {
+        serde_json::Value::Object(mut artifact) if artifact.contains_key("abi") => {
+            artifact.remove("abi").expect("exists")
+        }
+        abi => abi,
+    };
+    Ok(serde_json::from_value(abi)?)
+}
+
+/// Reads the airdrop claims of the CSV at the `path`
+fn read_airdrop_claims(path: &Path) -> eyre::Result<Vec<AirdropClaim>> {
+    let csv = std::fs::read_to_string(path)
+        .wrap_err_with(|| format!("failed to read {}", path.display()))?;
+    let claims = AirdropClaim::from_csv(&csv)?;
+    if claims.is_empty() {
+        eyre::bail!("no claims in {}", path.display())
+    }
+    Ok(claims)
+}
+
 fn unwrap_or_stdin<T>(what: Option<T>) -> eyre::Result<T>
 where
     T: FromStr + Send + Sync,
@@ -573,7 +1076,7 @@ where
             let input = std::io::stdin();
             let mut what = String::new();
             input.read_line(&mut what)?;
-            T::from_str(&what.replace('\n', ""))?
+            T::from_str(what.trim())?
         }
     })
 }
@@ -587,6 +1090,7 @@ async fn cast_send<M: Middleware, F: Into<NameOrAddress>, T: Into<NameOrAddress>
     gas: Option<U256>,
     value: Option<U256>,
     nonce: Option<U256>,
+    access_list: Option<AccessList>,
     chain: Chain,
     etherscan_api_key: Option<String>,
     cast_async: bool,
@@ -599,15 +1103,16 @@ where
     let sig = args.0;
     let params = args.1;
     let params = if !sig.is_empty() { Some((&sig[..], params)) } else { None };
-    let pending_tx =
-        cast.send(from, to, params, gas, value, nonce, chain, etherscan_api_key).await?;
+    let pending_tx = cast
+        .send(from, to, params, gas, value, nonce, access_list, chain, etherscan_api_key)
+        .await?;
     let tx_hash = *pending_tx;
 
     if cast_async {
-        println!("{}", tx_hash);
+        sh_println!("{}", tx_hash);
     } else {
         let receipt = pending_tx.await?.ok_or_else(|| eyre::eyre!("tx {} not found", tx_hash))?;
-        println!("Receipt: {:?}", receipt);
+        sh_println!("Receipt: {:?}", receipt);
     }
 
     Ok(())
@@ -0,0 +1,278 @@
+//! Bind command
+
+use crate::{
+    cmd::{
+        build::{BuildArgs, CompiledArtifact},
+        Cmd,
+    },
+    shell::sh_status,
+};
+use clap::{Parser, ValueHint};
+use ethers::{contract::Abigen, solc::Project};
+use foundry_config::Config;
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Clone, Parser)]
+pub struct BindArgs {
+    #[clap(
+        help = "where to write the bindings, defaults to the `bindings` config value or `<out>/bindings`",
+        long = "bindings-path",
+        short = 'b',
+        value_hint = ValueHint::DirPath
+    )]
+    pub bindings: Option<PathBuf>,
+
+    #[clap(
+        help = "the name of the generated Rust crate",
+        long,
+        default_value = "foundry-contracts"
+    )]
+    pub crate_name: String,
+
+    #[clap(help = "the version of the generated Rust crate", long, default_value = "0.0.1")]
+    pub crate_version: String,
+
+    #[clap(help = "also generate TypeScript typings in `<bindings>/typescript`", long)]
+    pub typescript: bool,
+
+    #[clap(help = "skip the Rust bindings", long, requires = "typescript")]
+    pub skip_rust: bool,
+
+    #[clap(flatten)]
+    opts: BuildArgs,
+}
+
+impl Cmd for BindArgs {
+    type Output = ();
+
+    fn run(self) -> eyre::Result<Self::Output> {
+        let config = Config::try_from_args(&self.opts)?;
+        let project = self.opts.project()?;
+        super::compile(&project)?;
+
+        let bindings = Bindings {
+            path: self
+                .bindings
+                .or(config.bindings)
+                .unwrap_or_else(|| project.paths.artifacts.join(Bindings::DIR)),
+            crate_name: self.crate_name,
+            crate_version: self.crate_version,
+            rust: !self.skip_rust,
+            typescript: self.typescript,
+        };
+        let generated = bindings.write(&project)?;
+        sh_status!("generated bindings of {} contracts in {}", generated, bindings.path.display());
+        Ok(())
+    }
+}
+
+/// Generates the bindings of all non test contracts of a project
+///
+/// The ABI of every contract is stored in `<path>/abi`, only contracts whose ABI changed since
+/// the last run are regenerated and the bindings of removed contracts are deleted.
+pub struct Bindings {
+    /// the directory of the bindings
+    pub path: PathBuf,
+    pub crate_name: String,
+    pub crate_version: String,
+    /// whether to generate a Rust crate with `abigen`
+    pub rust: bool,
+    /// whether to generate TypeScript typings
+    pub typescript: bool,
+}
+
+impl Bindings {
+    /// The default directory of the bindings in the project's output directory
+    pub const DIR: &'static str = "bindings";
+
+    /// The bindings of the config's `bindings` crate, if set
+    pub fn from_config(config: &Config) -> Option<Self> {
+        Some(Self {
+            path: config.bindings.clone()?,
+            crate_name: "foundry-contracts".to_string(),
+            crate_version: "0.0.1".to_string(),
+            rust: true,
+            typescript: false,
+        })
+    }
+
+    /// (Re)generates the bindings of the project's compiled contracts, returns the number of
+    /// contracts whose bindings were generated
+    pub fn write(&self, project: &Project) -> eyre::Result<usize> {
+        let mut abis = BTreeMap::new();
+        for compiled in CompiledArtifact::read_all(project)? {
+            let abi = match compiled.artifact.get("abi") {
+                Some(abi)
+                    if !compiled.is_test() && abi.as_array().map_or(false, |a| !a.is_empty()) =>
+                {
+                    serde_json::to_string_pretty(abi)?
+                }
+                _ => continue,
+            };
+            if abis.insert(compiled.name.clone(), abi).is_some() {
+                eyre::bail!(
+                    "multiple contracts are named `{}`, which is not supported by the bindings",
+                    compiled.name
+                )
+            }
+        }
+
+        let abi_dir = self.path.join("abi");
+        fs::create_dir_all(&abi_dir)?;
+        self.remove_stale(&abi_dir, &abis)?;
+
+        let mut generated = 0;
+        for (name, abi) in &abis {
+            let abi_path = abi_dir.join(format!("{}.json", name));
+            let unchanged = fs::read_to_string(&abi_path).map_or(false, |old| old == *abi);
+            if unchanged && self.is_generated(name) {
+                continue
+            }
+            fs::write(&abi_path, abi)?;
+            if self.rust {
+                self.write_rust(name, &abi_path)?;
+            }
+            if self.typescript {
+                self.write_typescript(name, abi)?;
+            }
+            generated += 1;
+        }
+
+        if self.rust {
+            self.write_crate(abis.keys())?;
+        }
+        if self.typescript {
+            let index = abis
+                .keys()
+                .map(|name| format!("export * from \"./{}\";\n", name))
+                .collect::<String>();
+            fs::write(self.path.join("typescript").join("index.ts"), index)?;
+        }
+        Ok(generated)
+    }
+
+    /// Whether all requested bindings of the contract exist
+    fn is_generated(&self, name: &str) -> bool {
+        (!self.rust || self.rust_path(name).exists()) &&
+            (!self.typescript || self.typescript_path(name).exists())
+    }
+
+    fn rust_path(&self, name: &str) -> PathBuf {
+        self.path.join("src").join(format!("{}.rs", to_snake_case(name)))
+    }
+
+    fn typescript_path(&self, name: &str) -> PathBuf {
+        self.path.join("typescript").join(format!("{}.ts", name))
+    }
+
+    /// Deletes the ABI and bindings of contracts that no longer exist
+    fn remove_stale(&self, abi_dir: &Path, abis: &BTreeMap<String, String>) -> eyre::Result<()> {
+        for entry in fs::read_dir(abi_dir)?.filter_map(Result::ok) {
+            let path = entry.path();
+            let name = match path.file_stem() {
+                Some(name) => name.to_string_lossy().to_string(),
+                None => continue,
+            };
+            if abis.contains_key(&name) {
+                continue
+            }
+            fs::remove_file(&path)?;
+            for binding in [self.rust_path(&name), self.typescript_path(&name)] {
+                if binding.exists() {
+                    fs::remove_file(binding)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn write_rust(&self, name: &str, abi_path: &Path) -> eyre::Result<()> {
+        let path = self.rust_path(name);
+        fs::create_dir_all(path.parent().expect("has parent"))?;
+        Abigen::new(name, abi_path.to_string_lossy())
+            .and_then(|abigen| abigen.generate())
+            .and_then(|bindings| bindings.write_to_file(&path))
+            .map_err(|err| eyre::eyre!("failed to generate the bindings of {}: {}", name, err))
+    }
+
+    fn write_typescript(&self, name: &str, abi: &str) -> eyre::Result<()> {
+        let path = self.typescript_path(name);
+        fs::create_dir_all(path.parent().expect("has parent"))?;
+        // `as const` lets typed clients infer the function and event signatures from the ABI
+        let typings = format!(
+            "export const {name}Abi = {abi} as const;\n\nexport default {name}Abi;\n",
+            name = name,
+            abi = abi
+        );
+        fs::write(path, typings)?;
+        Ok(())
+    }
+
+    /// Writes the crate's `lib.rs` and, unless it exists, its `Cargo.toml`
+    fn write_crate<'a>(&self, names: impl Iterator<Item = &'a String>) -> eyre::Result<()> {
+        let manifest = self.path.join("Cargo.toml");
+        if !manifest.exists() {
+            fs::write(
+                &manifest,
+                format!(
+                    r#"[package]
+name = "{}"
+version = "{}"
+edition = "2021"
+
+[dependencies]
+ethers = {{ git = "https://github.com/gakonst/ethers-rs", default-features = false, features = ["abigen"] }}
+"#,
+                    self.crate_name, self.crate_version
+                ),
+            )?;
+        }
+
+        let mut lib = String::from(
+            "//! Bindings of the project's contracts, generated by `forge bind`\n#![allow(clippy::all)]\n\n",
+        );
+        for name in names {
+            lib.push_str(&format!("pub mod {};\n", to_snake_case(name)));
+        }
+        fs::create_dir_all(self.path.join("src"))?;
+        fs::write(self.path.join("src").join("lib.rs"), lib)?;
+        Ok(())
+    }
+}
+
+/// Converts a contract name like `ERC20Token` into a module name like `erc20_token`
+fn to_snake_case(name: &str) -> String {
+    let chars = name.chars().collect::<Vec<_>>();
+    let mut snake = String::with_capacity(name.len() + 4);
+    for (idx, c) in chars.iter().enumerate() {
+        if c.is_ascii_uppercase() && idx > 0 {
+            let prev = chars[idx - 1];
+            let next_is_lower = chars.get(idx + 1).map_or(false, |next| next.is_ascii_lowercase());
+            if prev.is_ascii_lowercase() ||
+                prev.is_ascii_digit() ||
+                (prev.is_ascii_uppercase() && next_is_lower)
+            {
+                snake.push('_');
+            }
+        }
+        snake.push(c.to_ascii_lowercase());
+    }
+    snake
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_convert_contract_names() {
+        assert_eq!(to_snake_case("Counter"), "counter");
+        assert_eq!(to_snake_case("ERC20Token"), "erc20_token");
+        assert_eq!(to_snake_case("UniswapV2Pair"), "uniswap_v2_pair");
+        assert_eq!(to_snake_case("WETH9"), "weth9");
+    }
+}
@@ -0,0 +1,835 @@
+//! Broadcasting of the transactions a script recorded with the `broadcast` cheatcodes
+
+use crate::{
+    cmd::{
+        broadcast_log::update_registry,
+        build::BuildArgs,
+        multisend::MultisendBatch,
+        verify::{VerifierArgs, VerifyCompilation},
+    },
+    explorer::{explorer, Explorer, VerifySubmission},
+    opts::forge::FullContractInfo,
+    shell::{self, sh_println, sh_status, sh_warn},
+};
+use clap::Parser;
+use ethers::{
+    prelude::{Http, Middleware, PendingTransaction, Provider, SignerMiddleware},
+    signers::{LocalWallet, Signer},
+    solc::artifacts::{Bytecode, BytecodeObject},
+    types::{
+        transaction::eip2718::TypedTransaction, Address, BlockNumber, Chain,
+        Eip1559TransactionRequest, TransactionReceipt, TransactionRequest, H256, U256,
+    },
+    utils::id,
+};
+use evm_adapters::sputnik::cheatcodes::BroadcastableTransaction;
+use eyre::WrapErr;
+use foundry_config::Config;
+use foundry_utils::{create2_address, DEFAULT_CREATE2_DEPLOYER};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+#[derive(Debug, Clone, Parser)]
+pub struct BroadcastArgs {
+    #[clap(
+        long,
+        help = "send the transactions recorded with the `broadcast` cheatcodes to the chains they were made on"
+    )]
+    pub broadcast: bool,
+
+    #[clap(
+        long = "private-keys",
+        help = "private keys of the broadcasting accounts, transactions of other accounts are sent with `eth_sendTransaction`",
+        multiple_occurrences = true,
+        requires = "broadcast"
+    )]
+    pub private_keys: Vec<String>,
+
+    #[clap(
+        long,
+        help = "use legacy transactions instead of EIP1559 ones. this is auto-enabled for common networks without EIP1559",
+        requires = "broadcast"
+    )]
+    pub legacy: bool,
+
+    #[clap(
+        long,
+        help = "percentage of the gas a transaction used in the simulation that's set as its gas limit",
+        default_value = "130",
+        value_name = "PERCENT"
+    )]
+    pub gas_estimate_multiplier: u64,
+
+    #[clap(
+        long,
+        help = "wait for every transaction to be mined before sending the next one",
+        requires = "broadcast"
+    )]
+    pub slow: bool,
+
+    #[clap(
+        long,
+        help = "verify the source code of all contracts the broadcast deployed",
+        requires = "broadcast"
+    )]
+    pub verify: bool,
+
+    #[clap(flatten)]
+    pub verifier: VerifierArgs,
+
+    #[clap(
+        long,
+        help = "instead of sending the recorded transactions, write them as a Gnosis Safe `MultiSendCallOnly` batch of the Safe to propose to its owners",
+        conflicts_with = "broadcast",
+        requires = "safe"
+    )]
+    pub multisend: bool,
+
+    #[clap(long, help = "the Safe that sends the batched transactions", value_name = "ADDRESS")]
+    pub safe: Option<Address>,
+
+    #[clap(
+        long,
+        help = "the nonce of the Safe transaction, read from the Safe if not set",
+        requires = "safe"
+    )]
+    pub safe_nonce: Option<U256>,
+}
+
+/// The combined journal of a broadcast, with the transactions of every chain
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BroadcastJournal {
+    pub chains: Vec<ChainBroadcast>,
+    /// Unix timestamp of the broadcast
+    pub timestamp: u64,
+}
+
+/// The transactions sent to a single chain
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChainBroadcast {
+    pub chain_id: u64,
+    pub transactions: Vec<BroadcastedTransaction>,
+    pub receipts: Vec<TransactionReceipt>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BroadcastedTransaction {
+    pub hash: H256,
+    pub nonce: U256,
+    #[serde(flatten)]
+    pub transaction: BroadcastableTransaction,
+    /// The name of the compiled contract the transaction deployed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub contract_name: Option<String>,
+}
+
+impl BroadcastedTransaction {
+    /// Returns the init code if the transaction deploys a contract, either directly or via the
+    /// CREATE2 deployer
+    pub fn init_code(&self) -> Option<&[u8]> {
+        match self.transaction.to {
+            None => Some(&self.transaction.data[..]),
+            Some(DEFAULT_CREATE2_DEPLOYER) if self.transaction.data.len() > 32 => {
+                Some(&self.transaction.data[32..])
+            }
+            Some(_) => None,
+        }
+    }
+
+    /// Returns the address of the contract the transaction deployed and its init code, if the
+    /// transaction of the `receipt` succeeded
+    pub fn deployment(&self, receipt: &TransactionReceipt) -> Option<(Address, &[u8])> {
+        if receipt.status != Some(1u64.into()) {
+            return None
+        }
+        let init_code = self.init_code()?;
+        match self.transaction.to {
+            None => receipt.contract_address.map(|address| (address, init_code)),
+            Some(deployer) => {
+                let salt = H256::from_slice(&self.transaction.data[..32]);
+                Some((create2_address(deployer, salt, init_code), init_code))
+            }
+        }
+    }
+}
+
+impl BroadcastArgs {
+    /// Sends the `transactions` of the script at `script` and writes the journal of the broadcast
+    /// to `broadcast/<script>/run-latest.json` and `broadcast/<script>/run-<timestamp>.json` in
+    /// the project's root.
+    ///
+    /// The transactions are grouped by chain id, every chain is sent to the `fork_url` if it's on
+    /// that chain, or else to the endpoint of the config's `rpc_endpoints` that is on that chain.
+    /// Deployments are named after the contract of the `creation_codes` they deploy.
+    pub fn run(
+        &self,
+        transactions: &[BroadcastableTransaction],
+        creation_codes: &[CreationCode],
+        fork_url: Option<&str>,
+        config: &Config,
+        script: &Path,
+    ) -> eyre::Result<BroadcastJournal> {
+        if transactions.is_empty() {
+            sh_status!("No transactions to broadcast.");
+            return Ok(Default::default())
+        }
+        let wallets = self
+            .private_keys
+            .iter()
+            .map(|key| {
+                LocalWallet::from_str(key.strip_prefix("0x").unwrap_or(key))
+                    .map(|wallet| (wallet.address(), wallet))
+                    .wrap_err("invalid private key")
+            })
+            .collect::<eyre::Result<BTreeMap<_, _>>>()?;
+
+        let rt = tokio::runtime::Runtime::new().expect("could not start tokio rt");
+        let groups = group_by_chain(transactions);
+        let providers = rt.block_on(resolve_providers(groups.keys().copied(), fork_url, config))?;
+
+        let mut journal = BroadcastJournal {
+            chains: Vec::with_capacity(groups.len()),
+            timestamp: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)?
+                .as_secs(),
+        };
+        // the transactions that were sent before a failure are still written to the journal
+        let mut result = Ok(());
+        for (chain, transactions) in groups {
+            sh_println!("== Chain {} ==", chain);
+            let provider = &providers[&chain];
+            let mut broadcast = ChainBroadcast { chain_id: chain, ..Default::default() };
+            result = rt.block_on(self.send_all(&mut broadcast, provider, &wallets, &transactions));
+            for tx in &mut broadcast.transactions {
+                tx.contract_name = tx
+                    .init_code()
+                    .and_then(|init_code| identify_creation(creation_codes, init_code))
+                    .map(|(creation, _)| creation.contract.name.clone());
+            }
+            journal.chains.push(broadcast);
+            if result.is_err() {
+                break
+            }
+        }
+
+        let path = journal_path(&config.__root.0, script);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let content = serde_json::to_string_pretty(&journal)?;
+        // the latest journal is overwritten, the journals of all runs are kept
+        let run_path = path.with_file_name(format!("run-{}.json", journal.timestamp));
+        for path in [&path, &run_path] {
+            std::fs::write(path, &content)
+                .wrap_err_with(|| format!("failed to write {}", path.display()))?;
+        }
+        sh_status!("Broadcast journal written to {}", path.display());
+
+        let deployments = update_registry(config, script, &journal)?;
+        if deployments > 0 {
+            sh_status!(
+                "{} deployments written to the registry in {}",
+                deployments,
+                config.deployments.display()
+            );
+        }
+        result.wrap_err("the broadcast was aborted, the sent transactions are in the journal")?;
+        Ok(journal)
+    }
+
+    /// Verifies the contracts that were deployed by the broadcast of the `journal`.
+    ///
+    /// A creation is matched to the compiled contract whose creation code its init code starts
+    /// with, the rest of the init code are the constructor arguments. The project is compiled once
+    /// for all verifications. A failed verification doesn't stop the others, the failures are
+    /// summarized at the end.
+    pub fn verify(
+        &self,
+        journal: &BroadcastJournal,
+        creation_codes: &[CreationCode],
+        opts: &BuildArgs,
+    ) -> eyre::Result<()> {
+        let config = Config::try_from_args(opts)?.apply_chain_preset();
+        let compilation = VerifyCompilation::compile(&config)?;
+        let rt = tokio::runtime::Runtime::new().expect("could not start tokio rt");
+        let mut failures = Vec::new();
+        for chain in &journal.chains {
+            for (tx, receipt) in chain.transactions.iter().zip(&chain.receipts) {
+                let (address, init_code) = match tx.deployment(receipt) {
+                    Some(deployment) => deployment,
+                    None => continue,
+                };
+                let (creation, constructor_args) =
+                    match identify_creation(creation_codes, init_code) {
+                        Some(found) => found,
+                        None => {
+                            sh_warn!("Skipping verification of unknown contract at {:?}", address);
+                            continue
+                        }
+                    };
+
+                let contract = &creation.contract;
+                sh_println!(
+                    "Verifying {} at {:?} on chain {}",
+                    contract.name,
+                    address,
+                    chain.chain_id
+                );
+                let verified = compilation
+                    .submission(
+                        contract,
+                        address,
+                        chain.chain_id,
+                        hex::encode(constructor_args),
+                        &creation.libraries(init_code),
+                    )
+                    .and_then(|submission| {
+                        let explorer = explorer(
+                            &config,
+                            chain.chain_id,
+                            self.verifier.verifier,
+                            self.verifier.verifier_url.clone(),
+                            self.verifier.etherscan_api_key.clone(),
+                        )?;
+                        rt.block_on(verify_with_retries(explorer.as_ref(), &submission))
+                    });
+                if let Err(err) = verified {
+                    sh_warn!("Failed to verify {} at {:?}", contract.name, address);
+                    failures.push((&contract.name, address, chain.chain_id, err));
+                }
+            }
+        }
+
+        if failures.is_empty() {
+            return Ok(())
+        }
+        sh_println!("== Failed verifications ==");
+        for (name, address, chain, err) in &failures {
+            sh_println!("{} at {:?} on chain {}: {}", name, address, chain, err);
+        }
+        eyre::bail!("{} contracts could not be verified", failures.len())
+    }
+
+    /// Writes the `transactions` of every chain as a multisend batch of the Safe to
+    /// `broadcast/<script>/multisend-<chain>.json` in the project's root
+    pub fn write_multisend(
+        &self,
+        transactions: &[BroadcastableTransaction],
+        fork_url: Option<&str>,
+        config: &Config,
+        script: &Path,
+    ) -> eyre::Result<Vec<MultisendBatch>> {
+        let safe = self.safe.ok_or_else(|| eyre::eyre!("`--multisend` requires `--safe`"))?;
+        if let Some(tx) = transactions.iter().find(|tx| tx.from != safe) {
+            eyre::bail!(
+                "transaction from {:?} can't be batched, broadcast from the Safe {:?} instead",
+                tx.from,
+                safe
+            )
+        }
+        let groups = group_by_chain(transactions);
+
+        let rt = tokio::runtime::Runtime::new().expect("could not start tokio rt");
+        let providers = match self.safe_nonce {
+            Some(_) => BTreeMap::new(),
+            None => rt.block_on(resolve_providers(groups.keys().copied(), fork_url, config))?,
+        };
+
+        let dir = broadcast_dir(&config.__root.0, script);
+        std::fs::create_dir_all(&dir)?;
+        let mut batches = Vec::with_capacity(groups.len());
+        for (chain, transactions) in groups {
+            let nonce = match self.safe_nonce {
+                Some(nonce) => nonce,
+                None => {
+                    let call = TransactionRequest::new().to(safe).data(id("nonce()").to_vec());
+                    let nonce = rt.block_on(providers[&chain].call(&call.into(), None))?;
+                    U256::from_big_endian(&nonce)
+                }
+            };
+            let batch = MultisendBatch::new(
+                chain,
+                safe,
+                nonce,
+                transactions.into_iter().cloned().collect(),
+            )?;
+
+            let path = dir.join(format!("multisend-{}.json", chain));
+            std::fs::write(&path, serde_json::to_string_pretty(&batch)?)
+                .wrap_err_with(|| format!("failed to write {}", path.display()))?;
+            sh_println!("== Chain {} ==", chain);
+            sh_println!(
+                "Safe transaction hash: {:?}",
+                batch.safe_transaction.contract_transaction_hash
+            );
+            sh_status!("Multisend batch written to {}", path.display());
+            batches.push(batch);
+        }
+        Ok(batches)
+    }
+
+    /// Sends the transactions of a single chain, recording the sent transactions and their
+    /// receipts in the `broadcast` even if a later one fails.
+    ///
+    /// The nonces are tracked locally, so all transactions are sent without waiting for the
+    /// previous ones to be mined, unless `--slow` is set. If the node rejects a nonce, because
+    /// other transactions were sent from the same account meanwhile or an earlier transaction was
+    /// dropped, the transaction is resent with the pending nonce of the node. The broadcast is
+    /// aborted if a creation would use another nonce than in the simulation, since the contract
+    /// would be deployed to another address.
+    async fn send_all(
+        &self,
+        broadcast: &mut ChainBroadcast,
+        provider: &Provider<Http>,
+        wallets: &BTreeMap<Address, LocalWallet>,
+        transactions: &[&BroadcastableTransaction],
+    ) -> eyre::Result<()> {
+        let chain = broadcast.chain_id;
+        let legacy =
+            self.legacy || Chain::try_from(chain).map(|x| Chain::is_legacy(&x)).unwrap_or_default();
+        let mut nonces = NonceManager::default();
+
+        for (i, tx) in transactions.iter().enumerate() {
+            let mut request: TypedTransaction = if legacy {
+                TransactionRequest::new().chain_id(chain).into()
+            } else {
+                Eip1559TransactionRequest::new().chain_id(chain).into()
+            };
+            request.set_from(tx.from);
+            if let Some(to) = tx.to {
+                request.set_to(to);
+            }
+            request.set_value(tx.value);
+            request.set_data(tx.data.clone());
+            // later transactions may depend on earlier ones that aren't mined yet, which the
+            // node can't estimate, so the gas of the simulation is used
+            request.set_gas(tx.gas * self.gas_estimate_multiplier / 100);
+
+            let mut retries = 0;
+            let (nonce, hash) = loop {
+                let nonce = nonces.next(provider, tx.from).await?;
+                if tx.to.is_none() && nonce != tx.simulated_nonce {
+                    eyre::bail!(
+                        "the nonce of {:?} on chain {} is {}, but it was {} in the simulation, the contract would be deployed to another address",
+                        tx.from,
+                        chain,
+                        nonce,
+                        tx.simulated_nonce
+                    )
+                }
+                request.set_nonce(nonce);
+                let wallet = wallets.get(&tx.from);
+                match send_transaction(provider, wallet, chain, request.clone()).await {
+                    Ok(hash) => break (nonce, hash),
+                    Err(err) if retries < MAX_NONCE_RETRIES && is_nonce_error(&err) => {
+                        retries += 1;
+                        sh_warn!(
+                            "nonce {} of {:?} was rejected, retrying with the pending nonce",
+                            nonce,
+                            tx.from
+                        );
+                        nonces.reset(tx.from);
+                    }
+                    Err(err) if is_nonce_error(&err) => {
+                        return Err(err.wrap_err(format!(
+                            "nonce {} of {:?} was rejected, were other transactions sent from it?",
+                            nonce, tx.from
+                        )))
+                    }
+                    Err(err) => return Err(err),
+                }
+            };
+            sh_status!("Transaction: {:?}", hash);
+            broadcast.transactions.push(BroadcastedTransaction {
+                hash,
+                nonce,
+                transaction: (*tx).clone(),
+                contract_name: None,
+            });
+
+            if self.slow {
+                let receipt = wait_for_receipt(provider, hash).await?;
+                print_receipt(&receipt);
+                let success = ensure_success(&receipt);
+                broadcast.receipts.push(receipt);
+                success.wrap_err_with(|| {
+                    format!(
+                        "the remaining {} transactions on chain {} were not sent",
+                        transactions.len() - i - 1,
+                        chain
+                    )
+                })?;
+            }
+        }
+
+        if !self.slow {
+            // all receipts are collected, so the journal has the receipts of the transactions
+            // after a reverted one as well
+            let confirmations = shell::progress_bar(
+                broadcast.transactions.len() as u64,
+                "waiting for confirmations",
+            );
+            let mut result = Ok(());
+            for tx in &broadcast.transactions {
+                let receipt = wait_for_receipt(provider, tx.hash).await;
+                confirmations.inc(1);
+                let receipt = match receipt {
+                    Ok(receipt) => receipt,
+                    Err(err) => {
+                        result = result.and(Err(err));
+                        break
+                    }
+                };
+                print_receipt(&receipt);
+                result = result.and_then(|_| ensure_success(&receipt));
+                broadcast.receipts.push(receipt);
+            }
+            confirmations.finish_and_clear();
+            result?;
+        }
+        Ok(())
+    }
+}
+
+/// How often a transaction whose nonce was rejected is resent with the pending nonce of the node
+const MAX_NONCE_RETRIES: usize = 3;
+
+/// Tracks the next nonce of every sender on a chain, starting at its pending transaction count
+#[derive(Debug, Default)]
+struct NonceManager {
+    nonces: BTreeMap<Address, U256>,
+}
+
+impl NonceManager {
+    /// Returns the next nonce of the sender and reserves it
+    async fn next(&mut self, provider: &Provider<Http>, from: Address) -> eyre::Result<U256> {
+        let nonce = match self.nonces.get(&from) {
+            Some(nonce) => *nonce,
+            None => provider.get_transaction_count(from, Some(BlockNumber::Pending.into())).await?,
+        };
+        self.nonces.insert(from, nonce + 1);
+        Ok(nonce)
+    }
+
+    /// Forgets the next nonce of the sender, so it's read from the node again
+    fn reset(&mut self, from: Address) {
+        self.nonces.remove(&from);
+    }
+}
+
+/// Whether the node rejected the transaction because of its nonce
+fn is_nonce_error(err: &eyre::Report) -> bool {
+    let err = err.to_string().to_lowercase();
+    ["nonce too low", "nonce too high", "nonce gap", "invalid nonce", "nonce has already been used"]
+        .iter()
+        .any(|msg| err.contains(msg))
+}
+
+/// Signs the transaction with the wallet, or else sends it with `eth_sendTransaction`
+async fn send_transaction(
+    provider: &Provider<Http>,
+    wallet: Option<&LocalWallet>,
+    chain: u64,
+    request: TypedTransaction,
+) -> eyre::Result<H256> {
+    Ok(match wallet {
+        Some(wallet) => {
+            let signer =
+                SignerMiddleware::new(provider.clone(), wallet.clone().with_chain_id(chain));
+            let pending = signer.send_transaction(request, None).await?;
+            *pending
+        }
+        None => *provider.send_transaction(request, None).await?,
+    })
+}
+
+/// Waits for the receipt of the transaction, fails if it was dropped
+async fn wait_for_receipt(
+    provider: &Provider<Http>,
+    hash: H256,
+) -> eyre::Result<TransactionReceipt> {
+    PendingTransaction::new(hash, provider)
+        .await?
+        .ok_or_else(|| eyre::eyre!("transaction {:?} was dropped", hash))
+}
+
+/// Fails if the transaction of the receipt reverted
+fn ensure_success(receipt: &TransactionReceipt) -> eyre::Result<()> {
+    if receipt.status != Some(1u64.into()) {
+        eyre::bail!("transaction {:?} reverted", receipt.transaction_hash)
+    }
+    Ok(())
+}
+
+/// Prints the address of the contract the transaction of the receipt deployed
+fn print_receipt(receipt: &TransactionReceipt) {
+    if let Some(address) = receipt.contract_address {
+        sh_println!("Deployed to: {:?}", address);
+    }
+}
+
+/// How often a verification is retried while the explorer hasn't indexed the contract yet
+const MAX_VERIFY_RETRIES: usize = 10;
+
+/// Submits the verification, explorers only accept it after they indexed the deployment
+async fn verify_with_retries(
+    explorer: &dyn Explorer,
+    submission: &VerifySubmission,
+) -> eyre::Result<()> {
+    let mut retries = 0;
+    loop {
+        match explorer.verify(submission, true).await {
+            Err(err) if retries < MAX_VERIFY_RETRIES && is_not_indexed_error(&err) => {
+                retries += 1;
+                sh_status!("Contract is not indexed yet, retrying in 10 seconds.");
+                tokio::time::sleep(Duration::from_secs(10)).await;
+            }
+            res => return res,
+        }
+    }
+}
+
+/// Whether the verification failed because the explorer doesn't know the contract's code yet
+fn is_not_indexed_error(err: &eyre::Report) -> bool {
+    let err = err.to_string().to_lowercase();
+    ["unable to locate contractcode", "does not have bytecode", "no deployed bytecode"]
+        .iter()
+        .any(|msg| err.contains(msg))
+}
+
+/// The creation code of a compiled contract, with zeros in place of the addresses of the
+/// libraries it links
+#[derive(Debug, Clone)]
+pub struct CreationCode {
+    pub contract: FullContractInfo,
+    pub code: Vec<u8>,
+    /// Every library the code links and the offsets of its addresses in the code
+    pub links: Vec<(FullContractInfo, Vec<usize>)>,
+}
+
+impl CreationCode {
+    /// Returns the creation code of the compiled contract, none if it has no code, like interfaces
+    pub fn new(contract: FullContractInfo, bytecode: &Bytecode) -> Option<Self> {
+        let mut links = Vec::new();
+        for (path, libraries) in &bytecode.link_references {
+            for (name, offsets) in libraries {
+                let library = FullContractInfo { path: path.clone(), name: name.clone() };
+                links.push((library, offsets.iter().map(|offset| offset.start as usize).collect()));
+            }
+        }
+        let code = match &bytecode.object {
+            BytecodeObject::Bytecode(code) => code.to_vec(),
+            BytecodeObject::Unlinked(code) => {
+                // the placeholders of the addresses aren't hex
+                let mut code = code.trim_start_matches("0x").to_string();
+                for (_, offsets) in &links {
+                    for offset in offsets {
+                        let placeholder = offset * 2..(offset + Address::len_bytes()) * 2;
+                        code.get(placeholder.clone())?;
+                        code.replace_range(placeholder, &"00".repeat(Address::len_bytes()));
+                    }
+                }
+                hex::decode(code).ok()?
+            }
+        };
+        (!code.is_empty()).then(|| Self { contract, code, links })
+    }
+
+    /// Whether the `init_code` starts with the creation code, with any addresses of the libraries
+    fn matches(&self, init_code: &[u8]) -> bool {
+        if init_code.len() < self.code.len() {
+            return false
+        }
+        let mut init_code = init_code[..self.code.len()].to_vec();
+        for offset in self.links.iter().flat_map(|(_, offsets)| offsets) {
+            if let Some(address) = init_code.get_mut(*offset..*offset + Address::len_bytes()) {
+                address.fill(0);
+            }
+        }
+        init_code == self.code
+    }
+
+    /// Returns the addresses of the libraries linked into the `init_code` of the contract, by
+    /// their source file and name
+    pub fn libraries(&self, init_code: &[u8]) -> BTreeMap<String, BTreeMap<String, Address>> {
+        let mut libraries: BTreeMap<String, BTreeMap<String, Address>> = BTreeMap::new();
+        for (library, offsets) in &self.links {
+            let address = offsets
+                .first()
+                .and_then(|offset| init_code.get(*offset..*offset + Address::len_bytes()));
+            if let Some(address) = address {
+                let address = Address::from_slice(address);
+                libraries
+                    .entry(library.path.clone())
+                    .or_default()
+                    .insert(library.name.clone(), address);
+            }
+        }
+        libraries
+    }
+}
+
+/// Returns the contract with the longest creation code the `init_code` starts with, and the
+/// constructor arguments that follow it
+fn identify_creation<'a, 'b>(
+    creation_codes: &'a [CreationCode],
+    init_code: &'b [u8],
+) -> Option<(&'a CreationCode, &'b [u8])> {
+    creation_codes
+        .iter()
+        .filter(|creation| creation.matches(init_code))
+        .max_by_key(|creation| creation.code.len())
+        .map(|creation| (creation, &init_code[creation.code.len()..]))
+}
+
+/// Groups the transactions by chain id, keeping their order within every chain
+fn group_by_chain(
+    transactions: &[BroadcastableTransaction],
+) -> BTreeMap<u64, Vec<&BroadcastableTransaction>> {
+    let mut groups: BTreeMap<u64, Vec<_>> = BTreeMap::new();
+    for tx in transactions {
+        groups.entry(tx.chain_id).or_default().push(tx);
+    }
+    groups
+}
+
+/// Returns a provider for every chain, the `fork_url` is used for its own chain and the config's
+/// `rpc_endpoints` for the others
+async fn resolve_providers(
+    chains: impl Iterator<Item = u64>,
+    fork_url: Option<&str>,
+    config: &Config,
+) -> eyre::Result<BTreeMap<u64, Provider<Http>>> {
+    let mut missing = chains.collect::<Vec<_>>();
+    let mut providers = BTreeMap::new();
+    let urls = fork_url
+        .map(str::to_string)
+        .into_iter()
+        .chain(config.resolved_rpc_endpoints().into_values());
+    for url in urls {
+        if missing.is_empty() {
+            break
+        }
+        let provider = Provider::<Http>::try_from(url.as_str())?;
+        let chain = match provider.get_chainid().await {
+            Ok(chain) => chain.as_u64(),
+            Err(err) => {
+                tracing::warn!(?err, %url, "failed to get the chain id");
+                continue
+            }
+        };
+        if let Some(pos) = missing.iter().position(|c| *c == chain) {
+            missing.remove(pos);
+            providers.insert(chain, provider);
+        }
+    }
+    if !missing.is_empty() {
+        eyre::bail!(
+            "no rpc url for chain {:?}, pass `--fork-url` or add an endpoint to `rpc_endpoints`",
+            missing
+        )
+    }
+    Ok(providers)
+}
+
+/// Returns the directory the broadcasts of the script are written to
+pub(crate) fn broadcast_dir(root: &Path, script: &Path) -> PathBuf {
+    let name = script.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    root.join("broadcast").join(name)
+}
+
+/// Returns the path of the journal of the latest broadcast of the script
+fn journal_path(root: &Path, script: &Path) -> PathBuf {
+    broadcast_dir(root, script).join("run-latest.json")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_group_transactions_by_chain() {
+        let tx = |chain_id, from| BroadcastableTransaction {
+            chain_id,
+            from: Address::from_low_u64_be(from),
+            to: None,
+            value: U256::zero(),
+            gas: U256::zero(),
+            data: Default::default(),
+            simulated_nonce: U256::zero(),
+        };
+        let txs = vec![tx(1, 1), tx(10, 2), tx(1, 3)];
+        let groups = group_by_chain(&txs);
+        assert_eq!(groups.keys().copied().collect::<Vec<_>>(), vec![1, 10]);
+        assert_eq!(groups[&1], vec![&txs[0], &txs[2]]);
+        assert_eq!(groups[&10], vec![&txs[1]]);
+
+        assert_eq!(
+            journal_path(Path::new("/project"), Path::new("script/Deploy.s.sol")),
+            PathBuf::from("/project/broadcast/Deploy.s/run-latest.json")
+        );
+    }
+
+    #[test]
+    fn can_identify_creations() {
+        let info = |name: &str| FullContractInfo { path: "src/A.sol".into(), name: name.into() };
+        let code = |name: &str, code: Vec<u8>| CreationCode {
+            contract: info(name),
+            code,
+            links: Vec::new(),
+        };
+        let codes = vec![code("A", vec![1, 2]), code("B", vec![1, 2, 3]), code("C", vec![4])];
+
+        let (creation, args) = identify_creation(&codes, &[1, 2, 3, 9, 9]).unwrap();
+        assert_eq!(creation.contract.name, "B");
+        assert_eq!(args, &[9, 9]);
+        let (creation, args) = identify_creation(&codes, &[1, 2]).unwrap();
+        assert_eq!(creation.contract.name, "A");
+        assert!(args.is_empty());
+        assert!(identify_creation(&codes, &[5]).is_none());
+    }
+
+    #[test]
+    fn can_read_linked_libraries() {
+        let bytecode: Bytecode = serde_json::from_value(serde_json::json!({
+            "object": "6001__$0123456789abcdef0123456789abcdef01$__6002",
+            "opcodes": "",
+            "sourceMap": "",
+            "linkReferences": {
+                "src/Math.sol": { "Math": [{ "start": 2, "length": 20 }] }
+            }
+        }))
+        .unwrap();
+        let info = FullContractInfo { path: "src/Vault.sol".into(), name: "Vault".into() };
+        let creation = CreationCode::new(info, &bytecode).unwrap();
+        assert_eq!(creation.code.len(), 24);
+
+        let math = Address::repeat_byte(0x11);
+        let mut init_code = vec![0x60, 0x01];
+        init_code.extend(math.as_bytes());
+        init_code.extend([0x60, 0x02, 0xaa]);
+        let codes = vec![creation];
+        let (creation, args) = identify_creation(&codes, &init_code).unwrap();
+        assert_eq!(args, &[0xaa]);
+        let libraries = creation.libraries(&init_code);
+        assert_eq!(libraries["src/Math.sol"]["Math"], math);
+    }
+
+    #[test]
+    fn can_detect_nonce_errors() {
+        let err = eyre::eyre!("(code: -32000, message: nonce too low, data: None)");
+        assert!(is_nonce_error(&err));
+        let err = eyre::eyre!("(code: -32000, message: Nonce too high, data: None)");
+        assert!(is_nonce_error(&err));
+        let err = eyre::eyre!("(code: -32000, message: insufficient funds for gas, data: None)");
+        assert!(!is_nonce_error(&err));
+    }
+}
@@ -0,0 +1,419 @@
+//! broadcast-log command
+
+use crate::{
+    cmd::{
+        broadcast::{broadcast_dir, BroadcastJournal},
+        build::CompiledArtifact,
+        Cmd,
+    },
+    shell::{self, sh_println, sh_status},
+};
+use clap::{Parser, Subcommand, ValueHint};
+use ethers::types::{Address, H256, U64};
+use eyre::WrapErr;
+use foundry_config::{find_project_root_path, Config};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+/// Command to query the journals `forge run --broadcast` writes to `broadcast/<script>`
+#[derive(Debug, Clone, Parser)]
+pub struct BroadcastLogArgs {
+    #[clap(subcommand)]
+    pub sub: BroadcastLogSubcommands,
+
+    #[clap(
+        help = "the project's root path, default being the nearest directory with a foundry.toml or the git root",
+        long,
+        global = true,
+        value_hint = ValueHint::DirPath
+    )]
+    pub root: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum BroadcastLogSubcommands {
+    #[clap(about = "lists the broadcasts of every script, oldest first")]
+    List {
+        #[clap(help = "only list the broadcasts of this script, like `script/Deploy.s.sol`")]
+        script: Option<PathBuf>,
+        #[clap(help = "only list the broadcasts to this chain id", long)]
+        chain_id: Option<u64>,
+    },
+    #[clap(about = "shows the transactions, receipts and deployed contracts of a broadcast")]
+    Show {
+        #[clap(help = "the script, like `script/Deploy.s.sol`")]
+        script: PathBuf,
+        #[clap(help = "the timestamp of the broadcast, the latest broadcast if not set", long)]
+        run: Option<u64>,
+        #[clap(help = "only show the transactions of this chain id", long)]
+        chain_id: Option<u64>,
+    },
+    #[clap(
+        about = "writes the contracts deployed by all broadcasts to `<out>/<chain id>/<contract>.json`, the latest deployment of a contract on a chain wins"
+    )]
+    Export {
+        #[clap(
+            help = "the directory of the registry, relative to the root, default being the `deployments` of the config",
+            long,
+            value_hint = ValueHint::DirPath
+        )]
+        out: Option<PathBuf>,
+        #[clap(help = "only export the deployments on this chain id", long)]
+        chain_id: Option<u64>,
+    },
+}
+
+impl Cmd for BroadcastLogArgs {
+    type Output = ();
+
+    fn run(self) -> eyre::Result<Self::Output> {
+        let root = match self.root {
+            Some(root) => root,
+            None => find_project_root_path()?,
+        };
+        match self.sub {
+            BroadcastLogSubcommands::List { script, chain_id } => {
+                let runs = BroadcastRun::read_all(&root, script.as_deref())?
+                    .into_iter()
+                    .filter(|run| chain_id.map_or(true, |id| run.has_chain(id)))
+                    .collect::<Vec<_>>();
+                if shell::is_json() {
+                    let summaries = runs.iter().map(RunSummary::new).collect::<Vec<_>>();
+                    return shell::print_json(&summaries)
+                }
+                for run in &runs {
+                    sh_println!("{} {}", run.script, run.journal.timestamp);
+                    for chain in &run.journal.chains {
+                        let deployments = chain
+                            .transactions
+                            .iter()
+                            .zip(&chain.receipts)
+                            .filter(|(tx, receipt)| tx.deployment(receipt).is_some())
+                            .count();
+                        sh_println!(
+                            "  chain {}: {} transactions, {} deployments",
+                            chain.chain_id,
+                            chain.transactions.len(),
+                            deployments
+                        );
+                    }
+                }
+                sh_status!("{} broadcasts", runs.len());
+            }
+            BroadcastLogSubcommands::Show { script, run, chain_id } => {
+                let runs = BroadcastRun::read_all(&root, Some(&script))?;
+                let run = match run {
+                    Some(timestamp) => runs.iter().find(|run| run.journal.timestamp == timestamp),
+                    None => runs.last(),
+                }
+                .ok_or_else(|| eyre::eyre!("no broadcast of {} found", script.display()))?;
+                let mut journal = run.journal.clone();
+                journal.chains.retain(|chain| chain_id.map_or(true, |id| chain.chain_id == id));
+                if shell::is_json() {
+                    return shell::print_json(&journal)
+                }
+                print_journal(&journal);
+            }
+            BroadcastLogSubcommands::Export { out, chain_id } => {
+                let config = Config::try_load_with_root(&root)?.sanitized();
+                let runs = BroadcastRun::read_all(&root, None)?;
+                let mut deployments = registry(&runs, chain_id);
+                let out =
+                    out.map(|out| root.join(out)).unwrap_or_else(|| config.deployments.clone());
+                write_registry(&config, &out, &mut deployments)?;
+                sh_status!("exported {} deployments to {}", deployments.len(), out.display());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The journal of a single broadcast of a script
+#[derive(Debug, Clone)]
+struct BroadcastRun {
+    /// The name of the script's broadcast directory, like `Deploy.s`
+    script: String,
+    journal: BroadcastJournal,
+}
+
+impl BroadcastRun {
+    /// Reads the journals of all broadcasts, or only of the `script`, sorted by their timestamp
+    ///
+    /// Journals written before every run was kept only have their `run-latest.json`.
+    fn read_all(root: &Path, script: Option<&Path>) -> eyre::Result<Vec<Self>> {
+        let dirs = match script {
+            Some(script) => vec![broadcast_dir(root, script)],
+            None => match std::fs::read_dir(root.join("broadcast")) {
+                Ok(dir) => dir.filter_map(Result::ok).map(|entry| entry.path()).collect(),
+                Err(_) => Vec::new(),
+            },
+        };
+
+        let mut runs = Vec::new();
+        for dir in dirs.into_iter().filter(|dir| dir.is_dir()) {
+            let script = dir.file_name().unwrap_or_default().to_string_lossy().to_string();
+            let mut files = std::fs::read_dir(&dir)?
+                .filter_map(Result::ok)
+                .map(|entry| entry.path())
+                .filter(|path| {
+                    let name = path.file_name().unwrap_or_default().to_string_lossy();
+                    name.starts_with("run-") && name.ends_with(".json") && name != "run-latest.json"
+                })
+                .collect::<Vec<_>>();
+            if files.is_empty() && dir.join("run-latest.json").is_file() {
+                files.push(dir.join("run-latest.json"));
+            }
+            for file in files {
+                let journal = serde_json::from_str(&std::fs::read_to_string(&file)?)
+                    .map_err(|err| eyre::eyre!("invalid journal {}: {}", file.display(), err))?;
+                runs.push(BroadcastRun { script: script.clone(), journal });
+            }
+        }
+        runs.sort_by(|a, b| {
+            (a.journal.timestamp, &a.script).cmp(&(b.journal.timestamp, &b.script))
+        });
+        Ok(runs)
+    }
+
+    fn has_chain(&self, chain_id: u64) -> bool {
+        self.journal.chains.iter().any(|chain| chain.chain_id == chain_id)
+    }
+}
+
+/// The json output of `broadcast-log list`
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RunSummary<'a> {
+    script: &'a str,
+    timestamp: u64,
+    chain_ids: Vec<u64>,
+    transactions: usize,
+}
+
+impl<'a> RunSummary<'a> {
+    fn new(run: &'a BroadcastRun) -> Self {
+        RunSummary {
+            script: &run.script,
+            timestamp: run.journal.timestamp,
+            chain_ids: run.journal.chains.iter().map(|chain| chain.chain_id).collect(),
+            transactions: run.journal.chains.iter().map(|chain| chain.transactions.len()).sum(),
+        }
+    }
+}
+
+/// A contract deployed by a broadcast, as written to the deployments registry
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Deployment {
+    pub address: Address,
+    pub transaction_hash: H256,
+    pub block_number: Option<U64>,
+    /// Unix timestamp of the broadcast
+    pub timestamp: u64,
+    /// The name of the script's broadcast directory, like `Deploy.s`
+    pub script: String,
+    /// The abi of the contract, if it's compiled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub abi: Option<serde_json::Value>,
+}
+
+/// Adds the contracts deployed by the broadcast of the `script` to the `deployments` registry of
+/// the config and returns the number of contracts that were added
+pub(crate) fn update_registry(
+    config: &Config,
+    script: &Path,
+    journal: &BroadcastJournal,
+) -> eyre::Result<usize> {
+    let script = broadcast_dir(&config.__root.0, script)
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string();
+    let run = BroadcastRun { script, journal: journal.clone() };
+    let mut deployments = registry(&[run], None);
+    write_registry(config, &config.deployments, &mut deployments)?;
+    Ok(deployments.len())
+}
+
+/// Writes the `deployments` to `<dir>/<chain id>/<contract>.json`, with the abis of the compiled
+/// contracts
+fn write_registry(
+    config: &Config,
+    dir: &Path,
+    deployments: &mut BTreeMap<(u64, String), Deployment>,
+) -> eyre::Result<()> {
+    // frontends need the abi, which is only known for contracts that are compiled
+    let project = config.project()?;
+    if project.paths.artifacts.exists() {
+        let abis = CompiledArtifact::read_all(&project)?
+            .into_iter()
+            .filter_map(|compiled| Some((compiled.name, compiled.artifact.get("abi")?.clone())))
+            .collect::<BTreeMap<_, _>>();
+        for ((_, name), deployment) in deployments.iter_mut() {
+            deployment.abi = abis.get(name).cloned();
+        }
+    }
+
+    for ((chain, name), deployment) in deployments.iter() {
+        let chain_dir = dir.join(chain.to_string());
+        std::fs::create_dir_all(&chain_dir)?;
+        let path = chain_dir.join(format!("{}.json", name));
+        std::fs::write(&path, serde_json::to_string_pretty(deployment)?)
+            .wrap_err_with(|| format!("failed to write {}", path.display()))?;
+    }
+    Ok(())
+}
+
+/// Returns the latest deployment of every named contract by chain id and name, optionally only of
+/// the chain `chain_id`
+///
+/// The `runs` must be sorted by their timestamp.
+fn registry(runs: &[BroadcastRun], chain_id: Option<u64>) -> BTreeMap<(u64, String), Deployment> {
+    let mut deployments = BTreeMap::new();
+    for run in runs {
+        for chain in &run.journal.chains {
+            if chain_id.map_or(false, |id| id != chain.chain_id) {
+                continue
+            }
+            for (tx, receipt) in chain.transactions.iter().zip(&chain.receipts) {
+                let (name, (address, _)) = match (&tx.contract_name, tx.deployment(receipt)) {
+                    (Some(name), Some(deployment)) => (name, deployment),
+                    _ => continue,
+                };
+                deployments.insert(
+                    (chain.chain_id, name.clone()),
+                    Deployment {
+                        address,
+                        transaction_hash: tx.hash,
+                        block_number: receipt.block_number,
+                        timestamp: run.journal.timestamp,
+                        script: run.script.clone(),
+                        abi: None,
+                    },
+                );
+            }
+        }
+    }
+    deployments
+}
+
+/// Prints every transaction of the journal with its receipt and the contract it deployed
+fn print_journal(journal: &BroadcastJournal) {
+    for chain in &journal.chains {
+        sh_println!("== Chain {} ==", chain.chain_id);
+        for (idx, tx) in chain.transactions.iter().enumerate() {
+            let to =
+                tx.transaction.to.map_or_else(|| "create".to_string(), |to| format!("{:?}", to));
+            sh_println!("{:?}", tx.hash);
+            sh_println!("  from {:?} to {} nonce {}", tx.transaction.from, to, tx.nonce);
+            let receipt = match chain.receipts.get(idx) {
+                Some(receipt) => receipt,
+                None => {
+                    sh_println!("  no receipt");
+                    continue
+                }
+            };
+            let status = if receipt.status == Some(1u64.into()) { "success" } else { "reverted" };
+            sh_println!(
+                "  {} in block {} using {} gas",
+                status,
+                receipt.block_number.unwrap_or_default(),
+                receipt.gas_used.unwrap_or_default()
+            );
+            if let Some((address, _)) = tx.deployment(receipt) {
+                let name = tx.contract_name.as_deref().unwrap_or("unknown contract");
+                sh_println!("  deployed {} at {:?}", name, address);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::broadcast::{BroadcastedTransaction, ChainBroadcast};
+    use ethers::types::{TransactionReceipt, U256};
+    use evm_adapters::sputnik::cheatcodes::BroadcastableTransaction;
+
+    fn deployment(name: &str, address: u64) -> (BroadcastedTransaction, TransactionReceipt) {
+        let tx = BroadcastedTransaction {
+            hash: H256::from_low_u64_be(address),
+            nonce: U256::zero(),
+            transaction: BroadcastableTransaction {
+                chain_id: 1,
+                from: Address::from_low_u64_be(1),
+                to: None,
+                value: U256::zero(),
+                gas: U256::zero(),
+                data: vec![0x60, 0x80].into(),
+                simulated_nonce: U256::zero(),
+            },
+            contract_name: Some(name.to_string()),
+        };
+        let receipt = TransactionReceipt {
+            status: Some(1u64.into()),
+            contract_address: Some(Address::from_low_u64_be(address)),
+            ..Default::default()
+        };
+        (tx, receipt)
+    }
+
+    fn run(timestamp: u64, chain_id: u64, deployments: &[(&str, u64)]) -> BroadcastRun {
+        let (transactions, receipts) =
+            deployments.iter().map(|(name, address)| deployment(name, *address)).unzip();
+        BroadcastRun {
+            script: "Deploy.s".to_string(),
+            journal: BroadcastJournal {
+                chains: vec![ChainBroadcast { chain_id, transactions, receipts }],
+                timestamp,
+            },
+        }
+    }
+
+    #[test]
+    fn latest_deployment_wins() {
+        let runs = vec![
+            run(1, 1, &[("Token", 10), ("Vault", 11)]),
+            run(2, 1, &[("Token", 20)]),
+            run(3, 5, &[("Token", 30)]),
+        ];
+        let deployments = registry(&runs, None);
+        assert_eq!(deployments.len(), 3);
+        let token = &deployments[&(1, "Token".to_string())];
+        assert_eq!(token.address, Address::from_low_u64_be(20));
+        assert_eq!(token.timestamp, 2);
+        assert_eq!(deployments[&(1, "Vault".to_string())].address, Address::from_low_u64_be(11));
+
+        let deployments = registry(&runs, Some(5));
+        assert_eq!(deployments.keys().collect::<Vec<_>>(), vec![&(5, "Token".to_string())]);
+    }
+
+    #[test]
+    fn reads_journals_of_all_runs() {
+        let root = std::env::temp_dir().join("foundry-broadcast-log-test");
+        let _ = std::fs::remove_dir_all(&root);
+        let write = |script: &str, file: &str, run: &BroadcastRun| {
+            let dir = root.join("broadcast").join(script);
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(dir.join(file), serde_json::to_string(&run.journal).unwrap()).unwrap();
+        };
+        // the latest journal is a copy of the journal of the latest run
+        write("Deploy.s", "run-2.json", &run(2, 1, &[("Token", 20)]));
+        write("Deploy.s", "run-1.json", &run(1, 1, &[("Token", 10)]));
+        write("Deploy.s", "run-latest.json", &run(2, 1, &[("Token", 20)]));
+        // written before every run was kept
+        write("Upgrade.s", "run-latest.json", &run(3, 1, &[("Vault", 30)]));
+
+        let runs = BroadcastRun::read_all(&root, None).unwrap();
+        let runs =
+            runs.iter().map(|run| (run.script.as_str(), run.journal.timestamp)).collect::<Vec<_>>();
+        assert_eq!(runs, vec![("Deploy.s", 1), ("Deploy.s", 2), ("Upgrade.s", 3)]);
+
+        let runs = BroadcastRun::read_all(&root, Some(Path::new("script/Upgrade.s.sol"))).unwrap();
+        assert_eq!(runs.len(), 1);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -1,10 +1,29 @@
 //! build command
 
-use ethers::solc::{MinimalCombinedArtifacts, Project, ProjectCompileOutput};
-use std::path::PathBuf;
+use ethers::{
+    solc::{
+        artifacts::{CompilerOutput, Error as SolcError, Severity},
+        cache::SolFilesCache,
+        MinimalCombinedArtifacts, Project, ProjectCompileOutput,
+    },
+    utils::keccak256,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    ffi::OsStr,
+    path::{Path, PathBuf},
+};
 
-use crate::{cmd::Cmd, opts::forge::CompilerArgs};
+use crate::{
+    cmd::{bind::Bindings, Cmd},
+    exit_code::ExitCode,
+    hooks::{self, Hook},
+    ipfs::publish_metadata,
+    opts::forge::CompilerArgs,
+    shell::{self, sh_println, sh_status},
+};
 
+use ansi_term::Colour;
 use clap::{Parser, ValueHint};
 use ethers::solc::remappings::Remapping;
 use foundry_config::{
@@ -14,9 +33,10 @@ use foundry_config::{
         value::{Dict, Map, Value},
         Figment, Metadata, Profile, Provider,
     },
-    find_project_root_path, remappings_from_env_var, Config,
+    find_project_root_path, remappings_from_env_var, Config, ExtraOutput,
 };
-use serde::Serialize;
+use foundry_utils::{decode_artifact_code, metadata::SolcMetadata, HardhatArtifact};
+use serde::{Deserialize, Serialize};
 
 // Loads project's figment and merges the build cli arguments into it
 impl<'a> From<&'a BuildArgs> for Figment {
@@ -37,13 +57,6 @@ impl<'a> From<&'a BuildArgs> for Figment {
     }
 }
 
-impl<'a> From<&'a BuildArgs> for Config {
-    fn from(args: &'a BuildArgs) -> Self {
-        let figment: Figment = args.into();
-        Config::from_provider(figment).sanitized()
-    }
-}
-
 /// All `forge build` related arguments
 ///
 /// CLI arguments take the highest precedence in the Config/Figment hierarchy.
@@ -54,8 +67,9 @@ impl<'a> From<&'a BuildArgs> for Config {
 ///
 /// ```ignore
 /// use foundry_config::Config;
-/// # fn t(args: BuildArgs) {
-/// let config = Config::from(&args);
+/// # fn t(args: BuildArgs) -> Result<(), figment::Error> {
+/// let config = Config::try_from_args(&args)?;
+/// # Ok(())
 /// # }
 /// ```
 ///
@@ -109,6 +123,29 @@ pub struct BuildArgs {
     #[serde(rename = "out", skip_serializing_if = "Option::is_none")]
     pub out_path: Option<PathBuf>,
 
+    #[clap(
+        help = "the directory the compiler cache is written to",
+        long,
+        value_hint = ValueHint::DirPath
+    )]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_path: Option<PathBuf>,
+
+    #[clap(
+        help = "write Hardhat style build info files with the standard json input and output of every compiler version to `<out>/build-info`",
+        long
+    )]
+    #[serde(skip)]
+    pub build_info: bool,
+
+    #[clap(
+        help = "the directory the build info files are written to, implies `--build-info`",
+        long,
+        value_hint = ValueHint::DirPath
+    )]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub build_info_path: Option<PathBuf>,
+
     #[clap(flatten)]
     #[serde(flatten)]
     pub compiler: CompilerArgs,
@@ -117,6 +154,26 @@ pub struct BuildArgs {
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub ignored_error_codes: Vec<u64>,
 
+    #[clap(
+        help = "ignore all warnings of the sources under the path, like `lib`",
+        long,
+        value_hint = ValueHint::AnyPath
+    )]
+    #[serde(skip)]
+    pub ignored_warnings_from: Vec<PathBuf>,
+
+    #[clap(help = "fail the build on the compiler's warnings that aren't ignored", long)]
+    #[serde(skip)]
+    pub deny_warnings: bool,
+
+    #[clap(
+        help = "the outputs of solc to write next to the artifacts, like `ir` or `storageLayout`",
+        long,
+        possible_values = ExtraOutput::NAMES
+    )]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub extra_output: Vec<ExtraOutput>,
+
     #[clap(
         help = "if set to true, skips auto-detecting solc and uses what is in the user's $PATH ",
         long
@@ -132,7 +189,7 @@ pub struct BuildArgs {
     pub force: bool,
 
     #[clap(
-        help = "uses hardhat style project layout. This a convenience flag and is the same as `--contracts contracts --lib-paths node_modules`",
+        help = "uses hardhat style project layout and writes hardhat compatible artifacts to `artifacts`, which must not be the `out` directory. This a convenience flag and is the same as `--contracts contracts --lib-paths node_modules`",
         long,
         conflicts_with = "contracts",
         alias = "hh"
@@ -142,14 +199,563 @@ pub struct BuildArgs {
 
     #[clap(help = "add linked libraries", long, env = "DAPP_LIBRARIES")]
     pub libraries: Vec<String>,
+
+    #[clap(
+        help = "print the size of the runtime bytecode of every contract and its margin to the EIP-170 limit",
+        long
+    )]
+    #[serde(skip)]
+    pub sizes: bool,
+
+    #[clap(help = "fail if a contract exceeds the EIP-170 size limit, implies `--sizes`", long)]
+    #[serde(skip)]
+    pub fail_on_oversize: bool,
+
+    #[clap(
+        help = "write the metadata of the contracts next to their artifacts, to `<out>/<file>/<contract>.metadata.json`",
+        long
+    )]
+    #[serde(skip)]
+    pub extract_metadata: bool,
+
+    #[clap(
+        help = "pin the metadata of the contracts and their sources to the IPFS api of `ipfs_api_url` and print the metadata hashes, implies `--extract-metadata`",
+        long
+    )]
+    #[serde(skip)]
+    pub pin_metadata: bool,
+
+    #[clap(
+        help = "forbid all network access: forks are served from the fork cache of previous runs, solc must be installed and explorer lookups are skipped",
+        long
+    )]
+    #[serde(skip)]
+    pub offline: bool,
 }
 
 impl Cmd for BuildArgs {
     type Output = ProjectCompileOutput<MinimalCombinedArtifacts>;
     fn run(self) -> eyre::Result<Self::Output> {
-        let project = self.project()?;
-        super::compile(&project)
+        let config = Config::try_from_args(&self)?;
+        hooks::run_pre(&config, Hook::PreBuild)?;
+        let output = self.build(&config);
+        hooks::run_post(&config, Hook::PostBuild, output.is_ok());
+        output
+    }
+}
+
+/// The maximum size of the runtime bytecode of a contract, see EIP-170
+pub const CONTRACT_SIZE_LIMIT: usize = 24576;
+
+/// The directory in `<out>` the build info files are written to by default
+pub const BUILD_INFO_DIR: &str = "build-info";
+
+/// The format of Hardhat's build info files
+pub const BUILD_INFO_FORMAT: &str = "hh-sol-build-info-1";
+
+/// An artifact forge wrote to `<out>/<source file name>/<Name>.json`
+pub(crate) struct CompiledArtifact {
+    /// The source file of the contract
+    pub(crate) source: PathBuf,
+    pub(crate) name: String,
+    pub(crate) artifact: serde_json::Value,
+}
+
+impl CompiledArtifact {
+    /// Reads the artifacts of the project's current sources, the ones of its last compilation as
+    /// listed in the cache and the `.yul` and `.vy` sources
+    ///
+    /// Artifacts that are left in the output directory by removed sources or contracts are
+    /// skipped. Without a cache every artifact next to the current sources is read.
+    pub(crate) fn read_all(project: &Project) -> eyre::Result<Vec<Self>> {
+        // the source files and the names of their artifacts, if they're known
+        let mut sources: Vec<(PathBuf, Option<Vec<String>>)> = Vec::new();
+        if project.paths.cache.exists() {
+            let cache = SolFilesCache::read(&project.paths.cache)?;
+            let cached = cache.files.into_iter().map(|(file, entry)| (file, Some(entry.artifacts)));
+            sources.extend(cached);
+        } else {
+            sources.extend(project.paths.read_input_files()?.into_keys().map(|file| (file, None)));
+        }
+        // the Yul and Vyper sources are compiled separately and not cached
+        let non_solidity = forge::sources::source_files(&project.paths.sources)?;
+        sources.extend(non_solidity.into_iter().map(|file| (file, None)));
+
+        let mut artifacts = Vec::new();
+        for (source, names) in sources {
+            let dir = project.paths.artifacts.join(source.file_name().unwrap_or_default());
+            let names = match names {
+                Some(names) => names,
+                None if dir.is_dir() => std::fs::read_dir(&dir)?
+                    .filter_map(Result::ok)
+                    .map(|artifact| artifact.path())
+                    .filter(|artifact| artifact.extension() == Some(OsStr::new("json")))
+                    .filter_map(|artifact| Some(artifact.file_stem()?.to_string_lossy().into()))
+                    .collect(),
+                None => continue,
+            };
+            for name in names {
+                let path = dir.join(format!("{}.json", name));
+                // contracts without bytecode, like interfaces, may not have an artifact
+                if !path.exists() {
+                    continue
+                }
+                let artifact = serde_json::from_str(&std::fs::read_to_string(&path)?)?;
+                artifacts.push(CompiledArtifact { source: source.clone(), name, artifact });
+            }
+        }
+        artifacts.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(artifacts)
+    }
+
+    /// Whether the contract is a `DSTest` test contract
+    pub(crate) fn is_test(&self) -> bool {
+        let functions = self.artifact.get("abi").and_then(|abi| abi.as_array());
+        functions.into_iter().flatten().any(|func| func["name"] == "IS_TEST")
+    }
+
+    /// Returns the hex encoded creation bytecode, which may contain library placeholders
+    fn bytecode(&self) -> Option<&str> {
+        self.code(&["bin"])
+    }
+
+    /// Returns the hex encoded runtime bytecode, which may contain library placeholders
+    fn deployed_bytecode(&self) -> Option<&str> {
+        self.code(&["bin-runtime", "binRuntime"])
+    }
+
+    fn code(&self, keys: &[&str]) -> Option<&str> {
+        let code = keys.iter().find_map(|key| self.artifact.get(key)?.as_str())?;
+        Some(code.strip_prefix("0x").unwrap_or(code))
+    }
+}
+
+/// The compiler's warnings, without the ones the config ignores
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct Warnings {
+    /// The formatted warnings that aren't ignored
+    pub(crate) reported: Vec<String>,
+    /// The number of ignored warnings by error code
+    pub(crate) ignored: BTreeMap<u64, usize>,
+}
+
+impl Warnings {
+    /// Splits the warnings of the compiler output into the reported and the ignored ones, by the
+    /// `ignored_error_codes` and the `ignored_error_codes_by_path` of the config
+    pub(crate) fn new(errors: &[SolcError], root: &Path, config: &Config) -> Self {
+        let mut warnings = Warnings::default();
+        for error in errors.iter().filter(|error| error.severity == Severity::Warning) {
+            let code = error.error_code.unwrap_or_default();
+            let file = error.source_location.as_ref().map(|location| root.join(&location.file));
+            let ignored_by_path = config.ignored_error_codes_by_path.iter().any(|(path, codes)| {
+                file.as_ref().map_or(false, |file| file.starts_with(root.join(path))) &&
+                    (codes.is_empty() || codes.contains(&code))
+            });
+            if ignored_by_path || config.ignored_error_codes.contains(&code) {
+                *warnings.ignored.entry(code).or_default() += 1;
+            } else {
+                warnings
+                    .reported
+                    .push(error.formatted_message.clone().unwrap_or_else(|| error.message.clone()));
+            }
+        }
+        warnings
+    }
+}
+
+/// Prints the compiler's warnings that aren't ignored and the number of ignored warnings by error
+/// code, fails if there are reported warnings and the config sets `deny_warnings`
+pub(crate) fn report_warnings(
+    config: &Config,
+    project: &Project,
+    output: &ProjectCompileOutput<MinimalCombinedArtifacts>,
+) -> eyre::Result<()> {
+    // unchanged projects have no compiler output, with `deny_warnings` the warnings of a cached
+    // build are the ones cached by the compilations of its sources
+    let errors = if output.is_unchanged() {
+        if !config.deny_warnings {
+            return Ok(())
+        }
+        cached_warnings(config, project)?
+    } else {
+        let compiled = output.clone().output();
+        if project.cached && project.paths.cache.exists() {
+            let path = CachedWarnings::path(project);
+            let mut cached = CachedWarnings::read(&path)?;
+            cached.update(&compiled, &content_hashes(&SolFilesCache::read(&project.paths.cache)?));
+            cached.write(&path)?;
+        }
+        compiled.errors
+    };
+    let warnings = Warnings::new(&errors, &project.paths.root, config);
+    for warning in &warnings.reported {
+        sh_println!("{}", shell::paint(Colour::Yellow, warning.trim_end()));
+    }
+    if !warnings.ignored.is_empty() {
+        let counts = warnings
+            .ignored
+            .iter()
+            .map(|(code, count)| format!("{}: {}", code, count))
+            .collect::<Vec<_>>()
+            .join(", ");
+        sh_status!(
+            "ignored {} compiler warnings ({})",
+            warnings.ignored.values().sum::<usize>(),
+            counts
+        );
     }
+    if config.deny_warnings && !warnings.reported.is_empty() {
+        return Err(ExitCode::CompilationError.error(format!(
+            "{} compiler warnings, which are denied by `deny_warnings`",
+            warnings.reported.len()
+        )))
+    }
+    Ok(())
+}
+
+/// Returns the warnings of the sources of a cached build
+///
+/// The sources whose warnings weren't cached, like the ones compiled by an older version, are
+/// compiled again without the cache.
+fn cached_warnings(config: &Config, project: &Project) -> eyre::Result<Vec<SolcError>> {
+    let content_hashes = content_hashes(&SolFilesCache::read(&project.paths.cache)?);
+    let path = CachedWarnings::path(project);
+    let mut cached = CachedWarnings::read(&path)?;
+    if let Some(warnings) = cached.get(&content_hashes) {
+        return Ok(warnings)
+    }
+    let compiled = config.ephemeral_no_artifacts_project()?.compile()?.output();
+    cached.update(&compiled, &content_hashes);
+    cached.write(&path)?;
+    Ok(compiled.errors)
+}
+
+/// Returns the content hash of every source file in the compiler cache
+fn content_hashes(cache: &SolFilesCache) -> BTreeMap<PathBuf, String> {
+    cache.files.iter().map(|(file, entry)| (file.clone(), entry.content_hash.clone())).collect()
+}
+
+/// The compiler's warnings of every source file by the compilation that cached it, kept next to
+/// the compiler cache so the warnings of a cached build can be checked without compiling it
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CachedWarnings(BTreeMap<PathBuf, CachedSourceWarnings>);
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedSourceWarnings {
+    /// The content hash of the source in the compiler cache when it was compiled
+    content_hash: String,
+    warnings: Vec<SolcError>,
+}
+
+impl CachedWarnings {
+    /// Returns the path of the file the warnings are kept in
+    fn path(project: &Project) -> PathBuf {
+        project.paths.cache.with_file_name("compiler-warnings.json")
+    }
+
+    fn read(path: &Path) -> eyre::Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default())
+        }
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    fn write(&self, path: &Path) -> eyre::Result<()> {
+        Ok(std::fs::write(path, serde_json::to_string(self)?)?)
+    }
+
+    /// Replaces the warnings of the compiled sources and drops the sources that aren't cached
+    /// anymore, warnings without a source location are not kept
+    fn update(&mut self, compiled: &CompilerOutput, content_hashes: &BTreeMap<PathBuf, String>) {
+        self.0.retain(|file, _| content_hashes.contains_key(file));
+        for file in compiled.sources.keys().map(PathBuf::from) {
+            let content_hash = match content_hashes.get(&file) {
+                Some(content_hash) => content_hash.clone(),
+                None => continue,
+            };
+            let warnings = compiled
+                .errors
+                .iter()
+                .filter(|error| error.severity == Severity::Warning)
+                .filter(|error| {
+                    let location = error.source_location.as_ref();
+                    location.map_or(false, |location| Path::new(&location.file) == file.as_path())
+                })
+                .cloned()
+                .collect();
+            self.0.insert(file, CachedSourceWarnings { content_hash, warnings });
+        }
+    }
+
+    /// Returns the warnings of the sources, none if a source changed since its warnings were
+    /// cached
+    fn get(&self, content_hashes: &BTreeMap<PathBuf, String>) -> Option<Vec<SolcError>> {
+        let mut warnings = Vec::new();
+        for (file, content_hash) in content_hashes {
+            let cached = self.0.get(file).filter(|cached| &cached.content_hash == content_hash)?;
+            warnings.extend(cached.warnings.iter().cloned());
+        }
+        Some(warnings)
+    }
+}
+
+/// Writes the extra outputs of every compiled contract next to its artifact, to
+/// `<out>/<source file name>/<Name>.<output>.json`, or `.yul` for the IR
+///
+/// Returns the number of written files, nothing is written if the compilation was skipped.
+fn write_extra_output(
+    project: &Project,
+    extra_output: &[ExtraOutput],
+    output: &ProjectCompileOutput<MinimalCombinedArtifacts>,
+) -> eyre::Result<usize> {
+    if extra_output.is_empty() || output.is_unchanged() {
+        return Ok(0)
+    }
+    let compiled = serde_json::to_value(output.clone().output())?;
+    let mut written = 0;
+    for (file, contracts) in compiled["contracts"].as_object().into_iter().flatten() {
+        let dir = project.paths.artifacts.join(Path::new(file).file_name().unwrap_or_default());
+        for (name, contract) in contracts.as_object().into_iter().flatten() {
+            for extra in extra_output {
+                let content = match extra_output_content(*extra, contract)? {
+                    Some(content) => content,
+                    None => continue,
+                };
+                std::fs::create_dir_all(&dir)?;
+                std::fs::write(dir.join(extra.file_name(name)), content)?;
+                written += 1;
+            }
+        }
+    }
+    Ok(written)
+}
+
+/// Returns the content of the extra output file of a contract's json output, none if solc didn't
+/// emit the output, like the IR of interfaces
+fn extra_output_content(
+    extra: ExtraOutput,
+    contract: &serde_json::Value,
+) -> eyre::Result<Option<String>> {
+    let value = match extra {
+        ExtraOutput::Ir | ExtraOutput::IrOptimized => {
+            let ir = contract[extra.to_string()].as_str().unwrap_or_default();
+            return Ok((!ir.is_empty()).then(|| ir.to_string()))
+        }
+        ExtraOutput::FunctionDebugData => {
+            let bytecode = &contract["evm"]["bytecode"]["functionDebugData"];
+            let deployed_bytecode = &contract["evm"]["deployedBytecode"]["functionDebugData"];
+            if bytecode.is_null() && deployed_bytecode.is_null() {
+                return Ok(None)
+            }
+            serde_json::json!({ "bytecode": bytecode, "deployedBytecode": deployed_bytecode })
+        }
+        _ => contract[extra.to_string()].clone(),
+    };
+    if value.is_null() {
+        return Ok(None)
+    }
+    Ok(Some(serde_json::to_string_pretty(&value)?))
+}
+
+/// Writes a Hardhat style build info file, named by its id, with the standard json input and
+/// output of every compiler version of the compilation to `dir`
+///
+/// Returns the number of written build infos, nothing is written if the compilation was skipped.
+/// The compilation is split by the compiler version in the metadata of the contracts, which the
+/// config adds to the output selection if `build_info` is set.
+fn write_build_infos(
+    project: &Project,
+    output: &ProjectCompileOutput<MinimalCombinedArtifacts>,
+    dir: &Path,
+) -> eyre::Result<usize> {
+    if output.is_unchanged() {
+        return Ok(0)
+    }
+    let settings = serde_json::to_value(&project.solc_config.settings)?;
+    let output = serde_json::to_value(output.clone().output())?;
+    let root = &project.paths.root;
+    let build_infos =
+        build_infos(&settings, &output, |source| Ok(std::fs::read_to_string(root.join(source))?))?;
+
+    std::fs::create_dir_all(dir)?;
+    for build_info in &build_infos {
+        let id = build_info["id"].as_str().unwrap_or_default();
+        std::fs::write(dir.join(format!("{}.json", id)), serde_json::to_string(build_info)?)?;
+    }
+    Ok(build_infos.len())
+}
+
+/// Splits the json output of a compilation by the compiler version of the contracts, as listed in
+/// their metadata, into Hardhat style build infos with the standard json input and output of each
+/// version
+///
+/// The input lists the sources the metadata of the contracts list, read with `read_source`, and
+/// the `settings`. The id of a build info is the hash of its format, versions and input.
+fn build_infos(
+    settings: &serde_json::Value,
+    output: &serde_json::Value,
+    read_source: impl Fn(&str) -> eyre::Result<String>,
+) -> eyre::Result<Vec<serde_json::Value>> {
+    use serde_json::{json, Map, Value};
+
+    let mut versions = BTreeMap::<String, BTreeSet<String>>::new();
+    for contracts in output["contracts"].as_object().into_iter().flat_map(Map::values) {
+        for contract in contracts.as_object().into_iter().flat_map(Map::values) {
+            let metadata = match SolcMetadata::from_output(&contract["metadata"])? {
+                Some(metadata) => metadata.json,
+                None => continue,
+            };
+            let version = match metadata["compiler"]["version"].as_str() {
+                Some(version) => version.to_string(),
+                None => continue,
+            };
+            let sources = metadata["sources"].as_object().into_iter().flat_map(Map::keys);
+            versions.entry(version).or_default().extend(sources.cloned());
+        }
+    }
+
+    let mut build_infos = Vec::new();
+    for (long_version, sources) in versions {
+        let version = long_version.split('+').next().unwrap_or_default().to_string();
+        let mut input_sources = Map::new();
+        for source in &sources {
+            input_sources.insert(source.clone(), json!({ "content": read_source(source)? }));
+        }
+        let input =
+            json!({ "language": "Solidity", "sources": input_sources, "settings": settings });
+
+        let filter = |key: &str| {
+            let entries = output[key].as_object().into_iter().flatten();
+            entries
+                .filter(|(file, _)| sources.contains(*file))
+                .map(|(file, value)| (file.clone(), value.clone()))
+                .collect::<Map<_, _>>()
+        };
+        let errors = output["errors"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|error| {
+                error["sourceLocation"]["file"].as_str().map_or(true, |file| sources.contains(file))
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        let output = json!({
+            "contracts": filter("contracts"),
+            "sources": filter("sources"),
+            "errors": errors,
+        });
+
+        let id = keccak256(serde_json::to_vec(&json!([
+            BUILD_INFO_FORMAT,
+            &version,
+            &long_version,
+            &input
+        ]))?);
+        build_infos.push(json!({
+            "_format": BUILD_INFO_FORMAT,
+            "id": hex::encode(&id[..16]),
+            "solcVersion": version,
+            "solcLongVersion": long_version,
+            "input": input,
+            "output": output,
+        }));
+    }
+    Ok(build_infos)
+}
+
+/// Compiles the `.yul` and `.vy` sources of the project and writes their artifacts next to the
+/// Solidity ones, to `<out>/<source file name>/<Name>.json`
+///
+/// Returns the number of written artifacts.
+fn write_non_solidity_artifacts(project: &Project, vyper: Option<&Path>) -> eyre::Result<usize> {
+    let compiled = forge::sources::compile_sources(project, vyper)?;
+    for source in &compiled {
+        let dir = project.paths.artifacts.join(source.path.file_name().unwrap_or_default());
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(
+            dir.join(format!("{}.json", source.name)),
+            serde_json::to_string_pretty(&source.contract)?,
+        )?;
+    }
+    Ok(compiled.len())
+}
+
+/// Prints the size of the runtime bytecode of every contract and its margin to the
+/// [`CONTRACT_SIZE_LIMIT`], test contracts are skipped
+///
+/// Returns the number of contracts that exceed the limit.
+fn print_sizes(project: &Project) -> eyre::Result<usize> {
+    let artifacts = CompiledArtifact::read_all(project)?;
+    let sizes = artifacts
+        .iter()
+        .filter(|compiled| !compiled.is_test())
+        // every byte is 2 hex characters, including the library placeholders
+        .filter_map(|compiled| Some((&compiled.name, compiled.deployed_bytecode()?.len() / 2)))
+        .filter(|(_, size)| *size > 0)
+        .collect::<Vec<_>>();
+
+    let width = sizes.iter().map(|(name, _)| name.len()).max().unwrap_or_default().max(8);
+    sh_println!("{:<width$} {:>10} {:>12}", "Contract", "Size (B)", "Margin (B)", width = width);
+    let mut oversized = 0;
+    for (name, size) in sizes {
+        let margin = CONTRACT_SIZE_LIMIT as i64 - size as i64;
+        let line = format!("{:<width$} {:>10} {:>12}", name, size, margin, width = width);
+        if margin < 0 {
+            oversized += 1;
+            sh_println!("{}", shell::paint(Colour::Red, line));
+        } else if size * 10 >= CONTRACT_SIZE_LIMIT * 9 {
+            sh_println!("{}", shell::paint(Colour::Yellow, line));
+        } else {
+            sh_println!("{}", line);
+        }
+    }
+    Ok(oversized)
+}
+
+/// Writes every artifact of the project in the format of hardhat to
+/// `<root>/artifacts/<source>/<Name>.json`, so it can be consumed by js tooling
+///
+/// Returns the number of written artifacts, contracts with unlinked libraries are skipped.
+fn write_hardhat_artifacts(project: &Project) -> eyre::Result<usize> {
+    let root = &project.paths.root;
+    let mut written = 0;
+    for compiled in CompiledArtifact::read_all(project)? {
+        let source_name =
+            compiled.source.strip_prefix(root).unwrap_or(&compiled.source).display().to_string();
+        // unlinked bytecode contains placeholders
+        let code = |code: Option<&str>| {
+            code.and_then(decode_artifact_code).map(|code| format!("0x{}", hex::encode(code)))
+        };
+        let (abi, bytecode, deployed_bytecode) = match (
+            compiled.artifact.get("abi").and_then(|abi| serde_json::from_value(abi.clone()).ok()),
+            code(compiled.bytecode()),
+            code(compiled.deployed_bytecode()),
+        ) {
+            (Some(abi), Some(bytecode), Some(deployed_bytecode)) => {
+                (abi, bytecode, deployed_bytecode)
+            }
+            _ => continue,
+        };
+        let hardhat = HardhatArtifact {
+            format: HardhatArtifact::FORMAT.to_string(),
+            contract_name: compiled.name.clone(),
+            source_name: source_name.clone(),
+            abi,
+            bytecode,
+            deployed_bytecode,
+            link_references: Default::default(),
+            deployed_link_references: Default::default(),
+        };
+        let dir = root.join(HardhatArtifact::DIR).join(&source_name);
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(
+            dir.join(format!("{}.json", compiled.name)),
+            serde_json::to_string_pretty(&hardhat)?,
+        )?;
+        written += 1;
+    }
+    Ok(written)
 }
 
 impl BuildArgs {
@@ -159,10 +765,73 @@ impl BuildArgs {
     /// [`utils::find_project_root_path`] and merges the cli `BuildArgs` into it before returning
     /// [`foundry_config::Config::project()`]
     pub fn project(&self) -> eyre::Result<Project> {
-        let config: Config = self.into();
+        let config = Config::try_from_args(self)?;
         Ok(config.project()?)
     }
 
+    /// Compiles the project and writes the artifacts the arguments ask for
+    fn build(
+        &self,
+        config: &Config,
+    ) -> eyre::Result<ProjectCompileOutput<MinimalCombinedArtifacts>> {
+        let project = self.project()?;
+        // the hardhat artifacts must not be mixed up with forge's own
+        if self.hardhat && project.paths.artifacts == project.paths.root.join(HardhatArtifact::DIR)
+        {
+            return Err(ExitCode::ConfigError.error(format!(
+                "`--hardhat` writes the hardhat artifacts to `{}`, which is the `out` directory \
+                 of forge, set `out` to another directory",
+                HardhatArtifact::DIR
+            )))
+        }
+        let output = super::compile(&project)?;
+        report_warnings(config, &project, &output)?;
+        let written = write_extra_output(&project, &config.extra_output, &output)?;
+        if written > 0 {
+            sh_status!("wrote {} extra outputs to {}", written, project.paths.artifacts.display());
+        }
+        let written = write_non_solidity_artifacts(&project, config.vyper.as_deref())?;
+        if written > 0 {
+            sh_status!("compiled {} yul and vyper contracts", written);
+        }
+        if self.hardhat {
+            let written = write_hardhat_artifacts(&project)?;
+            sh_status!("wrote {} hardhat artifacts to {}", written, HardhatArtifact::DIR);
+        }
+        if let Some(bindings) = Bindings::from_config(config) {
+            let generated = bindings.write(&project)?;
+            if generated > 0 {
+                sh_status!(
+                    "generated bindings of {} contracts in {}",
+                    generated,
+                    bindings.path.display()
+                );
+            }
+        }
+        if self.sizes || self.fail_on_oversize {
+            let oversized = print_sizes(&project)?;
+            if self.fail_on_oversize && oversized > 0 {
+                eyre::bail!(
+                    "{} contracts exceed the size limit of {} bytes",
+                    oversized,
+                    CONTRACT_SIZE_LIMIT
+                )
+            }
+        }
+        if self.extract_metadata || self.pin_metadata {
+            publish_metadata(config, None, self.pin_metadata)?;
+        }
+        if config.build_info {
+            let dir =
+                config.build_info_path.clone().unwrap_or_else(|| config.out.join(BUILD_INFO_DIR));
+            let written = write_build_infos(&project, &output, &dir)?;
+            if written > 0 {
+                sh_status!("wrote {} build info files to {}", written, dir.display());
+            }
+        }
+        Ok(output)
+    }
+
     /// Returns the remappings to add to the config
     pub fn get_remappings(&self) -> Vec<Remapping> {
         let mut remappings = self.remappings.clone();
@@ -205,10 +874,205 @@ impl Provider for BuildArgs {
             dict.insert("force".to_string(), self.force.into());
         }
 
+        if self.offline {
+            dict.insert("offline".to_string(), self.offline.into());
+        }
+
+        if self.build_info || self.build_info_path.is_some() {
+            dict.insert("build_info".to_string(), true.into());
+        }
+
+        if self.deny_warnings {
+            dict.insert("deny_warnings".to_string(), true.into());
+        }
+
+        if !self.ignored_warnings_from.is_empty() {
+            let paths = self
+                .ignored_warnings_from
+                .iter()
+                .map(|path| (path.display().to_string(), Vec::<u64>::new()))
+                .collect::<BTreeMap<_, _>>();
+            dict.insert("ignored_error_codes_by_path".to_string(), Value::serialize(paths)?);
+        }
+
         if self.compiler.optimize {
             dict.insert("optimizer".to_string(), self.compiler.optimize.into());
         }
 
+        if self.compiler.via_ir {
+            dict.insert("via_ir".to_string(), self.compiler.via_ir.into());
+        }
+
         Ok(Map::from([(Config::selected_profile(), dict)]))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ignores_warnings_by_code_and_path() {
+        let warning = |file: &str, code: &str| {
+            serde_json::from_value::<SolcError>(serde_json::json!({
+                "sourceLocation": { "file": file, "start": 0, "end": 1 },
+                "type": "Warning",
+                "component": "general",
+                "severity": "warning",
+                "errorCode": code,
+                "message": "Unused local variable.",
+                "formattedMessage": format!("Warning: Unused local variable.\n --> {}", file)
+            }))
+            .unwrap()
+        };
+        let errors = [
+            warning("src/Vault.sol", "2072"),
+            warning("src/Vault.sol", "5667"),
+            warning("src/legacy/Old.sol", "2072"),
+            warning("src/legacy/Old.sol", "2018"),
+            warning("lib/solmate/src/ERC20.sol", "2018"),
+        ];
+        let config = Config {
+            ignored_error_codes: vec![5667],
+            ignored_error_codes_by_path: BTreeMap::from([
+                ("lib".to_string(), vec![]),
+                ("src/legacy".to_string(), vec![2072]),
+            ]),
+            ..Default::default()
+        };
+
+        let warnings = Warnings::new(&errors, Path::new("/project"), &config);
+        assert_eq!(
+            warnings.reported,
+            vec![
+                "Warning: Unused local variable.\n --> src/Vault.sol",
+                "Warning: Unused local variable.\n --> src/legacy/Old.sol"
+            ]
+        );
+        assert_eq!(warnings.ignored, BTreeMap::from([(2018, 1), (2072, 1), (5667, 1)]));
+    }
+
+    #[test]
+    fn caches_warnings_by_source() {
+        let output = serde_json::json!({
+            "sources": {
+                "src/Vault.sol": { "id": 0, "ast": {} },
+                "src/Math.sol": { "id": 1, "ast": {} }
+            },
+            "errors": [
+                {
+                    "sourceLocation": { "file": "src/Vault.sol", "start": 0, "end": 1 },
+                    "type": "Warning",
+                    "component": "general",
+                    "severity": "warning",
+                    "errorCode": "2072",
+                    "message": "Unused local variable."
+                },
+                {
+                    "type": "Warning",
+                    "component": "general",
+                    "severity": "warning",
+                    "message": "Warning without a source location."
+                }
+            ]
+        });
+        let compiled: CompilerOutput = serde_json::from_value(output).unwrap();
+        let hashes = |vault: &str| {
+            BTreeMap::from([
+                (PathBuf::from("src/Vault.sol"), vault.to_string()),
+                (PathBuf::from("src/Math.sol"), "math".to_string()),
+            ])
+        };
+
+        let mut cached = CachedWarnings::default();
+        cached.update(&compiled, &hashes("vault"));
+        let warnings = cached.get(&hashes("vault")).unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].message, "Unused local variable.");
+
+        // a changed source has to be compiled again
+        assert!(cached.get(&hashes("changed")).is_none());
+
+        // removed sources are dropped
+        let math = BTreeMap::from([(PathBuf::from("src/Math.sol"), "math".to_string())]);
+        cached.update(&CompilerOutput::default(), &math);
+        assert_eq!(cached.0.len(), 1);
+        assert!(cached.get(&math).unwrap().is_empty());
+    }
+
+    #[test]
+    fn reads_extra_outputs() {
+        let contract = serde_json::json!({
+            "abi": [],
+            "irOptimized": "object \"Vault\" {}",
+            "devdoc": { "kind": "dev", "methods": {}, "version": 1 },
+            "evm": {
+                "bytecode": { "functionDebugData": { "@_12": { "entryPoint": null } } },
+                "deployedBytecode": { "functionDebugData": {} }
+            }
+        });
+
+        assert_eq!(extra_output_content(ExtraOutput::Ir, &contract).unwrap(), None);
+        assert_eq!(
+            extra_output_content(ExtraOutput::IrOptimized, &contract).unwrap().unwrap(),
+            "object \"Vault\" {}"
+        );
+        assert_eq!(extra_output_content(ExtraOutput::Userdoc, &contract).unwrap(), None);
+        let devdoc = extra_output_content(ExtraOutput::Devdoc, &contract).unwrap().unwrap();
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&devdoc).unwrap(), contract["devdoc"]);
+        let debug_data =
+            extra_output_content(ExtraOutput::FunctionDebugData, &contract).unwrap().unwrap();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&debug_data).unwrap()["bytecode"],
+            contract["evm"]["bytecode"]["functionDebugData"]
+        );
+    }
+
+    #[test]
+    fn splits_build_infos_by_compiler_version() {
+        let metadata = |version: &str, sources: &[&str]| {
+            let sources = sources.iter().map(|source| (source.to_string(), serde_json::json!({})));
+            serde_json::json!({
+                "compiler": { "version": version },
+                "sources": sources.collect::<serde_json::Map<_, _>>()
+            })
+            .to_string()
+        };
+        let output = serde_json::json!({
+            "contracts": {
+                "src/Vault.sol": {
+                    "Vault": { "abi": [], "metadata": metadata("0.8.10+commit.fc410830", &["src/Vault.sol", "src/Math.sol"]) }
+                },
+                "src/Math.sol": {
+                    "Math": { "abi": [], "metadata": metadata("0.8.10+commit.fc410830", &["src/Math.sol"]) }
+                },
+                "src/Legacy.sol": {
+                    "Legacy": { "abi": [], "metadata": metadata("0.6.12+commit.27d51765", &["src/Legacy.sol"]) }
+                }
+            },
+            "sources": { "src/Vault.sol": { "id": 0 }, "src/Math.sol": { "id": 1 }, "src/Legacy.sol": { "id": 2 } },
+            "errors": [{ "sourceLocation": { "file": "src/Legacy.sol" }, "severity": "warning" }]
+        });
+        let settings = serde_json::json!({ "optimizer": { "enabled": true, "runs": 200 } });
+
+        let build_infos =
+            build_infos(&settings, &output, |source| Ok(format!("// {}", source))).unwrap();
+        assert_eq!(build_infos.len(), 2);
+
+        let legacy = &build_infos[0];
+        assert_eq!(legacy["_format"], BUILD_INFO_FORMAT);
+        assert_eq!(legacy["solcVersion"], "0.6.12");
+        assert_eq!(legacy["solcLongVersion"], "0.6.12+commit.27d51765");
+        assert_eq!(legacy["input"]["sources"]["src/Legacy.sol"]["content"], "// src/Legacy.sol");
+        assert_eq!(legacy["input"]["settings"], settings);
+        assert_eq!(legacy["output"]["errors"].as_array().unwrap().len(), 1);
+        assert_eq!(legacy["id"].as_str().unwrap().len(), 32);
+
+        let current = &build_infos[1];
+        assert_eq!(current["solcVersion"], "0.8.10");
+        assert_eq!(current["input"]["sources"].as_object().unwrap().len(), 2);
+        assert_eq!(current["output"]["contracts"].as_object().unwrap().len(), 2);
+        assert!(current["output"]["sources"].get("src/Legacy.sol").is_none());
+        assert!(current["output"]["errors"].as_array().unwrap().is_empty());
+    }
+}
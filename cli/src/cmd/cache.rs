@@ -0,0 +1,116 @@
+//! cache command
+
+use crate::{
+    cmd::Cmd,
+    shell::{sh_println, sh_status},
+};
+use clap::{Parser, Subcommand};
+use evm_adapters::sputnik::disk_cache::foundry_cache_dir;
+use std::path::Path;
+
+/// Command to manage the on-disk cache of forked state
+#[derive(Debug, Clone, Parser)]
+pub struct CacheArgs {
+    #[clap(subcommand)]
+    pub sub: CacheSubcommands,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum CacheSubcommands {
+    #[clap(about = "lists all cached blocks per chain")]
+    Ls {
+        #[clap(help = "only list the blocks cached for this chain id", long)]
+        chain_id: Option<u64>,
+    },
+    #[clap(about = "removes the cached state")]
+    Clean {
+        #[clap(help = "only remove the blocks cached for this chain id", long)]
+        chain_id: Option<u64>,
+        #[clap(
+            help = "only remove this block, requires `--chain-id`",
+            long,
+            requires = "chain-id"
+        )]
+        block: Option<u64>,
+    },
+}
+
+impl Cmd for CacheArgs {
+    type Output = ();
+
+    fn run(self) -> eyre::Result<Self::Output> {
+        let cache_dir =
+            foundry_cache_dir().ok_or_else(|| eyre::eyre!("failed to find the home directory"))?;
+        match self.sub {
+            CacheSubcommands::Ls { chain_id } => {
+                let mut total = 0;
+                for (chain, blocks) in cached_blocks(&cache_dir, chain_id)? {
+                    sh_println!("chain {}:", chain);
+                    for (block, size) in blocks {
+                        sh_println!("  block {} ({})", block, format_size(size));
+                        total += size;
+                    }
+                }
+                sh_println!("total: {}", format_size(total));
+            }
+            CacheSubcommands::Clean { chain_id, block } => {
+                let path = match (chain_id, block) {
+                    (Some(chain), Some(block)) => {
+                        cache_dir.join(chain.to_string()).join(format!("{}.json", block))
+                    }
+                    (Some(chain), None) => cache_dir.join(chain.to_string()),
+                    _ => cache_dir,
+                };
+                if path.is_dir() {
+                    std::fs::remove_dir_all(&path)?;
+                } else if path.is_file() {
+                    std::fs::remove_file(&path)?;
+                }
+                sh_status!("removed {}", path.display());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns all cached `(block, file size)` per chain id, optionally only of `chain_id`
+fn cached_blocks(
+    cache_dir: &Path,
+    chain_id: Option<u64>,
+) -> eyre::Result<Vec<(u64, Vec<(u64, u64)>)>> {
+    let mut chains = Vec::new();
+    if !cache_dir.exists() {
+        return Ok(chains)
+    }
+    for entry in std::fs::read_dir(cache_dir)? {
+        let path = entry?.path();
+        let chain = match file_number(&path) {
+            Some(chain) if path.is_dir() && chain_id.map(|id| id == chain).unwrap_or(true) => chain,
+            _ => continue,
+        };
+        let mut blocks = Vec::new();
+        for entry in std::fs::read_dir(&path)? {
+            let entry = entry?;
+            if let Some(block) = file_number(&entry.path()) {
+                blocks.push((block, entry.metadata()?.len()));
+            }
+        }
+        blocks.sort_unstable();
+        chains.push((chain, blocks));
+    }
+    chains.sort_unstable();
+    Ok(chains)
+}
+
+/// Parses the number in `<number>` or `<number>.json`
+fn file_number(path: &Path) -> Option<u64> {
+    path.file_stem()?.to_str()?.parse().ok()
+}
+
+fn format_size(bytes: u64) -> String {
+    match bytes {
+        b if b >= 1024 * 1024 => format!("{:.1} MiB", b as f64 / (1024.0 * 1024.0)),
+        b if b >= 1024 => format!("{:.1} KiB", b as f64 / 1024.0),
+        b => format!("{} B", b),
+    }
+}
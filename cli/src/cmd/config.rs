@@ -2,20 +2,31 @@
 
 use crate::{
     cmd::{build::BuildArgs, Cmd},
+    exit_code::ExitCode,
     opts::evm::EvmArgs,
+    shell::{self, sh_println, sh_status},
 };
+use ansi_term::Colour;
 use clap::Parser;
-use foundry_config::{figment::Figment, Config};
+use foundry_config::{figment::Figment, find_project_root_path, Config};
 
 foundry_config::impl_figment_convert!(ConfigArgs, opts, evm_opts);
 
 /// Command to list currently set config values
 #[derive(Debug, Clone, Parser)]
 pub struct ConfigArgs {
-    #[clap(help = "prints currently set config values as json", long)]
-    json: bool,
     #[clap(help = "prints basic set of currently set config values", long)]
     basic: bool,
+    #[clap(
+        help = "checks the config file for unknown and deprecated keys and invalid values",
+        long
+    )]
+    validate: bool,
+    #[clap(
+        help = "renames unknown keys with an unambiguous suggestion and deprecated keys in the config file",
+        long
+    )]
+    fix: bool,
     // support nested build arguments
     #[clap(flatten)]
     opts: BuildArgs,
@@ -27,22 +38,47 @@ impl Cmd for ConfigArgs {
     type Output = ();
 
     fn run(self) -> eyre::Result<Self::Output> {
+        if self.validate || self.fix {
+            return validate(self.fix)
+        }
+
         let figment: Figment = From::from(&self);
-        let config = Config::from_provider(figment);
-        let s = if self.basic {
+        let config = Config::try_from(figment)?;
+        if self.basic {
             let config = config.into_basic();
-            if self.json {
-                serde_json::to_string_pretty(&config)?
-            } else {
-                config.to_string_pretty()?
+            if shell::is_json() {
+                return shell::print_json(&config)
             }
-        } else if self.json {
-            serde_json::to_string_pretty(&config)?
+            sh_println!("{}", config.to_string_pretty()?);
         } else {
-            config.to_string_pretty()?
-        };
-
-        println!("{}", s);
+            if shell::is_json() {
+                return shell::print_json(&config)
+            }
+            sh_println!("{}", config.to_string_pretty()?);
+        }
         Ok(())
     }
 }
+
+/// Prints all issues of the config file, renames the fixable keys first if `fix` is set
+fn validate(fix: bool) -> eyre::Result<()> {
+    let path = match Config::find_config_file() {
+        Some(path) => path,
+        None => find_project_root_path()?.join(Config::FILE_NAME),
+    };
+    let mut issues = Config::validate_file(&path)?;
+    if fix && !issues.is_empty() {
+        let renamed = Config::fix_file(&path, &issues)?;
+        sh_status!("renamed {} keys in {}", renamed, path.display());
+        issues = Config::validate_file(&path)?;
+    }
+
+    if issues.is_empty() {
+        sh_println!("{}", shell::paint(Colour::Green, format!("{} is valid.", path.display())));
+        return Ok(())
+    }
+    for issue in &issues {
+        sh_println!("{}", shell::paint(Colour::Yellow, issue.to_string()));
+    }
+    Err(ExitCode::ConfigError.error(format!("found {} issues in {}", issues.len(), path.display())))
+}
@@ -2,20 +2,26 @@
 
 use crate::{
     cmd::{build::BuildArgs, Cmd},
-    opts::{EthereumOpts, WalletType},
+    ipfs,
+    opts::{wallet::with_wallet, EthereumOpts},
+    shell::{self, sh_println, sh_status},
+    utils::parse_slot,
 };
 use ethers::{
     abi::{Abi, Constructor, Token},
-    prelude::{artifacts::BytecodeObject, ContractFactory, Http, Middleware, Provider},
-    types::Chain,
+    prelude::{artifacts::BytecodeObject, Http, Middleware, Provider},
+    types::{
+        transaction::eip2718::TypedTransaction, Chain, Eip1559TransactionRequest,
+        TransactionRequest, H256, U256,
+    },
 };
 
 use eyre::Result;
-use foundry_utils::parse_tokens;
+use foundry_config::Config;
+use foundry_utils::{create2_address, parse_amount, parse_tokens, DEFAULT_CREATE2_DEPLOYER};
 
 use crate::opts::forge::ContractInfo;
 use clap::Parser;
-use std::sync::Arc;
 
 #[derive(Debug, Clone, Parser)]
 pub struct CreateArgs {
@@ -36,6 +42,48 @@ pub struct CreateArgs {
         help = "use legacy transactions instead of EIP1559 ones. this is auto-enabled for common networks without EIP1559"
     )]
     legacy: bool,
+
+    #[clap(
+        long,
+        help = "ether value sent to the constructor, in wei or with a unit like `1ether`",
+        parse(try_from_str = parse_amount)
+    )]
+    value: Option<U256>,
+
+    #[clap(
+        long,
+        help = "nonce of the deployment transaction, defaults to the sender's next nonce"
+    )]
+    nonce: Option<U256>,
+
+    #[clap(long, help = "gas limit of the deployment transaction, estimated if not set")]
+    gas_limit: Option<U256>,
+
+    #[clap(
+        long,
+        help = "gas price (in wei) of a legacy transaction, or the max fee per gas of an EIP1559 transaction"
+    )]
+    gas_price: Option<U256>,
+
+    #[clap(long, help = "max priority fee per gas (in wei) of an EIP1559 transaction")]
+    priority_gas_price: Option<U256>,
+
+    #[clap(long, help = "number of confirmations to wait for", default_value = "1")]
+    confirmations: usize,
+
+    #[clap(
+        long,
+        help = "deploy through the canonical CREATE2 deployer, which gives the contract the same address on every chain"
+    )]
+    create2: bool,
+
+    #[clap(
+        long,
+        help = "the salt of the CREATE2 deployment, a number or hex value, defaults to zero",
+        requires = "create2",
+        parse(try_from_str = parse_slot)
+    )]
+    salt: Option<H256>,
 }
 
 impl Cmd for CreateArgs {
@@ -61,25 +109,23 @@ impl Cmd for CreateArgs {
             None => vec![],
         };
 
+        // the metadata is published once the contract is deployed
+        let publish_metadata = self.opts.extract_metadata || self.opts.pin_metadata;
+        let (config, contract, pin) =
+            (Config::try_from_args(&self.opts)?, self.contract.clone(), self.opts.pin_metadata);
+
         // Deploy with signer
         let rt = tokio::runtime::Runtime::new().expect("could not start tokio rt");
         let chain_id = rt.block_on(provider.get_chainid())?;
         if let Some(signer) = rt.block_on(self.eth.signer_with(chain_id, provider))? {
-            match signer {
-                WalletType::Ledger(signer) => {
-                    rt.block_on(self.deploy(abi, bin, params, signer))?;
-                }
-                WalletType::Local(signer) => {
-                    rt.block_on(self.deploy(abi, bin, params, signer))?;
-                }
-                WalletType::Trezor(signer) => {
-                    rt.block_on(self.deploy(abi, bin, params, signer))?;
-                }
-            }
+            with_wallet!(signer, |signer| rt.block_on(self.deploy(abi, bin, params, signer))?);
         } else {
             eyre::bail!("could not find artifact")
         }
 
+        if publish_metadata {
+            ipfs::publish_metadata(&config, Some(&contract), pin)?;
+        }
         Ok(())
     }
 }
@@ -98,21 +144,88 @@ impl CreateArgs {
         let bin = bin.into_bytes().unwrap_or_else(|| {
             panic!("no bytecode found in bin object for {}", self.contract.name)
         });
-        let factory = ContractFactory::new(abi, bin, Arc::new(provider));
+        let mut data = match abi.constructor {
+            Some(ref constructor) => constructor.encode_input(bin.to_vec(), &args)?,
+            None => bin.to_vec(),
+        };
+
+        // the deployer creates the contract from the init code that follows the salt
+        let create2_address = if self.create2 {
+            let deployer = DEFAULT_CREATE2_DEPLOYER;
+            if provider.get_code(deployer, None).await?.as_ref().is_empty() {
+                eyre::bail!(
+                    "the CREATE2 deployer {:?} is not deployed on chain {}",
+                    deployer,
+                    chain
+                )
+            }
+            let salt = self.salt.unwrap_or_default();
+            let address = create2_address(deployer, salt, &data);
+            if !provider.get_code(address, None).await?.as_ref().is_empty() {
+                eyre::bail!("{} is already deployed at {:?}", self.contract.name, address)
+            }
+            data = [salt.as_bytes(), &data].concat();
+            Some(address)
+        } else {
+            None
+        };
 
-        let deployer = factory.deploy_tokens(args)?;
-        let deployer = if self.legacy ||
-            Chain::try_from(chain).map(|x| Chain::is_legacy(&x)).unwrap_or_default()
-        {
-            deployer.legacy()
+        let legacy =
+            self.legacy || Chain::try_from(chain).map(|x| Chain::is_legacy(&x)).unwrap_or_default();
+        let mut tx: TypedTransaction = if legacy {
+            let mut tx = TransactionRequest::new();
+            tx.gas_price = self.gas_price;
+            tx.into()
         } else {
-            deployer
+            let mut tx = Eip1559TransactionRequest::new();
+            tx.max_fee_per_gas = self.gas_price;
+            tx.max_priority_fee_per_gas = self.priority_gas_price;
+            tx.into()
         };
+        tx.set_from(deployer_address);
+        if self.create2 {
+            tx.set_to(DEFAULT_CREATE2_DEPLOYER);
+        }
+        tx.set_data(data.into());
+        if let Some(value) = self.value {
+            tx.set_value(value);
+        }
+        if let Some(nonce) = self.nonce {
+            tx.set_nonce(nonce);
+        }
+        if let Some(gas) = self.gas_limit {
+            tx.set_gas(gas);
+        }
 
-        let deployed_contract = deployer.send().await?;
+        let pending = provider.send_transaction(tx, None).await?;
+        let tx_hash = *pending;
+        sh_status!("Transaction: {:?}", tx_hash);
+        let receipt = pending
+            .confirmations(self.confirmations)
+            .await?
+            .ok_or_else(|| eyre::eyre!("deployment transaction {:?} was dropped", tx_hash))?;
+        if receipt.status != Some(1u64.into()) {
+            eyre::bail!("deployment transaction {:?} reverted", tx_hash)
+        }
+        let deployed_to = match create2_address {
+            Some(address) => address,
+            None => receipt.contract_address.ok_or_else(|| {
+                eyre::eyre!("deployment transaction {:?} did not create a contract", tx_hash)
+            })?,
+        };
 
-        println!("Deployer: {:?}", deployer_address);
-        println!("Deployed to: {:?}", deployed_contract.address());
+        if shell::is_json() {
+            shell::print_json(&serde_json::json!({
+                "deployer": deployer_address,
+                "deployedTo": deployed_to,
+                "transactionHash": tx_hash,
+                "blockNumber": receipt.block_number,
+                "gasUsed": receipt.gas_used,
+            }))?;
+        } else {
+            sh_println!("Deployer: {:?}", deployer_address);
+            sh_println!("Deployed to: {:?}", deployed_to);
+        }
 
         Ok(())
     }
@@ -1,10 +1,26 @@
-use std::path::PathBuf;
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
 
-use ethers::solc::remappings::Remapping;
+use ethers::solc::{remappings::Remapping, ProjectPathsConfig};
 
-use crate::cmd::{build::BuildArgs, Cmd};
+use crate::{
+    cmd::{build::BuildArgs, Cmd},
+    shell::{sh_println, sh_status},
+};
 use clap::{Parser, ValueHint};
+use eyre::WrapErr;
 use foundry_config::Config;
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+static IMPORT: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#"(?m)^\s*import\s[^;]*?["'](?P<path>[^"']+)["'][^;]*;"#).unwrap());
+static LICENSE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?m)^\s*//\s*SPDX-License-Identifier:\s*(?P<license>[^\r\n]*?)\s*$").unwrap()
+});
+static PRAGMA: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?m)^\s*pragma\s[^;]*;").unwrap());
 
 #[derive(Debug, Clone, Parser)]
 pub struct FlattenArgs {
@@ -78,25 +94,133 @@ impl Cmd for FlattenArgs {
             force: false,
             hardhat,
             libraries: vec![],
+            sizes: false,
+            fail_on_oversize: false,
+            extract_metadata: false,
+            pin_metadata: false,
+            offline: false,
         };
 
-        let config = Config::from(&build_args);
+        let config = Config::try_from_args(&build_args)?;
 
         let paths = config.project_paths();
         let target_path = dunce::canonicalize(target_path)?;
-        let flattened = paths
-            .flatten(&target_path)
-            .map_err(|err| eyre::Error::msg(format!("failed to flatten the file: {}", err)))?;
+        let flattened =
+            Flattener::new(&paths).flatten(&target_path).wrap_err("failed to flatten the file")?;
 
         match output {
             Some(output) => {
                 std::fs::create_dir_all(&output.parent().unwrap())?;
                 std::fs::write(&output, flattened)?;
-                println!("Flattened file written at {}", output.display());
+                sh_status!("Flattened file written at {}", output.display());
             }
-            None => println!("{}", flattened),
+            None => sh_println!("{}", flattened),
         };
 
         Ok(())
     }
 }
+
+/// Flattens a source file and all of its imports into a single file
+///
+/// Imports are resolved with the project's remappings and library paths. Every source is emitted
+/// once, after all of its imports, and the license identifiers and pragmas of all sources are
+/// merged into a single header.
+pub struct Flattener<'a> {
+    paths: &'a ProjectPathsConfig,
+}
+
+impl<'a> Flattener<'a> {
+    pub fn new(paths: &'a ProjectPathsConfig) -> Self {
+        Self { paths }
+    }
+
+    /// Returns the flattened source of the target file
+    pub fn flatten(&self, target: &Path) -> eyre::Result<String> {
+        let mut visited = HashSet::new();
+        let mut sources = Vec::new();
+        self.visit(target, &mut visited, &mut sources)?;
+
+        let mut licenses: Vec<String> = Vec::new();
+        let mut pragmas: Vec<String> = Vec::new();
+        let mut bodies = Vec::with_capacity(sources.len());
+        for (path, content) in sources {
+            for cap in LICENSE.captures_iter(&content) {
+                let license = cap["license"].to_string();
+                if !licenses.contains(&license) {
+                    licenses.push(license);
+                }
+            }
+            for pragma in PRAGMA.find_iter(&content) {
+                let pragma = pragma.as_str().trim().to_string();
+                if !pragmas.contains(&pragma) {
+                    pragmas.push(pragma);
+                }
+            }
+            let body = IMPORT.replace_all(&content, "");
+            let body = LICENSE.replace_all(&body, "");
+            let body = PRAGMA.replace_all(&body, "");
+            let path = path.strip_prefix(&self.paths.root).unwrap_or(&path);
+            bodies.push(format!("// {}\n{}", path.display(), body.trim()));
+        }
+
+        let mut flattened = String::new();
+        if !licenses.is_empty() {
+            flattened
+                .push_str(&format!("// SPDX-License-Identifier: {}\n", licenses.join(" AND ")));
+        }
+        for pragma in pragmas {
+            flattened.push_str(&pragma);
+            flattened.push('\n');
+        }
+        flattened.push('\n');
+        flattened.push_str(&bodies.join("\n\n"));
+        flattened.push('\n');
+        Ok(flattened)
+    }
+
+    /// Visits the imports of the file depth first and pushes every file after its imports, so the
+    /// sources end up in topological order
+    fn visit(
+        &self,
+        path: &Path,
+        visited: &mut HashSet<PathBuf>,
+        sources: &mut Vec<(PathBuf, String)>,
+    ) -> eyre::Result<()> {
+        if !visited.insert(path.to_path_buf()) {
+            return Ok(())
+        }
+        let content = std::fs::read_to_string(path)
+            .wrap_err_with(|| format!("failed to read {}", path.display()))?;
+        for cap in IMPORT.captures_iter(&content) {
+            let import = self.resolve_import(path, &cap["path"])?;
+            self.visit(&import, visited, sources)?;
+        }
+        sources.push((path.to_path_buf(), content));
+        Ok(())
+    }
+
+    /// Resolves an import of the file, either relative to the file, via the longest matching
+    /// remapping, or relative to the root and the library paths
+    fn resolve_import(&self, file: &Path, import: &str) -> eyre::Result<PathBuf> {
+        let candidates = if import.starts_with('.') {
+            vec![file.parent().unwrap_or(file).join(import)]
+        } else {
+            let remapped = self
+                .paths
+                .remappings
+                .iter()
+                .filter(|r| import.starts_with(&r.name))
+                .max_by_key(|r| r.name.len())
+                .map(|r| Path::new(&r.path).join(&import[r.name.len()..]));
+            remapped
+                .into_iter()
+                .chain(std::iter::once(self.paths.root.join(import)))
+                .chain(self.paths.libraries.iter().map(|lib| lib.join(import)))
+                .collect()
+        };
+        candidates.iter().find_map(|candidate| dunce::canonicalize(candidate).ok()).ok_or_else(
+            || eyre::eyre!("failed to resolve import \"{}\" of {}", import, file.display()),
+        )
+    }
+}
@@ -0,0 +1,373 @@
+//! Geiger command
+
+use crate::{
+    cmd::{
+        lint::{line_column, print_finding, relative, sarif, Finding},
+        Cmd,
+    },
+    exit_code::ExitCode,
+    hooks::Hook,
+    shell::{self, sh_println, sh_status},
+};
+use clap::{Parser, ValueHint};
+use foundry_config::{find_project_root_path, Config, LintSeverity};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+};
+
+/// A usage of a cheatcode or a pattern that's unsafe to run or to depend on
+struct Rule {
+    name: &'static str,
+    description: &'static str,
+    severity: LintSeverity,
+    /// The regex of the usage, the `name` group is the cheatcode or keyword
+    pattern: &'static str,
+    /// Whether the rule only applies to the sources of the dependencies
+    dependencies_only: bool,
+}
+
+/// The rules, the cheatcodes are found everywhere and the unsafe patterns in the dependencies
+const RULES: [Rule; 9] = [
+    Rule {
+        name: "ffi",
+        description: "runs arbitrary commands on the machine that runs the tests",
+        severity: LintSeverity::Error,
+        pattern: r"\.(?P<name>ffi)\s*\(",
+        dependencies_only: false,
+    },
+    Rule {
+        name: "file-write",
+        description: "writes to the file system of the machine that runs the tests",
+        severity: LintSeverity::Error,
+        pattern: r"\.(?P<name>writeFile|writeLine|closeFile|removeFile)\s*\(",
+        dependencies_only: false,
+    },
+    Rule {
+        name: "file-read",
+        description: "reads from the file system of the machine that runs the tests",
+        severity: LintSeverity::Info,
+        pattern: r"\.(?P<name>readFile|readLine|getCode|deployCode)\s*\(",
+        dependencies_only: false,
+    },
+    Rule {
+        name: "etch",
+        description: "replaces the code of an account, the tests may not run the deployed code",
+        severity: LintSeverity::Warning,
+        pattern: r"\.(?P<name>etch)\s*\(",
+        dependencies_only: false,
+    },
+    Rule {
+        name: "broadcast",
+        description: "records transactions that `forge run --broadcast` signs and sends",
+        severity: LintSeverity::Warning,
+        pattern: r"\.(?P<name>broadcast|startBroadcast)\s*\(",
+        dependencies_only: false,
+    },
+    Rule {
+        name: "rpc-url",
+        description: "reads an rpc endpoint of the config, which may contain api keys",
+        severity: LintSeverity::Info,
+        pattern: r"\.(?P<name>rpcUrl)\s*\(",
+        dependencies_only: false,
+    },
+    Rule {
+        name: "selfdestruct",
+        description: "destroys the contract and sends its ether to any address",
+        severity: LintSeverity::Warning,
+        pattern: r"\b(?P<name>selfdestruct)\s*\(",
+        dependencies_only: true,
+    },
+    Rule {
+        name: "delegatecall",
+        description: "runs the code of another contract on the contract's storage",
+        severity: LintSeverity::Warning,
+        pattern: r"\.(?P<name>delegatecall)\s*\(",
+        dependencies_only: true,
+    },
+    Rule {
+        name: "assembly",
+        description: "bypasses the safety checks of solidity",
+        severity: LintSeverity::Info,
+        pattern: r#"\b(?P<name>assembly)\s*["({]"#,
+        dependencies_only: true,
+    },
+];
+
+static PATTERNS: Lazy<Vec<Regex>> =
+    Lazy::new(|| RULES.iter().map(|rule| Regex::new(rule.pattern).unwrap()).collect());
+
+/// The hooks of the config, which are found in the config instead of the sources, the pattern is
+/// the key of a hook in the config file
+const HOOKS: Rule = Rule {
+    name: "hooks",
+    description: "runs a command on the machine that runs forge with `--allow-hooks`",
+    severity: LintSeverity::Warning,
+    pattern: r"(?m)^\s*(?P<name>(pre|post)_(build|test|broadcast))\s*=",
+    dependencies_only: false,
+};
+
+static HOOKS_PATTERN: Lazy<Regex> = Lazy::new(|| Regex::new(HOOKS.pattern).unwrap());
+
+#[derive(Debug, Clone, Parser)]
+pub struct GeigerArgs {
+    #[clap(
+        help = "the project's root path, by default the root of the current git repository or the current working directory",
+        long,
+        value_hint = ValueHint::DirPath
+    )]
+    pub root: Option<PathBuf>,
+
+    #[clap(help = "don't scan the sources of the dependencies", long)]
+    pub no_deps: bool,
+
+    #[clap(
+        help = "fail if there are findings of at least this severity",
+        long,
+        default_value = "error",
+        possible_values = &["info", "warning", "error"]
+    )]
+    pub fail_on: LintSeverity,
+
+    #[clap(help = "print the findings in the SARIF format, for code scanning in CI", long)]
+    pub sarif: bool,
+}
+
+impl Cmd for GeigerArgs {
+    type Output = ();
+
+    fn run(self) -> eyre::Result<Self::Output> {
+        let root = match self.root {
+            Some(root) => root,
+            None => find_project_root_path()?,
+        };
+        let config = Config::try_load_with_root(&root)?.sanitized();
+        let severity = |rule: &Rule| config.geiger.get(rule.name).copied().unwrap_or(rule.severity);
+
+        // the sources aren't compiled, third party code may not even compile
+        // whether the file is a dependency, by file, the tests may be within the sources
+        let mut files = BTreeMap::new();
+        for dir in [&config.src, &config.test] {
+            files.extend(sol_files(dir)?.into_iter().map(|file| (file, false)));
+        }
+        if !self.no_deps {
+            for lib in &config.libs {
+                for file in sol_files(lib)? {
+                    files.entry(file).or_insert(true);
+                }
+            }
+        }
+
+        let mut findings = BTreeSet::new();
+        // the number of findings by rule, in the project and in the dependencies
+        let mut counts = BTreeMap::<&str, (usize, usize)>::new();
+        for (file, is_dependency) in &files {
+            let source = std::fs::read_to_string(file)?;
+            for (offset, rule, name) in scan(&source, *is_dependency) {
+                let severity = severity(rule);
+                if severity == LintSeverity::Off {
+                    continue
+                }
+                let (line, column) = line_column(&source, offset);
+                let inserted = findings.insert(Finding {
+                    file: relative(&file.display().to_string(), &config.__root.0),
+                    line,
+                    column,
+                    severity,
+                    lint: rule.name.to_string(),
+                    message: format!("`{}` {}", name, rule.description),
+                });
+                if inserted {
+                    let count = counts.entry(rule.name).or_default();
+                    if *is_dependency {
+                        count.1 += 1;
+                    } else {
+                        count.0 += 1;
+                    }
+                }
+            }
+        }
+
+        // the hooks run commands like `ffi`, without a test calling them
+        let severity = severity(&HOOKS);
+        if severity != LintSeverity::Off {
+            let config_file = std::fs::read_to_string(config.__root.0.join(Config::FILE_NAME))
+                .unwrap_or_default();
+            for hook in Hook::ALL {
+                let command = match hook.command(&config.hooks) {
+                    Some(command) => command,
+                    None => continue,
+                };
+                let (line, column) = line_column(&config_file, hook_offset(&config_file, hook));
+                let message =
+                    format!("the {} hook `{}` {}", hook.name(), command, HOOKS.description);
+                findings.insert(Finding {
+                    file: Config::FILE_NAME.to_string(),
+                    line,
+                    column,
+                    severity,
+                    lint: HOOKS.name.to_string(),
+                    message,
+                });
+                counts.entry(HOOKS.name).or_default().0 += 1;
+            }
+        }
+
+        if self.sarif {
+            let rules = RULES
+                .iter()
+                .chain([&HOOKS])
+                .map(|rule| (rule.name, rule.description))
+                .collect::<Vec<_>>();
+            shell::print_json(&sarif("forge geiger", &rules, &findings))?;
+        } else if shell::is_json() {
+            shell::print_json(&findings)?;
+        } else {
+            findings.iter().for_each(print_finding);
+            if !counts.is_empty() {
+                sh_println!();
+                sh_println!("{:<14} {:>8} {:>13}", "Rule", "Project", "Dependencies");
+                for (rule, (project, dependencies)) in &counts {
+                    sh_println!("{:<14} {:>8} {:>13}", rule, project, dependencies);
+                }
+            }
+        }
+
+        let failing = findings.iter().filter(|finding| finding.severity >= self.fail_on).count();
+        if failing > 0 {
+            return Err(ExitCode::Failure
+                .error(format!("{} findings of at least {} severity", failing, self.fail_on)))
+        }
+        sh_status!("scanned {} files, {} findings", files.len(), findings.len());
+        Ok(())
+    }
+}
+
+/// Returns all solidity files in the directory and its subdirectories
+fn sol_files(dir: &Path) -> eyre::Result<Vec<PathBuf>> {
+    if !dir.exists() {
+        return Ok(Vec::new())
+    }
+    let pattern = format!("{}/**/*.sol", dir.display());
+    Ok(glob::glob(&pattern)?.filter_map(Result::ok).collect())
+}
+
+/// Returns the byte offset, the rule and the cheatcode or keyword of every usage in the source
+fn scan(source: &str, is_dependency: bool) -> Vec<(usize, &'static Rule, String)> {
+    let code = blank_comments_and_strings(source);
+    let mut found = Vec::new();
+    for (rule, pattern) in RULES.iter().zip(PATTERNS.iter()) {
+        if rule.dependencies_only && !is_dependency {
+            continue
+        }
+        for captures in pattern.captures_iter(&code) {
+            let name = captures.name("name").expect("every pattern has a name group");
+            found.push((name.start(), rule, name.as_str().to_string()));
+        }
+    }
+    found
+}
+
+/// Returns the byte offset of the hook's key in the config file, 0 if the hook is set elsewhere,
+/// like in the env
+fn hook_offset(config_file: &str, hook: Hook) -> usize {
+    HOOKS_PATTERN
+        .captures_iter(config_file)
+        .filter_map(|captures| captures.name("name"))
+        .find(|name| name.as_str() == hook.name())
+        .map_or(0, |name| name.start())
+}
+
+/// Replaces the comments and the contents of string literals with spaces, keeping the offsets and
+/// the line breaks
+fn blank_comments_and_strings(source: &str) -> String {
+    let bytes = source.as_bytes();
+    let mut code = bytes.to_vec();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'/' if bytes.get(i + 1) == Some(&b'/') => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    code[i] = b' ';
+                    i += 1;
+                }
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                let end = source[i + 2..].find("*/").map_or(bytes.len(), |end| i + 2 + end + 2);
+                for byte in &mut code[i..end] {
+                    if *byte != b'\n' {
+                        *byte = b' ';
+                    }
+                }
+                i = end;
+            }
+            quote @ (b'"' | b'\'') => {
+                i += 1;
+                while i < bytes.len() && bytes[i] != quote && bytes[i] != b'\n' {
+                    let len = if bytes[i] == b'\\' { 2 } else { 1 };
+                    for byte in code.iter_mut().skip(i).take(len) {
+                        *byte = b' ';
+                    }
+                    i += len;
+                }
+                i += 1;
+            }
+            _ => i += 1,
+        }
+    }
+    // only whole utf-8 sequences within comments and strings are replaced
+    String::from_utf8(code).expect("valid utf-8")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blanks_comments_and_strings() {
+        let source = "a // vm.ffi(x)\nb /* é\n */ c \"vm.etch(\\\"\" d";
+        let code = blank_comments_and_strings(source);
+        assert_eq!(code.len(), source.len());
+        let blank = |len: usize| " ".repeat(len);
+        assert_eq!(
+            code,
+            format!("a{}\nb{}\n{}c \"{}\" d", blank(13), blank(6), blank(4), blank(10))
+        );
+    }
+
+    #[test]
+    fn finds_cheatcodes_and_unsafe_patterns() {
+        let source = r#"
+contract T {
+    function test() public {
+        // vm.ffi(inputs);
+        vm.ffi(inputs);
+        vm.etch (address(1), code);
+        assembly { sstore(0, 1) }
+        string memory s = "selfdestruct(x)";
+    }
+}
+"#;
+        let found = |is_dependency| {
+            scan(source, is_dependency)
+                .into_iter()
+                .map(|(offset, rule, name)| (line_column(source, offset).0, rule.name, name))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            found(false),
+            vec![(5, "ffi", "ffi".to_string()), (6, "etch", "etch".to_string())]
+        );
+        assert_eq!(found(true).len(), 3);
+        assert_eq!(found(true)[2], (7, "assembly", "assembly".to_string()));
+    }
+
+    #[test]
+    fn finds_hooks_in_the_config_file() {
+        let config_file = "[default]\nsrc = 'src'\n\n[default.hooks]\n  post_test = 'notify'\n";
+        assert_eq!(line_column(config_file, hook_offset(config_file, Hook::PostTest)), (5, 3));
+        assert_eq!(hook_offset(config_file, Hook::PreBuild), 0);
+    }
+}
@@ -0,0 +1,637 @@
+//! generate command
+
+use crate::{
+    cmd::{
+        inspect::{self, ContractArtifactField},
+        Cmd,
+    },
+    explorer::explorer,
+    opts::{evm::chain_and_latest_block, forge::ContractInfo, ClapChain},
+    shell::{sh_status, sh_warn},
+};
+use clap::{Parser, Subcommand, ValueHint};
+use ethers::{types::Address, utils::to_checksum};
+use foundry_config::{find_project_root_path, ChainPreset, Config};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+/// Command to generate test contracts
+#[derive(Debug, Clone, Parser)]
+pub struct GenerateArgs {
+    #[clap(subcommand)]
+    pub sub: GenerateSubcommands,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum GenerateSubcommands {
+    #[clap(
+        about = "generates a test skeleton of a contract of the project, or a fork test of a deployed contract as a starting point for a proof of concept"
+    )]
+    Test(GenerateTestArgs),
+}
+
+#[derive(Debug, Clone, Parser)]
+pub struct GenerateTestArgs {
+    #[clap(
+        long,
+        help = "the contract of the project to test, `<path>:<contractname>` or `<contractname>`, which is deployed in `setUp` and gets a test per function",
+        required_unless_present = "target",
+        conflicts_with = "target"
+    )]
+    pub contract: Option<ContractInfo>,
+
+    #[clap(
+        long,
+        help = "the deployed contract, whose ABI is fetched from the explorer of the chain, to write a fork test for"
+    )]
+    pub target: Option<Address>,
+
+    #[clap(
+        long,
+        help = "the rpc url the latest block is fetched from, can also be the name of an endpoint in `rpc_endpoints`, defaults to the endpoint named like the chain",
+        requires = "target"
+    )]
+    #[clap(alias = "rpc-url")]
+    pub fork_url: Option<String>,
+
+    #[clap(
+        long,
+        help = "the name of the test contract, defaults to `<Name>Test`, or `<Name>Exploit` for a `--target`"
+    )]
+    pub name: Option<String>,
+
+    #[clap(long, default_value = "^0.8.10", help = "the pragma of the test file")]
+    pub pragma: String,
+
+    #[clap(
+        long,
+        short,
+        help = "the file the test is written to, defaults to `<test>/<Name>.t.sol`",
+        value_hint = ValueHint::FilePath
+    )]
+    pub output: Option<PathBuf>,
+
+    #[clap(long, help = "overwrite the output file if it exists")]
+    pub force: bool,
+
+    #[clap(long, env = "ETHERSCAN_API_KEY", help = "etherscan API key")]
+    pub etherscan_api_key: Option<String>,
+
+    #[clap(flatten)]
+    pub chain: ClapChain,
+}
+
+impl Cmd for GenerateArgs {
+    type Output = ();
+
+    fn run(self) -> eyre::Result<Self::Output> {
+        match self.sub {
+            GenerateSubcommands::Test(args) => args.run(),
+        }
+    }
+}
+
+impl Cmd for GenerateTestArgs {
+    type Output = ();
+
+    fn run(self) -> eyre::Result<Self::Output> {
+        let root = find_project_root_path()?;
+        let config = Config::try_load_with_root(&root)?.sanitized();
+        let default_output =
+            |contract_name: &str| root.join(&config.test).join(format!("{}.t.sol", contract_name));
+
+        let (name, output, source) = match (self.target, &self.contract) {
+            (Some(target), _) => {
+                let test = self.fork_test(&config, target)?;
+                let output =
+                    self.output.clone().unwrap_or_else(|| default_output(&test.contract_name));
+                (test.name.clone(), output, test.render(&self.pragma)?)
+            }
+            (None, Some(contract)) => {
+                let project = inspect::project_with_output_selection(&config)?;
+                let contracts = inspect::compile(&project)?;
+                let (file, output) = inspect::find_contract(&contracts, contract)?;
+                let bytecode = &ContractArtifactField::Bytecode.get(output)["object"];
+                if bytecode.as_str().map_or(true, str::is_empty) {
+                    eyre::bail!(
+                        "`{}` is abstract or an interface and can't be deployed",
+                        contract.name
+                    )
+                }
+
+                let test_output =
+                    self.output.clone().unwrap_or_else(|| default_output(&contract.name));
+                let test = ScaffoldTest {
+                    name: self.name.clone().unwrap_or_else(|| format!("{}Test", contract.name)),
+                    contract_name: contract.name.clone(),
+                    import: import_path(
+                        test_output.parent().unwrap_or(root.as_path()),
+                        &root.join(file),
+                    ),
+                    abi: ContractArtifactField::Abi.get(output).clone(),
+                };
+                (test.name.clone(), test_output, test.render(&self.pragma))
+            }
+            (None, None) => unreachable!("clap requires a `--contract` or a `--target`"),
+        };
+
+        write_test(&output, &source, self.force)?;
+        sh_status!("generated {} in {}", name, output.display());
+        Ok(())
+    }
+}
+
+impl GenerateTestArgs {
+    /// Fetches the ABI of the `target` and the latest block of the chain the test forks from
+    fn fork_test(&self, config: &Config, target: Address) -> eyre::Result<ForkTest> {
+        let chain_id = self.chain.inner as u64;
+        let explorer = explorer(config, chain_id, None, None, self.etherscan_api_key.clone())?;
+        let rt = tokio::runtime::Runtime::new().expect("could not start tokio rt");
+        let (contract_name, abi) = rt.block_on(explorer.contract_abi(target))?;
+
+        let chain_name = ChainPreset::with_id(chain_id).map(|chain| chain.name);
+        let url = self
+            .fork_url
+            .clone()
+            .or_else(|| {
+                chain_name.filter(|name| config.rpc_endpoints.contains_key(*name)).map(Into::into)
+            })
+            .or_else(|| std::env::var("ETH_RPC_URL").ok());
+        let block = match url {
+            Some(url) => {
+                let (fork_chain_id, latest) =
+                    chain_and_latest_block(&config.resolve_rpc_url(&url)?)?;
+                if fork_chain_id != chain_id {
+                    eyre::bail!(
+                        "the rpc url serves chain {}, not chain {}",
+                        fork_chain_id,
+                        chain_id
+                    )
+                }
+                Some(latest)
+            }
+            None => {
+                sh_warn!("no rpc url for chain {}, the fork block is not pinned", chain_id);
+                None
+            }
+        };
+
+        Ok(ForkTest {
+            name: self.name.clone().unwrap_or_else(|| format!("{}Exploit", contract_name)),
+            contract_name,
+            abi,
+            target,
+            chain_id,
+            chain_name: chain_name.map(str::to_string),
+            block,
+        })
+    }
+}
+
+/// Returns the path of `file` relative to the directory `dir`, as imported by a file in `dir`
+fn import_path(dir: &Path, file: &Path) -> String {
+    let dir = dir.components().collect::<Vec<_>>();
+    let file = file.components().collect::<Vec<_>>();
+    let common = dir.iter().zip(&file).take_while(|(a, b)| a == b).count();
+    let mut path = PathBuf::new();
+    for _ in common..dir.len() {
+        path.push("..");
+    }
+    path.extend(&file[common..]);
+    let path = path.to_string_lossy().replace('\\', "/");
+    if path.starts_with("..") {
+        path
+    } else {
+        format!("./{}", path)
+    }
+}
+
+/// Writes the source of a generated test, unless the file exists and `force` is not set
+fn write_test(output: &Path, source: &str, force: bool) -> eyre::Result<()> {
+    if output.exists() && !force {
+        eyre::bail!("{} already exists, use `--force` to overwrite it", output.display())
+    }
+    if let Some(parent) = output.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(output, source)?;
+    Ok(())
+}
+
+/// A fork test of a deployed contract
+#[derive(Debug, Clone)]
+struct ForkTest {
+    /// The name of the test contract
+    name: String,
+    /// The name of the verified contract
+    contract_name: String,
+    /// The JSON ABI of the verified contract
+    abi: serde_json::Value,
+    target: Address,
+    chain_id: u64,
+    /// The name of the chain if it's a well-known chain, used for `--chain`
+    chain_name: Option<String>,
+    /// The block the test forks from, the latest block when it was generated
+    block: Option<u64>,
+}
+
+impl ForkTest {
+    /// Returns the source of the test file, with the interface of the contract, the cheatcodes
+    /// the test uses and the test contract
+    fn render(&self, pragma: &str) -> eyre::Result<String> {
+        let interface_name = format!("I{}", self.contract_name);
+        let interface = foundry_utils::abi_to_solidity(&self.abi, &interface_name)?;
+        let chain = self.chain_name.clone().unwrap_or_else(|| self.chain_id.to_string());
+
+        let mut run = format!("forge test --chain {}", chain);
+        if let Some(block) = self.block {
+            run.push_str(&format!(" --fork-block-number {}", block));
+        }
+        run.push_str(&format!(" --match-contract {} -vvvv", self.name));
+
+        let mut source = format!(
+            r#"// SPDX-License-Identifier: UNLICENSED
+pragma solidity {pragma};
+
+import "ds-test/test.sol";
+
+// Generated by `forge generate test` for {contract_name} at {target} on chain {chain_id}.
+// Run it against a fork with:
+//   {run}
+
+{interface}
+interface Vm {{
+    function deal(address, uint256) external;
+    function prank(address) external;
+    function startPrank(address) external;
+    function stopPrank() external;
+}}
+
+contract {name} is DSTest {{
+    Vm constant vm = Vm(HEVM_ADDRESS);
+
+"#,
+            pragma = pragma,
+            contract_name = self.contract_name,
+            target = to_checksum(&self.target, None),
+            chain_id = self.chain_id,
+            run = run,
+            interface = interface,
+            name = self.name,
+        );
+        if let Some(block) = self.block {
+            source.push_str(&format!("    uint256 constant FORK_BLOCK = {};\n", block));
+        }
+        source.push_str(&format!(
+            r#"    address constant TARGET = {target};
+    address constant ATTACKER = address(0xBAD);
+
+    {interface_name} target = {interface_name}(TARGET);
+
+    function setUp() public {{
+        assertEq(block.chainid, {chain_id});
+"#,
+            target = to_checksum(&self.target, None),
+            interface_name = interface_name,
+            chain_id = self.chain_id,
+        ));
+        if self.block.is_some() {
+            source.push_str("        assertEq(block.number, FORK_BLOCK);\n");
+        }
+        source.push_str(
+            r#"        vm.deal(ATTACKER, 100 ether);
+
+        emit log_named_address("target", TARGET);
+        emit log_named_address("attacker", ATTACKER);
+    }
+
+    function testExploit() public {
+        vm.startPrank(ATTACKER);
+
+        vm.stopPrank();
+    }
+}
+"#,
+        );
+        Ok(source)
+    }
+}
+
+/// A test skeleton of a contract of the project
+#[derive(Debug, Clone)]
+struct ScaffoldTest {
+    /// The name of the test contract
+    name: String,
+    /// The name of the tested contract
+    contract_name: String,
+    /// The path the test file imports the contract from
+    import: String,
+    /// The JSON ABI of the tested contract
+    abi: serde_json::Value,
+}
+
+impl ScaffoldTest {
+    /// Returns the source of the test file, with a `setUp` that deploys the contract, an empty
+    /// test for every function and a fuzz test for every function with params
+    ///
+    /// The constructor is called with the zero values of its params, params that have no literal
+    /// zero value, like structs and fixed size arrays, are passed as zero-initialized locals.
+    fn render(&self, pragma: &str) -> String {
+        let items = self.abi.as_array().map(Vec::as_slice).unwrap_or_default();
+        let var = lower_first(&self.contract_name);
+        let mut locals = String::new();
+        let mut constructor_args = Vec::new();
+        if let Some(constructor) = items.iter().find(|item| item["type"] == "constructor") {
+            for (i, param) in params(constructor).iter().enumerate() {
+                match zero_value(param) {
+                    Some(value) => constructor_args.push(value),
+                    None => {
+                        let name = param.name_or(i);
+                        locals.push_str(&format!("        {} memory {};\n", param.ty, name));
+                        constructor_args.push(name);
+                    }
+                }
+            }
+        }
+
+        let mut source = format!(
+            r#"// SPDX-License-Identifier: UNLICENSED
+pragma solidity {pragma};
+
+import "ds-test/test.sol";
+import "{import}";
+
+contract {name} is DSTest {{
+    {contract_name} {var};
+
+    function setUp() public {{
+{locals}        {var} = new {contract_name}({args});
+    }}
+"#,
+            pragma = pragma,
+            import = self.import,
+            name = self.name,
+            contract_name = self.contract_name,
+            locals = locals,
+            var = var,
+            args = constructor_args.join(", "),
+        );
+
+        // overloaded functions get a numbered suffix, like `testTransfer2`
+        let mut seen = BTreeMap::<String, usize>::new();
+        for function in items.iter().filter(|item| item["type"] == "function") {
+            let mut name = upper_first(function["name"].as_str().unwrap_or_default());
+            let count = seen.entry(name.clone()).or_default();
+            *count += 1;
+            if *count > 1 {
+                name = format!("{}{}", name, count);
+            }
+            source.push_str(&format!("\n    function test{}() public {{}}\n", name));
+
+            let params = params(function);
+            if !params.is_empty() {
+                let params = params
+                    .iter()
+                    .enumerate()
+                    .map(|(i, param)| {
+                        let name = param.name_or(i);
+                        if param.is_reference() {
+                            format!("{} memory {}", param.ty, name)
+                        } else {
+                            format!("{} {}", param.ty, name)
+                        }
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                source.push_str(&format!(
+                    "\n    function testFuzz{}({}) public {{}}\n",
+                    name, params
+                ));
+            }
+        }
+        source.push_str("}\n");
+        source
+    }
+}
+
+/// A param of a function or a constructor, with its solidity type
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Param {
+    name: String,
+    /// The ABI type, or the user defined type of its internal type like `Vault.Order` or `ERC20`
+    ty: String,
+    kind: ParamKind,
+}
+
+/// What the solidity type of a param is, according to its internal type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ParamKind {
+    Value,
+    Contract,
+    Enum,
+    Struct,
+}
+
+impl Param {
+    /// Whether the param is passed in `memory`
+    fn is_reference(&self) -> bool {
+        self.ty.ends_with(']') ||
+            self.ty == "string" ||
+            self.ty == "bytes" ||
+            self.kind == ParamKind::Struct
+    }
+
+    /// Returns the name of the param, or `arg{i}` if it is unnamed
+    fn name_or(&self, i: usize) -> String {
+        if self.name.is_empty() {
+            format!("arg{}", i)
+        } else {
+            self.name.clone()
+        }
+    }
+}
+
+/// Returns the inputs of an ABI item, typed as the contracts, enums and structs of their internal
+/// type
+fn params(item: &serde_json::Value) -> Vec<Param> {
+    item["inputs"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|input| {
+            let abi_ty = input["type"].as_str().unwrap_or_default();
+            let internal_ty = input["internalType"].as_str().unwrap_or_default();
+            let (ty, kind) = if let Some(ty) = internal_ty.strip_prefix("struct ") {
+                (ty, ParamKind::Struct)
+            } else if let Some(ty) = internal_ty.strip_prefix("contract ") {
+                (ty, ParamKind::Contract)
+            } else if let Some(ty) = internal_ty.strip_prefix("enum ") {
+                (ty, ParamKind::Enum)
+            } else {
+                (abi_ty, ParamKind::Value)
+            };
+            Param {
+                name: input["name"].as_str().unwrap_or_default().to_string(),
+                ty: ty.to_string(),
+                kind,
+            }
+        })
+        .collect()
+}
+
+/// Returns the literal zero value of a param, if it has one
+fn zero_value(param: &Param) -> Option<String> {
+    let ty = param.ty.as_str();
+    if let Some(element) = ty.strip_suffix("[]") {
+        return Some(format!("new {}[](0)", element))
+    }
+    if ty.ends_with(']') {
+        return None
+    }
+    let value = match param.kind {
+        ParamKind::Contract => format!("{}(address(0))", ty),
+        ParamKind::Enum => format!("{}(0)", ty),
+        ParamKind::Struct => return None,
+        ParamKind::Value => match ty {
+            "address" => "address(0)".to_string(),
+            "bool" => "false".to_string(),
+            "string" | "bytes" => "\"\"".to_string(),
+            ty if ty.starts_with("uint") || ty.starts_with("int") => "0".to_string(),
+            ty if ty.starts_with("bytes") => format!("{}(0)", ty),
+            _ => return None,
+        },
+    };
+    Some(value)
+}
+
+fn lower_first(s: &str) -> String {
+    let mut chars = s.chars();
+    chars.next().map(|c| c.to_lowercase().chain(chars).collect()).unwrap_or_default()
+}
+
+fn upper_first(s: &str) -> String {
+    let mut chars = s.chars();
+    chars.next().map(|c| c.to_uppercase().chain(chars).collect()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn renders_fork_test() {
+        let test = ForkTest {
+            name: "VaultExploit".to_string(),
+            contract_name: "Vault".to_string(),
+            abi: json!([{
+                "type": "function",
+                "name": "withdraw",
+                "inputs": [{ "name": "amount", "type": "uint256" }],
+                "outputs": [],
+                "stateMutability": "nonpayable"
+            }]),
+            target: "0x6b175474e89094c44da98b954eedeac495271d0f".parse().unwrap(),
+            chain_id: 1,
+            chain_name: Some("mainnet".to_string()),
+            block: Some(15_000_000),
+        };
+        let source = test.render("^0.8.10").unwrap();
+
+        assert!(source.contains(
+            "//   forge test --chain mainnet --fork-block-number 15000000 --match-contract VaultExploit -vvvv"
+        ));
+        assert!(
+            source.contains("interface IVault {\n    function withdraw(uint256 amount) external;")
+        );
+        assert!(source.contains("contract VaultExploit is DSTest {"));
+        assert!(source.contains("uint256 constant FORK_BLOCK = 15000000;"));
+        assert!(source
+            .contains("address constant TARGET = 0x6B175474E89094C44Da98b954EedeAC495271d0F;"));
+        assert!(source.contains("assertEq(block.number, FORK_BLOCK);"));
+
+        let unpinned = ForkTest { block: None, ..test }.render("^0.8.10").unwrap();
+        assert!(!unpinned.contains("FORK_BLOCK"));
+    }
+
+    #[test]
+    fn renders_test_skeleton() {
+        let test = ScaffoldTest {
+            name: "VaultTest".to_string(),
+            contract_name: "Vault".to_string(),
+            import: "../src/Vault.sol".to_string(),
+            abi: json!([
+                {
+                    "type": "constructor",
+                    "inputs": [
+                        { "name": "asset", "type": "address", "internalType": "contract ERC20" },
+                        {
+                            "name": "config",
+                            "type": "tuple",
+                            "internalType": "struct Vault.Config",
+                            "components": []
+                        },
+                        { "name": "mode", "type": "uint8", "internalType": "enum Vault.Mode" },
+                        { "name": "fees", "type": "uint16[2]", "internalType": "uint16[2]" }
+                    ],
+                    "stateMutability": "nonpayable"
+                },
+                {
+                    "type": "function",
+                    "name": "deposit",
+                    "inputs": [
+                        { "name": "assets", "type": "uint256", "internalType": "uint256" },
+                        { "name": "", "type": "bytes", "internalType": "bytes" }
+                    ],
+                    "outputs": [],
+                    "stateMutability": "nonpayable"
+                },
+                { "type": "function", "name": "pause", "inputs": [], "outputs": [], "stateMutability": "nonpayable" },
+                { "type": "function", "name": "pause", "inputs": [{ "name": "until", "type": "uint64" }], "outputs": [], "stateMutability": "nonpayable" },
+                { "type": "event", "name": "Deposit", "inputs": [], "anonymous": false }
+            ]),
+        };
+
+        assert_eq!(
+            test.render("^0.8.10"),
+            r#"// SPDX-License-Identifier: UNLICENSED
+pragma solidity ^0.8.10;
+
+import "ds-test/test.sol";
+import "../src/Vault.sol";
+
+contract VaultTest is DSTest {
+    Vault vault;
+
+    function setUp() public {
+        Vault.Config memory config;
+        uint16[2] memory fees;
+        vault = new Vault(ERC20(address(0)), config, Vault.Mode(0), fees);
+    }
+
+    function testDeposit() public {}
+
+    function testFuzzDeposit(uint256 assets, bytes memory arg1) public {}
+
+    function testPause() public {}
+
+    function testPause2() public {}
+
+    function testFuzzPause2(uint64 until) public {}
+}
+"#
+        );
+    }
+
+    #[test]
+    fn imports_relative_to_the_test() {
+        let root = Path::new("/project");
+        assert_eq!(
+            import_path(&root.join("test"), &root.join("src/Vault.sol")),
+            "../src/Vault.sol"
+        );
+        assert_eq!(import_path(&root.join("src"), &root.join("src/Vault.sol")), "./Vault.sol");
+    }
+}
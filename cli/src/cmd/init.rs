@@ -2,8 +2,9 @@
 
 use crate::{
     cmd::{install::install, Cmd},
+    exit_code::ExitCode,
     opts::forge::Dependency,
-    utils::p_println,
+    shell::{self, sh_status, sh_warn},
 };
 use clap::{Parser, ValueHint};
 use foundry_config::Config;
@@ -25,18 +26,16 @@ pub struct InitArgs {
     value_hint = ValueHint::DirPath
     )]
     root: Option<PathBuf>,
-    #[clap(help = "optional solidity template to start from", long, short)]
-    template: Option<String>,
     #[clap(
-        help = "initialize without creating a git repository",
-        conflicts_with = "template",
-        long
+        help = "optional template repository to start from, like `owner/repo`, `owner/repo@tag`, a git url or a local path",
+        long,
+        short
     )]
+    template: Option<String>,
+    #[clap(help = "initialize without creating a git repository", long)]
     no_git: bool,
-    #[clap(help = "do not create initial commit", conflicts_with = "template", long)]
+    #[clap(help = "do not create initial commit", long)]
     no_commit: bool,
-    #[clap(help = "do not print messages", short, long)]
-    quiet: bool,
     #[clap(
         help = "run without installing libs from the network",
         conflicts_with = "template",
@@ -44,7 +43,7 @@ pub struct InitArgs {
         alias = "no-deps"
     )]
     offline: bool,
-    #[clap(help = "force init if project dir is not empty", conflicts_with = "template", long)]
+    #[clap(help = "force init if project dir is not empty", long)]
     force: bool,
 }
 
@@ -52,7 +51,7 @@ impl Cmd for InitArgs {
     type Output = ();
 
     fn run(self) -> eyre::Result<Self::Output> {
-        let InitArgs { root, template, no_git, no_commit, quiet, offline, force } = self;
+        let InitArgs { root, template, no_git, no_commit, offline, force } = self;
 
         let root = root.unwrap_or_else(|| std::env::current_dir().unwrap());
         // create the root dir if it does not exist
@@ -61,28 +60,33 @@ impl Cmd for InitArgs {
         }
         let root = dunce::canonicalize(root)?;
 
-        // if a template is provided, then this command is just an alias to `git clone <url>
-        // <path>`
+        // check if target is empty
+        if !force && root.read_dir().map(|mut i| i.next().is_some()).unwrap_or(false) {
+            shell::with_shell(|shell| {
+                shell.error(
+                    "`forge init` cannot be run on a non-empty directory.
+
+        run `forge init --force` to initialize regardless.",
+                )
+            });
+            ExitCode::ConfigError.exit()
+        }
+
         if let Some(ref template) = template {
-            p_println!(!quiet => "Initializing {} from {}...", root.display(), template);
-            Command::new("git")
-                .args(&["clone", template, &root.display().to_string()])
-                .stdout(Stdio::piped())
-                .spawn()?
-                .wait()?;
-        } else {
-            // check if target is empty
-            if !force && root.read_dir().map(|mut i| i.next().is_some()).unwrap_or(false) {
-                eprintln!(
-                    r#"{}: `forge init` cannot be run on a non-empty directory.
-
-        run `forge init --force` to initialize regardless."#,
-                    Colour::Red.paint("error")
-                );
-                std::process::exit(1);
+            sh_status!("Initializing {} from {}...", root.display(), template);
+            let submodules = init_from_template(&root, template, no_git)?;
+            if !no_git {
+                init_git_repo(&root, true)?;
+                for submodule in submodules {
+                    submodule.add(&root)?;
+                }
+                if !no_commit {
+                    git(&root, &["add", "."])?;
+                    git(&root, &["commit", "-m", &format!("chore: forge init from {}", template)])?;
+                }
             }
-
-            p_println!(!quiet => "Initializing {}...", root.display());
+        } else {
+            sh_status!("Initializing {}...", root.display());
 
             // make the dirs
             let src = root.join("src");
@@ -99,7 +103,7 @@ impl Cmd for InitArgs {
             let dest = root.join(Config::FILE_NAME);
             if !dest.exists() {
                 // write foundry.toml
-                let mut config = Config::load_with_root(&root).into_basic();
+                let mut config = Config::try_load_with_root(&root)?.into_basic();
                 // add the ds-test remapping manually because we initialize before installing it
                 if !offline {
                     config
@@ -115,13 +119,13 @@ impl Cmd for InitArgs {
             }
 
             if !offline {
-                let opts = DependencyInstallOpts { no_git, no_commit, quiet };
+                let opts = DependencyInstallOpts { no_git, no_commit };
                 Dependency::from_str("https://github.com/dapphub/ds-test")
                     .and_then(|dependency| install(&root, vec![dependency], opts))?;
             }
         }
 
-        p_println!(!quiet => "    {} forge project.",   Colour::Green.paint("Initialized"));
+        sh_status!("    {} forge project.", shell::paint(Colour::Green, "Initialized"));
         Ok(())
     }
 }
@@ -138,7 +142,9 @@ fn init_git_repo(root: &Path, no_commit: bool) -> eyre::Result<()> {
 
     if !is_git.success() {
         let gitignore_path = root.join(".gitignore");
-        std::fs::write(gitignore_path, include_str!("../../../assets/.gitignoreTemplate"))?;
+        if !gitignore_path.exists() {
+            std::fs::write(gitignore_path, include_str!("../../../assets/.gitignoreTemplate"))?;
+        }
 
         Command::new("git")
             .arg("init")
@@ -161,3 +167,151 @@ fn init_git_repo(root: &Path, no_commit: bool) -> eyre::Result<()> {
 
     Ok(())
 }
+
+/// A git submodule of a template
+struct TemplateSubmodule {
+    path: String,
+    url: String,
+    /// the commit the template pins the submodule to
+    commit: Option<String>,
+}
+
+impl TemplateSubmodule {
+    /// Adds the submodule to the repository at the root and checks out its pinned commit
+    fn add(&self, root: &Path) -> eyre::Result<()> {
+        git(root, &["submodule", "add", &self.url, &self.path])?;
+        if let Some(ref commit) = self.commit {
+            let path = root.join(&self.path);
+            git(&path, &["checkout", commit])?;
+            git(&path, &["submodule", "update", "--init", "--recursive"])?;
+        }
+        Ok(())
+    }
+}
+
+/// Copies the files of the template into the root, the template's history is not kept.
+///
+/// Files that already exist in the root are kept and reported. If `no_git` is set the template's
+/// submodules are copied as ordinary folders, otherwise the submodules are returned so they can be
+/// added to the new repository.
+fn init_from_template(
+    root: &Path,
+    template: &str,
+    no_git: bool,
+) -> eyre::Result<Vec<TemplateSubmodule>> {
+    let (url, tag) = if Path::new(template).is_dir() {
+        (template.to_string(), None)
+    } else {
+        let dependency = Dependency::from_str(template)?;
+        (dependency.url, dependency.tag)
+    };
+
+    // removed when dropped, also if the template can't be cloned
+    let tmp_dir = tempfile::TempDir::new()?;
+    let tmp = tmp_dir.path();
+    let tmp_path = tmp.display().to_string();
+    let mut args = vec!["clone", "--quiet"];
+    // a shallow clone can't check out an arbitrary commit
+    if tag.is_none() {
+        args.extend(["--depth", "1"]);
+    }
+    if no_git {
+        args.push("--recursive");
+    }
+    args.extend([url.as_str(), tmp_path.as_str()]);
+    git(root, &args)?;
+    if let Some(ref tag) = tag {
+        git(tmp, &["checkout", "--quiet", tag])?;
+        if no_git {
+            git(tmp, &["submodule", "update", "--init", "--recursive"])?;
+        }
+    }
+
+    let submodules = if no_git { Vec::new() } else { template_submodules(tmp)? };
+    let skip = submodules.iter().map(|submodule| tmp.join(&submodule.path)).collect::<Vec<_>>();
+    let existing = copy_dir(tmp, root, &skip)?;
+    for path in &existing {
+        sh_warn!("kept the existing {}", path.strip_prefix(root).unwrap_or(path).display());
+    }
+    let gitmodules = root.join(".gitmodules");
+    if !submodules.is_empty() && !existing.contains(&gitmodules) {
+        // recreated when the submodules are added
+        std::fs::remove_file(gitmodules)?;
+    }
+    Ok(submodules)
+}
+
+/// Returns the submodules declared in the `.gitmodules` of the repository and their commits
+fn template_submodules(repo: &Path) -> eyre::Result<Vec<TemplateSubmodule>> {
+    let gitmodules = repo.join(".gitmodules");
+    if !gitmodules.is_file() {
+        return Ok(Vec::new())
+    }
+    let content = std::fs::read_to_string(gitmodules)?;
+    let mut submodules: Vec<TemplateSubmodule> = Vec::new();
+    let mut path = None;
+    for line in content.lines().map(str::trim) {
+        match line.split_once('=').map(|(key, value)| (key.trim(), value.trim())) {
+            Some(("path", value)) => path = Some(value.to_string()),
+            Some(("url", value)) => {
+                if let Some(path) = path.take() {
+                    submodules.push(TemplateSubmodule {
+                        path,
+                        url: value.to_string(),
+                        commit: None,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    for submodule in submodules.iter_mut() {
+        // `160000 commit <hash>\t<path>`
+        let output = Command::new("git")
+            .args(&["ls-tree", "HEAD", &submodule.path])
+            .current_dir(repo)
+            .output()?;
+        submodule.commit =
+            String::from_utf8_lossy(&output.stdout).split_whitespace().nth(2).map(str::to_string);
+    }
+    Ok(submodules)
+}
+
+/// Recursively copies the contents of `from` into `to`, skipping git directories and the `skip`
+/// paths
+///
+/// Files that already exist in `to` aren't overwritten, their paths are returned.
+fn copy_dir(from: &Path, to: &Path, skip: &[PathBuf]) -> eyre::Result<Vec<PathBuf>> {
+    std::fs::create_dir_all(to)?;
+    let mut existing = Vec::new();
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_name() == ".git" || skip.contains(&path) {
+            continue
+        }
+        let dest = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            existing.extend(copy_dir(&path, &dest, skip)?);
+        } else if dest.exists() {
+            existing.push(dest);
+        } else {
+            std::fs::copy(&path, &dest)?;
+        }
+    }
+    Ok(existing)
+}
+
+/// Runs git with the `args` in the `dir`, fails if git exits with an error
+fn git(dir: &Path, args: &[&str]) -> eyre::Result<()> {
+    let output = Command::new("git").args(args).current_dir(dir).output()?;
+    if !output.status.success() {
+        eyre::bail!(
+            "`git {}` failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+    }
+    Ok(())
+}
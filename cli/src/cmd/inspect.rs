@@ -0,0 +1,398 @@
+//! Inspect command
+
+use crate::{
+    cmd::{build::BuildArgs, Cmd},
+    exit_code::ExitCode,
+    opts::forge::ContractInfo,
+    shell::{self, sh_println, sh_status},
+};
+use ansi_term::Colour;
+use clap::Parser;
+use ethers::solc::Project;
+use foundry_config::Config;
+use foundry_utils::metadata::SolcMetadata;
+use serde_json::Value;
+use std::{collections::BTreeMap, fmt, str::FromStr};
+
+#[derive(Debug, Clone, Parser)]
+pub struct InspectArgs {
+    #[clap(help = "contract source info `<path>:<contractname>` or `<contractname>`")]
+    pub contract: ContractInfo,
+
+    #[clap(
+        help = "the artifact field to inspect",
+        possible_values = ContractArtifactField::VARIANTS
+    )]
+    pub field: ContractArtifactField,
+
+    #[clap(
+        long,
+        help = "diffs the storage layout against another contract's and fails if the layout of the other contract is not preserved, e.g. `forge inspect NewImpl storage-layout --compare OldImpl`"
+    )]
+    pub compare: Option<ContractInfo>,
+
+    #[clap(flatten)]
+    opts: BuildArgs,
+}
+
+impl Cmd for InspectArgs {
+    type Output = ();
+
+    fn run(self) -> eyre::Result<Self::Output> {
+        let InspectArgs { contract, field, compare, opts } = self;
+        if compare.is_some() && field != ContractArtifactField::StorageLayout {
+            eyre::bail!("`--compare` is only supported for the storage layout")
+        }
+
+        let config = Config::try_from_args(&opts)?;
+        let project = project_with_output_selection(&config)?;
+        let contracts = compile(&project)?;
+
+        let (_, value) = find_contract(&contracts, &contract)?;
+        if let Some(other) = compare {
+            let (_, other) = find_contract(&contracts, &other)?;
+            let diff = StorageLayoutDiff::new(
+                StorageSlot::read_all(field.get(other)),
+                StorageSlot::read_all(field.get(value)),
+            );
+            sh_println!("{}", diff.to_string().trim_end());
+            if !diff.is_compatible() {
+                eyre::bail!("the storage layout of `{}` is not compatible", contract.name)
+            }
+            sh_status!("storage layout is compatible");
+            return Ok(())
+        }
+
+        let value = field.get(value);
+        if shell::is_json() {
+            shell::print_json(value)?;
+        } else {
+            sh_println!("{}", field.format(value, &contract.name)?);
+        }
+        Ok(())
+    }
+}
+
+/// The compiler output fields of a contract that can be inspected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContractArtifactField {
+    Abi,
+    StorageLayout,
+    MethodIdentifiers,
+    Bytecode,
+    DeployedBytecode,
+    Assembly,
+    Metadata,
+}
+
+impl ContractArtifactField {
+    const VARIANTS: &'static [&'static str] = &[
+        "abi",
+        "storage-layout",
+        "method-identifiers",
+        "bytecode",
+        "deployed-bytecode",
+        "assembly",
+        "metadata",
+    ];
+
+    /// The solc output selection the field is emitted under
+    fn output_selection(&self) -> &'static str {
+        match self {
+            ContractArtifactField::Abi => "abi",
+            ContractArtifactField::StorageLayout => "storageLayout",
+            ContractArtifactField::MethodIdentifiers => "evm.methodIdentifiers",
+            ContractArtifactField::Bytecode => "evm.bytecode",
+            ContractArtifactField::DeployedBytecode => "evm.deployedBytecode",
+            ContractArtifactField::Assembly => "evm.assembly",
+            ContractArtifactField::Metadata => "metadata",
+        }
+    }
+
+    /// Returns the field's value in the json output of a contract
+    pub(crate) fn get<'a>(&self, contract: &'a Value) -> &'a Value {
+        self.output_selection().split('.').fold(contract, |value, key| &value[key])
+    }
+
+    /// Formats the field's value for humans
+    fn format(&self, value: &Value, name: &str) -> eyre::Result<String> {
+        Ok(match self {
+            ContractArtifactField::Abi => foundry_utils::abi_to_solidity(value, name)?,
+            ContractArtifactField::StorageLayout => StorageSlot::read_all(value)
+                .iter()
+                .map(|slot| format!("{}\n", slot))
+                .collect::<String>()
+                .trim_end()
+                .to_string(),
+            ContractArtifactField::MethodIdentifiers => {
+                let mut methods = value
+                    .as_object()
+                    .into_iter()
+                    .flatten()
+                    .map(|(sig, selector)| (selector.as_str().unwrap_or_default(), sig))
+                    .collect::<Vec<_>>();
+                methods.sort();
+                methods
+                    .into_iter()
+                    .map(|(selector, sig)| format!("0x{}  {}", selector, sig))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            }
+            ContractArtifactField::Bytecode | ContractArtifactField::DeployedBytecode => {
+                let object = value["object"].as_str().unwrap_or_default();
+                format!("0x{}", object.trim_start_matches("0x"))
+            }
+            ContractArtifactField::Assembly => value.as_str().unwrap_or_default().to_string(),
+            ContractArtifactField::Metadata => {
+                let metadata = SolcMetadata::from_output(&value)?.map(|metadata| metadata.json);
+                serde_json::to_string_pretty(&metadata.unwrap_or_default())?
+            }
+        })
+    }
+}
+
+impl FromStr for ContractArtifactField {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "abi" => Ok(ContractArtifactField::Abi),
+            "storage-layout" | "storagelayout" | "storage" => {
+                Ok(ContractArtifactField::StorageLayout)
+            }
+            "method-identifiers" | "methodidentifiers" | "methods" => {
+                Ok(ContractArtifactField::MethodIdentifiers)
+            }
+            "bytecode" | "bin" => Ok(ContractArtifactField::Bytecode),
+            "deployed-bytecode" | "deployedbytecode" | "bin-runtime" => {
+                Ok(ContractArtifactField::DeployedBytecode)
+            }
+            "assembly" | "asm" => Ok(ContractArtifactField::Assembly),
+            "metadata" => Ok(ContractArtifactField::Metadata),
+            _ => Err(format!("unknown contract artifact field: {}", s)),
+        }
+    }
+}
+
+/// Returns an ephemeral project of the config that emits every field that can be inspected
+pub(crate) fn project_with_output_selection(config: &Config) -> eyre::Result<Project> {
+    let mut project = config.ephemeral_no_artifacts_project()?;
+    project.cached = false;
+    project.no_artifacts = true;
+    let selection = [
+        ContractArtifactField::Abi,
+        ContractArtifactField::StorageLayout,
+        ContractArtifactField::MethodIdentifiers,
+        ContractArtifactField::Bytecode,
+        ContractArtifactField::DeployedBytecode,
+        ContractArtifactField::Assembly,
+        ContractArtifactField::Metadata,
+    ]
+    .iter()
+    .map(|field| field.output_selection().to_string())
+    .collect::<Vec<_>>();
+    project
+        .solc_config
+        .settings
+        .output_selection
+        .insert("*".to_string(), BTreeMap::from([("*".to_string(), selection)]));
+    Ok(project)
+}
+
+/// Compiles the project without printing anything, so the output can be piped, and returns the
+/// json output of every contract keyed by its source file
+pub(crate) fn compile(
+    project: &Project,
+) -> eyre::Result<BTreeMap<String, BTreeMap<String, Value>>> {
+    if !project.paths.sources.exists() {
+        eyre::bail!(
+            "no contracts to compile, contracts folder \"{}\" does not exist",
+            project.paths.sources.display()
+        )
+    }
+    let output = project.compile()?;
+    if output.has_compiler_errors() {
+        return Err(ExitCode::CompilationError.error(output))
+    }
+    let output = serde_json::to_value(output.output())?;
+    Ok(serde_json::from_value(output["contracts"].clone())?)
+}
+
+/// Finds the json output of the given contract and the source file that defines it, the path is
+/// required if the name is ambiguous
+pub(crate) fn find_contract<'a>(
+    contracts: &'a BTreeMap<String, BTreeMap<String, Value>>,
+    contract: &ContractInfo,
+) -> eyre::Result<(&'a str, &'a Value)> {
+    let mut matches = contracts
+        .iter()
+        .filter(|(file, _)| contract.path.as_ref().map(|path| file.ends_with(path)).unwrap_or(true))
+        .filter_map(|(file, contracts)| Some((file.as_str(), contracts.get(&contract.name)?)));
+    match (matches.next(), matches.next()) {
+        (Some(found), None) => Ok(found),
+        (Some(_), Some(_)) => eyre::bail!(
+            "contract `{}` is defined in multiple files, pass `<path>:<contractname>`",
+            contract.name
+        ),
+        (None, _) => eyre::bail!("could not find contract `{}`", contract.name),
+    }
+}
+
+/// A state variable in a contract's storage layout
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct StorageSlot {
+    pub(crate) label: String,
+    pub(crate) ty: String,
+    pub(crate) slot: String,
+    pub(crate) offset: u64,
+    pub(crate) bytes: String,
+    pub(crate) contract: String,
+}
+
+impl StorageSlot {
+    /// Reads all state variables of the solc `storageLayout` output
+    pub(crate) fn read_all(layout: &Value) -> Vec<Self> {
+        let types = &layout["types"];
+        layout["storage"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|storage| {
+                let ty = &types[storage["type"].as_str().unwrap_or_default()];
+                StorageSlot {
+                    label: storage["label"].as_str().unwrap_or_default().to_string(),
+                    ty: ty["label"].as_str().unwrap_or_default().to_string(),
+                    slot: storage["slot"].as_str().unwrap_or_default().to_string(),
+                    offset: storage["offset"].as_u64().unwrap_or_default(),
+                    bytes: ty["numberOfBytes"].as_str().unwrap_or_default().to_string(),
+                    contract: storage["contract"].as_str().unwrap_or_default().to_string(),
+                }
+            })
+            .collect()
+    }
+
+    /// Whether both variables occupy the same storage with the same type, renames are allowed
+    fn is_compatible(&self, other: &Self) -> bool {
+        self.ty == other.ty && self.slot == other.slot && self.offset == other.offset
+    }
+}
+
+impl fmt::Display for StorageSlot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:<24} {:<32} slot: {:<4} offset: {:<3} bytes: {:<4} {}",
+            self.label, self.ty, self.slot, self.offset, self.bytes, self.contract
+        )
+    }
+}
+
+/// The diff of the storage layout of a contract against the layout of another, e.g. the previous
+/// implementation of a proxy
+#[derive(Debug)]
+struct StorageLayoutDiff {
+    old: Vec<StorageSlot>,
+    new: Vec<StorageSlot>,
+}
+
+impl StorageLayoutDiff {
+    fn new(old: Vec<StorageSlot>, new: Vec<StorageSlot>) -> Self {
+        Self { old, new }
+    }
+
+    /// The new layout is compatible if it starts with all variables of the old layout
+    fn is_compatible(&self) -> bool {
+        self.old.len() <= self.new.len() &&
+            self.old.iter().zip(self.new.iter()).all(|(old, new)| old.is_compatible(new))
+    }
+}
+
+impl fmt::Display for StorageLayoutDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for idx in 0..self.old.len().max(self.new.len()) {
+            match (self.old.get(idx), self.new.get(idx)) {
+                (Some(old), Some(new)) if old == new => writeln!(f, "  {}", new)?,
+                (Some(old), Some(new)) if old.is_compatible(new) => {
+                    writeln!(f, "{}", shell::paint(Colour::Yellow, format!("~ {}", new)))?
+                }
+                (old, new) => {
+                    if let Some(old) = old {
+                        writeln!(f, "{}", shell::paint(Colour::Red, format!("- {}", old)))?;
+                    }
+                    if let Some(new) = new {
+                        writeln!(f, "{}", shell::paint(Colour::Green, format!("+ {}", new)))?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn slot(label: &str, ty: &str, slot: &str) -> StorageSlot {
+        StorageSlot {
+            label: label.to_string(),
+            ty: ty.to_string(),
+            slot: slot.to_string(),
+            offset: 0,
+            bytes: "32".to_string(),
+            contract: "src/Impl.sol:Impl".to_string(),
+        }
+    }
+
+    #[test]
+    fn can_read_storage_layout() {
+        let layout = serde_json::json!({
+            "storage": [{
+                "astId": 3,
+                "contract": "src/Counter.sol:Counter",
+                "label": "count",
+                "offset": 0,
+                "slot": "0",
+                "type": "t_uint256"
+            }],
+            "types": {
+                "t_uint256": { "encoding": "inplace", "label": "uint256", "numberOfBytes": "32" }
+            }
+        });
+        let slots = StorageSlot::read_all(&layout);
+        assert_eq!(slots.len(), 1);
+        assert_eq!(slots[0].label, "count");
+        assert_eq!(slots[0].ty, "uint256");
+        assert_eq!(slots[0].bytes, "32");
+    }
+
+    #[test]
+    fn can_diff_storage_layouts() {
+        let old = vec![slot("owner", "address", "0"), slot("count", "uint256", "1")];
+
+        let appended = vec![
+            slot("owner", "address", "0"),
+            slot("total", "uint256", "1"),
+            slot("paused", "bool", "2"),
+        ];
+        assert!(StorageLayoutDiff::new(old.clone(), appended).is_compatible());
+
+        let reordered = vec![slot("count", "uint256", "0"), slot("owner", "address", "1")];
+        assert!(!StorageLayoutDiff::new(old.clone(), reordered).is_compatible());
+
+        let removed = vec![slot("owner", "address", "0")];
+        assert!(!StorageLayoutDiff::new(old, removed).is_compatible());
+    }
+
+    #[test]
+    fn can_parse_artifact_fields() {
+        for field in ContractArtifactField::VARIANTS {
+            assert!(field.parse::<ContractArtifactField>().is_ok());
+        }
+        assert_eq!(
+            "storage".parse::<ContractArtifactField>().unwrap(),
+            ContractArtifactField::StorageLayout
+        );
+        assert!("unknown".parse::<ContractArtifactField>().is_err());
+    }
+}
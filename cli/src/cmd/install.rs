@@ -1,11 +1,19 @@
-//! Create command
+//! Install command
 use std::path::PathBuf;
 
-use crate::{cmd::Cmd, opts::forge::Dependency, utils::p_println};
+use crate::{
+    cmd::{remappings::REMAPPINGS_FILE, Cmd},
+    opts::forge::Dependency,
+    shell::{self, sh_status},
+};
 use ansi_term::Colour;
 use clap::{Parser, ValueHint};
-use foundry_config::find_project_root_path;
+use ethers::solc::remappings::Remapping;
+use eyre::WrapErr;
+use foundry_config::{find_project_root_path, remappings_from_newline};
+use serde::{Deserialize, Serialize};
 use std::{
+    collections::BTreeMap,
     path::Path,
     process::{Command, Stdio},
 };
@@ -14,7 +22,7 @@ use std::{
 #[derive(Debug, Clone, Parser)]
 pub struct InstallArgs {
     #[clap(
-        help = "installs one or more dependencies as git submodules (will install existing dependencies if no arguments are provided)"
+        help = "installs one or more dependencies as git submodules, like `owner/repo@tag`, or npm packages, like `@openzeppelin/contracts@4.8.0` or `npm:solmate` (will install existing dependencies if no arguments are provided)"
     )]
     dependencies: Vec<Dependency>,
     #[clap(flatten)]
@@ -43,72 +51,366 @@ pub struct DependencyInstallOpts {
     pub no_git: bool,
     #[clap(help = "do not create a commit", long)]
     pub no_commit: bool,
-    #[clap(help = "do not print messages", short, long)]
-    pub quiet: bool,
 }
 
 /// Installs all dependencies
+///
+/// The commit of every installed dependency is recorded in the `foundry.lock`, if no
+/// dependencies are provided the existing dependencies are installed at their locked commits.
 pub(crate) fn install(
     root: impl AsRef<Path>,
     dependencies: Vec<Dependency>,
     opts: DependencyInstallOpts,
 ) -> eyre::Result<()> {
+    let root = root.as_ref();
+    let mut lock = DependencyLock::read(root)?;
+
     if dependencies.is_empty() {
         let mut cmd = Command::new("git");
-        cmd.args(&["submodule", "update", "--init", "--recursive"]);
+        cmd.args(&["submodule", "update", "--init", "--recursive"]).current_dir(root);
         cmd.spawn()?.wait()?;
+
+        for (name, locked) in &lock.0 {
+            let path = root.join(LIBS).join(name);
+            if locked.npm && !path.exists() {
+                sh_status!("Fetching {}@{}", name, locked.rev);
+                install_from_registry(name, &locked.rev, root, &Path::new(LIBS).join(name))?;
+            } else if path.join(".git").exists() {
+                sh_status!("Checking out {} at {}", name, locked.rev);
+                checkout(&path, &locked.rev)?;
+            }
+        }
+        return Ok(())
     }
 
-    let root = root.as_ref();
-    let libs = Path::new("lib");
-    std::fs::create_dir_all(&libs)?;
+    let libs = Path::new(LIBS);
+    std::fs::create_dir_all(root.join(libs))?;
 
     for dep in dependencies {
-        let DependencyInstallOpts { no_git, no_commit, quiet } = opts;
+        let DependencyInstallOpts { no_git, no_commit } = opts;
         let path = libs.join(&dep.name);
-        p_println!(!quiet => "Installing {} in {:?}, (url: {}, tag: {:?})", dep.name, path, dep.url, dep.tag);
-        if no_git {
-            install_as_folder(&dep, &path)?;
+        sh_status!("Installing {} in {:?}, (url: {}, tag: {:?})", dep.name, path, dep.url, dep.tag);
+        let rev = if dep.npm {
+            let version = dep.tag.as_deref().unwrap_or("latest");
+            install_from_registry(&dep.name, version, root, &path)?
+        } else if no_git {
+            install_as_folder(&dep, root, &path)?
         } else {
-            install_as_submodule(&dep, root, &path, no_commit)?;
+            install_as_submodule(&dep, root, &path)?
+        };
+
+        lock.0.insert(
+            dep.name.clone(),
+            LockedDependency { url: dep.url.clone(), tag: dep.tag.clone(), rev, npm: dep.npm },
+        );
+        lock.write(root)?;
+        add_remappings(root, &path, dep.npm.then(|| dep.name.as_str()))?;
+
+        if !no_git && !no_commit {
+            let mut files = vec![path.display().to_string(), DependencyLock::FILE_NAME.to_string()];
+            if root.join(REMAPPINGS_FILE).exists() {
+                files.push(REMAPPINGS_FILE.to_string());
+            }
+            Command::new("git").arg("add").args(&files).current_dir(&root).spawn()?.wait()?;
+
+            let message = match dep.tag {
+                Some(ref tag) => format!("forge install: {}\n\n{}", dep.name, tag),
+                None => format!("forge install: {}", dep.name),
+            };
+            Command::new("git")
+                .args(&["commit", "-m", &message])
+                .current_dir(&root)
+                .stdout(Stdio::piped())
+                .spawn()?
+                .wait()?;
         }
 
-        p_println!(!quiet => "    {} {}",    Colour::Green.paint("Installed"), dep.name);
+        sh_status!("    {} {}", shell::paint(Colour::Green, "Installed"), dep.name);
     }
     Ok(())
 }
 
-/// installs the dependency as an ordinary folder instead of a submodule
-fn install_as_folder(dep: &Dependency, path: &Path) -> eyre::Result<()> {
+/// Updates the dependency installed at `lib`, or all dependencies if `None`, to the latest commit
+/// of their remote and records the new commits in the `foundry.lock`
+///
+/// Dependencies that were installed at a tag stay at the tag, npm packages are fetched again at
+/// the latest version that matches the requested version.
+pub(crate) fn update(root: impl AsRef<Path>, lib: Option<&Path>) -> eyre::Result<()> {
+    let root = root.as_ref();
+    let mut cmd = Command::new("git");
+    cmd.args(&["submodule", "update", "--remote", "--init", "--recursive"]).current_dir(root);
+    // if a lib is specified, open it
+    if let Some(lib) = lib {
+        cmd.args(&["--", lib.display().to_string().as_str()]);
+    }
+    cmd.spawn()?.wait()?;
+
+    let mut lock = DependencyLock::read(root)?;
+    for (name, locked) in lock.0.iter_mut() {
+        if lib.map(|lib| !lib.ends_with(name)).unwrap_or_default() {
+            continue
+        }
+        let path = root.join(LIBS).join(name);
+        if locked.npm {
+            std::fs::remove_dir_all(&path).ok();
+            let version = locked.tag.as_deref().unwrap_or("latest");
+            locked.rev = install_from_registry(name, version, root, &Path::new(LIBS).join(name))?;
+            continue
+        }
+        if !path.join(".git").exists() {
+            continue
+        }
+        if let Some(ref tag) = locked.tag {
+            checkout(&path, tag)?;
+        }
+        locked.rev = rev(&path)?;
+    }
+    lock.write(root)
+}
+
+/// Removes the dependencies, their submodules, remappings and entries in the `foundry.lock`
+pub(crate) fn remove(root: impl AsRef<Path>, dependencies: Vec<Dependency>) -> eyre::Result<()> {
+    let root = root.as_ref();
+    let mut lock = DependencyLock::read(root)?;
+
+    for dep in dependencies {
+        let path = Path::new(LIBS).join(&dep.name);
+        sh_status!("Removing {} in {:?}, (url: {}, tag: {:?})", dep.name, path, dep.url, dep.tag);
+
+        // remove the submodule entry from .git/config, .gitmodules and the index
+        for args in [&["submodule", "deinit", "-f"][..], &["rm", "-rf", "--quiet"][..]] {
+            Command::new("git")
+                .args(args)
+                .arg(&path)
+                .current_dir(root)
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .status()?;
+        }
+
+        // remove the submodule repository from the .git/modules directory
+        let git_mod_path = root.join(".git/modules").join(&path);
+        if git_mod_path.exists() {
+            std::fs::remove_dir_all(git_mod_path)?;
+        }
+
+        // dependencies installed without git are not tracked
+        if root.join(&path).exists() {
+            std::fs::remove_dir_all(root.join(&path))?;
+        }
+        // the scope directory of npm packages, like `lib/@openzeppelin`, if it's empty now
+        if let Some(parent) = path.parent().filter(|parent| *parent != Path::new(LIBS)) {
+            std::fs::remove_dir(root.join(parent)).ok();
+        }
+
+        lock.0.remove(&dep.name);
+        remove_remappings(root, &path)?;
+    }
+    lock.write(root)
+}
+
+/// The directory dependencies are installed in
+const LIBS: &str = "lib";
+
+/// The `foundry.lock` file which pins the commit of every installed dependency
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct DependencyLock(BTreeMap<String, LockedDependency>);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct LockedDependency {
+    url: String,
+    /// the tag, branch or commit the dependency was installed at
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tag: Option<String>,
+    /// the commit the dependency is checked out at, or the version of npm packages
+    rev: String,
+    /// whether the dependency is a package fetched from the npm registry
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    npm: bool,
+}
+
+impl DependencyLock {
+    const FILE_NAME: &'static str = "foundry.lock";
+
+    /// Reads the lock file of the root, which is empty if the file does not exist
+    fn read(root: &Path) -> eyre::Result<Self> {
+        let path = root.join(Self::FILE_NAME);
+        if !path.exists() {
+            return Ok(Default::default())
+        }
+        let content = std::fs::read_to_string(&path)?;
+        serde_json::from_str(&content)
+            .wrap_err_with(|| format!("failed to parse {}", path.display()))
+    }
+
+    fn write(&self, root: &Path) -> eyre::Result<()> {
+        std::fs::write(root.join(Self::FILE_NAME), serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+/// Returns the commit the repository at `path` is checked out at
+fn rev(path: &Path) -> eyre::Result<String> {
+    let output = Command::new("git").args(&["rev-parse", "HEAD"]).current_dir(path).output()?;
+    if !output.status.success() {
+        eyre::bail!("failed to read the commit of {}", path.display())
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Checks out the tag, branch or commit in the repository at `path`
+fn checkout(path: &Path, tag: &str) -> eyre::Result<()> {
+    let status = Command::new("git")
+        .args(&["checkout", "--quiet", "--recurse-submodules", tag])
+        .current_dir(path)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .status()?;
+    if !status.success() {
+        eyre::bail!("failed to check out {} in {}", tag, path.display())
+    }
+    Ok(())
+}
+
+/// Adds the remappings inferred for the dependency at `path` to the `remappings.txt`, projects
+/// without a `remappings.txt` infer the remappings when the config is loaded
+///
+/// npm packages are remapped by their package name, so imports like
+/// `@openzeppelin/contracts/token/ERC20/ERC20.sol` resolve without changes.
+fn add_remappings(root: &Path, path: &Path, package: Option<&str>) -> eyre::Result<()> {
+    let file = root.join(REMAPPINGS_FILE);
+    if !file.is_file() {
+        return Ok(())
+    }
+    let content = std::fs::read_to_string(&file)?;
+    let mut remappings = remappings_from_newline(&content).collect::<Result<Vec<_>, _>>()?;
+    let dependency = root.join(path);
+    let inferred = match package {
+        Some(package) => vec![Remapping {
+            name: format!("{}/", package),
+            path: dependency.display().to_string(),
+        }],
+        None => Remapping::find_many(root.join(LIBS)),
+    };
+    let added = inferred
+        .into_iter()
+        .filter(|r| Path::new(&r.path).starts_with(&dependency))
+        .filter(|r| !remappings.iter().any(|existing| existing.name == r.name))
+        .map(|mut r| {
+            r.path = format!("{}/", Path::new(&r.path).strip_prefix(root)?.display());
+            Ok(r)
+        })
+        .collect::<eyre::Result<Vec<_>>>()?;
+    if added.is_empty() {
+        return Ok(())
+    }
+    remappings.extend(added);
+    remappings.sort_by(|a, b| a.name.cmp(&b.name));
+    std::fs::write(file, remappings.iter().map(|r| format!("{}\n", r)).collect::<String>())?;
+    Ok(())
+}
+
+/// Removes the remappings that point into the dependency at `path` from the `remappings.txt`
+fn remove_remappings(root: &Path, path: &Path) -> eyre::Result<()> {
+    let file = root.join(REMAPPINGS_FILE);
+    if !file.is_file() {
+        return Ok(())
+    }
+    let content = std::fs::read_to_string(&file)?;
+    let remappings = remappings_from_newline(&content).collect::<Result<Vec<_>, _>>()?;
+    let kept = remappings
+        .iter()
+        .filter(|r| !Path::new(&r.path).starts_with(path))
+        .map(|r| format!("{}\n", r))
+        .collect::<String>();
+    std::fs::write(file, kept)?;
+    Ok(())
+}
+
+/// Fetches the tarball of the npm package at the version, or version range, from the configured
+/// registry and unpacks it into `path`, returns the fetched version
+fn install_from_registry(
+    package: &str,
+    version: &str,
+    root: &Path,
+    path: &Path,
+) -> eyre::Result<String> {
+    // unpacked next to the dependencies, so it can be moved into place
+    let tmp = root.join(LIBS).join(format!(".forge-npm-{}", std::process::id()));
+    if tmp.exists() {
+        std::fs::remove_dir_all(&tmp)?;
+    }
+    std::fs::create_dir_all(&tmp)?;
+
+    // `npm pack` respects the registry and credentials of the project's `.npmrc`
+    let output = Command::new("npm")
+        .args(&["pack", "--json", "--pack-destination"])
+        .arg(&tmp)
+        .arg(format!("{}@{}", package, version))
+        .current_dir(root)
+        .stderr(Stdio::piped())
+        .output()
+        .wrap_err("failed to run `npm pack`, is npm installed?")?;
+    if !output.status.success() {
+        eyre::bail!(
+            "failed to fetch {}@{}: {}",
+            package,
+            version,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+    }
+    let packed: Vec<serde_json::Value> = serde_json::from_slice(&output.stdout)
+        .wrap_err_with(|| format!("failed to parse the output of `npm pack {}`", package))?;
+    let (filename, fetched) = packed
+        .first()
+        .and_then(|packed| {
+            Some((packed.get("filename")?.as_str()?, packed.get("version")?.as_str()?))
+        })
+        .ok_or_else(|| eyre::eyre!("`npm pack {}` did not fetch a tarball", package))?;
+
+    // every file of the tarball is nested in a `package` directory
+    let status = Command::new("tar")
+        .args(&["-xzf", filename])
+        .current_dir(&tmp)
+        .status()
+        .wrap_err("failed to run `tar`")?;
+    if !status.success() {
+        eyre::bail!("failed to unpack {}", filename)
+    }
+    let path = root.join(path);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::rename(tmp.join("package"), &path)?;
+    std::fs::remove_dir_all(&tmp)?;
+
+    Ok(fetched.to_string())
+}
+
+/// installs the dependency as an ordinary folder instead of a submodule, returns the installed
+/// commit
+fn install_as_folder(dep: &Dependency, root: &Path, path: &Path) -> eyre::Result<String> {
     Command::new("git")
         .args(&["clone", &dep.url, &path.display().to_string()])
+        .current_dir(root)
         .stdout(Stdio::piped())
         .spawn()?
         .wait()?;
 
+    let path = root.join(path);
     if let Some(ref tag) = dep.tag {
-        Command::new("git")
-            .args(&["checkout", tag])
-            .current_dir(&path)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()?
-            .wait()?;
+        checkout(&path, tag)?;
     }
+    let rev = rev(&path)?;
 
     // rm git artifacts
     std::fs::remove_dir_all(path.join(".git"))?;
 
-    Ok(())
+    Ok(rev)
 }
 
-/// installs the dependency as new submodule
-fn install_as_submodule(
-    dep: &Dependency,
-    root: &Path,
-    path: &Path,
-    no_commit: bool,
-) -> eyre::Result<()> {
+/// installs the dependency as new submodule, returns the installed commit
+fn install_as_submodule(dep: &Dependency, root: &Path, path: &Path) -> eyre::Result<String> {
     // install the dep
     Command::new("git")
         .args(&["submodule", "add", &dep.url, &path.display().to_string()])
@@ -127,31 +429,9 @@ fn install_as_submodule(
         .wait()?;
 
     // checkout the tag if necessary
-    let message = if let Some(ref tag) = dep.tag {
-        Command::new("git")
-            .args(&["checkout", "--recurse-submodules", tag])
-            .current_dir(&path)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()?
-            .wait()?;
-
-        if !no_commit {
-            Command::new("git").args(&["add", &path.display().to_string()]).spawn()?.wait()?;
-        }
-        format!("forge install: {}\n\n{}", dep.name, tag)
-    } else {
-        format!("forge install: {}", dep.name)
-    };
-
-    if !no_commit {
-        Command::new("git")
-            .args(&["commit", "-m", &message])
-            .current_dir(&root)
-            .stdout(Stdio::piped())
-            .spawn()?
-            .wait()?;
+    let path = root.join(path);
+    if let Some(ref tag) = dep.tag {
+        checkout(&path, tag)?;
     }
-
-    Ok(())
+    rev(&path)
 }
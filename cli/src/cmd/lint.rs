@@ -0,0 +1,486 @@
+//! Lint command
+
+use crate::{
+    cmd::{build::BuildArgs, Cmd},
+    exit_code::ExitCode,
+    shell::{self, sh_println, sh_status},
+};
+use ansi_term::Colour;
+use clap::Parser;
+use ethers::solc::artifacts::Severity;
+use foundry_config::{Config, LintSeverity};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+};
+
+/// A lint that's run on the AST of every source file
+struct Lint {
+    name: &'static str,
+    description: &'static str,
+    severity: LintSeverity,
+    check: fn(&Ast<'_>, &mut Vec<(u64, String)>),
+}
+
+/// The lints, the compiler's diagnostics are reported as the `solc` lint
+const LINTS: [Lint; 3] = [
+    Lint {
+        name: "unchecked-transfer",
+        description: "the `bool` returned by an ERC20 `transfer` or `transferFrom` is ignored",
+        severity: LintSeverity::Warning,
+        check: unchecked_transfers,
+    },
+    Lint {
+        name: "tx-origin",
+        description: "`tx.origin` is compared, which lets any contract the user calls act as them",
+        severity: LintSeverity::Error,
+        check: tx_origin_comparisons,
+    },
+    Lint {
+        name: "missing-zero-check",
+        description: "an address parameter is stored without checking that it isn't zero",
+        severity: LintSeverity::Info,
+        check: missing_zero_checks,
+    },
+];
+
+/// The name of the lint of the compiler's diagnostics
+const SOLC: &str = "solc";
+
+#[derive(Debug, Clone, Parser)]
+pub struct LintArgs {
+    #[clap(help = "print the findings in the SARIF format, for code scanning in CI", long)]
+    pub sarif: bool,
+
+    #[clap(flatten)]
+    opts: BuildArgs,
+}
+
+/// A finding of a lint
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct Finding {
+    /// The source file, relative to the root
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+    pub severity: LintSeverity,
+    pub lint: String,
+    pub message: String,
+}
+
+impl Cmd for LintArgs {
+    type Output = ();
+
+    fn run(self) -> eyre::Result<Self::Output> {
+        let config = Config::try_from_args(&self.opts)?;
+        let mut project = config.ephemeral_no_artifacts_project()?;
+        project.cached = false;
+        project.no_artifacts = true;
+        project
+            .solc_config
+            .settings
+            .output_selection
+            .insert("*".to_string(), BTreeMap::from([("".to_string(), vec!["ast".to_string()])]));
+        let output = project.compile()?.output();
+        let severity =
+            |lint: &str, default: LintSeverity| config.lints.get(lint).copied().unwrap_or(default);
+        // the dependencies are not linted
+        let is_library = |file: &str| {
+            let path = project.paths.root.join(file);
+            project.paths.libraries.iter().any(|lib| path.starts_with(lib))
+        };
+
+        let mut findings = BTreeSet::new();
+        for error in &output.errors {
+            let severity = match error.severity {
+                Severity::Error => LintSeverity::Error,
+                Severity::Warning => severity(SOLC, LintSeverity::Warning),
+                Severity::Info => severity(SOLC, LintSeverity::Info),
+            };
+            let location = match error.source_location {
+                Some(ref location)
+                    if severity != LintSeverity::Off && !is_library(&location.file) =>
+                {
+                    location
+                }
+                _ => continue,
+            };
+            let source = std::fs::read_to_string(project.paths.root.join(&location.file))?;
+            let (line, column) = line_column(&source, location.start.max(0) as usize);
+            findings.insert(Finding {
+                file: relative(&location.file, &project.paths.root),
+                line,
+                column,
+                severity,
+                lint: SOLC.to_string(),
+                message: error.message.clone(),
+            });
+        }
+
+        // without an AST for every source, only the compiler's diagnostics are reported
+        if !findings.iter().any(|finding| finding.severity == LintSeverity::Error) {
+            let sources = serde_json::to_value(&output.sources)?;
+            for (file, source) in sources.as_object().into_iter().flatten() {
+                if is_library(file) {
+                    continue
+                }
+                let content = std::fs::read_to_string(project.paths.root.join(file))?;
+                let ast = Ast::new(&source["ast"]);
+                for lint in &LINTS {
+                    let severity = severity(lint.name, lint.severity);
+                    if severity == LintSeverity::Off {
+                        continue
+                    }
+                    let mut found = Vec::new();
+                    (lint.check)(&ast, &mut found);
+                    for (offset, message) in found {
+                        let (line, column) = line_column(&content, offset as usize);
+                        findings.insert(Finding {
+                            file: relative(file, &project.paths.root),
+                            line,
+                            column,
+                            severity,
+                            lint: lint.name.to_string(),
+                            message,
+                        });
+                    }
+                }
+            }
+        }
+
+        if self.sarif {
+            let mut rules = vec![(SOLC, "a diagnostic of the compiler")];
+            rules.extend(LINTS.iter().map(|lint| (lint.name, lint.description)));
+            shell::print_json(&sarif("forge lint", &rules, &findings))?;
+        } else if shell::is_json() {
+            shell::print_json(&findings)?;
+        } else {
+            findings.iter().for_each(print_finding);
+        }
+
+        let errors =
+            findings.iter().filter(|finding| finding.severity == LintSeverity::Error).count();
+        if errors > 0 {
+            return Err(ExitCode::Failure.error(format!("{} lint errors", errors)))
+        }
+        sh_status!("{} findings", findings.len());
+        Ok(())
+    }
+}
+
+/// Prints the finding with its location
+pub(crate) fn print_finding(finding: &Finding) {
+    let colour = match finding.severity {
+        LintSeverity::Error => Colour::Red,
+        LintSeverity::Warning => Colour::Yellow,
+        _ => Colour::Cyan,
+    };
+    sh_println!(
+        "{}: {}",
+        shell::paint(colour, format!("{}[{}]", finding.severity, finding.lint)),
+        finding.message
+    );
+    sh_println!("  --> {}:{}:{}", finding.file, finding.line, finding.column);
+}
+
+/// Returns the findings of the tool's rules, by id and description, as a SARIF log, the format
+/// code scanning tools read
+pub(crate) fn sarif(tool: &str, rules: &[(&str, &str)], findings: &BTreeSet<Finding>) -> Value {
+    let rules = rules
+        .iter()
+        .map(|(id, description)| json!({ "id": id, "shortDescription": { "text": description } }))
+        .collect::<Vec<_>>();
+    let results = findings
+        .iter()
+        .map(|finding| {
+            let level = match finding.severity {
+                LintSeverity::Error => "error",
+                LintSeverity::Warning => "warning",
+                _ => "note",
+            };
+            json!({
+                "ruleId": finding.lint,
+                "level": level,
+                "message": { "text": finding.message },
+                "locations": [{
+                    "physicalLocation": {
+                        "artifactLocation": { "uri": finding.file },
+                        "region": { "startLine": finding.line, "startColumn": finding.column },
+                    }
+                }],
+            })
+        })
+        .collect::<Vec<_>>();
+    json!({
+        "version": "2.1.0",
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": tool,
+                    "informationUri": "https://github.com/gakonst/foundry",
+                    "rules": rules,
+                }
+            },
+            "results": results,
+        }],
+    })
+}
+
+/// Returns the line and column, both starting at 1, of the byte offset
+pub(crate) fn line_column(source: &str, offset: usize) -> (usize, usize) {
+    let before = source.get(..offset).unwrap_or(source);
+    let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or_default();
+    (before.matches('\n').count() + 1, before[line_start..].chars().count() + 1)
+}
+
+/// Returns the path relative to the root if it's within the root
+pub(crate) fn relative(file: &str, root: &Path) -> String {
+    Path::new(file)
+        .strip_prefix(root)
+        .map(|path| path.display().to_string())
+        .unwrap_or_else(|_| file.to_string())
+}
+
+/// The solc AST of a source file
+pub(crate) struct Ast<'a> {
+    /// Every node of the AST, parents before their children
+    nodes: Vec<&'a Value>,
+}
+
+impl<'a> Ast<'a> {
+    pub(crate) fn new(root: &'a Value) -> Self {
+        let mut nodes = Vec::new();
+        collect_nodes(root, &mut nodes);
+        Self { nodes }
+    }
+
+    /// Returns the nodes of the type
+    pub(crate) fn nodes_of(&self, node_type: &'a str) -> impl Iterator<Item = &'a Value> + '_ {
+        self.nodes.iter().copied().filter(move |node| node["nodeType"] == node_type)
+    }
+}
+
+/// Pushes the node and all nodes below it
+fn collect_nodes<'a>(value: &'a Value, nodes: &mut Vec<&'a Value>) {
+    match value {
+        Value::Object(object) => {
+            if object.contains_key("nodeType") {
+                nodes.push(value);
+            }
+            object.values().for_each(|value| collect_nodes(value, nodes));
+        }
+        Value::Array(values) => values.iter().for_each(|value| collect_nodes(value, nodes)),
+        _ => {}
+    }
+}
+
+/// Returns the byte offset of the node in its source file
+fn offset(node: &Value) -> u64 {
+    source_range(node).map(|(offset, _)| offset as u64).unwrap_or_default()
+}
+
+/// Returns the byte offset and length of the node in its source file
+pub(crate) fn source_range(node: &Value) -> Option<(usize, usize)> {
+    let mut src = node["src"].as_str()?.split(':');
+    Some((src.next()?.parse().ok()?, src.next()?.parse().ok()?))
+}
+
+/// Whether the node is `tx.origin`
+fn is_tx_origin(node: &Value) -> bool {
+    node["nodeType"] == "MemberAccess" &&
+        node["memberName"] == "origin" &&
+        node["expression"]["nodeType"] == "Identifier" &&
+        node["expression"]["name"] == "tx"
+}
+
+/// Finds the `transfer` and `transferFrom` calls whose `bool` result is ignored
+fn unchecked_transfers(ast: &Ast<'_>, found: &mut Vec<(u64, String)>) {
+    for statement in ast.nodes_of("ExpressionStatement") {
+        let call = &statement["expression"];
+        let member = call["expression"]["memberName"].as_str().unwrap_or_default();
+        if call["nodeType"] == "FunctionCall" &&
+            matches!(member, "transfer" | "transferFrom") &&
+            call["typeDescriptions"]["typeString"] == "bool"
+        {
+            found.push((
+                offset(call),
+                format!("the return value of `{}` is ignored, require it to be true", member),
+            ));
+        }
+    }
+}
+
+/// Finds the comparisons with `tx.origin`
+fn tx_origin_comparisons(ast: &Ast<'_>, found: &mut Vec<(u64, String)>) {
+    for comparison in ast.nodes_of("BinaryOperation") {
+        if matches!(comparison["operator"].as_str(), Some("==" | "!=")) &&
+            (is_tx_origin(&comparison["leftExpression"]) ||
+                is_tx_origin(&comparison["rightExpression"]))
+        {
+            found.push((
+                offset(comparison),
+                "`tx.origin` is used for authorization, use `msg.sender` instead".to_string(),
+            ));
+        }
+    }
+}
+
+/// Finds the address parameters of state changing functions that are assigned to state variables
+/// and never compared in the function
+fn missing_zero_checks(ast: &Ast<'_>, found: &mut Vec<(u64, String)>) {
+    let state_variables = ast
+        .nodes_of("VariableDeclaration")
+        .filter(|var| var["stateVariable"] == true)
+        .filter_map(|var| Some((var["id"].as_u64()?, var["name"].as_str()?)))
+        .collect::<BTreeMap<_, _>>();
+
+    for function in ast.nodes_of("FunctionDefinition") {
+        if matches!(function["stateMutability"].as_str(), Some("view" | "pure")) ||
+            function["body"].is_null()
+        {
+            continue
+        }
+        let body = Ast::new(&function["body"]);
+        let compared = body
+            .nodes_of("BinaryOperation")
+            .flat_map(|comparison| Ast::new(comparison).nodes)
+            .filter_map(|node| node["referencedDeclaration"].as_u64())
+            .collect::<BTreeSet<_>>();
+
+        let params = function["parameters"]["parameters"].as_array().into_iter().flatten();
+        for param in params {
+            let is_address = param["typeDescriptions"]["typeString"]
+                .as_str()
+                .map_or(false, |ty| ty == "address" || ty == "address payable");
+            let id = match param["id"].as_u64() {
+                Some(id) if is_address && !compared.contains(&id) => id,
+                _ => continue,
+            };
+            for assignment in body.nodes_of("Assignment") {
+                let var = assignment["leftHandSide"]["referencedDeclaration"].as_u64();
+                let value = assignment["rightHandSide"]["referencedDeclaration"].as_u64();
+                if let (Some(var), Some(value)) =
+                    (var.and_then(|var| state_variables.get(&var)), value)
+                {
+                    if value == id {
+                        found.push((
+                            offset(assignment),
+                            format!(
+                                "`{}` is stored in `{}` without checking that it isn't the zero address",
+                                param["name"].as_str().unwrap_or_default(),
+                                var
+                            ),
+                        ));
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(lint: fn(&Ast<'_>, &mut Vec<(u64, String)>), ast: Value) -> Vec<(u64, String)> {
+        let mut found = Vec::new();
+        lint(&Ast::new(&ast), &mut found);
+        found
+    }
+
+    fn identifier(name: &str, id: u64) -> Value {
+        json!({ "nodeType": "Identifier", "name": name, "referencedDeclaration": id, "src": "0:0:0" })
+    }
+
+    #[test]
+    fn finds_unchecked_transfers() {
+        let call = |ty: &str| {
+            json!({
+                "nodeType": "ExpressionStatement",
+                "expression": {
+                    "nodeType": "FunctionCall",
+                    "src": "10:20:0",
+                    "expression": { "nodeType": "MemberAccess", "memberName": "transfer" },
+                    "typeDescriptions": { "typeString": ty },
+                }
+            })
+        };
+        assert_eq!(
+            run(unchecked_transfers, json!([call("bool"), call("tuple()")])),
+            vec![(
+                10,
+                "the return value of `transfer` is ignored, require it to be true".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn finds_tx_origin_comparisons() {
+        let origin = json!({
+            "nodeType": "MemberAccess",
+            "memberName": "origin",
+            "expression": { "nodeType": "Identifier", "name": "tx" },
+        });
+        let ast = json!({
+            "nodeType": "BinaryOperation",
+            "src": "5:20:0",
+            "operator": "==",
+            "leftExpression": origin,
+            "rightExpression": identifier("owner", 1),
+        });
+        assert_eq!(run(tx_origin_comparisons, ast).len(), 1);
+    }
+
+    #[test]
+    fn finds_missing_zero_checks() {
+        let function = |checked: bool| {
+            let mut statements = vec![json!({
+                "nodeType": "Assignment",
+                "src": "40:12:0",
+                "leftHandSide": identifier("owner", 1),
+                "rightHandSide": identifier("newOwner", 2),
+            })];
+            if checked {
+                statements.push(json!({
+                    "nodeType": "BinaryOperation",
+                    "operator": "!=",
+                    "leftExpression": identifier("newOwner", 2),
+                    "rightExpression": { "nodeType": "Literal" },
+                }));
+            }
+            json!([
+                { "nodeType": "VariableDeclaration", "id": 1, "name": "owner", "stateVariable": true },
+                {
+                    "nodeType": "FunctionDefinition",
+                    "stateMutability": "nonpayable",
+                    "parameters": { "parameters": [{
+                        "nodeType": "VariableDeclaration",
+                        "id": 2,
+                        "name": "newOwner",
+                        "stateVariable": false,
+                        "typeDescriptions": { "typeString": "address" },
+                    }]},
+                    "body": { "nodeType": "Block", "statements": statements },
+                }
+            ])
+        };
+        assert_eq!(
+            run(missing_zero_checks, function(false)),
+            vec![(
+                40,
+                "`newOwner` is stored in `owner` without checking that it isn't the zero address"
+                    .to_string()
+            )]
+        );
+        assert!(run(missing_zero_checks, function(true)).is_empty());
+    }
+
+    #[test]
+    fn finds_line_and_column() {
+        assert_eq!(line_column("a\nbc\nd", 3), (2, 2));
+        assert_eq!(line_column("abc", 0), (1, 1));
+    }
+}
@@ -37,19 +37,38 @@
 //! let config: Config = From::from(&args);
 //! ```
 
+pub mod bind;
+pub mod broadcast;
+pub mod broadcast_log;
 pub mod build;
+pub mod cache;
 pub mod config;
 pub mod create;
 pub mod flatten;
+pub mod geiger;
+pub mod generate;
 pub mod init;
+pub mod inspect;
 pub mod install;
+pub mod lint;
+pub mod multisend;
+pub mod mutate;
+pub mod node;
 pub mod remappings;
+pub mod replay;
 pub mod run;
 pub mod snapshot;
+pub mod solc;
 pub mod test;
+pub mod upgrade;
+pub mod upload_selectors;
 pub mod verify;
 
-use crate::opts::forge::ContractInfo;
+use crate::{
+    exit_code::ExitCode,
+    opts::forge::ContractInfo,
+    shell::{self, sh_status},
+};
 use ethers::{
     abi::Abi,
     prelude::{
@@ -86,18 +105,29 @@ If you are in a subdirectory in a Git repository, try adding `--root .`"#,
         );
     }
 
-    println!("compiling...");
-    let output = project.compile()?;
+    let output = with_compile_spinner(project.paths.input_files().len(), || project.compile())?;
     if output.has_compiler_errors() {
-        eyre::bail!(output.to_string())
+        return Err(ExitCode::CompilationError.error(output))
     } else if output.is_unchanged() {
-        println!("no files changed, compilation skipped.");
+        sh_status!("no files changed, compilation skipped.");
     } else {
-        println!("success.");
+        sh_status!("success.");
     }
     Ok(output)
 }
 
+/// Runs the compilation while a spinner with the number of files to compile is rendered, prints a
+/// status instead if the spinner is hidden
+fn with_compile_spinner<T>(files: usize, compile: impl FnOnce() -> T) -> T {
+    let spinner = shell::spinner(format!("compiling {} files...", files));
+    if spinner.is_hidden() {
+        sh_status!("compiling...");
+    }
+    let output = compile();
+    spinner.finish_and_clear();
+    output
+}
+
 /// Manually compile a project with added sources
 pub fn manual_compile(
     project: &Project<MinimalCombinedArtifacts>,
@@ -105,13 +135,20 @@ pub fn manual_compile(
 ) -> eyre::Result<ProjectCompileOutput<MinimalCombinedArtifacts>> {
     let mut sources = project.paths.read_input_files()?;
     sources.extend(Source::read_all_files(added_sources)?);
-    println!("compiling...");
+    compile_sources(project, sources)
+}
+
+/// Compiles the sources and the sources they import, the imports are read from disk
+pub fn compile_sources(
+    project: &Project<MinimalCombinedArtifacts>,
+    sources: Sources,
+) -> eyre::Result<ProjectCompileOutput<MinimalCombinedArtifacts>> {
     if project.auto_detect {
         tracing::trace!("using solc auto detection to compile sources");
-        let output = project.svm_compile(sources)?;
+        let output = with_compile_spinner(sources.len(), || project.svm_compile(sources))?;
         if output.has_compiler_errors() {
             // return the diagnostics error back to the user.
-            eyre::bail!(output.to_string())
+            return Err(ExitCode::CompilationError.error(output))
         }
         return Ok(output)
     }
@@ -122,10 +159,11 @@ pub fn manual_compile(
     }
 
     let sources = Graph::resolve_sources(&project.paths, sources)?.into_sources();
-    let output = project.compile_with_version(&solc, sources)?;
+    let output =
+        with_compile_spinner(sources.len(), || project.compile_with_version(&solc, sources))?;
     if output.has_compiler_errors() {
         // return the diagnostics error back to the user.
-        eyre::bail!(output.to_string())
+        return Err(ExitCode::CompilationError.error(output))
     }
     Ok(output)
 }
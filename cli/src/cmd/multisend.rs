@@ -0,0 +1,156 @@
+//! Encoding of a script's broadcast as a Gnosis Safe `MultiSendCallOnly` batch
+
+use ethers::{
+    abi::{self, Token},
+    types::{Address, Bytes, H160, H256, U256},
+    utils::{id, keccak256},
+};
+use evm_adapters::sputnik::cheatcodes::BroadcastableTransaction;
+use serde::{Deserialize, Serialize};
+
+/// The `MultiSendCallOnly` contract of Safe v1.3.0, deployed at the same address on all chains
+pub const MULTISEND_CALL_ONLY: Address = H160([
+    0x40, 0xa2, 0xac, 0xcb, 0xd9, 0x2b, 0xca, 0x93, 0x8b, 0x02, 0x01, 0x0e, 0x17, 0xa5, 0xb8, 0x92,
+    0x9b, 0x49, 0x13, 0x0d,
+]);
+
+/// The Safe delegatecalls into the multisend contract
+const DELEGATE_CALL: u8 = 1;
+
+/// A Safe transaction as expected by the Safe Transaction Service, the proposing owner adds the
+/// `sender` and its `signature` of the `contractTransactionHash`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SafeTransaction {
+    pub safe: Address,
+    pub to: Address,
+    pub value: String,
+    pub data: Bytes,
+    pub operation: u8,
+    pub safe_tx_gas: String,
+    pub base_gas: String,
+    pub gas_price: String,
+    pub gas_token: Address,
+    pub refund_receiver: Address,
+    pub nonce: String,
+    pub contract_transaction_hash: H256,
+}
+
+/// The multisend batch of the transactions of a single chain
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MultisendBatch {
+    pub chain_id: u64,
+    pub transactions: Vec<BroadcastableTransaction>,
+    pub safe_transaction: SafeTransaction,
+}
+
+impl MultisendBatch {
+    /// Batches the `transactions` of the `safe` into a single `multiSend` call
+    pub fn new(
+        chain_id: u64,
+        safe: Address,
+        nonce: U256,
+        transactions: Vec<BroadcastableTransaction>,
+    ) -> eyre::Result<Self> {
+        let data = encode_multisend(&transactions)?;
+        let safe_tx_hash = safe_tx_hash(chain_id, safe, MULTISEND_CALL_ONLY, &data, nonce);
+        Ok(Self {
+            chain_id,
+            transactions,
+            safe_transaction: SafeTransaction {
+                safe,
+                to: MULTISEND_CALL_ONLY,
+                value: "0".to_string(),
+                data,
+                operation: DELEGATE_CALL,
+                safe_tx_gas: "0".to_string(),
+                base_gas: "0".to_string(),
+                gas_price: "0".to_string(),
+                gas_token: Address::zero(),
+                refund_receiver: Address::zero(),
+                nonce: nonce.to_string(),
+                contract_transaction_hash: safe_tx_hash,
+            },
+        })
+    }
+}
+
+/// Returns the calldata of `multiSend(bytes)` for the transactions, which are packed as
+/// `operation (uint8) ++ to (address) ++ value (uint256) ++ data length (uint256) ++ data`
+pub fn encode_multisend(transactions: &[BroadcastableTransaction]) -> eyre::Result<Bytes> {
+    let mut packed = Vec::new();
+    for tx in transactions {
+        let to = tx.to.ok_or_else(|| {
+            eyre::eyre!("contract creations can't be batched, deploy the contract with CREATE2")
+        })?;
+        packed.push(0u8);
+        packed.extend_from_slice(to.as_bytes());
+        packed.extend_from_slice(&abi::encode(&[
+            Token::Uint(tx.value),
+            Token::Uint(tx.data.len().into()),
+        ]));
+        packed.extend_from_slice(&tx.data);
+    }
+    Ok([&id("multiSend(bytes)")[..], &abi::encode(&[Token::Bytes(packed)])].concat().into())
+}
+
+/// Returns the EIP-712 hash of a Safe transaction with no gas refund, which the owners sign
+pub fn safe_tx_hash(chain_id: u64, safe: Address, to: Address, data: &[u8], nonce: U256) -> H256 {
+    let domain_separator = keccak256(abi::encode(&[
+        Token::FixedBytes(
+            keccak256("EIP712Domain(uint256 chainId,address verifyingContract)").to_vec(),
+        ),
+        Token::Uint(chain_id.into()),
+        Token::Address(safe),
+    ]));
+    let struct_hash = keccak256(abi::encode(&[
+        Token::FixedBytes(keccak256("SafeTx(address to,uint256 value,bytes data,uint8 operation,uint256 safeTxGas,uint256 baseGas,uint256 gasPrice,address gasToken,address refundReceiver,uint256 nonce)").to_vec()),
+        Token::Address(to),
+        Token::Uint(U256::zero()),
+        Token::FixedBytes(keccak256(data).to_vec()),
+        Token::Uint(DELEGATE_CALL.into()),
+        Token::Uint(U256::zero()),
+        Token::Uint(U256::zero()),
+        Token::Uint(U256::zero()),
+        Token::Address(Address::zero()),
+        Token::Address(Address::zero()),
+        Token::Uint(nonce),
+    ]));
+    H256::from(keccak256([&[0x19, 0x01][..], &domain_separator, &struct_hash].concat()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_encode_multisend() {
+        let tx = BroadcastableTransaction {
+            chain_id: 1,
+            from: Address::zero(),
+            to: Some(Address::repeat_byte(0x11)),
+            value: 5.into(),
+            gas: 30000.into(),
+            data: vec![0xab, 0xcd].into(),
+            simulated_nonce: U256::zero(),
+        };
+        let data = encode_multisend(&[tx.clone(), tx.clone()]).unwrap();
+        assert_eq!(&data[..4], &[0x8d, 0x80, 0xff, 0x0a]);
+
+        let packed = match abi::decode(&[abi::ParamType::Bytes], &data[4..]).unwrap().remove(0) {
+            Token::Bytes(packed) => packed,
+            _ => unreachable!(),
+        };
+        // 1 + 20 + 32 + 32 + 2 bytes per transaction
+        assert_eq!(packed.len(), 2 * 87);
+        assert_eq!(packed[0], 0);
+        assert_eq!(&packed[1..21], &[0x11; 20]);
+        assert_eq!(U256::from_big_endian(&packed[21..53]), 5.into());
+        assert_eq!(U256::from_big_endian(&packed[53..85]), 2.into());
+        assert_eq!(&packed[85..87], &[0xab, 0xcd]);
+
+        let creation = BroadcastableTransaction { to: None, ..tx };
+        assert!(encode_multisend(&[creation]).is_err());
+    }
+}
@@ -0,0 +1,409 @@
+//! Mutation testing command
+
+use crate::{
+    cmd::{
+        build::BuildArgs,
+        compile_sources,
+        lint::{line_column, source_range, Ast},
+        test::Filter,
+        Cmd,
+    },
+    exit_code::ExitCode,
+    opts::evm::EvmArgs,
+    shell::{self, sh_println, sh_status},
+};
+use ansi_term::Colour;
+use clap::Parser;
+use ethers::solc::{
+    artifacts::{CompilerOutput, Source},
+    MinimalCombinedArtifacts, Project, ProjectCompileOutput,
+};
+use evm_adapters::{call_tracing::metadata, evm_opts::EvmOpts};
+use forge::MultiContractRunnerBuilder;
+use foundry_config::{figment::Figment, Config};
+use serde::Serialize;
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+};
+
+// Loads project's figment and merges the build cli arguments into it
+foundry_config::impl_figment_convert!(MutateArgs, opts, evm_opts);
+
+#[derive(Debug, Clone, Parser)]
+pub struct MutateArgs {
+    #[clap(flatten)]
+    evm_opts: EvmArgs,
+
+    #[clap(flatten)]
+    filter: Filter,
+
+    #[clap(
+        help = "only mutate the source files matching the glob, relative to the root",
+        long,
+        value_name = "GLOB"
+    )]
+    match_path: Option<glob::Pattern>,
+
+    #[clap(flatten)]
+    opts: BuildArgs,
+}
+
+/// A change of the source code
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Mutation {
+    /// The source file, relative to the root
+    pub file: String,
+    pub line: usize,
+    /// The byte offset of the replaced code in the source file
+    #[serde(skip)]
+    pub offset: usize,
+    /// The replaced code
+    pub original: String,
+    pub replacement: String,
+}
+
+impl Mutation {
+    /// Returns the source code with the mutation applied
+    fn apply(&self, content: &str) -> String {
+        let end = self.offset + self.original.len();
+        format!("{}{}{}", &content[..self.offset], self.replacement, &content[end..])
+    }
+}
+
+/// What running the tests against a mutant found
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MutantStatus {
+    /// A test failed
+    Killed,
+    /// Every test passed
+    Survived,
+    /// The mutant compiles to the same code as the original
+    Equivalent,
+    /// The mutant doesn't compile
+    Invalid,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Mutant {
+    #[serde(flatten)]
+    pub mutation: Mutation,
+    pub status: MutantStatus,
+}
+
+impl Cmd for MutateArgs {
+    type Output = Vec<Mutant>;
+
+    fn run(self) -> eyre::Result<Self::Output> {
+        let figment: Figment = From::from(&self);
+        let evm_opts = figment.extract::<EvmOpts>()?;
+        let config = Config::try_from(figment)?.sanitized();
+        let mut project = config.project()?;
+        project.cached = false;
+        project.no_artifacts = true;
+        project
+            .solc_config
+            .settings
+            .output_selection
+            .entry("*".to_string())
+            .or_default()
+            .insert("".to_string(), vec!["ast".to_string()]);
+
+        let output = compile_sources(&project, project.paths.read_input_files()?)?;
+        if !self.run_tests(&project, &config, &evm_opts, output.clone())? {
+            return Err(ExitCode::Failure.error("the tests must pass before mutating the sources"))
+        }
+        let original = output.output();
+        let importers = importers(&original)?;
+
+        let mut mutants = Vec::new();
+        let targets = self.targets(&project, &original)?;
+        let total = targets.iter().map(|(_, _, mutations)| mutations.len()).sum::<usize>();
+        let progress = shell::progress_bar(total as u64, "testing mutants");
+        for (file, content, mutations) in targets {
+            // only the sources that import the mutated file, directly or transitively, and their
+            // tests are recompiled and run
+            let affected = affected_sources(&file, &importers);
+            for mutation in mutations {
+                let mut sources = Source::read_all_files(affected.iter().cloned().collect())?;
+                sources.insert(file.clone(), Source { content: mutation.apply(&content) });
+                let status = match compile_sources(&project, sources) {
+                    Err(_) => MutantStatus::Invalid,
+                    Ok(output) if same_code(&original, &output.clone().output(), &file) => {
+                        MutantStatus::Equivalent
+                    }
+                    Ok(output) => {
+                        if self.run_tests(&project, &config, &evm_opts, output)? {
+                            MutantStatus::Survived
+                        } else {
+                            MutantStatus::Killed
+                        }
+                    }
+                };
+                progress.inc(1);
+                mutants.push(Mutant { mutation, status });
+            }
+        }
+        progress.finish_and_clear();
+
+        let count = |status| mutants.iter().filter(|mutant| mutant.status == status).count();
+        let (killed, survived) = (count(MutantStatus::Killed), count(MutantStatus::Survived));
+        // equivalent and invalid mutants can't be killed and don't count
+        let score = if killed + survived == 0 {
+            100.0
+        } else {
+            100.0 * killed as f64 / (killed + survived) as f64
+        };
+        if shell::is_json() {
+            shell::print_json(&serde_json::json!({ "score": score, "mutants": mutants }))?;
+        } else {
+            sh_println!(
+                "Mutants: {} killed, {} survived, {} equivalent, {} invalid",
+                shell::paint(Colour::Green, killed),
+                shell::paint(Colour::Red, survived),
+                count(MutantStatus::Equivalent),
+                count(MutantStatus::Invalid)
+            );
+            sh_println!("Mutation score: {:.2}%", score);
+            if survived > 0 {
+                sh_println!();
+                sh_println!("Surviving mutants:");
+                for mutant in
+                    mutants.iter().filter(|mutant| mutant.status == MutantStatus::Survived)
+                {
+                    let Mutation { file, line, original, replacement, .. } = &mutant.mutation;
+                    sh_println!("  {}:{}: `{}` → `{}`", file, line, original, replacement);
+                }
+            }
+        }
+        sh_status!("tested {} mutants", mutants.len());
+        Ok(mutants)
+    }
+}
+
+impl MutateArgs {
+    /// Runs the tests of the compiled contracts, returns whether all of them passed
+    fn run_tests(
+        &self,
+        project: &Project<MinimalCombinedArtifacts>,
+        config: &Config,
+        evm_opts: &EvmOpts,
+        output: ProjectCompileOutput<MinimalCombinedArtifacts>,
+    ) -> eyre::Result<bool> {
+        let fuzzer = proptest::test_runner::TestRunner::new(proptest::test_runner::Config {
+            failure_persistence: None,
+            cases: config.fuzz_runs,
+            ..Default::default()
+        });
+        let mut evm_cfg = crate::utils::sputnik_cfg(&config.evm_version)?;
+        evm_cfg.create_contract_limit = None;
+        let mut runner = MultiContractRunnerBuilder::default()
+            .fuzzer(fuzzer)
+            .initial_balance(evm_opts.initial_balance)
+            .evm_cfg(evm_cfg)
+            .sender(evm_opts.sender)
+            .bytecode_contracts(config.read_bytecode_contracts()?)
+            .build_with_output(project, output, evm_opts.clone())?;
+        let results = runner.test(&self.filter)?;
+        Ok(results.values().flat_map(|tests| tests.values()).all(|result| result.success))
+    }
+
+    /// Returns the mutations of the source files that aren't tests, with their content
+    fn targets(
+        &self,
+        project: &Project<MinimalCombinedArtifacts>,
+        output: &CompilerOutput,
+    ) -> eyre::Result<Vec<(PathBuf, String, Vec<Mutation>)>> {
+        let sources = serde_json::to_value(&output.sources)?;
+        let mut targets = Vec::new();
+        for (file, source) in sources.as_object().into_iter().flatten() {
+            let path = PathBuf::from(file);
+            let relative = path.strip_prefix(&project.paths.root).unwrap_or(&path).to_path_buf();
+            let is_test = path.starts_with(&project.paths.tests) ||
+                file.ends_with(".t.sol") ||
+                !path.starts_with(&project.paths.sources);
+            let matches =
+                self.match_path.as_ref().map_or(true, |glob| glob.matches_path(&relative));
+            if is_test || !matches {
+                continue
+            }
+            let content = std::fs::read_to_string(&path)?;
+            let mutations = mutations(&Ast::new(&source["ast"]), &content, &relative);
+            targets.push((path, content, mutations));
+        }
+        Ok(targets)
+    }
+}
+
+/// Returns the mutations of the source code: swapped operators, shifted boundaries and deleted
+/// `require`s
+fn mutations(ast: &Ast<'_>, content: &str, file: &Path) -> Vec<Mutation> {
+    let mut found: Vec<(usize, &str, &str)> = Vec::new();
+    for operation in ast.nodes_of("BinaryOperation") {
+        let operator = operation["operator"].as_str().unwrap_or_default();
+        let replacement = match operator {
+            "+" => "-",
+            "-" => "+",
+            "*" => "/",
+            "/" => "*",
+            "%" => "*",
+            "==" => "!=",
+            "!=" => "==",
+            "&&" => "||",
+            "||" => "&&",
+            "<" => "<=",
+            "<=" => "<",
+            ">" => ">=",
+            ">=" => ">",
+            _ => continue,
+        };
+        // the operator is between the operands
+        let left = source_range(&operation["leftExpression"]);
+        let right = source_range(&operation["rightExpression"]);
+        if let (Some((left, left_length)), Some((right, _))) = (left, right) {
+            let between = content.get(left + left_length..right).unwrap_or_default();
+            if let Some(pos) = between.find(operator) {
+                found.push((left + left_length + pos, operator, replacement));
+            }
+        }
+    }
+    for statement in ast.nodes_of("ExpressionStatement") {
+        let call = &statement["expression"];
+        if call["nodeType"] == "FunctionCall" && call["expression"]["name"] == "require" {
+            if let Some((offset, length)) = source_range(call) {
+                if let Some(original) = content.get(offset..offset + length) {
+                    found.push((offset, original, "true"));
+                }
+            }
+        }
+    }
+
+    found.sort_by_key(|(offset, _, _)| *offset);
+    found
+        .into_iter()
+        .map(|(offset, original, replacement)| Mutation {
+            file: file.display().to_string(),
+            line: line_column(content, offset).0,
+            offset,
+            original: original.to_string(),
+            replacement: replacement.to_string(),
+        })
+        .collect()
+}
+
+/// Returns the files that import each file, from the import directives of the ASTs
+fn importers(output: &CompilerOutput) -> eyre::Result<BTreeMap<PathBuf, BTreeSet<PathBuf>>> {
+    let sources = serde_json::to_value(&output.sources)?;
+    let mut importers: BTreeMap<PathBuf, BTreeSet<PathBuf>> = BTreeMap::new();
+    for (file, source) in sources.as_object().into_iter().flatten() {
+        for import in Ast::new(&source["ast"]).nodes_of("ImportDirective") {
+            if let Some(imported) = import["absolutePath"].as_str() {
+                importers.entry(imported.into()).or_default().insert(file.into());
+            }
+        }
+    }
+    Ok(importers)
+}
+
+/// Returns the file and the files that import it, directly or transitively
+fn affected_sources(
+    file: &Path,
+    importers: &BTreeMap<PathBuf, BTreeSet<PathBuf>>,
+) -> BTreeSet<PathBuf> {
+    let mut affected = BTreeSet::from([file.to_path_buf()]);
+    let mut queue = vec![file.to_path_buf()];
+    while let Some(file) = queue.pop() {
+        for importer in importers.get(&file).into_iter().flatten() {
+            if affected.insert(importer.clone()) {
+                queue.push(importer.clone());
+            }
+        }
+    }
+    affected
+}
+
+/// Whether the contracts of the file compile to the same code in both outputs, apart from the
+/// metadata hash
+fn same_code(original: &CompilerOutput, mutant: &CompilerOutput, file: &Path) -> bool {
+    let code = |output: &CompilerOutput| -> Option<Vec<Vec<u8>>> {
+        let contracts = serde_json::to_value(output.contracts.get(file.to_str()?)?).ok()?;
+        let mut code = Vec::new();
+        for contract in contracts.as_object()?.values() {
+            for field in ["bytecode", "deployedBytecode"] {
+                code.push(strip_metadata(&contract["evm"][field]["object"]));
+            }
+        }
+        Some(code)
+    };
+    matches!((code(original), code(mutant)), (Some(a), Some(b)) if a == b)
+}
+
+/// Decodes the hex encoded bytecode without the metadata appended to it
+fn strip_metadata(object: &Value) -> Vec<u8> {
+    let code = hex::decode(object.as_str().unwrap_or_default().trim_start_matches("0x"))
+        .unwrap_or_default();
+    match metadata(&code) {
+        Some(metadata) => code[..code.len() - metadata.len() - 2].to_vec(),
+        None => code,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn finds_mutations() {
+        let content = "if (a < b) { require(a + 1 > 0); }";
+        let operation = |start: usize, len: usize, op: &str, left: usize, right: usize| {
+            json!({
+                "nodeType": "BinaryOperation",
+                "src": format!("{}:{}:0", start, len),
+                "operator": op,
+                "leftExpression": { "nodeType": "Identifier", "src": format!("{}:1:0", left) },
+                "rightExpression": { "nodeType": "Identifier", "src": format!("{}:1:0", right) },
+            })
+        };
+        let ast = json!([
+            operation(4, 5, "<", 4, 8),
+            {
+                "nodeType": "ExpressionStatement",
+                "expression": {
+                    "nodeType": "FunctionCall",
+                    "src": "13:18:0",
+                    "expression": { "nodeType": "Identifier", "name": "require" },
+                    "arguments": [operation(21, 5, "+", 21, 25)],
+                },
+            },
+        ]);
+        let mutations = mutations(&Ast::new(&ast), content, Path::new("src/A.sol"));
+        let applied = mutations.iter().map(|mutation| mutation.apply(content)).collect::<Vec<_>>();
+        assert_eq!(
+            applied,
+            vec![
+                "if (a <= b) { require(a + 1 > 0); }",
+                "if (a < b) { true; }",
+                "if (a < b) { require(a - 1 > 0); }",
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_affected_sources() {
+        let importers = BTreeMap::from([
+            (PathBuf::from("Lib.sol"), BTreeSet::from([PathBuf::from("Vault.sol")])),
+            (PathBuf::from("Vault.sol"), BTreeSet::from([PathBuf::from("Vault.t.sol")])),
+        ]);
+        assert_eq!(
+            affected_sources(Path::new("Lib.sol"), &importers),
+            BTreeSet::from([
+                PathBuf::from("Lib.sol"),
+                PathBuf::from("Vault.sol"),
+                PathBuf::from("Vault.t.sol")
+            ])
+        );
+    }
+}
@@ -0,0 +1,1103 @@
+//! node command
+
+use crate::{
+    cmd::Cmd,
+    opts::evm::EvmArgs,
+    shell::{self, sh_println, sh_status},
+    utils,
+};
+use ansi_term::Colour;
+use clap::Parser;
+use ethers::{
+    abi::{self, RawLog, Token},
+    signers::{coins_bip39::English, LocalWallet, MnemonicBuilder, Signer},
+    types::{Address, Bytes, Signature, H256, U256, U64},
+    utils::keccak256,
+};
+use evm_adapters::{
+    chain_profile::{precompiles, system_contracts},
+    evm_opts::{BackendKind, EvmOpts},
+    sputnik::{
+        cheatcodes::cheatcode_handler::CheatcodeStackState,
+        helpers::TestSputnikVM,
+        sputnik_evm::{
+            backend::{Backend, MemoryVicinity},
+            executor::stack::StackState,
+            Config as EvmConfig,
+        },
+        Executor, SputnikExecutor,
+    },
+    Evm,
+};
+use foundry_config::{find_project_root_path, Config};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Value};
+use std::{
+    collections::BTreeSet,
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{IpAddr, SocketAddr, TcpListener, TcpStream},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// The mnemonic of the default accounts, the same as the one of hardhat's node
+pub const DEFAULT_MNEMONIC: &str = "test test test test test test test test test test test junk";
+
+#[derive(Debug, Clone, Parser)]
+pub struct NodeArgs {
+    #[clap(help = "the address to listen on", long, default_value = "127.0.0.1")]
+    pub host: IpAddr,
+
+    #[clap(help = "the port to listen on", long, short, default_value = "8545")]
+    pub port: u16,
+
+    #[clap(
+        help = "the number of unlocked accounts to generate",
+        long,
+        short,
+        default_value = "10"
+    )]
+    pub accounts: u32,
+
+    #[clap(
+        help = "the mnemonic the accounts are derived from",
+        long,
+        short,
+        default_value = DEFAULT_MNEMONIC
+    )]
+    pub mnemonic: String,
+
+    #[clap(help = "the balance of every account, in ether", long, default_value = "10000")]
+    pub balance: u64,
+
+    #[clap(
+        help = "mine a block every `block-time` seconds instead of a block per transaction",
+        long,
+        short
+    )]
+    pub block_time: Option<u64>,
+
+    #[clap(flatten)]
+    pub evm_opts: EvmArgs,
+}
+
+impl Cmd for NodeArgs {
+    type Output = ();
+
+    fn run(self) -> eyre::Result<Self::Output> {
+        let figment = Config::figment_with_root(find_project_root_path()?).merge(&self.evm_opts);
+        let mut evm_opts = figment.extract::<EvmOpts>()?;
+        let config = Config::try_from(figment)?.sanitized();
+        self.evm_opts.resolve_fork(&mut evm_opts, &config)?;
+
+        let accounts = (0..self.accounts)
+            .map(|index| {
+                Ok(MnemonicBuilder::<English>::default()
+                    .phrase(self.mnemonic.as_str())
+                    .index(index)?
+                    .build()?)
+            })
+            .collect::<eyre::Result<Vec<LocalWallet>>>()?;
+        let balance = U256::from(self.balance) * U256::exp10(18);
+
+        sh_println!("Available accounts");
+        sh_println!("==================");
+        for (idx, wallet) in accounts.iter().enumerate() {
+            sh_println!("({}) {:?} ({} ETH)", idx, wallet.address(), self.balance);
+        }
+        sh_println!();
+        sh_println!("Private keys");
+        sh_println!("==================");
+        for (idx, wallet) in accounts.iter().enumerate() {
+            sh_println!("({}) 0x{}", idx, hex::encode(wallet.signer().to_bytes()));
+        }
+        sh_println!();
+
+        let listener = TcpListener::bind(SocketAddr::new(self.host, self.port))?;
+        sh_println!(
+            "{}",
+            shell::paint(Colour::Green, format!("Listening on {}", listener.local_addr()?))
+        );
+
+        let accounts = accounts.iter().map(Signer::address).collect::<Vec<_>>();
+        let block_time = self.block_time.map(Duration::from_secs);
+        let vicinity = evm_opts.vicinity()?;
+        let cfg = utils::sputnik_cfg(&config.evm_version)?;
+        match evm_opts.backend(&vicinity)? {
+            BackendKind::Simple(backend) => {
+                let mut node = Node::new(backend, &cfg, &vicinity, &evm_opts);
+                node.set_up(&accounts, balance, block_time);
+                node.serve(listener)
+            }
+            BackendKind::Shared(backend) => {
+                let mut node = Node::new(backend, &cfg, &vicinity, &evm_opts);
+                node.set_up(&accounts, balance, block_time);
+                node.serve(listener)
+            }
+        }
+    }
+}
+
+/// A transaction that was sent to the node
+#[derive(Debug, Clone)]
+struct PendingTransaction {
+    hash: H256,
+    /// the EIP-2718 type
+    ty: u8,
+    from: Address,
+    to: Option<Address>,
+    nonce: U256,
+    gas: U256,
+    gas_price: U256,
+    value: U256,
+    input: Bytes,
+    /// unset for transactions sent by the unlocked accounts
+    signature: Option<Signature>,
+}
+
+/// The outcome of executing a transaction
+#[derive(Debug, Clone, Default)]
+struct Execution {
+    success: bool,
+    output: Bytes,
+    /// the gas used without refunds, the gas a transaction needs to succeed
+    gross_gas: U256,
+    /// the gas paid for, after refunds
+    gas_used: U256,
+    contract_address: Option<Address>,
+    logs: Vec<(Address, RawLog)>,
+}
+
+#[derive(Debug, Clone)]
+struct MinedTransaction {
+    tx: PendingTransaction,
+    execution: Execution,
+    block_number: u64,
+    block_hash: H256,
+    index: usize,
+    cumulative_gas_used: U256,
+    /// the index of the transaction's first log in its block
+    log_index: usize,
+}
+
+#[derive(Debug, Clone)]
+struct MinedBlock {
+    number: u64,
+    hash: H256,
+    parent_hash: H256,
+    timestamp: u64,
+    gas_used: U256,
+    transactions: Vec<H256>,
+}
+
+/// The chain state at the time of an `evm_snapshot`
+struct Snapshot<'a, B> {
+    id: U256,
+    state: CheatcodeStackState<'a, B>,
+    blocks: usize,
+    transactions: usize,
+    pending: Vec<PendingTransaction>,
+}
+
+/// An error object of a JSON-RPC response
+#[derive(Debug, Clone, PartialEq)]
+struct RpcError {
+    code: i64,
+    message: String,
+    data: Option<Value>,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self { code, message: message.into(), data: None }
+    }
+
+    fn invalid_params(message: impl Into<String>) -> Self {
+        Self::new(-32602, message)
+    }
+}
+
+/// The fields of `eth_call`, `eth_estimateGas` and `eth_sendTransaction` requests
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TransactionRequest {
+    from: Option<Address>,
+    to: Option<Address>,
+    gas: Option<U256>,
+    gas_price: Option<U256>,
+    value: Option<U256>,
+    #[serde(alias = "input")]
+    data: Option<Bytes>,
+    nonce: Option<U256>,
+}
+
+/// A local development chain that mines the transactions it receives over JSON-RPC with the
+/// same EVM the tests are executed with
+///
+/// Transactions of impersonated accounts and the `anvil_set*` methods modify the state like the
+/// cheatcodes do, which lets scripts written for Hardhat Network run unmodified.
+///
+/// Queries always read the latest state, the block parameter of `eth_getBalance` and alike is
+/// ignored.
+pub struct Node<'a, B> {
+    evm: TestSputnikVM<'a, B>,
+    chain_id: U256,
+    gas_price: U256,
+    block_gas_limit: U256,
+    base_fee: U256,
+    accounts: Vec<Address>,
+    /// the accounts whose transactions are accepted without a signature, like unlocked accounts
+    impersonated: BTreeSet<Address>,
+    /// the timestamp of the next block, if set by `evm_setNextBlockTimestamp`
+    next_timestamp: Option<u64>,
+    /// whether a block is mined as soon as a transaction is sent
+    automine: bool,
+    /// the interval of blocks if interval mining is enabled
+    block_time: Option<Duration>,
+    blocks: Vec<MinedBlock>,
+    transactions: Vec<MinedTransaction>,
+    pending: Vec<PendingTransaction>,
+    snapshots: Vec<Snapshot<'a, B>>,
+    next_snapshot_id: U256,
+}
+
+impl<'a, B: Backend + Clone> Node<'a, B> {
+    pub fn new(
+        backend: B,
+        cfg: &'a EvmConfig,
+        vicinity: &MemoryVicinity,
+        evm_opts: &EvmOpts,
+    ) -> Self {
+        // the gas is metered per transaction, the executor's own limit spans all transactions
+        let mut evm = Executor::new_with_cheatcodes(
+            backend,
+            u64::MAX,
+            cfg,
+            precompiles(evm_opts.chain_profile),
+            evm_opts.ffi,
+            true,
+            false,
+        );
+        evm.set_rpc_endpoints(evm_opts.rpc_endpoints.clone());
+        evm.initialize_contracts(system_contracts(evm_opts.chain_profile));
+        evm.set_tracing_enabled(true);
+
+        let number = vicinity.block_number.as_u64();
+        let timestamp = vicinity.block_timestamp.as_u64();
+        let genesis = MinedBlock {
+            number,
+            hash: block_hash(number, H256::zero(), timestamp, &[]),
+            parent_hash: H256::zero(),
+            timestamp,
+            gas_used: U256::zero(),
+            transactions: Vec::new(),
+        };
+
+        Self {
+            evm,
+            chain_id: vicinity.chain_id,
+            gas_price: vicinity.gas_price,
+            block_gas_limit: vicinity.block_gas_limit,
+            base_fee: vicinity.block_base_fee_per_gas,
+            accounts: Vec::new(),
+            impersonated: BTreeSet::new(),
+            next_timestamp: None,
+            automine: true,
+            block_time: None,
+            blocks: vec![genesis],
+            transactions: Vec::new(),
+            pending: Vec::new(),
+            snapshots: Vec::new(),
+            next_snapshot_id: U256::one(),
+        }
+    }
+
+    /// Funds and unlocks the accounts and enables interval mining if `block_time` is set
+    pub fn set_up(&mut self, accounts: &[Address], balance: U256, block_time: Option<Duration>) {
+        for account in accounts {
+            self.evm.set_balance(*account, balance);
+        }
+        self.accounts = accounts.to_vec();
+        self.block_time = block_time;
+        self.automine = block_time.is_none();
+    }
+
+    /// Serves JSON-RPC requests over HTTP until the process is stopped, mining a block every
+    /// `block_time` if interval mining is enabled
+    pub fn serve(mut self, listener: TcpListener) -> eyre::Result<()> {
+        listener.set_nonblocking(true)?;
+        let mut last_block = Instant::now();
+        loop {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    if let Err(err) = self.handle_connection(stream) {
+                        tracing::debug!(?err, "failed to handle connection");
+                    }
+                }
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                    std::thread::sleep(Duration::from_millis(5))
+                }
+                Err(err) => return Err(err.into()),
+            }
+            match self.block_time {
+                Some(block_time) if last_block.elapsed() >= block_time => {
+                    self.mine();
+                    last_block = Instant::now();
+                }
+                Some(_) => {}
+                None => last_block = Instant::now(),
+            }
+        }
+    }
+
+    fn handle_connection(&mut self, mut stream: TcpStream) -> io::Result<()> {
+        stream.set_nonblocking(false)?;
+        stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+        let (method, body) = match read_http_request(&mut stream)? {
+            Some(request) => request,
+            None => return Ok(()),
+        };
+        // CORS preflight requests of browsers are answered without a body
+        let response = if method == "OPTIONS" { String::new() } else { self.handle_body(&body) };
+        write_http_response(&mut stream, &response)
+    }
+
+    /// Handles the body of an HTTP request, a single JSON-RPC request or a batch of requests
+    fn handle_body(&mut self, body: &[u8]) -> String {
+        match serde_json::from_slice::<Value>(body) {
+            Ok(Value::Array(batch)) => Value::Array(
+                batch.into_iter().map(|request| self.handle_request(request)).collect(),
+            )
+            .to_string(),
+            Ok(request) => self.handle_request(request).to_string(),
+            Err(err) => {
+                rpc_response(Value::Null, Err(RpcError::new(-32700, err.to_string()))).to_string()
+            }
+        }
+    }
+
+    fn handle_request(&mut self, request: Value) -> Value {
+        let id = request.get("id").cloned().unwrap_or(Value::Null);
+        let params = match request.get("params") {
+            Some(Value::Array(params)) => params.clone(),
+            _ => Vec::new(),
+        };
+        let result = match request.get("method").and_then(Value::as_str) {
+            Some(method) => {
+                tracing::trace!(method, ?params, "rpc request");
+                self.execute(method, &params)
+            }
+            None => Err(RpcError::new(-32600, "invalid request")),
+        };
+        rpc_response(id, result)
+    }
+
+    /// Executes a JSON-RPC method
+    fn execute(&mut self, method: &str, params: &[Value]) -> Result<Value, RpcError> {
+        Ok(match method {
+            "web3_clientVersion" => json!(format!("forge/v{}", env!("CARGO_PKG_VERSION"))),
+            "web3_sha3" => json!(H256::from(keccak256(param::<Bytes>(params, 0)?))),
+            "net_version" => json!(self.chain_id.to_string()),
+            "net_listening" => json!(true),
+            "net_peerCount" => json!(U64::zero()),
+            "eth_chainId" => json!(self.chain_id),
+            "eth_accounts" => json!(self.accounts),
+            "eth_coinbase" => json!(self.evm.state().block_coinbase()),
+            "eth_syncing" => json!(false),
+            "eth_mining" => json!(self.automine || self.block_time.is_some()),
+            "eth_blockNumber" => json!(U64::from(self.latest().number)),
+            "eth_gasPrice" => json!(self.gas_price),
+            "eth_getBalance" => json!(self.evm.state().basic(param(params, 0)?).balance),
+            "eth_getTransactionCount" => {
+                let address = param(params, 0)?;
+                let pending = params.get(1).and_then(Value::as_str) == Some("pending");
+                json!(self.nonce(address, pending))
+            }
+            "eth_getCode" => json!(Bytes::from(self.evm.code(param(params, 0)?))),
+            "eth_getStorageAt" => {
+                let slot = param::<U256>(params, 1)?;
+                let mut key = H256::zero();
+                slot.to_big_endian(key.as_bytes_mut());
+                json!(self.evm.state().storage(param(params, 0)?, key))
+            }
+            "eth_call" => {
+                let execution = self.simulate(param(params, 0)?);
+                if !execution.success {
+                    return Err(revert_error(&execution))
+                }
+                json!(execution.output)
+            }
+            "eth_estimateGas" => {
+                let execution = self.simulate(param(params, 0)?);
+                if !execution.success {
+                    return Err(revert_error(&execution))
+                }
+                json!(execution.gross_gas)
+            }
+            "eth_sendTransaction" => json!(self.send_transaction(param(params, 0)?)?),
+            "eth_sendRawTransaction" => {
+                let raw = param::<Bytes>(params, 0)?;
+                let tx = decode_raw_transaction(&raw, self.chain_id, self.base_fee)
+                    .map_err(|err| RpcError::invalid_params(err.to_string()))?;
+                json!(self.submit(tx)?)
+            }
+            "eth_getTransactionByHash" => {
+                let hash = param::<H256>(params, 0)?;
+                match self.transactions.iter().find(|tx| tx.tx.hash == hash) {
+                    Some(tx) => self.transaction_json(&tx.tx, Some(tx)),
+                    None => self
+                        .pending
+                        .iter()
+                        .find(|tx| tx.hash == hash)
+                        .map_or(Value::Null, |tx| self.transaction_json(tx, None)),
+                }
+            }
+            "eth_getTransactionReceipt" => {
+                let hash = param::<H256>(params, 0)?;
+                self.transactions
+                    .iter()
+                    .find(|tx| tx.tx.hash == hash)
+                    .map_or(Value::Null, receipt_json)
+            }
+            "eth_getBlockByNumber" => {
+                let full = params.get(1).and_then(Value::as_bool).unwrap_or_default();
+                let number = match params.get(0).and_then(Value::as_str) {
+                    Some("earliest") => self.blocks[0].number,
+                    None | Some("latest") | Some("pending") => self.latest().number,
+                    Some(_) => param::<U64>(params, 0)?.as_u64(),
+                };
+                self.blocks
+                    .iter()
+                    .find(|block| block.number == number)
+                    .map_or(Value::Null, |block| self.block_json(block, full))
+            }
+            "eth_getBlockByHash" => {
+                let hash = param::<H256>(params, 0)?;
+                let full = params.get(1).and_then(Value::as_bool).unwrap_or_default();
+                self.blocks
+                    .iter()
+                    .find(|block| block.hash == hash)
+                    .map_or(Value::Null, |block| self.block_json(block, full))
+            }
+            "evm_snapshot" => json!(self.snapshot()),
+            "evm_revert" => json!(self.revert(param(params, 0)?)),
+            "evm_setAutomine" => {
+                self.automine = param(params, 0)?;
+                if self.automine && !self.pending.is_empty() {
+                    self.mine();
+                }
+                json!(true)
+            }
+            "evm_setIntervalMining" => {
+                let millis = quantity(params, 0)?;
+                self.block_time = (millis > 0).then(|| Duration::from_millis(millis));
+                json!(true)
+            }
+            "evm_setNextBlockTimestamp" => {
+                let timestamp = quantity(params, 0)?;
+                if timestamp <= self.latest().timestamp {
+                    return Err(RpcError::invalid_params(format!(
+                        "timestamp {} is not after the latest block's timestamp {}",
+                        timestamp,
+                        self.latest().timestamp
+                    )))
+                }
+                self.next_timestamp = Some(timestamp);
+                json!(U64::from(timestamp))
+            }
+            "evm_mine" => {
+                if !params.is_empty() {
+                    self.next_timestamp = Some(quantity(params, 0)?);
+                }
+                self.mine();
+                json!("0x0")
+            }
+            "anvil_impersonateAccount" | "hardhat_impersonateAccount" => {
+                self.impersonated.insert(param(params, 0)?);
+                json!(true)
+            }
+            "anvil_stopImpersonatingAccount" | "hardhat_stopImpersonatingAccount" => {
+                self.impersonated.remove(&param::<Address>(params, 0)?);
+                json!(true)
+            }
+            "anvil_setBalance" | "hardhat_setBalance" => {
+                let address = param(params, 0)?;
+                let state = self.evm.executor.state_mut();
+                state.reset_balance(address);
+                state.deposit(address, param(params, 1)?);
+                json!(true)
+            }
+            "anvil_setCode" | "hardhat_setCode" => {
+                let code = param::<Bytes>(params, 1)?;
+                self.evm.executor.state_mut().set_code(param(params, 0)?, code.to_vec());
+                json!(true)
+            }
+            "anvil_setStorageAt" | "hardhat_setStorageAt" => {
+                let slot = param::<U256>(params, 1)?;
+                let mut key = H256::zero();
+                slot.to_big_endian(key.as_bytes_mut());
+                self.evm.executor.state_mut().set_storage(
+                    param(params, 0)?,
+                    key,
+                    param(params, 2)?,
+                );
+                json!(true)
+            }
+            _ => return Err(RpcError::new(-32601, format!("method `{}` is not supported", method))),
+        })
+    }
+
+    fn latest(&self) -> &MinedBlock {
+        self.blocks.last().expect("the genesis block always exists")
+    }
+
+    /// The nonce of the account, including its pending transactions if `pending` is set
+    fn nonce(&self, address: Address, pending: bool) -> U256 {
+        let nonce = self.evm.state().basic(address).nonce;
+        if pending {
+            nonce + self.pending.iter().filter(|tx| tx.from == address).count()
+        } else {
+            nonce
+        }
+    }
+
+    fn default_gas(&self) -> U256 {
+        self.block_gas_limit.min(u64::MAX.into())
+    }
+
+    /// Executes the request without persisting its state changes
+    fn simulate(&mut self, request: TransactionRequest) -> Execution {
+        let tx = PendingTransaction {
+            hash: H256::zero(),
+            ty: 0,
+            from: request.from.unwrap_or_default(),
+            to: request.to,
+            nonce: U256::zero(),
+            gas: request.gas.unwrap_or_else(|| self.default_gas()),
+            gas_price: request.gas_price.unwrap_or(self.gas_price),
+            value: request.value.unwrap_or_default(),
+            input: request.data.unwrap_or_default(),
+            signature: None,
+        };
+        let snapshot = self.evm.snapshot();
+        let execution = self.execute_transaction(&tx);
+        self.evm.revert_to(snapshot);
+        execution
+    }
+
+    /// Queues a transaction of an unlocked account
+    fn send_transaction(&mut self, request: TransactionRequest) -> Result<H256, RpcError> {
+        let from = request.from.ok_or_else(|| RpcError::invalid_params("missing `from`"))?;
+        if !self.accounts.contains(&from) && !self.impersonated.contains(&from) {
+            return Err(RpcError::invalid_params(format!(
+                "account {:?} is neither unlocked nor impersonated",
+                from
+            )))
+        }
+        let nonce = request.nonce.unwrap_or_else(|| self.nonce(from, true));
+        // unlocked transactions are not signed, their hash only needs to be unique
+        let hash = H256::from(keccak256(abi::encode(&[
+            Token::Address(from),
+            Token::Uint(nonce),
+            Token::Uint(self.chain_id),
+        ])));
+        let tx = PendingTransaction {
+            hash,
+            ty: 0,
+            from,
+            to: request.to,
+            nonce,
+            gas: request.gas.unwrap_or_else(|| self.default_gas()),
+            gas_price: request.gas_price.unwrap_or(self.gas_price),
+            value: request.value.unwrap_or_default(),
+            input: request.data.unwrap_or_default(),
+            signature: None,
+        };
+        self.submit(tx)
+    }
+
+    /// Adds the transaction to the pending transactions, mines it right away if automining
+    fn submit(&mut self, tx: PendingTransaction) -> Result<H256, RpcError> {
+        let expected = self.nonce(tx.from, true);
+        if tx.nonce != expected {
+            return Err(RpcError::invalid_params(format!(
+                "invalid nonce {} of {:?}, expected {}",
+                tx.nonce, tx.from, expected
+            )))
+        }
+        if tx.gas > self.block_gas_limit {
+            return Err(RpcError::invalid_params("gas limit exceeds the block gas limit"))
+        }
+        let hash = tx.hash;
+        self.pending.push(tx);
+        if self.automine {
+            self.mine();
+        }
+        Ok(hash)
+    }
+
+    /// Mines a new block with all pending transactions and returns its number
+    fn mine(&mut self) -> u64 {
+        let parent = self.latest();
+        let number = parent.number + 1;
+        let parent_hash = parent.hash;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let timestamp = self.next_timestamp.take().unwrap_or_else(|| now.max(parent.timestamp + 1));
+
+        let cheats = &mut self.evm.executor.state_mut().backend.cheats;
+        cheats.block_number = Some(number.into());
+        cheats.block_timestamp = Some(timestamp.into());
+
+        let pending = std::mem::take(&mut self.pending);
+        let hashes = pending.iter().map(|tx| tx.hash).collect::<Vec<_>>();
+        let hash = block_hash(number, parent_hash, timestamp, &hashes);
+        let mut gas_used = U256::zero();
+        let mut log_index = 0;
+        for (index, tx) in pending.into_iter().enumerate() {
+            let execution = self.execute_transaction(&tx);
+            gas_used += execution.gas_used;
+            let logs = execution.logs.len();
+            self.transactions.push(MinedTransaction {
+                tx,
+                execution,
+                block_number: number,
+                block_hash: hash,
+                index,
+                cumulative_gas_used: gas_used,
+                log_index,
+            });
+            log_index += logs;
+        }
+
+        sh_status!("Mined block {} with {} transactions", number, hashes.len());
+        self.blocks.push(MinedBlock {
+            number,
+            hash,
+            parent_hash,
+            timestamp,
+            gas_used,
+            transactions: hashes,
+        });
+        number
+    }
+
+    fn execute_transaction(&mut self, tx: &PendingTransaction) -> Execution {
+        self.evm.gas_limit = tx.gas.min(u64::MAX.into()).as_u64();
+        self.evm.executor.state_mut().backend.cheats.origin = Some(tx.from);
+        self.evm.reset_traces();
+        let gas_before = self.evm.executor.gas_used();
+        let refund_before = self.evm.executor.gas_refund();
+
+        let mut execution = Execution::default();
+        match tx.to {
+            Some(to) => {
+                if let Ok((output, status, _, _)) =
+                    self.evm.call_raw(tx.from, to, tx.input.clone(), tx.value, false)
+                {
+                    execution.success = status.is_succeed();
+                    execution.output = output;
+                }
+            }
+            None => {
+                if let Ok((address, _, _, _)) = self.evm.deploy(tx.from, tx.input.clone(), tx.value)
+                {
+                    execution.success = true;
+                    execution.contract_address = Some(address);
+                }
+            }
+        }
+
+        execution.gross_gas = self.evm.executor.gas_used().saturating_sub(gas_before);
+        let refund = self.evm.executor.gas_refund().saturating_sub(refund_before);
+        // since London the refund is capped at a fifth of the gas used
+        execution.gas_used = execution.gross_gas - refund.min(execution.gross_gas / 5);
+        if execution.success {
+            execution.logs = self
+                .evm
+                .traces()
+                .iter()
+                .flat_map(|arena| arena.arena.iter())
+                .flat_map(|node| node.logs.iter().map(move |log| (node.trace.addr, log.clone())))
+                .collect();
+        }
+        execution
+    }
+
+    /// Returns the id of the snapshot of the current state
+    fn snapshot(&mut self) -> U256 {
+        let id = self.next_snapshot_id;
+        self.next_snapshot_id += U256::one();
+        self.snapshots.push(Snapshot {
+            id,
+            state: self.evm.snapshot(),
+            blocks: self.blocks.len(),
+            transactions: self.transactions.len(),
+            pending: self.pending.clone(),
+        });
+        id
+    }
+
+    /// Reverts to the snapshot, which invalidates it and all later snapshots, returns whether the
+    /// snapshot exists
+    fn revert(&mut self, id: U256) -> bool {
+        let pos = match self.snapshots.iter().position(|snapshot| snapshot.id == id) {
+            Some(pos) => pos,
+            None => return false,
+        };
+        let snapshot = self.snapshots.drain(pos..).next().expect("snapshot exists");
+        self.evm.revert_to(snapshot.state);
+        self.blocks.truncate(snapshot.blocks);
+        self.transactions.truncate(snapshot.transactions);
+        self.pending = snapshot.pending;
+        true
+    }
+
+    fn transaction_json(&self, tx: &PendingTransaction, mined: Option<&MinedTransaction>) -> Value {
+        let (v, r, s) = tx
+            .signature
+            .as_ref()
+            .map_or((0, U256::zero(), U256::zero()), |sig| (sig.v, sig.r, sig.s));
+        json!({
+            "hash": tx.hash,
+            "type": U64::from(tx.ty),
+            "nonce": tx.nonce,
+            "blockHash": mined.map(|mined| mined.block_hash),
+            "blockNumber": mined.map(|mined| U64::from(mined.block_number)),
+            "transactionIndex": mined.map(|mined| U64::from(mined.index)),
+            "from": tx.from,
+            "to": tx.to,
+            "value": tx.value,
+            "gas": tx.gas,
+            "gasPrice": tx.gas_price,
+            "input": tx.input,
+            "chainId": self.chain_id,
+            "v": U64::from(v),
+            "r": r,
+            "s": s,
+        })
+    }
+
+    fn block_json(&self, block: &MinedBlock, full: bool) -> Value {
+        let txs = self.transactions.iter().filter(|tx| tx.block_number == block.number);
+        let bloom = logs_bloom(txs.clone().flat_map(|tx| tx.execution.logs.iter()));
+        let transactions = if full {
+            txs.map(|tx| self.transaction_json(&tx.tx, Some(tx))).collect()
+        } else {
+            block.transactions.iter().map(|hash| json!(hash)).collect::<Vec<_>>()
+        };
+        json!({
+            "number": U64::from(block.number),
+            "hash": block.hash,
+            "parentHash": block.parent_hash,
+            "nonce": "0x0000000000000000",
+            "mixHash": H256::zero(),
+            "sha3Uncles": H256::from(keccak256([0xc0u8])),
+            "logsBloom": Bytes::from(bloom.to_vec()),
+            "transactionsRoot": H256::zero(),
+            "stateRoot": H256::zero(),
+            "receiptsRoot": H256::zero(),
+            "miner": self.evm.state().block_coinbase(),
+            "difficulty": U256::zero(),
+            "totalDifficulty": U256::zero(),
+            "extraData": Bytes::default(),
+            "size": U64::zero(),
+            "gasLimit": self.block_gas_limit,
+            "gasUsed": block.gas_used,
+            "baseFeePerGas": self.base_fee,
+            "timestamp": U64::from(block.timestamp),
+            "transactions": transactions,
+            "uncles": [],
+        })
+    }
+}
+
+fn receipt_json(tx: &MinedTransaction) -> Value {
+    let logs = tx
+        .execution
+        .logs
+        .iter()
+        .enumerate()
+        .map(|(idx, (address, log))| {
+            json!({
+                "address": address,
+                "topics": log.topics,
+                "data": Bytes::from(log.data.clone()),
+                "blockHash": tx.block_hash,
+                "blockNumber": U64::from(tx.block_number),
+                "transactionHash": tx.tx.hash,
+                "transactionIndex": U64::from(tx.index),
+                "logIndex": U64::from(tx.log_index + idx),
+                "removed": false,
+            })
+        })
+        .collect::<Vec<_>>();
+    json!({
+        "transactionHash": tx.tx.hash,
+        "transactionIndex": U64::from(tx.index),
+        "type": U64::from(tx.tx.ty),
+        "blockHash": tx.block_hash,
+        "blockNumber": U64::from(tx.block_number),
+        "from": tx.tx.from,
+        "to": tx.tx.to,
+        "cumulativeGasUsed": tx.cumulative_gas_used,
+        "gasUsed": tx.execution.gas_used,
+        "effectiveGasPrice": tx.tx.gas_price,
+        "contractAddress": tx.execution.contract_address,
+        "logs": logs,
+        "logsBloom": Bytes::from(logs_bloom(tx.execution.logs.iter()).to_vec()),
+        "status": U64::from(tx.execution.success as u64),
+    })
+}
+
+/// The error of a failed `eth_call` or `eth_estimateGas`, with the revert data as its data
+fn revert_error(execution: &Execution) -> RpcError {
+    let reason = foundry_utils::decode_revert(&execution.output, None).unwrap_or_default();
+    let mut err = RpcError::new(3, format!("execution reverted: {}", reason));
+    err.data = Some(json!(execution.output));
+    err
+}
+
+fn rpc_response(id: Value, result: Result<Value, RpcError>) -> Value {
+    match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(err) => {
+            let mut error = json!({ "code": err.code, "message": err.message });
+            if let Some(data) = err.data {
+                error["data"] = data;
+            }
+            json!({ "jsonrpc": "2.0", "id": id, "error": error })
+        }
+    }
+}
+
+/// Deserializes the quantity at the index, either a number or a hex string
+fn quantity(params: &[Value], index: usize) -> Result<u64, RpcError> {
+    match params.get(index) {
+        Some(Value::Number(num)) => num
+            .as_u64()
+            .ok_or_else(|| RpcError::invalid_params(format!("invalid quantity {}", num))),
+        _ => Ok(param::<U64>(params, index)?.as_u64()),
+    }
+}
+
+/// Deserializes the parameter at the index
+fn param<T: DeserializeOwned>(params: &[Value], index: usize) -> Result<T, RpcError> {
+    let value = params
+        .get(index)
+        .cloned()
+        .ok_or_else(|| RpcError::invalid_params(format!("missing parameter {}", index)))?;
+    serde_json::from_value(value).map_err(|err| RpcError::invalid_params(err.to_string()))
+}
+
+fn block_hash(number: u64, parent_hash: H256, timestamp: u64, transactions: &[H256]) -> H256 {
+    H256::from(keccak256(abi::encode(&[
+        Token::Uint(number.into()),
+        Token::FixedBytes(parent_hash.as_bytes().to_vec()),
+        Token::Uint(timestamp.into()),
+        Token::Array(
+            transactions.iter().map(|hash| Token::FixedBytes(hash.as_bytes().to_vec())).collect(),
+        ),
+    ])))
+}
+
+/// The 2048 bit bloom filter of the addresses and topics of the logs
+fn logs_bloom<'a>(logs: impl Iterator<Item = &'a (Address, RawLog)>) -> [u8; 256] {
+    let mut bloom = [0u8; 256];
+    let mut accrue = |input: &[u8]| {
+        let hash = keccak256(input);
+        for i in [0, 2, 4] {
+            let bit = (((hash[i] as usize) << 8) | hash[i + 1] as usize) & 2047;
+            bloom[255 - bit / 8] |= 1 << (bit % 8);
+        }
+    };
+    for (address, log) in logs {
+        accrue(address.as_bytes());
+        for topic in &log.topics {
+            accrue(topic.as_bytes());
+        }
+    }
+    bloom
+}
+
+/// Decodes a signed legacy, EIP-2930 or EIP-1559 transaction of the chain and recovers its sender
+fn decode_raw_transaction(
+    raw: &[u8],
+    chain_id: U256,
+    base_fee: U256,
+) -> eyre::Result<PendingTransaction> {
+    let tx = cast::decode_raw_transaction(raw)?;
+    if tx.chain_id.map_or(false, |id| id != chain_id) {
+        eyre::bail!("invalid chain id, expected {}", chain_id)
+    }
+    Ok(PendingTransaction {
+        hash: tx.hash,
+        ty: tx.transaction_type.map_or(0, |ty| ty.as_u64() as u8),
+        from: tx.from,
+        to: tx.to,
+        nonce: tx.nonce,
+        gas: tx.gas,
+        gas_price: cast::tx::effective_gas_price(&tx, base_fee),
+        value: tx.value,
+        input: tx.input,
+        signature: Some(Signature { r: tx.r, s: tx.s, v: tx.v.as_u64() }),
+    })
+}
+
+/// Reads the method and body of an HTTP/1.1 request, returns `None` if the connection was closed
+/// without a request
+fn read_http_request(reader: impl Read) -> io::Result<Option<(String, Vec<u8>)>> {
+    let mut reader = BufReader::new(reader);
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Ok(None)
+    }
+    let method = line.split_whitespace().next().unwrap_or_default().to_string();
+
+    let mut content_length = 0;
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            break
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().map_err(|_| {
+                    io::Error::new(io::ErrorKind::InvalidData, "invalid content length")
+                })?;
+            }
+        }
+    }
+
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+    Ok(Some((method, body)))
+}
+
+fn write_http_response(mut writer: impl Write, body: &str) -> io::Result<()> {
+    write!(
+        writer,
+        "HTTP/1.1 200 OK\r\n\
+         Content-Type: application/json\r\n\
+         Content-Length: {}\r\n\
+         Access-Control-Allow-Origin: *\r\n\
+         Access-Control-Allow-Methods: POST, OPTIONS\r\n\
+         Access-Control-Allow-Headers: *\r\n\
+         Connection: close\r\n\r\n{}",
+        body.len(),
+        body
+    )?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vicinity() -> MemoryVicinity {
+        MemoryVicinity {
+            gas_price: U256::zero(),
+            origin: Address::zero(),
+            chain_id: 1.into(),
+            block_hashes: Vec::new(),
+            block_number: U256::zero(),
+            block_coinbase: Address::zero(),
+            block_timestamp: U256::zero(),
+            block_difficulty: U256::zero(),
+            block_gas_limit: 30_000_000.into(),
+            block_base_fee_per_gas: U256::zero(),
+        }
+    }
+
+    #[test]
+    fn can_read_http_requests() {
+        let request = b"POST / HTTP/1.1\r\nHost: localhost\r\ncontent-length: 2\r\n\r\n{}";
+        let (method, body) = read_http_request(&request[..]).unwrap().unwrap();
+        assert_eq!(method, "POST");
+        assert_eq!(body, b"{}");
+        assert!(read_http_request(&b""[..]).unwrap().is_none());
+    }
+
+    #[test]
+    fn can_decode_raw_transactions() {
+        // the example of EIP-155
+        let raw = hex::decode("f86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a76400008025a028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83").unwrap();
+        let tx = decode_raw_transaction(&raw, 1.into(), U256::zero()).unwrap();
+        assert_eq!(tx.from, "0x9d8A62f656a8d1615C1294fd71e9CFb3E4855A4F".parse().unwrap());
+        assert_eq!(tx.to, Some("0x3535353535353535353535353535353535353535".parse().unwrap()));
+        assert_eq!(tx.nonce, 9.into());
+        assert_eq!(tx.gas, 21000.into());
+        assert_eq!(tx.value, U256::exp10(18));
+        assert!(decode_raw_transaction(&raw, 5.into(), U256::zero()).is_err());
+    }
+
+    #[test]
+    fn can_mine_and_revert_transactions() {
+        let vicinity = vicinity();
+        let evm_opts = EvmOpts::default();
+        let backend = match evm_opts.backend(&vicinity).unwrap() {
+            BackendKind::Simple(backend) => backend,
+            BackendKind::Shared(_) => unreachable!("not forking"),
+        };
+        let cfg = EvmConfig::london();
+        let mut node = Node::new(backend, &cfg, &vicinity, &evm_opts);
+        let from = Address::random();
+        let to = Address::random();
+        node.set_up(&[from], U256::exp10(18), None);
+
+        let snapshot = node.execute("evm_snapshot", &[]).unwrap();
+        let tx = json!({ "from": from, "to": to, "value": "0x2a" });
+        let hash = node.execute("eth_sendTransaction", &[tx]).unwrap();
+        assert_eq!(node.execute("eth_blockNumber", &[]).unwrap(), json!("0x1"));
+        assert_eq!(node.execute("eth_getBalance", &[json!(to)]).unwrap(), json!("0x2a"));
+        let receipt = node.execute("eth_getTransactionReceipt", &[hash.clone()]).unwrap();
+        assert_eq!(receipt["status"], json!("0x1"));
+        assert_eq!(receipt["blockNumber"], json!("0x1"));
+
+        assert_eq!(node.execute("evm_revert", &[snapshot.clone()]).unwrap(), json!(true));
+        assert_eq!(node.execute("eth_blockNumber", &[]).unwrap(), json!("0x0"));
+        assert_eq!(node.execute("eth_getBalance", &[json!(to)]).unwrap(), json!("0x0"));
+        assert_eq!(node.execute("eth_getTransactionReceipt", &[hash]).unwrap(), Value::Null);
+        assert_eq!(node.execute("evm_revert", &[snapshot]).unwrap(), json!(false));
+        assert_eq!(node.execute("eth_foo", &[]).unwrap_err().code, -32601);
+    }
+
+    #[test]
+    fn can_impersonate_and_set_state() {
+        let vicinity = vicinity();
+        let evm_opts = EvmOpts::default();
+        let backend = match evm_opts.backend(&vicinity).unwrap() {
+            BackendKind::Simple(backend) => backend,
+            BackendKind::Shared(_) => unreachable!("not forking"),
+        };
+        let cfg = EvmConfig::london();
+        let mut node = Node::new(backend, &cfg, &vicinity, &evm_opts);
+        node.set_up(&[], U256::zero(), None);
+        let whale = Address::random();
+        let to = Address::random();
+
+        let tx = json!({ "from": whale, "to": to, "value": "0x2a" });
+        assert!(node.execute("eth_sendTransaction", &[tx.clone()]).is_err());
+        node.execute("anvil_impersonateAccount", &[json!(whale)]).unwrap();
+        node.execute("hardhat_setBalance", &[json!(whale), json!("0x100")]).unwrap();
+        node.execute("eth_sendTransaction", &[tx]).unwrap();
+        assert_eq!(node.execute("eth_getBalance", &[json!(whale)]).unwrap(), json!("0xd6"));
+
+        node.execute("anvil_setCode", &[json!(to), json!("0x6000")]).unwrap();
+        assert_eq!(node.execute("eth_getCode", &[json!(to)]).unwrap(), json!("0x6000"));
+        let value = H256::from_low_u64_be(7);
+        node.execute("anvil_setStorageAt", &[json!(to), json!("0x1"), json!(value)]).unwrap();
+        assert_eq!(
+            node.execute("eth_getStorageAt", &[json!(to), json!("0x1")]).unwrap(),
+            json!(value)
+        );
+
+        node.execute("evm_setNextBlockTimestamp", &[json!(2_000_000_000)]).unwrap();
+        node.execute("evm_mine", &[]).unwrap();
+        let block = node.execute("eth_getBlockByNumber", &[json!("latest"), json!(false)]).unwrap();
+        assert_eq!(block["number"], json!("0x2"));
+        assert_eq!(block["timestamp"], json!(U64::from(2_000_000_000u64)));
+    }
+}
@@ -1,11 +1,23 @@
 //! remappings command
 
-use crate::cmd::Cmd;
+use crate::{
+    cmd::Cmd,
+    exit_code::ExitCode,
+    shell::{sh_println, sh_status, sh_warn},
+};
 use clap::{Parser, ValueHint};
 use ethers::solc::{remappings::Remapping, ProjectPathsConfig};
-use std::path::PathBuf;
+use foundry_config::remappings_from_newline;
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
-/// Command to list remappings
+/// The file the remappings are written to
+pub const REMAPPINGS_FILE: &str = "remappings.txt";
+
+/// Command to list, write and check remappings
 #[derive(Debug, Clone, Parser)]
 pub struct RemappingArgs {
     #[clap(
@@ -20,6 +32,17 @@ pub struct RemappingArgs {
         value_hint = ValueHint::DirPath
     )]
     lib_paths: Vec<PathBuf>,
+    #[clap(
+        help = "write the remappings to `remappings.txt`, existing entries are kept",
+        long,
+        conflicts_with = "check"
+    )]
+    write: bool,
+    #[clap(
+        help = "fail if `remappings.txt` is missing a remapping, contains a remapping to a path that doesn't exist or if dependencies remap the same prefix to different paths",
+        long
+    )]
+    check: bool,
 }
 
 impl Cmd for RemappingArgs {
@@ -34,8 +57,206 @@ impl Cmd for RemappingArgs {
         } else {
             self.lib_paths
         };
-        let remappings: Vec<_> = lib_paths.iter().flat_map(Remapping::find_many).collect();
-        remappings.iter().for_each(|x| println!("{}", x));
+        let remappings = DependencyRemappings::find(&root, &lib_paths)?;
+        for conflict in remappings.conflicts() {
+            sh_warn!("{}", conflict);
+        }
+
+        let file = root.join(REMAPPINGS_FILE);
+        if self.check {
+            let existing = read_remappings(&file)?
+                .ok_or_else(|| eyre::eyre!("{} not found", REMAPPINGS_FILE))?;
+            let mut failures = remappings.conflicts();
+            failures.extend(
+                remappings
+                    .canonical()
+                    .into_iter()
+                    .filter(|r| !existing.iter().any(|e| e.name == r.name))
+                    .map(|r| format!("missing remapping `{}`", r)),
+            );
+            failures.extend(
+                existing
+                    .iter()
+                    .filter(|r| !root.join(&r.path).exists())
+                    .map(|r| format!("remapping `{}` points to a path that doesn't exist", r)),
+            );
+            if !failures.is_empty() {
+                failures.iter().for_each(|failure| sh_println!("{}", failure));
+                return Err(ExitCode::Failure.error(format!(
+                    "{} is out of date, run `forge remappings --write`",
+                    REMAPPINGS_FILE
+                )))
+            }
+            sh_status!("{} is up to date.", REMAPPINGS_FILE);
+        } else if self.write {
+            let mut all = read_remappings(&file)?.unwrap_or_default();
+            let added = remappings
+                .canonical()
+                .into_iter()
+                .filter(|r| !all.iter().any(|e| e.name == r.name))
+                .collect::<Vec<_>>();
+            sh_status!("added {} remappings to {}", added.len(), REMAPPINGS_FILE);
+            all.extend(added);
+            all.sort_by(|a, b| a.name.cmp(&b.name));
+            let content = all.iter().map(|r| format!("{}\n", r)).collect::<String>();
+            std::fs::write(file, content)?;
+        } else {
+            remappings.canonical().iter().for_each(|x| sh_println!("{}", x));
+        }
         Ok(())
     }
 }
+
+/// The remappings declared by the dependencies of a project, relative to the project's root
+#[derive(Debug, Default)]
+pub struct DependencyRemappings {
+    /// All candidates for a prefix and where they were found, in order of precedence
+    candidates: BTreeMap<String, Vec<(String, String)>>,
+}
+
+impl DependencyRemappings {
+    /// Collects the remappings of the dependencies installed in the `lib_paths`.
+    ///
+    /// The remappings inferred from the layout of the libraries take precedence, followed by the
+    /// remappings the dependencies declare themselves in their `remappings.txt`, `foundry.toml`
+    /// and `package.json` files.
+    pub fn find(root: &Path, lib_paths: &[PathBuf]) -> eyre::Result<Self> {
+        let mut remappings = Self::default();
+        for lib in lib_paths {
+            let lib = root.join(lib);
+            for remapping in Remapping::find_many(&lib) {
+                remappings.insert(root, remapping, &display(root, &lib));
+            }
+        }
+
+        for lib in lib_paths {
+            let lib = root.join(lib);
+            if !lib.is_dir() {
+                continue
+            }
+            let mut dependencies = std::fs::read_dir(&lib)?
+                .filter_map(Result::ok)
+                .map(|entry| entry.path())
+                .filter(|path| path.is_dir())
+                .collect::<Vec<_>>();
+            dependencies.sort();
+            for dependency in dependencies {
+                remappings.insert_declared(root, &dependency)?;
+            }
+        }
+        Ok(remappings)
+    }
+
+    /// Returns the remapping with the highest precedence for every prefix
+    pub fn canonical(&self) -> Vec<Remapping> {
+        self.candidates
+            .iter()
+            .map(|(name, candidates)| Remapping {
+                name: name.clone(),
+                path: candidates[0].0.clone(),
+            })
+            .collect()
+    }
+
+    /// Returns a message for every prefix that is remapped to different paths
+    pub fn conflicts(&self) -> Vec<String> {
+        self.candidates
+            .iter()
+            .filter(|(_, candidates)| candidates.iter().any(|(path, _)| *path != candidates[0].0))
+            .map(|(name, candidates)| {
+                let found = candidates
+                    .iter()
+                    .map(|(path, source)| format!("`{}` ({})", path, source))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                format!(
+                    "conflicting remappings for `{}`: {}, using `{}`",
+                    name, found, candidates[0].0
+                )
+            })
+            .collect()
+    }
+
+    /// Adds the remappings of the dependency's `remappings.txt`, `foundry.toml` and
+    /// `package.json` files
+    fn insert_declared(&mut self, root: &Path, dependency: &Path) -> eyre::Result<()> {
+        let file = dependency.join(REMAPPINGS_FILE);
+        for remapping in read_remappings(&file)?.unwrap_or_default() {
+            self.insert(root, rebase(dependency, remapping), &display(root, &file));
+        }
+
+        let file = dependency.join(foundry_config::Config::FILE_NAME);
+        if file.is_file() {
+            let config: toml::Value = toml::from_str(&std::fs::read_to_string(&file)?)?;
+            let declared = config
+                .get("profile")
+                .and_then(|profiles| profiles.get("default"))
+                .or_else(|| config.get("default"))
+                .and_then(|profile| profile.get("remappings"))
+                .and_then(|remappings| remappings.as_array())
+                .cloned()
+                .unwrap_or_default();
+            for remapping in declared.iter().filter_map(|r| r.as_str()) {
+                let remapping = Remapping::from_str(remapping)?;
+                self.insert(root, rebase(dependency, remapping), &display(root, &file));
+            }
+        }
+
+        // npm style imports, like `@openzeppelin/contracts/token/ERC20/ERC20.sol`, resolve to
+        // the directory of the package's manifest
+        let mut manifests = vec![dependency.to_path_buf()];
+        manifests.extend(
+            std::fs::read_dir(dependency)?
+                .filter_map(Result::ok)
+                .map(|entry| entry.path())
+                .filter(|path| path.is_dir() && !path.ends_with("node_modules")),
+        );
+        manifests.sort();
+        for dir in manifests {
+            let file = dir.join("package.json");
+            if !file.is_file() {
+                continue
+            }
+            let manifest: serde_json::Value =
+                serde_json::from_str(&std::fs::read_to_string(&file)?)?;
+            if let Some(name) = manifest.get("name").and_then(|name| name.as_str()) {
+                let remapping =
+                    Remapping { name: format!("{}/", name), path: dir.display().to_string() };
+                self.insert(root, remapping, &display(root, &file));
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds the remapping, the path is made relative to the `root`
+    fn insert(&mut self, root: &Path, remapping: Remapping, source: &str) {
+        let path = Path::new(&remapping.path);
+        let mut path = path.strip_prefix(root).unwrap_or(path).to_string_lossy().to_string();
+        if !path.ends_with('/') {
+            path.push('/');
+        }
+        self.candidates.entry(remapping.name).or_default().push((path, source.to_string()));
+    }
+}
+
+/// Reads the remappings of the file, `None` if the file doesn't exist
+fn read_remappings(file: &Path) -> eyre::Result<Option<Vec<Remapping>>> {
+    if !file.is_file() {
+        return Ok(None)
+    }
+    let content = std::fs::read_to_string(file)?;
+    Ok(Some(remappings_from_newline(&content).collect::<Result<_, _>>()?))
+}
+
+/// Turns a remapping declared by the dependency, relative to the dependency, into an absolute one
+fn rebase(dependency: &Path, mut remapping: Remapping) -> Remapping {
+    if Path::new(&remapping.path).is_relative() {
+        remapping.path = dependency.join(&remapping.path).display().to_string();
+    }
+    remapping
+}
+
+/// Returns the path relative to the root for display purposes
+fn display(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root).unwrap_or(path).display().to_string()
+}
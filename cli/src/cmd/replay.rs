@@ -0,0 +1,268 @@
+//! replay command
+use crate::{
+    cmd::Cmd,
+    explorer::explorer,
+    opts::{evm::EvmArgs, ClapChain},
+    shell::{self, sh_println},
+    utils,
+};
+use ansi_term::Colour;
+use clap::Parser;
+use ethers::{
+    providers::Middleware,
+    types::{Transaction, H256},
+};
+use evm_adapters::{
+    call_tracing::ExecutionInfo,
+    chain_profile::{precompiles, system_contracts},
+    evm_opts::{BackendKind, EvmOpts},
+    sputnik::{
+        cheatcodes::debugger::DebugArena,
+        helpers::{vm, TestSputnikVM},
+        Executor, SharedBackend, SputnikExecutor, PRECOMPILES_MAP,
+    },
+    Evm, ForkClient,
+};
+use foundry_config::{find_project_root_path, Config};
+use std::collections::{BTreeMap, BTreeSet};
+use ui::{TUIExitReason, Tui, Ui};
+
+#[derive(Debug, Clone, Parser)]
+pub struct ReplayArgs {
+    #[clap(help = "the hash of the transaction to replay")]
+    pub tx_hash: H256,
+
+    #[clap(flatten)]
+    pub evm_opts: EvmArgs,
+
+    #[clap(
+        long,
+        short,
+        help = "skip the transactions that precede the transaction in its block, faster but the state may differ from the original execution"
+    )]
+    pub quick: bool,
+
+    #[clap(
+        long,
+        env = "ETHERSCAN_API_KEY",
+        help = "decode the calls to verified contracts with their ABIs from the explorer of the chain"
+    )]
+    pub etherscan_api_key: Option<String>,
+
+    #[clap(flatten)]
+    pub chain: ClapChain,
+}
+
+impl Cmd for ReplayArgs {
+    type Output = ();
+
+    fn run(self) -> eyre::Result<Self::Output> {
+        let figment = Config::figment_with_root(find_project_root_path()?).merge(&self.evm_opts);
+        let mut evm_opts = figment.extract::<EvmOpts>()?;
+        let config = Config::try_from(figment)?.sanitized();
+        if config.offline {
+            eyre::bail!(
+                "replaying a transaction fetches it and its block, which can't be done offline"
+            )
+        }
+        let url = evm_opts
+            .fork_url
+            .clone()
+            .or_else(|| std::env::var("ETH_RPC_URL").ok())
+            .ok_or_else(|| eyre::eyre!("replaying a transaction requires a `--fork-url`"))?;
+        let url = config.resolve_rpc_url(&url)?;
+        evm_opts.rpc_endpoints = config.resolved_rpc_endpoints();
+
+        let rt = tokio::runtime::Runtime::new().expect("could not start tokio rt");
+        let provider = ForkClient::provider(url.as_str())?;
+        let tx = rt
+            .block_on(provider.get_transaction(self.tx_hash))?
+            .ok_or_else(|| eyre::eyre!("transaction {:?} not found", self.tx_hash))?;
+        let block_number = tx
+            .block_number
+            .ok_or_else(|| eyre::eyre!("transaction {:?} is still pending", self.tx_hash))?
+            .as_u64();
+        let block = rt
+            .block_on(provider.get_block_with_txs(block_number))?
+            .ok_or_else(|| eyre::eyre!("block {} not found", block_number))?;
+
+        // the state is forked at the end of the parent block, the environment is the tx's block
+        evm_opts.fork_url = Some(url);
+        evm_opts.fork_block_number = Some(block_number.saturating_sub(1));
+        let mut vicinity = rt.block_on(evm_adapters::sputnik::vicinity(
+            &provider,
+            evm_opts.env.chain_id,
+            Some(block_number),
+            Some(tx.from),
+        ))?;
+        vicinity.gas_price = tx.gas_price.unwrap_or_default();
+        self.evm_opts.env.overrides().apply(&mut vicinity);
+        let backend = match evm_opts.backend(&vicinity)? {
+            BackendKind::Shared(backend) => backend,
+            BackendKind::Simple(_) => unreachable!("forking always uses the shared backend"),
+        };
+
+        let cfg = utils::sputnik_cfg(&config.evm_version)?;
+        let mut evm = Executor::new_with_cheatcodes(
+            backend,
+            tx.gas.as_u64(),
+            &cfg,
+            precompiles(evm_opts.chain_profile),
+            evm_opts.ffi,
+            false,
+            evm_opts.debug,
+        );
+        evm.set_rpc_endpoints(evm_opts.rpc_endpoints.clone());
+        evm.set_debug_step_limit(evm_opts.debug_step_limit);
+        evm.initialize_contracts(system_contracts(evm_opts.chain_profile));
+
+        if !self.quick {
+            let preceding =
+                block.transactions.iter().take_while(|t| t.hash != tx.hash).collect::<Vec<_>>();
+            sh_println!(
+                "Executing {} preceding transactions of block {}",
+                preceding.len(),
+                block_number
+            );
+            let debug = std::mem::replace(&mut evm.executor.state_mut().debug_enabled, false);
+            for preceding_tx in preceding {
+                execute_tx(&mut evm, preceding_tx);
+            }
+            evm.executor.state_mut().debug_enabled = debug;
+        }
+
+        evm.reset_traces();
+        evm.executor.state_mut().debug_steps = vec![Default::default()];
+        evm.set_tracing_enabled(true);
+        let (success, retdata, gas_used) = execute_tx(&mut evm, &tx);
+
+        if evm_opts.debug {
+            let calls: Vec<DebugArena> = evm.debug_calls();
+            let mut flattened = Vec::new();
+            calls.last().expect("debug must be enabled by now").flatten(0, &mut flattened);
+            flattened = flattened[1..].to_vec();
+            let tui = Tui::new(flattened, 0, BTreeMap::new(), BTreeMap::new(), BTreeMap::new())?;
+            match tui.start().expect("Failed to start tui") {
+                TUIExitReason::CharExit => return Ok(()),
+            }
+        }
+
+        let known_contracts = BTreeMap::new();
+        let mut identified_contracts = BTreeMap::new();
+        if let Ok(explorer) =
+            explorer(&config, self.chain.inner as u64, None, None, self.etherscan_api_key)
+        {
+            let addresses = evm
+                .traces()
+                .iter()
+                .flat_map(|arena| arena.arena.iter().map(|node| node.trace.addr))
+                .collect::<BTreeSet<_>>();
+            for address in addresses {
+                // unverified contracts and precompiles are left undecoded
+                let fetched = rt.block_on(explorer.contract(address));
+                if let Ok(contract) = fetched {
+                    identified_contracts.insert(address, contract);
+                }
+            }
+        }
+        let (funcs, events, errors) = foundry_utils::flatten_known_contracts(&known_contracts);
+        let mut exec_info = ExecutionInfo::new(
+            &known_contracts,
+            &mut identified_contracts,
+            &funcs,
+            &events,
+            &errors,
+        );
+        let vm = vm();
+        let mut out = String::new();
+        evm.traces()
+            .iter()
+            .for_each(|trace| trace.pretty_print(0, &mut exec_info, &vm, "", &mut out));
+        sh_println!("{}", out);
+
+        if success {
+            sh_println!("{}", shell::paint(Colour::Green, "Transaction executed successfully."));
+        } else {
+            let reason = foundry_utils::decode_revert(&retdata, None).unwrap_or_default();
+            sh_println!(
+                "{}",
+                shell::paint(Colour::Red, format!("Transaction reverted: {}", reason))
+            );
+        }
+        sh_println!("Gas Used: {}", gas_used);
+
+        Ok(())
+    }
+}
+
+/// Executes the failed `tx` on top of the state at the end of its parent block and returns its
+/// revert reason
+///
+/// The transactions that precede the `tx` in its block are skipped, so a transaction that only
+/// failed because of them succeeds in the simulation, which is an error.
+pub fn simulate_revert_reason(url: &str, tx: &Transaction) -> eyre::Result<String> {
+    let block_number = tx
+        .block_number
+        .ok_or_else(|| eyre::eyre!("transaction {:?} is still pending", tx.hash))?
+        .as_u64();
+
+    let rt = tokio::runtime::Runtime::new().expect("could not start tokio rt");
+    let provider = ForkClient::provider(url)?;
+    let evm_opts = EvmOpts {
+        fork_url: Some(url.to_string()),
+        fork_block_number: Some(block_number.saturating_sub(1)),
+        ..Default::default()
+    };
+    let mut vicinity = rt.block_on(evm_adapters::sputnik::vicinity(
+        &provider,
+        None,
+        Some(block_number),
+        Some(tx.from),
+    ))?;
+    vicinity.gas_price = tx.gas_price.unwrap_or_default();
+    let backend = match evm_opts.backend(&vicinity)? {
+        BackendKind::Shared(backend) => backend,
+        BackendKind::Simple(_) => unreachable!("forking always uses the shared backend"),
+    };
+
+    let cfg = utils::sputnik_cfg(&Default::default())?;
+    let mut evm = Executor::new_with_cheatcodes(
+        backend,
+        tx.gas.as_u64(),
+        &cfg,
+        &*PRECOMPILES_MAP,
+        false,
+        false,
+        false,
+    );
+    let (success, retdata, _) = execute_tx(&mut evm, tx);
+    if success {
+        eyre::bail!(
+            "the transaction succeeds at the end of block {}, it depends on the transactions that precede it",
+            block_number.saturating_sub(1)
+        )
+    }
+    Ok(foundry_utils::decode_revert(&retdata, None)
+        .unwrap_or_else(|_| format!("0x{}", hex::encode(&retdata))))
+}
+
+/// Executes the `tx` as it was sent and returns whether it succeeded, its returndata and the gas
+/// used
+fn execute_tx(
+    evm: &mut TestSputnikVM<'_, SharedBackend>,
+    tx: &Transaction,
+) -> (bool, Vec<u8>, u64) {
+    evm.gas_limit = tx.gas.as_u64();
+    evm.executor.state_mut().backend.cheats.origin = Some(tx.from);
+    let result = if let Some(to) = tx.to {
+        evm.call_raw(tx.from, to, tx.input.clone(), tx.value, false)
+            .map(|(retdata, status, gas, _)| (status.is_succeed(), retdata.to_vec(), gas))
+    } else {
+        evm.deploy(tx.from, tx.input.clone(), tx.value)
+            .map(|(_, status, gas, _)| (status.is_succeed(), Vec::new(), gas))
+    };
+    result.unwrap_or_else(|err| {
+        tracing::debug!(?err, "failed to execute transaction {:?}", tx.hash);
+        (false, Vec::new(), 0)
+    })
+}
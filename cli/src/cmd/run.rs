@@ -1,24 +1,52 @@
-use crate::cmd::{build::BuildArgs, compile, manual_compile, Cmd};
+use crate::{
+    cmd::{
+        broadcast::{BroadcastArgs, CreationCode},
+        build::BuildArgs,
+        compile, manual_compile, Cmd,
+    },
+    exit_code::ExitCode,
+    hooks::{self, Hook},
+    shell::{self, sh_println, sh_status, sh_warn},
+    utils::UNLIMITED_GAS_WARNING,
+};
 use clap::{Parser, ValueHint};
-use ethers::{abi::Abi, prelude::artifacts::CompactContract};
+use ethers::{
+    abi::{Abi, Function},
+    prelude::{artifacts::CompactContract, Graph},
+    types::Address,
+};
 use forge::ContractRunner;
-use foundry_utils::IntoFunction;
-use std::{collections::BTreeMap, path::PathBuf};
+use foundry_utils::{HardhatArtifact, IntoFunction};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
 use ui::{TUIExitReason, Tui, Ui};
 
-use ethers::solc::{MinimalCombinedArtifacts, Project};
+use ethers::solc::{
+    artifacts::{CompilerOutput, Source},
+    sourcemap::SourceMap,
+    MinimalCombinedArtifacts, Project,
+};
 
-use crate::opts::evm::EvmArgs;
+use crate::opts::{evm::EvmArgs, forge::FullContractInfo};
 use ansi_term::Colour;
 use ethers::{
     prelude::artifacts::ContractBytecode,
     solc::artifacts::{CompactContractSome, ContractBytecodeSome},
 };
 use evm_adapters::{
-    call_tracing::ExecutionInfo,
+    call_tracing::{CallTraceArena, CallTraceDiff, ExecutionInfo},
     evm_opts::{BackendKind, EvmOpts},
-    sputnik::{cheatcodes::debugger::DebugArena, helpers::vm},
+    sputnik::{
+        cheatcodes::debugger::{
+            arena_internal_calls, explain_arena_revert, DebugArena, RevertExplanation,
+        },
+        helpers::vm,
+    },
 };
+use eyre::WrapErr;
 use foundry_config::{figment::Figment, Config};
 
 // Loads project's figment and merges the build cli arguments into it
@@ -48,6 +76,35 @@ pub struct RunArgs {
         help = "the function you want to call on the script contract, defaults to run()"
     )]
     pub sig: Option<String>,
+
+    #[clap(
+        long,
+        help = "write the call traces of the run as json to the given file",
+        value_hint = ValueHint::FilePath
+    )]
+    pub export: Option<PathBuf>,
+
+    #[clap(
+        long,
+        help = "diff the call traces of the run against the traces of a previous run, exported with `--export`",
+        value_hint = ValueHint::FilePath
+    )]
+    pub compare: Option<PathBuf>,
+
+    #[clap(
+        long,
+        help = "print the EIP-2930 access list of the accounts and storage slots accessed by the script as json"
+    )]
+    pub access_list: bool,
+
+    #[clap(
+        long,
+        help = "reconstruct the internal function calls of the script from the source maps and print them with the gas they used"
+    )]
+    pub decode_internal: bool,
+
+    #[clap(flatten)]
+    pub broadcast: BroadcastArgs,
 }
 
 impl Cmd for RunArgs {
@@ -55,21 +112,45 @@ impl Cmd for RunArgs {
     fn run(self) -> eyre::Result<Self::Output> {
         // Keeping it like this for simplicity.
         #[cfg(not(feature = "sputnik-evm"))]
-        unimplemented!("`run` does not work with EVMs other than Sputnik yet");
+        eyre::bail!(
+            "`run` requires Sputnik, other EVMs have no cheatcodes, traces or debugger support yet"
+        );
 
         let figment: Figment = From::from(&self);
         let mut evm_opts = figment.extract::<EvmOpts>()?;
-        let config = Config::from_provider(figment).sanitized();
+        let config = Config::try_from(figment)?.sanitized();
+        self.evm_opts.resolve_fork(&mut evm_opts, &config)?;
+        let broadcast_config =
+            (self.broadcast.broadcast || self.broadcast.multisend).then(|| config.clone());
+        evm_opts.record_access_list = self.access_list;
+        evm_opts.deployments = config.read_deployments()?;
         let evm_version = config.evm_version;
+        let external_artifacts = config.external_artifacts.clone();
+        let bytecode_contracts = config.read_bytecode_contracts()?;
         if evm_opts.debug {
             evm_opts.verbosity = 3;
         }
+        let verbosity = evm_opts.verbosity;
+        let debug = evm_opts.debug;
+        // exporting and comparing requires traces
+        if self.export.is_some() || self.compare.is_some() {
+            evm_opts.verbosity = evm_opts.verbosity.max(3);
+        }
+        // internal calls are decoded from the debug steps of the identified contracts
+        if self.decode_internal {
+            evm_opts.debug = true;
+            evm_opts.verbosity = evm_opts.verbosity.max(3);
+        }
+
+        if evm_opts.unlimited_gas {
+            sh_warn!("{}", UNLIMITED_GAS_WARNING);
+        }
 
         let func = IntoFunction::into(self.sig.as_deref().unwrap_or("run()"));
-        let BuildOutput { project, contract, highlevel_known_contracts, sources } =
+        let BuildOutput { project, contract, highlevel_known_contracts, creation_codes, sources } =
             self.build(config)?;
 
-        let known_contracts = highlevel_known_contracts
+        let mut known_contracts = highlevel_known_contracts
             .iter()
             .map(|(name, c)| {
                 (
@@ -81,73 +162,124 @@ impl Cmd for RunArgs {
                 )
             })
             .collect::<BTreeMap<String, (Abi, Vec<u8>)>>();
+        // compiled contracts take precedence over precompiled ones of the same name
+        HardhatArtifact::extend_known_contracts(&mut known_contracts, &external_artifacts)?;
+        for (name, contract) in bytecode_contracts {
+            if let Some(code) = contract.bin.and_then(|code| code.into_bytes()) {
+                evm_opts.bytecode_contracts.insert(name.clone(), code.to_vec());
+            }
+            if let Some(code) = contract.bin_runtime.and_then(|code| code.into_bytes()) {
+                known_contracts
+                    .entry(name)
+                    .or_insert((contract.abi.unwrap_or_default(), code.to_vec()));
+            }
+        }
 
         let CompactContractSome { abi, bin, .. } = contract;
         // this should never fail if compilation was successful
         let bytecode = bin.into_bytes().unwrap();
         let needs_setup = abi.functions().any(|func| func.name == "setUp");
 
-        let cfg = crate::utils::sputnik_cfg(&evm_version);
+        let cfg = crate::utils::sputnik_cfg(&evm_version)?;
         let vicinity = evm_opts.vicinity()?;
-        let backend = evm_opts.backend(&vicinity)?;
+        let fetching =
+            shell::progress_bar(evm_opts.prefetch_accounts.len() as u64, "fetching fork state");
+        let backend = evm_opts
+            .backend_with_progress(&vicinity, |fetched, _| fetching.set_position(fetched as u64));
+        fetching.finish_and_clear();
+        let backend = backend?;
 
         // need to match on the backend type
-        let result = match backend {
+        let execute = |evm_opts: &EvmOpts| match backend {
             BackendKind::Simple(ref backend) => {
                 let runner = ContractRunner::new(
-                    &evm_opts,
+                    evm_opts,
                     &cfg,
                     backend,
                     &abi,
-                    bytecode,
+                    bytecode.clone(),
                     Some(evm_opts.sender),
                     None,
                 );
-                runner.run_test(&func, needs_setup, Some(&known_contracts))?
+                runner.run_test(&func, needs_setup, None, Some(&known_contracts))
             }
             BackendKind::Shared(ref backend) => {
                 let runner = ContractRunner::new(
-                    &evm_opts,
+                    evm_opts,
                     &cfg,
                     backend,
                     &abi,
-                    bytecode,
+                    bytecode.clone(),
                     Some(evm_opts.sender),
                     None,
                 );
-                runner.run_test(&func, needs_setup, Some(&known_contracts))?
+                runner.run_test(&func, needs_setup, None, Some(&known_contracts))
             }
         };
+        let result = execute(&evm_opts)?;
 
-        if evm_opts.debug {
-            // 4. Boot up debugger
-            let source_code: BTreeMap<u32, String> = sources
-                .iter()
-                .map(|(id, path)| {
-                    if let Some(resolved) =
-                        project.paths.resolve_library_import(&PathBuf::from(path))
-                    {
-                        (
-                            *id,
-                            std::fs::read_to_string(resolved).expect(&*format!(
-                                "Something went wrong reading the source file: {:?}",
-                                path
-                            )),
-                        )
-                    } else {
-                        (
-                            *id,
-                            std::fs::read_to_string(path).expect(&*format!(
-                                "Something went wrong reading the source file: {:?}",
-                                path
-                            )),
-                        )
-                    }
-                })
-                .collect();
+        if let Some(ref config) = broadcast_config {
+            if !result.success {
+                eyre::bail!("script failed, no transactions were recorded")
+            }
+            let (txs, fork_url) =
+                (&result.broadcastable_transactions, evm_opts.fork_url.as_deref());
+            if self.broadcast.multisend {
+                self.broadcast.write_multisend(txs, fork_url, config, &self.path)?;
+            } else {
+                hooks::run_pre(config, Hook::PreBroadcast)?;
+                let journal =
+                    self.broadcast.run(txs, &creation_codes, fork_url, config, &self.path);
+                hooks::run_post(config, Hook::PostBroadcast, journal.is_ok());
+                let journal = journal?;
+                if self.broadcast.verify {
+                    self.broadcast.verify(&journal, &creation_codes, &self.opts)?;
+                }
+            }
+        }
+
+        if let Some(ref path) = self.export {
+            let traces = result.traces.clone().unwrap_or_default();
+            std::fs::write(path, serde_json::to_string(&traces)?)?;
+            sh_status!("Traces written to {}", path.display());
+        }
+
+        if let Some(ref access_list) = result.access_list {
+            // only the json goes to stdout, so it can be piped
+            sh_println!("{}", serde_json::to_string_pretty(access_list)?);
+        }
+
+        if let Some(ref path) = self.compare {
+            let old: Vec<CallTraceArena> = serde_json::from_str(&std::fs::read_to_string(path)?)
+                .wrap_err_with(|| format!("failed to read traces from {}", path.display()))?;
+            let new = result.traces.clone().unwrap_or_default();
+            let (funcs, _, _) = foundry_utils::flatten_known_contracts(&known_contracts);
+            print_trace_diff(&old, &new, &funcs);
+            return Ok(())
+        }
 
+        if self.decode_internal {
+            let calls = result.debug_calls.as_ref().expect("debug steps are recorded");
+            let index = if needs_setup && calls.len() > 1 { 1 } else { 0 };
+            let identified_contracts = result.identified_contracts.clone().unwrap_or_default();
+            let mut profile = InternalGasProfile::new();
+            sh_println!("== Internal Calls ==");
+            print_internal_calls(
+                &calls[index],
+                &identified_contracts,
+                &highlevel_known_contracts,
+                &read_source_code(&project, &sources),
+                &mut profile,
+            );
+            sh_println!();
+            print_internal_gas_profile(profile);
+        }
+
+        if debug {
+            // 4. Boot up debugger
+            let source_code = read_source_code(&project, &sources);
             let calls: Vec<DebugArena> = result.debug_calls.expect("Debug must be enabled by now");
-            println!("debugging");
+            sh_status!("debugging");
             let index = if needs_setup && calls.len() > 1 { 1 } else { 0 };
             let mut flattened = Vec::new();
             calls[index].flatten(0, &mut flattened);
@@ -162,22 +294,23 @@ impl Cmd for RunArgs {
             match tui.start().expect("Failed to start tui") {
                 TUIExitReason::CharExit => return Ok(()),
             }
-        } else if evm_opts.verbosity > 2 {
+        } else if verbosity > 2 {
             // support traces
             if let (Some(traces), Some(identified_contracts)) =
                 (&result.traces, &result.identified_contracts)
             {
-                if !result.success && evm_opts.verbosity == 3 || evm_opts.verbosity > 3 {
+                if !result.success && verbosity == 3 || verbosity > 3 {
                     let mut ident = identified_contracts.clone();
                     let (funcs, events, errors) =
                         foundry_utils::flatten_known_contracts(&known_contracts);
                     let mut exec_info =
                         ExecutionInfo::new(&known_contracts, &mut ident, &funcs, &events, &errors);
                     let vm = vm();
-                    if evm_opts.verbosity > 4 || !result.success {
+                    let mut out = String::new();
+                    if verbosity > 4 || !result.success {
                         // print setup calls as well
                         traces.iter().for_each(|trace| {
-                            trace.pretty_print(0, &mut exec_info, &vm, "");
+                            trace.pretty_print(0, &mut exec_info, &vm, "", &mut out);
                         });
                     } else if !traces.is_empty() {
                         traces.last().expect("no last but not empty").pretty_print(
@@ -185,32 +318,320 @@ impl Cmd for RunArgs {
                             &mut exec_info,
                             &vm,
                             "",
+                            &mut out,
                         );
                     }
+                    sh_println!("{}", out.trim_end());
                 }
-                println!();
+                sh_println!();
             }
+        } else if shell::is_json() {
+            shell::print_json(&serde_json::json!({
+                "success": result.success,
+                "gasUsed": result.gas_used,
+                "logs": result.logs,
+            }))?;
         } else {
             // 5. print the result nicely
             if result.success {
-                println!("{}", Colour::Green.paint("Script ran successfully."));
+                sh_println!("{}", shell::paint(Colour::Green, "Script ran successfully."));
             } else {
-                println!("{}", Colour::Red.paint("Script failed."));
+                sh_println!("{}", shell::paint(Colour::Red, "Script failed."));
             }
 
-            println!("Gas Used: {}", result.gas_used);
-            println!("== Logs == ");
-            result.logs.iter().for_each(|log| println!("{}", log));
+            sh_println!("Gas Used: {}", result.gas_used);
+            sh_println!("== Logs == ");
+            result.logs.iter().for_each(|log| sh_println!("{}", log));
         }
 
+        if !result.success {
+            let has_reason = result.reason.as_deref().map_or(false, |reason| !reason.is_empty());
+            if !has_reason && !debug && !shell::is_json() {
+                // the revert is located in the debug steps, which are only recorded on demand
+                let result = if result.debug_calls.is_some() {
+                    result
+                } else {
+                    let mut evm_opts = evm_opts.clone();
+                    evm_opts.debug = true;
+                    evm_opts.verbosity = evm_opts.verbosity.max(3);
+                    execute(&evm_opts)?
+                };
+                if let (Some(calls), Some(identified_contracts)) =
+                    (&result.debug_calls, &result.identified_contracts)
+                {
+                    let index = if needs_setup && calls.len() > 1 { 1 } else { 0 };
+                    print_revert_explanation(
+                        &calls[index],
+                        identified_contracts,
+                        &highlevel_known_contracts,
+                        &sources,
+                        &read_source_code(&project, &sources),
+                    );
+                }
+            }
+            ExitCode::Failure.exit()
+        }
         Ok(())
     }
 }
 
+/// Returns the source code of the compiled sources by source file index
+pub(crate) fn read_source_code(
+    project: &Project<MinimalCombinedArtifacts>,
+    sources: &BTreeMap<u32, String>,
+) -> BTreeMap<u32, String> {
+    sources
+        .iter()
+        .map(|(id, path)| {
+            let path = project
+                .paths
+                .resolve_library_import(&PathBuf::from(path))
+                .unwrap_or_else(|| PathBuf::from(path));
+            (
+                *id,
+                std::fs::read_to_string(&path)
+                    .expect(&*format!("Something went wrong reading the source file: {:?}", path)),
+            )
+        })
+        .collect()
+}
+
+/// Returns the source map of the contract identified at the address, of its creation bytecode if
+/// the flag is set
+fn contract_source_map(
+    identified_contracts: &BTreeMap<Address, (String, Abi)>,
+    known_contracts: &BTreeMap<String, ContractBytecodeSome>,
+    address: Address,
+    creation: bool,
+) -> Option<SourceMap> {
+    let (name, _) = identified_contracts.get(&address)?;
+    let known = known_contracts.get(name)?;
+    let source_map = if creation {
+        known.bytecode.source_map()
+    } else {
+        known.deployed_bytecode.bytecode.as_ref()?.source_map()
+    };
+    source_map?.ok()
+}
+
+/// The calls and the gas used of internal functions by `contract.function`
+pub(crate) type InternalGasProfile = BTreeMap<String, (usize, u64)>;
+
+/// Prints the internal function calls of every call of the arena and adds the gas they used to
+/// the `profile`
+pub(crate) fn print_internal_calls(
+    arena: &DebugArena,
+    identified_contracts: &BTreeMap<Address, (String, Abi)>,
+    known_contracts: &BTreeMap<String, ContractBytecodeSome>,
+    source_code: &BTreeMap<u32, String>,
+    profile: &mut InternalGasProfile,
+) {
+    let source_map = |address, creation| {
+        contract_source_map(identified_contracts, known_contracts, address, creation)
+    };
+    for call in arena_internal_calls(arena, source_map, source_code) {
+        let name = identified_contracts.get(&call.address).map(|(name, _)| name.as_str());
+        let name = name.unwrap_or_default();
+        let constructor = if call.creation { "::constructor" } else { "" };
+        sh_println!("{}{} ({:?})", name, constructor, call.address);
+        for internal in call.calls {
+            sh_println!(
+                "{}└─ {} [{}]",
+                "   ".repeat(internal.depth),
+                internal.name,
+                internal.gas_used
+            );
+            let entry = profile.entry(format!("{}.{}", name, internal.name)).or_default();
+            entry.0 += 1;
+            entry.1 += internal.gas_used;
+        }
+    }
+}
+
+/// Prints the calls and the gas used of every internal function, the most expensive first
+pub(crate) fn print_internal_gas_profile(profile: InternalGasProfile) {
+    sh_println!("== Internal Gas Profile ==");
+    sh_println!("{:<48} {:>8} {:>12}", "Function", "Calls", "Gas");
+    let mut profile = profile.into_iter().collect::<Vec<_>>();
+    profile.sort_by(|(_, (_, a)), (_, (_, b))| b.cmp(a));
+    for (name, (count, gas)) in profile {
+        sh_println!("{:<48} {:>8} {:>12}", name, count, gas);
+    }
+    sh_println!();
+}
+
+/// Prints the source expression that the run reverted in, e.g. a failed `require` or an
+/// arithmetic overflow, with the line it's on
+fn print_revert_explanation(
+    calls: &DebugArena,
+    identified_contracts: &BTreeMap<Address, (String, Abi)>,
+    known_contracts: &BTreeMap<String, ContractBytecodeSome>,
+    sources: &BTreeMap<u32, String>,
+    source_code: &BTreeMap<u32, String>,
+) {
+    let source_map = |address, creation| {
+        contract_source_map(identified_contracts, known_contracts, address, creation)
+    };
+    if let Some(explanation) = explain_arena_revert(calls, source_map, source_code) {
+        let path = sources.get(&explanation.index).map(String::as_str).unwrap_or("<unknown>");
+        print_explanation(&explanation, path);
+    }
+}
+
+/// Prints the source expression that reverted with the line it's on, the `path` is the path of
+/// its source file
+pub(crate) fn print_explanation(explanation: &RevertExplanation, path: &str) {
+    sh_println!(
+        "{} {} in `{}`",
+        shell::paint(Colour::Red, "Reverted:"),
+        explanation.kind,
+        explanation.expression
+    );
+    sh_println!("  --> {}:{}", path, explanation.line);
+    let gutter = " ".repeat(explanation.line.to_string().len());
+    sh_println!("{} |", gutter);
+    sh_println!("{} | {}", explanation.line, explanation.snippet);
+    sh_println!("{} |", gutter);
+}
+
+/// Prints the diff of the `old` and `new` traces of a run
+fn print_trace_diff(
+    old: &[CallTraceArena],
+    new: &[CallTraceArena],
+    funcs: &BTreeMap<[u8; 4], Function>,
+) {
+    if old.len() != new.len() {
+        sh_warn!("number of traces differ: {} before, {} now", old.len(), new.len());
+    }
+
+    // setup traces come first, so align the traces at the end
+    let skip_old = old.len().saturating_sub(new.len());
+    let skip_new = new.len().saturating_sub(old.len());
+    let (mut added, mut removed, mut changed, mut gas) = (0, 0, 0, 0i128);
+    for (old, new) in old.iter().skip(skip_old).zip(new.iter().skip(skip_new)) {
+        let diffs = old.diff(new);
+        for diff in &diffs {
+            let mut out = String::new();
+            diff.print(funcs, &mut out);
+            sh_println!("{}", out.trim_end());
+            match diff {
+                CallTraceDiff::Added(_) => added += 1,
+                CallTraceDiff::Removed(_) => removed += 1,
+                CallTraceDiff::Matched { .. } if !diff.is_unchanged() => changed += 1,
+                _ => {}
+            }
+        }
+        // the gas of the entry frame includes the gas of all its children
+        gas += diffs.first().map(CallTraceDiff::gas_delta).unwrap_or_default();
+        sh_println!();
+    }
+
+    sh_println!(
+        "{} added, {} removed, {} changed calls. Gas delta: {}",
+        shell::paint(Colour::Green, added.to_string()),
+        shell::paint(Colour::Red, removed.to_string()),
+        shell::paint(Colour::Yellow, changed.to_string()),
+        gas
+    );
+}
+
+/// The compiler output of the last `forge run` of the most recently compiled target contracts,
+/// which is reused as long as neither the compiled sources nor the solc settings change
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct RunCache {
+    /// The cached compiler outputs by the path of their target
+    targets: BTreeMap<PathBuf, RunCacheEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RunCacheEntry {
+    /// hash of the solc settings, including remappings and optimizer settings, and of the content
+    /// of every compiled source
+    hash: String,
+    /// The number of the compilation, newer outputs have higher numbers
+    number: u64,
+    output: CompilerOutput,
+}
+
+impl RunCache {
+    const FILE_NAME: &'static str = "forge-run-cache.json";
+    /// The number of targets whose output is cached, the oldest outputs are evicted first
+    const MAX_TARGETS: usize = 8;
+
+    /// The cache is stored next to the project's solidity files cache
+    pub(crate) fn path(project: &Project<MinimalCombinedArtifacts>) -> PathBuf {
+        project.paths.cache.with_file_name(Self::FILE_NAME)
+    }
+
+    /// Returns the hash of the solc settings and of the sources compiled with the target, which
+    /// are all sources of the project and the target with their imports
+    fn hash(
+        project: &Project<MinimalCombinedArtifacts>,
+        config: &Config,
+        target: &Path,
+    ) -> eyre::Result<String> {
+        let remappings = project.paths.remappings.iter().map(|r| r.to_string()).collect::<Vec<_>>();
+        let mut content = format!(
+            "{}:{}:{:?}:{}:{}",
+            serde_json::to_string(&project.solc_config.settings)?,
+            remappings.join(","),
+            config.solc_version,
+            project.auto_detect,
+            project.allowed_lib_paths
+        );
+
+        let mut sources = project.paths.read_input_files()?;
+        sources.extend(Source::read_all_files(vec![target.to_path_buf()])?);
+        for (path, source) in Graph::resolve_sources(&project.paths, sources)?.into_sources() {
+            let source_hash = hex::encode(ethers::utils::keccak256(source.content.as_bytes()));
+            content.push_str(&format!(":{}={}", path.display(), source_hash));
+        }
+        Ok(hex::encode(ethers::utils::keccak256(content.as_bytes())))
+    }
+
+    /// Reads the cache, empty if it's missing or can't be read
+    fn read(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    /// Returns the cached output of the target if its hash matches
+    fn get(&self, target: &Path, hash: &str) -> Option<&CompilerOutput> {
+        self.targets.get(target).filter(|entry| entry.hash == hash).map(|entry| &entry.output)
+    }
+
+    /// Caches the output of the target, evicting the oldest outputs if too many are cached
+    fn insert(&mut self, target: PathBuf, hash: String, output: CompilerOutput) {
+        let number = self.targets.values().map(|entry| entry.number + 1).max().unwrap_or_default();
+        self.targets.insert(target, RunCacheEntry { hash, number, output });
+        while self.targets.len() > Self::MAX_TARGETS {
+            let oldest = self
+                .targets
+                .iter()
+                .min_by_key(|(_, entry)| entry.number)
+                .map(|(target, _)| target.clone())
+                .expect("not empty");
+            self.targets.remove(&oldest);
+        }
+    }
+
+    fn write(&self, path: &Path) -> eyre::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string(self)?)
+            .wrap_err_with(|| format!("failed to write {}", path.display()))
+    }
+}
+
 pub struct BuildOutput {
     pub project: Project<MinimalCombinedArtifacts>,
     pub contract: CompactContractSome,
     pub highlevel_known_contracts: BTreeMap<String, ContractBytecodeSome>,
+    /// The creation code of every compiled contract
+    pub creation_codes: Vec<CreationCode>,
     pub sources: BTreeMap<u32, String>,
 }
 
@@ -220,33 +641,55 @@ impl RunArgs {
         let target_contract = dunce::canonicalize(&self.path)?;
         let (project, output) = if let Ok(mut project) = config.project() {
             // TODO: caching causes no output until https://github.com/gakonst/ethers-rs/issues/727
-            // is fixed
+            // is fixed, the output is cached by the `RunCache` instead
             project.cached = false;
             project.no_artifacts = true;
 
-            // target contract may not be in the compilation path, add it and manually compile
-            match manual_compile(&project, vec![target_contract.clone()]) {
-                Ok(output) => (project, output),
-                Err(e) => {
-                    println!("No extra contracts compiled {:?}", e);
-                    let mut target_project = config.ephemeral_no_artifacts_project()?;
-                    target_project.cached = false;
-                    target_project.no_artifacts = true;
-                    let res = compile(&target_project)?;
-                    (target_project, res)
+            let hash = RunCache::hash(&project, &config, &target_contract).ok();
+            let cache_path = RunCache::path(&project);
+            let mut cache = RunCache::read(&cache_path);
+            let cached = hash
+                .as_deref()
+                .filter(|_| !config.force)
+                .and_then(|hash| cache.get(&target_contract, hash))
+                .cloned();
+
+            match cached {
+                Some(output) => {
+                    sh_status!("no files changed, compilation skipped.");
+                    (project, output)
                 }
+                // target contract may not be in the compilation path, add it and manually compile
+                None => match manual_compile(&project, vec![target_contract.clone()]) {
+                    Ok(output) => {
+                        let output = output.output();
+                        if let Some(hash) = hash {
+                            cache.insert(target_contract.clone(), hash, output.clone());
+                            cache.write(&cache_path)?;
+                        }
+                        (project, output)
+                    }
+                    Err(e) => {
+                        sh_status!("No extra contracts compiled {:?}", e);
+                        let mut target_project = config.ephemeral_no_artifacts_project()?;
+                        target_project.cached = false;
+                        target_project.no_artifacts = true;
+                        let res = compile(&target_project)?;
+                        (target_project, res.output())
+                    }
+                },
             }
         } else {
             let mut target_project = config.ephemeral_no_artifacts_project()?;
             target_project.cached = false;
             target_project.no_artifacts = true;
             let res = compile(&target_project)?;
-            (target_project, res)
+            (target_project, res.output())
         };
-        println!("success.");
+        sh_status!("success.");
 
         // get the contracts
-        let (sources, contracts) = output.output().split();
+        let (sources, contracts) = output.split();
 
         // get the specific contract
         let contract_bytecode = if let Some(contract_name) = self.target_contract.clone() {
@@ -288,12 +731,15 @@ impl RunArgs {
         let contract = CompactContract::from(contract_bytecode).try_into().expect("Couldn't create contract from bytecodes, either abi, bytecode, or deployed_bytecode were empty.");
 
         let mut highlevel_known_contracts = BTreeMap::new();
+        let mut creation_codes = Vec::new();
 
         // build the entire highlevel_known_contracts based on all compiled contracts
         contracts.0.into_iter().for_each(|(src, mapping)| {
             mapping.into_iter().for_each(|(name, c)| {
                 let cb: ContractBytecode = c.into();
                 if let Ok(cbs) = ContractBytecodeSome::try_from(cb) {
+                    let info = FullContractInfo { path: src.clone(), name: name.clone() };
+                    creation_codes.extend(CreationCode::new(info, &cbs.bytecode));
                     if highlevel_known_contracts.contains_key(&name) {
                         highlevel_known_contracts.insert(src.to_string() + ":" + &name, cbs);
                     } else {
@@ -307,6 +753,7 @@ impl RunArgs {
             project,
             contract,
             highlevel_known_contracts,
+            creation_codes,
             sources: sources.into_ids().collect(),
         })
     }
@@ -6,6 +6,8 @@ use crate::{
         test::{Test, TestOutcome},
         Cmd,
     },
+    exit_code::ExitCode,
+    shell::{self, sh_println},
     utils,
 };
 use ansi_term::Colour;
@@ -74,9 +76,9 @@ impl Cmd for SnapshotArgs {
             let snap = path.as_ref().unwrap_or(&self.snap);
             let snaps = read_snapshot(snap)?;
             if check(tests, snaps) {
-                std::process::exit(0)
+                ExitCode::Success.exit()
             } else {
-                std::process::exit(1)
+                ExitCode::Failure.exit()
             }
         } else {
             write_to_snapshot_file(&tests, self.snap, self.format)?;
@@ -263,14 +265,16 @@ fn check(tests: Vec<Test>, snaps: Vec<SnapshotEntry>) -> bool {
         if let Some(target_gas) = snaps.get(&test.signature).cloned() {
             let source_gas = test.result.kind.gas_used();
             if source_gas.gas() != target_gas.gas() {
-                println!(
+                sh_println!(
                     "Diff in \"{}\": consumed \"{}\" gas, expected \"{}\" gas ",
-                    test.signature, source_gas, target_gas
+                    test.signature,
+                    source_gas,
+                    target_gas
                 );
                 has_diff = true;
             }
         } else {
-            println!(
+            sh_println!(
                 "No matching snapshot entry found for \"{}\" in snapshot file",
                 test.signature
             );
@@ -310,7 +314,7 @@ fn diff(tests: Vec<Test>, snaps: Vec<SnapshotEntry>) -> eyre::Result<()> {
         overall_gas_change += gas_change;
         let gas_diff = diff.gas_diff();
         overall_gas_diff += gas_diff;
-        println!(
+        sh_println!(
             "{} (gas: {} ({})) ",
             diff.signature,
             fmt_change(gas_change),
@@ -318,7 +322,7 @@ fn diff(tests: Vec<Test>, snaps: Vec<SnapshotEntry>) -> eyre::Result<()> {
         );
     }
 
-    println!(
+    sh_println!(
         "Overall gas change: {} ({})",
         fmt_change(overall_gas_change),
         fmt_pct_change(overall_gas_diff)
@@ -328,21 +332,21 @@ fn diff(tests: Vec<Test>, snaps: Vec<SnapshotEntry>) -> eyre::Result<()> {
 
 fn fmt_pct_change(change: f64) -> String {
     match change.partial_cmp(&0.0).unwrap_or(Ordering::Equal) {
-        Ordering::Less => Colour::Green.paint(format!("{:.3}%", change)).to_string(),
+        Ordering::Less => shell::paint(Colour::Green, format!("{:.3}%", change)),
         Ordering::Equal => {
             format!("{:.3}%", change)
         }
-        Ordering::Greater => Colour::Red.paint(format!("{:.3}%", change)).to_string(),
+        Ordering::Greater => shell::paint(Colour::Red, format!("{:.3}%", change)),
     }
 }
 
 fn fmt_change(change: i128) -> String {
     match change.cmp(&0) {
-        Ordering::Less => Colour::Green.paint(format!("{}", change)).to_string(),
+        Ordering::Less => shell::paint(Colour::Green, format!("{}", change)),
         Ordering::Equal => {
             format!("{}", change)
         }
-        Ordering::Greater => Colour::Red.paint(format!("{}", change)).to_string(),
+        Ordering::Greater => shell::paint(Colour::Red, format!("{}", change)),
     }
 }
 
@@ -0,0 +1,260 @@
+//! solc command
+
+use crate::{
+    cmd::Cmd,
+    shell::{sh_println, sh_status},
+};
+use clap::{Parser, Subcommand, ValueHint};
+use ethers::solc::Solc;
+use foundry_config::{find_project_root_path, Config};
+use semver::{Version, VersionReq};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+/// Command to manage the solc compilers
+///
+/// Compilers are installed in the same directory the solc auto detection installs to, so a
+/// project whose sources require different versions compiles every source with the latest
+/// installed version that satisfies its pragma.
+#[derive(Debug, Clone, Parser)]
+pub struct SolcArgs {
+    #[clap(subcommand)]
+    pub sub: SolcSubcommands,
+
+    #[clap(
+        help = "the project's root path, default being the nearest directory with a foundry.toml or the git root",
+        long,
+        global = true,
+        value_hint = ValueHint::DirPath
+    )]
+    pub root: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum SolcSubcommands {
+    #[clap(
+        about = "lists the installed solc versions and the version requirements of the project's sources"
+    )]
+    List,
+    #[clap(
+        about = "installs solc versions, the latest versions that satisfy the project's sources if none are provided"
+    )]
+    Install {
+        #[clap(help = "the versions to install, like `0.8.10`")]
+        versions: Vec<Version>,
+    },
+    #[clap(
+        about = "pins the solc version of the selected profile in the foundry.toml, installs it if it's missing"
+    )]
+    Use {
+        #[clap(help = "the version to use, like `0.8.10`")]
+        version: Version,
+    },
+}
+
+impl Cmd for SolcArgs {
+    type Output = ();
+
+    fn run(self) -> eyre::Result<Self::Output> {
+        let root = match self.root {
+            Some(root) => root,
+            None => find_project_root_path()?,
+        };
+        match self.sub {
+            SolcSubcommands::List => {
+                let installed = installed_versions();
+                sh_println!("installed:");
+                for version in &installed {
+                    sh_println!("  {}", version);
+                }
+
+                let requirements = version_requirements(&root)?;
+                if !requirements.is_empty() {
+                    sh_println!("required by the sources:");
+                }
+                for (req, files) in requirements.values() {
+                    let status = match installed.iter().rev().find(|version| req.matches(version)) {
+                        Some(version) => format!("compiled with {}", version),
+                        None => "not installed".to_string(),
+                    };
+                    sh_println!("  {} ({} files, {})", req, files.len(), status);
+                }
+            }
+            SolcSubcommands::Install { versions } => {
+                if versions.is_empty() {
+                    for (req, _) in version_requirements(&root)?.values() {
+                        let version = Solc::ensure_installed(req)?;
+                        sh_println!("{} is satisfied by solc {}", req, version);
+                    }
+                }
+                for version in versions {
+                    install(&version)?;
+                }
+            }
+            SolcSubcommands::Use { version } => {
+                install(&version)?;
+                set_solc_version(&root, &version)?;
+                sh_println!(
+                    "using solc {} for the `{}` profile",
+                    version,
+                    Config::selected_profile().as_str()
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Installs the version unless it's already installed
+fn install(version: &Version) -> eyre::Result<()> {
+    if installed_versions().contains(version) {
+        sh_status!("solc {} is already installed", version);
+        return Ok(())
+    }
+    sh_status!("installing solc {}...", version);
+    Solc::blocking_install(version)?;
+    Ok(())
+}
+
+/// Returns all installed solc versions, sorted in ascending order
+fn installed_versions() -> Vec<Version> {
+    let mut versions = Solc::svm_home()
+        .and_then(|home| std::fs::read_dir(home).ok())
+        .map(|dir| {
+            dir.filter_map(Result::ok)
+                .filter_map(|entry| entry.file_name().to_string_lossy().parse().ok())
+                .collect::<Vec<Version>>()
+        })
+        .unwrap_or_default();
+    versions.sort();
+    versions
+}
+
+/// Returns the distinct version requirements of the project's sources and the files that declare
+/// them
+fn version_requirements(root: &Path) -> eyre::Result<BTreeMap<String, (VersionReq, Vec<PathBuf>)>> {
+    let paths = Config::try_load_with_root(root)?.sanitized().project_paths();
+    let mut requirements: BTreeMap<String, (VersionReq, Vec<PathBuf>)> = BTreeMap::new();
+    for (file, source) in paths.read_input_files()? {
+        // sources without a version pragma compile with any version
+        if let Ok(req) = Solc::source_version_req(&source) {
+            requirements.entry(req.to_string()).or_insert_with(|| (req, Vec::new())).1.push(file);
+        }
+    }
+    Ok(requirements)
+}
+
+/// Sets the `solc_version` of the selected profile in the foundry.toml of the root
+///
+/// Only the line of the key is edited, so the comments and layout of the file are kept.
+fn set_solc_version(root: &Path, version: &Version) -> eyre::Result<()> {
+    let path = root.join(Config::FILE_NAME);
+    let content = if path.is_file() { std::fs::read_to_string(&path)? } else { String::new() };
+    let profile = Config::selected_profile().as_str().to_string();
+    let doc: toml::Value = toml::from_str(&content)?;
+
+    // the values of a profile nested in the `profile` table take precedence
+    let nested =
+        doc.get(Config::PROFILE_SECTION).and_then(|profiles| profiles.get(&profile)).is_some();
+    let header = if nested {
+        format!("[{}.{}]", Config::PROFILE_SECTION, profile)
+    } else {
+        format!("[{}]", profile)
+    };
+    let line = format!("solc_version = \"{}\"", version);
+    match with_key_line(&content, &header, "solc_version", &line) {
+        Some(content) => std::fs::write(path, content)?,
+        None => eyre::bail!(
+            "the `{}` profile of {} can't be edited, add `{}` to it",
+            profile,
+            path.display(),
+            line
+        ),
+    }
+    Ok(())
+}
+
+/// Returns the toml `content` with the `line` of the `key` in the table of the `header`, replacing
+/// the line of the key or adding the table if there is none
+///
+/// Returns `None` if the key is set in another way, like in an inline table or with a dotted key,
+/// so the edited content doesn't set it to the line.
+fn with_key_line(content: &str, header: &str, key: &str, line: &str) -> Option<String> {
+    let mut lines = content.lines().map(str::to_string).collect::<Vec<_>>();
+    let is_header = |l: &str| l.split('#').next().unwrap_or_default().trim() == header;
+    match lines.iter().position(|l| is_header(l)) {
+        Some(start) => {
+            let end = lines[start + 1..]
+                .iter()
+                .position(|l| l.trim_start().starts_with('['))
+                .map_or(lines.len(), |pos| start + 1 + pos);
+            let existing = lines[start + 1..end].iter().position(|l| {
+                l.trim_start()
+                    .strip_prefix(key)
+                    .map_or(false, |rest| rest.trim_start().starts_with('='))
+            });
+            match existing {
+                Some(pos) => lines[start + 1 + pos] = line.to_string(),
+                None => lines.insert(start + 1, line.to_string()),
+            }
+        }
+        None => {
+            if lines.last().map_or(false, |l| !l.trim().is_empty()) {
+                lines.push(String::new());
+            }
+            lines.push(header.to_string());
+            lines.push(line.to_string());
+        }
+    }
+    let edited = lines.join("\n") + "\n";
+
+    // the key must be set by the line in the edited table
+    let expected: toml::Value = toml::from_str(line).ok()?;
+    let doc: toml::Value = toml::from_str(&edited).ok()?;
+    let table = header
+        .trim_matches(|c| c == '[' || c == ']')
+        .split('.')
+        .try_fold(&doc, |table, name| table.get(name))?;
+    (table.get(key) == expected.get(key)).then(|| edited)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edits_the_key_line_only() {
+        let content = r#"# the default profile
+[default]
+# pinned for the audit
+solc_version = "0.8.10" # keep in sync with CI
+src = "src"
+
+[ci]
+fuzz_runs = 1000
+"#;
+        let edited =
+            with_key_line(content, "[default]", "solc_version", r#"solc_version = "0.8.11""#)
+                .unwrap();
+        assert_eq!(edited, content.replace(r#""0.8.10" # keep in sync with CI"#, r#""0.8.11""#));
+
+        let edited =
+            with_key_line(content, "[ci]", "solc_version", r#"solc_version = "0.8.11""#).unwrap();
+        assert!(edited.ends_with("[ci]\nsolc_version = \"0.8.11\"\nfuzz_runs = 1000\n"));
+        assert!(edited.starts_with("# the default profile\n"));
+
+        let edited =
+            with_key_line(content, "[local]", "solc_version", r#"solc_version = "0.8.11""#)
+                .unwrap();
+        assert!(edited.ends_with("fuzz_runs = 1000\n\n[local]\nsolc_version = \"0.8.11\"\n"));
+
+        // keys of inline tables aren't edited
+        let inline = "default = { solc_version = \"0.8.10\" }\n";
+        assert!(
+            with_key_line(inline, "[default]", "solc_version", r#"solc_version = "0.8.11""#)
+                .is_none()
+        );
+    }
+}
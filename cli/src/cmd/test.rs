@@ -1,16 +1,57 @@
 //! Test command
 
 use crate::{
-    cmd::{build::BuildArgs, Cmd},
+    cmd::{
+        build::{report_warnings, BuildArgs},
+        compile, manual_compile,
+        run::{
+            print_explanation, print_internal_calls, print_internal_gas_profile, read_source_code,
+            InternalGasProfile,
+        },
+        Cmd,
+    },
+    exit_code::ExitCode,
+    hooks::{self, Hook},
     opts::evm::EvmArgs,
+    shell::{self, sh_println, sh_status, sh_warn},
+    utils::{read_dapp_json, UNLIMITED_GAS_WARNING},
 };
 use ansi_term::Colour;
-use clap::{AppSettings, Parser};
-use ethers::solc::{ArtifactOutput, Project};
-use evm_adapters::{call_tracing::ExecutionInfo, evm_opts::EvmOpts, sputnik::helpers::vm};
-use forge::{MultiContractRunnerBuilder, TestFilter};
+use clap::{AppSettings, Parser, ValueHint};
+use ethers::{
+    abi::Abi,
+    prelude::artifacts::ContractBytecode,
+    solc::{artifacts::ContractBytecodeSome, sourcemap::SourceMap, EvmVersion},
+    types::Address,
+};
+use evm_adapters::{
+    call_tracing::ExecutionInfo,
+    evm_opts::EvmOpts,
+    fuzz::FuzzBounds,
+    rpc_metrics::RPC_METRICS,
+    sputnik::{
+        cheatcodes::debugger::{
+            explain_arena_revert, stack_trace, DebugArena, RevertExplanation, SourceLocation,
+            StackFrame,
+        },
+        gas_hints::gas_hints,
+        helpers::vm,
+    },
+};
+use forge::{
+    CounterExample, MultiContractRunner, MultiContractRunnerBuilder, ScopedSettings, TestDiscovery,
+    TestFilter, TestKind, TestProgress, TestResult,
+};
 use foundry_config::{figment::Figment, Config};
-use std::collections::BTreeMap;
+use indicatif::ProgressBar;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
 
 #[derive(Debug, Clone, Parser)]
 pub struct Filter {
@@ -85,9 +126,6 @@ foundry_config::impl_figment_convert!(TestArgs, opts, evm_opts);
 // This is required to group Filter options in help output
 #[clap(global_setting = AppSettings::DeriveDisplayOrder)]
 pub struct TestArgs {
-    #[clap(help = "print the test results in json format", long, short)]
-    json: bool,
-
     #[clap(flatten)]
     evm_opts: EvmArgs,
 
@@ -103,6 +141,57 @@ pub struct TestArgs {
         env = "FORGE_ALLOW_FAILURE"
     )]
     allow_failure: bool,
+
+    #[clap(
+        help = "run the tests of an existing dapptools build, like `./out/dapp.sol.json`, without compiling",
+        long,
+        value_hint = ValueHint::FilePath
+    )]
+    dapp_json: Option<PathBuf>,
+
+    #[clap(
+        help = "list the discovered test contracts and functions and why they were discovered, without running them",
+        long
+    )]
+    list: bool,
+
+    #[clap(
+        help = "warn about tests that don't observe the state `setUp` left behind, which indicates state leaking between tests",
+        long
+    )]
+    check_isolation: bool,
+
+    #[clap(
+        help = "suggest gas optimizations from the patterns observed while running the unit tests: repeated storage reads, zero-to-nonzero storage writes in loops, memory expansion spikes and calls that could be staticcalls",
+        long
+    )]
+    gas_hints: bool,
+
+    #[clap(
+        help = "reconstruct the internal function calls of the unit tests from the source maps and print them with a gas profile of the internal functions",
+        long
+    )]
+    decode_internal: bool,
+
+    #[clap(
+        help = "only run the tests that failed in the last run, or all tests if none failed",
+        long
+    )]
+    rerun_failures: bool,
+
+    #[clap(
+        help = "run the tests that failed in the last run before the other tests, and print their results first",
+        long,
+        conflicts_with = "rerun-failures"
+    )]
+    failed_first: bool,
+
+    #[clap(
+        help = "run the tests once per EVM version, like `istanbul,berlin,london`, and summarize the results of every version",
+        long,
+        use_delimiter = true
+    )]
+    evm_versions: Vec<EvmVersion>,
 }
 
 impl Cmd for TestArgs {
@@ -112,9 +201,78 @@ impl Cmd for TestArgs {
         // merge all configs
         let figment: Figment = From::from(&self);
         let evm_opts = figment.extract::<EvmOpts>()?;
-        let config = Config::from_provider(figment).sanitized();
+        let config = Config::try_from(figment)?.sanitized();
+
+        hooks::run_pre(&config, Hook::PreTest)?;
+        let outcome = self.run_tests(evm_opts, &config);
+        let success = outcome
+            .as_ref()
+            .map_or(false, |outcome| outcome.allow_failure || outcome.failures().next().is_none());
+        hooks::run_post(&config, Hook::PostTest, success);
+        outcome
+    }
+}
+
+impl TestArgs {
+    /// Compiles the project and runs the tests
+    fn run_tests(self, mut evm_opts: EvmOpts, config: &Config) -> eyre::Result<TestOutcome> {
+        let metrics = RPC_METRICS.snapshot();
+        self.evm_opts.resolve_fork(&mut evm_opts, config)?;
+        evm_opts.check_isolation = self.check_isolation;
+        evm_opts.deployments = config.read_deployments()?;
+
+        // resolve the settings of the test contracts of path globs
+        let mut scoped = Vec::with_capacity(config.overrides.len());
+        for (glob, overrides) in &config.overrides {
+            let pattern = glob::Pattern::new(glob).map_err(|err| {
+                ExitCode::ConfigError
+                    .error(format!("invalid glob `{}` in `overrides`: {}", glob, err))
+            })?;
+            let mut scoped_opts = evm_opts.clone();
+            if overrides.fork_url.is_some() || overrides.fork_block_number.is_some() {
+                if let Some(ref url) = overrides.fork_url {
+                    scoped_opts.fork_url = Some(url.clone());
+                }
+                if let Some(block) = overrides.fork_block_number {
+                    scoped_opts.fork_block_number = Some(block);
+                }
+                self.evm_opts.resolve_fork(&mut scoped_opts, config)?;
+            }
+            scoped.push((pattern, scoped_opts, overrides.evm_version, overrides.fuzz_runs));
+        }
+
+        let mut discovery = TestDiscovery { bases: config.test_bases.clone(), globs: vec![] };
+        for glob in &config.test_globs {
+            discovery.globs.push(glob::Pattern::new(glob).map_err(|err| {
+                ExitCode::ConfigError
+                    .error(format!("invalid glob `{}` in `test_globs`: {}", glob, err))
+            })?);
+        }
 
-        let TestArgs { json, filter, allow_failure, .. } = self;
+        let TestArgs {
+            filter,
+            allow_failure,
+            dapp_json,
+            list,
+            gas_hints,
+            decode_internal,
+            rerun_failures,
+            failed_first,
+            evm_versions,
+            ..
+        } = self;
+        // fail before compiling if any of the `--evm-versions` is unsupported
+        for evm_version in &evm_versions {
+            crate::utils::sputnik_cfg(evm_version)?;
+        }
+
+        let failures_path = config.project_paths().cache.with_file_name("test-failures.json");
+        let mut last_failures = LastFailures::read(&failures_path)?;
+        let failures_filter = FailuresFilter {
+            filter: &filter,
+            last_failures: &last_failures,
+            failed: if rerun_failures && !last_failures.is_empty() { Some(true) } else { None },
+        };
 
         // Setup the fuzzer
         // TODO: Add CLI Options to modify the persistence
@@ -123,22 +281,192 @@ impl Cmd for TestArgs {
             cases: config.fuzz_runs,
             ..Default::default()
         };
-        let fuzzer = proptest::test_runner::TestRunner::new(cfg);
-
-        // Set up the project
-        let project = config.project()?;
+        let fuzzer = proptest::test_runner::TestRunner::new(cfg.clone());
+        let fuzz_bounds = FuzzBounds {
+            max_array_length: config.fuzz.max_array_length,
+            max_bytes_length: config.fuzz.max_bytes_length,
+            max_string_length: config.fuzz.max_string_length,
+            string_charset: config.fuzz.string_charset.clone(),
+            max_test_time: config.fuzz.max_test_time.map(Duration::from_secs),
+        };
+        fuzz_bounds.validate()?;
 
         // prepare the test builder
-        let mut evm_cfg = crate::utils::sputnik_cfg(&config.evm_version);
+        let mut evm_cfg = crate::utils::sputnik_cfg(&config.evm_version)?;
         evm_cfg.create_contract_limit = None;
 
-        let builder = MultiContractRunnerBuilder::default()
+        let mut builder = MultiContractRunnerBuilder::default()
             .fuzzer(fuzzer)
             .initial_balance(evm_opts.initial_balance)
             .evm_cfg(evm_cfg)
-            .sender(evm_opts.sender);
+            .sender(evm_opts.sender)
+            .external_artifacts(config.external_artifacts.clone())
+            .vyper(config.vyper.clone())
+            .bytecode_contracts(config.read_bytecode_contracts()?)
+            .fuzz_corpus(config.fuzz_corpus.clone())
+            .fixture_cache(Some(config.cache_path.join("fixtures")))
+            .fuzz_bounds(fuzz_bounds)
+            .discovery(discovery);
+        for (pattern, evm_opts, evm_version, fuzz_runs) in scoped {
+            builder = builder.scoped(ScopedSettings {
+                pattern,
+                evm_opts,
+                evm_cfg: evm_version
+                    .map(|evm_version| {
+                        let mut evm_cfg = crate::utils::sputnik_cfg(&evm_version)?;
+                        evm_cfg.create_contract_limit = None;
+                        Ok::<_, eyre::Report>(evm_cfg)
+                    })
+                    .transpose()?,
+                fuzzer: fuzz_runs.map(|cases| {
+                    proptest::test_runner::TestRunner::new(proptest::test_runner::Config {
+                        cases,
+                        ..cfg.clone()
+                    })
+                }),
+            });
+        }
 
-        test(builder, project, evm_opts, filter, json, allow_failure)
+        let verbosity = evm_opts.verbosity;
+        let unlimited_gas = evm_opts.unlimited_gas;
+        // the stack traces and gas hints need the sources, which dapptools builds don't have
+        let (mut runner, debug_config) = match dapp_json {
+            Some(dapp_json) => {
+                (builder.build_precompiled(read_dapp_json(&dapp_json)?, evm_opts)?, None)
+            }
+            None => {
+                let mut project = config.project()?;
+                builder.prepare_project(&mut project)?;
+                let output = compile(&project)?;
+                report_warnings(&config, &project, &output)?;
+                (builder.build_with_output(&project, output, evm_opts)?, Some(config))
+            }
+        };
+        if list {
+            list_tests(&runner, &failures_filter)?;
+            return Ok(TestOutcome::new(Default::default(), allow_failure))
+        }
+        let run = |runner: &mut MultiContractRunner| {
+            test(
+                runner,
+                verbosity,
+                unlimited_gas,
+                &failures_filter,
+                failed_first,
+                allow_failure,
+                debug_config,
+                gas_hints,
+                decode_internal,
+            )
+        };
+        let outcome = if evm_versions.is_empty() {
+            let outcome = run(&mut runner)?;
+            if shell::is_json() {
+                shell::print_json(&outcome.results)?;
+            }
+            outcome
+        } else {
+            let mut outcomes = Vec::with_capacity(evm_versions.len());
+            for evm_version in &evm_versions {
+                if !shell::is_json() {
+                    sh_println!("Testing with EVM version {}", evm_version);
+                }
+                let mut evm_cfg = crate::utils::sputnik_cfg(evm_version)?;
+                evm_cfg.create_contract_limit = None;
+                runner.set_evm_cfg(evm_cfg);
+                outcomes.push((evm_version.to_string(), run(&mut runner)?));
+                if !shell::is_json() {
+                    sh_println!();
+                }
+            }
+            TestOutcome::merge_evm_versions(outcomes, allow_failure)?
+        };
+
+        last_failures.update(&outcome.results);
+        last_failures.write(&failures_path)?;
+
+        // only fork runs send requests
+        let metrics = RPC_METRICS.snapshot() - metrics;
+        if !metrics.is_empty() {
+            sh_status!("{}", metrics);
+        }
+        Ok(outcome)
+    }
+}
+
+/// The test functions that failed in the last run by test contract, which are kept in the cache
+/// directory
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+struct LastFailures(BTreeMap<String, BTreeSet<String>>);
+
+impl LastFailures {
+    /// Reads the failures of the last run, there are none if no tests were run before
+    fn read(path: &Path) -> eyre::Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default())
+        }
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    fn write(&self, path: &Path) -> eyre::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        Ok(std::fs::write(path, serde_json::to_string_pretty(self)?)?)
+    }
+
+    /// Replaces the failures of the tests that were run with their results, the failures of the
+    /// tests that weren't run are kept
+    fn update(&mut self, results: &BTreeMap<String, BTreeMap<String, TestResult>>) {
+        for (contract, tests) in results {
+            let failures = self.0.entry(contract.clone()).or_default();
+            for (signature, result) in tests {
+                let name = signature.split('(').next().unwrap_or_default();
+                if result.success {
+                    failures.remove(name);
+                } else {
+                    failures.insert(name.to_string());
+                }
+            }
+        }
+        self.0.retain(|_, failures| !failures.is_empty());
+    }
+
+    fn contains(&self, contract_name: &str, test_name: &str) -> bool {
+        self.0.get(contract_name).map_or(false, |failures| failures.contains(test_name))
+    }
+
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Narrows the [`Filter`] down to either the tests that failed in the last run or all other tests
+#[derive(Debug, Clone, Copy)]
+struct FailuresFilter<'a> {
+    filter: &'a Filter,
+    last_failures: &'a LastFailures,
+    /// Whether only the failed tests or only the other tests match, all tests match if `None`
+    failed: Option<bool>,
+}
+
+impl<'a> TestFilter for FailuresFilter<'a> {
+    fn matches_test(&self, test_name: &str) -> bool {
+        self.filter.matches_test(test_name)
+    }
+
+    fn matches_contract(&self, contract_name: &str) -> bool {
+        self.filter.matches_contract(contract_name) &&
+            (self.failed != Some(true) || self.last_failures.0.contains_key(contract_name))
+    }
+
+    fn matches_contract_test(&self, contract_name: &str, test_name: &str) -> bool {
+        self.matches_contract(contract_name) &&
+            self.matches_test(test_name) &&
+            self.failed.map_or(true, |failed| {
+                self.last_failures.contains(contract_name, test_name) == failed
+            })
     }
 }
 
@@ -176,6 +504,49 @@ impl TestOutcome {
         Self { results, allow_failure }
     }
 
+    /// Merges the outcomes of the runs with every EVM version, printing how many tests passed and
+    /// failed with every version, or all results by version if the output is JSON
+    ///
+    /// The merged tests are named by their signature and EVM version, like `testX() [berlin]`.
+    fn merge_evm_versions(
+        outcomes: Vec<(String, TestOutcome)>,
+        allow_failure: bool,
+    ) -> eyre::Result<Self> {
+        if shell::is_json() {
+            let results = outcomes
+                .iter()
+                .map(|(evm_version, outcome)| (evm_version, &outcome.results))
+                .collect::<BTreeMap<_, _>>();
+            shell::print_json(&results)?;
+        } else {
+            sh_println!("EVM versions:");
+            for (evm_version, outcome) in &outcomes {
+                let failures = outcome.failures().count();
+                let status = if failures > 0 {
+                    shell::paint(Colour::Red, format!("{} failed", failures))
+                } else {
+                    shell::paint(Colour::Green, "0 failed")
+                };
+                sh_println!(
+                    "  {}: {} passed, {}",
+                    evm_version,
+                    outcome.successes().count(),
+                    status
+                );
+            }
+        }
+
+        let mut results: BTreeMap<String, BTreeMap<String, forge::TestResult>> = BTreeMap::new();
+        for (evm_version, outcome) in outcomes {
+            for (contract, tests) in outcome.results {
+                results.entry(contract).or_default().extend(tests.into_iter().map(
+                    |(signature, result)| (format!("{} [{}]", signature, evm_version), result),
+                ));
+            }
+        }
+        Ok(Self::new(results, allow_failure))
+    }
+
     /// Iterator over all succeeding tests and their names
     pub fn successes(&self) -> impl Iterator<Item = (&String, &forge::TestResult)> {
         self.tests().filter(|(_, t)| t.success)
@@ -204,20 +575,20 @@ impl TestOutcome {
         if !self.allow_failure {
             let failures = self.failures().count();
             if failures > 0 {
-                println!();
-                println!("Failed tests:");
+                sh_println!();
+                sh_println!("Failed tests:");
                 for (name, result) in self.failures() {
                     short_test_result(name, result);
                 }
-                println!();
+                sh_println!();
 
                 let successes = self.successes().count();
-                println!(
+                sh_println!(
                     "Encountered a total of {} failing tests, {} tests succeeded",
-                    Colour::Red.paint(failures.to_string()),
-                    Colour::Green.paint(successes.to_string())
+                    shell::paint(Colour::Red, failures.to_string()),
+                    shell::paint(Colour::Green, successes.to_string())
                 );
-                std::process::exit(1);
+                ExitCode::Failure.exit()
             }
         }
         Ok(())
@@ -226,70 +597,454 @@ impl TestOutcome {
 
 fn short_test_result(name: &str, result: &forge::TestResult) {
     let status = if result.success {
-        Colour::Green.paint("[PASS]")
+        shell::paint(Colour::Green, "[PASS]")
     } else {
-        let txt = match (&result.reason, &result.counterexample) {
-            (Some(ref reason), Some(ref counterexample)) => {
-                format!("[FAIL. Reason: {}. Counterexample: {}]", reason, counterexample)
+        let txt = match result.reason {
+            Some(ref reason) => format!("[FAIL. Reason: {}]", reason),
+            None => "[FAIL]".to_string(),
+        };
+
+        shell::paint(Colour::Red, txt)
+    };
+
+    sh_println!("{} {} {}", status, name, result.kind.gas_used());
+    if let Some(ref counterexample) = result.counterexample {
+        print_counterexample(counterexample);
+    }
+}
+
+/// Prints the arguments of the counterexample as a table of the parameters and their values
+fn print_counterexample(counterexample: &CounterExample) {
+    sh_println!("  Counterexample:");
+    let name_width = counterexample.params.iter().map(|param| param.name.len()).max();
+    let kind_width = counterexample.params.iter().map(|param| param.kind.len()).max();
+    for param in &counterexample.params {
+        let origin = param
+            .origin
+            .map(|origin| format!(" ({})", shell::paint(Colour::Yellow, origin.to_string())))
+            .unwrap_or_default();
+        sh_println!(
+            "    {:name_width$}  {:kind_width$}  {}{}",
+            param.name,
+            param.kind,
+            param.value,
+            origin,
+            name_width = name_width.unwrap_or_default(),
+            kind_width = kind_width.unwrap_or_default()
+        );
+    }
+}
+
+/// Prints the discovered test contracts and their test functions
+fn list_tests(runner: &MultiContractRunner, filter: &impl TestFilter) -> eyre::Result<()> {
+    let listed = runner.list(filter);
+    if shell::is_json() {
+        let listed = listed
+            .into_iter()
+            .map(|(name, (reason, tests))| {
+                (name, serde_json::json!({ "reason": reason.to_string(), "tests": tests }))
+            })
+            .collect::<BTreeMap<_, _>>();
+        return shell::print_json(&listed)
+    }
+    for (name, (reason, tests)) in listed {
+        sh_println!("{} ({})", name, reason);
+        for test in tests {
+            sh_println!("  {}", test);
+        }
+    }
+    Ok(())
+}
+
+/// Renders the progress of the fork state fetches and the fuzz tests on a spinner
+struct TestProgressSpinner(ProgressBar);
+
+impl fmt::Debug for TestProgressSpinner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TestProgressSpinner").finish_non_exhaustive()
+    }
+}
+
+impl TestProgress for TestProgressSpinner {
+    fn fork_state_fetched(&self, fetched: usize, total: usize) {
+        self.0.set_message(format!("fetching fork state: {}/{} accounts", fetched, total));
+    }
+
+    fn fuzz_case(&self, test: &str, runs: u32, total: u32) {
+        self.0.set_message(format!("fuzzing {}: {}/{} runs", test, runs, total));
+    }
+}
+
+/// Unit tests by contract and test function name
+#[derive(Debug, Default)]
+struct UnitTests {
+    contracts: BTreeSet<String>,
+    tests: BTreeSet<String>,
+}
+
+impl UnitTests {
+    /// Returns the unit tests of the results that match the predicate, fuzz tests are left out
+    /// since their inputs aren't rerun
+    fn new(
+        results: &BTreeMap<String, BTreeMap<String, TestResult>>,
+        predicate: impl Fn(&TestResult) -> bool,
+    ) -> Self {
+        let mut tests = Self::default();
+        for (contract, results) in results {
+            for (signature, result) in results {
+                if matches!(result.kind, TestKind::Standard(_)) && predicate(result) {
+                    tests.contracts.insert(contract.clone());
+                    let name = signature.split('(').next().unwrap_or_default();
+                    tests.tests.insert(name.to_string());
+                }
             }
-            (None, Some(ref counterexample)) => {
-                format!("[FAIL. Counterexample: {}]", counterexample)
+        }
+        tests
+    }
+}
+
+impl TestFilter for UnitTests {
+    fn matches_test(&self, test_name: &str) -> bool {
+        self.tests.contains(test_name)
+    }
+
+    fn matches_contract(&self, contract_name: &str) -> bool {
+        self.contracts.contains(contract_name)
+    }
+}
+
+/// The compiled sources that the debug steps of tests are mapped to
+struct DebugSources {
+    /// The compiled contracts by name
+    known_contracts: BTreeMap<String, ContractBytecodeSome>,
+    /// The source code by source file index
+    source_code: BTreeMap<u32, String>,
+    /// The source files by source file index, relative to the project root
+    paths: BTreeMap<u32, String>,
+}
+
+impl DebugSources {
+    /// Compiles the project again without the cache, because only the compiler output has the
+    /// source maps and the source file indexes they refer to
+    fn compile(config: &Config) -> eyre::Result<Self> {
+        let mut project = config.project()?;
+        project.cached = false;
+        project.no_artifacts = true;
+        let (sources, contracts) = manual_compile(&project, vec![])?.output().split();
+        let paths: BTreeMap<u32, String> = sources.into_ids().collect();
+        let source_code = read_source_code(&project, &paths);
+
+        let mut known_contracts: BTreeMap<String, ContractBytecodeSome> = BTreeMap::new();
+        for (file, contracts) in contracts.0 {
+            for (name, contract) in contracts {
+                let contract: ContractBytecode = contract.into();
+                if let Ok(contract) = ContractBytecodeSome::try_from(contract) {
+                    let name = if known_contracts.contains_key(&name) {
+                        format!("{}:{}", file, name)
+                    } else {
+                        name
+                    };
+                    known_contracts.insert(name, contract);
+                }
             }
-            (Some(ref reason), None) => {
-                format!("[FAIL. Reason: {}]", reason)
+        }
+
+        let paths = paths
+            .into_iter()
+            .map(|(index, path)| {
+                let relative = PathBuf::from(&path)
+                    .strip_prefix(&project.paths.root)
+                    .map(|path| path.display().to_string())
+                    .unwrap_or(path);
+                (index, relative)
+            })
+            .collect();
+        Ok(Self { known_contracts, source_code, paths })
+    }
+
+    /// Returns the source map of the contract at the address, as identified by the test
+    fn source_map(
+        &self,
+        identified_contracts: &BTreeMap<Address, (String, Abi)>,
+        address: Address,
+        creation: bool,
+    ) -> Option<SourceMap> {
+        let (name, _) = identified_contracts.get(&address)?;
+        let contract = self.known_contracts.get(name)?;
+        let source_map = if creation {
+            contract.bytecode.source_map()
+        } else {
+            contract.deployed_bytecode.bytecode.as_ref()?.source_map()
+        };
+        source_map?.ok()
+    }
+
+    /// Returns the path of the source file
+    fn path(&self, index: u32) -> &str {
+        self.paths.get(&index).map(String::as_str).unwrap_or("<unknown>")
+    }
+}
+
+/// Returns the debug steps of the test call and the contracts identified by the test
+fn debug_steps(result: &TestResult) -> Option<(&DebugArena, &BTreeMap<Address, (String, Abi)>)> {
+    let arena = result.debug_calls.as_ref()?.last()?;
+    Some((arena, result.identified_contracts.as_ref()?))
+}
+
+/// Returns the test results by contract in the order they are printed in, with the tests that
+/// failed in the last run and their contracts first if the `last_failures` are given
+fn ordered_results<'a>(
+    results: &'a BTreeMap<String, BTreeMap<String, TestResult>>,
+    last_failures: Option<&LastFailures>,
+) -> Vec<(&'a String, Vec<(&'a String, &'a TestResult)>)> {
+    let mut ordered = results
+        .iter()
+        .map(|(contract, tests)| (contract, tests.iter().collect::<Vec<_>>()))
+        .collect::<Vec<_>>();
+    if let Some(last_failures) = last_failures {
+        for (contract, tests) in &mut ordered {
+            tests.sort_by_key(|(signature, _)| {
+                let name = signature.split('(').next().unwrap_or_default();
+                !last_failures.contains(contract.as_str(), name)
+            });
+        }
+        ordered.sort_by_key(|(contract, _)| !last_failures.0.contains_key(contract.as_str()));
+    }
+    ordered
+}
+
+/// Where a failing test reverted
+struct RevertTrace {
+    /// The Solidity stack trace of the revert
+    frames: Vec<StackFrame>,
+    /// The source expression that reverted
+    explanation: Option<RevertExplanation>,
+}
+
+/// Builds the Solidity stack traces of the reverts of the failing tests of a debug rerun
+fn stack_traces(
+    rerun: &BTreeMap<String, BTreeMap<String, TestResult>>,
+    sources: &DebugSources,
+) -> BTreeMap<String, BTreeMap<String, RevertTrace>> {
+    let mut traces: BTreeMap<String, BTreeMap<String, RevertTrace>> = BTreeMap::new();
+    for (contract, tests) in rerun {
+        for (signature, result) in tests.iter().filter(|(_, result)| !result.success) {
+            let (arena, identified_contracts) = match debug_steps(result) {
+                Some(steps) => steps,
+                None => continue,
+            };
+            let source_map =
+                |address, creation| sources.source_map(identified_contracts, address, creation);
+            let mut frames = stack_trace(arena, source_map, &sources.source_code);
+            // frames of unidentified contracts are named by the contracts they were deployed as
+            for frame in &mut frames {
+                if frame.contract.is_none() {
+                    frame.contract =
+                        identified_contracts.get(&frame.address).map(|(name, _)| name.clone());
+                }
             }
-            (None, None) => "[FAIL]".to_string(),
+            let explanation = explain_arena_revert(arena, source_map, &sources.source_code);
+            traces
+                .entry(contract.clone())
+                .or_default()
+                .insert(signature.clone(), RevertTrace { frames, explanation });
+        }
+    }
+    traces
+}
+
+/// Prints the gas hints of the tests of a debug rerun, once per source line and hint
+///
+/// Hints in code without a source map, like precompiled contracts, are left out.
+fn print_gas_hints(rerun: &BTreeMap<String, BTreeMap<String, TestResult>>, sources: &DebugSources) {
+    // (source file, line, hint) -> source line
+    let mut hints: BTreeMap<(String, usize, String), String> = BTreeMap::new();
+    for result in rerun.values().flat_map(|tests| tests.values()) {
+        let (arena, identified_contracts) = match debug_steps(result) {
+            Some(steps) => steps,
+            None => continue,
         };
+        for hint in gas_hints(arena) {
+            let location = sources
+                .source_map(identified_contracts, hint.address, hint.creation)
+                .and_then(|source_map| {
+                    SourceLocation::new(source_map.get(hint.ic)?, &sources.source_code)
+                });
+            if let Some(location) = location {
+                let path = sources.path(location.index).to_string();
+                hints.insert((path, location.line, hint.kind.to_string()), location.snippet);
+            }
+        }
+    }
+    if hints.is_empty() {
+        return
+    }
 
-        Colour::Red.paint(txt)
-    };
+    sh_println!();
+    sh_println!("Gas hints:");
+    for ((path, line, hint), snippet) in hints {
+        sh_println!("  {}:{}: {}", path, line, shell::paint(Colour::Yellow, hint));
+        sh_println!("    {} | {}", line, snippet);
+    }
+}
 
-    println!("{} {} {}", status, name, result.kind.gas_used());
+/// Prints the internal function calls of the tests of a debug rerun and the gas profile of the
+/// internal functions over all tests
+fn print_test_internal_calls(
+    rerun: &BTreeMap<String, BTreeMap<String, TestResult>>,
+    sources: &DebugSources,
+) {
+    let mut profile = InternalGasProfile::new();
+    sh_println!();
+    sh_println!("== Internal Calls ==");
+    for (contract, tests) in rerun {
+        for (signature, result) in tests {
+            let (arena, identified_contracts) = match debug_steps(result) {
+                Some(steps) => steps,
+                None => continue,
+            };
+            sh_println!("{}::{}", contract, signature);
+            print_internal_calls(
+                arena,
+                identified_contracts,
+                &sources.known_contracts,
+                &sources.source_code,
+                &mut profile,
+            );
+            sh_println!();
+        }
+    }
+    print_internal_gas_profile(profile);
 }
 
-/// Runs all the tests
-fn test<A: ArtifactOutput + 'static>(
-    builder: MultiContractRunnerBuilder,
-    project: Project<A>,
-    evm_opts: EvmOpts,
-    filter: Filter,
-    json: bool,
+/// Prints the source expression that reverted and the frames of the stack trace, the caller
+/// first, with the line of the last frame
+fn print_stack_trace(trace: &RevertTrace, sources: &DebugSources) {
+    if let Some(explanation) = &trace.explanation {
+        print_explanation(explanation, sources.path(explanation.index));
+    }
+    let frames = &trace.frames;
+    sh_println!("Stack trace:");
+    for (i, frame) in frames.iter().enumerate() {
+        let contract = frame.contract.clone().unwrap_or_else(|| format!("{:?}", frame.address));
+        let function = frame.function.as_deref().unwrap_or("<unknown>");
+        let location = frame
+            .location
+            .as_ref()
+            .map(|location| format!(" ({}:{})", sources.path(location.index), location.line))
+            .unwrap_or_default();
+        let arrow = if i > 0 { "→ " } else { "" };
+        sh_println!("  {}{}.{}{}", arrow, contract, function, location);
+    }
+    if let Some(location) = frames.last().and_then(|frame| frame.location.as_ref()) {
+        sh_println!("    {} | {}", location.line, shell::paint(Colour::Red, &location.snippet));
+    }
+}
+
+/// Runs all the tests, the ones that failed in the last run first if `failed_first`, and prints
+/// their results unless the output is JSON
+#[allow(clippy::too_many_arguments)]
+fn test(
+    runner: &mut MultiContractRunner,
+    verbosity: u8,
+    unlimited_gas: bool,
+    filter: &FailuresFilter<'_>,
+    failed_first: bool,
     allow_failure: bool,
+    config: Option<&Config>,
+    gas_hints: bool,
+    decode_internal: bool,
 ) -> eyre::Result<TestOutcome> {
-    let verbosity = evm_opts.verbosity;
-    let mut runner = builder.build(project, evm_opts)?;
+    let spinner = shell::spinner("running tests...");
+    runner.progress = Some(Arc::new(TestProgressSpinner(spinner.clone())));
+    let results = if failed_first && !filter.last_failures.is_empty() {
+        runner.test(&FailuresFilter { failed: Some(true), ..*filter }).and_then(|mut results| {
+            for (contract, tests) in
+                runner.test(&FailuresFilter { failed: Some(false), ..*filter })?
+            {
+                results.entry(contract).or_default().extend(tests);
+            }
+            Ok(results)
+        })
+    } else {
+        runner.test(filter)
+    };
+    spinner.finish_and_clear();
+    let results = results?;
 
-    let results = runner.test(&filter)?;
+    // the stack traces of failing tests, the gas hints and the internal calls are built from the
+    // debug steps of a rerun of the unit tests
+    let rerun_tests =
+        UnitTests::new(&results, |result| gas_hints || decode_internal || !result.success);
+    let debug = match config {
+        Some(config)
+            if (verbosity > 1 || gas_hints || decode_internal) &&
+                !shell::is_json() &&
+                !rerun_tests.tests.is_empty() =>
+        {
+            let sources = DebugSources::compile(config)?;
+            runner.enable_debug();
+            let rerun = runner.test(&rerun_tests);
+            runner.disable_debug(verbosity);
+            Some((rerun?, sources))
+        }
+        _ => None,
+    };
+    let stack_traces = match debug {
+        Some((ref rerun, ref sources)) if verbosity > 1 => stack_traces(rerun, sources),
+        _ => Default::default(),
+    };
 
-    let (funcs, events, errors) = runner.execution_info;
-    if json {
-        let res = serde_json::to_string(&results)?;
-        println!("{}", res);
-    } else {
+    let (funcs, events, errors) = &runner.execution_info;
+    if !shell::is_json() {
+        if unlimited_gas {
+            sh_warn!("{}", UNLIMITED_GAS_WARNING);
+        }
         // Dapptools-style printing of test results
-        for (i, (contract_name, tests)) in results.iter().enumerate() {
+        let order = failed_first.then(|| filter.last_failures);
+        for (i, (contract_name, tests)) in ordered_results(&results, order).into_iter().enumerate()
+        {
             if i > 0 {
-                println!()
+                sh_println!()
             }
             if !tests.is_empty() {
                 let term = if tests.len() > 1 { "tests" } else { "test" };
-                println!("Running {} {} for {}", tests.len(), term, contract_name);
+                sh_println!("Running {} {} for {}", tests.len(), term, contract_name);
             }
 
             for (name, result) in tests {
                 short_test_result(name, result);
+                if !result.isolation_warnings.is_empty() {
+                    sh_println!(
+                        "{}",
+                        shell::paint(
+                            Colour::Yellow,
+                            "  Warning: the test observed state `setUp` didn't leave behind:"
+                        )
+                    );
+                    for warning in &result.isolation_warnings {
+                        sh_println!("    {}", warning);
+                    }
+                }
 
                 // adds a linebreak only if there were any traces or logs, so that the
                 // output does not look like 1 big block.
                 let mut add_newline = false;
                 if verbosity > 1 && !result.logs.is_empty() {
                     add_newline = true;
-                    println!("Logs:");
+                    sh_println!("Logs:");
                     for log in &result.logs {
-                        println!("  {}", log);
+                        sh_println!("  {}", log);
                     }
                 }
 
+                if let (Some(trace), Some((_, sources))) =
+                    (stack_traces.get(contract_name).and_then(|t| t.get(name)), &debug)
+                {
+                    add_newline = true;
+                    print_stack_trace(trace, sources);
+                }
+
                 if verbosity > 2 {
                     if let (Some(traces), Some(identified_contracts)) =
                         (&result.traces, &result.identified_contracts)
@@ -298,46 +1053,57 @@ fn test<A: ArtifactOutput + 'static>(
                             // add a new line if any logs were printed & to separate them from
                             // the traces to be printed
                             if !result.logs.is_empty() {
-                                println!();
+                                sh_println!();
                             }
 
                             let mut ident = identified_contracts.clone();
                             let mut exec_info = ExecutionInfo::new(
                                 &runner.known_contracts,
                                 &mut ident,
-                                &funcs,
-                                &events,
-                                &errors,
+                                funcs,
+                                events,
+                                errors,
                             );
                             let vm = vm();
+                            let mut out = String::new();
                             if verbosity > 4 || !result.success {
-                                add_newline = true;
-                                println!("Traces:");
-
                                 // print setup calls as well
                                 traces.iter().for_each(|trace| {
-                                    trace.pretty_print(0, &mut exec_info, &vm, "  ");
+                                    trace.pretty_print(0, &mut exec_info, &vm, "  ", &mut out);
                                 });
                             } else if !traces.is_empty() {
-                                add_newline = true;
-                                println!("Traces:");
                                 traces.last().expect("no last but not empty").pretty_print(
                                     0,
                                     &mut exec_info,
                                     &vm,
                                     "  ",
+                                    &mut out,
                                 );
                             }
+                            if !out.is_empty() {
+                                add_newline = true;
+                                sh_println!("Traces:");
+                                sh_println!("{}", out.trim_end());
+                            }
                         }
                     }
                 }
 
                 if add_newline {
-                    println!();
+                    sh_println!();
                 }
             }
         }
     }
 
+    if let Some((ref rerun, ref sources)) = debug {
+        if gas_hints {
+            print_gas_hints(rerun, sources);
+        }
+        if decode_internal {
+            print_test_internal_calls(rerun, sources);
+        }
+    }
+
     Ok(TestOutcome::new(results, allow_failure))
 }
@@ -0,0 +1,264 @@
+//! Upgrade safety check command
+
+use crate::{
+    cmd::{
+        build::BuildArgs,
+        inspect::{self, ContractArtifactField, StorageSlot},
+        Cmd,
+    },
+    exit_code::ExitCode,
+    opts::forge::ContractInfo,
+    shell::{self, sh_println, sh_status},
+};
+use ansi_term::Colour;
+use clap::Parser;
+use evm_adapters::call_tracing::metadata;
+use foundry_config::Config;
+use serde_json::Value;
+
+/// The opcodes that are checked in the code of the new implementation
+const SSTORE: u8 = 0x55;
+const DELEGATECALL: u8 = 0xf4;
+const SELFDESTRUCT: u8 = 0xff;
+
+#[derive(Debug, Clone, Parser)]
+pub struct UpgradeCheckArgs {
+    #[clap(help = "the current implementation, `<path>:<contractname>` or `<contractname>`")]
+    pub old: ContractInfo,
+
+    #[clap(help = "the new implementation, `<path>:<contractname>` or `<contractname>`")]
+    pub new: ContractInfo,
+
+    #[clap(help = "fail on warnings too, not only on unsafe storage layout changes", long)]
+    pub deny_warnings: bool,
+
+    #[clap(flatten)]
+    opts: BuildArgs,
+}
+
+impl Cmd for UpgradeCheckArgs {
+    type Output = ();
+
+    fn run(self) -> eyre::Result<Self::Output> {
+        let UpgradeCheckArgs { old, new, deny_warnings, opts } = self;
+        let config = Config::try_from_args(&opts)?;
+        let project = inspect::project_with_output_selection(&config)?;
+        let contracts = inspect::compile(&project)?;
+
+        let (_, old_output) = inspect::find_contract(&contracts, &old)?;
+        let (_, new_output) = inspect::find_contract(&contracts, &new)?;
+        let layout = |output: &Value| {
+            StorageSlot::read_all(ContractArtifactField::StorageLayout.get(output))
+        };
+        let errors = layout_errors(&layout(old_output), &layout(new_output));
+        let warnings = code_warnings(&new.name, new_output);
+
+        if shell::is_json() {
+            shell::print_json(&serde_json::json!({ "errors": errors, "warnings": warnings }))?;
+        } else {
+            for error in &errors {
+                sh_println!("{} {}", shell::paint(Colour::Red, "error:"), error);
+            }
+            for warning in &warnings {
+                sh_println!("{} {}", shell::paint(Colour::Yellow, "warning:"), warning);
+            }
+        }
+
+        if !errors.is_empty() || (deny_warnings && !warnings.is_empty()) {
+            return Err(ExitCode::Failure
+                .error(format!("`{}` is not a safe upgrade of `{}`", new.name, old.name)))
+        }
+        sh_status!("`{}` is a safe upgrade of `{}`", new.name, old.name);
+        Ok(())
+    }
+}
+
+/// Returns the changes of the storage layout that corrupt the storage of the proxy: variables
+/// that were removed, moved to another slot or changed their type, and gaps that don't end at the
+/// same slot anymore
+///
+/// Gaps are the `__gap` arrays that reserve slots for variables of later versions, they must be
+/// shrunk by exactly the number of slots of the variables that are added before them. Renames are
+/// allowed.
+fn layout_errors(old: &[StorageSlot], new: &[StorageSlot]) -> Vec<String> {
+    let is_gap = |var: &StorageSlot| var.label.starts_with("__gap");
+    let end = |var: &StorageSlot| {
+        let bytes = var.bytes.parse::<u64>().unwrap_or_default();
+        var.slot.parse::<u64>().unwrap_or_default() + (bytes + 31) / 32
+    };
+
+    let mut errors = Vec::new();
+    for var in old {
+        if is_gap(var) {
+            match new.iter().find(|new| new.label == var.label && new.contract == var.contract) {
+                Some(gap) if end(gap) != end(var) => errors.push(format!(
+                    "gap `{}` of `{}` ends at slot {} instead of {}",
+                    var.label,
+                    var.contract,
+                    end(gap),
+                    end(var)
+                )),
+                Some(_) => {}
+                None => errors.push(format!(
+                    "removed gap `{}` of `{}` at slot {}",
+                    var.label, var.contract, var.slot
+                )),
+            }
+            continue
+        }
+
+        let at_same_slot =
+            new.iter().find(|new| !is_gap(new) && new.slot == var.slot && new.offset == var.offset);
+        let moved = new.iter().find(|new| {
+            new.label == var.label && (new.slot != var.slot || new.offset != var.offset)
+        });
+        match (at_same_slot, moved) {
+            (_, Some(new)) => errors
+                .push(format!("moved `{}` from slot {} to slot {}", var.label, var.slot, new.slot)),
+            (Some(new), None) if new.ty != var.ty => errors.push(format!(
+                "changed the type of `{}` at slot {} from `{}` to `{}`",
+                var.label, var.slot, var.ty, new.ty
+            )),
+            (Some(_), None) => {}
+            (None, None) => errors.push(format!("removed `{}` at slot {}", var.label, var.slot)),
+        }
+    }
+    errors
+}
+
+/// Returns warnings about code of the new implementation that's unsafe behind a proxy
+fn code_warnings(name: &str, output: &Value) -> Vec<String> {
+    let decode = |field: ContractArtifactField| {
+        let object = field.get(output)["object"].as_str().unwrap_or_default();
+        // code with unlinked libraries can't be decoded and isn't checked
+        hex::decode(object.trim_start_matches("0x")).unwrap_or_default()
+    };
+    let code = decode(ContractArtifactField::DeployedBytecode);
+    let creation_code = decode(ContractArtifactField::Bytecode);
+    // the creation code is the constructor followed by the runtime code
+    let constructor = creation_code.strip_suffix(&code[..]).unwrap_or(&creation_code[..]);
+
+    let mut warnings = Vec::new();
+    let declares_constructor = ContractArtifactField::Abi
+        .get(output)
+        .as_array()
+        .into_iter()
+        .flatten()
+        .any(|item| item["type"] == "constructor");
+    if declares_constructor || has_opcode(constructor, SSTORE) {
+        warnings.push(format!(
+            "`{}` has a constructor that initializes state, which isn't run in the context of the proxy, use an initializer instead",
+            name
+        ));
+    }
+    if has_opcode(&code, SELFDESTRUCT) {
+        warnings.push(format!(
+            "`{}` can `selfdestruct`, which destroys the implementation of every proxy",
+            name
+        ));
+    }
+    if has_opcode(&code, DELEGATECALL) {
+        warnings.push(format!(
+            "`{}` uses `delegatecall`, which can run code that `selfdestruct`s the implementation",
+            name
+        ));
+    }
+    warnings
+}
+
+/// Whether the code executes the opcode, push data and the metadata are skipped
+fn has_opcode(code: &[u8], opcode: u8) -> bool {
+    let code = match metadata(code) {
+        Some(metadata) => &code[..code.len() - metadata.len() - 2],
+        None => code,
+    };
+    let mut pc = 0;
+    while pc < code.len() {
+        let op = code[pc];
+        if op == opcode {
+            return true
+        }
+        pc += 1;
+        if (0x60..=0x7f).contains(&op) {
+            pc += (op - 0x5f) as usize;
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn var(label: &str, ty: &str, slot: u64, bytes: u64) -> StorageSlot {
+        StorageSlot {
+            label: label.to_string(),
+            ty: ty.to_string(),
+            slot: slot.to_string(),
+            offset: 0,
+            bytes: bytes.to_string(),
+            contract: "src/Impl.sol:Impl".to_string(),
+        }
+    }
+
+    #[test]
+    fn checks_storage_layout_changes() {
+        let old = vec![
+            var("owner", "address", 0, 20),
+            var("count", "uint256", 1, 32),
+            var("__gap", "uint256[48]", 2, 1536),
+        ];
+
+        // a variable added before the gap, which shrinks by one slot
+        let added = vec![
+            var("admin", "address", 0, 20),
+            var("count", "uint256", 1, 32),
+            var("paused", "bool", 2, 1),
+            var("__gap", "uint256[47]", 3, 1504),
+        ];
+        assert!(layout_errors(&old, &added).is_empty());
+
+        let reordered = vec![
+            var("count", "uint256", 0, 32),
+            var("owner", "address", 1, 20),
+            var("paused", "bool", 2, 1),
+            var("__gap", "uint256[48]", 3, 1536),
+        ];
+        assert_eq!(
+            layout_errors(&old, &reordered),
+            vec![
+                "moved `owner` from slot 0 to slot 1",
+                "moved `count` from slot 1 to slot 0",
+                "gap `__gap` of `src/Impl.sol:Impl` ends at slot 51 instead of 50",
+            ]
+        );
+
+        let changed = vec![
+            var("owner", "address", 0, 20),
+            var("count", "int256", 1, 32),
+            var("__gap", "uint256[48]", 2, 1536),
+        ];
+        assert_eq!(
+            layout_errors(&old, &changed),
+            vec!["changed the type of `count` at slot 1 from `uint256` to `int256`"]
+        );
+
+        let removed = vec![var("owner", "address", 0, 20)];
+        assert_eq!(
+            layout_errors(&old, &removed),
+            vec![
+                "removed `count` at slot 1",
+                "removed gap `__gap` of `src/Impl.sol:Impl` at slot 2"
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_opcodes() {
+        // PUSH1 0xff SELFDESTRUCT
+        assert!(has_opcode(&[0x60, 0xff, 0xff], SELFDESTRUCT));
+        // the 0xff is push data
+        assert!(!has_opcode(&[0x60, 0xff, 0x00], SELFDESTRUCT));
+        assert!(has_opcode(&[0x60, 0x00, 0xf4], DELEGATECALL));
+    }
+}
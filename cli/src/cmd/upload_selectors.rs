@@ -0,0 +1,94 @@
+//! upload-selectors command
+
+use crate::{
+    cmd::{
+        build::{BuildArgs, CompiledArtifact},
+        Cmd,
+    },
+    shell::{sh_println, sh_status},
+};
+use clap::Parser;
+use foundry_config::Config;
+use serde_json::Value;
+use std::collections::BTreeSet;
+
+#[derive(Debug, Clone, Parser)]
+pub struct UploadSelectorsArgs {
+    #[clap(long, help = "also upload the selectors of test contracts")]
+    pub tests: bool,
+
+    #[clap(flatten)]
+    opts: BuildArgs,
+}
+
+impl Cmd for UploadSelectorsArgs {
+    type Output = ();
+
+    fn run(self) -> eyre::Result<Self::Output> {
+        let config = Config::try_from_args(&self.opts)?;
+        if config.offline {
+            eyre::bail!("selectors can't be uploaded offline")
+        }
+        let project = self.opts.project()?;
+        super::compile(&project)?;
+
+        let artifacts = CompiledArtifact::read_all(&project)?;
+        let abis = artifacts
+            .iter()
+            .filter(|compiled| self.tests || !compiled.is_test())
+            .filter_map(|compiled| compiled.artifact.get("abi"));
+        let abi = merge_abis(abis);
+        let count = abi.as_array().map_or(0, Vec::len);
+        if count == 0 {
+            sh_println!("No functions or events to upload.");
+            return Ok(())
+        }
+
+        sh_status!("Uploading the signatures of {} functions and events to 4byte.directory", count);
+        let rt = tokio::runtime::Runtime::new().expect("could not start tokio rt");
+        let import = rt.block_on(foundry_utils::fourbyte_import_abi(&abi))?;
+        sh_println!(
+            "Imported {} new signatures, {} were already known and {} were ignored.",
+            import.num_imported,
+            import.num_duplicates,
+            import.num_ignored
+        );
+        Ok(())
+    }
+}
+
+/// Merges the functions and events of the ABIs into a single ABI, without duplicates
+fn merge_abis<'a>(abis: impl IntoIterator<Item = &'a Value>) -> Value {
+    let mut seen = BTreeSet::new();
+    let items = abis
+        .into_iter()
+        .filter_map(Value::as_array)
+        .flatten()
+        .filter(|item| item["type"] == "function" || item["type"] == "event")
+        .filter(|item| seen.insert(item.to_string()))
+        .cloned()
+        .collect();
+    Value::Array(items)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn merges_functions_and_events() {
+        let transfer = json!({
+            "type": "function",
+            "name": "transfer",
+            "inputs": [{ "name": "to", "type": "address" }, { "name": "amount", "type": "uint256" }],
+            "outputs": [{ "name": "", "type": "bool" }],
+            "stateMutability": "nonpayable"
+        });
+        let event = json!({ "type": "event", "name": "Paused", "inputs": [], "anonymous": false });
+        let token = json!([transfer, event, { "type": "constructor", "inputs": [] }]);
+        let vault = json!([transfer, { "type": "error", "name": "Unauthorized", "inputs": [] }]);
+
+        assert_eq!(merge_abis([&token, &vault]), json!([transfer, event]));
+    }
+}
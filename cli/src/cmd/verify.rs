@@ -1,28 +1,214 @@
-//! Verify contract source on etherscan
+//! Verify contract source on etherscan, blockscout or sourcify
 
-use crate::utils;
-use cast::SimpleCast;
+use crate::{
+    cmd::{build::BuildArgs, inspect, Cmd},
+    explorer::{explorer, VerifySubmission},
+    opts::forge::{ContractInfo, FullContractInfo},
+    utils,
+};
+use clap::Parser;
 use ethers::{
-    abi::{Address, Function, FunctionExt},
-    core::types::Chain,
-    etherscan::{contract::VerifyContract, Client},
+    abi::{Abi, Address},
     prelude::Provider,
     providers::Middleware,
+    solc::Project,
 };
 use eyre::ContextCompat;
-use std::convert::TryFrom;
-
-/// Run the verify command to submit the contract's source code for verification on etherscan
-pub async fn run(
-    path: String,
-    name: String,
-    address: Address,
-    args: Vec<String>,
-) -> eyre::Result<()> {
-    let etherscan_api_key = foundry_utils::etherscan_api_key()?;
-    let rpc_url = utils::rpc_url();
-    let provider = Provider::try_from(rpc_url)?;
-    let chain = provider
+use foundry_config::{Config, ExplorerKind};
+use foundry_utils::{metadata::SolcMetadata, parse_tokens};
+use serde_json::json;
+use std::{collections::BTreeMap, convert::TryFrom};
+
+#[derive(Debug, Clone, Parser)]
+pub struct VerifyArgs {
+    #[clap(help = "contract source info `<path>:<contractname>`")]
+    pub contract: FullContractInfo,
+
+    #[clap(help = "the address of the contract to verify.")]
+    pub address: Address,
+
+    #[clap(help = "constructor args calldata arguments.")]
+    pub constructor_args: Vec<String>,
+
+    #[clap(
+        long,
+        help = "the ABI encoded constructor arguments, instead of the arguments to encode",
+        conflicts_with = "constructor-args"
+    )]
+    pub encoded_constructor_args: Option<String>,
+
+    #[clap(
+        long,
+        help = "the chain id of the network the contract is deployed to, queried from `ETH_RPC_URL` if not set"
+    )]
+    pub chain_id: Option<u64>,
+
+    #[clap(flatten)]
+    pub verifier: VerifierArgs,
+
+    #[clap(long, help = "wait until the explorer has processed the submission")]
+    pub watch: bool,
+
+    #[clap(flatten)]
+    pub opts: BuildArgs,
+}
+
+/// The explorer a contract is verified with
+#[derive(Debug, Clone, Parser)]
+pub struct VerifierArgs {
+    #[clap(long, env = "ETHERSCAN_API_KEY", help = "the etherscan api key")]
+    pub etherscan_api_key: Option<String>,
+
+    #[clap(
+        long,
+        help = "the explorer to verify with, the explorer of the chain in the config or etherscan if not set",
+        possible_values = &["etherscan", "blockscout", "sourcify"]
+    )]
+    pub verifier: Option<ExplorerKind>,
+
+    #[clap(
+        long,
+        help = "the url of the verification api, like the api of a blockscout instance or a self hosted sourcify server"
+    )]
+    pub verifier_url: Option<String>,
+}
+
+impl Cmd for VerifyArgs {
+    type Output = ();
+
+    fn run(self) -> eyre::Result<Self::Output> {
+        let rt = tokio::runtime::Runtime::new().expect("could not start tokio rt");
+        rt.block_on(self.verify())
+    }
+}
+
+impl VerifyArgs {
+    /// Compiles the contract and submits its sources for verification
+    pub(crate) async fn verify(&self) -> eyre::Result<()> {
+        let config = Config::try_from_args(&self.opts)?.apply_chain_preset();
+        let chain_id = match self.chain_id.or_else(|| config.chain_id.map(u64::from)) {
+            Some(chain_id) => chain_id,
+            None => chain_id().await?,
+        };
+        let compilation = VerifyCompilation::compile(&config)?;
+        let constructor_args = match self.encoded_constructor_args {
+            Some(ref args) => args.trim_start_matches("0x").to_string(),
+            None => {
+                let abi = compilation.abi(&self.contract)?;
+                encode_constructor_args(&abi, &self.constructor_args)?
+            }
+        };
+        let submission = compilation.submission(
+            &self.contract,
+            self.address,
+            chain_id,
+            constructor_args,
+            &BTreeMap::new(),
+        )?;
+        let explorer = explorer(
+            &config,
+            chain_id,
+            self.verifier.verifier,
+            self.verifier.verifier_url.clone(),
+            self.verifier.etherscan_api_key.clone(),
+        )?;
+        explorer.verify(&submission, self.watch).await
+    }
+}
+
+/// The compiled contracts of a project, the submissions of the contracts to verify are built from
+pub(crate) struct VerifyCompilation {
+    project: Project,
+    /// The json output of every contract by its source file
+    contracts: BTreeMap<String, BTreeMap<String, serde_json::Value>>,
+}
+
+impl VerifyCompilation {
+    /// Compiles the project with the outputs the submissions need
+    pub(crate) fn compile(config: &Config) -> eyre::Result<Self> {
+        let project = inspect::project_with_output_selection(config)?;
+        let contracts = inspect::compile(&project)?;
+        Ok(Self { project, contracts })
+    }
+
+    /// Returns the source file and the json output of the contract
+    fn find(&self, contract: &FullContractInfo) -> eyre::Result<(&str, &serde_json::Value)> {
+        let FullContractInfo { path, name } = contract;
+        inspect::find_contract(
+            &self.contracts,
+            &ContractInfo { path: Some(path.clone()), name: name.clone() },
+        )
+    }
+
+    /// Returns the ABI of the contract
+    pub(crate) fn abi(&self, contract: &FullContractInfo) -> eyre::Result<Abi> {
+        let (_, output) = self.find(contract)?;
+        Ok(serde_json::from_value(output["abi"].clone())?)
+    }
+
+    /// Builds the submission of the contract deployed at the address
+    ///
+    /// `constructor_args` are the ABI encoded constructor arguments, hex encoded, and `libraries`
+    /// the addresses of the libraries the deployed code links by their source file and name, in
+    /// addition to the `libraries` of the config.
+    pub(crate) fn submission(
+        &self,
+        contract: &FullContractInfo,
+        address: Address,
+        chain_id: u64,
+        constructor_args: String,
+        libraries: &BTreeMap<String, BTreeMap<String, Address>>,
+    ) -> eyre::Result<VerifySubmission> {
+        let (file, output) = self.find(contract)?;
+        let SolcMetadata { raw: metadata, json: parsed } =
+            SolcMetadata::from_output(&output["metadata"])?
+                .wrap_err("no metadata for the contract")?;
+
+        // the metadata lists exactly the sources the contract was compiled from
+        let mut sources = BTreeMap::new();
+        for source in parsed["sources"].as_object().wrap_err("no sources in the metadata")?.keys() {
+            let content = std::fs::read_to_string(self.project.paths.root.join(source))?;
+            sources.insert(source.clone(), content);
+        }
+
+        let compiler_version = parsed["compiler"]["version"]
+            .as_str()
+            .wrap_err("no compiler version in the metadata")?;
+
+        let mut settings = serde_json::to_value(&self.project.solc_config.settings)?;
+        settings["outputSelection"] =
+            json!({ "*": { "*": ["abi", "evm.bytecode", "evm.deployedBytecode"] } });
+        for (source, libraries) in libraries {
+            for (name, address) in libraries {
+                settings["libraries"][source][name] = json!(format!("{:?}", address));
+            }
+        }
+        let input = json!({
+            "language": "Solidity",
+            "sources": sources
+                .iter()
+                .map(|(path, content)| (path.clone(), json!({ "content": content })))
+                .collect::<serde_json::Map<_, _>>(),
+            "settings": settings,
+        });
+
+        Ok(VerifySubmission {
+            address,
+            chain_id,
+            contract_name: format!("{}:{}", file, contract.name),
+            compiler_version: compiler_version.to_string(),
+            input,
+            constructor_args,
+            metadata,
+            sources,
+        })
+    }
+}
+
+/// Returns the chain id of the node at `ETH_RPC_URL`
+async fn chain_id() -> eyre::Result<u64> {
+    let provider = Provider::try_from(utils::rpc_url())?;
+    Ok(provider
         .get_chainid()
         .await
         .map_err(|err| {
@@ -32,79 +218,51 @@ pub async fn run(
         You could also try connecting to an external Ethereum node:
         For example, try `export ETH_RPC_URL=https://mainnet.infura.io'.
         If you have an Infura API key, add it to the end of the URL.
+        Alternatively, pass the chain id with `--chain-id`.
 
         Error: {}"#,
                 err
             )
         })?
-        .as_u64();
-
-    let contract = utils::find_dapp_json_contract(&path, &name)?;
-    std::fs::write("meta.json", serde_json::to_string_pretty(&contract).unwrap()).unwrap();
-    let metadata = contract.metadata.wrap_err("No compiler version found")?;
-    let compiler_version = format!("v{}", metadata.compiler.version);
-    let mut constructor_args = None;
-    if let Some(constructor) = contract.abi.unwrap().constructor {
-        // convert constructor into function
-        #[allow(deprecated)]
-        let fun = Function {
-            name: "constructor".to_string(),
-            inputs: constructor.inputs,
-            outputs: vec![],
-            constant: None,
-            state_mutability: Default::default(),
-        };
-
-        constructor_args = Some(SimpleCast::calldata(fun.abi_signature(), &args)?);
-    } else if !args.is_empty() {
-        eyre::bail!("No constructor found but contract arguments provided")
-    }
+        .as_u64())
+}
 
-    let chain = match chain {
-        1 => Chain::Mainnet,
-        3 => Chain::Ropsten,
-        4 => Chain::Rinkeby,
-        5 => Chain::Goerli,
-        42 => Chain::Kovan,
-        100 => Chain::XDai,
-        _ => eyre::bail!("unexpected chain {}", chain),
+/// ABI encodes the constructor arguments without a selector, as etherscan expects them
+fn encode_constructor_args(abi: &Abi, args: &[String]) -> eyre::Result<String> {
+    let constructor = match &abi.constructor {
+        Some(constructor) => constructor,
+        None if args.is_empty() => return Ok(String::new()),
+        None => eyre::bail!("No constructor found but contract arguments provided"),
     };
-    let etherscan = Client::new(chain, etherscan_api_key)
-        .map_err(|err| eyre::eyre!("Failed to create etherscan client: {}", err))?;
+    if constructor.inputs.len() != args.len() {
+        eyre::bail!(
+            "the constructor expects {} arguments, but {} were provided",
+            constructor.inputs.len(),
+            args.len()
+        )
+    }
+    let params =
+        constructor.inputs.iter().map(|input| &input.kind).zip(args.iter().map(String::as_str));
+    let tokens = parse_tokens(params, true)?;
+    Ok(hex::encode(ethers::abi::encode(&tokens)))
+}
 
-    let source = std::fs::read_to_string(&path)?;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let contract = VerifyContract::new(address, source, compiler_version)
-        .constructor_arguments(constructor_args)
-        .optimization(metadata.settings.optimizer.enabled.unwrap_or_default())
-        .runs(metadata.settings.optimizer.runs.unwrap_or_default() as u32);
+    #[test]
+    fn can_encode_constructor_args() {
+        let abi: Abi = serde_json::from_str(
+            r#"[{"type":"constructor","inputs":[{"name":"owner","type":"address"},{"name":"supply","type":"uint256"}],"stateMutability":"nonpayable"}]"#,
+        )
+        .unwrap();
+        let args = vec!["0x0000000000000000000000000000000000000001".to_string(), "2".to_string()];
+        let encoded = encode_constructor_args(&abi, &args).unwrap();
+        assert_eq!(encoded.len(), 128);
+        assert!(encoded.ends_with("02"));
 
-    let resp = etherscan
-        .submit_contract_verification(&contract)
-        .await
-        .map_err(|err| eyre::eyre!("Failed to submit contract verification: {}", err))?;
-
-    if resp.status == "0" {
-        if resp.message == "Contract source code already verified" {
-            println!("Contract source code already verified.");
-            Ok(())
-        } else {
-            eyre::bail!(
-                "Encountered an error verifying this contract:\nResponse: `{}`\nDetails: `{}`",
-                resp.message,
-                resp.result
-            );
-        }
-    } else {
-        println!(
-            r#"Submitted contract for verification:
-            Response: `{}`
-            GUID: `{}`
-            url: {}#code"#,
-            resp.message,
-            resp.result,
-            etherscan.address_url(address)
-        );
-        Ok(())
+        assert!(encode_constructor_args(&abi, &args[..1]).is_err());
+        assert_eq!(encode_constructor_args(&Abi::default(), &[]).unwrap(), "");
     }
 }
@@ -0,0 +1,129 @@
+//! The exit codes of the commands, so that CI can tell apart why a command failed
+//!
+//! | code | meaning                                               |
+//! |------|-------------------------------------------------------|
+//! | 0    | success                                               |
+//! | 1    | tests failed, a script reverted or a check failed     |
+//! | 2    | the sources didn't compile                            |
+//! | 3    | the config or the command line arguments are invalid  |
+//! | 4    | a request to an RPC endpoint or block explorer failed |
+//! | 5    | any other error                                       |
+
+use crate::shell;
+use ethers::{providers::ProviderError, solc::error::SolcError};
+use foundry_config::figment;
+use std::fmt;
+
+/// Why a command exited
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitCode {
+    Success,
+    /// Tests failed, a script reverted or a check like `forge snapshot --check` failed
+    Failure,
+    /// The sources didn't compile
+    CompilationError,
+    /// The config or the command line arguments are invalid
+    ConfigError,
+    /// A request to an RPC endpoint or block explorer failed
+    NetworkError,
+    /// Any other error
+    Error,
+}
+
+impl ExitCode {
+    pub fn code(self) -> i32 {
+        match self {
+            ExitCode::Success => 0,
+            ExitCode::Failure => 1,
+            ExitCode::CompilationError => 2,
+            ExitCode::ConfigError => 3,
+            ExitCode::NetworkError => 4,
+            ExitCode::Error => 5,
+        }
+    }
+
+    /// Exits the process with this code
+    pub fn exit(self) -> ! {
+        std::process::exit(self.code())
+    }
+
+    /// Returns an error with the message that exits with this code
+    pub fn error(self, msg: impl fmt::Display) -> eyre::Report {
+        ExitError { code: self, msg: msg.to_string() }.into()
+    }
+
+    /// Returns the code a command that failed with the error exits with
+    ///
+    /// Errors created with [`ExitCode::error`] exit with their code, other errors are classified
+    /// by their causes.
+    pub fn of(err: &eyre::Report) -> Self {
+        for cause in err.chain() {
+            if let Some(err) = cause.downcast_ref::<ExitError>() {
+                return err.code
+            }
+            if cause.is::<figment::Error>() {
+                return ExitCode::ConfigError
+            }
+            if cause.is::<SolcError>() {
+                return ExitCode::CompilationError
+            }
+            if cause.is::<ProviderError>() || cause.is::<reqwest::Error>() {
+                return ExitCode::NetworkError
+            }
+        }
+        ExitCode::Error
+    }
+}
+
+/// An error that exits the command with its code
+#[derive(Debug)]
+pub struct ExitError {
+    code: ExitCode,
+    msg: String,
+}
+
+impl fmt::Display for ExitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.msg)
+    }
+}
+
+impl std::error::Error for ExitError {}
+
+/// Prints the error of the command, unless the shell is silent, and exits with its code
+pub fn exit_with_error(err: eyre::Report) -> ! {
+    shell::with_shell(|shell| shell.error(format!("{:?}", err)));
+    ExitCode::of(&err).exit()
+}
+
+/// Installs a panic hook that doesn't print panics if the shell is silent
+///
+/// Panics keep their behavior otherwise, some are caught, e.g. by the fuzzer.
+pub fn install_panic_hook() {
+    let hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        if !shell::is_silent() {
+            hook(info)
+        }
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_errors() {
+        assert_eq!(
+            ExitCode::of(&ExitCode::CompilationError.error("failed")),
+            ExitCode::CompilationError
+        );
+        assert_eq!(
+            ExitCode::of(&ExitCode::Failure.error("1 failing test").wrap_err("forge test failed")),
+            ExitCode::Failure
+        );
+        let err: eyre::Report = figment::Error::from("invalid type".to_string()).into();
+        assert_eq!(ExitCode::of(&err), ExitCode::ConfigError);
+        assert_eq!(ExitCode::of(&eyre::eyre!("something went wrong")), ExitCode::Error);
+    }
+}
@@ -0,0 +1,403 @@
+//! Block explorers that verify contracts and serve the ABIs of verified contracts
+
+use crate::shell::{sh_println, sh_status};
+use async_trait::async_trait;
+use ethers::abi::{Abi, Address};
+use eyre::ContextCompat;
+use foundry_config::{ChainPreset, Config, ExplorerKind};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Value};
+use std::{collections::BTreeMap, time::Duration};
+
+/// The default url of the sourcify server
+pub const SOURCIFY_URL: &str = "https://sourcify.dev/server";
+
+/// The default url of the repository sourcify serves the metadata of verified contracts from
+pub const SOURCIFY_REPO_URL: &str = "https://repo.sourcify.dev";
+
+/// A compiled contract submitted for verification
+#[derive(Debug, Clone)]
+pub struct VerifySubmission {
+    /// The address the contract is deployed at
+    pub address: Address,
+    /// The chain the contract is deployed to
+    pub chain_id: u64,
+    /// The contract like `src/Token.sol:Token`
+    pub contract_name: String,
+    /// The version of solc the contract was compiled with, like `0.8.10+commit.fc410830`
+    pub compiler_version: String,
+    /// The solc standard json input of the contract's sources
+    pub input: Value,
+    /// The ABI encoded constructor arguments, hex encoded without `0x`
+    pub constructor_args: String,
+    /// The metadata of the contract as emitted by solc
+    pub metadata: String,
+    /// The sources of the contract by path, exactly the ones listed in the metadata
+    pub sources: BTreeMap<String, String>,
+}
+
+/// A block explorer that verifies contracts and serves the ABIs of verified contracts
+#[async_trait]
+pub trait Explorer: Send + Sync {
+    fn kind(&self) -> ExplorerKind;
+
+    /// Fetches the name and the JSON ABI of the verified contract at the address, the ABI of the
+    /// implementation for proxies the explorer knows the implementation of
+    ///
+    /// The JSON ABI keeps the names and internal types of tuple components, which [`Abi`] drops.
+    async fn contract_abi(&self, address: Address) -> eyre::Result<(String, Value)>;
+
+    /// Fetches the name and the ABI of the verified contract at the address, like
+    /// [`Explorer::contract_abi`]
+    async fn contract(&self, address: Address) -> eyre::Result<(String, Abi)> {
+        let (name, abi) = self.contract_abi(address).await?;
+        Ok((name, serde_json::from_value(abi)?))
+    }
+
+    /// Submits the contract for verification, if `watch` is set it returns once the explorer
+    /// processed the submission
+    async fn verify(&self, submission: &VerifySubmission, watch: bool) -> eyre::Result<()>;
+}
+
+/// Returns the explorer of the chain
+///
+/// The `kind` and the `url`, like `--verifier` and `--verifier-url`, take precedence over the
+/// explorer of the chain in the `explorers` of the config. The `api_key`, like `ETHERSCAN_API_KEY`,
+/// is used if the config has no api key for the chain. Chains without an explorer use etherscan.
+///
+/// Fails if the config is `offline`, so that lookups are skipped.
+pub fn explorer(
+    config: &Config,
+    chain_id: u64,
+    kind: Option<ExplorerKind>,
+    url: Option<String>,
+    api_key: Option<String>,
+) -> eyre::Result<Box<dyn Explorer>> {
+    if config.offline {
+        eyre::bail!("block explorers can't be reached offline")
+    }
+    let configured = config.explorer(chain_id)?;
+    let kind =
+        kind.or_else(|| configured.as_ref().map(|explorer| explorer.kind)).unwrap_or_default();
+    // the url and key of the config are only valid for its kind of explorer
+    let configured = configured.filter(|explorer| explorer.kind == kind);
+    let url = url.or_else(|| configured.as_ref().and_then(|explorer| explorer.url.clone()));
+    let api_key = configured.and_then(|explorer| explorer.api_key).or(api_key);
+
+    Ok(match kind {
+        ExplorerKind::Etherscan => {
+            // the `etherscan_api_url` of the config is the one of its chain
+            let url = url.or_else(|| {
+                config
+                    .etherscan_api_url
+                    .clone()
+                    .filter(|_| config.chain_id.map_or(true, |id| u64::from(id) == chain_id))
+            });
+            let chain = ChainPreset::with_id(chain_id);
+            let (api_url, browser_url) = match (url, chain) {
+                (Some(url), _) => (url, None),
+                (None, Some(chain)) => {
+                    (chain.etherscan_api_url.to_string(), Some(chain.etherscan_browser_url.to_string()))
+                }
+                (None, None) => eyre::bail!(
+                    "no etherscan api known for chain {}, set it in `explorers` or pass it with `--verifier-url`",
+                    chain_id
+                ),
+            };
+            Box::new(Etherscan::new(api_url, browser_url, api_key))
+        }
+        ExplorerKind::Blockscout => {
+            let api_url = url.wrap_err_with(|| {
+                format!("the blockscout api of chain {} must be set in `explorers` or with `--verifier-url`", chain_id)
+            })?;
+            let browser_url = api_url.trim_end_matches('/').trim_end_matches("/api").to_string();
+            Box::new(Blockscout(Etherscan::new(api_url, Some(browser_url), api_key)))
+        }
+        ExplorerKind::Sourcify => Box::new(Sourcify {
+            chain_id,
+            server_url: url.unwrap_or_else(|| SOURCIFY_URL.to_string()),
+            repo_url: SOURCIFY_REPO_URL.to_string(),
+            client: reqwest::Client::new(),
+        }),
+    })
+}
+
+/// A response of the etherscan api
+#[derive(Debug, Deserialize)]
+struct EtherscanResponse<T> {
+    status: String,
+    message: String,
+    result: T,
+}
+
+/// The source code of a contract as returned by the etherscan api
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct EtherscanSourceCode {
+    contract_name: String,
+    #[serde(rename = "ABI")]
+    abi: String,
+    #[serde(default)]
+    implementation: String,
+}
+
+/// Etherscan and the explorers with an etherscan compatible api, like Polygonscan, Arbiscan or
+/// Snowtrace
+#[derive(Debug, Clone)]
+pub struct Etherscan {
+    api_url: String,
+    browser_url: Option<String>,
+    api_key: Option<String>,
+    client: reqwest::Client,
+}
+
+impl Etherscan {
+    pub fn new(api_url: String, browser_url: Option<String>, api_key: Option<String>) -> Self {
+        Self { api_url, browser_url, api_key, client: reqwest::Client::new() }
+    }
+
+    /// Queries the api, failing if the status of the response isn't `1`
+    async fn get<T: DeserializeOwned>(&self, query: &[(&str, &str)]) -> eyre::Result<T> {
+        let mut request = self.client.get(&self.api_url).query(query);
+        if let Some(ref api_key) = self.api_key {
+            request = request.query(&[("apikey", api_key)]);
+        }
+        let resp: EtherscanResponse<Value> = request.send().await?.json().await?;
+        if resp.status != "1" {
+            eyre::bail!("{}: {}", resp.message, resp.result)
+        }
+        Ok(serde_json::from_value(resp.result)?)
+    }
+}
+
+#[async_trait]
+impl Explorer for Etherscan {
+    fn kind(&self) -> ExplorerKind {
+        ExplorerKind::Etherscan
+    }
+
+    async fn contract_abi(&self, address: Address) -> eyre::Result<(String, Value)> {
+        let address = format!("{:?}", address);
+        let sources: Vec<EtherscanSourceCode> = self
+            .get(&[("module", "contract"), ("action", "getsourcecode"), ("address", &address)])
+            .await?;
+        let source = sources.into_iter().next().wrap_err("no source code in the response")?;
+        if source.abi.starts_with("Contract source code not verified") {
+            eyre::bail!("contract {} is not verified", address)
+        }
+
+        let abi = if source.implementation.is_empty() {
+            source.abi
+        } else {
+            self.get(&[
+                ("module", "contract"),
+                ("action", "getabi"),
+                ("address", &source.implementation),
+            ])
+            .await?
+        };
+        Ok((source.contract_name, serde_json::from_str(&abi)?))
+    }
+
+    async fn verify(&self, submission: &VerifySubmission, watch: bool) -> eyre::Result<()> {
+        if self.api_key.is_none() {
+            eyre::bail!("`ETHERSCAN_API_KEY` is not set")
+        }
+        self.submit(submission, watch).await
+    }
+}
+
+impl Etherscan {
+    /// Submits the standard json input of the contract, watching the queue of submissions
+    async fn submit(&self, submission: &VerifySubmission, watch: bool) -> eyre::Result<()> {
+        let form = [
+            ("module", "contract".to_string()),
+            ("action", "verifysourcecode".to_string()),
+            ("contractaddress", format!("{:?}", submission.address)),
+            ("sourceCode", serde_json::to_string(&submission.input)?),
+            ("codeformat", "solidity-standard-json-input".to_string()),
+            ("contractname", submission.contract_name.clone()),
+            ("compilerversion", format!("v{}", submission.compiler_version)),
+            ("constructorArguements", submission.constructor_args.clone()),
+        ];
+        let mut request = self.client.post(&self.api_url).form(&form);
+        if let Some(ref api_key) = self.api_key {
+            request = request.query(&[("apikey", api_key)]);
+        }
+        let resp: EtherscanResponse<String> = request
+            .send()
+            .await?
+            .json()
+            .await
+            .map_err(|err| eyre::eyre!("Failed to submit contract verification: {}", err))?;
+
+        if resp.status == "0" {
+            if resp.result.contains("already verified") || resp.message.contains("already verified")
+            {
+                sh_println!("Contract source code already verified.");
+                return Ok(())
+            }
+            eyre::bail!(
+                "Encountered an error verifying this contract:\nResponse: `{}`\nDetails: `{}`",
+                resp.message,
+                resp.result
+            );
+        }
+
+        sh_status!(
+            "Submitted contract for verification:\n\tResponse: `{}`\n\tGUID: `{}`",
+            resp.message,
+            resp.result
+        );
+        if let Some(ref browser_url) = self.browser_url {
+            sh_status!("\turl: {}/address/{:?}#code", browser_url, submission.address);
+        }
+        if !watch {
+            return Ok(())
+        }
+
+        // etherscan processes submissions in a queue
+        let guid = resp.result;
+        for _ in 0..30 {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            let mut request = self.client.get(&self.api_url).query(&[
+                ("module", "contract"),
+                ("action", "checkverifystatus"),
+                ("guid", guid.as_str()),
+            ]);
+            if let Some(ref api_key) = self.api_key {
+                request = request.query(&[("apikey", api_key)]);
+            }
+            let status: EtherscanResponse<String> = request.send().await?.json().await?;
+            if status.result.starts_with("Pending") {
+                sh_status!("{}", status.result);
+                continue
+            }
+            if status.result.starts_with("Pass") || status.result.contains("already verified") {
+                sh_println!("Contract successfully verified.");
+                return Ok(())
+            }
+            eyre::bail!("Contract verification failed: `{}`", status.result)
+        }
+        eyre::bail!("Timed out waiting for the verification of GUID `{}`", guid)
+    }
+}
+
+/// Blockscout, which serves an etherscan compatible api that doesn't require an api key
+#[derive(Debug, Clone)]
+pub struct Blockscout(Etherscan);
+
+#[async_trait]
+impl Explorer for Blockscout {
+    fn kind(&self) -> ExplorerKind {
+        ExplorerKind::Blockscout
+    }
+
+    async fn contract_abi(&self, address: Address) -> eyre::Result<(String, Value)> {
+        self.0.contract_abi(address).await
+    }
+
+    async fn verify(&self, submission: &VerifySubmission, watch: bool) -> eyre::Result<()> {
+        self.0.submit(submission, watch).await
+    }
+}
+
+/// Sourcify, which verifies contracts by their metadata and serves the metadata of verified
+/// contracts from its repository
+#[derive(Debug, Clone)]
+pub struct Sourcify {
+    chain_id: u64,
+    server_url: String,
+    repo_url: String,
+    client: reqwest::Client,
+}
+
+#[async_trait]
+impl Explorer for Sourcify {
+    fn kind(&self) -> ExplorerKind {
+        ExplorerKind::Sourcify
+    }
+
+    async fn contract_abi(&self, address: Address) -> eyre::Result<(String, Value)> {
+        // contracts whose metadata hash doesn't match are partial matches
+        for repo in ["full_match", "partial_match"] {
+            let url = format!(
+                "{}/contracts/{}/{}/{}/metadata.json",
+                self.repo_url,
+                repo,
+                self.chain_id,
+                ethers::utils::to_checksum(&address, None)
+            );
+            let resp = self.client.get(&url).send().await?;
+            if !resp.status().is_success() {
+                continue
+            }
+            let mut metadata: Value = resp.json().await?;
+            let name = metadata["settings"]["compilationTarget"]
+                .as_object()
+                .and_then(|target| target.values().next())
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string();
+            return Ok((name, metadata["output"]["abi"].take()))
+        }
+        eyre::bail!("contract {:?} is not verified", address)
+    }
+
+    async fn verify(&self, submission: &VerifySubmission, _watch: bool) -> eyre::Result<()> {
+        let mut files = submission
+            .sources
+            .iter()
+            .map(|(path, content)| (path.clone(), json!(content)))
+            .collect::<serde_json::Map<_, _>>();
+        files.insert("metadata.json".to_string(), json!(submission.metadata));
+        let body = json!({
+            "address": format!("{:?}", submission.address),
+            "chain": submission.chain_id.to_string(),
+            "files": files,
+        });
+        let url = format!("{}/verify", self.server_url.trim_end_matches('/'));
+        let resp: Value = self.client.post(url).json(&body).send().await?.json().await?;
+        if let Some(error) = resp.get("error") {
+            eyre::bail!("Encountered an error verifying this contract: `{}`", error)
+        }
+        let status = resp["result"][0]["status"].as_str().unwrap_or_default();
+        match status {
+            "perfect" => sh_println!("Contract successfully verified, full match."),
+            "partial" => sh_println!("Contract successfully verified, partial match."),
+            _ => eyre::bail!("Unexpected sourcify response: `{}`", resp),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn selects_the_explorer_of_the_chain() {
+        let mut config = Config::default();
+        config.explorers.insert(
+            "100".to_string(),
+            foundry_config::ExplorerConfig {
+                kind: ExplorerKind::Blockscout,
+                url: Some("https://blockscout.com/xdai/mainnet/api".to_string()),
+                api_key: None,
+            },
+        );
+
+        let selected = explorer(&config, 100, None, None, None).unwrap();
+        assert_eq!(selected.kind(), ExplorerKind::Blockscout);
+        let selected = explorer(&config, 137, None, None, Some("key".to_string())).unwrap();
+        assert_eq!(selected.kind(), ExplorerKind::Etherscan);
+        let selected = explorer(&config, 100, Some(ExplorerKind::Sourcify), None, None).unwrap();
+        assert_eq!(selected.kind(), ExplorerKind::Sourcify);
+
+        // unknown chains need the url of their etherscan api
+        assert!(explorer(&config, 12345, None, None, None).is_err());
+        assert!(
+            explorer(&config, 12345, None, Some("https://example.com/api".into()), None).is_ok()
+        );
+    }
+}
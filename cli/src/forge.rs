@@ -1,12 +1,19 @@
 pub mod cmd;
+pub mod exit_code;
+pub mod explorer;
+pub mod hooks;
+pub mod ipfs;
 mod opts;
+pub mod shell;
 mod utils;
 
 use crate::cmd::Cmd;
 
-use ethers::solc::{Project, ProjectPathsConfig};
-use opts::forge::{Dependency, FullContractInfo, Opts, Subcommands};
-use std::process::Command;
+use ethers::solc::Project;
+use exit_code::ExitCode;
+use foundry_config::Config;
+use opts::forge::{Opts, Subcommands};
+use shell::OutputMode;
 
 use clap::{IntoApp, Parser};
 use clap_complete::generate;
@@ -14,8 +21,32 @@ use clap_complete::generate;
 fn main() -> eyre::Result<()> {
     color_eyre::install()?;
     utils::subscriber();
+    exit_code::install_panic_hook();
+
+    let opts = match Opts::try_parse() {
+        Ok(opts) => opts,
+        Err(err) => {
+            // the help and version are printed by clap too, but aren't errors
+            let _ = err.print();
+            if err.use_stderr() { ExitCode::ConfigError } else { ExitCode::Success }.exit()
+        }
+    };
+    opts.shell.install(if opts.json { OutputMode::Json } else { OutputMode::Human });
+    if let Err(err) = run(opts) {
+        exit_code::exit_with_error(err)
+    }
+    Ok(())
+}
 
-    let opts = Opts::parse();
+fn run(opts: Opts) -> eyre::Result<()> {
+    if let Some(ref profile) = opts.profile {
+        // the profile is read from the env when the config is loaded
+        std::env::set_var("FOUNDRY_PROFILE", profile);
+    }
+    if opts.allow_hooks {
+        // the hooks check the env, so they're also allowed by setting it
+        std::env::set_var(hooks::ALLOW_HOOKS_ENV, "true");
+    }
     match opts.sub {
         Subcommands::Test(cmd) => {
             let outcome = cmd.run()?;
@@ -24,35 +55,33 @@ fn main() -> eyre::Result<()> {
         Subcommands::Build(cmd) => {
             cmd.run()?;
         }
+        Subcommands::Bind(cmd) => {
+            cmd.run()?;
+        }
         Subcommands::Run(cmd) => {
             cmd.run()?;
         }
-        Subcommands::VerifyContract { contract, address, constructor_args } => {
-            let FullContractInfo { path, name } = contract;
-            let rt = tokio::runtime::Runtime::new().expect("could not start tokio rt");
-            rt.block_on(cmd::verify::run(path, name, address, constructor_args))?;
+        Subcommands::Replay(cmd) => {
+            cmd.run()?;
+        }
+        Subcommands::Node(cmd) => {
+            cmd.run()?;
+        }
+        Subcommands::VerifyContract(cmd) => {
+            cmd.run()?;
         }
         Subcommands::Create(cmd) => {
             cmd.run()?;
         }
         Subcommands::Update { lib } => {
-            let mut cmd = Command::new("git");
-
-            cmd.args(&["submodule", "update", "--remote", "--init", "--recursive"]);
-
-            // if a lib is specified, open it
-            if let Some(lib) = lib {
-                cmd.args(&["--", lib.display().to_string().as_str()]);
-            }
-
-            cmd.spawn()?.wait()?;
+            cmd::install::update(std::env::current_dir()?, lib.as_deref())?;
         }
         // TODO: Make it work with updates?
         Subcommands::Install(cmd) => {
             cmd.run()?;
         }
         Subcommands::Remove { dependencies } => {
-            remove(std::env::current_dir()?, dependencies)?;
+            cmd::install::remove(std::env::current_dir()?, dependencies)?;
         }
         Subcommands::Remappings(cmd) => {
             cmd.run()?;
@@ -65,9 +94,17 @@ fn main() -> eyre::Result<()> {
         }
         Subcommands::Clean { root } => {
             let root = root.unwrap_or_else(|| std::env::current_dir().unwrap());
-            let paths = ProjectPathsConfig::builder().root(&root).build()?;
-            let project = Project::builder().paths(paths).build()?;
+            // the `out` and the `cache_path` may be moved by the config
+            let config = Config::try_load_with_root(&root)?.sanitized();
+            let project = Project::builder().paths(config.project_paths()).build()?;
             project.cleanup()?;
+            let run_cache = cmd::run::RunCache::path(&project);
+            if run_cache.exists() {
+                std::fs::remove_file(run_cache)?;
+            }
+            if let Some(build_info) = config.build_info_path.filter(|dir| dir.exists()) {
+                std::fs::remove_dir_all(build_info)?;
+            }
         }
         Subcommands::Snapshot(cmd) => {
             cmd.run()?;
@@ -78,44 +115,37 @@ fn main() -> eyre::Result<()> {
         Subcommands::Flatten(cmd) => {
             cmd.run()?;
         }
+        Subcommands::Inspect(cmd) => {
+            cmd.run()?;
+        }
+        Subcommands::UpgradeCheck(cmd) => {
+            cmd.run()?;
+        }
+        Subcommands::Lint(cmd) => {
+            cmd.run()?;
+        }
+        Subcommands::Geiger(cmd) => {
+            cmd.run()?;
+        }
+        Subcommands::Mutate(cmd) => {
+            cmd.run()?;
+        }
+        Subcommands::Cache(cmd) => {
+            cmd.run()?;
+        }
+        Subcommands::Solc(cmd) => {
+            cmd.run()?;
+        }
+        Subcommands::BroadcastLog(cmd) => {
+            cmd.run()?;
+        }
+        Subcommands::UploadSelectors(cmd) => {
+            cmd.run()?;
+        }
+        Subcommands::Generate(cmd) => {
+            cmd.run()?;
+        }
     }
 
     Ok(())
 }
-
-fn remove(root: impl AsRef<std::path::Path>, dependencies: Vec<Dependency>) -> eyre::Result<()> {
-    let libs = std::path::Path::new("lib");
-    let git_mod_libs = std::path::Path::new(".git/modules/lib");
-
-    dependencies.iter().try_for_each(|dep| -> eyre::Result<_> {
-        let path = libs.join(&dep.name);
-        let git_mod_path = git_mod_libs.join(&dep.name);
-        println!("Removing {} in {:?}, (url: {}, tag: {:?})", dep.name, path, dep.url, dep.tag);
-
-        // remove submodule entry from .git/config
-        Command::new("git")
-            .args(&["submodule", "deinit", "-f", &path.display().to_string()])
-            .current_dir(&root)
-            .spawn()?
-            .wait()?;
-
-        // remove the submodule repository from .git/modules directory
-        Command::new("rm")
-            .args(&["-rf", &git_mod_path.display().to_string()])
-            .current_dir(&root)
-            .spawn()?
-            .wait()?;
-
-        // remove the leftover submodule directory
-        Command::new("git")
-            .args(&["rm", "-f", &path.display().to_string()])
-            .current_dir(&root)
-            .spawn()?
-            .wait()?;
-
-        // tell git to discard the removal of the submodule
-        Command::new("git").args(&["checkout", "--", "."]).current_dir(&root).spawn()?.wait()?;
-
-        Ok(())
-    })
-}
@@ -0,0 +1,135 @@
+//! Running the `hooks` of the config at points of the lifecycle of commands
+
+use crate::shell::{self, sh_status, sh_warn};
+use eyre::WrapErr;
+use foundry_config::{Config, Hooks};
+use std::process::{Command, Stdio};
+
+/// A point of the lifecycle of a command a hook runs at, see [`foundry_config::Hooks`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hook {
+    PreBuild,
+    PostBuild,
+    PreTest,
+    PostTest,
+    PreBroadcast,
+    PostBroadcast,
+}
+
+/// The env var that allows the hooks of the config to run, like `--allow-hooks`
+pub const ALLOW_HOOKS_ENV: &str = "FOUNDRY_ALLOW_HOOKS";
+
+impl Hook {
+    /// All hooks, in the order of their names in the config
+    pub const ALL: [Hook; 6] = [
+        Hook::PreBuild,
+        Hook::PostBuild,
+        Hook::PreTest,
+        Hook::PostTest,
+        Hook::PreBroadcast,
+        Hook::PostBroadcast,
+    ];
+
+    /// The name of the hook in the config
+    pub fn name(self) -> &'static str {
+        match self {
+            Hook::PreBuild => "pre_build",
+            Hook::PostBuild => "post_build",
+            Hook::PreTest => "pre_test",
+            Hook::PostTest => "post_test",
+            Hook::PreBroadcast => "pre_broadcast",
+            Hook::PostBroadcast => "post_broadcast",
+        }
+    }
+
+    /// The command of the hook, if it's set
+    pub fn command(self, hooks: &Hooks) -> Option<&str> {
+        match self {
+            Hook::PreBuild => hooks.pre_build.as_deref(),
+            Hook::PostBuild => hooks.post_build.as_deref(),
+            Hook::PreTest => hooks.pre_test.as_deref(),
+            Hook::PostTest => hooks.post_test.as_deref(),
+            Hook::PreBroadcast => hooks.pre_broadcast.as_deref(),
+            Hook::PostBroadcast => hooks.post_broadcast.as_deref(),
+        }
+    }
+}
+
+/// Runs the `pre_` hook, failing if the command fails
+pub fn run_pre(config: &Config, hook: Hook) -> eyre::Result<()> {
+    run(config, hook, None)
+}
+
+/// Runs the `post_` hook with the outcome of the command, a failing hook is only reported
+pub fn run_post(config: &Config, hook: Hook, success: bool) {
+    if let Err(err) = run(config, hook, Some(success)) {
+        sh_warn!("{}", err);
+    }
+}
+
+/// Whether the hooks may run, the config of a cloned project could run any command otherwise
+pub fn allowed() -> bool {
+    std::env::var(ALLOW_HOOKS_ENV).map_or(false, |value| matches!(value.as_str(), "1" | "true"))
+}
+
+fn run(config: &Config, hook: Hook, success: Option<bool>) -> eyre::Result<()> {
+    let command = match hook.command(&config.hooks) {
+        Some(command) => command,
+        None => return Ok(()),
+    };
+    if !allowed() {
+        sh_warn!(
+            "skipping the {} hook `{}`, hooks only run with `--allow-hooks` or `{}=true`",
+            hook.name(),
+            command,
+            ALLOW_HOOKS_ENV
+        );
+        return Ok(())
+    }
+    sh_status!("running the {} hook: {}", hook.name(), command);
+
+    let root = &config.__root.0;
+    let mut cmd = shell_command(command);
+    cmd.current_dir(root)
+        .env("FOUNDRY_HOOK", hook.name())
+        .env("FOUNDRY_PROFILE", Config::selected_profile().as_str().as_str());
+    if let Some(commit) = git_commit(root) {
+        cmd.env("FOUNDRY_COMMIT", commit);
+    }
+    if let Some(success) = success {
+        cmd.env("FOUNDRY_SUCCESS", success.to_string());
+    }
+
+    // json output is only the results, so the hook's output goes to stderr
+    let status = if shell::is_json() {
+        let output = cmd.stderr(Stdio::inherit()).output();
+        let output = output.wrap_err_with(|| format!("failed to run the {} hook", hook.name()))?;
+        eprint!("{}", String::from_utf8_lossy(&output.stdout));
+        output.status
+    } else {
+        cmd.status().wrap_err_with(|| format!("failed to run the {} hook", hook.name()))?
+    };
+    if !status.success() {
+        eyre::bail!("the {} hook `{}` failed with {}", hook.name(), command, status)
+    }
+    Ok(())
+}
+
+/// Returns the command that runs the line in the platform's shell
+fn shell_command(command: &str) -> Command {
+    if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.args(["/C", command]);
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", command]);
+        cmd
+    }
+}
+
+/// Returns the commit the git repository at the root is at
+fn git_commit(root: &std::path::Path) -> Option<String> {
+    let output = Command::new("git").args(["rev-parse", "HEAD"]).current_dir(root).output().ok()?;
+    output.status.success().then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
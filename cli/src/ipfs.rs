@@ -0,0 +1,184 @@
+//! Extracting the metadata of contracts and pinning it to IPFS
+
+use crate::{
+    cmd::inspect,
+    opts::forge::ContractInfo,
+    shell::{sh_println, sh_status, sh_warn},
+};
+use eyre::WrapErr;
+use foundry_config::Config;
+use foundry_utils::metadata::{bytecode_metadata_hash, SolcMetadata};
+use serde::Deserialize;
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+};
+
+/// The url of the http api of a local IPFS node
+pub const DEFAULT_IPFS_API_URL: &str = "http://127.0.0.1:5001";
+
+/// The metadata solc emitted for a contract
+#[derive(Debug, Clone)]
+pub struct ContractMetadata {
+    /// The source file of the contract
+    pub file: String,
+    pub name: String,
+    /// The metadata json, exactly as emitted by solc
+    pub metadata: String,
+    /// The IPFS hash of the metadata solc appended to the deployed bytecode
+    pub bytecode_hash: Option<String>,
+}
+
+impl ContractMetadata {
+    /// Returns the metadata of all compiled contracts, or only of the `contract` if set
+    pub fn collect(
+        contracts: &BTreeMap<String, BTreeMap<String, Value>>,
+        contract: Option<&ContractInfo>,
+    ) -> Vec<Self> {
+        contracts
+            .iter()
+            .filter(|(file, _)| {
+                contract.and_then(|c| c.path.as_ref()).map_or(true, |path| file.ends_with(path))
+            })
+            .flat_map(|(file, contracts)| contracts.iter().map(move |(name, c)| (file, name, c)))
+            .filter(|(_, name, _)| contract.map_or(true, |c| &c.name == *name))
+            .filter_map(|(file, name, contract)| {
+                let metadata = SolcMetadata::from_output(&contract["metadata"]).ok()??.raw;
+                let bytecode_hash = contract["evm"]["deployedBytecode"]["object"]
+                    .as_str()
+                    .and_then(|code| hex::decode(code.trim_start_matches("0x")).ok())
+                    .and_then(|code| bytecode_metadata_hash(&code));
+                Some(Self { file: file.clone(), name: name.clone(), metadata, bytecode_hash })
+            })
+            .collect()
+    }
+
+    /// The sources listed in the metadata
+    pub fn sources(&self) -> Vec<String> {
+        serde_json::from_str::<Value>(&self.metadata)
+            .ok()
+            .and_then(|metadata| {
+                metadata["sources"].as_object().map(|s| s.keys().cloned().collect())
+            })
+            .unwrap_or_default()
+    }
+
+    /// Writes the metadata next to the contract's artifact, to `<out>/<file>/<name>.metadata.json`
+    pub fn write(&self, out: &Path) -> eyre::Result<()> {
+        let file_name = Path::new(&self.file).file_name().unwrap_or_default();
+        let dir = out.join(file_name);
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(dir.join(format!("{}.metadata.json", self.name)), &self.metadata)?;
+        Ok(())
+    }
+}
+
+/// A response of the `add` endpoint of the IPFS http api
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct AddResponse {
+    hash: String,
+}
+
+/// A client of the http api of an IPFS node or pinning service
+#[derive(Debug, Clone)]
+pub struct IpfsClient {
+    api_url: String,
+    client: reqwest::Client,
+}
+
+impl IpfsClient {
+    pub fn new(api_url: impl Into<String>) -> Self {
+        Self { api_url: api_url.into(), client: reqwest::Client::new() }
+    }
+
+    /// Adds and pins the file, returning its hash
+    ///
+    /// The file is added as a CIDv0 with the default chunker, which is how solc hashes the
+    /// metadata and the sources.
+    pub async fn add(&self, name: &str, content: &[u8]) -> eyre::Result<String> {
+        const BOUNDARY: &str = "foundry-ipfs-boundary";
+        let mut body = format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{}\"\r\nContent-Type: application/octet-stream\r\n\r\n",
+            BOUNDARY, name
+        )
+        .into_bytes();
+        body.extend_from_slice(content);
+        body.extend_from_slice(format!("\r\n--{}--\r\n", BOUNDARY).as_bytes());
+
+        let url = format!("{}/api/v0/add", self.api_url.trim_end_matches('/'));
+        let resp = self
+            .client
+            .post(url)
+            .query(&[("pin", "true"), ("cid-version", "0")])
+            .header("Content-Type", format!("multipart/form-data; boundary={}", BOUNDARY))
+            .body(body)
+            .send()
+            .await
+            .wrap_err_with(|| format!("could not reach the IPFS api at {}", self.api_url))?;
+        if !resp.status().is_success() {
+            eyre::bail!("failed to add `{}` to IPFS: {}", name, resp.text().await?)
+        }
+        let resp: AddResponse = resp.json().await?;
+        Ok(resp.hash)
+    }
+}
+
+/// Compiles the project, writes the metadata of all contracts or only of the `contract` next to
+/// their artifacts and pins it and the sources it lists to the IPFS api of the config if `pin` is
+/// set
+pub fn publish_metadata(
+    config: &Config,
+    contract: Option<&ContractInfo>,
+    pin: bool,
+) -> eyre::Result<()> {
+    let project = inspect::project_with_output_selection(config)?;
+    let contracts = inspect::compile(&project)?;
+    let metadata = ContractMetadata::collect(&contracts, contract);
+    let out = config.project_paths().artifacts;
+    for contract in &metadata {
+        contract.write(&out)?;
+    }
+    sh_status!("wrote the metadata of {} contracts to {}", metadata.len(), out.display());
+    if !pin {
+        return Ok(())
+    }
+    if config.offline {
+        eyre::bail!("the metadata can't be pinned to IPFS offline")
+    }
+
+    let client = IpfsClient::new(config.ipfs_api_url.as_deref().unwrap_or(DEFAULT_IPFS_API_URL));
+    let rt = tokio::runtime::Runtime::new().expect("could not start tokio rt");
+    rt.block_on(pin_metadata(&client, &project.paths.root, &metadata))
+}
+
+/// Pins the sources and the metadata of the contracts, printing the hash of every metadata
+async fn pin_metadata(
+    client: &IpfsClient,
+    root: &Path,
+    metadata: &[ContractMetadata],
+) -> eyre::Result<()> {
+    // sourcify fetches the sources by the hashes in the metadata
+    let sources = metadata.iter().flat_map(ContractMetadata::sources).collect::<BTreeSet<_>>();
+    for source in &sources {
+        let content = std::fs::read(root.join(source))
+            .wrap_err_with(|| format!("could not read source `{}`", source))?;
+        client.add(source, &content).await?;
+    }
+    sh_status!("pinned {} sources", sources.len());
+
+    for contract in metadata {
+        let hash = client.add("metadata.json", contract.metadata.as_bytes()).await?;
+        sh_println!("{}:{} {}", contract.file, contract.name, hash);
+        match contract.bytecode_hash {
+            Some(ref bytecode_hash) if bytecode_hash != &hash => sh_warn!(
+                "the metadata hash in the bytecode of {} is {}, sourcify can't match it perfectly",
+                contract.name,
+                bytecode_hash
+            ),
+            _ => {}
+        }
+    }
+    Ok(())
+}
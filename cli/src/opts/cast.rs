@@ -1,9 +1,17 @@
 use std::{path::PathBuf, str::FromStr};
 
-use clap::{Parser, Subcommand};
-use ethers::types::{Address, BlockId, BlockNumber, NameOrAddress, H256, U256};
+use clap::{Parser, Subcommand, ValueHint};
+use ethers::types::{
+    transaction::eip2930::AccessList, Address, BlockId, BlockNumber, NameOrAddress, H256, U256,
+};
 
 use super::{ClapChain, EthereumOpts, Wallet};
+use crate::{
+    cmd::replay::ReplayArgs,
+    shell::ShellOpts,
+    utils::{parse_name_or_address, parse_slot, resolve_rpc_url},
+};
+use foundry_utils::parse_amount;
 
 #[derive(Debug, Subcommand)]
 #[clap(about = "Perform Ethereum RPC calls from the comfort of your command line.")]
@@ -21,7 +29,7 @@ pub enum Subcommands {
     #[clap(name = "--from-utf8")]
     #[clap(about = "convert text data into hexdata")]
     FromUtf8 { text: Option<String> },
-    #[clap(name = "--to-hex")]
+    #[clap(name = "--to-hex", alias = "to-hex")]
     #[clap(about = "convert a decimal number into hex")]
     ToHex { decimal: Option<String> },
     #[clap(name = "--to-hexdata")]
@@ -35,16 +43,24 @@ pub enum Subcommands {
     "#)]
     ToHexdata { input: Option<String> },
     #[clap(aliases = &["--to-checksum"])] // Compatibility with dapptools' cast
-    #[clap(name = "--to-checksum-address")]
+    #[clap(name = "--to-checksum-address", alias = "to-checksum-address")]
     #[clap(about = "convert an address to a checksummed format (EIP-55)")]
     ToCheckSumAddress { address: Option<Address> },
     #[clap(name = "--to-ascii")]
     #[clap(about = "convert hex data to text data")]
     ToAscii { hexdata: Option<String> },
-    #[clap(name = "--to-bytes32")]
+    #[clap(name = "--to-bytes32", alias = "to-bytes32")]
     #[clap(about = "left-pads a hex bytes string to 32 bytes)")]
     ToBytes32 { bytes: Option<String> },
-    #[clap(name = "--to-dec")]
+    #[clap(name = "--to-rlp", alias = "to-rlp")]
+    #[clap(
+        about = "RLP encodes a JSON array of nested arrays of hex strings, like `[\"0xaa\", [\"0x\"]]`"
+    )]
+    ToRlp { value: Option<String> },
+    #[clap(name = "--from-rlp", alias = "from-rlp")]
+    #[clap(about = "decodes RLP into a JSON array of nested arrays of hex strings")]
+    FromRlp { rlp: Option<String> },
+    #[clap(name = "--to-dec", alias = "to-dec")]
     #[clap(about = "convert hex value into decimal number")]
     ToDec { hexvalue: Option<String> },
     #[clap(name = "--to-fix")]
@@ -65,11 +81,15 @@ pub enum Subcommands {
     "#
     )]
     ToUnit { value: Option<String>, unit: Option<String> },
-    #[clap(name = "--to-wei")]
-    #[clap(about = "convert an ETH amount into wei. Consider using --to-unit.")]
+    #[clap(name = "--to-wei", alias = "to-wei")]
+    #[clap(
+        about = "convert an ETH amount into wei (default unit: eth). Consider using --to-unit."
+    )]
     ToWei { value: Option<String>, unit: Option<String> },
-    #[clap(name = "--from-wei")]
-    #[clap(about = "convert wei into an ETH amount. Consider using --to-unit.")]
+    #[clap(name = "--from-wei", alias = "from-wei")]
+    #[clap(
+        about = "convert wei into an ETH amount (default unit: eth). Consider using --to-unit."
+    )]
     FromWei { value: Option<String>, unit: Option<String> },
     #[clap(name = "block")]
     #[clap(
@@ -83,13 +103,13 @@ pub enum Subcommands {
         field: Option<String>,
         #[clap(long = "json", short = 'j')]
         to_json: bool,
-        #[clap(long, env = "ETH_RPC_URL")]
+        #[clap(long, env = "ETH_RPC_URL", parse(try_from_str = resolve_rpc_url))]
         rpc_url: String,
     },
     #[clap(name = "block-number")]
     #[clap(about = "Prints latest block number")]
     BlockNumber {
-        #[clap(long, env = "ETH_RPC_URL")]
+        #[clap(long, env = "ETH_RPC_URL", parse(try_from_str = resolve_rpc_url))]
         rpc_url: String,
     },
     #[clap(name = "call")]
@@ -116,13 +136,13 @@ pub enum Subcommands {
     #[clap(name = "chain")]
     #[clap(about = "Prints symbolic name of current blockchain by checking genesis hash")]
     Chain {
-        #[clap(long, env = "ETH_RPC_URL")]
+        #[clap(long, env = "ETH_RPC_URL", parse(try_from_str = resolve_rpc_url))]
         rpc_url: String,
     },
     #[clap(name = "chain-id")]
     #[clap(about = "returns ethereum chain id")]
     ChainId {
-        #[clap(long, env = "ETH_RPC_URL")]
+        #[clap(long, env = "ETH_RPC_URL", parse(try_from_str = resolve_rpc_url))]
         rpc_url: String,
     },
     #[clap(name = "namehash")]
@@ -135,9 +155,24 @@ pub enum Subcommands {
         field: Option<String>,
         #[clap(long = "json", short = 'j')]
         to_json: bool,
-        #[clap(long, env = "ETH_RPC_URL")]
+        #[clap(long, env = "ETH_RPC_URL", parse(try_from_str = resolve_rpc_url))]
         rpc_url: String,
     },
+    #[clap(name = "decode-tx")]
+    #[clap(
+        about = "Decode a signed legacy, EIP-2930 or EIP-1559 raw transaction and recover its sender"
+    )]
+    DecodeTx {
+        #[clap(help = "the signed raw transaction, read from stdin if not set")]
+        raw_tx: Option<String>,
+        #[clap(
+            long,
+            help = "the function signature to decode the calldata with, like `transfer(address,uint256)`"
+        )]
+        sig: Option<String>,
+        #[clap(long = "json", short = 'j')]
+        to_json: bool,
+    },
     #[clap(name = "send")]
     #[clap(about = "Publish a transaction signed by <from> to call <to> with <data>")]
     SendTx {
@@ -149,20 +184,67 @@ pub enum Subcommands {
         args: Vec<String>,
         #[clap(long, help = "gas quantity for the transaction")]
         gas: Option<U256>,
-        #[clap(long, help = "ether value (in wei) for the transaction")]
+        #[clap(
+            long,
+            help = "ether value for the transaction, in wei or with a unit like `1ether`",
+            parse(try_from_str = parse_amount)
+        )]
         value: Option<U256>,
         #[clap(long, help = "nonce for the transaction")]
         nonce: Option<U256>,
+        #[clap(
+            long,
+            help = "EIP-2930 access list of the transaction as json, or the path to a json file, e.g. the output of `forge run --access-list`",
+            parse(try_from_str = parse_access_list)
+        )]
+        access_list: Option<AccessList>,
         #[clap(long, env = "CAST_ASYNC")]
         cast_async: bool,
         #[clap(flatten)]
         eth: EthereumOpts,
     },
+    #[clap(name = "mktx")]
+    #[clap(
+        about = "Build a transaction from <from> to call <to> with every field populated, to sign it offline with `cast wallet sign-tx` and publish it with `cast publish`"
+    )]
+    MakeTx {
+        #[clap(help = "the address you want to transact with", parse(try_from_str = parse_name_or_address))]
+        to: NameOrAddress,
+        #[clap(help = "the function signature or name you want to call")]
+        sig: String,
+        #[clap(help = "the list of arguments you want to call the function with")]
+        args: Vec<String>,
+        #[clap(
+            long,
+            help = "gas quantity for the transaction, estimated by simulating it if not set"
+        )]
+        gas: Option<U256>,
+        #[clap(
+            long,
+            help = "ether value for the transaction, in wei or with a unit like `1ether`",
+            parse(try_from_str = parse_amount)
+        )]
+        value: Option<U256>,
+        #[clap(long, help = "nonce for the transaction, the pending nonce of <from> if not set")]
+        nonce: Option<U256>,
+        #[clap(
+            long,
+            help = "EIP-2930 access list of the transaction as json, or the path to a json file, e.g. the output of `forge run --access-list`",
+            parse(try_from_str = parse_access_list)
+        )]
+        access_list: Option<AccessList>,
+        #[clap(long, help = "print the RLP encoding of the unsigned transaction instead of JSON")]
+        rlp: bool,
+        #[clap(flatten)]
+        eth: EthereumOpts,
+    },
     #[clap(name = "publish")]
     #[clap(about = "Publish a raw transaction to the network")]
     PublishTx {
-        #[clap(help = "the raw transaction you want to publish")]
-        raw_tx: String,
+        #[clap(
+            help = "the raw transaction you want to publish, like the output of `cast wallet sign-tx`, read from stdin if not set"
+        )]
+        raw_tx: Option<String>,
         #[clap(long, env = "CAST_ASYNC")]
         cast_async: bool,
         #[clap(flatten)]
@@ -177,12 +259,16 @@ pub enum Subcommands {
         sig: String,
         #[clap(help = "the list of arguments you want to call the function with")]
         args: Vec<String>,
-        #[clap(long, help = "value for tx estimate (in wei)")]
+        #[clap(
+            long,
+            help = "value for tx estimate, in wei or with a unit like `1ether`",
+            parse(try_from_str = parse_amount)
+        )]
         value: Option<U256>,
         #[clap(flatten)]
         eth: EthereumOpts,
     },
-    #[clap(name = "--calldata-decode")]
+    #[clap(name = "--calldata-decode", alias = "calldata-decode")]
     #[clap(about = "Decode ABI-encoded hex input data. Use `--abi-decode` to decode output data")]
     CalldataDecode {
         #[clap(
@@ -192,7 +278,19 @@ pub enum Subcommands {
         #[clap(help = "the encoded calladata, in hex format")]
         calldata: String,
     },
-    #[clap(name = "--abi-decode")]
+    #[clap(name = "decode-calldata")]
+    #[clap(
+        about = "Decodes calldata into an indented tree, unwrapping the calls of multicalls, Gnosis Safe transactions and Uniswap universal router commands recursively"
+    )]
+    DecodeCalldata {
+        #[clap(
+            help = "the function signature, like `transfer(address,uint256)`, or the path to an ABI, either a JSON ABI or a forge artifact"
+        )]
+        sig: String,
+        #[clap(help = "the calldata, in hex format")]
+        calldata: String,
+    },
+    #[clap(name = "--abi-decode", alias = "abi-decode")]
     #[clap(
         about = "Decode ABI-encoded hex output data. Pass --input to decode as input, or use `--calldata-decode`"
     )]
@@ -222,6 +320,28 @@ pub enum Subcommands {
         #[clap(help = "the function selector")]
         selector: String,
     },
+    #[clap(name = "4byte-event")]
+    #[clap(about = "Fetches event signatures given the topic from 4byte.directory")]
+    FourByteEvent {
+        #[clap(help = "the topic of the event, read from stdin if not set")]
+        topic: Option<String>,
+    },
+    #[clap(name = "sig")]
+    #[clap(
+        about = "Computes the selector of a function signature, like `transfer(address,uint256)`"
+    )]
+    Sig {
+        #[clap(help = "the function signature, read from stdin if not set")]
+        sig: Option<String>,
+    },
+    #[clap(name = "sig-event")]
+    #[clap(
+        about = "Computes the topic of an event signature, like `Transfer(address indexed from, address indexed to, uint256 value)`"
+    )]
+    SigEvent {
+        #[clap(help = "the event signature, read from stdin if not set")]
+        sig: Option<String>,
+    },
     #[clap(name = "4byte-decode")]
     #[clap(about = "Decodes transaction calldata by fetching the signature using 4byte.directory")]
     FourByteDecode {
@@ -230,12 +350,98 @@ pub enum Subcommands {
         #[clap(long, help = "the 4byte selector id to use, can also be earliest/latest")]
         id: Option<String>,
     },
+    #[clap(name = "receipt")]
+    #[clap(
+        about = "Show the receipt of the transaction <tx-hash> once it's confirmed, decoding its logs with the given ABI or the Etherscan ABIs of the emitting contracts and the revert reason of a failed transaction"
+    )]
+    Receipt {
+        hash: String,
+        field: Option<String>,
+        #[clap(long = "json", short = 'j')]
+        to_json: bool,
+        #[clap(
+            long,
+            help = "wait until the transaction's block has this many confirmations, counting the block itself",
+            default_value = "1"
+        )]
+        confirmations: usize,
+        #[clap(
+            long,
+            help = "the seconds to wait for the transaction to be included and confirmed, waits indefinitely if not set"
+        )]
+        timeout: Option<u64>,
+        #[clap(
+            long,
+            help = "path to the ABI used to decode the logs, either a JSON ABI or a forge artifact",
+            value_hint = ValueHint::FilePath
+        )]
+        abi: Option<PathBuf>,
+        #[clap(
+            long,
+            help = "decode the logs with the ABIs of the emitting contracts on Etherscan"
+        )]
+        etherscan: bool,
+        #[clap(flatten)]
+        chain: ClapChain,
+        #[clap(long, env = "ETHERSCAN_API_KEY")]
+        etherscan_api_key: Option<String>,
+        #[clap(long, env = "ETH_RPC_URL", parse(try_from_str = resolve_rpc_url))]
+        rpc_url: String,
+    },
+    #[clap(name = "logs")]
+    #[clap(
+        about = "Prints the logs matching the filter, decoded with the event signature or the given ABI"
+    )]
+    Logs {
+        #[clap(
+            help = "the event signature like `Transfer(address indexed from,address indexed to,uint256 value)`, or the hash of an event's topic0"
+        )]
+        event: Option<String>,
+        #[clap(
+            help = "the indexed topics to filter by, as hex values, addresses or numbers",
+            parse(try_from_str = parse_slot)
+        )]
+        topics: Vec<H256>,
+        #[clap(long, help = "the contract emitting the logs", parse(try_from_str = parse_name_or_address))]
+        address: Option<NameOrAddress>,
+        #[clap(long, help = "the first block to query", parse(try_from_str = parse_block_number))]
+        from_block: Option<BlockNumber>,
+        #[clap(long, help = "the last block to query", parse(try_from_str = parse_block_number))]
+        to_block: Option<BlockNumber>,
+        #[clap(
+            long,
+            help = "path to the ABI used to decode the logs, either a JSON ABI or a forge artifact",
+            value_hint = ValueHint::FilePath
+        )]
+        abi: Option<PathBuf>,
+        #[clap(
+            long,
+            help = "keep printing new logs, subscribes over websockets for ws:// urls and polls otherwise"
+        )]
+        follow: bool,
+        #[clap(long = "json", short = 'j')]
+        to_json: bool,
+        #[clap(long, env = "ETH_RPC_URL", parse(try_from_str = resolve_rpc_url))]
+        rpc_url: String,
+    },
+    #[clap(name = "pending")]
+    #[clap(
+        about = "Watches for pending transactions, subscribes over websockets for ws:// urls and polls otherwise"
+    )]
+    Pending {
+        #[clap(long, help = "only print transactions sent to these addresses")]
+        to: Vec<Address>,
+        #[clap(long = "json", short = 'j', help = "print the full transactions as JSON")]
+        to_json: bool,
+        #[clap(long, env = "ETH_RPC_URL", parse(try_from_str = resolve_rpc_url))]
+        rpc_url: String,
+    },
     #[clap(name = "age")]
     #[clap(about = "Prints the timestamp of a block")]
     Age {
         #[clap(global = true, help = "the block you want to query, can also be earliest/latest/pending", parse(try_from_str = parse_block_id))]
         block: Option<BlockId>,
-        #[clap(short, long, env = "ETH_RPC_URL")]
+        #[clap(short, long, env = "ETH_RPC_URL", parse(try_from_str = resolve_rpc_url))]
         rpc_url: String,
     },
     #[clap(name = "balance")]
@@ -245,7 +451,7 @@ pub enum Subcommands {
         block: Option<BlockId>,
         #[clap(help = "the account you want to query", parse(try_from_str = parse_name_or_address))]
         who: NameOrAddress,
-        #[clap(short, long, env = "ETH_RPC_URL")]
+        #[clap(short, long, env = "ETH_RPC_URL", parse(try_from_str = resolve_rpc_url))]
         rpc_url: String,
     },
     #[clap(name = "basefee")]
@@ -253,7 +459,7 @@ pub enum Subcommands {
     BaseFee {
         #[clap(global = true, help = "the block you want to query, can also be earliest/latest/pending", parse(try_from_str = parse_block_id))]
         block: Option<BlockId>,
-        #[clap(short, long, env = "ETH_RPC_URL")]
+        #[clap(short, long, env = "ETH_RPC_URL", parse(try_from_str = resolve_rpc_url))]
         rpc_url: String,
     },
     #[clap(name = "code")]
@@ -263,24 +469,24 @@ pub enum Subcommands {
         block: Option<BlockId>,
         #[clap(help = "the address you want to query", parse(try_from_str = parse_name_or_address))]
         who: NameOrAddress,
-        #[clap(short, long, env = "ETH_RPC_URL")]
+        #[clap(short, long, env = "ETH_RPC_URL", parse(try_from_str = resolve_rpc_url))]
         rpc_url: String,
     },
     #[clap(name = "gas-price")]
     #[clap(about = "Prints current gas price of target chain")]
     GasPrice {
-        #[clap(short, long, env = "ETH_RPC_URL")]
+        #[clap(short, long, env = "ETH_RPC_URL", parse(try_from_str = resolve_rpc_url))]
         rpc_url: String,
     },
     #[clap(name = "keccak")]
-    #[clap(about = "Keccak-256 hashes arbitrary data")]
-    Keccak { data: String },
+    #[clap(about = "Keccak-256 hashes arbitrary data, 0x prefixed hex data is hashed as bytes")]
+    Keccak { data: Option<String> },
     #[clap(name = "resolve-name")]
     #[clap(about = "Returns the address the provided ENS name resolves to")]
     ResolveName {
         #[clap(help = "the account you want to resolve")]
         who: Option<String>,
-        #[clap(short, long, env = "ETH_RPC_URL")]
+        #[clap(short, long, env = "ETH_RPC_URL", parse(try_from_str = resolve_rpc_url))]
         rpc_url: String,
         #[clap(long, short, help = "do a forward resolution to ensure the ENS name is correct")]
         verify: bool,
@@ -290,18 +496,44 @@ pub enum Subcommands {
     LookupAddress {
         #[clap(help = "the account you want to resolve")]
         who: Option<Address>,
-        #[clap(short, long, env = "ETH_RPC_URL")]
+        #[clap(short, long, env = "ETH_RPC_URL", parse(try_from_str = resolve_rpc_url))]
         rpc_url: String,
         #[clap(long, short, help = "do a forward resolution to ensure the address is correct")]
         verify: bool,
     },
-    #[clap(name = "storage", about = "Show the raw value of a contract's storage slot")]
+    #[clap(
+        name = "storage",
+        about = "Show the raw value of a contract's storage slot, or the decoded values of its variables with its storage layout"
+    )]
     Storage {
         #[clap(help = "the contract address", parse(try_from_str = parse_name_or_address))]
         address: NameOrAddress,
-        #[clap(help = "the storage slot number (hex or number)", parse(try_from_str = parse_slot))]
-        slot: H256,
-        #[clap(short, long, env = "ETH_RPC_URL")]
+        #[clap(
+            help = "the storage slot number (hex or number)",
+            parse(try_from_str = parse_slot),
+            required_unless_present = "layout"
+        )]
+        slot: Option<H256>,
+        #[clap(
+            long,
+            help = "the contract's artifact built with the `storageLayout` extra output, or its storage layout JSON, to decode all its variables",
+            conflicts_with = "slot"
+        )]
+        layout: Option<PathBuf>,
+        #[clap(
+            long = "key",
+            help = "a mapping key to look up in the mappings whose key type it can be parsed as, can be repeated",
+            requires = "layout"
+        )]
+        keys: Vec<String>,
+        #[clap(
+            long,
+            help = "show the values that changed between this block and the queried block",
+            parse(try_from_str = parse_block_id),
+            requires = "layout"
+        )]
+        diff: Option<BlockId>,
+        #[clap(short, long, env = "ETH_RPC_URL", parse(try_from_str = resolve_rpc_url))]
         rpc_url: String,
         #[clap(
             long,
@@ -318,9 +550,31 @@ pub enum Subcommands {
         block: Option<BlockId>,
         #[clap(help = "the address you want to query", parse(try_from_str = parse_name_or_address))]
         who: NameOrAddress,
-        #[clap(short, long, env = "ETH_RPC_URL")]
+        #[clap(short, long, env = "ETH_RPC_URL", parse(try_from_str = resolve_rpc_url))]
         rpc_url: String,
     },
+    #[clap(name = "compute-address")]
+    #[clap(
+        about = "Computes the address of a contract created by <address> with CREATE at its nonce, or with CREATE2 if a salt is provided"
+    )]
+    ComputeAddress {
+        #[clap(
+            help = "the deployer, defaults to the canonical CREATE2 deployer for CREATE2 addresses"
+        )]
+        address: Option<Address>,
+        #[clap(long, help = "the nonce of the deployer, defaults to its current nonce")]
+        nonce: Option<U256>,
+        #[clap(long, help = "the salt of a CREATE2 deployment, a number or hex value", parse(try_from_str = parse_slot))]
+        salt: Option<H256>,
+        #[clap(
+            long,
+            help = "the init code of a CREATE2 deployment, the creation code followed by the constructor arguments",
+            requires = "salt"
+        )]
+        init_code: Option<String>,
+        #[clap(short, long, env = "ETH_RPC_URL", parse(try_from_str = resolve_rpc_url))]
+        rpc_url: Option<String>,
+    },
     #[clap(name = "etherscan-source")]
     #[clap(about = "Prints the source code of a contract from Etherscan")]
     EtherscanSource {
@@ -331,18 +585,48 @@ pub enum Subcommands {
         #[clap(long, env = "ETHERSCAN_API_KEY")]
         etherscan_api_key: String,
     },
+    #[clap(name = "run")]
+    #[clap(
+        about = "Runs a mined transaction locally on top of a fork of its block and prints its trace"
+    )]
+    Run(ReplayArgs),
+    #[clap(name = "eip712")]
+    #[clap(
+        about = "Hashes EIP-712 typed data and prints the domain separator, struct hash and digest, optionally signing the digest"
+    )]
+    Eip712 {
+        #[clap(help = "the typed data as JSON, or the path to a JSON file")]
+        data: Option<String>,
+        #[clap(long, help = "sign the digest with the wallet")]
+        sign: bool,
+        #[clap(flatten)]
+        wallet: Wallet,
+    },
     #[clap(name = "wallet", about = "Set of wallet management utilities")]
     Wallet {
         #[clap(subcommand)]
         command: WalletSubcommands,
     },
+    #[clap(name = "merkle", about = "Build and verify merkle trees of airdrop claims")]
+    Merkle {
+        #[clap(subcommand)]
+        command: MerkleSubcommands,
+    },
     #[clap(
         name = "interface",
-        about = "Generate contract's interface from ABI. Currently it doesn't support ABI encoder V2"
+        about = "Generate a contract's interface, with its structs, from its ABI or from the ABI of the verified contract at an address"
     )]
     Interface {
-        #[clap(help = "The contract address or path to ABI file")]
+        #[clap(
+            help = "The contract address, whose ABI is fetched from the explorer of the chain, or path to an ABI file or artifact"
+        )]
         path_or_address: String,
+        #[clap(
+            long,
+            short,
+            help = "the name of the interface, defaults to the name of the verified contract or `Interface`"
+        )]
+        name: Option<String>,
         #[clap(long, short, default_value = "^0.8.10", help = "pragma version")]
         pragma: String,
         #[clap(short, help = "Path to output file. Defaults to stdout")]
@@ -380,6 +664,8 @@ pub enum WalletSubcommands {
             env = "CAST_PASSWORD"
         )]
         unsafe_password: Option<String>,
+        #[clap(long, help = "Generate a vanity address that starts with the hex prefix")]
+        starts_with: Option<String>,
     },
     #[clap(name = "vanity", about = "Generate a vanity address")]
     Vanity {
@@ -397,9 +683,60 @@ pub enum WalletSubcommands {
     Sign {
         #[clap(help = "message to sign")]
         message: String,
+        #[clap(long, help = "the message is hex encoded, like `0x1234`, and signed as bytes")]
+        hex: bool,
+        #[clap(
+            long,
+            help = "sign the 32 byte hash as is, without the EIP-191 prefix, like an EIP-712 digest"
+        )]
+        no_prefix: bool,
+        #[clap(flatten)]
+        wallet: Wallet,
+    },
+    #[clap(
+        name = "sign-tx",
+        about = "Sign a transaction built with `cast mktx` without connecting to a network and print the signed raw transaction"
+    )]
+    SignTx {
+        #[clap(
+            help = "the unsigned transaction as JSON, or the path to a JSON file, read from stdin if not set"
+        )]
+        tx: Option<String>,
+        #[clap(flatten)]
+        wallet: Wallet,
+    },
+    #[clap(
+        name = "import",
+        about = "Import a private key into an encrypted keystore in `~/.foundry/keystores`, to use with `--account <NAME>`"
+    )]
+    Import {
+        #[clap(help = "the name of the account")]
+        name: String,
+        #[clap(
+            long,
+            help = "Password of the keystore in cleartext. This is UNSAFE to use, the password is prompted for if not set",
+            env = "CAST_PASSWORD"
+        )]
+        unsafe_password: Option<String>,
         #[clap(flatten)]
         wallet: Wallet,
     },
+    #[clap(name = "list", about = "List the accounts imported with `cast wallet import`")]
+    List,
+    #[clap(name = "recover", about = "Recover the address that signed the message")]
+    Recover {
+        #[clap(help = "the signed message")]
+        message: String,
+        #[clap(help = "the signature")]
+        signature: String,
+        #[clap(long, help = "the message is hex encoded, like `0x1234`, and was signed as bytes")]
+        hex: bool,
+        #[clap(
+            long,
+            help = "recover from the 32 byte hash as is, without the EIP-191 prefix, like an EIP-712 digest"
+        )]
+        no_prefix: bool,
+    },
     #[clap(name = "verify", about = "Verify the signature on the message")]
     Verify {
         #[clap(help = "original message")]
@@ -411,12 +748,37 @@ pub enum WalletSubcommands {
     },
 }
 
-fn parse_name_or_address(s: &str) -> eyre::Result<NameOrAddress> {
-    Ok(if s.starts_with("0x") {
-        NameOrAddress::Address(s.parse::<Address>()?)
-    } else {
-        NameOrAddress::Name(s.into())
-    })
+#[derive(Debug, Parser)]
+pub enum MerkleSubcommands {
+    #[clap(
+        name = "tree",
+        about = "Build the merkle tree of the claims of a CSV with an `address,amount` row per claim and print its root and the proof of every claim"
+    )]
+    Tree {
+        #[clap(help = "the path of the CSV")]
+        csv: PathBuf,
+    },
+    #[clap(name = "proof", about = "Print the merkle proof of the claim of the account")]
+    Proof {
+        #[clap(help = "the path of the CSV")]
+        csv: PathBuf,
+        #[clap(help = "the account of the claim")]
+        account: Address,
+    },
+    #[clap(
+        name = "verify",
+        about = "Verify that the claim of the amount by the account is part of the tree with the root"
+    )]
+    Verify {
+        #[clap(help = "the root of the tree")]
+        root: H256,
+        #[clap(help = "the account of the claim")]
+        account: Address,
+        #[clap(help = "the amount of the claim", parse(try_from_str = parse_amount))]
+        amount: U256,
+        #[clap(help = "the proof of the claim")]
+        proof: Vec<H256>,
+    },
 }
 
 fn parse_block_id(s: &str) -> eyre::Result<BlockId> {
@@ -429,18 +791,31 @@ fn parse_block_id(s: &str) -> eyre::Result<BlockId> {
     })
 }
 
-fn parse_slot(s: &str) -> eyre::Result<H256> {
-    Ok(if s.starts_with("0x") {
-        let padded = format!("{:0>64}", s.strip_prefix("0x").unwrap());
-        H256::from_str(&padded)?
-    } else {
-        H256::from_low_u64_be(u64::from_str(s)?)
+fn parse_block_number(s: &str) -> eyre::Result<BlockNumber> {
+    Ok(match s {
+        "earliest" => BlockNumber::Earliest,
+        "latest" => BlockNumber::Latest,
+        "pending" => BlockNumber::Pending,
+        s => BlockNumber::Number(u64::from_str(s)?.into()),
     })
 }
 
+fn parse_access_list(s: &str) -> eyre::Result<AccessList> {
+    let json = if s.trim_start().starts_with('[') {
+        s.to_string()
+    } else {
+        std::fs::read_to_string(s)
+            .map_err(|err| eyre::eyre!("failed to read access list from {}: {}", s, err))?
+    };
+    Ok(serde_json::from_str(&json)?)
+}
+
 #[derive(Debug, Parser)]
 #[clap(name = "cast", version = crate::utils::VERSION_MESSAGE)]
 pub struct Opts {
     #[clap(subcommand)]
     pub sub: Subcommands,
+
+    #[clap(flatten)]
+    pub shell: ShellOpts,
 }
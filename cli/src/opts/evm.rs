@@ -1,7 +1,13 @@
 //! cli arguments for configuring the evm settings
+use crate::{shell::sh_status, utils::parse_name_or_address};
 use clap::Parser;
-use ethers::types::{Address, U256};
-use evm_adapters::evm_opts::EvmType;
+use ethers::types::{Address, BlockId, NameOrAddress, U256};
+use evm_adapters::{
+    chain_profile::ChainProfile,
+    evm_opts::{EnvOverrides, EvmOpts, EvmType},
+    BlockingProvider, ForkClient,
+};
+use eyre::WrapErr;
 use foundry_config::{
     figment::{
         self,
@@ -11,7 +17,12 @@ use foundry_config::{
     },
     Config,
 };
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
 /// `EvmArgs` and `EnvArgs` take the highest precedence in the Config/Figment hierarchy.
 /// All vars are opt-in, their default values are expected to be set by the
@@ -48,29 +59,96 @@ pub struct EvmArgs {
     )]
     pub evm_type: EvmType,
 
-    #[clap(help = "fetch state over a remote instead of starting from empty state", long, short)]
+    #[clap(
+        long,
+        help = "the chain whose system contracts are available at their canonical addresses (ethereum, optimism, arbitrum)",
+        value_name = "CHAIN"
+    )]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chain_profile: Option<ChainProfile>,
+
+    #[clap(
+        help = "a well-known chain, like mainnet, optimism, arbitrum or polygon, that sets the chain id and forks from the endpoint of `rpc_endpoints` named like the chain",
+        long
+    )]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chain: Option<String>,
+
+    #[clap(
+        help = "fetch state over a remote (http, ws or ipc) instead of starting from empty state, can also be the name of an endpoint in `rpc_endpoints`",
+        long,
+        short
+    )]
     #[clap(alias = "rpc-url")]
     #[serde(rename = "eth_rpc_url", skip_serializing_if = "Option::is_none")]
     pub fork_url: Option<String>,
 
-    #[clap(help = "pins the block number for the state fork", long)]
+    #[clap(
+        help = "pins the block number for the state fork, can also be `latest` or relative to it, e.g. `latest-10`",
+        long
+    )]
+    #[serde(skip)]
+    pub fork_block_number: Option<ForkBlockNumber>,
+
+    #[clap(
+        help = "do not pin the fork block via the `fork.lock` file, if no block number is set",
+        long
+    )]
+    #[serde(skip)]
+    pub no_fork_lock: bool,
+
+    #[clap(help = "do not cache the state of a pinned fork in `~/.foundry/cache`", long)]
+    #[serde(skip)]
+    pub no_storage_caching: bool,
+
+    #[clap(
+        help = "verify all state fetched from the fork via `eth_getProof` against the block's state root",
+        long
+    )]
+    #[serde(skip)]
+    pub verify_fork_state: bool,
+
+    #[clap(
+        help = "accounts to fetch from the fork concurrently before any test is executed",
+        long = "prefetch"
+    )]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub prefetch_accounts: Vec<Address>,
+
+    #[clap(
+        help = "json file with geth-style state overrides (balance, nonce, code, state, stateDiff per address) that are applied on top of the fork",
+        long,
+        value_name = "PATH"
+    )]
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub fork_block_number: Option<u64>,
+    pub state_overrides: Option<PathBuf>,
 
     #[clap(help = "the initial balance of each deployed test contract", long)]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub initial_balance: Option<U256>,
 
-    #[clap(help = "the address which will be executing all tests", long)]
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub sender: Option<Address>,
+    #[clap(
+        help = "the address which will be executing all tests, ENS names are resolved via the fork url",
+        long,
+        parse(try_from_str = parse_name_or_address)
+    )]
+    #[serde(skip)]
+    pub sender: Option<NameOrAddress>,
 
     #[clap(help = "enables the FFI cheatcode", long)]
     #[serde(skip)]
     pub ffi: bool,
 
+    #[clap(
+        help = "disables the transaction and block gas limits and funds the sender with an unlimited balance, to simulate transactions that exceed mainnet limits",
+        long
+    )]
+    #[serde(skip)]
+    pub unlimited_gas: bool,
+
     #[clap(
         help = r#"Verbosity mode of EVM output as number of occurences of the `v` flag (-v, -vv, -vvv, etc.)
+    2: print logs for all tests, and stack traces for failing tests
     3: print test trace for failing tests
     4: always print test trace, print setup for failing tests
     5: always print test trace and setup
@@ -84,6 +162,14 @@ pub struct EvmArgs {
 
     #[clap(help = "enable debugger", long)]
     pub debug: bool,
+
+    #[clap(
+        help = "the maximum number of steps the debugger records per call, only the most recent steps are kept",
+        long,
+        value_name = "STEPS"
+    )]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub debug_step_limit: Option<usize>,
 }
 
 // Make this set of options a `figment::Provider` so that it can be merged into the `Config`
@@ -106,10 +192,200 @@ impl Provider for EvmArgs {
             dict.insert("ffi".to_string(), self.ffi.into());
         }
 
+        // ENS names are resolved in `resolve_fork`, once the fork url is known
+        if let Some(NameOrAddress::Address(sender)) = self.sender {
+            dict.insert("sender".to_string(), format!("{:?}", sender).into());
+        }
+
+        if let Some(ForkBlockNumber::Number(num)) = self.fork_block_number {
+            dict.insert("fork_block_number".to_string(), num.into());
+        }
+
+        if self.no_storage_caching {
+            dict.insert("no_storage_caching".to_string(), self.no_storage_caching.into());
+        }
+
+        if self.verify_fork_state {
+            dict.insert("verify_fork_state".to_string(), self.verify_fork_state.into());
+        }
+
+        if self.unlimited_gas {
+            dict.insert("unlimited_gas".to_string(), self.unlimited_gas.into());
+        }
+
         Ok(Map::from([(Config::selected_profile(), dict)]))
     }
 }
 
+impl EvmArgs {
+    /// Resolves the fork settings of the `evm_opts` with the `config`.
+    ///
+    /// If the `chain` of the `config` is set, the chain id is set to the chain's unless set
+    /// explicitly, and the fork url to the endpoint of `rpc_endpoints` named like the chain unless
+    /// set explicitly.
+    ///
+    /// If the fork url is the name of an endpoint in the `rpc_endpoints` of the `config`, it is
+    /// replaced with the endpoint's url. All resolvable endpoints are made available to the
+    /// `rpcUrl` cheatcode.
+    ///
+    /// Afterwards the block to fork from is set in the `evm_opts`, if a fork url is set. Blocks
+    /// relative to `latest` are resolved via the fork url. If no block was set at all, the
+    /// block pinned for the chain in the `fork.lock` file of the `root` is used. If the chain is
+    /// not pinned yet, the latest block is used and written to the `fork.lock` file, unless
+    /// `--no-fork-lock` is set.
+    ///
+    /// The block environment of a fork is taken from the forked block, only the values that are
+    /// set explicitly via the cli replace the block's values.
+    ///
+    /// If the `--sender` is an ENS name, it is resolved via the fork url.
+    ///
+    /// Offline, the block must be pinned, either explicitly or in the `fork.lock` file.
+    pub fn resolve_fork(&self, evm_opts: &mut EvmOpts, config: &Config) -> eyre::Result<()> {
+        if let Some(chain) = config.chain_preset() {
+            evm_opts.env.chain_id.get_or_insert(chain.id);
+            if evm_opts.fork_url.is_none() && config.rpc_endpoints.contains_key(chain.name) {
+                evm_opts.fork_url = Some(chain.name.to_string());
+            }
+        }
+        evm_opts.fork_url =
+            evm_opts.fork_url.as_deref().map(|url| config.resolve_rpc_url(url)).transpose()?;
+        evm_opts.rpc_endpoints = config.resolved_rpc_endpoints();
+        evm_opts.env_overrides = self.env.overrides();
+
+        if let Some(NameOrAddress::Name(ref name)) = self.sender {
+            let url = evm_opts.fork_url.as_deref().ok_or_else(|| {
+                eyre::eyre!("the ENS name `{}` of the sender requires a --fork-url", name)
+            })?;
+            if evm_opts.offline {
+                eyre::bail!("the ENS name `{}` of the sender can't be resolved offline", name)
+            }
+            let provider = BlockingProvider::new(ForkClient::provider(url)?);
+            evm_opts.sender = provider
+                .resolve_name(name)
+                .wrap_err_with(|| format!("failed to resolve the ENS name `{}`", name))?;
+        }
+
+        let url = match evm_opts.fork_url {
+            Some(ref url) => url.clone(),
+            None => return Ok(()),
+        };
+
+        match self.fork_block_number {
+            Some(ForkBlockNumber::Number(_)) => return Ok(()),
+            Some(ForkBlockNumber::Latest(_)) if evm_opts.offline => {
+                eyre::bail!("the latest block can't be resolved offline, fork a block number")
+            }
+            Some(ForkBlockNumber::Latest(offset)) => {
+                let (_, latest) = chain_and_latest_block(&url)?;
+                let block = latest.checked_sub(offset).ok_or_else(|| {
+                    eyre::eyre!("latest block {} is lower than offset {}", latest, offset)
+                })?;
+                sh_status!("forking from block {}", block);
+                evm_opts.fork_block_number = Some(block);
+            }
+            // pinned in the config
+            None if evm_opts.fork_block_number.is_some() => return Ok(()),
+            None if self.no_fork_lock => return Ok(()),
+            None if evm_opts.offline => {
+                let lock = ForkLock::read(&config.__root.0.join(ForkLock::FILE_NAME))?;
+                // the chain can't be queried, without a chain id a single pinned chain is assumed
+                let pinned = match evm_opts.env.chain_id {
+                    Some(chain_id) => lock.0.get(&chain_id),
+                    None if lock.0.len() == 1 => lock.0.values().next(),
+                    None => None,
+                };
+                let block = *pinned.ok_or_else(|| {
+                    eyre::eyre!(
+                        "forking offline requires a `--fork-block-number` or a block pinned in {}",
+                        ForkLock::FILE_NAME
+                    )
+                })?;
+                sh_status!("forking from block {} (pinned in {})", block, ForkLock::FILE_NAME);
+                evm_opts.fork_block_number = Some(block);
+            }
+            None => {
+                let lock_path = config.__root.0.join(ForkLock::FILE_NAME);
+                let mut lock = ForkLock::read(&lock_path)?;
+                let (chain_id, latest) = chain_and_latest_block(&url)?;
+                let block = if let Some(block) = lock.0.get(&chain_id) {
+                    sh_status!("forking from block {} (pinned in {})", block, ForkLock::FILE_NAME);
+                    *block
+                } else {
+                    lock.0.insert(chain_id, latest);
+                    lock.write(&lock_path)?;
+                    sh_status!(
+                        "forking from block {} (written to {})",
+                        latest,
+                        ForkLock::FILE_NAME
+                    );
+                    latest
+                };
+                evm_opts.fork_block_number = Some(block);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns the chain id and the latest block number of the chain at `url`
+pub(crate) fn chain_and_latest_block(url: &str) -> eyre::Result<(u64, u64)> {
+    let provider = BlockingProvider::new(ForkClient::provider(url)?);
+    let (block, chain_id) = provider.block_and_chainid(None::<BlockId>)?;
+    let number = block.number.ok_or_else(|| eyre::eyre!("latest block has no number"))?;
+    Ok((chain_id.as_u64(), number.as_u64()))
+}
+
+/// The block to fork from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ForkBlockNumber {
+    /// A concrete block number
+    Number(u64),
+    /// The latest block minus the given offset
+    Latest(u64),
+}
+
+impl FromStr for ForkBlockNumber {
+    type Err = eyre::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some(offset) = s.strip_prefix("latest") {
+            let offset = offset.trim();
+            if offset.is_empty() {
+                return Ok(ForkBlockNumber::Latest(0))
+            }
+            let offset = offset
+                .strip_prefix('-')
+                .ok_or_else(|| eyre::eyre!("expected `latest-<offset>`, got `{}`", s))?;
+            return Ok(ForkBlockNumber::Latest(offset.trim().parse()?))
+        }
+        Ok(ForkBlockNumber::Number(s.parse()?))
+    }
+}
+
+/// The `fork.lock` file which pins the fork block per chain id
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct ForkLock(BTreeMap<u64, u64>);
+
+impl ForkLock {
+    const FILE_NAME: &'static str = "fork.lock";
+
+    /// Reads the lock file at `path`, which is empty if the file does not exist
+    fn read(path: &Path) -> eyre::Result<Self> {
+        if !path.exists() {
+            return Ok(Default::default())
+        }
+        let content = std::fs::read_to_string(path)?;
+        serde_json::from_str(&content)
+            .wrap_err_with(|| format!("failed to parse {}", path.display()))
+    }
+
+    fn write(&self, path: &Path) -> eyre::Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, Default, Parser, Serialize)]
 pub struct EnvArgs {
     // structopt does not let use `u64::MAX`:
@@ -154,3 +430,33 @@ pub struct EnvArgs {
     pub block_gas_limit: Option<u64>,
     // TODO: Add configuration option for base fee.
 }
+
+impl EnvArgs {
+    /// Returns the block environment values that were set explicitly
+    pub fn overrides(&self) -> EnvOverrides {
+        EnvOverrides {
+            gas_price: self.gas_price,
+            block_base_fee_per_gas: self.block_base_fee_per_gas,
+            block_coinbase: self.block_coinbase,
+            block_timestamp: self.block_timestamp,
+            block_number: self.block_number,
+            block_difficulty: self.block_difficulty,
+            block_gas_limit: self.block_gas_limit,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_fork_block_number() {
+        assert_eq!("100".parse::<ForkBlockNumber>().unwrap(), ForkBlockNumber::Number(100));
+        assert_eq!("latest".parse::<ForkBlockNumber>().unwrap(), ForkBlockNumber::Latest(0));
+        assert_eq!("latest-10".parse::<ForkBlockNumber>().unwrap(), ForkBlockNumber::Latest(10));
+        assert_eq!("latest - 5".parse::<ForkBlockNumber>().unwrap(), ForkBlockNumber::Latest(5));
+        assert!("latest+5".parse::<ForkBlockNumber>().is_err());
+        assert!("earliest".parse::<ForkBlockNumber>().is_err());
+    }
+}
@@ -3,9 +3,16 @@ use clap::{Parser, Subcommand, ValueHint};
 use ethers::{solc::EvmVersion, types::Address};
 use std::{path::PathBuf, str::FromStr};
 
-use crate::cmd::{
-    build::BuildArgs, config, create::CreateArgs, flatten, init::InitArgs, install::InstallArgs,
-    remappings::RemappingArgs, run::RunArgs, snapshot, test,
+use crate::{
+    cmd::{
+        bind::BindArgs, broadcast_log::BroadcastLogArgs, build::BuildArgs, cache::CacheArgs,
+        config, create::CreateArgs, flatten, geiger::GeigerArgs, generate::GenerateArgs,
+        init::InitArgs, inspect::InspectArgs, install::InstallArgs, lint::LintArgs,
+        mutate::MutateArgs, node::NodeArgs, remappings::RemappingArgs, replay::ReplayArgs,
+        run::RunArgs, snapshot, solc::SolcArgs, test, upgrade::UpgradeCheckArgs,
+        upload_selectors::UploadSelectorsArgs, verify::VerifyArgs,
+    },
+    shell::ShellOpts,
 };
 use serde::Serialize;
 
@@ -14,6 +21,27 @@ use serde::Serialize;
 pub struct Opts {
     #[clap(subcommand)]
     pub sub: Subcommands,
+
+    #[clap(
+        long,
+        global = true,
+        env = "FOUNDRY_PROFILE",
+        help = "the profile of the config file to use, e.g. `ci` for the values of `[profile.ci]`"
+    )]
+    pub profile: Option<String>,
+
+    #[clap(flatten)]
+    pub shell: ShellOpts,
+
+    #[clap(long, global = true, help = "print the results as json")]
+    pub json: bool,
+
+    #[clap(
+        long,
+        global = true,
+        help = "run the `hooks` of the config, which are skipped otherwise, or set `FOUNDRY_ALLOW_HOOKS=true`"
+    )]
+    pub allow_hooks: bool,
 }
 
 #[derive(Debug, Subcommand)]
@@ -28,11 +56,25 @@ pub enum Subcommands {
     #[clap(alias = "b")]
     Build(BuildArgs),
 
+    #[clap(about = "generates Rust bindings and TypeScript typings of the project's contracts")]
+    Bind(BindArgs),
+
     #[clap(about = "run a single smart contract as a script")]
     #[clap(alias = "r")]
     Run(RunArgs),
 
-    #[clap(alias = "u", about = "fetches all upstream lib changes")]
+    #[clap(about = "replays a mined transaction locally on top of a fork of its block")]
+    Replay(ReplayArgs),
+
+    #[clap(
+        about = "starts a local development node that serves the EVM of the tests over JSON-RPC"
+    )]
+    Node(NodeArgs),
+
+    #[clap(
+        alias = "u",
+        about = "fetches all upstream lib changes and records the new commits in the foundry.lock"
+    )]
     Update {
         #[clap(
             help = "the submodule name of the library you want to update (will update all if none is provided)",
@@ -43,30 +85,28 @@ pub enum Subcommands {
 
     #[clap(
         alias = "i",
-        about = "installs one or more dependencies as git submodules (will install existing dependencies if no arguments are provided"
+        about = "installs one or more dependencies as git submodules and pins their commits in the foundry.lock (will install existing dependencies at their locked commits if no arguments are provided)"
     )]
     Install(InstallArgs),
 
-    #[clap(alias = "r", about = "removes one or more dependencies from git submodules")]
+    #[clap(
+        alias = "r",
+        about = "removes one or more dependencies, their remappings and lock entries"
+    )]
     Remove {
         #[clap(help = "the submodule name of the library you want to remove")]
         dependencies: Vec<Dependency>,
     },
 
-    #[clap(about = "prints the automatically inferred remappings for this repository")]
+    #[clap(
+        about = "prints, writes or checks the remappings inferred from the dependencies of this repository"
+    )]
     Remappings(RemappingArgs),
 
     #[clap(
-        about = "verify your smart contracts source code on Etherscan. Requires `ETHERSCAN_API_KEY` to be set."
+        about = "verify your smart contracts source code on Etherscan or Sourcify. Etherscan requires `ETHERSCAN_API_KEY` to be set."
     )]
-    VerifyContract {
-        #[clap(help = "contract source info `<path>:<contractname>`")]
-        contract: FullContractInfo,
-        #[clap(help = "the address of the contract to verify.")]
-        address: Address,
-        #[clap(help = "constructor args calldata arguments.")]
-        constructor_args: Vec<String>,
-    },
+    VerifyContract(VerifyArgs),
 
     #[clap(alias = "c", about = "deploy a compiled contract")]
     Create(CreateArgs),
@@ -98,6 +138,51 @@ pub enum Subcommands {
 
     #[clap(about = "concats a file with all of its imports")]
     Flatten(flatten::FlattenArgs),
+
+    #[clap(
+        about = "prints the storage layout, abi, method identifiers, bytecode, assembly or metadata of a compiled contract"
+    )]
+    Inspect(InspectArgs),
+
+    #[clap(
+        about = "checks that a new implementation of a proxy preserves the storage layout of the current one and warns about constructors, `selfdestruct` and `delegatecall`"
+    )]
+    UpgradeCheck(UpgradeCheckArgs),
+
+    #[clap(
+        alias = "analyze",
+        about = "reports the compiler's diagnostics and the findings of lints for common vulnerabilities"
+    )]
+    Lint(LintArgs),
+
+    #[clap(
+        about = "scans the sources and the dependencies for dangerous cheatcodes, like `ffi`, and unsafe patterns, to run before running the tests of third party code"
+    )]
+    Geiger(GeigerArgs),
+
+    #[clap(
+        about = "runs the tests against mutated sources and reports the mutants that no test kills"
+    )]
+    Mutate(MutateArgs),
+
+    #[clap(about = "manages the cached state of forked chains")]
+    Cache(CacheArgs),
+
+    #[clap(about = "manages the solc compilers")]
+    Solc(SolcArgs),
+
+    #[clap(
+        about = "lists the previous broadcasts of scripts, shows their transactions and exports the deployed contracts to a registry"
+    )]
+    BroadcastLog(BroadcastLogArgs),
+
+    #[clap(
+        about = "uploads the signatures of the functions and events of the project's contracts to 4byte.directory, so that traces of them can be decoded by anyone"
+    )]
+    UploadSelectors(UploadSelectorsArgs),
+
+    #[clap(about = "generates test contracts")]
+    Generate(GenerateArgs),
 }
 
 /// A set of solc compiler settings that can be set via command line arguments, which are intended
@@ -118,6 +203,27 @@ pub struct CompilerArgs {
     #[clap(help = "optimizer parameter runs", long)]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub optimize_runs: Option<usize>,
+
+    #[clap(help = "compile through the Yul IR pipeline", long)]
+    // skipped because, via-ir is opt-in
+    #[serde(skip)]
+    pub via_ir: bool,
+
+    #[clap(
+        help = "the hash of the metadata appended to the bytecode",
+        long,
+        possible_values = &["ipfs", "bzzr1", "none"]
+    )]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bytecode_hash: Option<String>,
+
+    #[clap(
+        help = "how revert reason strings are generated",
+        long,
+        possible_values = &["default", "strip", "debug", "verboseDebug"]
+    )]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revert_strings: Option<String>,
 }
 
 /// Represents the common dapp argument pattern for `<path>:<contractname>` where `<path>:` is
@@ -177,18 +283,44 @@ impl FromStr for FullContractInfo {
 pub struct Dependency {
     /// The name of the dependency
     pub name: String,
-    /// The url to the git repository corresponding to the dependency
+    /// The url to the git repository corresponding to the dependency, or the package name of
+    /// npm packages
     pub url: String,
-    /// Optional tag corresponding to a Git SHA, tag, or branch.
+    /// Optional tag corresponding to a Git SHA, tag, or branch, or the version of npm packages
     pub tag: Option<String>,
+    /// Whether the dependency is a package tarball fetched from the npm registry
+    pub npm: bool,
 }
 
 const GITHUB: &str = "github.com";
 const VERSION_SEPARATOR: char = '@';
+const NPM_PREFIX: &str = "npm:";
+
+impl Dependency {
+    /// Parses npm packages, like `npm:solmate@6` or the scoped `@openzeppelin/contracts@4.8.0`
+    fn from_npm(package: &str) -> eyre::Result<Self> {
+        // the scope of a package starts with the version separator as well
+        let (name, tag) = match package.get(1..).and_then(|rest| rest.rfind(VERSION_SEPARATOR)) {
+            Some(idx) => (&package[..idx + 1], Some(package[idx + 2..].to_string())),
+            None => (package, None),
+        };
+        if name.is_empty() || name.ends_with('/') {
+            eyre::bail!("invalid npm package `{}`", package)
+        }
+        Ok(Dependency { name: name.to_string(), url: name.to_string(), tag, npm: true })
+    }
+}
 
 impl FromStr for Dependency {
     type Err = eyre::Error;
     fn from_str(dependency: &str) -> Result<Self, Self::Err> {
+        if let Some(package) = dependency.strip_prefix(NPM_PREFIX) {
+            return Dependency::from_npm(package)
+        }
+        if dependency.starts_with(VERSION_SEPARATOR) {
+            return Dependency::from_npm(dependency)
+        }
+
         // TODO: Is there a better way to normalize these paths to having a
         // `https://github.com/` prefix?
         let path = if dependency.starts_with("https://") {
@@ -210,7 +342,7 @@ impl FromStr for Dependency {
             .to_string();
         let tag = split.next().map(ToString::to_string);
 
-        Ok(Dependency { name, url, tag })
+        Ok(Dependency { name, url, tag, npm: false })
     }
 }
 
@@ -239,6 +371,27 @@ mod tests {
             assert_eq!(dep.url, expected_path.to_string());
             assert_eq!(dep.tag, expected_tag.map(ToString::to_string));
             assert_eq!(dep.name, "lootloose");
+            assert!(!dep.npm);
+        });
+    }
+
+    #[test]
+    fn parses_npm_dependencies() {
+        [
+            ("npm:solmate", "solmate", None),
+            ("npm:solmate@6.2.0", "solmate", Some("6.2.0")),
+            ("@openzeppelin/contracts", "@openzeppelin/contracts", None),
+            ("@openzeppelin/contracts@4.8.0", "@openzeppelin/contracts", Some("4.8.0")),
+            ("npm:@openzeppelin/contracts@^4.8.0", "@openzeppelin/contracts", Some("^4.8.0")),
+        ]
+        .iter()
+        .for_each(|(input, expected_name, expected_tag)| {
+            let dep = Dependency::from_str(input).unwrap();
+            assert_eq!(dep.name, expected_name.to_string());
+            assert_eq!(dep.url, expected_name.to_string());
+            assert_eq!(dep.tag, expected_tag.map(ToString::to_string));
+            assert!(dep.npm);
         });
+        assert!(Dependency::from_str("npm:").is_err());
     }
 }
@@ -0,0 +1,341 @@
+//! Wallet options shared by all commands that sign messages or transactions
+
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use crate::utils::read_secret;
+use clap::Parser;
+use ethers::{
+    middleware::SignerMiddleware,
+    providers::{Http, Provider},
+    signers::{
+        coins_bip39::English, HDPath as LedgerHDPath, Ledger, LocalWallet, MnemonicBuilder, Signer,
+        Trezor, TrezorHDPath,
+    },
+    types::{transaction::eip2718::TypedTransaction, Address, Signature, H256},
+};
+use eyre::Result;
+use foundry_config::Config;
+
+/// A signer of any wallet type, connected to a provider
+#[derive(Debug)]
+pub enum WalletType {
+    Local(SignerMiddleware<Provider<Http>, LocalWallet>),
+    Ledger(SignerMiddleware<Provider<Http>, Ledger>),
+    Trezor(SignerMiddleware<Provider<Http>, Trezor>),
+}
+
+impl WalletType {
+    /// Returns the address of the signer
+    pub fn address(&self) -> Address {
+        match self {
+            WalletType::Local(signer) => signer.address(),
+            WalletType::Ledger(signer) => signer.address(),
+            WalletType::Trezor(signer) => signer.address(),
+        }
+    }
+}
+
+/// Evaluates the expression with `$signer` bound to the [`SignerMiddleware`] of a [`WalletType`],
+/// whatever its wallet type is, so generic code over `Middleware` is only written once
+macro_rules! with_wallet {
+    ($wallet:expr, |$signer:ident| $body:expr) => {
+        match $wallet {
+            $crate::opts::WalletType::Local($signer) => $body,
+            $crate::opts::WalletType::Ledger($signer) => $body,
+            $crate::opts::WalletType::Trezor($signer) => $body,
+        }
+    };
+}
+pub(crate) use with_wallet;
+
+/// A signer of any wallet type, not connected to a provider
+#[derive(Debug)]
+pub enum WalletSigner {
+    Local(LocalWallet),
+    Ledger(Ledger),
+    Trezor(Trezor),
+}
+
+impl WalletSigner {
+    /// Returns the address of the signer
+    pub fn address(&self) -> Address {
+        match self {
+            WalletSigner::Local(signer) => signer.address(),
+            WalletSigner::Ledger(signer) => signer.address(),
+            WalletSigner::Trezor(signer) => signer.address(),
+        }
+    }
+
+    /// Signs the message with the EIP-191 prefix
+    pub async fn sign_message<S: AsRef<[u8]> + Send + Sync>(
+        &self,
+        message: S,
+    ) -> Result<Signature> {
+        Ok(match self {
+            WalletSigner::Local(signer) => signer.sign_message(message).await?,
+            WalletSigner::Ledger(signer) => signer.sign_message(message).await?,
+            WalletSigner::Trezor(signer) => signer.sign_message(message).await?,
+        })
+    }
+
+    /// Signs the transaction, the chain id of the transaction takes precedence over the signer's
+    pub async fn sign_transaction(&self, tx: &TypedTransaction) -> Result<Signature> {
+        Ok(match self {
+            WalletSigner::Local(signer) => signer.sign_transaction(tx).await?,
+            WalletSigner::Ledger(signer) => signer.sign_transaction(tx).await?,
+            WalletSigner::Trezor(signer) => signer.sign_transaction(tx).await?,
+        })
+    }
+
+    /// Signs the hash without any prefix, like an EIP-712 digest
+    ///
+    /// Hardware wallets only sign messages and typed data they can display, so this is limited to
+    /// local wallets.
+    pub fn sign_hash(&self, hash: H256) -> Result<Signature> {
+        match self {
+            WalletSigner::Local(signer) => Ok(signer.sign_hash(hash, false)),
+            _ => eyre::bail!("hardware wallets can't sign raw hashes, use a local wallet instead"),
+        }
+    }
+}
+
+#[derive(Parser, Debug, Clone)]
+#[cfg_attr(not(doc), allow(missing_docs))]
+#[cfg_attr(
+    doc,
+    doc = r#"
+The wallet options can either be:
+1. Ledger
+2. Trezor
+3. Mnemonic (via file path)
+4. Keystore (via file path)
+5. Private Key (cleartext in CLI)
+6. Private Key (interactively via secure prompt)
+"#
+)]
+pub struct Wallet {
+    #[clap(long, short, help = "Interactive prompt to insert your private key")]
+    pub interactive: bool,
+
+    #[clap(long = "private-key", help = "Your private key string")]
+    pub private_key: Option<String>,
+
+    #[clap(
+        env = "ETH_KEYSTORE",
+        long = "keystore",
+        help = "Path to your keystore file, or to a folder that contains a single keystore"
+    )]
+    pub keystore_path: Option<String>,
+
+    #[clap(
+        env = "ETH_PASSWORD",
+        long = "password",
+        help = "Your keystore password, prompted for if not set"
+    )]
+    pub keystore_password: Option<String>,
+
+    #[clap(
+        long = "account",
+        help = "The name of a keystore imported with `cast wallet import`",
+        conflicts_with = "keystore-path"
+    )]
+    pub account: Option<String>,
+
+    #[clap(long = "mnemonic-path", help = "Path to your mnemonic file")]
+    pub mnemonic_path: Option<String>,
+
+    #[clap(
+        long = "mnemonic-passphrase",
+        help = "The BIP-39 passphrase of your mnemonic",
+        requires = "mnemonic-path"
+    )]
+    pub mnemonic_passphrase: Option<String>,
+
+    #[clap(short, long = "ledger", help = "Use your Ledger hardware wallet")]
+    pub ledger: bool,
+
+    #[clap(short, long = "trezor", help = "Use your Trezor hardware wallet")]
+    pub trezor: bool,
+
+    #[clap(
+        long = "hd-path",
+        help = "Derivation path for your hardware wallet (trezor or ledger) or mnemonic, like `m/44'/60'/0'/0/0`"
+    )]
+    pub hd_path: Option<String>,
+
+    #[clap(
+        long = "mnemonic_index",
+        help = "your index in the standard hd path",
+        default_value = "0"
+    )]
+    pub mnemonic_index: u32,
+}
+
+impl Wallet {
+    /// Returns the signer of the provided private key, mnemonic, keystore or hw wallet
+    pub async fn signer(&self, chain_id: u64) -> Result<WalletSigner> {
+        if self.ledger {
+            let derivation = match &self.hd_path {
+                Some(hd_path) => LedgerHDPath::Other(hd_path.clone()),
+                None => LedgerHDPath::LedgerLive(self.mnemonic_index as usize),
+            };
+            let ledger = Ledger::new(derivation, chain_id).await?;
+
+            Ok(WalletSigner::Ledger(ledger))
+        } else if self.trezor {
+            let derivation = match &self.hd_path {
+                Some(hd_path) => TrezorHDPath::Other(hd_path.clone()),
+                None => TrezorHDPath::TrezorLive(self.mnemonic_index as usize),
+            };
+
+            // cached to ~/.ethers-rs/trezor/cache/trezor.session
+            let trezor = Trezor::new(derivation, chain_id, None).await?;
+
+            Ok(WalletSigner::Trezor(trezor))
+        } else {
+            let local = self
+                .private_key()
+                .transpose()
+                .or_else(|| self.interactive().transpose())
+                .or_else(|| self.mnemonic().transpose())
+                .or_else(|| self.keystore().transpose())
+                .transpose()?
+                .ok_or_else(|| eyre::eyre!("error accessing local wallet, did you set a private key, mnemonic or keystore? Run `cast send --help` or `forge create --help` and use the corresponding CLI flag to set your key via --private-key, --mnemonic-path, --interactive, --trezor or --ledger. Alternatively, if you're using a local node with unlocked accounts, set the `ETH_FROM` environment variable to the address of the account you want to use"))?;
+
+            Ok(WalletSigner::Local(local.with_chain_id(chain_id)))
+        }
+    }
+
+    fn interactive(&self) -> Result<Option<LocalWallet>> {
+        Ok(if self.interactive {
+            println!("Insert private key:");
+            let private_key = rpassword::read_password()?;
+            let private_key = private_key.strip_prefix("0x").unwrap_or(&private_key);
+            Some(LocalWallet::from_str(private_key)?)
+        } else {
+            None
+        })
+    }
+
+    fn private_key(&self) -> Result<Option<LocalWallet>> {
+        Ok(if let Some(ref private_key) = self.private_key {
+            let private_key = private_key.strip_prefix("0x").unwrap_or(private_key);
+            Some(LocalWallet::from_str(private_key)?)
+        } else {
+            None
+        })
+    }
+
+    fn keystore(&self) -> Result<Option<LocalWallet>> {
+        let path = match (&self.keystore_path, &self.account) {
+            (Some(path), _) => keystore_file(Path::new(path))?,
+            (None, Some(account)) => account_keystore(account)?,
+            (None, None) => return Ok(None),
+        };
+        let password =
+            read_secret(self.keystore_password.is_none(), self.keystore_password.clone())?;
+        Ok(Some(LocalWallet::decrypt_keystore(path, password)?))
+    }
+
+    fn mnemonic(&self) -> Result<Option<LocalWallet>> {
+        Ok(if let Some(ref path) = self.mnemonic_path {
+            let mnemonic = std::fs::read_to_string(path)?.replace('\n', "");
+            let mut builder = MnemonicBuilder::<English>::default().phrase(mnemonic.as_str());
+            builder = match &self.hd_path {
+                Some(hd_path) => builder.derivation_path(hd_path)?,
+                None => builder.index(self.mnemonic_index)?,
+            };
+            if let Some(ref passphrase) = self.mnemonic_passphrase {
+                builder = builder.password(passphrase);
+            }
+            Some(builder.build()?)
+        } else {
+            None
+        })
+    }
+}
+
+/// Returns the keystore of the account in the foundry keystores directory
+pub fn account_keystore(account: &str) -> Result<PathBuf> {
+    validate_account_name(account)?;
+    let dir = Config::foundry_keystores_dir()
+        .ok_or_else(|| eyre::eyre!("could not find the home directory"))?;
+    let path = dir.join(account);
+    if !path.is_file() {
+        eyre::bail!(
+            "no account `{}` in {}, import it with `cast wallet import`",
+            account,
+            dir.display()
+        )
+    }
+    Ok(path)
+}
+
+/// Ensures the name of an account is a plain file name, so its keystore can't be outside of the
+/// foundry keystores directory
+pub fn validate_account_name(account: &str) -> Result<()> {
+    if account.is_empty() || account.contains(&['/', '\\'][..]) || account.contains("..") {
+        eyre::bail!("invalid account name `{}`, it can't contain path separators or `..`", account)
+    }
+    Ok(())
+}
+
+/// Returns the keystore at the path, or the only keystore in the folder at the path
+fn keystore_file(path: &Path) -> Result<PathBuf> {
+    if !path.is_dir() {
+        return Ok(path.to_path_buf())
+    }
+    let mut keystores = std::fs::read_dir(path)?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .collect::<Vec<_>>();
+    match keystores.len() {
+        1 => Ok(keystores.remove(0)),
+        0 => eyre::bail!("no keystore found in {}", path.display()),
+        _ => eyre::bail!(
+            "multiple keystores found in {}, pass the path of the keystore file",
+            path.display()
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_find_keystore_in_folder() {
+        let dir = std::env::temp_dir().join(format!("foundry-keystore-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        assert!(keystore_file(&dir).is_err());
+
+        let keystore = dir.join("UTC--2022-01-01T00-00-00.000000000Z--0000");
+        std::fs::write(&keystore, "{}").unwrap();
+        assert_eq!(keystore_file(&dir).unwrap(), keystore);
+        assert_eq!(keystore_file(&keystore).unwrap(), keystore);
+
+        std::fs::write(dir.join("other"), "{}").unwrap();
+        assert!(keystore_file(&dir).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn errors_on_unknown_account() {
+        let account = format!("foundry-unknown-account-{}", std::process::id());
+        let err = account_keystore(&account).unwrap_err().to_string();
+        assert!(err.contains("cast wallet import"));
+    }
+
+    #[test]
+    fn rejects_account_names_with_paths() {
+        assert!(validate_account_name("deployer").is_ok());
+        assert!(validate_account_name("deployer.old").is_ok());
+        for name in ["", "../deployer", "..", "keys/deployer", "keys\\deployer", "/deployer"] {
+            assert!(validate_account_name(name).is_err(), "{}", name);
+        }
+    }
+}
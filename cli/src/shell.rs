@@ -0,0 +1,398 @@
+//! The shell all command output goes through, so that every command respects the verbosity, the
+//! color choice and the output mode of the invocation
+//!
+//! Results are printed with [`sh_println!`], progress with [`sh_status!`] and warnings with
+//! [`sh_warn!`]. In json mode only the json documents printed with [`print_json`] go to stdout,
+//! statuses and warnings go to stderr.
+//!
+//! Long running operations render a [`spinner`] or [`progress_bar`] on stderr, which are hidden
+//! if the shell is quiet, in json mode or stderr is not a terminal.
+
+use ansi_term::Colour;
+use clap::Parser;
+use indicatif::{ProgressBar, ProgressStyle};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::{
+    borrow::Cow,
+    fmt,
+    io::{self, Write},
+    str::FromStr,
+    sync::Mutex,
+};
+
+/// The shell of the process, configured once by the binaries from the command line
+static SHELL: Lazy<Mutex<Shell>> = Lazy::new(|| Mutex::new(Shell::default()));
+
+/// The command line options that configure the [`Shell`]
+#[derive(Debug, Clone, Parser)]
+pub struct ShellOpts {
+    #[clap(
+        long,
+        short,
+        global = true,
+        help = "do not print status messages and warnings, only the results"
+    )]
+    pub quiet: bool,
+
+    #[clap(
+        long,
+        global = true,
+        help = "do not print anything, the exit code tells whether the command succeeded"
+    )]
+    pub silent: bool,
+
+    #[clap(
+        long,
+        global = true,
+        default_value = "auto",
+        possible_values = ["auto", "always", "never"],
+        help = "when to color the output"
+    )]
+    pub color: ColorChoice,
+}
+
+impl ShellOpts {
+    /// Installs the shell of these options as the shell of the process
+    pub fn install(&self, mode: OutputMode) {
+        let verbosity = if self.silent {
+            Verbosity::Silent
+        } else if self.quiet {
+            Verbosity::Quiet
+        } else {
+            Verbosity::Normal
+        };
+        set_shell(Shell::new(verbosity, self.color, mode));
+    }
+}
+
+/// When to color the output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorChoice {
+    /// Color the output if stdout is a terminal and `NO_COLOR` is not set
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Auto => {
+                std::env::var_os("NO_COLOR").is_none() && atty::is(atty::Stream::Stdout)
+            }
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+impl FromStr for ColorChoice {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            _ => Err(format!("unknown color choice: {}", s)),
+        }
+    }
+}
+
+/// Which messages are printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verbosity {
+    /// Nothing, only the exit code tells whether a command succeeded
+    Silent,
+    /// Only results and errors
+    Quiet,
+    /// Results, status messages, warnings and errors
+    Normal,
+}
+
+/// The format of the results
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputMode {
+    /// Results for humans
+    Human,
+    /// Results as json documents, one per line
+    Json,
+}
+
+/// Writes the output of the commands to stdout and stderr, or to other sinks in tests
+pub struct Shell {
+    verbosity: Verbosity,
+    color: bool,
+    mode: OutputMode,
+    /// Whether stderr is a terminal that progress can be rendered on
+    progress: bool,
+    out: Box<dyn Write + Send>,
+    err: Box<dyn Write + Send>,
+}
+
+impl Default for Shell {
+    fn default() -> Self {
+        Shell::new(Verbosity::Normal, ColorChoice::Auto, OutputMode::Human)
+    }
+}
+
+impl Shell {
+    pub fn new(verbosity: Verbosity, color: ColorChoice, mode: OutputMode) -> Self {
+        Self {
+            verbosity,
+            color: color.enabled(),
+            mode,
+            progress: atty::is(atty::Stream::Stderr),
+            out: Box::new(io::stdout()),
+            err: Box::new(io::stderr()),
+        }
+    }
+
+    /// Returns the shell with its output written to the sinks instead of stdout and stderr
+    #[must_use]
+    pub fn with_output(
+        mut self,
+        out: impl Write + Send + 'static,
+        err: impl Write + Send + 'static,
+    ) -> Self {
+        self.out = Box::new(out);
+        self.err = Box::new(err);
+        self.progress = false;
+        self
+    }
+
+    pub fn is_quiet(&self) -> bool {
+        self.verbosity != Verbosity::Normal
+    }
+
+    pub fn is_silent(&self) -> bool {
+        self.verbosity == Verbosity::Silent
+    }
+
+    pub fn is_json(&self) -> bool {
+        self.mode == OutputMode::Json
+    }
+
+    /// Whether progress is rendered, only on terminals and never if quiet or in json mode
+    pub fn is_progress_enabled(&self) -> bool {
+        !self.is_quiet() && !self.is_json() && self.progress
+    }
+
+    /// Prints a result, results for humans are not printed in json mode
+    pub fn println(&mut self, msg: impl fmt::Display) {
+        if !self.is_json() && !self.is_silent() {
+            let _ = writeln!(self.out, "{}", msg);
+        }
+    }
+
+    /// Prints a status message, which goes to stderr in json mode
+    pub fn status(&mut self, msg: impl fmt::Display) {
+        if self.is_quiet() {
+            return
+        }
+        let _ = if self.is_json() {
+            writeln!(self.err, "{}", msg)
+        } else {
+            writeln!(self.out, "{}", msg)
+        };
+    }
+
+    /// Prints a warning to stderr
+    pub fn warn(&mut self, msg: impl fmt::Display) {
+        if !self.is_quiet() {
+            let warning = self.paint(Colour::Yellow, "Warning:");
+            let _ = writeln!(self.err, "{} {}", warning, msg);
+        }
+    }
+
+    /// Prints an error to stderr, errors are only suppressed if the shell is silent
+    pub fn error(&mut self, msg: impl fmt::Display) {
+        if self.is_silent() {
+            return
+        }
+        let error = self.paint(Colour::Red, "Error:");
+        let _ = writeln!(self.err, "{} {}", error, msg);
+    }
+
+    /// Prints the value as a single line json document
+    pub fn print_json<T: Serialize>(&mut self, value: &T) -> eyre::Result<()> {
+        if self.is_silent() {
+            return Ok(())
+        }
+        writeln!(self.out, "{}", serde_json::to_string(value)?)?;
+        Ok(())
+    }
+
+    /// Returns the text in the colour if the output is colored
+    pub fn paint(&self, colour: Colour, text: impl fmt::Display) -> String {
+        if self.color {
+            colour.paint(text.to_string()).to_string()
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+/// Replaces the shell of the process
+pub fn set_shell(shell: Shell) {
+    *SHELL.lock().unwrap() = shell;
+}
+
+/// Runs the closure with the shell of the process
+pub fn with_shell<T>(f: impl FnOnce(&mut Shell) -> T) -> T {
+    f(&mut SHELL.lock().unwrap())
+}
+
+/// Whether nothing is printed, `false` if the shell is in use, e.g. by the thread that panics
+pub fn is_silent() -> bool {
+    SHELL.try_lock().map_or(false, |shell| shell.is_silent())
+}
+
+/// Whether only results and errors are printed
+pub fn is_quiet() -> bool {
+    with_shell(|shell| shell.is_quiet())
+}
+
+/// Whether results are printed as json
+pub fn is_json() -> bool {
+    with_shell(|shell| shell.is_json())
+}
+
+/// Prints the value as a json document, see [`Shell::print_json`]
+pub fn print_json<T: Serialize>(value: &T) -> eyre::Result<()> {
+    with_shell(|shell| shell.print_json(value))
+}
+
+/// Returns the text in the colour if the output is colored, see [`Shell::paint`]
+pub fn paint(colour: Colour, text: impl fmt::Display) -> String {
+    with_shell(|shell| shell.paint(colour, text))
+}
+
+/// Returns a spinner with the message, which is hidden if progress isn't rendered
+///
+/// The spinner ticks on its own until it is finished.
+pub fn spinner(msg: impl Into<Cow<'static, str>>) -> ProgressBar {
+    if !with_shell(|shell| shell.is_progress_enabled()) {
+        return ProgressBar::hidden()
+    }
+    let spinner = ProgressBar::new_spinner();
+    spinner.set_style(ProgressStyle::default_spinner().template("{spinner} {msg}"));
+    spinner.set_message(msg);
+    spinner.enable_steady_tick(100);
+    spinner
+}
+
+/// Returns a progress bar of `len` steps with the message, which is hidden if progress isn't
+/// rendered
+pub fn progress_bar(len: u64, msg: impl Into<Cow<'static, str>>) -> ProgressBar {
+    if !with_shell(|shell| shell.is_progress_enabled()) {
+        return ProgressBar::hidden()
+    }
+    let bar = ProgressBar::new(len);
+    bar.set_style(
+        ProgressStyle::default_bar()
+            .template("{msg} [{bar:40}] {pos}/{len} ({elapsed})")
+            .progress_chars("=> "),
+    );
+    bar.set_message(msg);
+    bar
+}
+
+/// Prints a result with the shell of the process, see [`Shell::println`]
+macro_rules! sh_println {
+    () => {
+        $crate::shell::with_shell(|shell| shell.println(""))
+    };
+    ($($arg:tt)*) => {{
+        // the message is formatted first, it may use the shell itself
+        let msg = format!($($arg)*);
+        $crate::shell::with_shell(|shell| shell.println(msg))
+    }};
+}
+pub(crate) use sh_println;
+
+/// Prints a status message with the shell of the process, see [`Shell::status`]
+macro_rules! sh_status {
+    ($($arg:tt)*) => {{
+        // the message is formatted first, it may use the shell itself
+        let msg = format!($($arg)*);
+        $crate::shell::with_shell(|shell| shell.status(msg))
+    }};
+}
+pub(crate) use sh_status;
+
+/// Prints a warning with the shell of the process, see [`Shell::warn`]
+macro_rules! sh_warn {
+    ($($arg:tt)*) => {{
+        // the message is formatted first, it may use the shell itself
+        let msg = format!($($arg)*);
+        $crate::shell::with_shell(|shell| shell.warn(msg))
+    }};
+}
+pub(crate) use sh_warn;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    /// A sink whose content can be read after it was moved into a shell
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Buffer {
+        fn content(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn test_shell(verbosity: Verbosity, mode: OutputMode) -> (Shell, Buffer, Buffer) {
+        let (out, err) = (Buffer::default(), Buffer::default());
+        let shell =
+            Shell::new(verbosity, ColorChoice::Never, mode).with_output(out.clone(), err.clone());
+        (shell, out, err)
+    }
+
+    #[test]
+    fn respects_verbosity_and_mode() {
+        let (mut shell, out, err) = test_shell(Verbosity::Normal, OutputMode::Human);
+        shell.status("compiling...");
+        shell.println("result");
+        shell.warn("careful");
+        assert_eq!(out.content(), "compiling...\nresult\n");
+        assert_eq!(err.content(), "Warning: careful\n");
+
+        let (mut shell, out, err) = test_shell(Verbosity::Quiet, OutputMode::Human);
+        shell.status("compiling...");
+        shell.println("result");
+        shell.warn("careful");
+        shell.error("failed");
+        assert_eq!(out.content(), "result\n");
+        assert_eq!(err.content(), "Error: failed\n");
+
+        let (mut shell, out, err) = test_shell(Verbosity::Normal, OutputMode::Json);
+        shell.status("compiling...");
+        shell.println("result");
+        shell.print_json(&serde_json::json!({ "success": true })).unwrap();
+        assert_eq!(out.content(), "{\"success\":true}\n");
+        assert_eq!(err.content(), "compiling...\n");
+        assert!(!shell.is_progress_enabled());
+
+        let (mut shell, out, err) = test_shell(Verbosity::Silent, OutputMode::Json);
+        shell.println("result");
+        shell.print_json(&serde_json::json!({ "success": true })).unwrap();
+        shell.error("failed");
+        assert!(out.content().is_empty() && err.content().is_empty());
+    }
+}
@@ -1,6 +1,14 @@
-use std::path::PathBuf;
+use std::{collections::BTreeMap, path::Path, str::FromStr};
 
-use ethers::solc::{artifacts::Contract, EvmVersion};
+use crate::exit_code::ExitCode;
+
+use ethers::{
+    solc::{
+        artifacts::{CompactContract, Contract},
+        EvmVersion,
+    },
+    types::{Address, NameOrAddress, H256},
+};
 #[cfg(feature = "evmodin-evm")]
 use evmodin::Revision;
 use eyre::{ContextCompat, WrapErr};
@@ -14,8 +22,9 @@ pub use foundry_config::utils::*;
 /// Default local RPC endpoint
 const LOCAL_RPC_URL: &str = "http://127.0.0.1:8545";
 
-/// Default Path to where the contract artifacts are stored
-pub const DAPP_JSON: &str = "./out/dapp.sol.json";
+/// Printed when gas limits are disabled, so the results aren't mistaken for mainnet results
+pub(crate) const UNLIMITED_GAS_WARNING: &str =
+    "running with --unlimited-gas, gas limits are disabled and the sender is funded with an unlimited balance. Results don't reflect mainnet limits.";
 
 /// The version message for the current program, like
 /// `forge 0.1.0 (f01b232bc 2022-01-22T23:28:39.493201+00:00)`
@@ -43,55 +52,128 @@ pub fn rpc_url() -> String {
     std::env::var("ETH_RPC_URL").unwrap_or_else(|_| LOCAL_RPC_URL.to_string())
 }
 
-/// The path to where the contract artifacts are stored
-pub fn dapp_json_path() -> PathBuf {
-    PathBuf::from(DAPP_JSON)
+/// Resolves the given rpc url or the name of an endpoint in the `rpc_endpoints` of the current
+/// project's config to an url, See [`foundry_config::Config::resolve_rpc_url()`]
+pub fn resolve_rpc_url(url_or_alias: &str) -> eyre::Result<String> {
+    // urls can't be endpoint names, so there's no need to load the config
+    if url_or_alias.contains("://") {
+        return Ok(url_or_alias.to_string())
+    }
+    let config = foundry_config::Config::try_load_with_root(find_project_root_path()?)?;
+    config.sanitized().resolve_rpc_url(url_or_alias)
 }
 
-/// Tries to extract the `Contract` in the `DAPP_JSON` file
-pub fn find_dapp_json_contract(path: &str, name: &str) -> eyre::Result<Contract> {
-    let dapp_json = dapp_json_path();
-    let file = std::io::BufReader::new(std::fs::File::open(&dapp_json)?);
+/// Parses an address, with or without `0x` prefix, or else an ENS name which is resolved against
+/// the rpc url when it's used
+pub fn parse_name_or_address(s: &str) -> eyre::Result<NameOrAddress> {
+    let s = s.trim();
+    if let Ok(address) = s.parse::<Address>() {
+        return Ok(NameOrAddress::Address(address))
+    }
+    if s.starts_with("0x") || !s.contains('.') {
+        eyre::bail!("`{}` is neither an address nor an ENS name", s)
+    }
+    Ok(NameOrAddress::Name(s.to_string()))
+}
+
+/// Parses a 32 byte word like a storage slot or a salt, either a number or a hex value which is
+/// left padded with zeros
+pub fn parse_slot(s: &str) -> eyre::Result<H256> {
+    Ok(if s.starts_with("0x") {
+        let padded = format!("{:0>64}", s.strip_prefix("0x").unwrap());
+        H256::from_str(&padded)?
+    } else {
+        H256::from_low_u64_be(u64::from_str(s)?)
+    })
+}
+
+/// Reads all contracts of a dapptools build, keyed by `<source file>:<name>`
+///
+/// Both the standard json output, `.contracts.<file>.<name>`, and the combined json output,
+/// `.contracts["<file>:<name>"]`, of solc are supported.
+pub fn read_dapp_json(dapp_json: &Path) -> eyre::Result<BTreeMap<String, CompactContract>> {
+    let file = std::io::BufReader::new(
+        std::fs::File::open(dapp_json)
+            .wrap_err_with(|| format!("Failed to open `{}`", dapp_json.display()))?,
+    );
     let mut value: serde_json::Value =
         serde_json::from_reader(file).wrap_err("Failed to read DAPP_JSON artifacts")?;
-
     let contracts = value["contracts"]
         .as_object_mut()
+        .map(std::mem::take)
         .wrap_err_with(|| format!("No `contracts` found in `{}`", dapp_json.display()))?;
 
-    let contract = if let serde_json::Value::Object(mut contract) = contracts[path].take() {
-        contract
-            .remove(name)
-            .wrap_err_with(|| format!("No contract found at `.contract.{}.{}`", path, name))?
-    } else {
-        let key = format!("{}:{}", path, name);
-        contracts
-            .remove(&key)
-            .wrap_err_with(|| format!("No contract found at `.contract.{}`", key))?
+    let mut compact = BTreeMap::new();
+    for (key, value) in contracts {
+        if key.contains(':') {
+            compact.insert(key.clone(), combined_json_contract(&key, value)?);
+        } else if let serde_json::Value::Object(file) = value {
+            for (name, contract) in file {
+                let contract: Contract = serde_json::from_value(contract)
+                    .wrap_err_with(|| format!("Invalid contract `{}:{}`", key, name))?;
+                compact.insert(format!("{}:{}", key, name), contract.into());
+            }
+        }
+    }
+    Ok(compact)
+}
+
+/// Converts a contract of solc's combined json output, whose abi is a json string in older solc
+/// versions, into a `CompactContract`
+fn combined_json_contract(
+    key: &str,
+    mut contract: serde_json::Value,
+) -> eyre::Result<CompactContract> {
+    let abi = match contract["abi"].take() {
+        serde_json::Value::String(abi) => serde_json::from_str(&abi)?,
+        abi => abi,
     };
+    let standard = serde_json::json!({
+        "abi": abi,
+        "evm": {
+            "bytecode": { "object": contract["bin"].take() },
+            "deployedBytecode": { "object": contract["bin-runtime"].take() },
+        },
+    });
+    let contract: Contract =
+        serde_json::from_value(standard).wrap_err_with(|| format!("Invalid contract `{}`", key))?;
+    Ok(contract.into())
+}
+
+/// The EVM versions tests and scripts can be run with
+pub(crate) const SUPPORTED_EVM_VERSIONS: [EvmVersion; 3] =
+    [EvmVersion::Istanbul, EvmVersion::Berlin, EvmVersion::London];
 
-    Ok(serde_json::from_value(contract)?)
+/// Returns the config error of an EVM version the EVM can't run with
+fn unsupported_evm_version(evm: &EvmVersion) -> eyre::Report {
+    let supported = SUPPORTED_EVM_VERSIONS.iter().map(ToString::to_string).collect::<Vec<_>>();
+    ExitCode::ConfigError.error(format!(
+        "unsupported EVM version `{}`, expected one of {}",
+        evm,
+        supported.join(", ")
+    ))
 }
 
+/// Returns the sputnik config of the EVM version, failing for the versions sputnik can't run
 #[cfg(feature = "sputnik-evm")]
-pub fn sputnik_cfg(evm: &EvmVersion) -> Config {
-    match evm {
+pub fn sputnik_cfg(evm: &EvmVersion) -> eyre::Result<Config> {
+    Ok(match evm {
         EvmVersion::Istanbul => Config::istanbul(),
         EvmVersion::Berlin => Config::berlin(),
         EvmVersion::London => Config::london(),
-        _ => panic!("Unsupported EVM version"),
-    }
+        _ => return Err(unsupported_evm_version(evm)),
+    })
 }
 
 #[cfg(feature = "evmodin-evm")]
 #[allow(dead_code)]
-pub fn evmodin_cfg(evm: EvmVersion) -> Revision {
-    match evm {
+pub fn evmodin_cfg(evm: EvmVersion) -> eyre::Result<Revision> {
+    Ok(match evm {
         EvmVersion::Istanbul => Revision::Istanbul,
         EvmVersion::Berlin => Revision::Berlin,
         EvmVersion::London => Revision::London,
-        _ => panic!("Unsupported EVM version"),
-    }
+        _ => return Err(unsupported_evm_version(&evm)),
+    })
 }
 
 /// Securely reads a secret from stdin, or proceeds to return a fallback value
@@ -126,19 +208,77 @@ pub fn get_contract_name(id: &str) -> &str {
     id.rsplit(':').next().unwrap_or(id)
 }
 
-/// Conditionally print a message
-///
-/// This macro accepts a predicate and the message to print if the predicate is tru
-///
-/// ```rust
-/// let quiet = true;
-/// p_println!(!quiet => "message");
-/// ```
-macro_rules! p_println {
-    ($p:expr => $($arg:tt)*) => {{
-        if $p {
-            println!($($arg)*)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_parse_names_or_addresses() {
+        let address = "0xd8dA6BF26964aF9D7eEd9e03E53415D37aA96045".parse::<Address>().unwrap();
+        assert_eq!(
+            parse_name_or_address("0xd8dA6BF26964aF9D7eEd9e03E53415D37aA96045").unwrap(),
+            NameOrAddress::Address(address)
+        );
+        assert_eq!(
+            parse_name_or_address("d8da6bf26964af9d7eed9e03e53415d37aa96045").unwrap(),
+            NameOrAddress::Address(address)
+        );
+        assert_eq!(
+            parse_name_or_address("vitalik.eth").unwrap(),
+            NameOrAddress::Name("vitalik.eth".to_string())
+        );
+        assert!(parse_name_or_address("0x1234").is_err());
+        assert!(parse_name_or_address("vitalik").is_err());
+    }
+
+    #[cfg(feature = "sputnik-evm")]
+    #[test]
+    fn fails_for_unsupported_evm_versions() {
+        assert!(sputnik_cfg(&EvmVersion::London).is_ok());
+        let err = sputnik_cfg(&EvmVersion::Petersburg).unwrap_err();
+        assert_eq!(ExitCode::of(&err), ExitCode::ConfigError);
+        assert_eq!(
+            err.to_string(),
+            "unsupported EVM version `petersburg`, expected one of istanbul, berlin, london"
+        );
+    }
+
+    #[test]
+    fn can_read_dapp_json() {
+        let abi = r#"[{"type":"function","name":"testGm","inputs":[],"outputs":[],"stateMutability":"view"}]"#;
+        let dapp_json = format!(
+            r#"{{
+                "contracts": {{
+                    "src/Gm.t.sol": {{
+                        "GmTest": {{
+                            "abi": {abi},
+                            "evm": {{
+                                "bytecode": {{ "object": "6080" }},
+                                "deployedBytecode": {{ "object": "6081" }}
+                            }}
+                        }}
+                    }},
+                    "src/Gn.t.sol:GnTest": {{
+                        "abi": {abi:?},
+                        "bin": "6082",
+                        "bin-runtime": "6083"
+                    }}
+                }}
+            }}"#,
+            abi = abi
+        );
+        let path = std::env::temp_dir().join(format!("dapp-{}.sol.json", std::process::id()));
+        std::fs::write(&path, dapp_json).unwrap();
+        let contracts = read_dapp_json(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(contracts.len(), 2);
+        for (key, bin) in
+            [("src/Gm.t.sol:GmTest", [0x60, 0x80]), ("src/Gn.t.sol:GnTest", [0x60, 0x82])]
+        {
+            let contract = contracts[key].clone();
+            assert_eq!(contract.abi.unwrap().functions().count(), 1);
+            assert_eq!(contract.bin.and_then(|bin| bin.into_bytes()).unwrap().to_vec(), bin);
         }
-    }}
+    }
 }
-pub(crate) use p_println;
@@ -338,6 +338,11 @@ impl TestCommand {
         self.expect_success(output)
     }
 
+    /// Gets the output of a command, which may have failed
+    pub fn unchecked_output(&mut self) -> process::Output {
+        self.cmd.output().unwrap()
+    }
+
     /// Runs the command and prints its output
     pub fn print_output(&mut self) {
         let output = self.cmd.output().unwrap();
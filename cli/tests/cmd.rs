@@ -122,6 +122,33 @@ forgetest!(can_init_non_empty, |prj: TestProject, mut cmd: TestCommand| {
     assert!(prj.root().join("lib/ds-test").exists());
 });
 
+// checks that a project can be initialized from a local template without its history
+forgetest!(can_init_from_template, |prj: TestProject, mut cmd: TestCommand| {
+    let template = std::env::temp_dir().join(format!("forge-test-template-{}", std::process::id()));
+    pretty_err(&template, fs::create_dir_all(template.join("src")));
+    pretty_err(&template, fs::write(template.join("src/Template.sol"), "contract Template {}"));
+    pretty_err(&template, fs::write(template.join("README.md"), "template"));
+    for args in [
+        vec!["init", "--quiet"],
+        vec!["add", "."],
+        vec!["-c", "user.name=test", "-c", "user.email=test@test", "commit", "-qm", "template"],
+    ] {
+        let status =
+            std::process::Command::new("git").args(args).current_dir(&template).status().unwrap();
+        assert!(status.success());
+    }
+
+    prj.wipe();
+    prj.create_file("README.md", "existing");
+    cmd.arg("init").arg(prj.root()).arg("--template").arg(&template).arg("--no-git").arg("--force");
+    cmd.assert_non_empty_stdout();
+    assert!(prj.root().join("src/Template.sol").exists());
+    assert!(!prj.root().join(".git").exists());
+    // existing files aren't overwritten
+    assert_eq!(read_string(prj.root().join("README.md")), "existing");
+    pretty_err(&template, fs::remove_dir_all(&template));
+});
+
 // checks that config works
 // - foundry.toml is properly generated
 // - paths are resolved properly
@@ -243,6 +270,685 @@ forgetest!(can_clean_hardhat, PathStyle::HardHat, |prj: TestProject, mut cmd: Te
     prj.assert_cleaned();
 });
 
+// checks that `build --hardhat` writes hardhat compatible artifacts
+forgetest!(
+    can_write_hardhat_artifacts,
+    PathStyle::HardHat,
+    |prj: TestProject, mut cmd: TestCommand| {
+        prj.create_file(
+            "contracts/Greeter.sol",
+            r#"
+pragma solidity >=0.8.0;
+contract Greeter {
+    function greet() public pure returns (string memory) { return "gm"; }
+}
+"#,
+        );
+        // forge's own artifacts are detected in `artifacts` as well
+        cmd.args(["build", "--hardhat"]);
+        let output = cmd.unchecked_output();
+        assert!(!output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("set `out` to another directory"), "{}", stderr);
+
+        prj.create_file(
+            "foundry.toml",
+            r#"
+[default]
+out = "out"
+"#,
+        );
+        assert!(cmd.stdout().contains("hardhat artifacts"));
+
+        let artifact = prj.root().join("artifacts/contracts/Greeter.sol/Greeter.json");
+        let artifact: serde_json::Value = serde_json::from_str(&read_string(&artifact)).unwrap();
+        assert_eq!(artifact["_format"], "hh-sol-artifact-1");
+        assert_eq!(artifact["contractName"], "Greeter");
+        assert_eq!(artifact["sourceName"], "contracts/Greeter.sol");
+        assert!(artifact["deployedBytecode"].as_str().unwrap().len() > 2);
+    }
+);
+
+// checks that `build --sizes` prints the runtime size of every non test contract
+forgetest!(can_print_contract_sizes, |prj: TestProject, mut cmd: TestCommand| {
+    prj.create_file(
+        "src/Counter.sol",
+        r#"
+pragma solidity >=0.8.0;
+contract Counter {
+    uint256 public count;
+    function increment() public { count += 1; }
+}
+"#,
+    );
+    cmd.args(["build", "--sizes"]);
+    let stdout = cmd.stdout();
+    assert!(stdout.contains("Margin (B)"));
+    let line = stdout.lines().find(|line| line.starts_with("Counter")).unwrap();
+    let size: usize = line.split_whitespace().nth(1).unwrap().parse().unwrap();
+    assert!(size > 0 && size < 24576);
+
+    // the artifacts of removed sources are left in `out`, but aren't listed
+    std::fs::remove_file(prj.root().join("src/Counter.sol")).unwrap();
+    prj.create_file("src/Token.sol", "pragma solidity >=0.8.0;\ncontract Token {}\n");
+    cmd.set_cmd(prj.bin()).args(["build", "--sizes"]);
+    let stdout = cmd.stdout();
+    assert!(stdout.lines().any(|line| line.starts_with("Token")));
+    assert!(!stdout.lines().any(|line| line.starts_with("Counter")));
+});
+
+// checks that the `hooks` run before and after `build` with the context in their environment
+forgetest!(can_run_hooks, |prj: TestProject, mut cmd: TestCommand| {
+    prj.create_file(
+        "src/Counter.sol",
+        r#"
+pragma solidity >=0.8.0;
+contract Counter {
+    uint256 public count;
+}
+"#,
+    );
+    prj.create_file(
+        Config::FILE_NAME,
+        r#"
+[default.hooks]
+pre_build = "echo $FOUNDRY_HOOK > pre.txt"
+post_build = "echo $FOUNDRY_HOOK $FOUNDRY_SUCCESS $FOUNDRY_PROFILE > post.txt"
+"#,
+    );
+    // hooks are skipped unless they're allowed
+    cmd.arg("build");
+    cmd.assert_non_empty_stdout();
+    assert!(!prj.root().join("pre.txt").exists());
+
+    cmd.set_cmd(prj.bin()).args(["build", "--allow-hooks"]);
+    cmd.assert_non_empty_stdout();
+    assert_eq!(read_string(prj.root().join("pre.txt")).trim(), "pre_build");
+    assert_eq!(read_string(prj.root().join("post.txt")).trim(), "post_build true default");
+
+    // a failing `pre_` hook aborts the command
+    prj.create_file(Config::FILE_NAME, "[default.hooks]\npre_build = 'exit 1'\n");
+    cmd.set_cmd(prj.bin()).args(["build", "--allow-hooks"]);
+    cmd.assert_err();
+});
+
+// checks that `--offline` forks only a block whose state is cached and never queries the fork url
+forgetest!(can_only_fork_cached_blocks_offline, |prj: TestProject, mut cmd: TestCommand| {
+    prj.create_file(
+        "src/Counter.sol",
+        r#"
+pragma solidity >=0.8.0;
+contract Counter {
+    uint256 public count;
+}
+"#,
+    );
+    // the url is unreachable, so any request would fail too
+    cmd.args(["test", "--offline", "--fork-url", "http://127.0.0.1:1"]);
+    let output = cmd.unchecked_output();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("forking offline requires"), "{}", stderr);
+
+    cmd.args(["--fork-block-number", "1", "--chain-id", "1"]);
+    let output = cmd.unchecked_output();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("is not cached"), "{}", stderr);
+});
+
+// checks that `build --extract-metadata` writes the metadata of every contract next to its artifact
+forgetest!(can_extract_metadata, |prj: TestProject, mut cmd: TestCommand| {
+    prj.create_file(
+        "src/Counter.sol",
+        r#"
+pragma solidity >=0.8.0;
+contract Counter {
+    uint256 public count;
+}
+"#,
+    );
+    cmd.args(["build", "--extract-metadata"]);
+    cmd.assert_non_empty_stdout();
+
+    let metadata = prj.paths().artifacts.join("Counter.sol/Counter.metadata.json");
+    let metadata: serde_json::Value = serde_json::from_str(&read_string(metadata)).unwrap();
+    let target = metadata["settings"]["compilationTarget"].as_object().unwrap();
+    assert_eq!(target.values().next().unwrap(), "Counter");
+    assert_eq!(metadata["sources"].as_object().unwrap().len(), 1);
+});
+
+// checks that `build --build-info` writes the build info of the compilation and nothing if the
+// compilation was skipped
+forgetest!(can_write_build_info, |prj: TestProject, mut cmd: TestCommand| {
+    prj.create_file(
+        "src/Counter.sol",
+        r#"
+pragma solidity >=0.8.0;
+contract Counter {
+    uint256 public count;
+}
+"#,
+    );
+    cmd.args(["build", "--build-info"]);
+    assert!(cmd.stdout().contains("wrote 1 build info files"));
+
+    let dir = prj.paths().artifacts.join("build-info");
+    let files = std::fs::read_dir(&dir).unwrap().collect::<Vec<_>>();
+    assert_eq!(files.len(), 1);
+    let build_info = read_string(files[0].as_ref().unwrap().path());
+    let build_info: serde_json::Value = serde_json::from_str(&build_info).unwrap();
+    assert_eq!(build_info["input"]["sources"].as_object().unwrap().len(), 1);
+    assert_eq!(build_info["output"]["contracts"].as_object().unwrap().len(), 1);
+
+    cmd.set_cmd(prj.bin()).args(["build", "--build-info"]);
+    assert!(!cmd.stdout().contains("build info"));
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+});
+
+// checks that `--quiet` only prints results and `--json` only prints json to stdout
+forgetest!(can_print_quiet_and_json, |prj: TestProject, mut cmd: TestCommand| {
+    prj.create_file(
+        "src/Counter.sol",
+        r#"
+pragma solidity >=0.8.0;
+contract Counter {
+    uint256 public count;
+}
+"#,
+    );
+    cmd.args(["build", "--quiet"]);
+    assert!(cmd.stdout().trim().is_empty());
+
+    cmd.set_cmd(prj.bin()).args(["inspect", "Counter", "abi", "--json"]);
+    let abi: serde_json::Value = serde_json::from_str(&cmd.stdout()).unwrap();
+    assert!(abi.is_array());
+});
+
+// checks that `--deny-warnings` fails cached builds with the warnings of their last compilation
+forgetest!(can_deny_cached_warnings, |prj: TestProject, mut cmd: TestCommand| {
+    prj.create_file(
+        "src/Counter.sol",
+        r#"
+pragma solidity >=0.8.0;
+contract Counter {
+    function count() public pure {
+        uint256 unused;
+    }
+}
+"#,
+    );
+    cmd.args(["build"]);
+    assert!(cmd.output().status.success());
+
+    cmd.set_cmd(prj.bin()).args(["build", "--deny-warnings"]);
+    let output = cmd.unchecked_output();
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("no files changed"));
+    assert!(prj.paths().cache.with_file_name("compiler-warnings.json").exists());
+});
+
+// checks that failing commands exit with the code of their failure and print nothing if silent
+forgetest!(can_exit_with_code, |prj: TestProject, mut cmd: TestCommand| {
+    prj.create_file("src/Broken.sol", "pragma solidity >=0.8.0; contract Broken {");
+    cmd.args(["build", "--silent"]);
+    let output = cmd.unchecked_output();
+    assert_eq!(output.status.code(), Some(2));
+    assert!(output.stdout.is_empty() && output.stderr.is_empty());
+
+    cmd.set_cmd(prj.bin()).args(["build", "--unknown-flag"]);
+    assert_eq!(cmd.unchecked_output().status.code(), Some(3));
+
+    // invalid configs are errors, not panics
+    prj.create_file("foundry.toml", "[default]\noptimizer_runs = \"many\"\n");
+    cmd.set_cmd(prj.bin()).args(["build", "--silent"]);
+    let output = cmd.unchecked_output();
+    assert_eq!(output.status.code(), Some(3));
+    assert!(output.stdout.is_empty() && output.stderr.is_empty());
+});
+
+// checks that failing tests print the Solidity stack trace of their revert
+forgetest!(can_print_stack_traces, |prj: TestProject, mut cmd: TestCommand| {
+    prj.create_file(
+        "src/Vault.t.sol",
+        r#"
+pragma solidity >=0.8.0;
+library Checks {
+    function positive(uint256 amount) internal pure {
+        require(amount > 1);
+    }
+}
+contract Vault {
+    function withdraw(uint256 amount) external pure {
+        Checks.positive(amount);
+    }
+}
+contract VaultTest {
+    function testWithdraw() public {
+        new Vault().withdraw(1);
+    }
+}
+"#,
+    );
+    cmd.args(["test", "-vv"]);
+    let output = cmd.unchecked_output();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("`require` failed in `require(amount > 1)`"));
+    assert!(stdout.contains("--> src/Vault.t.sol:5"));
+    assert!(stdout.contains("Stack trace:"));
+    assert!(stdout.contains("Vault.withdraw (src/Vault.t.sol:10)"));
+    assert!(stdout.contains("→ Checks.positive (src/Vault.t.sol:5)"));
+});
+
+// checks that `--gas-hints` reports the zero-to-nonzero writes of a loop at their source line
+forgetest!(can_print_gas_hints, |prj: TestProject, mut cmd: TestCommand| {
+    prj.create_file(
+        "src/Fill.t.sol",
+        r#"
+pragma solidity >=0.8.0;
+contract FillTest {
+    mapping(uint256 => uint256) values;
+    function testFill() public {
+        for (uint256 i = 0; i < 3; i++) {
+            values[i] = 1;
+        }
+    }
+}
+"#,
+    );
+    cmd.args(["test", "--gas-hints"]);
+    let stdout = cmd.stdout();
+    assert!(stdout.contains("Gas hints:"));
+    assert!(stdout.contains("src/Fill.t.sol:7: 3 zero-to-nonzero storage writes in a loop"));
+});
+
+// checks that `--decode-internal` prints the internal calls of the tests and their gas profile
+forgetest!(can_decode_internal_calls, |prj: TestProject, mut cmd: TestCommand| {
+    prj.create_file(
+        "src/Sum.t.sol",
+        r#"
+pragma solidity >=0.8.0;
+contract SumTest {
+    uint256 total;
+    function add(uint256 amount) internal {
+        total += amount;
+    }
+    function testSum() public {
+        add(1);
+        add(2);
+    }
+}
+"#,
+    );
+    cmd.args(["test", "--decode-internal"]);
+    let stdout = cmd.stdout();
+    assert!(stdout.contains("== Internal Calls =="));
+    assert!(stdout.contains("SumTest::testSum()"));
+    assert!(stdout.contains("└─ add ["));
+    assert!(stdout.contains("== Internal Gas Profile =="));
+    assert!(stdout.contains("SumTest.add"));
+});
+
+// checks that `lint` reports the findings of the lints with their configured severity
+forgetest!(can_lint, |prj: TestProject, mut cmd: TestCommand| {
+    prj.create_file(
+        "src/Wallet.sol",
+        r#"
+pragma solidity >=0.8.0;
+interface IERC20 {
+    function transfer(address to, uint256 amount) external returns (bool);
+}
+contract Wallet {
+    address owner;
+    function setOwner(address newOwner) external {
+        require(tx.origin == owner);
+        owner = newOwner;
+    }
+    function send(IERC20 token, address to) external {
+        token.transfer(to, 1);
+    }
+}
+"#,
+    );
+    cmd.args(["lint"]);
+    let output = cmd.unchecked_output();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stdout.contains("error[tx-origin]"));
+    assert!(stdout.contains("warning[unchecked-transfer]"));
+    assert!(stdout.contains("info[missing-zero-check]"));
+    assert!(stdout.contains("--> src/Wallet.sol:9:17"));
+
+    prj.create_file(Config::FILE_NAME, "[default.lints]\ntx-origin = 'off'\n");
+    cmd.set_cmd(prj.bin()).args(["lint", "--sarif"]);
+    let sarif: serde_json::Value = serde_json::from_str(&cmd.stdout()).unwrap();
+    let results = sarif["runs"][0]["results"].as_array().unwrap();
+    assert!(results.iter().all(|result| result["ruleId"] != "tx-origin"));
+    assert!(results.iter().any(|result| result["ruleId"] == "unchecked-transfer"));
+});
+
+// checks that `geiger` finds dangerous cheatcodes in the project and unsafe patterns in the
+// dependencies
+forgetest!(can_scan_with_geiger, |prj: TestProject, mut cmd: TestCommand| {
+    prj.create_file(
+        "src/Ffi.t.sol",
+        r#"
+pragma solidity >=0.8.0;
+contract FfiTest {
+    function testFfi() public {
+        // vm.ffi(inputs) in a comment is ignored
+        vm.etch(address(1), hex"00");
+        vm.ffi(inputs);
+    }
+}
+"#,
+    );
+    prj.create_file(
+        "lib/dep/src/Dep.sol",
+        r#"
+pragma solidity >=0.8.0;
+contract Dep {
+    function kill() external {
+        selfdestruct(payable(msg.sender));
+    }
+}
+"#,
+    );
+    cmd.args(["geiger"]);
+    let output = cmd.unchecked_output();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stdout.contains("error[ffi]"));
+    assert!(stdout.contains("--> src/Ffi.t.sol:7:12"));
+    assert!(stdout.contains("warning[etch]"));
+    assert!(stdout.contains("warning[selfdestruct]"));
+
+    prj.create_file(Config::FILE_NAME, "[default.geiger]\nffi = 'info'\n");
+    cmd.set_cmd(prj.bin()).args(["geiger", "--no-deps", "--json"]);
+    let findings: serde_json::Value = serde_json::from_str(&cmd.stdout()).unwrap();
+    let findings = findings.as_array().unwrap();
+    assert_eq!(findings.len(), 2);
+    assert!(findings.iter().any(|f| f["lint"] == "ffi" && f["severity"] == "info"));
+
+    cmd.set_cmd(prj.bin()).args(["geiger", "--no-deps", "--fail-on", "warning"]);
+    cmd.assert_err();
+
+    // configured hooks are findings of the config file
+    prj.create_file(Config::FILE_NAME, "[default.hooks]\npre_build = 'make codegen'\n");
+    cmd.set_cmd(prj.bin()).args(["geiger", "--no-deps"]);
+    let stdout = String::from_utf8_lossy(&cmd.unchecked_output().stdout).to_string();
+    assert!(stdout.contains("warning[hooks]"));
+    assert!(stdout.contains("--> foundry.toml:2:1"));
+});
+
+// checks that `mutate` kills the mutants the tests catch and reports the surviving ones
+forgetest!(can_mutate, |prj: TestProject, mut cmd: TestCommand| {
+    prj.create_file(
+        "src/Math.sol",
+        r#"
+pragma solidity >=0.8.0;
+contract Math {
+    function add(uint256 a, uint256 b) public pure returns (uint256) {
+        return a + b;
+    }
+    function isPositive(uint256 a) public pure returns (bool) {
+        return a > 0;
+    }
+}
+"#,
+    );
+    prj.create_file(
+        "src/Math.t.sol",
+        r#"
+pragma solidity >=0.8.0;
+import "./Math.sol";
+contract MathTest {
+    function testMath() public {
+        Math math = new Math();
+        require(math.add(1, 2) == 3);
+        require(math.isPositive(5));
+    }
+}
+"#,
+    );
+    cmd.args(["mutate"]);
+    let stdout = cmd.stdout();
+    assert!(stdout.contains("Mutants: 1 killed, 1 survived, 0 equivalent, 0 invalid"));
+    assert!(stdout.contains("Mutation score: 50.00%"));
+    assert!(stdout.contains("src/Math.sol:8: `>` → `>=`"));
+});
+
+// checks that the state of a fixture is applied to every test contract inheriting it
+forgetest!(can_share_fixtures, |prj: TestProject, mut cmd: TestCommand| {
+    prj.create_file(
+        "src/Token.t.sol",
+        r#"
+pragma solidity >=0.8.0;
+contract Token {
+    mapping(address => uint256) public balanceOf;
+    constructor() {
+        balanceOf[msg.sender] = 100;
+    }
+}
+contract TokenFixture {
+    Token token;
+    function fixtureToken() public {
+        token = new Token();
+    }
+}
+contract BalanceTest is TokenFixture {
+    function testBalance() public {
+        require(token.balanceOf(address(this)) == 100);
+    }
+}
+contract SupplyTest is TokenFixture {
+    function testSupply() public {
+        require(address(token).code.length > 0);
+    }
+}
+"#,
+    );
+    cmd.args(["test"]);
+    let stdout = cmd.stdout();
+    assert!(stdout.contains("[PASS] testBalance()"));
+    assert!(stdout.contains("[PASS] testSupply()"));
+
+    // the state changes are kept for the next run
+    assert!(prj.root().join("cache/fixtures/fixtures.json").exists());
+    cmd.set_cmd(prj.bin()).args(["test"]);
+    let stdout = cmd.stdout();
+    assert!(stdout.contains("[PASS] testBalance()"));
+    assert!(stdout.contains("[PASS] testSupply()"));
+});
+
+// checks that `--rerun-failures` only runs the tests that failed in the last run
+forgetest!(can_rerun_failures, |prj: TestProject, mut cmd: TestCommand| {
+    prj.create_file(
+        "src/Rerun.t.sol",
+        r#"
+pragma solidity >=0.8.0;
+contract RerunTest {
+    function testAlwaysPasses() public {}
+    function testFail() public {}
+    function testBroken() public {
+        require(false);
+    }
+}
+"#,
+    );
+    cmd.args(["test"]);
+    let output = cmd.unchecked_output();
+    assert!(String::from_utf8_lossy(&output.stdout).contains("testAlwaysPasses()"));
+
+    cmd.set_cmd(prj.bin()).args(["test", "--rerun-failures"]);
+    let output = cmd.unchecked_output();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("[FAIL] testBroken()"));
+    assert!(stdout.contains("[FAIL] testFail()"));
+    assert!(!stdout.contains("testAlwaysPasses()"));
+
+    cmd.set_cmd(prj.bin()).args(["test", "--failed-first"]);
+    let output = cmd.unchecked_output();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.find("testBroken()").unwrap() < stdout.find("testAlwaysPasses()").unwrap());
+});
+
+// checks that `--evm-versions` runs the tests once per EVM version and summarizes the results
+forgetest!(can_test_evm_versions, |prj: TestProject, mut cmd: TestCommand| {
+    prj.create_file(
+        "src/BaseFee.t.sol",
+        r#"
+pragma solidity >=0.8.7;
+contract BaseFeeTest {
+    function testBaseFee() public view {
+        require(block.basefee == 0);
+    }
+}
+"#,
+    );
+    cmd.args(["test", "--evm-versions", "berlin,london"]);
+    let output = cmd.unchecked_output();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Testing with EVM version berlin"));
+    assert!(stdout.contains("berlin: 0 passed, 1 failed"));
+    assert!(stdout.contains("london: 1 passed, 0 failed"));
+    assert!(stdout.contains("testBaseFee() [berlin]"));
+});
+
+// checks that `inspect` prints the storage layout and fails on incompatible upgrades
+forgetest!(can_inspect_storage_layout, |prj: TestProject, mut cmd: TestCommand| {
+    prj.create_file(
+        "src/Impl.sol",
+        r#"
+pragma solidity >=0.8.0;
+contract ImplV1 {
+    address public owner;
+    uint256 public count;
+}
+contract ImplV2 {
+    address public owner;
+    uint256 public count;
+    bool public paused;
+}
+contract ImplV3 {
+    uint256 public count;
+    address public owner;
+}
+"#,
+    );
+    cmd.args(["inspect", "ImplV1", "storage-layout"]);
+    let stdout = cmd.stdout();
+    assert!(stdout.contains("owner") && stdout.contains("count"));
+
+    cmd.set_cmd(prj.bin()).args(["inspect", "ImplV2", "storage-layout", "--compare", "ImplV1"]);
+    assert!(cmd.stdout().contains("storage layout is compatible"));
+
+    cmd.set_cmd(prj.bin()).args(["inspect", "ImplV3", "storage-layout", "--compare", "ImplV1"]);
+    cmd.assert_err();
+});
+
+// checks that `upgrade-check` fails on unsafe storage layout changes and warns about selfdestruct
+forgetest!(can_check_upgrade_safety, |prj: TestProject, mut cmd: TestCommand| {
+    prj.create_file(
+        "src/Impl.sol",
+        r#"
+pragma solidity >=0.8.0;
+contract ImplV1 {
+    address public owner;
+    uint256[49] private __gap;
+}
+contract ImplV2 {
+    address public owner;
+    uint256 public count;
+    uint256[48] private __gap;
+    function kill() external { selfdestruct(payable(owner)); }
+}
+contract ImplV3 {
+    uint256 public count;
+    address public owner;
+    uint256[49] private __gap;
+}
+"#,
+    );
+    cmd.args(["upgrade-check", "ImplV1", "ImplV2"]);
+    let stdout = cmd.stdout();
+    assert!(stdout.contains("selfdestruct") && stdout.contains("is a safe upgrade"));
+
+    cmd.set_cmd(prj.bin()).args(["upgrade-check", "ImplV1", "ImplV2", "--deny-warnings"]);
+    cmd.assert_err();
+
+    cmd.set_cmd(prj.bin()).args(["upgrade-check", "ImplV1", "ImplV3"]);
+    assert_eq!(cmd.unchecked_output().status.code(), Some(1));
+});
+
+// checks that `flatten` emits every import once, in order, with a single license and pragma
+forgetest!(can_flatten_with_remappings, |prj: TestProject, mut cmd: TestCommand| {
+    prj.create_file(
+        "lib/token/src/Token.sol",
+        r#"
+// SPDX-License-Identifier: MIT
+pragma solidity >=0.8.0;
+import "./Base.sol";
+contract Token is Base {}
+"#,
+    );
+    prj.create_file(
+        "lib/token/src/Base.sol",
+        r#"
+// SPDX-License-Identifier: MIT
+pragma solidity >=0.8.0;
+contract Base {}
+"#,
+    );
+    let target = prj.create_file(
+        "src/Vault.sol",
+        r#"
+// SPDX-License-Identifier: UNLICENSED
+pragma solidity >=0.8.0;
+import {Token} from "token/Token.sol";
+import "token/Base.sol";
+contract Vault is Token {}
+"#,
+    );
+    cmd.args(["flatten", "--remappings", "token/=lib/token/src/"]).arg(target);
+    let stdout = cmd.stdout();
+
+    assert!(stdout.contains("// SPDX-License-Identifier: UNLICENSED AND MIT"));
+    assert_eq!(stdout.matches("pragma solidity").count(), 1);
+    assert!(!stdout.contains("import"));
+    let base = stdout.find("contract Base").unwrap();
+    let token = stdout.find("contract Token").unwrap();
+    let vault = stdout.find("contract Vault").unwrap();
+    assert!(base < token && token < vault);
+});
+
+// checks that `bind` generates a bindings crate and typings for every non test contract
+forgetest!(can_generate_bindings, |prj: TestProject, mut cmd: TestCommand| {
+    prj.create_file(
+        "src/Counter.sol",
+        r#"
+pragma solidity >=0.8.0;
+contract Counter {
+    uint256 public count;
+    function increment() public { count += 1; }
+}
+"#,
+    );
+    cmd.args(["bind", "--typescript"]);
+    assert!(cmd.stdout().contains("generated bindings of 1 contracts"));
+
+    let bindings = prj.paths().artifacts.join("bindings");
+    assert!(bindings.join("Cargo.toml").exists());
+    assert!(bindings.join("abi/Counter.json").exists());
+    assert!(bindings.join("src/counter.rs").exists());
+    assert!(read_string(bindings.join("src/lib.rs")).contains("pub mod counter;"));
+    assert!(read_string(bindings.join("typescript/Counter.ts")).contains("CounterAbi"));
+
+    // unchanged contracts are not regenerated
+    cmd.set_cmd(prj.bin()).args(["bind", "--typescript"]);
+    assert!(cmd.stdout().contains("generated bindings of 0 contracts"));
+});
+
 // test against a local checkout, useful to debug with local ethers-rs patch
 forgetest_ignore!(can_compile_local_spells, |_: TestProject, mut cmd: TestCommand| {
     let current_dir = std::env::current_dir().unwrap();
@@ -268,3 +974,24 @@ forgetest_ignore!(can_compile_local_spells, |_: TestProject, mut cmd: TestComman
     ]);
     cmd.print_output();
 });
+
+// checks that `remappings --write` collects the remappings declared by dependencies
+forgetest!(can_write_dependency_remappings, |prj: TestProject, mut cmd: TestCommand| {
+    prj.create_file("lib/dep-a/src/A.sol", "contract A {}");
+    prj.create_file("lib/dep-a/remappings.txt", "solmate/=lib/solmate/src/");
+    prj.create_file("lib/dep-b/src/B.sol", "contract B {}");
+    prj.create_file("lib/dep-b/remappings.txt", "solmate/=lib/solmate-fork/src/");
+
+    cmd.set_current_dir(prj.root());
+    cmd.args(["remappings", "--write"]);
+    cmd.assert_non_empty_stdout();
+
+    let remappings = read_string(prj.root().join("remappings.txt"));
+    // the first dependency takes precedence
+    assert!(remappings.contains("solmate/=lib/dep-a/lib/solmate/src/"));
+    assert!(!remappings.contains("solmate-fork"));
+
+    // the conflict fails the check
+    cmd.set_cmd(prj.bin()).args(["remappings", "--check"]);
+    cmd.assert_err();
+});
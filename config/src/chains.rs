@@ -0,0 +1,89 @@
+//! Well-known chains that the `chain` setting can be set to
+use serde::{Deserialize, Deserializer};
+
+/// A well-known chain with the settings that are derived from it
+///
+/// The rpc endpoint of a chain is the entry of `rpc_endpoints` with the name of the chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChainPreset {
+    /// The name of the chain, like `mainnet`
+    pub name: &'static str,
+    /// The chain id
+    pub id: u64,
+    /// The url of the etherscan api of the chain, or of the etherscan compatible explorer
+    pub etherscan_api_url: &'static str,
+    /// The url of the explorer website
+    pub etherscan_browser_url: &'static str,
+}
+
+/// All well-known chains
+pub const CHAIN_PRESETS: &[ChainPreset] = &[
+    preset("mainnet", 1, "https://api.etherscan.io/api", "https://etherscan.io"),
+    preset("ropsten", 3, "https://api-ropsten.etherscan.io/api", "https://ropsten.etherscan.io"),
+    preset("rinkeby", 4, "https://api-rinkeby.etherscan.io/api", "https://rinkeby.etherscan.io"),
+    preset("goerli", 5, "https://api-goerli.etherscan.io/api", "https://goerli.etherscan.io"),
+    preset("kovan", 42, "https://api-kovan.etherscan.io/api", "https://kovan.etherscan.io"),
+    preset(
+        "optimism",
+        10,
+        "https://api-optimistic.etherscan.io/api",
+        "https://optimistic.etherscan.io",
+    ),
+    preset("bsc", 56, "https://api.bscscan.com/api", "https://bscscan.com"),
+    preset("bsc-testnet", 97, "https://api-testnet.bscscan.com/api", "https://testnet.bscscan.com"),
+    preset("polygon", 137, "https://api.polygonscan.com/api", "https://polygonscan.com"),
+    preset(
+        "mumbai",
+        80001,
+        "https://api-testnet.polygonscan.com/api",
+        "https://mumbai.polygonscan.com",
+    ),
+    preset("fantom", 250, "https://api.ftmscan.com/api", "https://ftmscan.com"),
+    preset("arbitrum", 42161, "https://api.arbiscan.io/api", "https://arbiscan.io"),
+    preset("avalanche", 43114, "https://api.snowtrace.io/api", "https://snowtrace.io"),
+];
+
+const fn preset(
+    name: &'static str,
+    id: u64,
+    etherscan_api_url: &'static str,
+    etherscan_browser_url: &'static str,
+) -> ChainPreset {
+    ChainPreset { name, id, etherscan_api_url, etherscan_browser_url }
+}
+
+impl ChainPreset {
+    /// Returns the chain with the name, case insensitive
+    pub fn named(name: &str) -> Option<&'static ChainPreset> {
+        CHAIN_PRESETS.iter().find(|chain| chain.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Returns the chain with the chain id
+    pub fn with_id(id: u64) -> Option<&'static ChainPreset> {
+        CHAIN_PRESETS.iter().find(|chain| chain.id == id)
+    }
+
+    /// Returns the names of all chains
+    pub fn names() -> impl Iterator<Item = &'static str> {
+        CHAIN_PRESETS.iter().map(|chain| chain.name)
+    }
+}
+
+/// Deserializes the name of a well-known chain, failing for other names
+pub(crate) fn deserialize_name<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let name = match Option::<String>::deserialize(deserializer)? {
+        Some(name) => name,
+        None => return Ok(None),
+    };
+    match ChainPreset::named(&name) {
+        Some(chain) => Ok(Some(chain.name.to_string())),
+        None => Err(serde::de::Error::custom(format!(
+            "unknown chain `{}`, expected one of {}",
+            name,
+            ChainPreset::names().collect::<Vec<_>>().join(", ")
+        ))),
+    }
+}
@@ -1,12 +1,14 @@
 //! foundry configuration.
 use std::{
     borrow::Cow,
+    collections::BTreeMap,
+    fmt,
     path::{Path, PathBuf},
 };
 
 use figment::{
     providers::{Env, Format, Serialized, Toml},
-    value::{Dict, Map},
+    value::{Dict, Map, Value},
     Error, Figment, Metadata, Profile, Provider,
 };
 // reexport so cli types can implement `figment::Provider` to easily merge compiler arguments
@@ -14,13 +16,20 @@ pub use figment;
 use semver::Version;
 use serde::{Deserialize, Serialize};
 
-use ethers_core::types::{Address, U256};
+use ethers_core::{
+    types::{Address, U256},
+    utils::hex,
+};
 use ethers_solc::{
-    artifacts::{Optimizer, Settings},
+    artifacts::{
+        BytecodeHash, BytecodeObject, CompactContract, DebuggingSettings, Optimizer,
+        OptimizerDetails, RevertStrings, Settings, SettingsMetadata,
+    },
     error::SolcError,
     remappings::{RelativeRemapping, Remapping},
-    EvmVersion, Project, ProjectPathsConfig, SolcConfig,
+    EvmVersion, Graph, Project, ProjectPathsConfig, Solc, SolcConfig, Source,
 };
+use eyre::WrapErr;
 use figment::providers::Data;
 use inflector::Inflector;
 
@@ -31,6 +40,12 @@ mod macros;
 pub mod utils;
 pub use crate::utils::*;
 
+mod validate;
+pub use validate::ConfigIssue;
+
+mod chains;
+pub use chains::{ChainPreset, CHAIN_PRESETS};
+
 /// Foundry configuration
 ///
 /// # Defaults
@@ -78,8 +93,23 @@ pub struct Config {
     pub test: PathBuf,
     /// path to where artifacts shut be written to
     pub out: PathBuf,
+    /// path of the directory the compiler cache is written to
+    pub cache_path: PathBuf,
+    /// Whether `forge build` writes a Hardhat style build info file with the standard json input
+    /// and output of every compiler version to the `build_info_path`
+    pub build_info: bool,
+    /// The directory the build info files are written to, `<out>/build-info` if not set
+    pub build_info_path: Option<PathBuf>,
     /// all library folders to include, `lib`, `node_modules`
     pub libs: Vec<PathBuf>,
+    /// folders of precompiled Hardhat or Truffle artifacts, like `artifacts`, whose contracts are
+    /// identified in traces
+    pub external_artifacts: Vec<PathBuf>,
+    /// The crate `forge build` (re)generates the Rust bindings of all contracts in, if set
+    pub bindings: Option<PathBuf>,
+    /// The registry of the contracts deployed by broadcasts, written to
+    /// `<deployments>/<chain id>/<contract>.json` and read by the `getDeployment` cheatcode
+    pub deployments: PathBuf,
     /// `Remappings` to use for this repo
     pub remappings: Vec<RelativeRemapping>,
     /// library addresses to link
@@ -98,20 +128,75 @@ pub struct Config {
     pub solc_version: Option<Version>,
     /// whether to autodetect the solc compiler version to use
     pub auto_detect_solc: bool,
+    /// The `vyper` binary the `.vy` sources are compiled with, like `vyper` or a path to it
+    ///
+    /// `.yul` sources are always compiled with solc, `.vy` sources are skipped if this isn't set.
+    pub vyper: Option<PathBuf>,
     /// Whether to activate optimizer
     pub optimizer: bool,
     /// Sets the optimizer runs
     pub optimizer_runs: usize,
+    /// Switches the optimizer steps on or off, like `yul`, `deduplicate` or `cse`, unset steps
+    /// use solc's default for the `optimizer` setting
+    pub optimizer_details: Option<OptimizerDetails>,
+    /// Whether to compile through the Yul IR pipeline
+    pub via_ir: bool,
+    /// The hash of the metadata that's appended to the bytecode, `ipfs`, `bzzr1` or `none`
+    pub bytecode_hash: BytecodeHash,
+    /// How revert reason strings are generated, `default`, `strip`, `debug` or `verboseDebug`
+    pub revert_strings: Option<RevertStrings>,
+    /// The outputs of solc that `forge build` writes next to the artifact of every contract, like
+    /// `ir` or `storageLayout`, they're selected in the same compiler run as the artifacts
+    pub extra_output: Vec<ExtraOutput>,
     /// verbosity to use
     pub verbosity: u8,
+    /// The chain whose system contracts and precompiles are available during execution
+    pub chain_profile: ChainProfile,
+    /// The maximum number of steps the debugger records per call, the oldest steps are dropped
+    /// once the limit is reached
+    pub debug_step_limit: Option<usize>,
+    /// A well-known chain, like `mainnet` or `optimism`, that sets the `chain_id`, the
+    /// `eth_rpc_url` to the endpoint of `rpc_endpoints` named like the chain and the
+    /// `etherscan_api_url`, unless they're set
+    ///
+    /// See [`CHAIN_PRESETS`] for all chains.
+    #[serde(default, deserialize_with = "chains::deserialize_name")]
+    pub chain: Option<String>,
     /// url of the rpc server that should be used for any rpc calls
     pub eth_rpc_url: Option<String>,
+    /// The url of the etherscan api, or of an etherscan compatible explorer, contracts are
+    /// verified with, derived from the chain id if not set
+    pub etherscan_api_url: Option<String>,
+    /// The url of the IPFS http api the metadata of contracts is pinned to with
+    /// `--pin-metadata`, a local IPFS node if not set
+    pub ipfs_api_url: Option<String>,
     /// list of solidity error codes to always silence
     pub ignored_error_codes: Vec<u64>,
+    /// The solidity error codes silenced for the sources under a path relative to the root, like
+    /// `lib = []`, an empty list silences all warnings of the sources
+    pub ignored_error_codes_by_path: BTreeMap<String, Vec<u64>>,
+    /// Whether the compiler's warnings that aren't silenced fail the build
+    pub deny_warnings: bool,
     /// The number of test cases that must execute for each property test
     pub fuzz_runs: u32,
+    /// The directory the inputs that increased the coverage of each fuzz test are kept in, they're
+    /// mutated into new inputs by later runs
+    pub fuzz_corpus: Option<PathBuf>,
+    /// The bounds of the fuzzed values of dynamic types, the `[fuzz]` section
+    pub fuzz: FuzzSettings,
+    /// Only contracts inheriting one of these contracts, like `DSTest`, are test contracts
+    ///
+    /// If neither `test_bases` nor `test_globs` is set, every contract with `test` functions is
+    /// a test contract.
+    pub test_bases: Vec<String>,
+    /// Only contracts whose source file matches one of these globs relative to the root, like
+    /// `src/test/**`, are test contracts, in addition to the ones of the `test_bases`
+    pub test_globs: Vec<String>,
     /// Whether to allow ffi cheatcodes in test
     pub ffi: bool,
+    /// Disables the transaction and block gas limits and funds the sender with an unlimited
+    /// balance, the results don't reflect mainnet limits
+    pub unlimited_gas: bool,
     /// The address which will be executing all tests
     pub sender: Address,
     /// The tx.origin value during EVM execution
@@ -122,6 +207,19 @@ pub struct Config {
     pub block_number: u64,
     /// pins the block number for the state fork
     pub fork_block_number: Option<u64>,
+    /// Disables the on-disk cache of the state fetched from a pinned fork
+    pub no_storage_caching: bool,
+    /// Verifies all state fetched from the fork via `eth_getProof` against the block's state root
+    pub verify_fork_state: bool,
+    /// Forbids all network access: a fork is served from the on-disk cache of previous runs only,
+    /// the solc versions of the sources must already be installed and explorer lookups are
+    /// skipped
+    pub offline: bool,
+    /// Accounts that are fetched concurrently from the fork before the tests are executed
+    pub prefetch_accounts: Vec<Address>,
+    /// Path to a json file with geth-style state overrides (balance, nonce, code, storage per
+    /// address) that are applied on top of the (forked) state before execution
+    pub state_overrides: Option<PathBuf>,
     /// the chainid opcode value
     pub chain_id: Option<Chain>,
     /// Block gas limit
@@ -156,6 +254,40 @@ pub struct Config {
     // }
     // "#
     pub solc_settings: Option<String>,
+    /// Named rpc endpoints, like `mainnet = "https://eth-mainnet.alchemyapi.io/v2/${API_KEY}"`
+    ///
+    /// The names can be used instead of an url wherever an rpc url is expected, `${ENV_VAR}`
+    /// references in the url are interpolated when the endpoint is resolved, See
+    /// [`Config::resolve_rpc_url()`]
+    pub rpc_endpoints: BTreeMap<String, String>,
+    /// Settings that only apply to the test contracts whose source file matches a glob relative
+    /// to the root, like `[default.overrides."src/test/fork/**"]`
+    ///
+    /// If several globs match a file, the first one in lexicographical order is used.
+    pub overrides: BTreeMap<String, ConfigOverride>,
+    /// The block explorers contracts are verified with and the ABIs of verified contracts are
+    /// fetched from, by the name or the id of their chain, like `[default.explorers.gnosis]`
+    ///
+    /// Chains without an explorer use etherscan, See [`Config::explorer()`].
+    pub explorers: BTreeMap<String, ExplorerConfig>,
+    /// Contracts compiled outside of forge, like with Huff, registered by name from their abi and
+    /// bytecode files, like `[default.bytecode_contracts.Token]`
+    ///
+    /// They're identified in traces and can be deployed with the `deployCode` cheatcode.
+    pub bytecode_contracts: BTreeMap<String, BytecodeContract>,
+    /// The severity of the findings of `forge lint` by lint, like `tx-origin = "error"`
+    ///
+    /// `solc` sets the severity of the compiler's warnings, lints that aren't set keep their
+    /// default severity and `off` disables a lint.
+    pub lints: BTreeMap<String, LintSeverity>,
+    /// The severity of the findings of `forge geiger` by rule, like `ffi = "warning"`
+    ///
+    /// Rules that aren't set keep their default severity and `off` disables a rule.
+    pub geiger: BTreeMap<String, LintSeverity>,
+    /// Shell commands run at points of the lifecycle of forge commands, like `pre_build`
+    ///
+    /// See [`Hooks`] for the environment variables the commands are run with.
+    pub hooks: Hooks,
     /// The root path where the config detection started from, `Config::with_root`
     ///
     /// **Note:** This field is never serialized nor deserialized. This is merely used to provided
@@ -189,6 +321,12 @@ impl Config {
     /// File name of config toml file
     pub const FILE_NAME: &'static str = "foundry.toml";
 
+    /// File name of the compiler cache in the `cache_path`
+    pub const FILES_CACHE_FILE_NAME: &'static str = "solidity-files-cache.json";
+
+    /// The table in the config toml file that contains the profiles, e.g. `[profile.ci]`
+    pub const PROFILE_SECTION: &'static str = "profile";
+
     /// Returns the current `Config`
     ///
     /// See `Config::figment`
@@ -203,6 +341,20 @@ impl Config {
         Config::from_provider(Config::figment_with_root(root))
     }
 
+    /// Returns the current `Config`, or the error if it couldn't be extracted
+    ///
+    /// See `Config::figment`
+    pub fn try_load() -> Result<Self, figment::Error> {
+        Config::try_from(Config::figment())
+    }
+
+    /// Returns the current `Config`, or the error if it couldn't be extracted
+    ///
+    /// See `Config::figment_with_root`
+    pub fn try_load_with_root(root: impl Into<PathBuf>) -> Result<Self, figment::Error> {
+        Config::try_from(Config::figment_with_root(root))
+    }
+
     /// Extract a `Config` from `provider`, panicking if extraction fails.
     ///
     /// # Panics
@@ -249,6 +401,15 @@ impl Config {
         Ok(config)
     }
 
+    /// Attempts to extract the sanitized `Config` of cli arguments that are converted into a
+    /// `Figment`, see [`impl_figment_convert`](crate::impl_figment_convert)
+    pub fn try_from_args<'a, T>(args: &'a T) -> Result<Self, figment::Error>
+    where
+        Figment: From<&'a T>,
+    {
+        Ok(Config::try_from(Figment::from(args))?.sanitized())
+    }
+
     /// The config supports relative paths and tracks the root path separately see
     /// `Config::with_root`
     ///
@@ -287,8 +448,21 @@ impl Config {
         self.src = p(&root, &self.src);
         self.test = p(&root, &self.test);
         self.out = p(&root, &self.out);
+        self.cache_path = p(&root, &self.cache_path);
+        self.build_info_path = self.build_info_path.map(|dir| p(&root, &dir));
 
         self.libs = self.libs.into_iter().map(|lib| p(&root, &lib)).collect();
+        self.external_artifacts =
+            self.external_artifacts.into_iter().map(|dir| p(&root, &dir)).collect();
+        self.bindings = self.bindings.map(|bindings| p(&root, &bindings));
+        self.deployments = p(&root, &self.deployments);
+        self.fuzz_corpus = self.fuzz_corpus.map(|dir| p(&root, &dir));
+        for contract in self.bytecode_contracts.values_mut() {
+            contract.abi = p(&root, &contract.abi);
+            contract.bytecode = p(&root, &contract.bytecode);
+            contract.deployed_bytecode =
+                contract.deployed_bytecode.as_ref().map(|code| p(&root, code));
+        }
 
         self.remappings =
             self.remappings.into_iter().map(|r| RelativeRemapping::new(r.into(), &root)).collect();
@@ -311,7 +485,92 @@ impl Config {
         config.libs.sort_unstable();
         config.libs.dedup();
 
-        config
+        config.apply_chain_preset()
+    }
+
+    /// Returns the well-known chain the `chain` is set to
+    pub fn chain_preset(&self) -> Option<&'static ChainPreset> {
+        self.chain.as_deref().and_then(ChainPreset::named)
+    }
+
+    /// Sets the `chain_id`, the `eth_rpc_url` and the `etherscan_api_url` of the `chain` if they
+    /// aren't set, the `eth_rpc_url` only if `rpc_endpoints` has an endpoint named like the chain
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use foundry_config::Config;
+    /// let mut config = Config { chain: Some("optimism".to_string()), ..Default::default() };
+    /// config.rpc_endpoints.insert("optimism".to_string(), "https://mainnet.optimism.io".to_string());
+    /// let config = config.apply_chain_preset();
+    /// assert_eq!(config.chain_id.map(u64::from), Some(10));
+    /// assert_eq!(config.eth_rpc_url.as_deref(), Some("optimism"));
+    /// ```
+    #[must_use]
+    pub fn apply_chain_preset(mut self) -> Self {
+        if let Some(chain) = self.chain_preset() {
+            self.chain_id.get_or_insert(Chain::Id(chain.id));
+            if self.eth_rpc_url.is_none() && self.rpc_endpoints.contains_key(chain.name) {
+                self.eth_rpc_url = Some(chain.name.to_string());
+            }
+            self.etherscan_api_url.get_or_insert_with(|| chain.etherscan_api_url.to_string());
+        }
+        self
+    }
+
+    /// Resolves the given rpc url or the name of an endpoint in `rpc_endpoints` to an url.
+    ///
+    /// If `url_or_alias` is the name of an endpoint, all `${ENV_VAR}` references in its url are
+    /// interpolated, otherwise `url_or_alias` is returned unchanged.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use foundry_config::Config;
+    /// let mut config = Config::default();
+    /// config.rpc_endpoints.insert("local".to_string(), "http://localhost:8545".to_string());
+    /// assert_eq!(config.resolve_rpc_url("local").unwrap(), "http://localhost:8545");
+    /// assert_eq!(config.resolve_rpc_url("http://127.0.0.1:8545").unwrap(), "http://127.0.0.1:8545");
+    /// ```
+    pub fn resolve_rpc_url(&self, url_or_alias: &str) -> eyre::Result<String> {
+        match self.rpc_endpoints.get(url_or_alias) {
+            Some(url) => interpolate_env_vars(url).map_err(|err| {
+                eyre::eyre!("failed to resolve rpc endpoint `{}`: {}", url_or_alias, err)
+            }),
+            None => Ok(url_or_alias.to_string()),
+        }
+    }
+
+    /// Returns all `rpc_endpoints` that can be resolved, endpoints that reference unset
+    /// environment variables are skipped
+    pub fn resolved_rpc_endpoints(&self) -> BTreeMap<String, String> {
+        self.rpc_endpoints
+            .iter()
+            .filter_map(|(name, url)| Some((name.clone(), interpolate_env_vars(url).ok()?)))
+            .collect()
+    }
+
+    /// Returns the explorer of the chain from `explorers`, the entry named like the chain or
+    /// its chain id, with all `${ENV_VAR}` references in its url and api key interpolated
+    pub fn explorer(&self, chain_id: u64) -> eyre::Result<Option<ExplorerConfig>> {
+        let name = ChainPreset::with_id(chain_id).map(|chain| chain.name);
+        let explorer = self.explorers.iter().find(|(key, _)| {
+            Some(key.as_str()) == name || key.parse::<u64>().ok() == Some(chain_id)
+        });
+        let (key, explorer) = match explorer {
+            Some(explorer) => explorer,
+            None => return Ok(None),
+        };
+        let interpolate = |value: &Option<String>| {
+            value.as_deref().map(interpolate_env_vars).transpose().map_err(|err| {
+                eyre::eyre!("failed to resolve the explorer of chain `{}`: {}", key, err)
+            })
+        };
+        Ok(Some(ExplorerConfig {
+            kind: explorer.kind,
+            url: interpolate(&explorer.url)?,
+            api_key: interpolate(&explorer.api_key)?,
+        }))
     }
 
     /// Serves as the entrypoint for obtaining the project.
@@ -338,6 +597,9 @@ impl Config {
     }
 
     fn create_project(&self, cached: bool, no_artifacts: bool) -> Result<Project, SolcError> {
+        if self.offline {
+            self.ensure_solc_installed()?;
+        }
         let project = Project::builder()
             .paths(self.project_paths())
             .allowed_path(&self.__root.0)
@@ -356,6 +618,90 @@ impl Config {
         Ok(project)
     }
 
+    /// Fails if the pinned solc version isn't installed, or, if the version is auto detected, if a
+    /// source requires a version that isn't installed, since it would be installed from the network
+    fn ensure_solc_installed(&self) -> Result<(), SolcError> {
+        let installed = installed_solc_versions();
+        if let Some(ref version) = self.solc_version {
+            if !installed.contains(version) {
+                return Err(SolcError::msg(format!(
+                    "solc {} is not installed and can't be installed offline",
+                    version
+                )))
+            }
+            return Ok(())
+        }
+        if !self.auto_detect_solc {
+            return Ok(())
+        }
+
+        let paths = self.project_paths();
+        let mut sources = paths.read_input_files()?;
+        sources.extend(Source::read_all_files(ethers_solc::utils::source_files(&paths.tests))?);
+        // the libraries are compiled only if they're imported, with the version they require
+        for (file, source) in Graph::resolve_sources(&paths, sources)?.into_sources() {
+            // sources without a version pragma compile with any version
+            let req = match Solc::source_version_req(&source) {
+                Ok(req) => req,
+                Err(_) => continue,
+            };
+            if !installed.iter().any(|version| req.matches(version)) {
+                return Err(SolcError::msg(format!(
+                    "{} requires solc {}, which is not installed and can't be installed offline",
+                    file.display(),
+                    req
+                )))
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads the abi and bytecode files of all `bytecode_contracts`
+    pub fn read_bytecode_contracts(&self) -> eyre::Result<BTreeMap<String, CompactContract>> {
+        self.bytecode_contracts
+            .iter()
+            .map(|(name, contract)| {
+                let read = contract
+                    .read()
+                    .wrap_err_with(|| format!("failed to read bytecode contract `{}`", name))?;
+                Ok((name.clone(), read))
+            })
+            .collect()
+    }
+
+    /// Reads the addresses of the contracts in the `deployments` registry by chain id and name
+    ///
+    /// A missing registry has no deployments.
+    pub fn read_deployments(&self) -> eyre::Result<BTreeMap<u64, BTreeMap<String, Address>>> {
+        #[derive(Deserialize)]
+        struct Deployment {
+            address: Address,
+        }
+
+        let mut deployments = BTreeMap::<u64, BTreeMap<String, Address>>::new();
+        let chains = match std::fs::read_dir(&self.deployments) {
+            Ok(chains) => chains,
+            Err(_) => return Ok(deployments),
+        };
+        for chain in chains.filter_map(Result::ok).map(|entry| entry.path()) {
+            let chain_id = match chain.file_name().and_then(|name| name.to_str()?.parse().ok()) {
+                Some(chain_id) if chain.is_dir() => chain_id,
+                _ => continue,
+            };
+            for file in std::fs::read_dir(&chain)?.filter_map(Result::ok).map(|entry| entry.path())
+            {
+                if file.extension().map_or(true, |ext| ext != "json") {
+                    continue
+                }
+                let name = file.file_stem().unwrap_or_default().to_string_lossy().to_string();
+                let deployment: Deployment = serde_json::from_str(&std::fs::read_to_string(&file)?)
+                    .wrap_err_with(|| format!("invalid deployment {}", file.display()))?;
+                deployments.entry(chain_id).or_default().insert(name, deployment.address);
+            }
+        }
+        Ok(deployments)
+    }
+
     /// Returns the `ProjectPathsConfig`  sub set of the config.
     ///
     /// **NOTE**: this uses the paths as they are and does __not__ modify them, see
@@ -372,6 +718,7 @@ impl Config {
         ProjectPathsConfig::builder()
             .sources(&self.src)
             .artifacts(&self.out)
+            .cache(self.cache_path.join(Config::FILES_CACHE_FILE_NAME))
             .libs(self.libs.clone())
             .remappings(self.remappings.iter().map(|m| m.clone().into()))
             .build_with_root(&self.__root.0)
@@ -379,22 +726,54 @@ impl Config {
 
     /// Returns the `Optimizer` based on the configured settings
     pub fn optimizer(&self) -> Optimizer {
-        Optimizer { enabled: Some(self.optimizer), runs: Some(self.optimizer_runs) }
+        Optimizer {
+            enabled: Some(self.optimizer),
+            runs: Some(self.optimizer_runs),
+            details: self.optimizer_details.clone(),
+        }
     }
 
     /// Returns the configured `solc` `Settings` that includes:
     ///   - all libraries
-    ///   - the optimizer
+    ///   - the optimizer, including the optimizer details
     ///   - evm version
+    ///   - the IR pipeline, metadata hash and revert strings
     pub fn solc_settings(&self) -> Result<Settings, SolcError> {
         let libraries = parse_libraries(&self.libraries)?;
         let optimizer = self.optimizer();
-        Ok(Settings {
+        let mut settings = Settings {
             optimizer,
+            metadata: Some(SettingsMetadata {
+                use_literal_content: None,
+                bytecode_hash: Some(self.bytecode_hash),
+            }),
             evm_version: Some(self.evm_version),
             libraries,
+            via_ir: self.via_ir.then(|| true),
+            debug: self.revert_strings.map(|revert_strings| DebuggingSettings {
+                revert_strings: Some(revert_strings),
+                ..Default::default()
+            }),
             ..Default::default()
-        })
+        };
+        if !self.extra_output.is_empty() || self.build_info {
+            let selection = settings
+                .output_selection
+                .entry("*".to_string())
+                .or_default()
+                .entry("*".to_string())
+                .or_default();
+            for output in &self.extra_output {
+                selection.extend(output.output_selection().iter().map(|s| s.to_string()));
+            }
+            // the build infos are split by the compiler version in the metadata
+            if self.build_info {
+                selection.push("metadata".to_string());
+            }
+            selection.sort();
+            selection.dedup();
+        }
+        Ok(settings)
     }
 
     /// Returns the default figment
@@ -407,7 +786,9 @@ impl Config {
     ///   3. `FOUNDRY_` prefixed environment variables
     ///
     /// The profile selected is the value set in the `FOUNDRY_PROFILE`
-    /// environment variable. If it is not set, it defaults to `default`.
+    /// environment variable. If it is not set, it defaults to `default`. Values of the selected
+    /// profile take precedence over the values of the `default` profile, environment variables
+    /// take precedence over both.
     ///
     /// # Example
     ///
@@ -545,6 +926,17 @@ impl Config {
         }
         find(Env::var_or("FOUNDRY_CONFIG", Config::FILE_NAME).as_ref())
     }
+
+    /// Returns the path to foundry's global directory: `~/.foundry`
+    pub fn foundry_dir() -> Option<PathBuf> {
+        dirs_next::home_dir().map(|p| p.join(".foundry"))
+    }
+
+    /// Returns the path to the directory of the named keystores, which `cast wallet import`
+    /// creates: `~/.foundry/keystores`
+    pub fn foundry_keystores_dir() -> Option<PathBuf> {
+        Self::foundry_dir().map(|p| p.join("keystores"))
+    }
 }
 
 impl From<Config> for Figment {
@@ -552,13 +944,17 @@ impl From<Config> for Figment {
         let profile = Config::selected_profile();
         let figment = Figment::default()
             .merge(DappHardhatDirProvider(&c.__root.0))
-            .merge(ForcedSnakeCaseData(
+            .merge(DeprecatedKeysProvider(ForcedSnakeCaseData(
                 Toml::file(Env::var_or("FOUNDRY_CONFIG", Config::FILE_NAME)).nested(),
-            ))
+            )))
             .merge(Env::prefixed("DAPP_").ignore(&["REMAPPINGS"]).global())
             .merge(Env::prefixed("DAPP_TEST_").global())
             .merge(DappEnvCompatProvider)
-            .merge(Env::prefixed("FOUNDRY_").ignore(&["PROFILE", "REMAPPINGS"]).global())
+            .merge(
+                Env::prefixed("FOUNDRY_")
+                    .ignore(&["PROFILE", "REMAPPINGS", "HOOK", "COMMIT", "SUCCESS"])
+                    .global(),
+            )
             .select(profile.clone());
 
         // we try to merge remappings after we've merged all other providers, this prevents
@@ -634,22 +1030,51 @@ impl Default for Config {
             src: "src".into(),
             test: "test".into(),
             out: "out".into(),
+            cache_path: "cache".into(),
+            build_info: false,
+            build_info_path: None,
             libs: vec!["lib".into()],
+            external_artifacts: vec![],
+            bindings: None,
+            deployments: "deployments".into(),
             cache: true,
             force: false,
             evm_version: Default::default(),
             solc_version: None,
             auto_detect_solc: true,
+            vyper: None,
             optimizer: true,
             optimizer_runs: 200,
+            optimizer_details: None,
+            via_ir: false,
+            bytecode_hash: BytecodeHash::Ipfs,
+            revert_strings: None,
             solc_settings: None,
+            extra_output: vec![],
+            rpc_endpoints: Default::default(),
+            overrides: Default::default(),
+            explorers: Default::default(),
+            bytecode_contracts: Default::default(),
+            lints: Default::default(),
+            geiger: Default::default(),
+            hooks: Default::default(),
             fuzz_runs: 256,
+            fuzz_corpus: Some("cache/fuzz".into()),
+            fuzz: Default::default(),
+            test_bases: vec![],
+            test_globs: vec![],
             ffi: false,
+            unlimited_gas: false,
             sender: "00a329c0648769A73afAc7F9381E08FB43dBEA72".parse().unwrap(),
             tx_origin: "00a329c0648769A73afAc7F9381E08FB43dBEA72".parse().unwrap(),
             initial_balance: U256::from(0xffffffffffffffffffffffffu128),
             block_number: 0,
             fork_block_number: None,
+            no_storage_caching: false,
+            verify_fork_state: false,
+            offline: false,
+            prefetch_accounts: vec![],
+            state_overrides: None,
             chain_id: None,
             // toml-rs can't handle larger number because integers are stored signed
             // https://github.com/alexcrichton/toml-rs/issues/256
@@ -660,17 +1085,367 @@ impl Default for Config {
             block_timestamp: 0,
             block_difficulty: 0,
             block_gas_limit: None,
+            chain: None,
             eth_rpc_url: None,
+            etherscan_api_url: None,
+            ipfs_api_url: None,
             verbosity: 0,
+            chain_profile: ChainProfile::Ethereum,
+            debug_step_limit: None,
             remappings: vec![],
             libraries: vec![],
             ignored_error_codes: vec![],
+            ignored_error_codes_by_path: Default::default(),
+            deny_warnings: false,
             __non_exhaustive: (),
         }
     }
 }
 
+/// The settings of a [`Config`] that can be overridden for the test contracts of a path glob,
+/// see [`Config::overrides`]
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct ConfigOverride {
+    /// url of the rpc server, or name of an rpc endpoint, to fork from
+    #[serde(alias = "eth_rpc_url", skip_serializing_if = "Option::is_none")]
+    pub fork_url: Option<String>,
+    /// pins the block number for the state fork
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fork_block_number: Option<u64>,
+    /// The number of test cases that must execute for each property test
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fuzz_runs: Option<u32>,
+    /// evm version to use
+    #[serde(default, with = "from_str_lowercase::option", skip_serializing_if = "Option::is_none")]
+    pub evm_version: Option<EvmVersion>,
+}
+
+/// A block explorer of a chain, see [`Config::explorers`]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ExplorerConfig {
+    /// The kind of the explorer
+    #[serde(default)]
+    pub kind: ExplorerKind,
+    /// The url of the explorer's api, the etherscan api of the chain or the public sourcify
+    /// server if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// The api key of the explorer, etherscan compatible explorers require one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<String>,
+}
+
+/// The kinds of block explorers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExplorerKind {
+    /// Etherscan and its clones, like Polygonscan, Arbiscan or Snowtrace
+    Etherscan,
+    Blockscout,
+    Sourcify,
+}
+
+impl Default for ExplorerKind {
+    fn default() -> Self {
+        ExplorerKind::Etherscan
+    }
+}
+
+impl fmt::Display for ExplorerKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExplorerKind::Etherscan => f.write_str("etherscan"),
+            ExplorerKind::Blockscout => f.write_str("blockscout"),
+            ExplorerKind::Sourcify => f.write_str("sourcify"),
+        }
+    }
+}
+
+impl std::str::FromStr for ExplorerKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "etherscan" => Ok(ExplorerKind::Etherscan),
+            "blockscout" => Ok(ExplorerKind::Blockscout),
+            "sourcify" => Ok(ExplorerKind::Sourcify),
+            _ => Err(format!("unknown explorer: {}", s)),
+        }
+    }
+}
+
+/// An output of solc that's written next to the artifacts, see [`Config::extra_output`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ExtraOutput {
+    /// The Yul IR of the contract
+    Ir,
+    /// The optimized Yul IR of the contract
+    IrOptimized,
+    /// The eWASM text and binary of the contract
+    Ewasm,
+    StorageLayout,
+    Devdoc,
+    Userdoc,
+    /// The entry points and parameter counts of the internal functions of the creation and the
+    /// runtime bytecode
+    FunctionDebugData,
+}
+
+impl ExtraOutput {
+    /// All extra outputs, by their names
+    pub const NAMES: &'static [&'static str] = &[
+        "ir",
+        "irOptimized",
+        "ewasm",
+        "storageLayout",
+        "devdoc",
+        "userdoc",
+        "functionDebugData",
+    ];
+
+    /// The solc output selections of the output
+    pub fn output_selection(&self) -> &'static [&'static str] {
+        match self {
+            ExtraOutput::Ir => &["ir"],
+            ExtraOutput::IrOptimized => &["irOptimized"],
+            ExtraOutput::Ewasm => &["ewasm.wast", "ewasm.wasm"],
+            ExtraOutput::StorageLayout => &["storageLayout"],
+            ExtraOutput::Devdoc => &["devdoc"],
+            ExtraOutput::Userdoc => &["userdoc"],
+            ExtraOutput::FunctionDebugData => {
+                &["evm.bytecode.functionDebugData", "evm.deployedBytecode.functionDebugData"]
+            }
+        }
+    }
+
+    /// The name of the file the output of the contract is written to, like `Token.ir.yul`
+    pub fn file_name(&self, contract: &str) -> String {
+        match self {
+            ExtraOutput::Ir | ExtraOutput::IrOptimized => format!("{}.{}.yul", contract, self),
+            _ => format!("{}.{}.json", contract, self),
+        }
+    }
+}
+
+impl fmt::Display for ExtraOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            ExtraOutput::Ir => "ir",
+            ExtraOutput::IrOptimized => "irOptimized",
+            ExtraOutput::Ewasm => "ewasm",
+            ExtraOutput::StorageLayout => "storageLayout",
+            ExtraOutput::Devdoc => "devdoc",
+            ExtraOutput::Userdoc => "userdoc",
+            ExtraOutput::FunctionDebugData => "functionDebugData",
+        };
+        f.write_str(s)
+    }
+}
+
+impl std::str::FromStr for ExtraOutput {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ir" => Ok(ExtraOutput::Ir),
+            "irOptimized" => Ok(ExtraOutput::IrOptimized),
+            "ewasm" => Ok(ExtraOutput::Ewasm),
+            "storageLayout" => Ok(ExtraOutput::StorageLayout),
+            "devdoc" => Ok(ExtraOutput::Devdoc),
+            "userdoc" => Ok(ExtraOutput::Userdoc),
+            "functionDebugData" => Ok(ExtraOutput::FunctionDebugData),
+            _ => Err(format!("unknown extra output: {}", s)),
+        }
+    }
+}
+
+/// The severity of the findings of a lint, see [`Config::lints`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LintSeverity {
+    Off,
+    Info,
+    Warning,
+    Error,
+}
+
+impl fmt::Display for LintSeverity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            LintSeverity::Off => "off",
+            LintSeverity::Info => "info",
+            LintSeverity::Warning => "warning",
+            LintSeverity::Error => "error",
+        };
+        f.write_str(s)
+    }
+}
+
+impl std::str::FromStr for LintSeverity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "off" => Ok(LintSeverity::Off),
+            "info" => Ok(LintSeverity::Info),
+            "warning" => Ok(LintSeverity::Warning),
+            "error" => Ok(LintSeverity::Error),
+            _ => Err(format!("unknown severity: {}", s)),
+        }
+    }
+}
+
+/// The chain whose system contracts are made available at their canonical addresses, so that
+/// contracts that rely on them, e.g. contracts targeting an L2, can be tested locally, see
+/// [`Config::chain_profile`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChainProfile {
+    #[serde(alias = "mainnet")]
+    Ethereum,
+    Optimism,
+    Arbitrum,
+}
+
+impl Default for ChainProfile {
+    fn default() -> Self {
+        ChainProfile::Ethereum
+    }
+}
+
+impl fmt::Display for ChainProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            ChainProfile::Ethereum => "ethereum",
+            ChainProfile::Optimism => "optimism",
+            ChainProfile::Arbitrum => "arbitrum",
+        };
+        f.write_str(s)
+    }
+}
+
+impl std::str::FromStr for ChainProfile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "ethereum" | "mainnet" => Ok(ChainProfile::Ethereum),
+            "optimism" => Ok(ChainProfile::Optimism),
+            "arbitrum" => Ok(ChainProfile::Arbitrum),
+            _ => Err(format!(
+                "unknown chain profile `{}`, expected one of `ethereum`, `optimism` or `arbitrum`",
+                s
+            )),
+        }
+    }
+}
+
+/// The bounds of the fuzzed values of dynamic types, see [`Config::fuzz`]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct FuzzSettings {
+    /// The maximum length of dynamic arrays
+    pub max_array_length: usize,
+    /// The maximum length of `bytes`
+    pub max_bytes_length: usize,
+    /// The maximum length of strings, in characters
+    pub max_string_length: usize,
+    /// The regex character class the characters of strings are drawn from, like `[a-zA-Z0-9]`
+    pub string_charset: String,
+    /// The wall-clock budget of each fuzz test in seconds, a test stops early once it's spent even
+    /// if not all `fuzz_runs` were executed
+    pub max_test_time: Option<u64>,
+}
+
+impl Default for FuzzSettings {
+    fn default() -> Self {
+        Self {
+            max_array_length: 256,
+            max_bytes_length: 256,
+            max_string_length: 256,
+            string_charset: ".".to_string(),
+            max_test_time: None,
+        }
+    }
+}
+
+/// The shell commands run before and after forge commands, see [`Config::hooks`]
+///
+/// The commands run in the project's root with `FOUNDRY_HOOK` set to the name of the hook,
+/// `FOUNDRY_PROFILE` to the selected profile and `FOUNDRY_COMMIT` to the commit of the project if
+/// it's a git repository. The `post_` hooks also run if the command failed, with `FOUNDRY_SUCCESS`
+/// set to `true` or `false`. `${ENV_VAR}` references are left to the shell.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Hooks {
+    /// Run before `forge build` compiles the project, the build is aborted if it fails
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pre_build: Option<String>,
+    /// Run after `forge build`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub post_build: Option<String>,
+    /// Run before `forge test` compiles and runs the tests, the tests are aborted if it fails
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pre_test: Option<String>,
+    /// Run after `forge test`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub post_test: Option<String>,
+    /// Run before `forge run --broadcast` sends the recorded transactions, nothing is sent if it
+    /// fails
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pre_broadcast: Option<String>,
+    /// Run after the recorded transactions are sent
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub post_broadcast: Option<String>,
+}
+
+/// A contract compiled outside of forge, like with Huff, see [`Config::bytecode_contracts`]
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct BytecodeContract {
+    /// path of the json abi
+    pub abi: PathBuf,
+    /// path of the hex encoded creation bytecode
+    pub bytecode: PathBuf,
+    /// path of the hex encoded runtime bytecode, the contract is only identified in traces if set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deployed_bytecode: Option<PathBuf>,
+}
+
+impl BytecodeContract {
+    /// Reads the abi and bytecode files of the contract
+    pub fn read(&self) -> eyre::Result<CompactContract> {
+        fn read_code(path: &Path) -> eyre::Result<BytecodeObject> {
+            let code = std::fs::read_to_string(path)
+                .wrap_err_with(|| format!("failed to read {}", path.display()))?;
+            let code = code.trim();
+            let code = hex::decode(code.strip_prefix("0x").unwrap_or(code))
+                .wrap_err_with(|| format!("{} is not hex encoded bytecode", path.display()))?;
+            Ok(BytecodeObject::Bytecode(code.into()))
+        }
+
+        let abi = std::fs::read_to_string(&self.abi)
+            .wrap_err_with(|| format!("failed to read {}", self.abi.display()))?;
+        let abi = serde_json::from_str(&abi)
+            .wrap_err_with(|| format!("{} is not a json abi", self.abi.display()))?;
+        Ok(CompactContract {
+            abi: Some(abi),
+            bin: Some(read_code(&self.bytecode)?),
+            bin_runtime: self.deployed_bytecode.as_deref().map(read_code).transpose()?,
+        })
+    }
+}
+
 /// A Provider that ensures all keys are snake case
+///
+/// Profiles can be declared as top level tables, `[ci]`, or nested in the `profile` table,
+/// `[profile.ci]`. If a profile is declared both ways, the values of the nested table take
+/// precedence.
+///
+/// `${ENV_VAR}` references in string values of the default and the selected profile are replaced
+/// with the value of the environment variable, except for the `rpc_endpoints` and `explorers` which
+/// are only resolved when they're used and the `hooks` which are resolved by the shell. Other
+/// profiles are left as is, so a variable that's only set for one profile doesn't fail the others.
 struct ForcedSnakeCaseData<F: Format>(Data<F>);
 
 impl<F: Format> Provider for ForcedSnakeCaseData<F> {
@@ -680,13 +1455,75 @@ impl<F: Format> Provider for ForcedSnakeCaseData<F> {
 
     fn data(&self) -> Result<Map<Profile, Dict>, Error> {
         let mut map = Map::new();
+        let mut nested = Vec::new();
         for (profile, dict) in self.0.data()? {
+            if profile == Config::PROFILE_SECTION {
+                nested.push(dict);
+                continue
+            }
             map.insert(profile, dict.into_iter().map(|(k, v)| (k.to_snake_case(), v)).collect());
         }
+        for profiles in nested {
+            for (name, value) in profiles {
+                let dict = value.into_dict().ok_or_else(|| {
+                    Error::from(format!("`{}.{}` must be a table", Config::PROFILE_SECTION, name))
+                })?;
+                map.entry(Profile::new(&name))
+                    .or_insert_with(Dict::new)
+                    .extend(dict.into_iter().map(|(k, v)| (k.to_snake_case(), v)));
+            }
+        }
+        let selected = Config::selected_profile();
+        let resolved =
+            |profile: &Profile| *profile == selected || *profile == Config::DEFAULT_PROFILE;
+        for (profile, dict) in map.iter_mut().filter(|(profile, _)| resolved(profile)) {
+            let lazy = ["rpc_endpoints", "explorers", "hooks"];
+            for (key, value) in dict.iter_mut().filter(|(key, _)| !lazy.contains(&key.as_str())) {
+                interpolate_value(value).map_err(|err| {
+                    Error::from(format!(
+                        "failed to resolve `{}` of profile `{}`: {}",
+                        key, profile, err
+                    ))
+                })?;
+            }
+        }
         Ok(map)
     }
 }
 
+/// A provider that renames the deprecated keys of every profile to the key that replaced them,
+/// unless the profile sets both
+struct DeprecatedKeysProvider<P: Provider>(P);
+
+impl<P: Provider> Provider for DeprecatedKeysProvider<P> {
+    fn metadata(&self) -> Metadata {
+        self.0.metadata()
+    }
+
+    fn data(&self) -> Result<Map<Profile, Dict>, Error> {
+        let mut map = self.0.data()?;
+        for dict in map.values_mut() {
+            for (key, replacement) in validate::DEPRECATED_KEYS {
+                if let Some(value) = dict.remove(*key) {
+                    dict.entry(replacement.to_string()).or_insert(value);
+                }
+            }
+        }
+        Ok(map)
+    }
+}
+
+/// Replaces the `${ENV_VAR}` references in all string values, See [`interpolate_env_vars()`]
+fn interpolate_value(value: &mut Value) -> eyre::Result<()> {
+    match value {
+        Value::String(_, s) if s.contains("${") => *s = interpolate_env_vars(s)?,
+        Value::Dict(_, dict) => dict.values_mut().try_for_each(interpolate_value)?,
+        Value::Array(_, values) => values.iter_mut().try_for_each(interpolate_value)?,
+        _ => {}
+    }
+    Ok(())
+}
+
 /// A provider that sets the `src` and `output` path depending on their existence.
 struct DappHardhatDirProvider<'a>(&'a Path);
 
@@ -724,7 +1561,8 @@ impl<'a> Provider for DappHardhatDirProvider<'a> {
                 .into(),
         );
 
-        Ok(Map::from([(Config::selected_profile(), dict)]))
+        // detected dirs are defaults, every profile of the config file takes precedence
+        Ok(Map::from([(Config::DEFAULT_PROFILE, dict)]))
     }
 }
 
@@ -846,7 +1684,12 @@ impl<'a> RemappingsProvider<'a> {
                 .collect::<Vec<Remapping>>(),
         );
 
-        // remove duplicates
+        // npm packages are imported by their package name, unless the lookup already remapped
+        // them, like to their `src` folder
+        new_remappings
+            .extend(self.lib_paths.iter().map(|lib| self.root.join(lib)).flat_map(npm_remappings));
+
+        // remove duplicates, the first remapping of a name wins
         new_remappings.sort_by(|a, b| a.name.cmp(&b.name));
         new_remappings.dedup_by(|a, b| a.name.eq(&b.name));
 
@@ -854,6 +1697,48 @@ impl<'a> RemappingsProvider<'a> {
     }
 }
 
+/// Returns the remappings of the npm packages in the lib directory, like
+/// `@openzeppelin/contracts/=node_modules/@openzeppelin/contracts/`
+///
+/// Every package of a `node_modules` directory is remapped, other directories only remap scoped
+/// packages, like the ones `forge install @openzeppelin/contracts` installs into `lib`.
+fn npm_remappings(lib: PathBuf) -> Vec<Remapping> {
+    let dirs = |dir: &Path| {
+        let mut dirs = std::fs::read_dir(dir)
+            .map(|entries| {
+                entries
+                    .filter_map(Result::ok)
+                    .map(|entry| entry.path())
+                    .filter(|path| path.is_dir())
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        dirs.sort();
+        dirs
+    };
+    let is_node_modules = lib.file_name().map(|name| name == "node_modules").unwrap_or_default();
+
+    let mut remappings = Vec::new();
+    for dir in dirs(&lib) {
+        let name = dir.file_name().unwrap_or_default().to_string_lossy().to_string();
+        let packages = if name.starts_with('@') {
+            dirs(&dir)
+        } else if is_node_modules && !name.starts_with('.') {
+            vec![dir]
+        } else {
+            continue
+        };
+        for package in packages {
+            let name = package.strip_prefix(&lib).unwrap_or(&package).to_string_lossy();
+            remappings.push(Remapping {
+                name: format!("{}/", name),
+                path: format!("{}/", package.display()),
+            });
+        }
+    }
+    remappings
+}
+
 impl<'a> Provider for RemappingsProvider<'a> {
     fn metadata(&self) -> Metadata {
         Metadata::named("Remapping Provider")
@@ -959,6 +1844,34 @@ mod from_str_lowercase {
     {
         String::deserialize(deserializer)?.to_lowercase().parse().map_err(serde::de::Error::custom)
     }
+
+    pub mod option {
+        use std::str::FromStr;
+
+        use serde::{Deserialize, Deserializer, Serializer};
+
+        pub fn serialize<T, S>(value: &Option<T>, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            T: std::fmt::Display,
+            S: Serializer,
+        {
+            match value {
+                Some(value) => super::serialize(value, serializer),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+        where
+            D: Deserializer<'de>,
+            T: FromStr,
+            T::Err: std::fmt::Display,
+        {
+            Option::<String>::deserialize(deserializer)?
+                .map(|s| s.to_lowercase().parse().map_err(serde::de::Error::custom))
+                .transpose()
+        }
+    }
 }
 
 impl From<Chain> for u64 {
@@ -970,6 +1883,18 @@ impl From<Chain> for u64 {
     }
 }
 
+/// Returns the solc versions installed in `~/.svm`, where the solc auto detection installs them
+fn installed_solc_versions() -> Vec<Version> {
+    dirs_next::home_dir()
+        .and_then(|home| std::fs::read_dir(home.join(".svm")).ok())
+        .map(|dir| {
+            dir.filter_map(Result::ok)
+                .filter_map(|entry| entry.file_name().to_string_lossy().parse().ok())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
 fn canonic(path: impl Into<PathBuf>) -> PathBuf {
     let path = path.into();
     ethers_solc::utils::canonicalize(&path).unwrap_or(path)
@@ -1008,6 +1933,318 @@ mod tests {
         });
     }
 
+    #[test]
+    fn test_nested_profiles() {
+        figment::Jail::expect_with(|jail| {
+            jail.create_file(
+                "foundry.toml",
+                r#"
+                [default]
+                fuzz_runs = 100
+                optimizer_runs = 1000
+
+                [ci]
+                verbosity = 1
+
+                [profile.ci]
+                fuzz_runs = 10000
+                verbosity = 4
+
+                [profile.lite]
+                optimizer = false
+            "#,
+            )?;
+
+            let config = Config::load();
+            assert_eq!(config.fuzz_runs, 100);
+            assert!(config.optimizer);
+
+            jail.set_env("FOUNDRY_PROFILE", "ci");
+            let config = Config::load();
+            assert_eq!(config.profile, Profile::new("ci"));
+            assert_eq!(config.fuzz_runs, 10000);
+            assert_eq!(config.verbosity, 4);
+            // inherited from the default profile
+            assert_eq!(config.optimizer_runs, 1000);
+
+            // env vars take precedence over the profile
+            jail.set_env("FOUNDRY_FUZZ_RUNS", 5);
+            assert_eq!(Config::load().fuzz_runs, 5);
+
+            jail.set_env("FOUNDRY_PROFILE", "lite");
+            let config = Config::load();
+            assert!(!config.optimizer);
+            assert_eq!(config.optimizer_runs, 1000);
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_overrides() {
+        figment::Jail::expect_with(|jail| {
+            jail.create_file(
+                "foundry.toml",
+                r#"
+                [profile.default]
+                fuzz_runs = 100
+
+                [profile.default.overrides."src/test/fork/**"]
+                fork_url = "mainnet"
+                fork_block_number = 14000000
+                evm_version = "Berlin"
+
+                [profile.default.overrides."src/test/unit/*.t.sol"]
+                fuzz_runs = 1000
+            "#,
+            )?;
+
+            let config = Config::load();
+            assert_eq!(config.fuzz_runs, 100);
+            assert_eq!(
+                config.overrides["src/test/fork/**"],
+                ConfigOverride {
+                    fork_url: Some("mainnet".to_string()),
+                    fork_block_number: Some(14000000),
+                    evm_version: Some(EvmVersion::Berlin),
+                    ..Default::default()
+                }
+            );
+            assert_eq!(
+                config.overrides["src/test/unit/*.t.sol"],
+                ConfigOverride { fuzz_runs: Some(1000), ..Default::default() }
+            );
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_bytecode_contracts() {
+        figment::Jail::expect_with(|jail| {
+            jail.create_file(
+                "foundry.toml",
+                r#"
+                [default.bytecode_contracts.Token]
+                abi = "huff/Token.json"
+                bytecode = "huff/Token.bin"
+                deployed_bytecode = "huff/Token.runtime.bin"
+            "#,
+            )?;
+            jail.create_dir("huff")?;
+            jail.create_file("huff/Token.json", "[]")?;
+            jail.create_file("huff/Token.bin", "0x6001600c60003960016000f300\n")?;
+            jail.create_file("huff/Token.runtime.bin", "00")?;
+
+            let config = Config::load();
+            assert_eq!(
+                config.bytecode_contracts["Token"],
+                BytecodeContract {
+                    abi: "huff/Token.json".into(),
+                    bytecode: "huff/Token.bin".into(),
+                    deployed_bytecode: Some("huff/Token.runtime.bin".into()),
+                }
+            );
+
+            let contracts = config.read_bytecode_contracts().unwrap();
+            let token = contracts["Token"].clone();
+            assert_eq!(token.bin.and_then(|bin| bin.into_bytes()).unwrap().len(), 13);
+            assert_eq!(token.bin_runtime.and_then(|bin| bin.into_bytes()).unwrap().to_vec(), [0]);
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_read_deployments() {
+        figment::Jail::expect_with(|jail| {
+            jail.create_dir("deployments/1")?;
+            jail.create_dir("deployments/5")?;
+            jail.create_file(
+                "deployments/1/Token.json",
+                r#"{"address":"0x0000000000000000000000000000000000000001","timestamp":1}"#,
+            )?;
+            jail.create_file(
+                "deployments/5/Token.json",
+                r#"{"address":"0x0000000000000000000000000000000000000005","timestamp":1}"#,
+            )?;
+            jail.create_file("deployments/README.md", "")?;
+
+            let config = Config::load();
+            let deployments = config.read_deployments().unwrap();
+            assert_eq!(deployments.len(), 2);
+            assert_eq!(deployments[&1]["Token"], Address::from_low_u64_be(1));
+            assert_eq!(deployments[&5]["Token"], Address::from_low_u64_be(5));
+
+            jail.set_env("FOUNDRY_DEPLOYMENTS", "registry");
+            let config = Config::load();
+            assert!(config.read_deployments().unwrap().is_empty());
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_hooks() {
+        figment::Jail::expect_with(|jail| {
+            jail.create_file(
+                "foundry.toml",
+                r#"
+                [default.hooks]
+                pre_build = "make codegen"
+                post_test = "notify ${FOUNDRY_SUCCESS}"
+            "#,
+            )?;
+
+            let config = Config::load();
+            assert_eq!(
+                config.hooks,
+                Hooks {
+                    pre_build: Some("make codegen".to_string()),
+                    post_test: Some("notify ${FOUNDRY_SUCCESS}".to_string()),
+                    ..Default::default()
+                }
+            );
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_offline() {
+        figment::Jail::expect_with(|jail| {
+            // no solc versions are installed in the home of the jail
+            jail.set_env("HOME", jail.directory().display().to_string());
+            jail.create_file("src/A.sol", "pragma solidity 0.8.10;\ncontract A {}")?;
+            assert!(Config::load().project().is_ok());
+
+            jail.set_env("FOUNDRY_OFFLINE", "true");
+            let config = Config::load();
+            assert!(config.offline);
+            let err = config.project().unwrap_err().to_string();
+            assert!(err.contains("can't be installed offline"), "{}", err);
+
+            std::fs::create_dir_all(jail.directory().join(".svm/0.8.10")).unwrap();
+            assert!(Config::load().project().is_ok());
+
+            // the tests are compiled as well
+            jail.create_file("test/A.t.sol", "pragma solidity 0.8.11;\ncontract ATest {}")?;
+            let err = Config::load().project().unwrap_err().to_string();
+            assert!(err.contains("A.t.sol requires solc"), "{}", err);
+            std::fs::remove_file(jail.directory().join("test/A.t.sol")).unwrap();
+
+            jail.set_env("FOUNDRY_SOLC_VERSION", "0.8.12");
+            jail.set_env("FOUNDRY_AUTO_DETECT_SOLC", "false");
+            let err = Config::load().project().unwrap_err().to_string();
+            assert!(err.contains("solc 0.8.12 is not installed"), "{}", err);
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_lints() {
+        figment::Jail::expect_with(|jail| {
+            jail.create_file(
+                "foundry.toml",
+                r#"
+                [default.lints]
+                tx-origin = "warning"
+                solc = "off"
+
+                [default.geiger]
+                ffi = "warning"
+            "#,
+            )?;
+
+            let config = Config::load();
+            assert_eq!(
+                config.lints,
+                BTreeMap::from([
+                    ("tx-origin".to_string(), LintSeverity::Warning),
+                    ("solc".to_string(), LintSeverity::Off),
+                ])
+            );
+            assert_eq!(config.geiger, BTreeMap::from([("ffi".to_string(), LintSeverity::Warning)]));
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_fuzz_settings() {
+        figment::Jail::expect_with(|jail| {
+            jail.create_file(
+                "foundry.toml",
+                r#"
+                [default.fuzz]
+                max_array_length = 8
+                string_charset = "[a-z]"
+                max_test_time = 60
+            "#,
+            )?;
+
+            let config = Config::load();
+            assert_eq!(
+                config.fuzz,
+                FuzzSettings {
+                    max_array_length: 8,
+                    string_charset: "[a-z]".to_string(),
+                    max_test_time: Some(60),
+                    ..Default::default()
+                }
+            );
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_chain_preset() {
+        figment::Jail::expect_with(|jail| {
+            jail.create_file(
+                "foundry.toml",
+                r#"
+                [default]
+                chain = "Arbitrum"
+
+                [default.rpc_endpoints]
+                arbitrum = "https://arb1.arbitrum.io/rpc"
+            "#,
+            )?;
+
+            let config = Config::load().sanitized();
+            assert_eq!(config.chain.as_deref(), Some("arbitrum"));
+            assert_eq!(config.chain_id, Some(Chain::Id(42161)));
+            assert_eq!(config.eth_rpc_url.as_deref(), Some("arbitrum"));
+            assert_eq!(config.etherscan_api_url.as_deref(), Some("https://api.arbiscan.io/api"));
+
+            jail.create_file(
+                "foundry.toml",
+                r#"
+                [default]
+                chain = "polygon"
+                chain_id = 80001
+            "#,
+            )?;
+            let config = Config::load().sanitized();
+            assert_eq!(config.chain_id, Some(Chain::Id(80001)));
+            assert_eq!(config.eth_rpc_url, None);
+
+            jail.create_file("foundry.toml", "[default]\nchain = \"unknown\"\n")?;
+            assert!(Config::try_from(Config::figment()).is_err());
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_chain_profile() {
+        figment::Jail::expect_with(|jail| {
+            jail.create_file("foundry.toml", "[default]\nchain_profile = \"mainnet\"\n")?;
+            assert_eq!(Config::load().chain_profile, ChainProfile::Ethereum);
+
+            jail.create_file("foundry.toml", "[default]\nchain_profile = \"optimism\"\n")?;
+            assert_eq!(Config::load().chain_profile, ChainProfile::Optimism);
+
+            jail.create_file("foundry.toml", "[default]\nchain_profile = \"zksync\"\n")?;
+            assert!(Config::try_load().is_err());
+            Ok(())
+        });
+    }
+
     #[test]
     fn test_profile_env() {
         figment::Jail::expect_with(|jail| {
@@ -1074,6 +2311,39 @@ mod tests {
         });
     }
 
+    #[test]
+    fn test_npm_remappings() {
+        figment::Jail::expect_with(|jail| {
+            jail.create_file(
+                "foundry.toml",
+                r#"
+                [default]
+                libs = ["node_modules", "lib"]
+            "#,
+            )?;
+            std::fs::create_dir_all("node_modules/@openzeppelin/contracts/token").unwrap();
+            std::fs::create_dir_all("node_modules/solmate/src/auth").unwrap();
+            jail.create_file("node_modules/solmate/src/auth/Auth.sol", "contract Auth {}")?;
+            std::fs::create_dir_all("node_modules/ds-test").unwrap();
+            std::fs::create_dir_all("lib/@uniswap/v3-core/contracts").unwrap();
+
+            let config = Config::load();
+            for expected in [
+                "@openzeppelin/contracts/=node_modules/@openzeppelin/contracts/",
+                "ds-test/=node_modules/ds-test/",
+                "@uniswap/v3-core/=lib/@uniswap/v3-core/",
+                // the remapping to the `src` folder of a package is kept
+                "solmate/=node_modules/solmate/src/",
+            ] {
+                let expected = Remapping::from_str(expected).unwrap().into();
+                assert!(config.remappings.contains(&expected), "{:?}", config.remappings);
+            }
+            let npm_solmate = Remapping::from_str("solmate/=node_modules/solmate/").unwrap().into();
+            assert!(!config.remappings.contains(&npm_solmate), "{:?}", config.remappings);
+            Ok(())
+        });
+    }
+
     #[test]
     fn test_remappings_override() {
         figment::Jail::expect_with(|jail| {
@@ -1160,6 +2430,57 @@ mod tests {
         });
     }
 
+    #[test]
+    fn test_compiler_pipeline_settings() {
+        figment::Jail::expect_with(|jail| {
+            jail.create_file(
+                "foundry.toml",
+                r#"
+                [default]
+                via_ir = true
+                bytecode_hash = "none"
+                revert_strings = "strip"
+
+                [default.optimizer_details]
+                yul = false
+                deduplicate = true
+            "#,
+            )?;
+
+            let config = Config::load();
+            assert!(config.via_ir);
+            assert_eq!(config.bytecode_hash, BytecodeHash::None);
+            assert_eq!(config.revert_strings, Some(RevertStrings::Strip));
+
+            let settings = config.solc_settings().unwrap();
+            assert_eq!(settings.via_ir, Some(true));
+            assert_eq!(settings.metadata.unwrap().bytecode_hash, Some(BytecodeHash::None));
+            assert_eq!(settings.debug.unwrap().revert_strings, Some(RevertStrings::Strip));
+            let details = settings.optimizer.details.unwrap();
+            assert_eq!(details.yul, Some(false));
+            assert_eq!(details.deduplicate, Some(true));
+            assert_eq!(details.cse, None);
+
+            let settings = Config::default().solc_settings().unwrap();
+            assert_eq!(settings.via_ir, None);
+            assert!(settings.debug.is_none());
+
+            let config = Config {
+                extra_output: vec![ExtraOutput::IrOptimized, ExtraOutput::FunctionDebugData],
+                ..Default::default()
+            };
+            let selection = &config.solc_settings().unwrap().output_selection["*"]["*"];
+            assert!(selection.contains(&"abi".to_string()));
+            assert!(selection.contains(&"irOptimized".to_string()));
+            assert!(selection.contains(&"evm.deployedBytecode.functionDebugData".to_string()));
+
+            let config = Config { build_info: true, ..Default::default() };
+            let selection = &config.solc_settings().unwrap().output_selection["*"]["*"];
+            assert!(selection.contains(&"metadata".to_string()));
+            Ok(())
+        });
+    }
+
     #[test]
     fn test_toml_casing_file() {
         figment::Jail::expect_with(|jail| {
@@ -1238,6 +2559,138 @@ mod tests {
         });
     }
 
+    #[test]
+    fn test_interpolate_env_vars() {
+        figment::Jail::expect_with(|jail| {
+            jail.create_file(
+                "foundry.toml",
+                r#"
+                [default]
+                eth_rpc_url = "https://eth-mainnet.alchemyapi.io/v2/${INTERPOLATE_API_KEY}"
+                sender = "${INTERPOLATE_SENDER}"
+                libraries = ["src/Lib.sol:Lib:${INTERPOLATE_SENDER}"]
+
+                [default.rpc_endpoints]
+                optimism = "https://optimism.example.com/${INTERPOLATE_UNSET}"
+            "#,
+            )?;
+            jail.set_env("INTERPOLATE_API_KEY", "123");
+            jail.set_env("INTERPOLATE_SENDER", "0x00a329c0648769a73afac7f9381e08fb43dbea70");
+
+            let config = Config::load();
+            assert_eq!(
+                config.eth_rpc_url.as_deref(),
+                Some("https://eth-mainnet.alchemyapi.io/v2/123")
+            );
+            assert_eq!(
+                config.sender,
+                "0x00a329c0648769a73afac7f9381e08fb43dbea70".parse().unwrap()
+            );
+            assert_eq!(
+                config.libraries,
+                vec!["src/Lib.sol:Lib:0x00a329c0648769a73afac7f9381e08fb43dbea70".to_string()]
+            );
+            // endpoints are resolved lazily
+            assert!(config.resolve_rpc_url("optimism").is_err());
+
+            jail.create_file(
+                "foundry.toml",
+                r#"
+                [default]
+                eth_rpc_url = "${INTERPOLATE_UNSET}"
+            "#,
+            )?;
+            let err = Config::figment().extract::<Config>().unwrap_err();
+            assert!(err.to_string().contains("`INTERPOLATE_UNSET` is not set"));
+
+            // profiles that aren't selected are not resolved
+            jail.create_file(
+                "foundry.toml",
+                r#"
+                [default]
+                sender = "${INTERPOLATE_SENDER}"
+
+                [ci]
+                eth_rpc_url = "${INTERPOLATE_UNSET}"
+            "#,
+            )?;
+            let config = Config::figment().extract::<Config>()?;
+            assert_eq!(
+                config.sender,
+                "0x00a329c0648769a73afac7f9381e08fb43dbea70".parse().unwrap()
+            );
+            jail.set_env("FOUNDRY_PROFILE", "ci");
+            assert!(Config::figment().extract::<Config>().is_err());
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_rpc_endpoints() {
+        figment::Jail::expect_with(|jail| {
+            jail.create_file(
+                "foundry.toml",
+                r#"
+                [default]
+                [default.rpc_endpoints]
+                mainnet = "https://eth-mainnet.alchemyapi.io/v2/${RPC_MAINNET_KEY}"
+                optimism = "https://optimism.example.com/${RPC_OPTIMISM_KEY}"
+            "#,
+            )?;
+            jail.set_env("RPC_MAINNET_KEY", "123");
+
+            let config = Config::load();
+            assert_eq!(
+                config.resolve_rpc_url("mainnet").unwrap(),
+                "https://eth-mainnet.alchemyapi.io/v2/123"
+            );
+            assert!(config.resolve_rpc_url("optimism").is_err());
+            assert_eq!(
+                config.resolve_rpc_url("http://localhost:8545").unwrap(),
+                "http://localhost:8545"
+            );
+            assert_eq!(
+                config.resolved_rpc_endpoints().into_iter().collect::<Vec<_>>(),
+                vec![(
+                    "mainnet".to_string(),
+                    "https://eth-mainnet.alchemyapi.io/v2/123".to_string()
+                )]
+            );
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_explorers() {
+        figment::Jail::expect_with(|jail| {
+            jail.create_file(
+                "foundry.toml",
+                r#"
+                [default.explorers.polygon]
+                api_key = "${POLYGONSCAN_API_KEY}"
+
+                [default.explorers.100]
+                kind = "blockscout"
+                url = "https://blockscout.com/xdai/mainnet/api"
+            "#,
+            )?;
+            jail.set_env("POLYGONSCAN_API_KEY", "123");
+
+            let config = Config::load();
+            assert_eq!(
+                config.explorer(137).unwrap(),
+                Some(ExplorerConfig {
+                    kind: ExplorerKind::Etherscan,
+                    url: None,
+                    api_key: Some("123".to_string())
+                })
+            );
+            assert_eq!(config.explorer(100).unwrap().unwrap().kind, ExplorerKind::Blockscout);
+            assert_eq!(config.explorer(1).unwrap(), None);
+            Ok(())
+        });
+    }
+
     #[test]
     fn test_extract_basic() {
         figment::Jail::expect_with(|jail| {
@@ -85,6 +85,38 @@ pub fn remappings_from_env_var(env_var: &str) -> Option<Result<Vec<Remapping>, R
     Some(remappings_from_newline(&val).collect())
 }
 
+/// Replaces all `${ENV_VAR}` references in `input` with the value of the environment variable
+///
+/// Returns an error if a referenced environment variable is not set or a reference is not closed.
+///
+/// # Example
+///
+/// ```
+/// use foundry_config::interpolate_env_vars;
+/// std::env::set_var("FOUNDRY_DOC_API_KEY", "123");
+/// assert_eq!(
+///     interpolate_env_vars("https://example.com/${FOUNDRY_DOC_API_KEY}").unwrap(),
+///     "https://example.com/123"
+/// );
+/// ```
+pub fn interpolate_env_vars(input: &str) -> eyre::Result<String> {
+    let mut out = String::with_capacity(input.len());
+    let mut rest = input;
+    while let Some(start) = rest.find("${") {
+        out.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| eyre::eyre!("unclosed environment variable in `{}`", input))?;
+        let var = &rest[start + 2..start + end];
+        let val = std::env::var(var)
+            .map_err(|_| eyre::eyre!("environment variable `{}` is not set", var))?;
+        out.push_str(&val);
+        rest = &rest[start + end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
 /// Parses all libraries in the form of
 /// `<file>:<lib>:<addr>`
 ///
@@ -0,0 +1,377 @@
+//! Validation of config files
+use crate::{Config, ForcedSnakeCaseData};
+use figment::{
+    providers::{Format, Serialized, Toml},
+    Figment, Profile, Provider,
+};
+use inflector::Inflector;
+use std::{fmt, path::Path};
+
+/// The keys of the settings of the [`Config`]
+const CONFIG_KEYS: &[&str] = &[
+    "src",
+    "test",
+    "out",
+    "cache_path",
+    "build_info",
+    "build_info_path",
+    "libs",
+    "external_artifacts",
+    "bindings",
+    "deployments",
+    "remappings",
+    "libraries",
+    "cache",
+    "force",
+    "evm_version",
+    "solc_version",
+    "auto_detect_solc",
+    "vyper",
+    "optimizer",
+    "optimizer_runs",
+    "optimizer_details",
+    "via_ir",
+    "bytecode_hash",
+    "revert_strings",
+    "extra_output",
+    "verbosity",
+    "chain_profile",
+    "debug_step_limit",
+    "chain",
+    "eth_rpc_url",
+    "etherscan_api_url",
+    "ipfs_api_url",
+    "ignored_error_codes",
+    "ignored_error_codes_by_path",
+    "deny_warnings",
+    "fuzz_runs",
+    "fuzz_corpus",
+    "fuzz",
+    "test_bases",
+    "test_globs",
+    "ffi",
+    "unlimited_gas",
+    "sender",
+    "tx_origin",
+    "initial_balance",
+    "block_number",
+    "fork_block_number",
+    "no_storage_caching",
+    "verify_fork_state",
+    "offline",
+    "prefetch_accounts",
+    "state_overrides",
+    "chain_id",
+    "gas_limit",
+    "gas_price",
+    "block_base_fee_per_gas",
+    "block_coinbase",
+    "block_timestamp",
+    "block_difficulty",
+    "block_gas_limit",
+    "solc_settings",
+    "rpc_endpoints",
+    "overrides",
+    "explorers",
+    "bytecode_contracts",
+    "lints",
+    "geiger",
+    "hooks",
+];
+
+/// Keys that are not part of the [`Config`] but are read from the same figment, like the EVM
+/// options
+const EXTERNAL_KEYS: &[&str] = &["evm_type", "debug"];
+
+/// Keys that are still supported but were replaced by another key, the dapptools names of the
+/// settings that are also read from their `DAPP_` environment variables
+pub(crate) const DEPRECATED_KEYS: &[(&str, &str)] = &[
+    ("fork_block", "fork_block_number"),
+    ("build_optimize_runs", "optimizer_runs"),
+    ("test_fuzz_runs", "fuzz_runs"),
+    ("test_number", "block_number"),
+    ("test_timestamp", "block_timestamp"),
+    ("test_address", "sender"),
+];
+
+/// Keys that don't exist but are commonly used for another key, e.g. because they're named after
+/// the cli argument
+const MISNAMED_KEYS: &[(&str, &str)] = &[("fork_url", "eth_rpc_url"), ("rpc_url", "eth_rpc_url")];
+
+/// A problem with a setting of a config file, See [`Config::validate_file()`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigIssue {
+    /// The key is not a setting and is ignored
+    UnknownKey { profile: Profile, key: String, suggestion: Option<String> },
+    /// The value can't be converted into the type of the setting
+    InvalidValue { profile: Profile, key: String, error: String },
+    /// The key was replaced by another key
+    DeprecatedKey { profile: Profile, key: String, replacement: String },
+}
+
+impl ConfigIssue {
+    /// Returns the profile the issue was found in
+    pub fn profile(&self) -> &Profile {
+        match self {
+            ConfigIssue::UnknownKey { profile, .. } |
+            ConfigIssue::InvalidValue { profile, .. } |
+            ConfigIssue::DeprecatedKey { profile, .. } => profile,
+        }
+    }
+
+    /// Returns the key and the key it should be renamed to, if the issue can be fixed by renaming
+    /// the key
+    pub fn rename(&self) -> Option<(&str, &str)> {
+        match self {
+            ConfigIssue::UnknownKey { key, suggestion: Some(suggestion), .. } => {
+                Some((key, suggestion))
+            }
+            ConfigIssue::DeprecatedKey { key, replacement, .. } => Some((key, replacement)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigIssue::UnknownKey { profile, key, suggestion } => {
+                write!(f, "[{}] unknown key `{}` is ignored", profile, key)?;
+                if let Some(suggestion) = suggestion {
+                    write!(f, ", did you mean `{}`?", suggestion)?;
+                }
+                Ok(())
+            }
+            ConfigIssue::InvalidValue { profile, key, error } => {
+                write!(f, "[{}] invalid value for `{}`: {}", profile, key, error)
+            }
+            ConfigIssue::DeprecatedKey { profile, key, replacement } => {
+                write!(f, "[{}] `{}` is deprecated, use `{}` instead", profile, key, replacement)
+            }
+        }
+    }
+}
+
+impl Config {
+    /// Checks every profile of the config file for unknown and deprecated keys and values that
+    /// can't be converted into their setting
+    ///
+    /// A missing file has no issues.
+    pub fn validate_file(path: impl AsRef<Path>) -> Result<Vec<ConfigIssue>, figment::Error> {
+        let known = known_keys();
+        let mut issues = Vec::new();
+        for (profile, dict) in ForcedSnakeCaseData(Toml::file(path.as_ref()).nested()).data()? {
+            for key in dict.keys() {
+                if let Some((_, replacement)) = DEPRECATED_KEYS.iter().find(|(k, _)| k == key) {
+                    issues.push(ConfigIssue::DeprecatedKey {
+                        profile: profile.clone(),
+                        key: key.clone(),
+                        replacement: replacement.to_string(),
+                    });
+                } else if !known.iter().any(|k| k == key) {
+                    issues.push(ConfigIssue::UnknownKey {
+                        profile: profile.clone(),
+                        key: key.clone(),
+                        suggestion: suggest(key, &known),
+                    });
+                }
+            }
+
+            // every profile is checked on its own on top of the defaults
+            let figment = Figment::new().merge(Config::default()).merge(Serialized::defaults(dict));
+            if let Err(err) = figment.extract::<Config>() {
+                for err in err {
+                    issues.push(ConfigIssue::InvalidValue {
+                        profile: profile.clone(),
+                        key: err.path.join("."),
+                        error: err.kind.to_string(),
+                    });
+                }
+            }
+        }
+        Ok(issues)
+    }
+
+    /// Renames the keys of all `issues` that can be fixed by renaming the key in the config file,
+    /// comments and formatting are preserved
+    ///
+    /// Returns the number of renamed keys.
+    pub fn fix_file(path: impl AsRef<Path>, issues: &[ConfigIssue]) -> std::io::Result<usize> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)?;
+        let mut profile: Option<Profile> = None;
+        let mut renamed = 0;
+        let mut fixed = content
+            .lines()
+            .map(|line| {
+                let trimmed = line.trim_start();
+                if trimmed.starts_with('[') {
+                    // only the keys of `[<profile>]` and `[profile.<profile>]` are settings
+                    let header = trimmed.trim_start_matches('[').split(']').next().unwrap_or("");
+                    let header = header.trim();
+                    let name = header
+                        .strip_prefix(Config::PROFILE_SECTION)
+                        .and_then(|name| name.strip_prefix('.'))
+                        .unwrap_or(header);
+                    profile = (!name.contains('.')).then(|| Profile::new(name));
+                    return line.to_string()
+                }
+                let rename = profile.as_ref().zip(line.split_once('=')).and_then(
+                    |(profile, (key, value))| {
+                        let key = key.trim().to_snake_case();
+                        issues
+                            .iter()
+                            .filter(|issue| issue.profile() == profile)
+                            .filter_map(ConfigIssue::rename)
+                            .find(|(old, _)| *old == key)
+                            .map(|(_, new)| (new, value))
+                    },
+                );
+                match rename {
+                    Some((new, value)) => {
+                        renamed += 1;
+                        let indent = &line[..line.len() - trimmed.len()];
+                        format!("{}{} ={}", indent, new, value)
+                    }
+                    None => line.to_string(),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        if content.ends_with('\n') {
+            fixed.push('\n');
+        }
+        std::fs::write(path, fixed)?;
+        Ok(renamed)
+    }
+}
+
+/// Returns all keys that are read from a config file
+fn known_keys() -> Vec<String> {
+    CONFIG_KEYS.iter().chain(EXTERNAL_KEYS).map(|key| key.to_string()).collect()
+}
+
+/// Returns the known key that was most likely meant instead of the unknown `key`
+fn suggest(key: &str, known: &[String]) -> Option<String> {
+    if let Some((_, key)) = MISNAMED_KEYS.iter().find(|(k, _)| *k == key) {
+        return Some(key.to_string())
+    }
+    known
+        .iter()
+        .map(|k| (edit_distance(key, k), k))
+        .filter(|(distance, _)| *distance <= 2)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, k)| k.clone())
+}
+
+/// Returns the levenshtein distance of the strings
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut prev = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = prev[j] + usize::from(ca != *cb);
+            current.push(substitution.min(prev[j + 1] + 1).min(current[j] + 1));
+        }
+        prev = current;
+    }
+    prev[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_validate_and_fix() {
+        figment::Jail::expect_with(|jail| {
+            jail.create_file(
+                "foundry.toml",
+                r#"
+                [default]
+                fuzz_run = 100
+                optimizer_runs = "many"
+
+                [profile.ci]
+                # the rpc to fork from
+                fork_url = "mainnet"
+                verbosity = 4
+            "#,
+            )?;
+
+            let issues = Config::validate_file("foundry.toml").unwrap();
+            assert_eq!(issues.len(), 3);
+            assert!(issues.contains(&ConfigIssue::UnknownKey {
+                profile: Config::DEFAULT_PROFILE,
+                key: "fuzz_run".to_string(),
+                suggestion: Some("fuzz_runs".to_string()),
+            }));
+            assert!(issues.contains(&ConfigIssue::UnknownKey {
+                profile: Profile::new("ci"),
+                key: "fork_url".to_string(),
+                suggestion: Some("eth_rpc_url".to_string()),
+            }));
+            assert!(issues.iter().any(|issue| matches!(
+                issue,
+                ConfigIssue::InvalidValue { key, .. } if key == "optimizer_runs"
+            )));
+
+            assert_eq!(Config::fix_file("foundry.toml", &issues).unwrap(), 2);
+            let content = std::fs::read_to_string("foundry.toml").unwrap();
+            assert!(content.contains("# the rpc to fork from"));
+            assert!(content.contains("eth_rpc_url = \"mainnet\""));
+
+            let issues = Config::validate_file("foundry.toml").unwrap();
+            assert_eq!(issues.len(), 1);
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn warns_about_deprecated_keys() {
+        figment::Jail::expect_with(|jail| {
+            jail.create_file(
+                "foundry.toml",
+                r#"
+                [default]
+                fork_block = 100
+            "#,
+            )?;
+
+            let issues = Config::validate_file("foundry.toml").unwrap();
+            assert_eq!(
+                issues,
+                vec![ConfigIssue::DeprecatedKey {
+                    profile: Config::DEFAULT_PROFILE,
+                    key: "fork_block".to_string(),
+                    replacement: "fork_block_number".to_string(),
+                }]
+            );
+            assert_eq!(
+                issues[0].to_string(),
+                "[default] `fork_block` is deprecated, use `fork_block_number` instead"
+            );
+
+            // the deprecated key is still read
+            assert_eq!(Config::load().fork_block_number, Some(100));
+
+            assert_eq!(Config::fix_file("foundry.toml", &issues).unwrap(), 1);
+            assert!(Config::validate_file("foundry.toml").unwrap().is_empty());
+            assert_eq!(Config::load().fork_block_number, Some(100));
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn config_keys_are_complete() {
+        let serialized = match serde_json::to_value(Config::default()).unwrap() {
+            serde_json::Value::Object(map) => map,
+            _ => unreachable!(),
+        };
+        for key in serialized.keys() {
+            assert!(CONFIG_KEYS.contains(&key.as_str()), "`{}` is missing", key);
+        }
+        assert_eq!(serialized.len(), CONFIG_KEYS.len());
+    }
+}
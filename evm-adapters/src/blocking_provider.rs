@@ -84,6 +84,11 @@ where
         Ok((nonce, balance, code))
     }
 
+    /// Resolves the ENS name to an address.
+    pub fn resolve_name(&self, ens_name: &str) -> Result<Address, M::Error> {
+        self.block_on(self.provider.resolve_name(ens_name))
+    }
+
     /// Gets the current block number.
     pub fn get_block_number(&self) -> Result<U64, M::Error> {
         self.block_on(self.provider.get_block_number())
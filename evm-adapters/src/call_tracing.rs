@@ -59,13 +59,22 @@ impl<'a> ExecutionInfo<'a> {
     }
 }
 
+/// Appends a line to the printed trace in `out`
+macro_rules! push_line {
+    ($out:expr, $($arg:tt)*) => {{
+        $out.push_str(&format!($($arg)*));
+        $out.push('\n');
+    }};
+}
+
 impl Output {
-    /// Prints the output of a function call
-    pub fn print(self, color: Colour, left: &str) {
+    /// Prints the output of a function call to `out`
+    pub fn print(self, color: Colour, left: &str, out: &mut String) {
         match self {
             Output::Token(token) => {
                 let strings = token.iter().map(format_token).collect::<Vec<_>>().join(", ");
-                println!(
+                push_line!(
+                    out,
                     "{}  └─ {} {}",
                     left.replace("├─", "│").replace("└─", "  "),
                     color.paint("←"),
@@ -73,7 +82,8 @@ impl Output {
                 );
             }
             Output::Raw(bytes) => {
-                println!(
+                push_line!(
+                    out,
                     "{}  └─ {} {}",
                     left.replace("├─", "│").replace("└─", "  "),
                     color.paint("←"),
@@ -155,10 +165,7 @@ impl CallTraceArena {
         let res = identified_contracts.get(&trace.addr);
         if res.is_none() {
             let code = if trace.created { trace.output.clone() } else { evm.code(trace.addr) };
-            if let Some((name, (abi, _code))) = contracts
-                .iter()
-                .find(|(_key, (_abi, known_code))| diff_score(known_code, &code) < 0.10)
-            {
+            if let Some((name, abi)) = identify_contract(contracts, &code) {
                 identified_contracts.insert(trace.addr, (name.to_string(), abi.clone()));
             }
         }
@@ -198,12 +205,15 @@ impl CallTraceArena {
     ///
     /// For a user, `left` input should generally be `""`. Left is used recursively
     /// to build the tree print out structure and is built up as we recurse down the tree.
+    ///
+    /// The lines are appended to `out`, so the caller decides where they're printed.
     pub fn pretty_print<'a, S: Clone, E: crate::Evm<S>>(
         &self,
         idx: usize,
         exec_info: &mut ExecutionInfo<'a>,
         evm: &'a E,
         left: &str,
+        out: &mut String,
     ) {
         let trace = &self.arena[idx].trace;
 
@@ -240,17 +250,21 @@ impl CallTraceArena {
         if res.is_none() {
             // get the code to compare
             let code = if trace.created { trace.output.clone() } else { evm.code(trace.addr) };
-            if let Some((name, (abi, _code))) = exec_info
-                .contracts
-                .iter()
-                .find(|(_key, (_abi, known_code))| diff_score(known_code, &code) < 0.10)
-            {
+            if let Some((name, abi)) = identify_contract(exec_info.contracts, &code) {
                 // found matching contract, insert and print
                 exec_info.identified_contracts.insert(trace.addr, (name.to_string(), abi.clone()));
                 if trace.created {
-                    println!("{}{} {}@{}", left, Colour::Yellow.paint("→ new"), name, trace.addr);
-                    self.print_children_and_logs(idx, exec_info, evm, left);
-                    println!(
+                    push_line!(
+                        out,
+                        "{}{} {}@{}",
+                        left,
+                        Colour::Yellow.paint("→ new"),
+                        name,
+                        trace.addr
+                    );
+                    self.print_children_and_logs(idx, exec_info, evm, left, out);
+                    push_line!(
+                        out,
                         "{}  └─ {} {} bytes of code",
                         left.replace("├─", "│").replace("└─", "  "),
                         color.paint("←"),
@@ -258,55 +272,71 @@ impl CallTraceArena {
                     );
                 } else {
                     // re-enter this function at the current node
-                    self.pretty_print(idx, exec_info, evm, left);
+                    self.pretty_print(idx, exec_info, evm, left, out);
                 }
             } else if trace.created {
                 // we couldn't identify, print the children and logs without the abi
-                println!("{}{} <Unknown>@{}", left, Colour::Yellow.paint("→ new"), trace.addr);
-                self.print_children_and_logs(idx, exec_info, evm, left);
-                println!(
+                push_line!(
+                    out,
+                    "{}{} <Unknown>@{}",
+                    left,
+                    Colour::Yellow.paint("→ new"),
+                    trace.addr
+                );
+                self.print_children_and_logs(idx, exec_info, evm, left, out);
+                push_line!(
+                    out,
                     "{}  └─ {} {} bytes of code",
                     left.replace("├─", "│").replace("└─", "  "),
                     color.paint("←"),
                     trace.output.len()
                 );
             } else {
-                let output = trace.print_func_call(exec_info, None, color, left);
-                self.print_children_and_logs(idx, exec_info, evm, left);
-                output.print(color, left);
+                let output = trace.print_func_call(exec_info, None, color, left, out);
+                self.print_children_and_logs(idx, exec_info, evm, left, out);
+                output.print(color, left, out);
             }
         } else if let Some((name, _abi)) = res {
             if trace.created {
-                println!("{}{} {}@{}", left, Colour::Yellow.paint("→ new"), name, trace.addr);
-                self.print_children_and_logs(idx, exec_info, evm, left);
-                println!(
+                push_line!(
+                    out,
+                    "{}{} {}@{}",
+                    left,
+                    Colour::Yellow.paint("→ new"),
+                    name,
+                    trace.addr
+                );
+                self.print_children_and_logs(idx, exec_info, evm, left, out);
+                push_line!(
+                    out,
                     "{}  └─ {} {} bytes of code",
                     left.replace("├─", "│").replace("└─", "  "),
                     color.paint("←"),
                     trace.output.len()
                 );
             } else {
-                let output = trace.print_func_call(exec_info, Some(&name), color, left);
-                self.print_children_and_logs(idx, exec_info, evm, left);
-                output.print(color, left);
+                let output = trace.print_func_call(exec_info, Some(&name), color, left, out);
+                self.print_children_and_logs(idx, exec_info, evm, left, out);
+                output.print(color, left, out);
             }
         }
     }
 
-    /// Prints child calls and logs in order
+    /// Prints child calls and logs in order to `out`
     pub fn print_children_and_logs<'a, S: Clone, E: crate::Evm<S>>(
         &self,
         node_idx: usize,
         exec_info: &mut ExecutionInfo<'a>,
         evm: &'a E,
         left: &str,
+        out: &mut String,
     ) {
         // Ordering stores a vec of `LogCallOrder` which is populated based on if
         // a log or a call was called first. This makes it such that we always print
         // logs and calls in the correct order
         self.arena[node_idx].ordering.iter().for_each(|ordering| match ordering {
             LogCallOrder::Log(index) => {
-                self.arena[node_idx].print_log(*index, exec_info.events, left);
+                self.arena[node_idx].print_log(*index, exec_info.events, left, out);
             }
             LogCallOrder::Call(index) => {
                 self.pretty_print(
@@ -314,12 +344,177 @@ impl CallTraceArena {
                     exec_info,
                     evm,
                     &(left.replace("├─", "│").replace("└─", "  ") + "  ├─ "),
+                    out,
                 );
             }
         });
     }
 }
 
+impl CallTraceArena {
+    /// Diffs this call tree (the old one) against `other` (the new one), returning every frame of
+    /// both trees in depth-first order.
+    ///
+    /// Child calls of frames present in both trees are aligned by their callee and function
+    /// selector, so that an inserted or removed call does not shift all following siblings.
+    pub fn diff(&self, other: &CallTraceArena) -> Vec<CallTraceDiff> {
+        let mut diffs = Vec::new();
+        self.diff_node(self.entry, other, other.entry, &mut diffs);
+        diffs
+    }
+
+    fn diff_node(
+        &self,
+        idx: usize,
+        other: &CallTraceArena,
+        other_idx: usize,
+        diffs: &mut Vec<CallTraceDiff>,
+    ) {
+        let old = &self.arena[idx];
+        let new = &other.arena[other_idx];
+        diffs.push(CallTraceDiff::Matched { old: old.trace.clone(), new: new.trace.clone() });
+
+        // longest common subsequence of the child calls
+        let (a, b) = (&old.children, &new.children);
+        let same =
+            |i: usize, j: usize| self.arena[a[i]].trace.is_same_call(&other.arena[b[j]].trace);
+        let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+        for i in (0..a.len()).rev() {
+            for j in (0..b.len()).rev() {
+                lcs[i][j] = if same(i, j) {
+                    lcs[i + 1][j + 1] + 1
+                } else {
+                    usize::max(lcs[i + 1][j], lcs[i][j + 1])
+                };
+            }
+        }
+
+        let (mut i, mut j) = (0, 0);
+        while i < a.len() && j < b.len() {
+            if same(i, j) {
+                self.diff_node(a[i], other, b[j], diffs);
+                i += 1;
+                j += 1;
+            } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+                self.push_subtree(a[i], CallTraceDiff::Removed, diffs);
+                i += 1;
+            } else {
+                other.push_subtree(b[j], CallTraceDiff::Added, diffs);
+                j += 1;
+            }
+        }
+        a[i..].iter().for_each(|idx| self.push_subtree(*idx, CallTraceDiff::Removed, diffs));
+        b[j..].iter().for_each(|idx| other.push_subtree(*idx, CallTraceDiff::Added, diffs));
+    }
+
+    /// Pushes the node at `idx` and all of its children
+    fn push_subtree(
+        &self,
+        idx: usize,
+        kind: fn(CallTrace) -> CallTraceDiff,
+        diffs: &mut Vec<CallTraceDiff>,
+    ) {
+        let node = &self.arena[idx];
+        diffs.push(kind(node.trace.clone()));
+        node.children.iter().for_each(|child| self.push_subtree(*child, kind, diffs));
+    }
+}
+
+/// A single frame of the diff between two call trees, see [`CallTraceArena::diff`]
+#[derive(Debug, Clone)]
+pub enum CallTraceDiff {
+    /// The call only exists in the new trace
+    Added(CallTrace),
+    /// The call only exists in the old trace
+    Removed(CallTrace),
+    /// The call exists in both traces
+    Matched { old: CallTrace, new: CallTrace },
+}
+
+impl CallTraceDiff {
+    /// The depth of the frame in its call tree
+    pub fn depth(&self) -> usize {
+        match self {
+            CallTraceDiff::Added(trace) | CallTraceDiff::Removed(trace) => trace.depth,
+            CallTraceDiff::Matched { new, .. } => new.depth,
+        }
+    }
+
+    /// The gas difference of the frame, `new - old`
+    pub fn gas_delta(&self) -> i128 {
+        match self {
+            CallTraceDiff::Added(trace) => trace.cost as i128,
+            CallTraceDiff::Removed(trace) => -(trace.cost as i128),
+            CallTraceDiff::Matched { old, new } => new.cost as i128 - old.cost as i128,
+        }
+    }
+
+    /// Whether the return value or the success of the frame changed
+    pub fn output_changed(&self) -> bool {
+        match self {
+            CallTraceDiff::Matched { old, new } => {
+                old.success != new.success || old.output != new.output
+            }
+            _ => true,
+        }
+    }
+
+    /// Whether neither the gas nor the output of the frame changed
+    pub fn is_unchanged(&self) -> bool {
+        matches!(self, CallTraceDiff::Matched { .. }) &&
+            self.gas_delta() == 0 &&
+            !self.output_changed()
+    }
+
+    /// Prints the frame to `out`, using `funcs` to resolve function names
+    pub fn print(&self, funcs: &BTreeMap<[u8; 4], Function>, out: &mut String) {
+        let left = "  ".repeat(self.depth());
+        match self {
+            CallTraceDiff::Added(trace) => push_line!(
+                out,
+                "{}{}",
+                left,
+                Colour::Green.paint(format!("+ [{}] {}", trace.cost, trace.label(funcs)))
+            ),
+            CallTraceDiff::Removed(trace) => push_line!(
+                out,
+                "{}{}",
+                left,
+                Colour::Red.paint(format!("- [{}] {}", trace.cost, trace.label(funcs)))
+            ),
+            CallTraceDiff::Matched { old, new } => {
+                let delta = self.gas_delta();
+                let gas = match delta {
+                    0 => format!("[{}]", new.cost),
+                    d if d > 0 => Colour::Red
+                        .paint(format!("[{} → {} (+{})]", old.cost, new.cost, d))
+                        .to_string(),
+                    d => Colour::Green
+                        .paint(format!("[{} → {} ({})]", old.cost, new.cost, d))
+                        .to_string(),
+                };
+                push_line!(out, "{}  {} {}", left, gas, new.label(funcs));
+                if self.output_changed() {
+                    push_line!(
+                        out,
+                        "{}    {} {}",
+                        left,
+                        Colour::Red.paint("←"),
+                        Colour::Red.paint(old.output_string())
+                    );
+                    push_line!(
+                        out,
+                        "{}    {} {}",
+                        left,
+                        Colour::Green.paint("←"),
+                        Colour::Green.paint(new.output_string())
+                    );
+                }
+            }
+        }
+    }
+}
+
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
 /// A node in the arena
 pub struct CallTraceNode {
@@ -339,8 +534,14 @@ pub struct CallTraceNode {
 }
 
 impl CallTraceNode {
-    /// Prints a log at a particular index, optionally decoding if abi is provided
-    pub fn print_log(&self, index: usize, events: &BTreeMap<H256, Event>, left: &str) {
+    /// Prints a log at a particular index to `out`, optionally decoding if abi is provided
+    pub fn print_log(
+        &self,
+        index: usize,
+        events: &BTreeMap<H256, Event>,
+        left: &str,
+        out: &mut String,
+    ) {
         let log = &self.logs[index];
         let right = "  ├─ ";
 
@@ -352,7 +553,8 @@ impl CallTraceNode {
                     .map(|param| format!("{}: {}", param.name, format_token(&param.value)))
                     .collect::<Vec<String>>()
                     .join(", ");
-                println!(
+                push_line!(
+                    out,
                     "{}emit {}({})",
                     left.replace("├─", "│") + right,
                     Colour::Cyan.paint(event.name.clone()),
@@ -369,7 +571,8 @@ impl CallTraceNode {
             } else {
                 "  ├─"
             };
-            println!(
+            push_line!(
+                out,
                 "{}{}topic {}: {}",
                 if i == 0 {
                     left.replace("├─", "│") + right
@@ -381,7 +584,8 @@ impl CallTraceNode {
                 Colour::Cyan.paint(format!("0x{}", hex::encode(&topic)))
             )
         }
-        println!(
+        push_line!(
+            out,
             "{}        data: {}",
             left.replace("├─", "│").replace("└─", "  ") + "  │  ",
             Colour::Cyan.paint(format!("0x{}", hex::encode(&log.data)))
@@ -432,13 +636,50 @@ impl CallTrace {
         self.addr = new_trace.addr;
     }
 
-    /// Prints function call, returning the decoded or raw output
+    /// Whether both traces are calls to the same function on the same contract, or both are
+    /// contract creations
+    fn is_same_call(&self, other: &Self) -> bool {
+        if self.created || other.created {
+            return self.created == other.created
+        }
+        self.addr == other.addr && self.data.get(..4) == other.data.get(..4)
+    }
+
+    /// Returns a short, single line description of the call
+    fn label(&self, funcs: &BTreeMap<[u8; 4], Function>) -> String {
+        if self.created {
+            return format!("new@{:?}", self.addr)
+        }
+        let name = match self.data.get(..4) {
+            Some(selector) => funcs
+                .get(selector)
+                .map(|func| func.name.clone())
+                .unwrap_or_else(|| hex::encode(selector)),
+            None => "fallback".to_string(),
+        };
+        format!("{:?}::{}", self.addr, name)
+    }
+
+    /// Returns the raw output as hex, including the success status of the call
+    fn output_string(&self) -> String {
+        let status = if self.success { "" } else { "revert " };
+        if self.created {
+            format!("{}{} bytes of code", status, self.output.len())
+        } else if self.output.is_empty() {
+            format!("{}()", status)
+        } else {
+            format!("{}0x{}", status, hex::encode(&self.output))
+        }
+    }
+
+    /// Prints function call to `out`, returning the decoded or raw output
     pub fn print_func_call<'a>(
         &self,
         exec_info: &mut ExecutionInfo<'a>,
         name: Option<&String>,
         color: Colour,
         left: &str,
+        out: &mut String,
     ) -> Output {
         // Is data longer than 4, meaning we can attempt to decode it
         if self.data.len() >= 4 {
@@ -459,7 +700,8 @@ impl CallTrace {
                     }
                 }
 
-                println!(
+                push_line!(
+                    out,
                     "{}[{}] {}::{}{}({})",
                     left,
                     self.cost,
@@ -491,7 +733,8 @@ impl CallTrace {
             }
         } else {
             // fallback function
-            println!(
+            push_line!(
+                out,
                 "{}[{}] {}::fallback{}()",
                 left,
                 self.cost,
@@ -514,7 +757,8 @@ impl CallTrace {
         }
 
         // We couldn't decode the function call, so print it as an abstract call
-        println!(
+        push_line!(
+            out,
             "{}[{}] {}::{}{}({})",
             left,
             self.cost,
@@ -547,6 +791,80 @@ impl CallTrace {
     }
 }
 
+/// Returns the name and abi of the known contract the deployed runtime `code` belongs to
+///
+/// The code is matched, in this order, exactly, by the hash of the metadata solc appends, with
+/// the immutables and the library address masked, and as a fallback by the closest fuzzy match.
+pub fn identify_contract<'a>(
+    contracts: &'a BTreeMap<String, (Abi, Vec<u8>)>,
+    code: &[u8],
+) -> Option<(&'a String, &'a Abi)> {
+    if code.is_empty() {
+        return None
+    }
+    let known = || contracts.iter().filter(|(_, (_, known_code))| !known_code.is_empty());
+
+    let found = known()
+        .find(|(_, (_, known_code))| known_code == code)
+        .or_else(|| {
+            let hash = metadata(code)?;
+            known().find(|(_, (_, known_code))| metadata(known_code) == Some(hash))
+        })
+        .or_else(|| known().find(|(_, (_, known_code))| matches_masked(known_code, code)))
+        .or_else(|| {
+            known()
+                .map(|found| (found, diff_score(&(found.1).1, code)))
+                .filter(|(_, score)| *score < 0.10)
+                .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
+                .map(|(found, _)| found)
+        });
+    found.map(|(name, (abi, _))| (name, abi))
+}
+
+/// Returns the CBOR encoded metadata solc appends to the runtime code, which contains the hash of
+/// the contract's metadata and is not affected by immutables
+pub fn metadata(code: &[u8]) -> Option<&[u8]> {
+    let len_start = code.len().checked_sub(2)?;
+    let len = u16::from_be_bytes([code[len_start], code[len_start + 1]]) as usize;
+    let metadata = &code[len_start.checked_sub(len)?..len_start];
+    // a CBOR map with the `ipfs`, `bzzr0` or `bzzr1` hash
+    let is_map = matches!(metadata.first(), Some(0xa1..=0xa5));
+    let has_hash = metadata.windows(4).any(|key| key == b"ipfs" || key == b"bzzr");
+    (is_map && has_hash).then(|| metadata)
+}
+
+/// Whether the code matches the known runtime code, ignoring the metadata and the values of the
+/// immutables and of the library address, which solc leaves as zero `PUSH32` and `PUSH20`
+/// placeholders and which are filled in at deployment
+fn matches_masked(known_code: &[u8], code: &[u8]) -> bool {
+    fn strip(code: &[u8]) -> &[u8] {
+        match metadata(code) {
+            Some(metadata) => &code[..code.len() - metadata.len() - 2],
+            None => code,
+        }
+    }
+    let (known_code, code) = (strip(known_code), strip(code));
+    if known_code.len() != code.len() {
+        return false
+    }
+    let mut pc = 0;
+    while pc < known_code.len() {
+        let op = known_code[pc];
+        if code[pc] != op {
+            return false
+        }
+        let push_len = if (0x60..=0x7f).contains(&op) { (op - 0x5f) as usize } else { 0 };
+        let end = usize::min(pc + 1 + push_len, known_code.len());
+        let immediate = &known_code[pc + 1..end];
+        let is_placeholder = matches!(push_len, 20 | 32) && immediate.iter().all(|byte| *byte == 0);
+        if !is_placeholder && code[pc + 1..end] != *immediate {
+            return false
+        }
+        pc = end;
+    }
+    true
+}
+
 // very simple fuzzy matching to account for immutables. Will fail for small contracts that are
 // basically all immutable vars
 fn diff_score(bytecode1: &[u8], bytecode2: &[u8]) -> f64 {
@@ -567,3 +885,90 @@ fn diff_score(bytecode1: &[u8], bytecode2: &[u8]) -> f64 {
     // println!("diff_score {}", diff_chars as f64 / cutoff_len as f64);
     diff_chars as f64 / cutoff_len as f64
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trace(depth: usize, addr: u64, selector: [u8; 4], cost: u64, output: u8) -> CallTrace {
+        CallTrace {
+            depth,
+            success: true,
+            addr: H160::from_low_u64_be(addr),
+            data: selector.to_vec(),
+            cost,
+            output: vec![output],
+            ..Default::default()
+        }
+    }
+
+    fn arena(traces: Vec<CallTrace>) -> CallTraceArena {
+        let mut arena = CallTraceArena::default();
+        for mut trace in traces {
+            arena.push_trace(0, &mut trace);
+        }
+        arena
+    }
+
+    #[test]
+    fn identifies_contracts() {
+        // PUSH32 <immutable> PUSH1 0x01 followed by metadata with an ipfs hash
+        let mut known_code = vec![0x7f];
+        known_code.extend([0u8; 32]);
+        known_code.extend([0x60, 0x01, 0x00]);
+        let metadata = [&[0xa1, 0x64][..], b"ipfs", &[0x42, 0x12, 0x34], &[0x00, 0x09]].concat();
+        let contracts = BTreeMap::from([
+            ("Immutable".to_string(), (Abi::default(), [&known_code[..], &metadata].concat())),
+            ("Other".to_string(), (Abi::default(), vec![0x60, 0x02, 0x00])),
+        ]);
+
+        // the immutable is set and the metadata differs
+        let mut code = vec![0x7f];
+        code.extend([0xffu8; 32]);
+        code.extend([0x60, 0x01, 0x00]);
+        let (name, _) = identify_contract(&contracts, &code).unwrap();
+        assert_eq!(name, "Immutable");
+
+        // same metadata, different code
+        let code = [&[0x60, 0x03, 0x00][..], &metadata].concat();
+        let (name, _) = identify_contract(&contracts, &code).unwrap();
+        assert_eq!(name, "Immutable");
+
+        let (name, _) = identify_contract(&contracts, &[0x60, 0x02, 0x00]).unwrap();
+        assert_eq!(name, "Other");
+        assert!(identify_contract(&contracts, &[0x61, 0x12, 0x34, 0x56]).is_none());
+        assert!(identify_contract(&contracts, &[]).is_none());
+    }
+
+    #[test]
+    fn diffs_call_trees() {
+        let old = arena(vec![
+            trace(0, 1, [0; 4], 100, 0),
+            trace(1, 2, [1; 4], 30, 1),
+            trace(1, 3, [2; 4], 20, 2),
+        ]);
+        let new = arena(vec![
+            trace(0, 1, [0; 4], 90, 0),
+            trace(1, 4, [3; 4], 5, 3),
+            trace(1, 3, [2; 4], 20, 5),
+        ]);
+
+        let diffs = old.diff(&new);
+        assert_eq!(diffs.len(), 4);
+
+        assert!(matches!(diffs[0], CallTraceDiff::Matched { .. }));
+        assert_eq!(diffs[0].gas_delta(), -10);
+        assert!(!diffs[0].output_changed());
+
+        assert!(matches!(diffs[1], CallTraceDiff::Removed(_)));
+        assert_eq!(diffs[1].gas_delta(), -30);
+        assert!(matches!(diffs[2], CallTraceDiff::Added(_)));
+        assert_eq!(diffs[2].gas_delta(), 5);
+
+        assert!(matches!(diffs[3], CallTraceDiff::Matched { .. }));
+        assert_eq!(diffs[3].gas_delta(), 0);
+        assert!(diffs[3].output_changed());
+
+        assert!(old.diff(&old).iter().all(CallTraceDiff::is_unchanged));
+    }
+}
@@ -0,0 +1,188 @@
+//! Chain specific system contracts and precompiles
+use ethers::{
+    types::{Address, Bytes, U256},
+    utils::id,
+};
+
+pub use foundry_config::ChainProfile;
+
+#[cfg(feature = "sputnik")]
+use crate::sputnik::{PrecompileFn, PRECOMPILES_MAP};
+#[cfg(feature = "sputnik")]
+use std::collections::BTreeMap;
+
+/// Precompiles by their address
+#[cfg(feature = "sputnik")]
+pub type Precompiles = BTreeMap<Address, PrecompileFn>;
+
+/// Returns the precompiles of the chain profile.
+///
+/// The supported L2s have the Ethereum precompiles at the same addresses. Their own precompiles
+/// read the block environment, which isn't passed to precompiles, so they are emulated by the
+/// [`system_contracts`] instead.
+#[cfg(feature = "sputnik")]
+pub fn precompiles(profile: ChainProfile) -> &'static Precompiles {
+    match profile {
+        ChainProfile::Ethereum | ChainProfile::Optimism | ChainProfile::Arbitrum => {
+            &PRECOMPILES_MAP
+        }
+    }
+}
+
+/// Returns the system contracts of the chain and their addresses.
+///
+/// The system contracts of L2s are precompiles which need access to the block environment, they
+/// are emulated with minimal contracts that return the values of the local environment.
+pub fn system_contracts(profile: ChainProfile) -> Vec<(Address, Bytes)> {
+    match profile {
+        ChainProfile::Ethereum => vec![],
+        ChainProfile::Optimism => vec![
+            // OVM_GasPriceOracle, there's no L1 locally so the L1 fee is always 0
+            (
+                address("0x420000000000000000000000000000000000000F"),
+                getters(&[
+                    ("gasPrice()", Getter::Opcode(GASPRICE)),
+                    ("baseFee()", Getter::Opcode(BASEFEE)),
+                    ("l1BaseFee()", Getter::Constant(U256::zero())),
+                    ("overhead()", Getter::Constant(U256::zero())),
+                    ("scalar()", Getter::Constant(U256::zero())),
+                    ("decimals()", Getter::Constant(6u64.into())),
+                    ("getL1Fee(bytes)", Getter::Constant(U256::zero())),
+                    ("getL1GasUsed(bytes)", Getter::Constant(U256::zero())),
+                ]),
+            ),
+            // L1Block, the local block is used as the L1 block
+            (
+                address("0x4200000000000000000000000000000000000015"),
+                getters(&[
+                    ("number()", Getter::Opcode(NUMBER)),
+                    ("timestamp()", Getter::Opcode(TIMESTAMP)),
+                    ("basefee()", Getter::Opcode(BASEFEE)),
+                ]),
+            ),
+        ],
+        ChainProfile::Arbitrum => vec![
+            // ArbSys
+            (
+                Address::from_low_u64_be(0x64),
+                getters(&[
+                    ("arbBlockNumber()", Getter::Opcode(NUMBER)),
+                    ("arbChainID()", Getter::Opcode(CHAINID)),
+                    ("isTopLevelCall()", Getter::Constant(U256::one())),
+                    ("wasMyCallersAddressAliased()", Getter::Constant(U256::zero())),
+                ]),
+            ),
+            // ArbGasInfo, there's no L1 locally so the L1 fee is always 0
+            (
+                Address::from_low_u64_be(0x6c),
+                getters(&[
+                    ("getL1BaseFeeEstimate()", Getter::Constant(U256::zero())),
+                    ("getCurrentTxL1GasFees()", Getter::Constant(U256::zero())),
+                    ("getMinimumGasPrice()", Getter::Opcode(BASEFEE)),
+                ]),
+            ),
+        ],
+    }
+}
+
+const TIMESTAMP: u8 = 0x42;
+const NUMBER: u8 = 0x43;
+const CHAINID: u8 = 0x46;
+const BASEFEE: u8 = 0x48;
+const GASPRICE: u8 = 0x3a;
+
+/// The value returned by a function of a system contract
+enum Getter {
+    /// The value pushed by the opcode
+    Opcode(u8),
+    /// A fixed value
+    Constant(U256),
+}
+
+fn address(s: &str) -> Address {
+    s.parse().expect("valid address")
+}
+
+/// Assembles a contract that returns the value of the getter whose signature matches the
+/// selector of the calldata and reverts for unknown selectors. Arguments are ignored.
+fn getters(getters: &[(&str, Getter)]) -> Bytes {
+    // PUSH1 0 CALLDATALOAD PUSH1 0xe0 SHR
+    let mut code = vec![0x60, 0x00, 0x35, 0x60, 0xe0, 0x1c];
+    // every dispatch is DUP1 PUSH4 <selector> EQ PUSH2 <dest> JUMPI, 11 bytes
+    // followed by PUSH1 0 DUP1 REVERT, 4 bytes
+    let mut dest = code.len() + getters.len() * 11 + 4;
+    let mut bodies = Vec::new();
+    for (signature, getter) in getters {
+        code.push(0x80);
+        code.push(0x63);
+        code.extend_from_slice(&id(signature));
+        code.push(0x14);
+        code.push(0x61);
+        code.extend_from_slice(&(dest as u16).to_be_bytes());
+        code.push(0x57);
+
+        // JUMPDEST <value> PUSH1 0 MSTORE PUSH1 0x20 PUSH1 0 RETURN
+        let mut body = vec![0x5b];
+        match getter {
+            Getter::Opcode(op) => body.push(*op),
+            Getter::Constant(value) => {
+                let mut word = [0u8; 32];
+                value.to_big_endian(&mut word);
+                body.push(0x7f);
+                body.extend_from_slice(&word);
+            }
+        }
+        body.extend_from_slice(&[0x60, 0x00, 0x52, 0x60, 0x20, 0x60, 0x00, 0xf3]);
+        dest += body.len();
+        bodies.extend(body);
+    }
+    code.extend_from_slice(&[0x60, 0x00, 0x80, 0xfd]);
+    code.extend(bodies);
+    code.into()
+}
+
+#[cfg(all(test, feature = "sputnik"))]
+mod tests {
+    use super::*;
+    use crate::{sputnik::helpers::vm, Evm};
+
+    #[test]
+    fn arbitrum_system_contracts() {
+        let mut evm = vm();
+        evm.initialize_contracts(system_contracts(ChainProfile::Arbitrum));
+        let arb_sys = Address::from_low_u64_be(0x64);
+
+        let (retdata, status, _, _) = evm
+            .call_raw(Address::zero(), arb_sys, id("arbChainID()").to_vec().into(), 0.into(), true)
+            .unwrap();
+        assert!(status.is_succeed());
+        assert_eq!(U256::from_big_endian(&retdata), U256::one());
+
+        let (retdata, status, _, _) = evm
+            .call_raw(
+                Address::zero(),
+                arb_sys,
+                id("isTopLevelCall()").to_vec().into(),
+                0.into(),
+                true,
+            )
+            .unwrap();
+        assert!(status.is_succeed());
+        assert_eq!(U256::from_big_endian(&retdata), U256::one());
+
+        let (_, status, _, _) = evm
+            .call_raw(Address::zero(), arb_sys, id("unknown()").to_vec().into(), 0.into(), true)
+            .unwrap();
+        assert!(!status.is_succeed());
+    }
+
+    #[test]
+    fn l2s_have_the_ethereum_precompiles() {
+        let ecrecover = Address::from_low_u64_be(1);
+        for profile in [ChainProfile::Ethereum, ChainProfile::Optimism, ChainProfile::Arbitrum] {
+            let precompiles = precompiles(profile);
+            assert_eq!(precompiles.len(), PRECOMPILES_MAP.len());
+            assert!(precompiles.contains_key(&ecrecover));
+        }
+    }
+}
@@ -1,6 +1,7 @@
+use crate::chain_profile::ChainProfile;
 use ethers::types::{Address, U256};
 use serde::{Deserialize, Serialize};
-use std::str::FromStr;
+use std::{collections::BTreeMap, path::PathBuf, str::FromStr};
 
 #[cfg(feature = "evmodin")]
 use evmodin::util::mocked_host::MockedHost;
@@ -55,6 +56,9 @@ pub struct EvmOpts {
     /// the EVM type you want to use (e.g. sputnik, evmodin)
     pub evm_type: EvmType,
 
+    /// the chain whose system contracts are available
+    pub chain_profile: ChainProfile,
+
     /// fetch state over a remote instead of starting from empty state
     #[serde(rename = "eth_rpc_url")]
     pub fork_url: Option<String>,
@@ -62,6 +66,24 @@ pub struct EvmOpts {
     /// pins the block number for the state fork
     pub fork_block_number: Option<u64>,
 
+    /// disables storage caching entirely
+    pub no_storage_caching: bool,
+
+    /// verifies all fetched fork state via `eth_getProof` against the block's state root
+    pub verify_fork_state: bool,
+
+    /// forbids network access, a fork is served from the state cached on disk by previous runs
+    pub offline: bool,
+
+    /// accounts to fetch from the fork before any test is executed
+    pub prefetch_accounts: Vec<Address>,
+
+    /// path to a json file with geth-style state overrides that are applied on top of the state
+    pub state_overrides: Option<PathBuf>,
+
+    /// named rpc urls that are available to the `rpcUrl` cheatcode
+    pub rpc_endpoints: BTreeMap<String, String>,
+
     /// the initial balance of each deployed test contract
     pub initial_balance: U256,
 
@@ -71,11 +93,54 @@ pub struct EvmOpts {
     /// enables the FFI cheatcode
     pub ffi: bool,
 
+    /// disables the transaction and block gas limits and funds the sender with an unlimited
+    /// balance
+    pub unlimited_gas: bool,
+
     /// Verbosity mode of EVM output as number of occurences
     pub verbosity: u8,
 
     /// enable debugger
     pub debug: bool,
+
+    /// the maximum number of steps the debugger records per call, the oldest steps are dropped
+    /// first
+    pub debug_step_limit: Option<usize>,
+
+    /// block environment values that were set explicitly and take precedence over the values of
+    /// the forked block
+    #[serde(skip)]
+    pub env_overrides: EnvOverrides,
+
+    /// records the EIP-2930 access list of the executed test function
+    #[serde(skip)]
+    pub record_access_list: bool,
+
+    /// warns about tests that don't observe the state `setUp` left behind
+    #[serde(skip)]
+    pub check_isolation: bool,
+
+    /// creation code of the contracts registered in the config, which the `getCode` and
+    /// `deployCode` cheatcodes resolve by name
+    #[serde(skip)]
+    pub bytecode_contracts: BTreeMap<String, Vec<u8>>,
+
+    /// addresses of the contracts deployed by broadcasts by chain id and name, which the
+    /// `getDeployment` cheatcode resolves
+    #[serde(skip)]
+    pub deployments: BTreeMap<u64, BTreeMap<String, Address>>,
+}
+
+impl EvmOpts {
+    /// Returns the gas limit of a transaction, which is effectively unlimited if `unlimited_gas`
+    /// is set
+    pub fn gas_limit(&self) -> u64 {
+        if self.unlimited_gas {
+            u64::MAX
+        } else {
+            self.env.gas_limit
+        }
+    }
 }
 
 #[cfg(feature = "sputnik")]
@@ -86,8 +151,17 @@ pub use sputnik_helpers::BackendKind;
 mod sputnik_helpers {
     use super::*;
 
-    use crate::{sputnik::cache::SharedBackend, FAUCET_ACCOUNT};
-    use ethers::providers::Provider;
+    use crate::{
+        sputnik::{
+            cache::SharedBackend,
+            disk_cache::{
+                block_cache_file, block_env_file, cached_chain_ids, read_block_env, write_block_env,
+            },
+            state_override::read_state_overrides,
+            DiskCache,
+        },
+        BlockingProvider, ForkClient, FAUCET_ACCOUNT,
+    };
     use sputnik::backend::MemoryBackend;
 
     pub enum BackendKind<'a> {
@@ -100,6 +174,17 @@ mod sputnik_helpers {
         pub fn backend<'a>(
             &'a self,
             vicinity: &'a MemoryVicinity,
+        ) -> eyre::Result<BackendKind<'a>> {
+            self.backend_with_progress(vicinity, |_, _| {})
+        }
+
+        /// Returns the backend like [`Self::backend`], calling `progress` with the number of
+        /// fetched and all accounts while the `prefetch_accounts` of a fork are fetched
+        #[cfg(feature = "sputnik")]
+        pub fn backend_with_progress<'a>(
+            &'a self,
+            vicinity: &'a MemoryVicinity,
+            progress: impl Fn(usize, usize),
         ) -> eyre::Result<BackendKind<'a>> {
             let mut backend = MemoryBackend::new(vicinity, Default::default());
             // max out the balance of the faucet
@@ -111,19 +196,71 @@ mod sputnik_helpers {
             // `0x00a329c0648769A73afAc7F9381E08FB43dBEA72`
             let deployer = backend.state_mut().entry(self.sender).or_insert_with(Default::default);
             deployer.nonce = U256::from(1);
+            if self.unlimited_gas {
+                // leave room for incoming transfers
+                deployer.balance = U256::MAX >> 1;
+            }
+
+            let overrides = self.state_overrides.as_ref().map(read_state_overrides).transpose()?;
 
             let backend = if let Some(ref url) = self.fork_url {
-                let provider = Provider::try_from(url.as_str())?;
+                if self.offline {
+                    self.offline_fork()?;
+                }
+                let provider = ForkClient::provider(url.as_str())?;
                 let init_state = backend.state().clone();
                 let cache = crate::sputnik::new_shared_cache(init_state);
-                let backend = SharedBackend::new(
-                    provider,
-                    cache,
-                    vicinity.clone(),
-                    self.fork_block_number.map(Into::into),
-                );
+                // only a pinned block can be cached, `latest` changes between runs, state loaded
+                // from disk can't be verified
+                let disk_cache = self
+                    .fork_block_number
+                    .filter(|_| !self.no_storage_caching && !self.verify_fork_state)
+                    .and_then(|block| block_cache_file(vicinity.chain_id.as_u64(), block))
+                    .map(|path| DiskCache::load(path, cache.clone()));
+                let mut backend = if self.offline {
+                    SharedBackend::new_offline(
+                        provider,
+                        cache,
+                        vicinity.clone(),
+                        self.fork_block_number.map(Into::into),
+                    )
+                } else if self.verify_fork_state {
+                    // proofs can only be verified against a fixed block
+                    let block = self.fork_block_number.unwrap_or(vicinity.block_number.as_u64());
+                    let (header, _) =
+                        BlockingProvider::new(provider.clone()).block_and_chainid(Some(block))?;
+                    SharedBackend::new_verified(
+                        provider,
+                        cache,
+                        vicinity.clone(),
+                        block.into(),
+                        header.state_root,
+                    )
+                } else {
+                    SharedBackend::new(
+                        provider,
+                        cache,
+                        vicinity.clone(),
+                        self.fork_block_number.map(Into::into),
+                    )
+                };
+                if let Some(disk_cache) = disk_cache {
+                    backend = backend.with_disk_cache(disk_cache);
+                }
+                if let Some(overrides) = overrides {
+                    backend = backend.with_state_overrides(overrides);
+                }
+                if !self.prefetch_accounts.is_empty() {
+                    backend.prefetch_accounts_with_progress(
+                        self.prefetch_accounts.iter().copied(),
+                        progress,
+                    )?;
+                }
                 BackendKind::Shared(backend)
             } else {
+                for (addr, acc) in overrides.into_iter().flatten() {
+                    acc.apply(backend.state_mut().entry(addr).or_insert_with(Default::default));
+                }
                 BackendKind::Simple(backend)
             };
 
@@ -132,18 +269,136 @@ mod sputnik_helpers {
 
         #[cfg(feature = "sputnik")]
         pub fn vicinity(&self) -> eyre::Result<MemoryVicinity> {
-            Ok(if let Some(ref url) = self.fork_url {
-                let provider = ethers::providers::Provider::try_from(url.as_str())?;
-                let rt = tokio::runtime::Runtime::new().expect("could not start tokio rt");
-                rt.block_on(crate::sputnik::vicinity(
-                    &provider,
-                    self.env.chain_id,
-                    self.fork_block_number,
-                    Some(self.env.tx_origin),
-                ))?
+            let mut vicinity = if let Some(ref url) = self.fork_url {
+                let mut vicinity = if self.offline {
+                    let (chain_id, block) = self.offline_fork()?;
+                    let path = block_env_file(chain_id, block)
+                        .ok_or_else(|| eyre::eyre!("no home directory for the fork cache"))?;
+                    read_block_env(path, self.env.tx_origin)?
+                } else {
+                    let provider = ForkClient::provider(url.as_str())?;
+                    let rt = tokio::runtime::Runtime::new().expect("could not start tokio rt");
+                    let vicinity = rt.block_on(crate::sputnik::vicinity(
+                        &provider,
+                        self.env.chain_id,
+                        self.fork_block_number,
+                        Some(self.env.tx_origin),
+                    ))?;
+                    // the environment of a cached block is cached too, so it can be forked offline
+                    if let Some(path) = self
+                        .fork_block_number
+                        .filter(|_| !self.no_storage_caching)
+                        .and_then(|block| block_env_file(vicinity.chain_id.as_u64(), block))
+                    {
+                        if let Err(err) = write_block_env(&path, &vicinity) {
+                            tracing::warn!(?err, "failed to write block env to {}", path.display())
+                        }
+                    }
+                    vicinity
+                };
+                self.env_overrides.apply(&mut vicinity);
+                vicinity
             } else {
                 self.env.sputnik_state()
-            })
+            };
+            if self.unlimited_gas {
+                vicinity.block_gas_limit = self.gas_limit().into();
+            }
+            Ok(vicinity)
+        }
+
+        /// Returns the chain id and the block of the fork if its state and environment are cached
+        /// on disk, which is required to fork offline
+        ///
+        /// If no chain id is set, the block must be cached for a single chain only.
+        fn offline_fork(&self) -> eyre::Result<(u64, u64)> {
+            let block = self.fork_block_number.ok_or_else(|| {
+                eyre::eyre!("forking offline requires a pinned block, set a `--fork-block-number`")
+            })?;
+            if self.no_storage_caching {
+                eyre::bail!("forking offline requires the fork cache, which is disabled by `--no-storage-caching`")
+            }
+            if self.verify_fork_state {
+                eyre::bail!("the fork state can't be verified offline")
+            }
+            let chain_id = match self.env.chain_id {
+                Some(chain_id) => chain_id,
+                None => match cached_chain_ids(block)[..] {
+                    [chain_id] => chain_id,
+                    [] => eyre::bail!(
+                        "the state of block {} is not cached, run once online to cache it",
+                        block
+                    ),
+                    _ => eyre::bail!(
+                        "the state of block {} is cached for multiple chains, set a `--chain-id`",
+                        block
+                    ),
+                },
+            };
+            let cached = |path: Option<std::path::PathBuf>| path.map_or(false, |p| p.is_file());
+            if !cached(block_cache_file(chain_id, block)) ||
+                !cached(block_env_file(chain_id, block))
+            {
+                eyre::bail!(
+                    "the state of block {} of chain {} is not cached, run once online to cache it",
+                    block,
+                    chain_id
+                )
+            }
+            Ok((chain_id, block))
+        }
+    }
+}
+
+/// Block environment values that replace the values of the forked block's header
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EnvOverrides {
+    /// the tx.gasprice value during EVM execution
+    pub gas_price: Option<u64>,
+
+    /// the base fee in a block
+    pub block_base_fee_per_gas: Option<u64>,
+
+    /// the block.coinbase value during EVM execution
+    pub block_coinbase: Option<Address>,
+
+    /// the block.timestamp value during EVM execution
+    pub block_timestamp: Option<u64>,
+
+    /// the block.number value during EVM execution
+    pub block_number: Option<u64>,
+
+    /// the block.difficulty value during EVM execution
+    pub block_difficulty: Option<u64>,
+
+    /// the block.gaslimit value during EVM execution
+    pub block_gas_limit: Option<u64>,
+}
+
+impl EnvOverrides {
+    /// Replaces the values of the `vicinity` with all values that are set
+    #[cfg(feature = "sputnik")]
+    pub fn apply(&self, vicinity: &mut MemoryVicinity) {
+        if let Some(gas_price) = self.gas_price {
+            vicinity.gas_price = gas_price.into();
+        }
+        if let Some(base_fee) = self.block_base_fee_per_gas {
+            vicinity.block_base_fee_per_gas = base_fee.into();
+        }
+        if let Some(coinbase) = self.block_coinbase {
+            vicinity.block_coinbase = coinbase;
+        }
+        if let Some(timestamp) = self.block_timestamp {
+            vicinity.block_timestamp = timestamp.into();
+        }
+        if let Some(number) = self.block_number {
+            vicinity.block_number = number.into();
+        }
+        if let Some(difficulty) = self.block_difficulty {
+            vicinity.block_difficulty = difficulty.into();
+        }
+        if let Some(gas_limit) = self.block_gas_limit {
+            vicinity.block_gas_limit = gas_limit.into();
         }
     }
 }
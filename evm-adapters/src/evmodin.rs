@@ -3,7 +3,10 @@ use crate::Evm;
 #[cfg(feature = "sputnik")]
 use crate::sputnik::cheatcodes::debugger::DebugArena;
 
-use ethers::types::{Address, Bytes, U256};
+use ethers::{
+    types::{Address, Bytes, U256},
+    utils::get_contract_address,
+};
 
 use evmodin::{tracing::Tracer, AnalyzedCode, CallKind, Host, Message, Revision, StatusCode};
 
@@ -37,6 +40,8 @@ pub trait HostExt: Host {
     fn set_code(&mut self, address: Address, code: bytes::Bytes);
     /// Sets the account's balance to the provided value.
     fn set_balance(&mut self, address: Address, balance: U256);
+    /// Increments the account's nonce and returns the nonce before the increment.
+    fn increment_nonce(&mut self, address: Address) -> U256;
 }
 
 impl<S: HostExt, Tr: Tracer> Evm<S> for EvmOdin<S, Tr> {
@@ -102,14 +107,45 @@ impl<S: HostExt, Tr: Tracer> Evm<S> for EvmOdin<S, Tr> {
         vec![]
     }
 
-    #[allow(unused)]
+    /// Deploys the provided contract bytecode
     fn deploy(
         &mut self,
         from: Address,
         calldata: Bytes,
         value: U256,
     ) -> Result<(Address, Self::ReturnReason, u64, Vec<String>)> {
-        unimplemented!("Contract deployment is not implemented for evmodin yet")
+        // the created contract address is pre-computed by using the account's nonce before it
+        // executes the contract deployment transaction.
+        let nonce = self.host.increment_nonce(from);
+        let address = get_contract_address(from, nonce);
+
+        let message = Message {
+            sender: from,
+            recipient: address,
+            code_address: address,
+            depth: 0,
+            kind: CallKind::Create,
+            input_data: Default::default(),
+            value,
+            gas: self.gas_limit as i64,
+            is_static: false,
+        };
+
+        // the init code is executed in the context of the new account and returns the runtime code
+        let bytecode = AnalyzedCode::analyze(calldata.as_ref());
+        let output =
+            bytecode.execute(&mut self.host, &mut self.tracer, None, message, self.revision);
+        let gas = self.gas_limit - output.gas_left as u64;
+
+        if !Self::is_success(&output.status_code) {
+            tracing::trace!(status = ?output.status_code, "failed");
+            eyre::bail!("deployment reverted, reason: {:?}", output.status_code)
+        }
+        self.host.set_code(address, output.output_data);
+        tracing::trace!(?address, ?gas, "success");
+
+        // TODO: Add emitted event logs.
+        Ok((address, output.status_code, gas, vec![]))
     }
 
     /// Runs the selected function
@@ -175,18 +211,25 @@ mod helpers {
             let entry = self.accounts.entry(address).or_insert_with(Default::default);
             entry.balance = amount;
         }
+
+        fn increment_nonce(&mut self, address: Address) -> U256 {
+            let entry = self.accounts.entry(address).or_insert_with(Default::default);
+            let nonce = entry.nonce;
+            entry.nonce += 1;
+            nonce.into()
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::test_helpers::{can_call_vm_directly, solidity_unit_test, COMPILED};
+    use crate::test_helpers::{
+        can_call_vm_directly, can_snapshot_and_revert, solidity_unit_test, COMPILED,
+    };
     use evmodin::{tracing::NoopTracer, util::mocked_host::MockedHost};
 
     #[test]
-    #[ignore]
-    // TODO: Ignore until we figure out how to deploy stuff in evmodin
     fn evmodin_can_call_vm_directly() {
         let revision = Revision::Istanbul;
         let compiled = COMPILED.find("Greeter").expect("could not find contract");
@@ -200,7 +243,15 @@ mod tests {
     }
 
     #[test]
-    // TODO: This fails because the cross-contract host does not work.
+    fn evmodin_can_snapshot_and_revert() {
+        let compiled = COMPILED.find("Greeter").expect("could not find contract");
+        let evm = EvmOdin::new(MockedHost::default(), 12_000_000, Revision::Istanbul, NoopTracer);
+        can_snapshot_and_revert(evm, compiled);
+    }
+
+    #[test]
+    // TODO: This fails because the `MockedHost` doesn't execute calls to other contracts, and
+    // there are no cheatcode hooks for the test's `vm` calls.
     #[ignore]
     fn evmodin_can_call_solidity_unit_test() {
         let revision = Revision::Istanbul;
@@ -0,0 +1,281 @@
+//! A JSON-RPC client for forking that supports http, websocket and IPC endpoints
+use async_trait::async_trait;
+use ethers::providers::{
+    Http, HttpClientError, Ipc, IpcError, JsonRpcClient, Provider, ProviderError, Ws, WsClientError,
+};
+use once_cell::sync::Lazy;
+use parking_lot::{Mutex, RwLock};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{collections::HashMap, fmt::Debug, str::FromStr, sync::Arc, time::Duration};
+
+use crate::rpc_metrics::RPC_METRICS;
+
+/// How often a request is retried on a fresh connection before it fails
+const MAX_RECONNECTS: usize = 3;
+
+/// How often a request is retried after the endpoint rate limited it
+const MAX_RATE_LIMIT_RETRIES: u32 = 8;
+
+/// The backoff before the first retry of a rate limited request, doubled on every retry
+const INITIAL_BACKOFF: Duration = Duration::from_millis(250);
+
+/// The longest backoff between two retries of a rate limited request
+const MAX_BACKOFF: Duration = Duration::from_secs(8);
+
+/// The http transports by endpoint, all clients of an endpoint share one connection pool
+static HTTP_TRANSPORTS: Lazy<Mutex<HashMap<String, Http>>> = Lazy::new(Default::default);
+
+/// Errors of the [`ForkClient`]
+#[derive(Debug, thiserror::Error)]
+pub enum ForkClientError {
+    #[error(transparent)]
+    Http(#[from] HttpClientError),
+    #[error(transparent)]
+    Ws(#[from] WsClientError),
+    #[error(transparent)]
+    Ipc(#[from] IpcError),
+}
+
+impl From<ForkClientError> for ProviderError {
+    fn from(err: ForkClientError) -> Self {
+        ProviderError::JsonRpcClientError(Box::new(err))
+    }
+}
+
+/// The connected transport
+#[derive(Debug, Clone)]
+enum Transport {
+    Http(Http),
+    Ws(Ws),
+    Ipc(Ipc),
+}
+
+impl Transport {
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, ForkClientError>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned,
+    {
+        Ok(match self {
+            Transport::Http(client) => client.request(method, params).await?,
+            Transport::Ws(client) => client.request(method, params).await?,
+            Transport::Ipc(client) => client.request(method, params).await?,
+        })
+    }
+}
+
+/// A JSON-RPC client that selects the transport based on the endpoint:
+///
+///   - `ws://` and `wss://` urls connect via websocket
+///   - `*.ipc` paths (or any other existing file) connect via IPC
+///   - everything else is treated as an http url
+///
+/// Websocket and IPC connections are established lazily on the first request, so that they're
+/// bound to the runtime that executes the requests. If a request fails in the transport, like a
+/// dropped connection or a timeout, the connection is re-established and the request is retried
+/// up to `MAX_RECONNECTS` times. JSON-RPC errors of the endpoint are returned right away.
+///
+/// All http clients of the same endpoint share their connection pool. Requests the endpoint rate
+/// limits (e.g. HTTP 429 of Infura or Alchemy) are retried with an exponential backoff.
+#[derive(Debug, Clone)]
+pub struct ForkClient {
+    endpoint: String,
+    transport: Arc<RwLock<Option<Transport>>>,
+}
+
+impl ForkClient {
+    /// Creates a new client for the `endpoint`
+    pub fn new(endpoint: impl Into<String>) -> eyre::Result<Self> {
+        let endpoint = endpoint.into();
+        let transport = if is_ws(&endpoint) || is_ipc(&endpoint) {
+            None
+        } else {
+            Some(Transport::Http(http_transport(&endpoint)?))
+        };
+        Ok(Self { endpoint, transport: Arc::new(RwLock::new(transport)) })
+    }
+
+    /// Creates a new [`Provider`] for the `endpoint`
+    pub fn provider(endpoint: impl Into<String>) -> eyre::Result<Provider<Self>> {
+        Ok(Provider::new(Self::new(endpoint)?))
+    }
+
+    /// Returns the endpoint this client connects to
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    /// Returns the current transport, connects if there is none
+    async fn transport(&self) -> Result<Transport, ForkClientError> {
+        // the lock must not be held across an await point
+        let current = self.transport.read().clone();
+        if let Some(transport) = current {
+            return Ok(transport)
+        }
+        let transport = if is_ws(&self.endpoint) {
+            Transport::Ws(Ws::connect(self.endpoint.as_str()).await?)
+        } else {
+            Transport::Ipc(Ipc::connect(self.endpoint.as_str()).await?)
+        };
+        *self.transport.write() = Some(transport.clone());
+        Ok(transport)
+    }
+}
+
+#[async_trait]
+impl JsonRpcClient for ForkClient {
+    type Error = ForkClientError;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, Self::Error>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned,
+    {
+        let mut reconnects = 0;
+        let mut retries = 0;
+        loop {
+            let transport = self.transport().await?;
+            RPC_METRICS.record_request();
+            match transport.request(method, &params).await {
+                Ok(res) => return Ok(res),
+                Err(err) if is_rate_limited(&err) && retries < MAX_RATE_LIMIT_RETRIES => {
+                    let backoff = backoff(retries);
+                    tracing::debug!(
+                        ?err,
+                        "{} rate limited {}, retrying in {:?}",
+                        self.endpoint,
+                        method,
+                        backoff
+                    );
+                    RPC_METRICS.record_retry();
+                    retries += 1;
+                    tokio::time::sleep(backoff).await;
+                }
+                Err(err) if !is_transport_error(&err) || reconnects >= MAX_RECONNECTS => {
+                    return Err(err)
+                }
+                Err(err) => {
+                    tracing::warn!(?err, "request to {} failed, retrying", self.endpoint);
+                    // http clients reconnect on their own
+                    if !matches!(transport, Transport::Http(_)) {
+                        *self.transport.write() = None;
+                    }
+                    reconnects += 1;
+                }
+            }
+        }
+    }
+}
+
+/// Returns the shared http transport of the endpoint
+fn http_transport(endpoint: &str) -> eyre::Result<Http> {
+    let mut transports = HTTP_TRANSPORTS.lock();
+    if let Some(transport) = transports.get(endpoint) {
+        return Ok(transport.clone())
+    }
+    let transport = Http::from_str(endpoint)?;
+    transports.insert(endpoint.to_string(), transport.clone());
+    Ok(transport)
+}
+
+/// Whether the endpoint rejected the request because of its rate limit
+///
+/// Providers report this differently: as HTTP 429, as JSON-RPC error `-32005` (Infura) or with
+/// error code `429` (Alchemy).
+fn is_rate_limited(err: &ForkClientError) -> bool {
+    let err = err.to_string().to_lowercase();
+    ["code: 429", "code: -32005", "rate limit", "too many requests", "exceeded its compute units"]
+        .iter()
+        .any(|needle| err.contains(needle))
+}
+
+/// Whether the request failed in the transport, rather than with an error response of the
+/// endpoint or a response that can't be decoded, which don't change when the request is retried
+fn is_transport_error(err: &ForkClientError) -> bool {
+    !matches!(
+        err,
+        ForkClientError::Http(HttpClientError::JsonRpcError(_)) |
+            ForkClientError::Http(HttpClientError::SerdeJson { .. }) |
+            ForkClientError::Ws(WsClientError::JsonRpcError(_) | WsClientError::JsonError(_)) |
+            ForkClientError::Ipc(IpcError::JsonRpcError(_) | IpcError::JsonError(_))
+    )
+}
+
+/// Returns the backoff before the retry, doubling with every retry up to `MAX_BACKOFF`
+fn backoff(retries: u32) -> Duration {
+    INITIAL_BACKOFF.saturating_mul(2u32.saturating_pow(retries)).min(MAX_BACKOFF)
+}
+
+fn is_ws(endpoint: &str) -> bool {
+    endpoint.starts_with("ws://") || endpoint.starts_with("wss://")
+}
+
+fn is_ipc(endpoint: &str) -> bool {
+    endpoint.ends_with(".ipc") || std::path::Path::new(endpoint).is_file()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn selects_transport() {
+        let http = ForkClient::new("http://localhost:8545").unwrap();
+        assert!(matches!(*http.transport.read(), Some(Transport::Http(_))));
+
+        let ws = ForkClient::new("ws://localhost:8546").unwrap();
+        assert!(ws.transport.read().is_none());
+
+        let ipc = ForkClient::new("/tmp/geth.ipc").unwrap();
+        assert!(ipc.transport.read().is_none());
+
+        assert!(ForkClient::new("not a url").is_err());
+    }
+
+    #[test]
+    fn shares_http_transports() {
+        let a = ForkClient::new("http://localhost:8547").unwrap();
+        let b = ForkClient::new("http://localhost:8547").unwrap();
+        assert_eq!(a.endpoint(), b.endpoint());
+        assert_eq!(
+            HTTP_TRANSPORTS.lock().keys().filter(|e| *e == "http://localhost:8547").count(),
+            1
+        );
+    }
+
+    #[test]
+    fn detects_rate_limits() {
+        let rate_limited = |code: i64, message: &str| {
+            let err = HttpClientError::JsonRpcError(ethers::providers::JsonRpcError {
+                code,
+                message: message.to_string(),
+                data: None,
+            });
+            is_rate_limited(&ForkClientError::Http(err))
+        };
+        assert!(rate_limited(-32005, "daily request count exceeded, request rate limited"));
+        assert!(rate_limited(429, "Your app has exceeded its compute units per second capacity"));
+        assert!(!rate_limited(-32000, "execution reverted"));
+    }
+
+    #[test]
+    fn does_not_retry_error_responses() {
+        let err = || ethers::providers::JsonRpcError {
+            code: -32000,
+            message: "execution reverted".to_string(),
+            data: None,
+        };
+        assert!(!is_transport_error(&HttpClientError::JsonRpcError(err()).into()));
+        assert!(!is_transport_error(&WsClientError::JsonRpcError(err()).into()));
+        assert!(!is_transport_error(&IpcError::JsonRpcError(err()).into()));
+    }
+
+    #[test]
+    fn backs_off_exponentially() {
+        assert_eq!(backoff(0), INITIAL_BACKOFF);
+        assert_eq!(backoff(1), INITIAL_BACKOFF * 2);
+        assert_eq!(backoff(3), INITIAL_BACKOFF * 8);
+        assert_eq!(backoff(MAX_RATE_LIMIT_RETRIES), MAX_BACKOFF);
+        assert_eq!(backoff(u32::MAX), MAX_BACKOFF);
+    }
+}
@@ -6,12 +6,17 @@ use ethers::{
 };
 use std::{
     cell::{RefCell, RefMut},
+    collections::BTreeSet,
+    fmt,
     marker::PhantomData,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
 };
 
 pub use proptest::test_runner::Config as FuzzConfig;
 use proptest::{
     prelude::*,
+    strategy::{NewTree, ValueTree},
     test_runner::{TestError, TestRunner},
 };
 use serde::{Deserialize, Serialize};
@@ -21,12 +26,21 @@ use serde::{Deserialize, Serialize};
 /// After instantiation, calling `fuzz` will proceed to hammer the deployed smart contract with
 /// inputs, until it finds a counterexample. The provided `TestRunner` contains all the
 /// configuration which can be overridden via [environment variables](https://docs.rs/proptest/1.0.0/proptest/test_runner/struct.Config.html)
+///
+/// The fuzzing is guided by coverage: the inputs that hit new program counters are kept in a
+/// corpus, and half of the inputs are mutations of the corpus entries.
 #[derive(Debug)]
 pub struct FuzzedExecutor<'a, E, S> {
     evm: RefCell<&'a mut E>,
     runner: TestRunner,
     state: PhantomData<S>,
     sender: Address,
+    /// The file the corpus is read from and written to, it's only kept in memory if not set
+    corpus: Option<PathBuf>,
+    /// The values the fuzzer picks from besides random ones
+    dictionary: FuzzDictionary,
+    /// The bounds of the generated values of dynamic types and of the run time
+    bounds: FuzzBounds,
 }
 
 impl<'a, S, E: Evm<S>> FuzzedExecutor<'a, E, S> {
@@ -41,7 +55,34 @@ impl<'a, S, E: Evm<S>> FuzzedExecutor<'a, E, S> {
 
     /// Instantiates a fuzzed executor EVM given a testrunner
     pub fn new(evm: &'a mut E, runner: TestRunner, sender: Address) -> Self {
-        Self { evm: RefCell::new(evm), runner, state: PhantomData, sender }
+        Self {
+            evm: RefCell::new(evm),
+            runner,
+            state: PhantomData,
+            sender,
+            corpus: None,
+            dictionary: Default::default(),
+            bounds: Default::default(),
+        }
+    }
+
+    /// Sets the file the corpus of the fuzzed function is kept in across runs
+    pub fn with_corpus(mut self, corpus: Option<PathBuf>) -> Self {
+        self.corpus = corpus;
+        self
+    }
+
+    /// Sets the values the fuzzer picks from besides random ones, like the constants of the
+    /// contracts under test
+    pub fn with_dictionary(mut self, dictionary: FuzzDictionary) -> Self {
+        self.dictionary = dictionary;
+        self
+    }
+
+    /// Sets the bounds of the generated values of dynamic types and of the run time
+    pub fn with_bounds(mut self, bounds: FuzzBounds) -> Self {
+        self.bounds = bounds;
+        self
     }
 
     /// Fuzzes the provided function, assuming it is available at the contract at `address`
@@ -56,17 +97,51 @@ impl<'a, S, E: Evm<S>> FuzzedExecutor<'a, E, S> {
         should_fail: bool,
         abi: Option<&Abi>,
     ) -> FuzzTestResult<E::ReturnReason>
+    where
+        S: Clone,
+    {
+        self.fuzz_with_progress(func, address, should_fail, abi, |_| {})
+    }
+
+    /// Fuzzes the provided function like [`Self::fuzz`], calling `progress` with the number of
+    /// cases run so far after every successful case
+    pub fn fuzz_with_progress(
+        &self,
+        func: &Function,
+        address: Address,
+        should_fail: bool,
+        abi: Option<&Abi>,
+        progress: impl Fn(u32),
+    ) -> FuzzTestResult<E::ReturnReason>
     where
         // We need to be able to clone the state so as to snapshot it and reset
         // it back after every test run, to have isolation of state across each
         // fuzz test run.
         S: Clone,
     {
-        let strat = fuzz_calldata(func);
-
-        // Snapshot the state before the test starts running
+        let corpus = RefCell::new(read_corpus(self.corpus.as_deref()));
+        let strat = CorpusStrategy {
+            func,
+            random: fuzz_calldata(func, &self.dictionary, &self.bounds),
+            corpus: &corpus,
+            dictionary: &self.dictionary,
+            bounds: &self.bounds,
+        };
+
+        // Snapshot the state before the test starts running, with coverage recording turned on
+        let coverage_enabled = self.evm.borrow_mut().set_coverage_enabled(true);
         let pre_test_state = self.evm.borrow().state().clone();
 
+        // the program counters hit by the corpus, starting with the entries of previous runs
+        let covered = RefCell::new(BTreeSet::new());
+        for calldata in corpus.borrow().iter() {
+            let mut evm = self.evm.borrow_mut();
+            evm.reset(pre_test_state.clone());
+            if evm.call_raw(self.sender, address, calldata.clone(), 0.into(), false).is_ok() {
+                covered.borrow_mut().extend(evm.coverage());
+            }
+        }
+
         // stores the consumed gas and calldata of every successful fuzz call
         let fuzz_cases: RefCell<Vec<FuzzCase>> = RefCell::new(Default::default());
 
@@ -77,8 +152,14 @@ impl<'a, S, E: Evm<S>> FuzzedExecutor<'a, E, S> {
 
         let mut runner = self.runner.clone();
         tracing::debug!(func = ?func.name, should_fail, "fuzzing");
+        let deadline = self.bounds.max_test_time.map(|budget| Instant::now() + budget);
         let test_error = runner
             .run(&strat, |calldata| {
+                // once the budget is spent the remaining runs pass without executing
+                if deadline.map_or(false, |deadline| Instant::now() >= deadline) {
+                    return Ok(())
+                }
+
                 let mut evm = self.evm.borrow_mut();
                 // Before each test, we must reset to the initial state
                 evm.reset(pre_test_state.clone());
@@ -112,18 +193,40 @@ impl<'a, S, E: Evm<S>> FuzzedExecutor<'a, E, S> {
                     }
                 );
 
+                // keep the inputs that hit new program counters
+                let coverage = evm.coverage();
+                if !coverage.is_subset(&covered.borrow()) {
+                    covered.borrow_mut().extend(coverage);
+                    corpus.borrow_mut().push(calldata.clone());
+                }
+
                 // push test case to the case set
-                fuzz_cases.borrow_mut().push(FuzzCase { calldata, gas });
+                let mut cases = fuzz_cases.borrow_mut();
+                cases.push(FuzzCase { calldata, gas });
+                progress(cases.len() as u32);
 
                 Ok(())
             })
             .err()
             .map(|test_error| FuzzError {
+                origins: match &test_error {
+                    TestError::Fail(_, calldata) => {
+                        value_origins(func, calldata, &self.dictionary, &corpus.borrow())
+                    }
+                    TestError::Abort(_) => Vec::new(),
+                },
                 test_error,
                 return_reason: return_reason.into_inner().expect("Reason must be set"),
                 revert_reason: revert_reason.into_inner().expect("Revert error string must be set"),
             });
 
+        self.evm.borrow_mut().set_coverage_enabled(coverage_enabled);
+        if let Some(path) = &self.corpus {
+            if let Err(err) = write_corpus(path, &corpus.borrow()) {
+                tracing::warn!(?err, ?path, "could not write the fuzz corpus");
+            }
+        }
+
         FuzzTestResult { cases: FuzzedCases::new(fuzz_cases.into_inner()), test_error }
     }
 }
@@ -156,6 +259,99 @@ pub struct FuzzError<Reason> {
     pub return_reason: Reason,
     /// The revert string of the offending call
     pub revert_reason: String,
+    /// The likely origins of the arguments of the offending call
+    pub origins: Vec<Option<ValueOrigin>>,
+}
+
+/// Where a fuzzed value likely came from
+///
+/// The origins of the generated values aren't tracked through shrinking, so they are derived from
+/// the values themselves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ValueOrigin {
+    /// A boundary of the type, like zero, one or the maximum value, or an empty `bytes`
+    Boundary,
+    /// A value of the [`FuzzDictionary`]
+    Dictionary,
+    /// The same argument of a corpus entry
+    Corpus,
+}
+
+impl fmt::Display for ValueOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValueOrigin::Boundary => f.write_str("boundary value"),
+            ValueOrigin::Dictionary => f.write_str("dictionary"),
+            ValueOrigin::Corpus => f.write_str("corpus"),
+        }
+    }
+}
+
+/// Returns the likely origin of every argument of the calldata of the function, no arguments if
+/// the calldata can't be decoded
+pub fn value_origins(
+    func: &Function,
+    calldata: &[u8],
+    dictionary: &FuzzDictionary,
+    corpus: &[Bytes],
+) -> Vec<Option<ValueOrigin>> {
+    let decode = |calldata: &[u8]| func.decode_input(calldata.get(4..)?).ok();
+    let args = match decode(calldata) {
+        Some(args) => args,
+        None => return Vec::new(),
+    };
+    let corpus = corpus.iter().filter_map(|entry| decode(entry)).collect::<Vec<_>>();
+    args.iter()
+        .zip(&func.inputs)
+        .enumerate()
+        .map(|(i, (arg, param))| {
+            if is_boundary(arg, &param.kind) {
+                Some(ValueOrigin::Boundary)
+            } else if dictionary.contains(arg, &param.kind) {
+                Some(ValueOrigin::Dictionary)
+            } else if corpus.iter().any(|entry| entry.get(i) == Some(arg)) {
+                Some(ValueOrigin::Corpus)
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Returns whether the value is a boundary of its type
+fn is_boundary(token: &Token, param: &ParamType) -> bool {
+    let max = |bits: usize| {
+        if bits >= 256 {
+            U256::MAX
+        } else {
+            (U256::one() << bits) - 1
+        }
+    };
+    match (token, param) {
+        (Token::Uint(value), ParamType::Uint(bits)) => {
+            value.is_zero() || *value == U256::one() || *value == max(*bits)
+        }
+        // ints are sign extended to 256 bits
+        (Token::Int(value), ParamType::Int(bits)) => {
+            let int_max = max(*bits - 1);
+            value.is_zero() ||
+                *value == U256::one() ||
+                *value == U256::MAX ||
+                *value == int_max ||
+                *value == !int_max
+        }
+        (Token::Address(address), _) => {
+            address.is_zero() || address.as_bytes().iter().all(|byte| *byte == 0xff)
+        }
+        (Token::FixedBytes(bytes), _) => {
+            bytes.iter().all(|byte| *byte == 0) || bytes.iter().all(|byte| *byte == 0xff)
+        }
+        (Token::Bytes(bytes), _) => bytes.is_empty(),
+        (Token::String(value), _) => value.is_empty(),
+        (Token::Array(values), _) => values.is_empty(),
+        _ => false,
+    }
 }
 
 /// Container type for all successful test cases
@@ -220,12 +416,126 @@ pub struct FuzzCase {
     pub gas: u64,
 }
 
+/// Values harvested from the contracts under test, like the constants of their bytecode and the
+/// storage `setUp` left behind, which the fuzzer picks from so that magic thresholds and specific
+/// addresses are exercised
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FuzzDictionary {
+    /// The values as 32 byte words
+    values: BTreeSet<[u8; 32]>,
+}
+
+impl FuzzDictionary {
+    /// Adds the immediates of the `PUSH` instructions of the bytecode
+    pub fn insert_code(&mut self, code: &[u8]) {
+        let mut pc = 0;
+        while pc < code.len() {
+            let op = code[pc];
+            if (0x60..=0x7f).contains(&op) {
+                let size = (op - 0x5f) as usize;
+                if let Some(immediate) = code.get(pc + 1..pc + 1 + size) {
+                    self.insert_value(U256::from_big_endian(immediate));
+                }
+                pc += size;
+            }
+            pc += 1;
+        }
+    }
+
+    pub fn insert_value(&mut self, value: U256) {
+        let mut word = [0u8; 32];
+        value.to_big_endian(&mut word);
+        self.values.insert(word);
+    }
+
+    pub fn insert_address(&mut self, address: Address) {
+        self.insert_value(U256::from_big_endian(address.as_bytes()));
+    }
+
+    /// Returns whether the value of the type is one the dictionary yields
+    pub fn contains(&self, token: &Token, param: &ParamType) -> bool {
+        match param {
+            ParamType::Address |
+            ParamType::Int(_) |
+            ParamType::Uint(_) |
+            ParamType::FixedBytes(_) => {
+                self.values.iter().any(|word| dictionary_token(param, *word) == *token)
+            }
+            _ => false,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+/// The bounds of a fuzz test: of the generated values of dynamic types and of its run time
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FuzzBounds {
+    /// The maximum length of dynamic arrays
+    pub max_array_length: usize,
+    /// The maximum length of `bytes`
+    pub max_bytes_length: usize,
+    /// The maximum length of strings, in characters
+    pub max_string_length: usize,
+    /// The regex character class the characters of strings are drawn from, like `[a-zA-Z0-9]`
+    pub string_charset: String,
+    /// The wall-clock budget of the test, it stops early once the budget is spent even if not all
+    /// runs were executed
+    pub max_test_time: Option<Duration>,
+}
+
+impl Default for FuzzBounds {
+    fn default() -> Self {
+        Self {
+            max_array_length: 256,
+            max_bytes_length: 256,
+            max_string_length: 256,
+            string_charset: ".".to_string(),
+            max_test_time: None,
+        }
+    }
+}
+
+impl FuzzBounds {
+    /// Returns an error if the string charset isn't a valid regex
+    pub fn validate(&self) -> eyre::Result<()> {
+        self.string_chars().map(drop).map_err(|err| {
+            eyre::eyre!("invalid fuzz string charset `{}`: {}", self.string_charset, err)
+        })
+    }
+
+    /// Returns the strategy of the characters of strings
+    fn string_chars(&self) -> Result<BoxedStrategy<String>, proptest::string::Error> {
+        proptest::string::string_regex(&self.string_charset).map(Strategy::boxed)
+    }
+
+    /// Returns the strategy of the lengths of dynamic values, which are short three quarters of
+    /// the time
+    fn lengths(max: usize) -> impl Strategy<Value = usize> {
+        prop_oneof![3 => 0..=max.min(4), 1 => 0..=max]
+    }
+}
+
 /// Given a function, it returns a proptest strategy which generates valid abi-encoded calldata
-/// for that function's input types.
-pub fn fuzz_calldata(func: &Function) -> impl Strategy<Value = Bytes> + '_ {
+/// for that function's input types, which are picked from the dictionary a quarter of the time.
+pub fn fuzz_calldata<'a>(
+    func: &'a Function,
+    dictionary: &FuzzDictionary,
+    bounds: &FuzzBounds,
+) -> impl Strategy<Value = Bytes> + 'a {
     // We need to compose all the strategies generated for each parameter in all
     // possible combinations
-    let strats = func.inputs.iter().map(|input| fuzz_param(&input.kind)).collect::<Vec<_>>();
+    let strats = func
+        .inputs
+        .iter()
+        .map(|input| fuzz_param(&input.kind, dictionary, bounds))
+        .collect::<Vec<_>>();
 
     strats.prop_map(move |tokens| {
         tracing::trace!(input = ?tokens);
@@ -233,19 +543,118 @@ pub fn fuzz_calldata(func: &Function) -> impl Strategy<Value = Bytes> + '_ {
     })
 }
 
-/// The max length of arrays we fuzz for is 256.
-const MAX_ARRAY_LEN: usize = 256;
+/// Generates random calldata for the function, or mutates an input of the corpus half of the time
+///
+/// The mutated inputs aren't shrunk.
+#[derive(Debug)]
+struct CorpusStrategy<'a, S> {
+    func: &'a Function,
+    random: S,
+    corpus: &'a RefCell<Vec<Bytes>>,
+    dictionary: &'a FuzzDictionary,
+    bounds: &'a FuzzBounds,
+}
+
+impl<'a, S> Strategy for CorpusStrategy<'a, S>
+where
+    S: Strategy<Value = Bytes>,
+    S::Tree: 'a,
+{
+    type Tree = Box<dyn ValueTree<Value = Bytes> + 'a>;
+    type Value = Bytes;
+
+    fn new_tree(&self, runner: &mut TestRunner) -> NewTree<Self> {
+        let corpus = self.corpus.borrow();
+        if !corpus.is_empty() && runner.rng().gen_bool(0.5) {
+            let input = &corpus[runner.rng().gen_range(0..corpus.len())];
+            if let Some(mutated) =
+                mutate_calldata(self.func, input, self.dictionary, self.bounds, runner)
+            {
+                return Ok(Box::new(Just(mutated)))
+            }
+        }
+        Ok(Box::new(self.random.new_tree(runner)?))
+    }
+}
+
+/// Mutates one argument of the calldata, returns `None` if the function has no arguments or the
+/// calldata doesn't decode
+fn mutate_calldata(
+    func: &Function,
+    calldata: &Bytes,
+    dictionary: &FuzzDictionary,
+    bounds: &FuzzBounds,
+    runner: &mut TestRunner,
+) -> Option<Bytes> {
+    let mut tokens = func.decode_input(calldata.as_ref().get(4..)?).ok()?;
+    if tokens.is_empty() {
+        return None
+    }
+    let idx = runner.rng().gen_range(0..tokens.len());
+    tokens[idx] = mutate_token(&func.inputs[idx].kind, &tokens[idx], dictionary, bounds, runner)?;
+    func.encode_input(&tokens).ok().map(Into::into)
+}
+
+/// Nudges integers by one or flips one of their bits and negates booleans, the values of the
+/// other types are replaced by new ones
+fn mutate_token(
+    param: &ParamType,
+    token: &Token,
+    dictionary: &FuzzDictionary,
+    bounds: &FuzzBounds,
+    runner: &mut TestRunner,
+) -> Option<Token> {
+    let mutated = match (param, token) {
+        (ParamType::Uint(n), Token::Uint(value)) => {
+            let value = match runner.rng().gen_range(0..3) {
+                0 => value.overflowing_add(U256::one()).0,
+                1 => value.overflowing_sub(U256::one()).0,
+                _ => *value ^ (U256::one() << runner.rng().gen_range(0..*n)),
+            };
+            // wrap the value around to the size of the type
+            Token::Uint(if *n < 256 { value % (U256::one() << *n) } else { value })
+        }
+        // flipping a bit below the sign bit keeps the value in the range of the type
+        (ParamType::Int(n), Token::Int(value)) => {
+            Token::Int(*value ^ (U256::one() << runner.rng().gen_range(0..n - 1)))
+        }
+        (ParamType::Bool, Token::Bool(value)) => Token::Bool(!value),
+        _ => fuzz_param(param, dictionary, bounds).new_tree(runner).ok()?.current(),
+    };
+    Some(mutated)
+}
+
+/// Reads the corpus from the file, it's empty if the file doesn't exist yet
+fn read_corpus(path: Option<&Path>) -> Vec<Bytes> {
+    path.and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|corpus| serde_json::from_str(&corpus).ok())
+        .unwrap_or_default()
+}
+
+fn write_corpus(path: &Path, corpus: &[Bytes]) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, serde_json::to_string_pretty(corpus)?)
+}
 
 /// Given an ethabi parameter type, returns a proptest strategy for generating values for that
 /// datatype. Works with ABI Encoder v2 tuples.
-fn fuzz_param(param: &ParamType) -> impl Strategy<Value = Token> {
-    match param {
+fn fuzz_param(
+    param: &ParamType,
+    dictionary: &FuzzDictionary,
+    bounds: &FuzzBounds,
+) -> BoxedStrategy<Token> {
+    let random = match param {
         ParamType::Address => {
             // The key to making this work is the `boxed()` call which type erases everything
             // https://altsysrq.github.io/proptest-book/proptest/tutorial/transforming-strategies.html
             any::<[u8; 20]>().prop_map(|x| Address::from_slice(&x).into_token()).boxed()
         }
-        ParamType::Bytes => any::<Vec<u8>>().prop_map(|x| Bytes::from(x).into_token()).boxed(),
+        ParamType::Bytes => FuzzBounds::lengths(bounds.max_bytes_length)
+            .prop_flat_map(|len| proptest::collection::vec(any::<u8>(), len))
+            .prop_map(|x| Bytes::from(x).into_token())
+            .boxed(),
         // For ints and uints we sample from a U256, then wrap it to the correct size with a
         // modulo operation. Note that this introduces modulo bias, but it can be removed with
         // rejection sampling if it's determined the bias is too severe. Rejection sampling may
@@ -277,25 +686,73 @@ fn fuzz_param(param: &ParamType) -> impl Strategy<Value = Token> {
             _ => panic!("unsupported solidity type uint{}", n),
         },
         ParamType::Bool => any::<bool>().prop_map(|x| x.into_token()).boxed(),
-        ParamType::String => any::<Vec<u8>>()
-            .prop_map(|x| Token::String(unsafe { std::str::from_utf8_unchecked(&x).to_string() }))
-            .boxed(),
-        ParamType::Array(param) => proptest::collection::vec(fuzz_param(param), 0..MAX_ARRAY_LEN)
-            .prop_map(Token::Array)
-            .boxed(),
+        ParamType::String => {
+            let chars = bounds.string_chars().expect("the string charset is validated");
+            FuzzBounds::lengths(bounds.max_string_length)
+                .prop_flat_map(move |len| proptest::collection::vec(chars.clone(), len))
+                .prop_map(|chars| Token::String(chars.concat()))
+                .boxed()
+        }
+        ParamType::Array(param) => {
+            let element = fuzz_param(param, dictionary, bounds);
+            FuzzBounds::lengths(bounds.max_array_length)
+                .prop_flat_map(move |len| proptest::collection::vec(element.clone(), len))
+                .prop_map(Token::Array)
+                .boxed()
+        }
         ParamType::FixedBytes(size) => (0..*size as u64)
             .map(|_| any::<u8>())
             .collect::<Vec<_>>()
             .prop_map(Token::FixedBytes)
             .boxed(),
         ParamType::FixedArray(param, size) => (0..*size as u64)
-            .map(|_| fuzz_param(param).prop_map(|param| param.into_token()))
+            .map(|_| fuzz_param(param, dictionary, bounds).prop_map(|param| param.into_token()))
             .collect::<Vec<_>>()
             .prop_map(Token::FixedArray)
             .boxed(),
-        ParamType::Tuple(params) => {
-            params.iter().map(fuzz_param).collect::<Vec<_>>().prop_map(Token::Tuple).boxed()
+        ParamType::Tuple(params) => params
+            .iter()
+            .map(|param| fuzz_param(param, dictionary, bounds))
+            .collect::<Vec<_>>()
+            .prop_map(Token::Tuple)
+            .boxed(),
+    };
+
+    let is_value = matches!(
+        param,
+        ParamType::Address | ParamType::Int(_) | ParamType::Uint(_) | ParamType::FixedBytes(_)
+    );
+    if dictionary.is_empty() || !is_value {
+        return random
+    }
+    let values = dictionary.values.iter().copied().collect::<Vec<_>>();
+    let param = param.clone();
+    prop_oneof![
+        3 => random,
+        1 => proptest::sample::select(values).prop_map(move |word| dictionary_token(&param, word)),
+    ]
+    .boxed()
+}
+
+/// Converts a word of the dictionary to a value of the type, keeping its low bytes
+fn dictionary_token(param: &ParamType, word: [u8; 32]) -> Token {
+    let value = U256::from(&word);
+    match param {
+        ParamType::Address => Address::from_slice(&word[12..]).into_token(),
+        ParamType::Uint(n) if *n < 256 => Token::Uint(value % (U256::one() << *n)),
+        // sign extend the low bits so that the value is in the range of the type
+        ParamType::Int(n) if *n < 256 => {
+            let low = value % (U256::one() << *n);
+            if low.bit(n - 1) {
+                Token::Int(low | !((U256::one() << *n) - 1))
+            } else {
+                Token::Int(low)
+            }
         }
+        ParamType::Uint(_) => Token::Uint(value),
+        ParamType::Int(_) => Token::Int(value),
+        ParamType::FixedBytes(size) => Token::FixedBytes(word[32 - size..].to_vec()),
+        _ => unreachable!("the dictionary only holds values"),
     }
 }
 
@@ -326,4 +783,139 @@ mod tests {
         let revert_reason = error.revert_reason;
         assert_eq!(revert_reason, "fuzztest-revert");
     }
+
+    #[test]
+    fn stops_once_the_budget_is_spent() {
+        let mut evm = vm();
+
+        let compiled = COMPILED.find("FuzzTests").expect("could not find contract");
+        let (addr, _, _, _) =
+            evm.deploy(Address::zero(), compiled.bytecode().unwrap().clone(), 0.into()).unwrap();
+
+        let bounds = FuzzBounds { max_test_time: Some(Duration::ZERO), ..Default::default() };
+        let evm = fuzzvm(&mut evm).with_bounds(bounds);
+
+        let func = compiled.abi.unwrap().function("testFuzzedRevert").unwrap();
+        let res = evm.fuzz(func, addr, false, compiled.abi);
+        assert!(res.is_ok());
+        assert!(res.cases.cases().is_empty());
+    }
+
+    #[test]
+    fn mutates_one_argument() {
+        let abi = ethers::abi::parse_abi(&["function f(uint8 a, bool b)"]).unwrap();
+        let func = abi.function("f").unwrap();
+        let calldata: Bytes =
+            func.encode_input(&[Token::Uint(255.into()), Token::Bool(true)]).unwrap().into();
+
+        let mut runner = TestRunner::deterministic();
+        for _ in 0..100 {
+            let mutated = mutate_calldata(
+                func,
+                &calldata,
+                &Default::default(),
+                &Default::default(),
+                &mut runner,
+            )
+            .unwrap();
+            let tokens = func.decode_input(&mutated.as_ref()[4..]).unwrap();
+            let changed = match (&tokens[0], &tokens[1]) {
+                (Token::Uint(a), Token::Bool(true)) => {
+                    *a != U256::from(255) && *a < U256::from(256)
+                }
+                (Token::Uint(a), Token::Bool(false)) => *a == U256::from(255),
+                _ => false,
+            };
+            assert!(changed, "{:?}", tokens);
+        }
+    }
+
+    #[test]
+    fn picks_dictionary_values() {
+        let mut dictionary = FuzzDictionary::default();
+        // PUSH2 0x1337 PUSH1 0x80 MSTORE
+        dictionary.insert_code(&[0x61, 0x13, 0x37, 0x60, 0x80, 0x52]);
+        assert_eq!(dictionary.len(), 2);
+
+        let mut runner = TestRunner::deterministic();
+        let strategy = fuzz_param(&ParamType::Uint(256), &dictionary, &Default::default());
+        let picked = (0..1000)
+            .map(|_| strategy.new_tree(&mut runner).unwrap().current())
+            .filter(|token| *token == Token::Uint(0x1337.into()))
+            .count();
+        assert!(picked > 0);
+
+        assert_eq!(dictionary_token(&ParamType::Int(8), [0xff; 32]), Token::Int(U256::MAX));
+        assert_eq!(dictionary_token(&ParamType::Uint(8), [0xff; 32]), Token::Uint(255.into()));
+    }
+
+    #[test]
+    fn derives_value_origins() {
+        let abi = ethers::abi::parse_abi(&["function f(uint8 a, int256 b, uint256 c, uint256 d)"])
+            .unwrap();
+        let func = abi.function("f").unwrap();
+        let encode = |d: u64| {
+            let args = [
+                Token::Uint(255.into()),
+                Token::Int(U256::MAX),
+                Token::Uint(0x1337.into()),
+                Token::Uint(d.into()),
+            ];
+            Bytes::from(func.encode_input(&args).unwrap())
+        };
+        let mut dictionary = FuzzDictionary::default();
+        dictionary.insert_value(0x1337.into());
+
+        let origins = value_origins(func, &encode(42), &dictionary, &[encode(42)]);
+        assert_eq!(
+            origins,
+            vec![
+                Some(ValueOrigin::Boundary),
+                Some(ValueOrigin::Boundary),
+                Some(ValueOrigin::Dictionary),
+                Some(ValueOrigin::Corpus)
+            ]
+        );
+        let origins = value_origins(func, &encode(42), &dictionary, &[]);
+        assert_eq!(origins[3], None);
+    }
+
+    #[test]
+    fn bounds_dynamic_values() {
+        let bounds = FuzzBounds {
+            max_array_length: 3,
+            max_bytes_length: 2,
+            max_string_length: 5,
+            string_charset: "[ab]".to_string(),
+            ..Default::default()
+        };
+        bounds.validate().unwrap();
+        assert!(FuzzBounds { string_charset: "[a".to_string(), ..Default::default() }
+            .validate()
+            .is_err());
+
+        let param = ParamType::Tuple(vec![
+            ParamType::Array(Box::new(ParamType::Tuple(vec![ParamType::Bytes]))),
+            ParamType::String,
+        ]);
+        let strategy = fuzz_param(&param, &Default::default(), &bounds);
+        let mut runner = TestRunner::deterministic();
+        for _ in 0..100 {
+            let token = strategy.new_tree(&mut runner).unwrap().current();
+            let (array, string) = match token {
+                Token::Tuple(tokens) => match &tokens[..] {
+                    [Token::Array(array), Token::String(string)] => (array.clone(), string.clone()),
+                    _ => panic!("{:?}", tokens),
+                },
+                token => panic!("{:?}", token),
+            };
+            assert!(array.len() <= 3);
+            for element in array {
+                assert!(
+                    matches!(element, Token::Tuple(bytes) if bytes[0].clone().into_bytes().unwrap().len() <= 2)
+                );
+            }
+            assert!(string.len() <= 5 && string.chars().all(|c| c == 'a' || c == 'b'));
+        }
+    }
 }
@@ -14,6 +14,12 @@ use crate::call_tracing::CallTraceArena;
 
 pub use blocking_provider::BlockingProvider;
 
+pub mod fork_client;
+pub use fork_client::ForkClient;
+
+pub mod rpc_metrics;
+pub use rpc_metrics::RPC_METRICS;
+
 pub mod fuzz;
 
 pub mod call_tracing;
@@ -21,6 +27,8 @@ pub mod call_tracing;
 /// Helpers for easily constructing EVM objects.
 pub mod evm_opts;
 
+pub mod chain_profile;
+
 use ethers::{
     abi::{Abi, Detokenize, Tokenize},
     contract::{decode_function_data, encode_function_data},
@@ -31,6 +39,7 @@ use foundry_utils::IntoFunction;
 
 use eyre::Result;
 use once_cell::sync::Lazy;
+use std::collections::BTreeSet;
 
 /// The account that we use to fund all the deployed contracts
 pub static FAUCET_ACCOUNT: Lazy<Address> =
@@ -82,6 +91,20 @@ pub trait Evm<State> {
     /// Resets the EVM's state to the provided value
     fn reset(&mut self, state: State);
 
+    /// Takes a snapshot of the EVM's state which can be restored via
+    /// [`revert_to`](Self::revert_to)
+    fn snapshot(&self) -> State
+    where
+        State: Clone,
+    {
+        self.state().clone()
+    }
+
+    /// Restores the EVM's state to a [`snapshot`](Self::snapshot)
+    fn revert_to(&mut self, snapshot: State) {
+        self.reset(snapshot)
+    }
+
     /// Turns on/off tracing, returning the previously set value
     fn set_tracing_enabled(&mut self, enabled: bool) -> bool;
 
@@ -92,6 +115,23 @@ pub trait Evm<State> {
     #[cfg(feature = "sputnik")]
     fn debug_calls(&self) -> Vec<DebugArena>;
 
+    /// Returns whether debug steps are recorded
+    fn debug_enabled(&self) -> bool {
+        false
+    }
+
+    /// Turns on/off recording the program counters hit, returning the previously set value
+    ///
+    /// Turning it on clears the program counters recorded so far.
+    fn set_coverage_enabled(&mut self, _enabled: bool) -> bool {
+        false
+    }
+
+    /// Returns the program counters hit since coverage was enabled, by the address of the code
+    fn coverage(&self) -> BTreeSet<(Address, usize)> {
+        Default::default()
+    }
+
     /// Gets all logs from the execution, regardless of reverts
     fn all_logs(&self) -> Vec<String>;
 
@@ -277,6 +317,35 @@ mod test_helpers {
         });
     }
 
+    pub fn can_snapshot_and_revert<S: Clone, E: Evm<S>>(mut evm: E, compiled: CompactContractRef) {
+        let (addr, _, _, _) =
+            evm.deploy(Address::zero(), compiled.bytecode().unwrap().clone(), 0.into()).unwrap();
+
+        let snapshot = evm.snapshot();
+        evm.call::<(), _, _>(
+            Address::zero(),
+            addr,
+            "greet(string)",
+            "hi".to_owned(),
+            0.into(),
+            compiled.abi,
+        )
+        .unwrap();
+
+        evm.revert_to(snapshot);
+        let (retdata, _, _, _) = evm
+            .call::<String, _, _>(
+                Address::zero(),
+                addr,
+                "greeting()(string)",
+                (),
+                0.into(),
+                compiled.abi,
+            )
+            .unwrap();
+        assert_eq!(retdata, "");
+    }
+
     pub fn solidity_unit_test<S, E: Evm<S>>(mut evm: E, compiled: CompactContractRef) {
         let (addr, _, _, _) =
             evm.deploy(Address::zero(), compiled.bytecode().unwrap().clone(), 0.into()).unwrap();
@@ -0,0 +1,100 @@
+//! Counters of the requests forked backends send to the RPC endpoints
+use std::{
+    fmt,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// The counters of all forked backends of the process
+pub static RPC_METRICS: RpcMetrics = RpcMetrics::new();
+
+/// Counters that are shared by all forked backends and fork clients
+#[derive(Debug, Default)]
+pub struct RpcMetrics {
+    /// Requests sent to an endpoint, including retries
+    requests: AtomicUsize,
+    /// Lookups that were answered by the in memory cache
+    cache_hits: AtomicUsize,
+    /// Lookups that joined an identical request in progress
+    coalesced: AtomicUsize,
+    /// Requests that were retried after the endpoint rate limited them
+    retries: AtomicUsize,
+}
+
+impl RpcMetrics {
+    pub const fn new() -> Self {
+        Self {
+            requests: AtomicUsize::new(0),
+            cache_hits: AtomicUsize::new(0),
+            coalesced: AtomicUsize::new(0),
+            retries: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn record_request(&self) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_cache_hit(&self) {
+        self.cache_hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_coalesced(&self) {
+        self.coalesced.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_retry(&self) {
+        self.retries.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Returns the current values of the counters
+    pub fn snapshot(&self) -> RpcMetricsSnapshot {
+        RpcMetricsSnapshot {
+            requests: self.requests.load(Ordering::Relaxed),
+            cache_hits: self.cache_hits.load(Ordering::Relaxed),
+            coalesced: self.coalesced.load(Ordering::Relaxed),
+            retries: self.retries.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// The values of the [`RpcMetrics`] at some point
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RpcMetricsSnapshot {
+    pub requests: usize,
+    pub cache_hits: usize,
+    pub coalesced: usize,
+    pub retries: usize,
+}
+
+impl RpcMetricsSnapshot {
+    /// Whether any lookup went through a forked backend
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl std::ops::Sub for RpcMetricsSnapshot {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self {
+            requests: self.requests.saturating_sub(rhs.requests),
+            cache_hits: self.cache_hits.saturating_sub(rhs.cache_hits),
+            coalesced: self.coalesced.saturating_sub(rhs.coalesced),
+            retries: self.retries.saturating_sub(rhs.retries),
+        }
+    }
+}
+
+impl fmt::Display for RpcMetricsSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} RPC requests, {} cache hits", self.requests, self.cache_hits)?;
+        if self.coalesced > 0 {
+            write!(f, ", {} coalesced", self.coalesced)?;
+        }
+        if self.retries > 0 {
+            write!(f, ", {} retried after rate limiting", self.retries)?;
+        }
+        Ok(())
+    }
+}
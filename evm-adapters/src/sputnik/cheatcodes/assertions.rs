@@ -0,0 +1,137 @@
+//! Comparison of the operands of the `assertEq` cheatcodes, a failing assertion reverts with a
+//! diff of the expected and actual value
+
+use super::HEVMCalls;
+use ethers::{
+    abi::Token,
+    types::{I256, U256},
+    utils::to_checksum,
+};
+
+/// Returns the actual and expected operand and the kind of the values of an `assertEq` call,
+/// `None` if the call is another cheatcode
+///
+/// Like `ds-test`'s `assertEq(a, b)`, `a` is the actual and `b` the expected value.
+pub fn assertion_operands(call: &HEVMCalls) -> Option<(Token, Token, &'static str)> {
+    let uints = |values: &[U256]| Token::Array(values.iter().copied().map(Token::Uint).collect());
+    let ints = |values: &[I256]| {
+        Token::Array(values.iter().map(|value| Token::Int(value.into_raw())).collect())
+    };
+    let operands = match call {
+        HEVMCalls::AssertEq0(inner) => (Token::Uint(inner.0), Token::Uint(inner.1), "uint"),
+        HEVMCalls::AssertEq1(inner) => {
+            (Token::Int(inner.0.into_raw()), Token::Int(inner.1.into_raw()), "int")
+        }
+        HEVMCalls::AssertEq2(inner) => {
+            (Token::Address(inner.0), Token::Address(inner.1), "address")
+        }
+        HEVMCalls::AssertEq3(inner) => {
+            (Token::FixedBytes(inner.0.to_vec()), Token::FixedBytes(inner.1.to_vec()), "bytes32")
+        }
+        HEVMCalls::AssertEq4(inner) => {
+            (Token::Bytes(inner.0.to_vec()), Token::Bytes(inner.1.to_vec()), "bytes")
+        }
+        HEVMCalls::AssertEq5(inner) => (uints(&inner.0), uints(&inner.1), "uint[]"),
+        HEVMCalls::AssertEq6(inner) => (ints(&inner.0), ints(&inner.1), "int[]"),
+        HEVMCalls::AssertEq7(inner) => (
+            Token::Array(inner.0.iter().copied().map(Token::Address).collect()),
+            Token::Array(inner.1.iter().copied().map(Token::Address).collect()),
+            "address[]",
+        ),
+        _ => return None,
+    };
+    Some(operands)
+}
+
+/// Compares the operands of an assertion, returning the diff of the values if they differ
+///
+/// Arrays are compared element-wise, only the elements that differ are part of the diff.
+pub fn assert_eq(actual: &Token, expected: &Token, kind: &str) -> Result<(), String> {
+    if actual == expected {
+        return Ok(())
+    }
+
+    let mut diff = vec![format!("assertion failed: a == b not satisfied [{}]", kind)];
+    match (actual, expected) {
+        (Token::Array(actual), Token::Array(expected)) => {
+            if actual.len() != expected.len() {
+                diff.push(format!(
+                    "  Length: expected {}, actual {}",
+                    expected.len(),
+                    actual.len()
+                ));
+            }
+            for idx in 0..actual.len().max(expected.len()) {
+                let (actual, expected) = (actual.get(idx), expected.get(idx));
+                if actual == expected {
+                    continue
+                }
+                let prefix = format!("[{}] ", idx);
+                diff.push(format!(
+                    "  {}Expected: {}",
+                    prefix,
+                    expected.map(format_value).unwrap_or_else(|| "-".to_string())
+                ));
+                diff.push(format!(
+                    "  {}  Actual: {}",
+                    " ".repeat(prefix.len()),
+                    actual.map(format_value).unwrap_or_else(|| "-".to_string())
+                ));
+            }
+        }
+        _ => {
+            diff.push(format!("  Expected: {}", format_value(expected)));
+            diff.push(format!("    Actual: {}", format_value(actual)));
+        }
+    }
+    Err(diff.join("\n"))
+}
+
+/// Formats numbers in their decimal and hex form, addresses checksummed and bytes as hex
+fn format_value(value: &Token) -> String {
+    match value {
+        Token::Uint(value) => format!("{} ({:#x})", value, value),
+        Token::Int(value) => format!("{} ({:#x})", I256::from_raw(*value), value),
+        Token::Address(address) => to_checksum(address, None),
+        Token::FixedBytes(bytes) | Token::Bytes(bytes) => format!("0x{}", hex::encode(bytes)),
+        value => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_failed_assertions() {
+        assert!(assert_eq(&Token::Uint(1.into()), &Token::Uint(1.into()), "uint").is_ok());
+
+        let err = assert_eq(&Token::Uint(11.into()), &Token::Uint(10.into()), "uint").unwrap_err();
+        assert_eq!(
+            err,
+            "assertion failed: a == b not satisfied [uint]\n  Expected: 10 (0xa)\n    Actual: 11 (0xb)"
+        );
+
+        let err = assert_eq(
+            &Token::Int(I256::from(-1).into_raw()),
+            &Token::Int(I256::from(1).into_raw()),
+            "int",
+        )
+        .unwrap_err();
+        assert!(err.contains("Actual: -1 (0xffff"));
+
+        let uints = |values: &[u64]| {
+            Token::Array(values.iter().map(|value| Token::Uint((*value).into())).collect())
+        };
+        let err = assert_eq(&uints(&[1, 5, 3]), &uints(&[1, 2, 3, 4]), "uint[]").unwrap_err();
+        assert_eq!(
+            err,
+            "assertion failed: a == b not satisfied [uint[]]
+  Length: expected 4, actual 3
+  [1] Expected: 2 (0x2)
+        Actual: 5 (0x5)
+  [3] Expected: 4 (0x4)
+        Actual: -"
+        );
+    }
+}
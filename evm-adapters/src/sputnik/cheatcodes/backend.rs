@@ -57,7 +57,7 @@ impl<B: Backend> Backend for CheatcodeBackend<B> {
     }
 
     fn chain_id(&self) -> U256 {
-        self.backend.chain_id()
+        self.cheats.chain_id.unwrap_or_else(|| self.backend.chain_id())
     }
 
     fn exists(&self, address: H160) -> bool {
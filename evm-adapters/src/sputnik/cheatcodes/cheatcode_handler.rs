@@ -1,46 +1,53 @@
 //! Hooks to EVM execution
 use super::{
-    backend::CheatcodeBackend, memory_stackstate_owned::MemoryStackStateOwned, ConsoleCalls,
-    HEVMCalls, HevmConsoleEvents,
+    assertions,
+    backend::CheatcodeBackend,
+    memory_stackstate_owned::{MemoryStackStateOwned, RecordAccess},
+    Cheatcodes, ConsoleCalls, HEVMCalls, HevmConsoleEvents,
 };
 use crate::{
     call_tracing::{CallTrace, CallTraceArena, LogCallOrder},
-    sputnik::{cheatcodes::memory_stackstate_owned::ExpectedEmit, Executor, SputnikExecutor},
+    sputnik::{
+        cheatcodes::memory_stackstate_owned::ExpectedEmit,
+        journal::{Journal, JournalDiff, StateSnapshot},
+        Executor, SputnikExecutor, PRECOMPILES_MAP,
+    },
     Evm,
 };
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
 
-use serde::Deserialize;
-use std::{fs::File, io::Read, path::Path};
+use std::path::{Path, PathBuf};
 
 use sputnik::{
     backend::Backend,
     executor::stack::{
-        Log, PrecompileFailure, PrecompileOutput, PrecompileSet, StackExecutor, StackExitKind,
-        StackState, StackSubstateMetadata,
+        Log, MemoryStackSubstate, PrecompileFailure, PrecompileOutput, PrecompileSet,
+        StackExecutor, StackExitKind, StackState, StackSubstateMetadata,
     },
     gasometer, Capture, Config, Context, CreateScheme, ExitError, ExitReason, ExitRevert,
-    ExitSucceed, Handler, Memory, Opcode, Runtime, Transfer,
+    ExitSucceed, Handler, Opcode, Runtime, Transfer,
 };
-use std::{process::Command, rc::Rc};
+use std::{cell::RefCell, process::Command, rc::Rc};
 
 use ethers::{
     abi::{RawLog, Token},
     contract::EthLogDecode,
     core::{abi::AbiDecode, k256::ecdsa::SigningKey, utils},
-    prelude::artifacts::deserialize_bytes,
     signers::{LocalWallet, Signer},
     solc::ProjectPathsConfig,
-    types::{Address, H160, H256, U256},
+    types::{
+        transaction::eip2930::{AccessList, AccessListItem},
+        Address, H160, H256, U256,
+    },
 };
-use ethers_core::types::Bytes;
 use std::{convert::Infallible, str::FromStr};
 
 use crate::sputnik::cheatcodes::{
-    debugger::{CheatOp, DebugArena, DebugNode, DebugStep, OpCode},
-    memory_stackstate_owned::Prank,
-    patch_hardhat_console_log_selector,
+    debugger::{CheatOp, DebugArena, DebugMemory, DebugNode, DebugStep, OpCode},
+    memory_stackstate_owned::{Broadcast, Prank},
+    patch_hardhat_console_log_selector, BroadcastableTransaction,
 };
+use foundry_utils::{decode_artifact_code, eip712::TypedData, DEFAULT_CREATE2_DEPLOYER};
 use once_cell::sync::Lazy;
 
 use ethers::abi::Tokenize;
@@ -107,6 +114,14 @@ pub struct CheatcodeHandler<H> {
     enable_ffi: bool,
     enable_trace: bool,
     console_logs: Vec<String>,
+    /// named rpc urls returned by the `rpcUrl` cheatcode
+    rpc_endpoints: BTreeMap<String, String>,
+    /// creation code of the contracts registered by name, returned by `getCode` and deployed by
+    /// `deployCode`
+    bytecode_contracts: BTreeMap<String, Vec<u8>>,
+    /// addresses of the contracts in the deployments registry by chain id and name, returned by
+    /// `getDeployment`
+    deployments: BTreeMap<u64, BTreeMap<String, Address>>,
 }
 
 pub(crate) fn convert_log(log: Log) -> Option<String> {
@@ -183,6 +198,21 @@ impl<'a, 'b, B: Backend, P: PrecompileSet> SputnikExecutor<CheatcodeStackState<'
         self.state().debug_steps.clone()
     }
 
+    fn debug_enabled(&self) -> bool {
+        self.state().debug_enabled
+    }
+
+    fn set_coverage_enabled(&mut self, enabled: bool) -> bool {
+        let coverage = &mut self.state_mut().coverage;
+        let curr = coverage.is_some();
+        *coverage = enabled.then(Default::default);
+        curr
+    }
+
+    fn coverage(&self) -> BTreeSet<(Address, usize)> {
+        self.state().coverage.clone().unwrap_or_default()
+    }
+
     fn gas_left(&self) -> U256 {
         // NB: We do this to avoid `function cannot return without recursing`
         U256::from(self.state().metadata().gasometer().gas())
@@ -367,6 +397,9 @@ impl<'a, 'b, B: Backend, P: PrecompileSet>
             enable_ffi,
             enable_trace,
             console_logs: Vec::new(),
+            rpc_endpoints: Default::default(),
+            bytecode_contracts: Default::default(),
+            deployments: Default::default(),
         };
 
         let mut evm = Executor::from_executor(executor, gas_limit);
@@ -380,6 +413,219 @@ impl<'a, 'b, B: Backend, P: PrecompileSet>
 
         evm
     }
+
+    /// Sets the named rpc urls that are returned by the `rpcUrl` cheatcode
+    pub fn set_rpc_endpoints(&mut self, rpc_endpoints: BTreeMap<String, String>) {
+        self.executor.rpc_endpoints = rpc_endpoints;
+    }
+
+    /// Sets the creation code of the contracts registered by name, which `getCode` returns and
+    /// `deployCode` deploys
+    pub fn set_bytecode_contracts(&mut self, contracts: BTreeMap<String, Vec<u8>>) {
+        self.executor.bytecode_contracts = contracts;
+    }
+
+    /// Sets the addresses of the deployed contracts by chain id and name, which `getDeployment`
+    /// returns for the current chain
+    pub fn set_deployments(&mut self, deployments: BTreeMap<u64, BTreeMap<String, Address>>) {
+        self.executor.deployments = deployments;
+    }
+
+    /// Sets the maximum number of debug steps that are recorded per call, older steps are
+    /// dropped once the limit is reached
+    pub fn set_debug_step_limit(&mut self, limit: Option<usize>) {
+        self.executor.state_mut().debug_step_limit = limit;
+    }
+
+    /// Starts recording all accessed accounts and storage slots for an access list, previously
+    /// recorded accesses are discarded
+    pub fn record_access_list(&mut self) {
+        self.executor.state_mut().access_list = Some(Default::default());
+    }
+
+    /// Returns the EIP-2930 access list of all accounts and storage slots that were accessed
+    /// since [`record_access_list`](Self::record_access_list) was called.
+    ///
+    /// Accounts that are warm anyway are excluded: the sender `from`, the precompiles and the
+    /// cheatcode and console addresses. The receiver `to` is only included for its storage slots.
+    pub fn access_list(&self, from: Address, to: Address) -> AccessList {
+        let accessed = match self.executor.state().access_list {
+            Some(ref access_list) => access_list.accessed.borrow().clone(),
+            None => return AccessList(Vec::new()),
+        };
+        AccessList(
+            accessed
+                .into_iter()
+                .filter(|(address, slots)| {
+                    *address != from &&
+                        *address != *CHEATCODE_ADDRESS &&
+                        *address != *CONSOLE_ADDRESS &&
+                        !PRECOMPILES_MAP.contains_key(address) &&
+                        (*address != to || !slots.is_empty())
+                })
+                .map(|(address, slots)| AccessListItem {
+                    address,
+                    storage_keys: slots.into_iter().collect(),
+                })
+                .collect(),
+        )
+    }
+
+    /// Returns all calls and creations that were made while broadcasting, see the `broadcast`
+    /// cheatcodes
+    pub fn broadcastable_transactions(&self) -> &[BroadcastableTransaction] {
+        &self.executor.state().broadcastable_transactions
+    }
+
+    /// Commits all state changes into the journal and returns the current state of every account
+    /// and storage slot that was changed since the executor was created, e.g. by `setUp`
+    ///
+    /// Must only be called between transactions.
+    pub fn touched_state(&mut self) -> StateSnapshot {
+        let state = self.executor.state_mut();
+        state.commit();
+        StateSnapshot::read(&state.journal.touched(), &*state)
+    }
+
+    /// Returns the current state of the accounts and storage slots of the `snapshot`
+    pub fn reread_state(&self, snapshot: &StateSnapshot) -> StateSnapshot {
+        snapshot.reread(self.executor.state())
+    }
+
+    /// Commits all state changes into the journal and returns its pinned depth, the changes made
+    /// afterwards can be taken with [`journal_diff`](Self::journal_diff)
+    ///
+    /// Must only be called between transactions.
+    pub fn journal_depth(&mut self) -> usize {
+        let state = self.executor.state_mut();
+        state.commit();
+        state.journal.pin()
+    }
+
+    /// Commits all state changes into the journal and returns the changes made since it had the
+    /// `depth`
+    ///
+    /// Must only be called between transactions.
+    pub fn journal_diff(&mut self, depth: usize) -> JournalDiff {
+        let state = self.executor.state_mut();
+        state.commit();
+        state.journal.diff_since(depth)
+    }
+
+    /// Applies the state changes of the `diff` on top of the current state
+    ///
+    /// Must only be called between transactions.
+    pub fn apply_journal_diff(&mut self, diff: &JournalDiff) {
+        let state = self.executor.state_mut();
+        state.commit();
+        state.journal.apply(diff);
+    }
+
+    /// Commits all state changes into the journal and returns a [`Checkpoint`] of the state,
+    /// which can be restored via [`restore`](Self::restore).
+    ///
+    /// Must only be called between transactions.
+    pub fn checkpoint(&mut self) -> Checkpoint {
+        let state = self.executor.state_mut();
+        state.commit();
+        Checkpoint {
+            journal: state.journal.clone(),
+            cheats: state.backend.cheats.clone(),
+            call_index: state.call_index,
+            trace_index: state.trace_index,
+            traces: state.traces.clone(),
+            expected_revert: state.expected_revert.clone(),
+            next_prank: state.next_prank.clone(),
+            prank: state.prank.clone(),
+            broadcast: state.broadcast.clone(),
+            broadcastable_transactions: state.broadcastable_transactions.clone(),
+            accesses: state.accesses.as_ref().map(|accesses| {
+                (accesses.reads.borrow().clone(), accesses.writes.borrow().clone())
+            }),
+            all_logs: state.all_logs.clone(),
+            expected_emits: state.expected_emits.clone(),
+            mocked_calls: state.mocked_calls.clone(),
+            expected_calls: state.expected_calls.clone(),
+        }
+    }
+
+    /// Restores the state of the `checkpoint` on top of this executor's backend, all changes of
+    /// the current substate are discarded
+    pub fn restore(&mut self, checkpoint: &Checkpoint) {
+        let state = self.executor.state_mut();
+        let metadata = state.substate.metadata().clone();
+        state.substate = MemoryStackSubstate::new(metadata);
+        state.journal = checkpoint.journal.clone();
+        state.backend.cheats = checkpoint.cheats.clone();
+        state.call_index = checkpoint.call_index;
+        state.trace_index = checkpoint.trace_index;
+        state.traces = checkpoint.traces.clone();
+        state.expected_revert = checkpoint.expected_revert.clone();
+        state.next_prank = checkpoint.next_prank.clone();
+        state.prank = checkpoint.prank.clone();
+        state.broadcast = checkpoint.broadcast.clone();
+        state.broadcastable_transactions = checkpoint.broadcastable_transactions.clone();
+        state.accesses = checkpoint.accesses.clone().map(|(reads, writes)| RecordAccess {
+            reads: RefCell::new(reads),
+            writes: RefCell::new(writes),
+        });
+        state.all_logs = checkpoint.all_logs.clone();
+        state.expected_emits = checkpoint.expected_emits.clone();
+        state.mocked_calls = checkpoint.mocked_calls.clone();
+        state.expected_calls = checkpoint.expected_calls.clone();
+    }
+}
+
+/// The state of a cheatcode-enabled [`Executor`] between transactions, e.g. after `setUp`.
+///
+/// All state changes are committed into the [`Journal`] whose layers are shared, so that taking
+/// and restoring a checkpoint does not copy the state. Unlike the executor's state, a checkpoint
+/// can be shared across threads. Debug steps are not part of a checkpoint.
+#[derive(Debug, Clone)]
+pub struct Checkpoint {
+    journal: Journal,
+    cheats: Cheatcodes,
+    call_index: usize,
+    trace_index: usize,
+    traces: Vec<CallTraceArena>,
+    expected_revert: Option<Vec<u8>>,
+    next_prank: Option<Prank>,
+    prank: Option<Prank>,
+    broadcast: Option<Broadcast>,
+    broadcastable_transactions: Vec<BroadcastableTransaction>,
+    accesses: Option<(BTreeMap<H160, Vec<H256>>, BTreeMap<H160, Vec<H256>>)>,
+    all_logs: Vec<String>,
+    expected_emits: Vec<ExpectedEmit>,
+    mocked_calls: BTreeMap<H160, BTreeMap<Vec<u8>, Vec<u8>>>,
+    expected_calls: BTreeMap<H160, Vec<Vec<u8>>>,
+}
+
+/// The directory hardhat writes its artifacts to
+const HARDHAT_ARTIFACTS: &str = "artifacts";
+
+/// Returns the artifact `<contract_file>/<contract_name>.json` in the directory or its
+/// subdirectories
+fn find_artifact(dir: &Path, contract_file: &str, contract_name: &str) -> Option<PathBuf> {
+    let path = dir.join(contract_file).join(format!("{}.json", contract_name));
+    if path.is_file() {
+        return Some(path)
+    }
+    std::fs::read_dir(dir)
+        .ok()?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir() && !path.ends_with("build-info"))
+        .find_map(|dir| find_artifact(&dir, contract_file, contract_name))
+}
+
+/// Returns the creation bytecode of a forge (`bin`), hardhat or truffle (`bytecode`) artifact
+fn artifact_code(artifact: &str) -> Option<Vec<u8>> {
+    let artifact: serde_json::Value = serde_json::from_str(artifact).ok()?;
+    let code = ["bin", "bytecode"].iter().find_map(|key| {
+        let code = artifact.get(key)?;
+        code.as_str().or_else(|| code.get("object")?.as_str())
+    })?;
+    decode_artifact_code(code)
 }
 
 // helper for creating an exit type
@@ -508,16 +754,14 @@ impl<'a, 'b, B: Backend, P: PrecompileSet> CheatcodeStackExecutor<'a, 'b, B, P>
         if self.state().debug_enabled {
             let depth =
                 if let Some(depth) = self.state().metadata().depth() { depth + 1 } else { 0 };
+            let trace_idx = self.state().trace_index;
             self.state_mut().debug_mut().push_node(
                 0,
                 DebugNode {
                     address: *CHEATCODE_ADDRESS,
                     depth,
-                    steps: vec![DebugStep {
-                        op: OpCode::from(cheatop),
-                        memory: Memory::new(0),
-                        ..Default::default()
-                    }],
+                    trace_idx,
+                    steps: vec![DebugStep { op: OpCode::from(cheatop), ..Default::default() }],
                     ..Default::default()
                 },
             );
@@ -564,6 +808,42 @@ impl<'a, 'b, B: Backend, P: PrecompileSet> CheatcodeStackExecutor<'a, 'b, B, P>
         Ok(())
     }
 
+    fn broadcast(
+        &mut self,
+        single_call: bool,
+        msg_sender: Address,
+        new_caller: Option<Address>,
+    ) -> Result<(), Capture<(ExitReason, Vec<u8>), Infallible>> {
+        if self.state().broadcast.is_some() {
+            return Err(evm_error("You have an active broadcast already. Use `stopBroadcast` first"))
+        }
+        let curr_depth =
+            if let Some(depth) = self.state().metadata().depth() { depth + 1 } else { 0 };
+        // broadcast from the default sender unless told otherwise
+        let new_caller = new_caller.unwrap_or_else(|| self.state().backend.backend.origin());
+        self.state_mut().broadcast =
+            Some(Broadcast { caller: msg_sender, new_caller, depth: curr_depth, single_call });
+        Ok(())
+    }
+
+    /// Returns the intrinsic gas of a transaction with the calldata or init code
+    fn intrinsic_gas(&self, data: &[u8], create: bool) -> u64 {
+        let config = self.config();
+        let base = if create { config.gas_transaction_create } else { config.gas_transaction_call };
+        let zeros = data.iter().filter(|b| **b == 0).count() as u64;
+        base + zeros * config.gas_transaction_zero_data +
+            (data.len() as u64 - zeros) * config.gas_transaction_non_zero_data
+    }
+
+    /// Adds the gas the execution of the broadcast transaction at `idx` took in the simulation,
+    /// the gas that's left of the current frame after the execution is compared to `gas_before`
+    fn record_broadcast_gas(&mut self, idx: usize, gas_before: u64) {
+        let used = gas_before.saturating_sub(self.state().metadata().gasometer().gas());
+        if let Some(tx) = self.state_mut().broadcastable_transactions.get_mut(idx) {
+            tx.gas += used.into();
+        }
+    }
+
     fn expect_revert(
         &mut self,
         inner: Vec<u8>,
@@ -579,6 +859,37 @@ impl<'a, 'b, B: Backend, P: PrecompileSet> CheatcodeStackExecutor<'a, 'b, B, P>
         Ok(())
     }
 
+    /// Returns the creation code of a registered contract, or of the artifact of `<file>:<name>`,
+    /// `<file>` or a path to a json artifact
+    fn contract_code(&self, id: &str) -> Result<Vec<u8>, String> {
+        if let Some(code) = self.bytecode_contracts.get(id) {
+            return Ok(code.clone())
+        }
+
+        let path = if id.ends_with(".json") {
+            Path::new(id).to_path_buf()
+        } else {
+            let parts = id.split(':').collect::<Vec<&str>>();
+            let contract_file = parts[0];
+            let contract_name =
+                if parts.len() == 1 { parts[0].replace(".sol", "") } else { parts[1].to_string() };
+
+            let outdir = ProjectPathsConfig::find_artifacts_dir(Path::new("./"));
+            let path = outdir.join(format!("{}/{}.json", contract_file, contract_name));
+            if path.exists() {
+                path
+            } else {
+                // precompiled hardhat artifacts are nested in the path of their source
+                find_artifact(Path::new(HARDHAT_ARTIFACTS), contract_file, &contract_name)
+                    .unwrap_or(path)
+            }
+        };
+
+        let artifact = std::fs::read_to_string(&path)
+            .map_err(|err| format!("failed to read {}: {}", path.display(), err))?;
+        artifact_code(&artifact).ok_or_else(|| format!("no bytecode found in {}", path.display()))
+    }
+
     /// Given a transaction's calldata, it tries to parse it as an [`HEVM cheatcode`](super::HEVM)
     /// call and modify the state accordingly.
     fn apply_cheatcode(
@@ -646,34 +957,67 @@ impl<'a, 'b, B: Backend, P: PrecompileSet> CheatcodeStackExecutor<'a, 'b, B, P>
             }
             HEVMCalls::GetCode(inner) => {
                 self.add_debug(CheatOp::GETCODE);
-
-                #[derive(Deserialize)]
-                struct ContractFile {
-                    #[serde(deserialize_with = "deserialize_bytes")]
-                    bin: Bytes,
+                match self.contract_code(&inner.0) {
+                    Ok(code) => res = ethers::abi::encode(&[Token::Bytes(code)]),
+                    Err(err) => return evm_error(&err),
                 }
-
-                let path = if inner.0.ends_with(".json") {
-                    Path::new(&inner.0).to_path_buf()
-                } else {
-                    let parts = inner.0.split(':').collect::<Vec<&str>>();
-                    let contract_file = parts[0];
-                    let contract_name = if parts.len() == 1 {
-                        parts[0].replace(".sol", "")
-                    } else {
-                        parts[1].to_string()
-                    };
-
-                    let outdir = ProjectPathsConfig::find_artifacts_dir(Path::new("./"));
-                    outdir.join(format!("{}/{}.json", contract_file, contract_name))
+            }
+            HEVMCalls::DeployCode(inner) => {
+                self.add_debug(CheatOp::DEPLOYCODE);
+                let code = match self.contract_code(&inner.0) {
+                    Ok(code) => code,
+                    Err(err) => return evm_error(&err),
                 };
-
-                let mut file = File::open(path).unwrap();
-                let mut data = String::new();
-                file.read_to_string(&mut data).unwrap();
-
-                let contract_file: ContractFile = serde_json::from_str(&data).unwrap();
-                res = ethers::abi::encode(&[Token::Bytes(contract_file.bin.to_vec())]);
+                let scheme = CreateScheme::Legacy { caller: msg_sender };
+                match self.create_inner(msg_sender, scheme, U256::zero(), code, None, false) {
+                    Capture::Exit((ExitReason::Succeed(_), Some(address), _)) => {
+                        res = ethers::abi::encode(&[Token::Address(address)])
+                    }
+                    Capture::Exit((reason, _, retdata)) => {
+                        return evm_error(&format!(
+                            "failed to deploy {}: {:?} {}",
+                            inner.0,
+                            reason,
+                            hex::encode(retdata)
+                        ))
+                    }
+                    Capture::Trap(_) => unreachable!(),
+                }
+            }
+            HEVMCalls::RpcUrl(inner) => {
+                self.add_debug(CheatOp::RPCURL);
+                let url = match self.rpc_endpoints.get(&inner.0) {
+                    Some(url) => url.clone(),
+                    None => {
+                        return evm_error(&format!(
+                            "rpc endpoint `{}` not found in the `rpc_endpoints` of the config",
+                            inner.0
+                        ))
+                    }
+                };
+                res = ethers::abi::encode(&[Token::String(url)]);
+            }
+            HEVMCalls::GetDeployment(inner) => {
+                self.add_debug(CheatOp::GETDEPLOYMENT);
+                let chain_id = self.handler.chain_id().as_u64();
+                let address = match self.deployments.get(&chain_id).and_then(|d| d.get(&inner.0)) {
+                    Some(address) => *address,
+                    None => {
+                        return evm_error(&format!(
+                            "no deployment of `{}` on chain {} in the deployments registry",
+                            inner.0, chain_id
+                        ))
+                    }
+                };
+                res = ethers::abi::encode(&[Token::Address(address)]);
+            }
+            HEVMCalls::HashTypedData(inner) => {
+                self.add_debug(CheatOp::HASHTYPEDDATA);
+                let digest = match TypedData::from_json(&inner.0).and_then(|data| data.digest()) {
+                    Ok(digest) => digest,
+                    Err(err) => return evm_error(&format!("invalid typed data: {}", err)),
+                };
+                res = ethers::abi::encode(&[Token::FixedBytes(digest.to_vec())]);
             }
             HEVMCalls::Addr(inner) => {
                 self.add_debug(CheatOp::ADDR);
@@ -758,6 +1102,47 @@ impl<'a, 'b, B: Backend, P: PrecompileSet> CheatcodeStackExecutor<'a, 'b, B, P>
                 self.add_debug(CheatOp::STOPPRANK);
                 self.state_mut().prank = None;
             }
+            HEVMCalls::ChainId(inner) => {
+                self.add_debug(CheatOp::CHAINID);
+                // there's a single fork, so the transactions of another chain would be simulated
+                // against the state of the chain that was broadcast to before
+                let broadcast_chain =
+                    self.state().broadcastable_transactions.last().map(|tx| tx.chain_id);
+                if broadcast_chain.map_or(false, |chain| U256::from(chain) != inner.0) {
+                    return evm_error(
+                        "the chain id can't be changed after broadcasting, use a script per chain",
+                    )
+                }
+                self.state_mut().backend.cheats.chain_id = Some(inner.0);
+            }
+            HEVMCalls::Broadcast0(_) => {
+                self.add_debug(CheatOp::BROADCAST);
+                if let Err(err) = self.broadcast(true, msg_sender, None) {
+                    return err
+                }
+            }
+            HEVMCalls::Broadcast1(inner) => {
+                self.add_debug(CheatOp::BROADCAST);
+                if let Err(err) = self.broadcast(true, msg_sender, Some(inner.0)) {
+                    return err
+                }
+            }
+            HEVMCalls::StartBroadcast0(_) => {
+                self.add_debug(CheatOp::STARTBROADCAST);
+                if let Err(err) = self.broadcast(false, msg_sender, None) {
+                    return err
+                }
+            }
+            HEVMCalls::StartBroadcast1(inner) => {
+                self.add_debug(CheatOp::STARTBROADCAST);
+                if let Err(err) = self.broadcast(false, msg_sender, Some(inner.0)) {
+                    return err
+                }
+            }
+            HEVMCalls::StopBroadcast(_) => {
+                self.add_debug(CheatOp::STOPBROADCAST);
+                self.state_mut().broadcast = None;
+            }
             HEVMCalls::ExpectRevert0(inner) => {
                 if let Err(e) = self.expect_revert(inner.0.to_vec()) {
                     return e
@@ -845,6 +1230,21 @@ impl<'a, 'b, B: Backend, P: PrecompileSet> CheatcodeStackExecutor<'a, 'b, B, P>
                 self.add_debug(CheatOp::EXPECTCALL);
                 self.state_mut().expected_calls.entry(inner.0).or_default().push(inner.1.to_vec());
             }
+            call @ (HEVMCalls::AssertEq0(_) |
+            HEVMCalls::AssertEq1(_) |
+            HEVMCalls::AssertEq2(_) |
+            HEVMCalls::AssertEq3(_) |
+            HEVMCalls::AssertEq4(_) |
+            HEVMCalls::AssertEq5(_) |
+            HEVMCalls::AssertEq6(_) |
+            HEVMCalls::AssertEq7(_)) => {
+                self.add_debug(CheatOp::ASSERTEQ);
+                let (actual, expected, kind) =
+                    assertions::assertion_operands(&call).expect("is an assertion");
+                if let Err(diff) = assertions::assert_eq(&actual, &expected, kind) {
+                    return evm_error(&diff)
+                }
+            }
         };
 
         self.fill_trace(&trace, true, Some(res.clone()), pre_index);
@@ -882,17 +1282,40 @@ impl<'a, 'b, B: Backend, P: PrecompileSet> CheatcodeStackExecutor<'a, 'b, B, P>
         }
     }
 
+    /// Executes the call/create while recording the program counters it hits
+    fn coverage_execute(&mut self, runtime: &mut Runtime, address: Address) -> ExitReason {
+        loop {
+            if let Ok(pc) = runtime.machine().position() {
+                if let Some(coverage) = self.state_mut().coverage.as_mut() {
+                    coverage.insert((address, *pc));
+                }
+            }
+            match runtime.step(self) {
+                Ok(()) => {}
+                Err(Capture::Exit(s)) => return s,
+                Err(Capture::Trap(_)) => unreachable!("Trap is Infallible"),
+            }
+        }
+    }
+
     /// Does *not* actually perform a step, just records the debug information for the step
     fn debug_step(
         &mut self,
         runtime: &mut Runtime,
         code: Rc<Vec<u8>>,
-        steps: &mut Vec<DebugStep>,
+        steps: &mut VecDeque<DebugStep>,
         pc_ic: Rc<BTreeMap<usize, usize>>,
     ) -> bool {
         // grab the pc, opcode and stack
         let pc = runtime.machine().position().as_ref().map(|p| *p).unwrap_or_default();
         let mut push_bytes = None;
+        // only record the pages of the memory that changed since the previous step
+        let previous = steps.back();
+        let memory = DebugMemory::record(
+            runtime.machine().memory(),
+            previous.map(|step| &step.memory),
+            previous.map_or(true, |step| step.op.writes_memory()),
+        );
 
         if let Some((op, stack)) = runtime.machine().inspect() {
             // wrap the op to make it compatible with opcode extensions for cheatops
@@ -913,10 +1336,10 @@ impl<'a, 'b, B: Backend, P: PrecompileSet> CheatcodeStackExecutor<'a, 'b, B, P>
             let mut stack = stack.data().clone();
             stack.reverse();
             // push the step into the vector
-            steps.push(DebugStep {
+            steps.push_back(DebugStep {
                 pc,
                 stack,
-                memory: runtime.machine().memory().clone(),
+                memory,
                 op: wrapped_op,
                 push_bytes,
                 ic: *pc_ic.get(&pc).as_ref().copied().unwrap_or(&0usize),
@@ -940,10 +1363,10 @@ impl<'a, 'b, B: Backend, P: PrecompileSet> CheatcodeStackExecutor<'a, 'b, B, P>
             // failure case.
             let mut stack = runtime.machine().stack().data().clone();
             stack.reverse();
-            steps.push(DebugStep {
+            steps.push_back(DebugStep {
                 pc,
                 stack,
-                memory: runtime.machine().memory().clone(),
+                memory,
                 op: OpCode::from(Opcode::INVALID),
                 push_bytes,
                 ic: *pc_ic.get(&pc).as_ref().copied().unwrap_or(&0usize),
@@ -953,6 +1376,28 @@ impl<'a, 'b, B: Backend, P: PrecompileSet> CheatcodeStackExecutor<'a, 'b, B, P>
         }
     }
 
+    /// Pushes the `steps` as a new node into the current debug arena and drops the oldest steps
+    /// of the arena if the step limit is exceeded
+    fn push_debug_node(
+        &mut self,
+        address: Address,
+        depth: usize,
+        steps: VecDeque<DebugStep>,
+        creation: bool,
+    ) {
+        let step_limit = self.state().debug_step_limit;
+        let trace_idx = self.state().trace_index;
+        let arena = self.state_mut().debug_mut();
+        let steps = steps.into();
+        arena.push_node(
+            0,
+            DebugNode { address, depth, steps, creation, trace_idx, ..Default::default() },
+        );
+        if let Some(limit) = step_limit {
+            arena.limit_steps(limit);
+        }
+    }
+
     fn debug_run(
         &mut self,
         runtime: &mut Runtime,
@@ -963,7 +1408,8 @@ impl<'a, 'b, B: Backend, P: PrecompileSet> CheatcodeStackExecutor<'a, 'b, B, P>
     ) -> Capture<ExitReason, ()> {
         let mut done = false;
         let mut res = Capture::Exit(ExitReason::Succeed(ExitSucceed::Returned));
-        let mut steps = Vec::new();
+        let mut steps = VecDeque::new();
+        let step_limit = self.state().debug_step_limit;
         // grab the debug instruction pointers for either construct or runtime bytecode
         let dip = if creation {
             &mut self.state_mut().debug_instruction_pointers.0
@@ -1003,19 +1449,15 @@ impl<'a, 'b, B: Backend, P: PrecompileSet> CheatcodeStackExecutor<'a, 'b, B, P>
             // debug step doesnt actually execute the step, it just peeks into the machine
             // will return true or false, which signifies whether to push the steps
             // as a node and reset the steps vector or not
-            if self.debug_step(runtime, code.clone(), &mut steps, ics.clone()) && !steps.is_empty()
-            {
-                self.state_mut().debug_mut().push_node(
-                    0,
-                    DebugNode {
-                        address,
-                        depth,
-                        steps: steps.clone(),
-                        creation,
-                        ..Default::default()
-                    },
-                );
-                steps = Vec::new();
+            let interrupt = self.debug_step(runtime, code.clone(), &mut steps, ics.clone());
+            if let Some(limit) = step_limit {
+                // only keep the most recent steps
+                while steps.len() > limit {
+                    steps.pop_front();
+                }
+            }
+            if interrupt && !steps.is_empty() {
+                self.push_debug_node(address, depth, std::mem::take(&mut steps), creation);
             }
             // actually executes the opcode step
             let r = runtime.step(self);
@@ -1026,16 +1468,7 @@ impl<'a, 'b, B: Backend, P: PrecompileSet> CheatcodeStackExecutor<'a, 'b, B, P>
                     // we wont hit an interrupt when we finish stepping
                     // so we have add the accumulated steps as if debug_step returned true
                     if !steps.is_empty() {
-                        self.state_mut().debug_mut().push_node(
-                            0,
-                            DebugNode {
-                                address,
-                                depth,
-                                steps: steps.clone(),
-                                creation,
-                                ..Default::default()
-                            },
-                        );
+                        self.push_debug_node(address, depth, std::mem::take(&mut steps), creation);
                     }
                     match e {
                         Capture::Exit(s) => res = Capture::Exit(s),
@@ -1226,6 +1659,9 @@ impl<'a, 'b, B: Backend, P: PrecompileSet> CheatcodeStackExecutor<'a, 'b, B, P>
             let code = Rc::new(code);
             runtime = Runtime::new(code.clone(), Rc::new(input), context, &config);
             self.debug_execute(&mut runtime, code_address, code, false)
+        } else if self.state().coverage.is_some() {
+            runtime = Runtime::new(Rc::new(code), Rc::new(input), context, &config);
+            self.coverage_execute(&mut runtime, code_address)
         } else {
             runtime = Runtime::new(Rc::new(code), Rc::new(input), context, &config);
             self.execute(&mut runtime)
@@ -1373,6 +1809,9 @@ impl<'a, 'b, B: Backend, P: PrecompileSet> CheatcodeStackExecutor<'a, 'b, B, P>
             let code = Rc::new(init_code);
             runtime = Runtime::new(code.clone(), Rc::new(Vec::new()), context, &config);
             self.debug_execute(&mut runtime, address, code, true)
+        } else if self.state().coverage.is_some() {
+            runtime = Runtime::new(Rc::new(init_code), Rc::new(Vec::new()), context, &config);
+            self.coverage_execute(&mut runtime, address)
         } else {
             runtime = Runtime::new(Rc::new(init_code), Rc::new(Vec::new()), context, &config);
             self.execute(&mut runtime)
@@ -1506,6 +1945,41 @@ impl<'a, 'b, B: Backend, P: PrecompileSet> Handler for CheatcodeStackExecutor<'a
                 self.state_mut().backend.cheats.origin = new_origin;
             }
 
+            // handle `broadcast` and `startBroadcast`, the call is sent from the broadcaster and
+            // recorded so it can be sent as a transaction later
+            let mut broadcast_index = None;
+            if let Some(Broadcast { caller, new_caller, depth, single_call }) =
+                self.state().broadcast
+            {
+                if !is_static && curr_depth == depth && new_context.caller == caller {
+                    new_context.caller = new_caller;
+
+                    if let Some(t) = &new_transfer {
+                        new_transfer =
+                            Some(Transfer { source: new_caller, target: t.target, value: t.value });
+                    }
+                    self.state_mut().backend.cheats.origin = Some(new_caller);
+
+                    let tx = BroadcastableTransaction {
+                        chain_id: self.state().backend.chain_id().as_u64(),
+                        from: new_caller,
+                        to: Some(code_address),
+                        value: new_transfer.as_ref().map(|t| t.value).unwrap_or_default(),
+                        gas: self.intrinsic_gas(&input, false).into(),
+                        data: input.clone().into(),
+                        simulated_nonce: self.handler.nonce(new_caller),
+                    };
+                    // the call is a transaction of its own on chain, which uses up a nonce of the
+                    // broadcaster, so the addresses of its later creations match
+                    self.state_mut().inc_nonce(new_caller);
+                    broadcast_index = Some(self.state().broadcastable_transactions.len());
+                    self.state_mut().broadcastable_transactions.push(tx);
+                    if single_call {
+                        self.state_mut().broadcast = None;
+                    }
+                }
+            }
+
             // handle expected calls
             if let Some(expecteds) = self.state_mut().expected_calls.get_mut(&code_address) {
                 if let Some(found_match) = expecteds.iter().position(|expected| {
@@ -1533,6 +2007,7 @@ impl<'a, 'b, B: Backend, P: PrecompileSet> Handler for CheatcodeStackExecutor<'a
             }
 
             // perform the call
+            let gas_before = self.state().metadata().gasometer().gas();
             let res = self.call_inner(
                 code_address,
                 new_transfer,
@@ -1543,6 +2018,9 @@ impl<'a, 'b, B: Backend, P: PrecompileSet> Handler for CheatcodeStackExecutor<'a
                 true,
                 new_context,
             );
+            if let Some(idx) = broadcast_index {
+                self.record_broadcast_gas(idx, gas_before);
+            }
 
             // if we set the origin, now we should reset to previous
             self.state_mut().backend.cheats.origin = prev_origin;
@@ -1746,7 +2224,52 @@ impl<'a, 'b, B: Backend, P: PrecompileSet> Handler for CheatcodeStackExecutor<'a
             self.state_mut().backend.cheats.origin = new_origin
         }
 
-        if caller != new_tx_caller {
+        // handle `broadcast` and `startBroadcast`, CREATE2 creations are sent through the
+        // canonical CREATE2 deployer so the address matches the simulated one
+        let mut broadcast_create2 = None;
+        let mut broadcast_index = None;
+        if let Some(Broadcast { caller: broadcast_caller, new_caller, depth, single_call }) =
+            self.state().broadcast
+        {
+            if curr_depth == depth && new_tx_caller == broadcast_caller {
+                new_tx_caller = new_caller;
+                self.state_mut().backend.cheats.origin = Some(new_caller);
+
+                let (to, data, gas) = match scheme {
+                    CreateScheme::Create2 { code_hash, salt, .. } => {
+                        broadcast_create2 = Some(CreateScheme::Create2 {
+                            caller: DEFAULT_CREATE2_DEPLOYER,
+                            code_hash,
+                            salt,
+                        });
+                        let data = [salt.as_bytes(), init_code.as_slice()].concat();
+                        // the deployer pays for the CREATE2 and for hashing the init code
+                        let create2_gas = 32000 + 6 * ((init_code.len() as u64 + 31) / 32);
+                        let gas = self.intrinsic_gas(&data, false) + create2_gas;
+                        (Some(DEFAULT_CREATE2_DEPLOYER), data, gas)
+                    }
+                    _ => (None, init_code.clone(), self.intrinsic_gas(&init_code, true)),
+                };
+                let tx = BroadcastableTransaction {
+                    chain_id: self.state().backend.chain_id().as_u64(),
+                    from: new_caller,
+                    to,
+                    value,
+                    gas: gas.into(),
+                    data: data.into(),
+                    simulated_nonce: self.handler.nonce(new_caller),
+                };
+                broadcast_index = Some(self.state().broadcastable_transactions.len());
+                self.state_mut().broadcastable_transactions.push(tx);
+                if single_call {
+                    self.state_mut().broadcast = None;
+                }
+            }
+        }
+
+        if let Some(create2) = broadcast_create2 {
+            new_scheme = create2;
+        } else if caller != new_tx_caller {
             new_scheme = match scheme {
                 CreateScheme::Legacy { .. } => CreateScheme::Legacy { caller: new_tx_caller },
                 CreateScheme::Create2 { code_hash, salt, .. } => {
@@ -1756,7 +2279,11 @@ impl<'a, 'b, B: Backend, P: PrecompileSet> Handler for CheatcodeStackExecutor<'a
             };
         }
 
+        let gas_before = self.state().metadata().gasometer().gas();
         let res = self.create_inner(new_tx_caller, new_scheme, value, init_code, target_gas, true);
+        if let Some(idx) = broadcast_index {
+            self.record_broadcast_gas(idx, gas_before);
+        }
 
         // if we set the origin, now we should reset to prior origin
         self.state_mut().backend.cheats.origin = prev_origin;
@@ -1797,6 +2324,27 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn records_access_list() {
+        let mut evm = vm();
+        let addr = Address::from_low_u64_be(0x1234);
+        let other = Address::from_low_u64_be(0x4242);
+        // SLOAD(1), BALANCE(0x4242)
+        let code = hex::decode("60015450614242315000").unwrap();
+        evm.initialize_contracts([(addr, code.into())]);
+
+        evm.record_access_list();
+        evm.call_raw(Address::zero(), addr, Default::default(), 0.into(), false).unwrap();
+        let access_list = evm.access_list(Address::zero(), addr);
+        assert_eq!(
+            access_list.0,
+            vec![
+                AccessListItem { address: addr, storage_keys: vec![H256::from_low_u64_be(1)] },
+                AccessListItem { address: other, storage_keys: vec![] },
+            ]
+        );
+    }
+
     #[test]
     fn ds_test_logs() {
         let mut evm = vm();
@@ -2054,6 +2602,174 @@ mod tests {
         assert_eq!(reason, "ffi disabled: run again with --ffi if you want to allow tests to call external scripts");
     }
 
+    #[test]
+    fn rpc_url_resolves_endpoint() {
+        let mut evm = vm_no_limit();
+        evm.set_rpc_endpoints(BTreeMap::from([(
+            "mainnet".to_string(),
+            "https://mainnet.example.com".to_string(),
+        )]));
+
+        let compiled = COMPILED.find("CheatCodes").expect("could not find contract");
+        let (addr, _, _, _) =
+            evm.deploy(Address::zero(), compiled.bytecode().unwrap().clone(), 0.into()).unwrap();
+
+        let (url, _, _, _) = evm
+            .call::<String, _, _>(
+                Address::zero(),
+                addr,
+                "rpcUrl(string)(string)",
+                "mainnet".to_string(),
+                0.into(),
+                compiled.abi,
+            )
+            .unwrap();
+        assert_eq!(url, "https://mainnet.example.com");
+    }
+
+    #[test]
+    fn get_deployment_resolves_address_of_current_chain() {
+        let mut evm = vm_no_limit();
+        let chain_id = evm.executor.chain_id().as_u64();
+        let token = Address::from_low_u64_be(1337);
+        evm.set_deployments(BTreeMap::from([
+            (chain_id, BTreeMap::from([("Token".to_string(), token)])),
+            (chain_id + 1, BTreeMap::from([("Vault".to_string(), Address::from_low_u64_be(1))])),
+        ]));
+
+        let compiled = COMPILED.find("CheatCodes").expect("could not find contract");
+        let (addr, _, _, _) =
+            evm.deploy(Address::zero(), compiled.bytecode().unwrap().clone(), 0.into()).unwrap();
+
+        let (deployed, _, _, _) = evm
+            .call::<Address, _, _>(
+                Address::zero(),
+                addr,
+                "getDeployment(string)(address)",
+                "Token".to_string(),
+                0.into(),
+                compiled.abi,
+            )
+            .unwrap();
+        assert_eq!(deployed, token);
+
+        let err = evm
+            .call::<Address, _, _>(
+                Address::zero(),
+                addr,
+                "getDeployment(string)(address)",
+                "Vault".to_string(),
+                0.into(),
+                compiled.abi,
+            )
+            .unwrap_err();
+        let reason = match err {
+            crate::EvmError::Execution { reason, .. } => reason,
+            _ => panic!("unexpected error"),
+        };
+        assert_eq!(
+            reason,
+            format!("no deployment of `Vault` on chain {} in the deployments registry", chain_id)
+        );
+    }
+
+    #[test]
+    fn deploy_code_deploys_registered_contract() {
+        let mut evm = vm_no_limit();
+        let artifact = std::fs::read_to_string("testdata/Contract.json").unwrap();
+        let code = artifact_code(&artifact).unwrap();
+        evm.set_bytecode_contracts(BTreeMap::from([("Seven".to_string(), code)]));
+
+        let compiled = COMPILED.find("CheatCodes").expect("could not find contract");
+        let (addr, _, _, _) =
+            evm.deploy(Address::zero(), compiled.bytecode().unwrap().clone(), 0.into()).unwrap();
+
+        let (deployed, _, _, _) = evm
+            .call::<Address, _, _>(
+                Address::zero(),
+                addr,
+                "deployCode(string)(address)",
+                "Seven".to_string(),
+                0.into(),
+                compiled.abi,
+            )
+            .unwrap();
+        assert!(!evm.code(deployed).is_empty());
+    }
+
+    #[test]
+    fn broadcast_records_transactions() {
+        let mut evm = vm_no_limit();
+
+        let compiled = COMPILED.find("CheatCodes").expect("could not find contract");
+        let (addr, _, _, _) =
+            evm.deploy(Address::zero(), compiled.bytecode().unwrap().clone(), 0.into()).unwrap();
+
+        evm.call::<(), _, _>(
+            Address::zero(),
+            addr,
+            "testStartBroadcast()",
+            (),
+            0.into(),
+            compiled.abi,
+        )
+        .unwrap();
+
+        // the creation and the first call, nothing after `stopBroadcast`
+        let broadcaster = Address::from_low_u64_be(1337);
+        let txs = evm.broadcastable_transactions();
+        assert_eq!(txs.len(), 2);
+        assert!(txs.iter().all(|tx| tx.from == broadcaster));
+        assert_eq!(txs[0].to, None);
+        assert!(txs[1].to.is_some());
+        // the simulated gas includes the intrinsic gas
+        assert!(txs[0].gas > 53000.into());
+        assert!(txs[1].gas > 21000.into());
+    }
+
+    #[test]
+    fn hash_typed_data_returns_digest() {
+        let mut evm = vm_no_limit();
+
+        let compiled = COMPILED.find("CheatCodes").expect("could not find contract");
+        let (addr, _, _, _) =
+            evm.deploy(Address::zero(), compiled.bytecode().unwrap().clone(), 0.into()).unwrap();
+
+        let typed_data = r#"{
+            "types": {
+                "Permit": [
+                    { "name": "owner", "type": "address" },
+                    { "name": "spender", "type": "address" },
+                    { "name": "value", "type": "uint256" },
+                    { "name": "nonce", "type": "uint256" },
+                    { "name": "deadline", "type": "uint256" }
+                ]
+            },
+            "primaryType": "Permit",
+            "domain": { "name": "Token", "version": "1", "chainId": 1 },
+            "message": {
+                "owner": "0x0000000000000000000000000000000000000001",
+                "spender": "0x0000000000000000000000000000000000000002",
+                "value": "1000000000000000000",
+                "nonce": 0,
+                "deadline": "0xffffffff"
+            }
+        }"#;
+        let expected = TypedData::from_json(typed_data).unwrap().digest().unwrap();
+
+        let (digest, _, _, _) = evm
+            .call::<H256, _, _>(
+                Address::zero(),
+                addr,
+                "hashTypedData(string)(bytes32)",
+                typed_data.to_string(),
+                0.into(),
+                compiled.abi,
+            )
+            .unwrap();
+        assert_eq!(digest, H256::from(expected));
+    }
+
     #[test]
     fn tracing_call() {
         use std::collections::BTreeMap;
@@ -2111,7 +2827,7 @@ mod tests {
         let mut identified = Default::default();
         let (funcs, events, errors) = foundry_utils::flatten_known_contracts(&mapping);
         let mut exec_info = ExecutionInfo::new(&mapping, &mut identified, &funcs, &events, &errors);
-        evm.traces()[1].pretty_print(0, &mut exec_info, &evm, "");
+        evm.traces()[1].pretty_print(0, &mut exec_info, &evm, "", &mut String::new());
     }
 
     #[test]
@@ -2172,6 +2888,6 @@ mod tests {
         let mut identified = Default::default();
         let (funcs, events, errors) = foundry_utils::flatten_known_contracts(&mapping);
         let mut exec_info = ExecutionInfo::new(&mapping, &mut identified, &funcs, &events, &errors);
-        evm.traces()[1].pretty_print(0, &mut exec_info, &evm, "");
+        evm.traces()[1].pretty_print(0, &mut exec_info, &evm, "", &mut String::new());
     }
 }
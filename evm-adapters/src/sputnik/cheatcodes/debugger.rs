@@ -1,8 +1,11 @@
 use sputnik::{Memory, Opcode};
 
-use ethers::types::{Address, H256};
+use ethers::{
+    solc::sourcemap::{Jump, SourceElement, SourceMap},
+    types::{Address, H256, U256},
+};
 
-use std::{borrow::Cow, fmt::Display};
+use std::{borrow::Cow, collections::BTreeMap, fmt::Display, sync::Arc};
 
 #[derive(Debug, Clone)]
 /// An arena of `DebugNode`s
@@ -47,14 +50,52 @@ impl DebugArena {
     /// 1. the address of the contract being executed
     /// 2. a vector of all the debug steps along that contract's execution path.
     ///  
-    /// This then makes it easy to pretty print the execution steps.
+    /// This then makes it easy to pretty print the execution steps. Nodes without steps, like the
+    /// nodes whose steps were dropped by [`Self::limit_steps`], are skipped.
     pub fn flatten(&self, entry: usize, flattened: &mut Vec<(Address, Vec<DebugStep>, bool)>) {
         let node = &self.arena[entry];
-        flattened.push((node.address, node.steps.clone(), node.creation));
+        if !node.steps.is_empty() {
+            flattened.push((node.address, node.steps.clone(), node.creation));
+        }
         node.children.iter().for_each(|child| {
             self.flatten(*child, flattened);
         });
     }
+
+    /// Returns the nodes of every call in the arena that has steps
+    ///
+    /// The steps of a call are split into nodes at every call it makes, which are the children of
+    /// the caller's node that ends with the call. The nodes of a call share its index in the call
+    /// trace arena, so two calls to the same contract are not mixed up.
+    pub fn calls(&self) -> Vec<Vec<&DebugNode>> {
+        let mut calls = vec![vec![&self.arena[self.entry]]];
+        for node in &self.arena {
+            let mut children: BTreeMap<usize, Vec<&DebugNode>> = BTreeMap::new();
+            for child in node.children.iter().map(|child| &self.arena[*child]) {
+                children.entry(child.trace_idx).or_default().push(child);
+            }
+            calls.extend(children.into_values());
+        }
+        calls.retain(|nodes| nodes.iter().any(|node| !node.steps.is_empty()));
+        calls
+    }
+
+    /// Drops the oldest steps until at most `limit` steps are recorded in the arena.
+    ///
+    /// Nodes are pushed in the order of execution, so the steps of the nodes with the lowest
+    /// index are dropped first.
+    pub fn limit_steps(&mut self, limit: usize) {
+        let total = self.arena.iter().map(|node| node.steps.len()).sum::<usize>();
+        let mut excess = total.saturating_sub(limit);
+        for node in self.arena.iter_mut() {
+            if excess == 0 {
+                break
+            }
+            let dropped = excess.min(node.steps.len());
+            node.steps.drain(..dropped);
+            excess -= dropped;
+        }
+    }
 }
 
 #[derive(Default, Debug, Clone)]
@@ -76,6 +117,9 @@ pub struct DebugNode {
     pub steps: Vec<DebugStep>,
     /// Contract Creation
     pub creation: bool,
+    /// The index of the call in the call trace arena, which is the same for all the nodes the
+    /// steps of a call are split into
+    pub trace_idx: usize,
 }
 
 impl DebugNode {
@@ -94,7 +138,7 @@ pub struct DebugStep {
     /// Stack *prior* to running this struct's associated opcode
     pub stack: Vec<H256>,
     /// Memory *prior* to running this struct's associated opcode
-    pub memory: Memory,
+    pub memory: DebugMemory,
     /// Opcode to be executed
     pub op: OpCode,
     /// Optional bytes that are being pushed onto the stack
@@ -110,7 +154,7 @@ impl Default for DebugStep {
         Self {
             pc: 0,
             stack: vec![],
-            memory: Memory::new(0),
+            memory: Default::default(),
             op: OpCode(Opcode::INVALID, None),
             push_bytes: None,
             ic: 0,
@@ -119,6 +163,74 @@ impl Default for DebugStep {
     }
 }
 
+/// The size of the pages of a [`DebugMemory`]
+const MEMORY_PAGE_SIZE: usize = 1024;
+
+/// A snapshot of the EVM's memory that is delta-encoded against the previous step's memory.
+///
+/// The memory is split into pages of [`MEMORY_PAGE_SIZE`] bytes and only the pages that changed
+/// since the previous step are copied, all other pages are shared with the previous snapshot.
+/// Most opcodes don't touch the memory at all, so a step usually only costs a vector of pointers
+/// instead of a full copy of the memory.
+#[derive(Debug, Clone, Default)]
+pub struct DebugMemory {
+    pages: Vec<Arc<[u8]>>,
+    len: usize,
+    effective_len: U256,
+}
+
+impl DebugMemory {
+    /// Records the `memory`, sharing all unchanged pages with the `previous` snapshot
+    ///
+    /// The pages are only compared if the previous step's opcode `written` to the memory,
+    /// otherwise the memory can only have been expanded, which leaves all full pages unchanged.
+    pub fn record(memory: &Memory, previous: Option<&DebugMemory>, written: bool) -> Self {
+        let data = memory.data();
+        let effective_len = memory.effective_len();
+        let previous = match previous {
+            Some(previous) if !written && previous.len == data.len() => {
+                return Self { effective_len, ..previous.clone() }
+            }
+            Some(previous) => previous,
+            None => {
+                let pages = data.chunks(MEMORY_PAGE_SIZE).map(Arc::from).collect();
+                return Self { pages, len: data.len(), effective_len }
+            }
+        };
+        let full_pages = previous.len / MEMORY_PAGE_SIZE;
+        let pages = data
+            .chunks(MEMORY_PAGE_SIZE)
+            .enumerate()
+            .map(|(idx, chunk)| match previous.pages.get(idx) {
+                Some(page) if !written && idx < full_pages => page.clone(),
+                Some(page) if written && page[..] == chunk[..] => page.clone(),
+                _ => Arc::from(chunk),
+            })
+            .collect();
+        Self { pages, len: data.len(), effective_len }
+    }
+
+    /// Returns the contents of the memory
+    pub fn data(&self) -> Vec<u8> {
+        self.pages.iter().flat_map(|page| page.iter().copied()).collect()
+    }
+
+    /// Returns the length of the memory
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether the memory is empty
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the effective length of the memory, i.e. the size it is expanded to
+    pub fn effective_len(&self) -> U256 {
+        self.effective_len
+    }
+}
+
 impl DebugStep {
     /// Pretty print the step's opcode
     pub fn pretty_opcode(&self) -> String {
@@ -179,6 +291,15 @@ pub enum CheatOp {
     CLEARMOCKEDCALLS,
     EXPECTCALL,
     GETCODE,
+    DEPLOYCODE,
+    RPCURL,
+    GETDEPLOYMENT,
+    HASHTYPEDDATA,
+    CHAINID,
+    BROADCAST,
+    STARTBROADCAST,
+    STOPBROADCAST,
+    ASSERTEQ,
 }
 
 impl From<CheatOp> for OpCode {
@@ -212,6 +333,15 @@ impl CheatOp {
             CheatOp::CLEARMOCKEDCALLS => "VM_CLEARMOCKEDCALLS",
             CheatOp::EXPECTCALL => "VM_EXPECTCALL",
             CheatOp::GETCODE => "VM_GETCODE",
+            CheatOp::DEPLOYCODE => "VM_DEPLOYCODE",
+            CheatOp::RPCURL => "VM_RPCURL",
+            CheatOp::GETDEPLOYMENT => "VM_GETDEPLOYMENT",
+            CheatOp::HASHTYPEDDATA => "VM_HASHTYPEDDATA",
+            CheatOp::CHAINID => "VM_CHAINID",
+            CheatOp::BROADCAST => "VM_BROADCAST",
+            CheatOp::STARTBROADCAST => "VM_STARTBROADCAST",
+            CheatOp::STOPBROADCAST => "VM_STOPBROADCAST",
+            CheatOp::ASSERTEQ => "VM_ASSERTEQ",
         }
     }
 }
@@ -232,6 +362,23 @@ impl From<Opcode> for OpCode {
 }
 
 impl OpCode {
+    /// Whether the opcode can write to the memory, calls write their return data to it
+    pub const fn writes_memory(&self) -> bool {
+        matches!(
+            self.0,
+            Opcode::MSTORE |
+                Opcode::MSTORE8 |
+                Opcode::CALLDATACOPY |
+                Opcode::CODECOPY |
+                Opcode::EXTCODECOPY |
+                Opcode::RETURNDATACOPY |
+                Opcode::CALL |
+                Opcode::CALLCODE |
+                Opcode::DELEGATECALL |
+                Opcode::STATICCALL
+        )
+    }
+
     /// Gets the name of the opcode as a string
     pub const fn name(&self) -> &'static str {
         match self.0 {
@@ -406,3 +553,695 @@ impl Display for OpCode {
         write!(f, "{}", n)
     }
 }
+
+/// An internal Solidity function call, reconstructed from the jumps into and out of functions
+/// that the source map marks
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InternalCall {
+    /// The name of the called function
+    pub name: String,
+    /// The nesting depth of the call within its call frame, starting at 0
+    pub depth: usize,
+    /// The gas used by the call, including the gas of the calls it makes
+    pub gas_used: u64,
+}
+
+/// The internal function calls of a call
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallInternalCalls {
+    /// The address of the contract whose code was executing
+    pub address: Address,
+    /// Whether the creation code was executing
+    pub creation: bool,
+    pub calls: Vec<InternalCall>,
+}
+
+/// Returns the internal function calls of every call in the arena that made any
+///
+/// `source_map` returns the source map of the contract at the address, of its creation bytecode
+/// if the flag is set, calls without a source map are left out. The steps of all the nodes of a
+/// call are decoded together, see [`DebugArena::calls`], so internal calls that span calls to
+/// other contracts are attributed the gas they used.
+pub fn arena_internal_calls(
+    arena: &DebugArena,
+    source_map: impl Fn(Address, bool) -> Option<SourceMap>,
+    sources: &BTreeMap<u32, String>,
+) -> Vec<CallInternalCalls> {
+    arena
+        .calls()
+        .into_iter()
+        .filter_map(|nodes| {
+            let (address, creation) = (nodes[0].address, nodes[0].creation);
+            let source_map = source_map(address, creation)?;
+            let steps = nodes.iter().flat_map(|node| &node.steps).collect::<Vec<_>>();
+            let calls = decode_internal_calls(&steps, &source_map, sources);
+            (!calls.is_empty()).then(|| CallInternalCalls { address, creation, calls })
+        })
+        .collect()
+}
+
+/// Returns the internal function calls of the steps of a call frame in the order they were made
+///
+/// `source_map` is the source map of the executed bytecode, the creation bytecode's for contract
+/// creations, and `sources` the source code by source file index. Jumps into code that isn't a
+/// named function, like the helpers the compiler generates, are not reported. Calls that never
+/// return, because the frame reverted, used the gas up to the last step.
+pub fn decode_internal_calls(
+    steps: &[&DebugStep],
+    source_map: &SourceMap,
+    sources: &BTreeMap<u32, String>,
+) -> Vec<InternalCall> {
+    let mut calls: Vec<InternalCall> = Vec::new();
+    // the index of every call that hasn't returned yet and the gas used before it
+    let mut open: Vec<(Option<usize>, u64)> = Vec::new();
+    for (i, step) in steps.iter().enumerate() {
+        if step.op.0 != Opcode::JUMP {
+            continue
+        }
+        let next = steps.get(i + 1);
+        match source_map.get(step.ic).map(|element| &element.jump) {
+            Some(Jump::In) => {
+                // the jump destination is the start of the function's definition
+                let name = next
+                    .and_then(|dest| source_map.get(dest.ic))
+                    .and_then(|element| function_name(element, sources));
+                let idx = name.map(|name| {
+                    let depth = open.iter().filter(|(idx, _)| idx.is_some()).count();
+                    calls.push(InternalCall { name, depth, gas_used: 0 });
+                    calls.len() - 1
+                });
+                open.push((idx, step.total_gas_used));
+            }
+            Some(Jump::Out) => {
+                if let Some((Some(idx), gas_before)) = open.pop() {
+                    let gas_after = next.unwrap_or(step).total_gas_used;
+                    calls[idx].gas_used = gas_after.saturating_sub(gas_before);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let last = steps.last().map(|step| step.total_gas_used).unwrap_or_default();
+    for (idx, gas_before) in open {
+        if let Some(idx) = idx {
+            calls[idx].gas_used = last.saturating_sub(gas_before);
+        }
+    }
+    calls
+}
+
+/// Returns the name of the function whose definition the source element spans
+fn function_name(element: &SourceElement, sources: &BTreeMap<u32, String>) -> Option<String> {
+    let source = sources.get(&element.index?)?;
+    let definition = source.get(element.offset..element.offset + element.length)?;
+    let name = definition
+        .trim_start()
+        .strip_prefix("function")?
+        .trim_start()
+        .chars()
+        .take_while(|c| c.is_alphanumeric() || *c == '_' || *c == '$')
+        .collect::<String>();
+    (!name.is_empty()).then(|| name)
+}
+
+/// The selector of `Panic(uint256)`, the error solc >= 0.8 reverts with on failed checks
+const PANIC_SELECTOR: [u8; 4] = [0x4e, 0x48, 0x7b, 0x71];
+
+/// The step of a call frame that reverted, see [`DebugArena::find_revert`]
+#[derive(Debug, Clone)]
+pub struct RevertSite<'a> {
+    /// The index of the node of the step in the arena
+    pub idx: usize,
+    /// The address of the contract that reverted
+    pub address: Address,
+    /// Whether the contract reverted in its creation code
+    pub creation: bool,
+    /// The `REVERT` or `INVALID` step
+    pub step: &'a DebugStep,
+}
+
+impl<'a> RevertSite<'a> {
+    /// Returns the data the frame reverted with, empty for `INVALID`
+    pub fn data(&self) -> Vec<u8> {
+        if self.step.op.0 != Opcode::REVERT || self.step.stack.len() < 2 {
+            return Vec::new()
+        }
+        // the stack is recorded with the top first
+        let offset = U256::from_big_endian(self.step.stack[0].as_bytes()).low_u64() as usize;
+        let size = U256::from_big_endian(self.step.stack[1].as_bytes()).low_u64() as usize;
+        let memory = self.step.memory.data();
+        memory.get(offset..offset.saturating_add(size)).map(<[u8]>::to_vec).unwrap_or_default()
+    }
+}
+
+impl DebugArena {
+    /// Returns where the execution reverted: the `REVERT` or `INVALID` the entry call ended in,
+    /// followed down the calls whose revert it bubbled up
+    ///
+    /// A caller bubbles up the revert of the last call it made if it reverts with the same data.
+    /// Reverts that were caught, by a `try`/`catch` or a low level call, aren't on that path.
+    pub fn find_revert(&self) -> Option<RevertSite<'_>> {
+        let entry = &self.arena[self.entry];
+        let mut nodes = if entry.steps.is_empty() { self.callee(entry) } else { vec![entry] };
+        loop {
+            let (last, rest) = nodes.split_last()?;
+            let step = last.steps.last()?;
+            if !matches!(step.op.0, Opcode::REVERT | Opcode::INVALID) {
+                return None
+            }
+            let site =
+                RevertSite { idx: last.idx, address: last.address, creation: last.creation, step };
+            // the node before the last one ends with the last call the caller made
+            let callee = rest.last().map(|node| self.callee(node)).unwrap_or_default();
+            let bubbled = callee.last().and_then(|node| node.steps.last()).map_or(false, |step| {
+                matches!(step.op.0, Opcode::REVERT | Opcode::INVALID) &&
+                    RevertSite { step, ..site.clone() }.data() == site.data()
+            });
+            if !bubbled {
+                return Some(site)
+            }
+            nodes = callee;
+        }
+    }
+
+    /// Returns the nodes of the call the node ends with
+    fn callee(&self, node: &DebugNode) -> Vec<&DebugNode> {
+        let children = node.children.iter().map(|child| &self.arena[*child]).collect::<Vec<_>>();
+        let trace_idx = children.last().map(|child| child.trace_idx);
+        children.into_iter().filter(|child| Some(child.trace_idx) == trace_idx).collect()
+    }
+
+    /// Pushes the indexes of the nodes from `from` down to the node `idx` onto the path, returns
+    /// whether the node was found
+    fn path_to(&self, from: usize, idx: usize, path: &mut Vec<usize>) -> bool {
+        path.push(from);
+        if from == idx ||
+            self.arena[from].children.iter().any(|child| self.path_to(*child, idx, path))
+        {
+            return true
+        }
+        path.pop();
+        false
+    }
+}
+
+/// The source expression a revert without a reason string originates from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RevertExplanation {
+    /// What failed, e.g. `arithmetic overflow or underflow`
+    pub kind: String,
+    /// The first line of the source expression that reverted
+    pub expression: String,
+    /// The index of the source file
+    pub index: u32,
+    /// The line of the expression, starting at 1
+    pub line: usize,
+    /// The source line the expression starts on
+    pub snippet: String,
+}
+
+/// Maps the reverting step through the source map to the source expression that reverted
+///
+/// `source_map` is the source map of the executed bytecode and `sources` the source code by
+/// source file index. Panics of solc >= 0.8 are classified by their error code, older `assert`s
+/// revert with `INVALID`.
+pub fn explain_revert(
+    site: &RevertSite<'_>,
+    source_map: &SourceMap,
+    sources: &BTreeMap<u32, String>,
+) -> Option<RevertExplanation> {
+    let element = source_map.get(site.step.ic)?;
+    let location = SourceLocation::new(element, sources)?;
+    let expression =
+        sources[&location.index].get(element.offset..element.offset + element.length)?;
+
+    let data = site.data();
+    let kind = if data.len() >= 36 && data[..4] == PANIC_SELECTOR {
+        panic_kind(U256::from_big_endian(&data[4..36]))
+    } else if site.step.op.0 == Opcode::INVALID {
+        "invalid opcode".to_string()
+    } else if expression.trim_start().starts_with("require") {
+        "`require` failed".to_string()
+    } else {
+        "reverted".to_string()
+    };
+
+    Some(RevertExplanation {
+        kind,
+        expression: expression.trim().lines().next().unwrap_or_default().to_string(),
+        index: location.index,
+        line: location.line,
+        snippet: location.snippet,
+    })
+}
+
+/// Explains where the execution of the arena reverted, see [`DebugArena::find_revert`] and
+/// [`explain_revert`]
+///
+/// `source_map` returns the source map of the contract at the address, of its creation bytecode
+/// if the flag is set, and `sources` the source code by source file index.
+pub fn explain_arena_revert(
+    arena: &DebugArena,
+    source_map: impl Fn(Address, bool) -> Option<SourceMap>,
+    sources: &BTreeMap<u32, String>,
+) -> Option<RevertExplanation> {
+    let site = arena.find_revert()?;
+    explain_revert(&site, &source_map(site.address, site.creation)?, sources)
+}
+
+/// A position in a source file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceLocation {
+    /// The index of the source file
+    pub index: u32,
+    /// The line, starting at 1
+    pub line: usize,
+    /// The source line
+    pub snippet: String,
+}
+
+impl SourceLocation {
+    /// Returns the location the source element starts at
+    pub fn new(element: &SourceElement, sources: &BTreeMap<u32, String>) -> Option<Self> {
+        let index = element.index?;
+        let source = sources.get(&index)?;
+        let before = source.get(..element.offset)?;
+        let start = before.rfind('\n').map(|i| i + 1).unwrap_or_default();
+        let end =
+            source[element.offset..].find('\n').map(|i| element.offset + i).unwrap_or(source.len());
+        Some(Self {
+            index,
+            line: before.matches('\n').count() + 1,
+            snippet: source[start..end].trim().to_string(),
+        })
+    }
+}
+
+/// A frame of a Solidity stack trace: a function that was executing when the execution reverted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StackFrame {
+    /// The address of the contract whose code was executing
+    pub address: Address,
+    /// The contract or library the function is defined in
+    pub contract: Option<String>,
+    /// The name of the function, `None` if the source maps don't tell
+    pub function: Option<String>,
+    /// Where the function was at: the call into the next frame, or the revert in the last frame
+    pub location: Option<SourceLocation>,
+}
+
+/// Returns the Solidity stack trace of the revert, the frames of the external calls and the
+/// internal function calls from the entry call down to the function that reverted
+///
+/// `source_map` returns the source map of the contract at the address, of its creation bytecode
+/// if the flag is set, and `sources` the source code by source file index. Internal calls are
+/// reconstructed from the jumps into and out of functions like in [`decode_internal_calls`].
+pub fn stack_trace(
+    arena: &DebugArena,
+    source_map: impl Fn(Address, bool) -> Option<SourceMap>,
+    sources: &BTreeMap<u32, String>,
+) -> Vec<StackFrame> {
+    let site = match arena.find_revert() {
+        Some(site) => site,
+        None => return Vec::new(),
+    };
+    let mut path = Vec::new();
+    arena.path_to(arena.entry, site.idx, &mut path);
+
+    let mut frames = Vec::new();
+    let mut parent: Option<usize> = None;
+    for idx in path {
+        let node = &arena.arena[idx];
+        // the steps of a call are split into nodes at every call it makes, the nodes are the
+        // children of the caller's node that ends with the call
+        let nodes = match parent {
+            Some(parent) => &arena.arena[parent].children[..=node.location],
+            None => std::slice::from_ref(&idx),
+        };
+        parent = Some(idx);
+        if node.steps.is_empty() {
+            continue
+        }
+        let steps = nodes.iter().flat_map(|idx| &arena.arena[*idx].steps).collect::<Vec<_>>();
+        let source_map = source_map(node.address, node.creation);
+        frames.extend(call_frames(node.address, &steps, source_map.as_ref(), sources));
+    }
+    frames
+}
+
+/// Returns the frames of the functions of a call that haven't returned by its last step
+fn call_frames(
+    address: Address,
+    steps: &[&DebugStep],
+    source_map: Option<&SourceMap>,
+    sources: &BTreeMap<u32, String>,
+) -> Vec<StackFrame> {
+    let unknown = StackFrame { address, contract: None, function: None, location: None };
+    let source_map = match source_map {
+        Some(source_map) => source_map,
+        None => return vec![unknown],
+    };
+
+    // the definition of every function that hasn't returned yet and the jump into it
+    let mut open: Vec<(&SourceElement, &SourceElement)> = Vec::new();
+    for (i, step) in steps.iter().enumerate() {
+        if step.op.0 != Opcode::JUMP {
+            continue
+        }
+        match source_map.get(step.ic) {
+            Some(call) if matches!(call.jump, Jump::In) => {
+                if let Some(definition) = steps.get(i + 1).and_then(|dest| source_map.get(dest.ic))
+                {
+                    open.push((definition, call));
+                }
+            }
+            Some(call) if matches!(call.jump, Jump::Out) => {
+                open.pop();
+            }
+            _ => {}
+        }
+    }
+
+    let last = steps.last().and_then(|step| source_map.get(step.ic));
+    let mut frames = Vec::new();
+    for (i, (definition, _)) in open.iter().enumerate() {
+        let function = match function_name(definition, sources) {
+            Some(function) => function,
+            None => continue,
+        };
+        // the jumps into compiler generated helpers are attributed to the calling function
+        let at = open.get(i + 1).map(|(_, call)| *call).or(last);
+        frames.push(StackFrame {
+            address,
+            contract: enclosing_definition(definition, sources, &["contract", "library"]),
+            function: Some(function),
+            location: at.and_then(|element| SourceLocation::new(element, sources)),
+        });
+    }
+    if frames.is_empty() {
+        // without jumps into named functions, the function is the one the last step is in
+        if let Some(last) = last {
+            return vec![StackFrame {
+                contract: enclosing_definition(last, sources, &["contract", "library"]),
+                function: enclosing_definition(last, sources, &["function"]),
+                location: SourceLocation::new(last, sources),
+                ..unknown
+            }]
+        }
+        frames.push(unknown);
+    }
+    frames
+}
+
+/// Returns the name of the last definition of one of the kinds, like `contract`, that starts
+/// before the source element
+fn enclosing_definition(
+    element: &SourceElement,
+    sources: &BTreeMap<u32, String>,
+    kinds: &[&str],
+) -> Option<String> {
+    let source = sources.get(&element.index?)?;
+    let before = source.get(..element.offset)?;
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_' || c == '$';
+    kinds
+        .iter()
+        .filter_map(|kind| {
+            before
+                .match_indices(kind)
+                .filter(|(i, _)| !before[..*i].ends_with(is_ident))
+                .filter_map(|(i, _)| {
+                    let rest = &source[i + kind.len()..];
+                    if !rest.starts_with(char::is_whitespace) {
+                        return None
+                    }
+                    let name =
+                        rest.trim_start().chars().take_while(|c| is_ident(*c)).collect::<String>();
+                    Some((i, name))
+                })
+                .last()
+        })
+        .max_by_key(|(i, _)| *i)
+        .map(|(_, name)| name)
+        .filter(|name| !name.is_empty())
+}
+
+/// Describes the error code of a `Panic(uint256)`
+fn panic_kind(code: U256) -> String {
+    let kind = match code.low_u64() {
+        _ if code > U256::from(u64::MAX) => "panic",
+        0x01 => "assertion failed",
+        0x11 => "arithmetic overflow or underflow",
+        0x12 => "division or modulo by zero",
+        0x21 => "conversion to an invalid enum value",
+        0x22 => "access to incorrectly encoded storage bytes",
+        0x31 => "`pop` on an empty array",
+        0x32 => "array index out of bounds",
+        0x41 => "out of memory",
+        0x51 => "call to an uninitialized function pointer",
+        _ => "panic",
+    };
+    format!("{} (panic code 0x{:02x})", kind, code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_shares_unchanged_pages() {
+        let mut memory = Memory::new(usize::MAX);
+        memory.set(0, &[1u8; MEMORY_PAGE_SIZE * 2], None).unwrap();
+        let first = DebugMemory::record(&memory, None, true);
+
+        memory.set(MEMORY_PAGE_SIZE, &[2u8], None).unwrap();
+        let second = DebugMemory::record(&memory, Some(&first), true);
+        assert!(Arc::ptr_eq(&first.pages[0], &second.pages[0]));
+        assert!(!Arc::ptr_eq(&first.pages[1], &second.pages[1]));
+        assert_eq!(second.len(), MEMORY_PAGE_SIZE * 2);
+        assert_eq!(second.data()[MEMORY_PAGE_SIZE], 2);
+        assert_eq!(second.data()[MEMORY_PAGE_SIZE - 1], 1);
+
+        // without a write, the pages are shared without comparing them
+        let third = DebugMemory::record(&memory, Some(&second), false);
+        assert!(third.pages.iter().zip(&second.pages).all(|(a, b)| Arc::ptr_eq(a, b)));
+    }
+
+    #[test]
+    fn limit_drops_oldest_steps() {
+        let mut arena = DebugArena::default();
+        arena.arena[0].steps = vec![DebugStep { pc: 0, ..Default::default() }; 3];
+        arena.push_node(0, DebugNode::new(Address::zero(), 1, vec![DebugStep::default(); 2]));
+        arena.limit_steps(3);
+        assert_eq!(arena.arena[0].steps.len(), 1);
+        assert_eq!(arena.arena[1].steps.len(), 2);
+        arena.limit_steps(1);
+        assert!(arena.arena[0].steps.is_empty());
+        assert_eq!(arena.arena[1].steps.len(), 1);
+
+        // the emptied node isn't flattened
+        let mut flattened = Vec::new();
+        arena.flatten(0, &mut flattened);
+        assert_eq!(flattened.len(), 1);
+        assert_eq!(flattened[0].1.len(), 1);
+    }
+
+    #[test]
+    fn decodes_internal_calls() {
+        let source = "function outer() { inner(); } function inner() {}".to_string();
+        let sources = BTreeMap::from([(0, source)]);
+        let element = |offset, length, jump| SourceElement {
+            offset,
+            length,
+            index: Some(0),
+            jump,
+            modifier_depth: 0,
+        };
+        // 0: jump into outer, 1: outer, 2: jump into inner, 3: inner, 4: return from inner,
+        // 5: return from outer, 6: back in the caller
+        let source_map = vec![
+            element(0, 0, Jump::In),
+            element(0, 29, Jump::Regular),
+            element(19, 7, Jump::In),
+            element(30, 20, Jump::Regular),
+            element(30, 20, Jump::Out),
+            element(0, 29, Jump::Out),
+            element(0, 0, Jump::Regular),
+        ];
+        let ops = [
+            Opcode::JUMP,
+            Opcode::JUMPDEST,
+            Opcode::JUMP,
+            Opcode::JUMPDEST,
+            Opcode::JUMP,
+            Opcode::JUMP,
+            Opcode::JUMPDEST,
+        ];
+        let steps = ops
+            .iter()
+            .enumerate()
+            .map(|(ic, op)| DebugStep {
+                ic,
+                op: OpCode::from(*op),
+                total_gas_used: 10 * ic as u64,
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+
+        let expected = vec![
+            InternalCall { name: "outer".to_string(), depth: 0, gas_used: 60 },
+            InternalCall { name: "inner".to_string(), depth: 1, gas_used: 30 },
+        ];
+        let calls = decode_internal_calls(&steps.iter().collect::<Vec<_>>(), &source_map, &sources);
+        assert_eq!(calls, expected);
+
+        // the steps are split into two nodes by a call to another contract within `inner`
+        let address = Address::repeat_byte(1);
+        let node = |steps: &[DebugStep], trace_idx| DebugNode {
+            trace_idx,
+            ..DebugNode::new(address, 1, steps.to_vec())
+        };
+        let mut arena = DebugArena::default();
+        arena.push_node(0, node(&steps[..4], 1));
+        let callee = DebugNode { trace_idx: 2, ..DebugNode::new(Address::zero(), 2, vec![]) };
+        arena.push_node(0, callee);
+        arena.push_node(0, node(&steps[4..], 1));
+        let calls = arena_internal_calls(
+            &arena,
+            |call, _| (call == address).then(|| source_map.clone()),
+            &sources,
+        );
+        assert_eq!(calls, vec![CallInternalCalls { address, creation: false, calls: expected }]);
+    }
+
+    #[test]
+    fn explains_reverts() {
+        let source = "contract A {\n    function f(uint a) {\n        a + 1;\n        require(a > 1);\n    }\n}"
+            .to_string();
+        let sources = BTreeMap::from([(0, source)]);
+        let element = |offset, length| SourceElement {
+            offset,
+            length,
+            index: Some(0),
+            jump: Jump::Regular,
+            modifier_depth: 0,
+        };
+        let source_map = vec![element(46, 5), element(61, 14)];
+
+        // Panic(0x11) at memory 0
+        let mut data = PANIC_SELECTOR.to_vec();
+        data.extend_from_slice(&[0u8; 31]);
+        data.push(0x11);
+        let mut memory = Memory::new(usize::MAX);
+        memory.set(0, &data, None).unwrap();
+        let panic = DebugStep {
+            ic: 0,
+            op: OpCode::from(Opcode::REVERT),
+            stack: vec![H256::zero(), H256::from_low_u64_be(data.len() as u64)],
+            memory: DebugMemory::record(&memory, None, true),
+            ..Default::default()
+        };
+        let mut arena = DebugArena::default();
+        arena.arena[0].steps = vec![panic.clone()];
+        let site = arena.find_revert().unwrap();
+        assert_eq!(site.data(), data);
+        assert_eq!(
+            explain_revert(&site, &source_map, &sources).unwrap(),
+            RevertExplanation {
+                kind: "arithmetic overflow or underflow (panic code 0x11)".to_string(),
+                expression: "a + 1".to_string(),
+                index: 0,
+                line: 3,
+                snippet: "a + 1;".to_string(),
+            }
+        );
+
+        // a revert that the caller bubbles up is explained in the call it originates from
+        let (caller, callee) = (Address::repeat_byte(1), Address::repeat_byte(2));
+        let call = DebugStep { op: OpCode::from(Opcode::CALL), ..Default::default() };
+        let require = DebugStep {
+            ic: 1,
+            op: OpCode::from(Opcode::REVERT),
+            stack: vec![H256::zero(), H256::zero()],
+            ..Default::default()
+        };
+        let bubble = DebugStep { ic: 0, ..require.clone() };
+        let mut arena = DebugArena::default();
+        arena.push_node(0, DebugNode::new(caller, 1, vec![call.clone()]));
+        arena.push_node(0, DebugNode::new(callee, 2, vec![require.clone()]));
+        arena.push_node(0, DebugNode::new(caller, 1, vec![bubble]));
+        let site = arena.find_revert().unwrap();
+        assert_eq!(site.address, callee);
+        let explanation =
+            explain_arena_revert(&arena, |_, _| Some(source_map.clone()), &sources).unwrap();
+        assert_eq!(explanation.kind, "`require` failed");
+        assert_eq!(explanation.expression, "require(a > 1)");
+        assert_eq!(explanation.line, 4);
+
+        // a revert that was caught isn't where the caller reverted
+        let mut arena = DebugArena::default();
+        arena.push_node(0, DebugNode::new(caller, 1, vec![call]));
+        arena.push_node(0, DebugNode::new(callee, 2, vec![require]));
+        arena.push_node(0, DebugNode::new(caller, 1, vec![panic]));
+        let site = arena.find_revert().unwrap();
+        assert_eq!(site.address, caller);
+        assert_eq!(site.data(), data);
+    }
+
+    #[test]
+    fn builds_stack_traces() {
+        let source = "library Lib {\n    function check(uint a) internal {\n        require(a > 1);\n    }\n}\ncontract Vault {\n    function withdraw(uint a) public {\n        Lib.check(a);\n    }\n}"
+            .to_string();
+        let offset = |text: &str| source.find(text).unwrap();
+        let element = |text: &str, jump| SourceElement {
+            offset: offset(text),
+            length: text.len(),
+            index: Some(0),
+            jump,
+            modifier_depth: 0,
+        };
+        // 0: jump into withdraw, 1: withdraw, 2: jump into check, 3: check, 4: revert in check
+        let source_map = vec![
+            element("contract Vault", Jump::In),
+            element(
+                "function withdraw(uint a) public {\n        Lib.check(a);\n    }",
+                Jump::Regular,
+            ),
+            element("Lib.check(a)", Jump::In),
+            element(
+                "function check(uint a) internal {\n        require(a > 1);\n    }",
+                Jump::Regular,
+            ),
+            element("require(a > 1)", Jump::Regular),
+        ];
+        let ops = [Opcode::JUMP, Opcode::JUMPDEST, Opcode::JUMP, Opcode::JUMPDEST, Opcode::REVERT];
+        let steps = ops
+            .iter()
+            .enumerate()
+            .map(|(ic, op)| DebugStep { ic, op: OpCode::from(*op), ..Default::default() })
+            .collect::<Vec<_>>();
+        let mut arena = DebugArena::default();
+        arena.push_node(0, DebugNode::new(Address::repeat_byte(1), 1, steps));
+        let sources = BTreeMap::from([(0, source.clone())]);
+
+        let frames = stack_trace(&arena, |_, _| Some(source_map.clone()), &sources);
+        let frame = |contract: &str, function: &str, line, snippet: &str| StackFrame {
+            address: Address::repeat_byte(1),
+            contract: Some(contract.to_string()),
+            function: Some(function.to_string()),
+            location: Some(SourceLocation { index: 0, line, snippet: snippet.to_string() }),
+        };
+        assert_eq!(
+            frames,
+            vec![
+                frame("Vault", "withdraw", 8, "Lib.check(a);"),
+                frame("Lib", "check", 3, "require(a > 1);"),
+            ]
+        );
+
+        // without a source map the frame is only known by its address
+        let frames = stack_trace(&arena, |_, _| None, &sources);
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].function, None);
+    }
+}
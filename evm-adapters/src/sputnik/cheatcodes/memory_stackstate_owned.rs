@@ -4,14 +4,24 @@ use sputnik::{
     ExitError, Transfer,
 };
 
-use crate::{call_tracing::CallTraceArena, sputnik::cheatcodes::debugger::DebugArena};
+use crate::{
+    call_tracing::CallTraceArena,
+    sputnik::{
+        cheatcodes::{debugger::DebugArena, BroadcastableTransaction},
+        journal::{Journal, JournalAccount},
+    },
+};
 
 use ethers::{
     abi::RawLog,
     types::{H160, H256, U256},
 };
 
-use std::{cell::RefCell, collections::BTreeMap, rc::Rc};
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet},
+    rc::Rc,
+};
 
 #[derive(Clone, Default)]
 pub struct RecordAccess {
@@ -19,6 +29,23 @@ pub struct RecordAccess {
     pub writes: RefCell<BTreeMap<H160, Vec<H256>>>,
 }
 
+/// Records every account and storage slot that is accessed during execution, used to generate
+/// an EIP-2930 access list
+#[derive(Clone, Default, Debug)]
+pub struct AccessListTracer {
+    pub accessed: RefCell<BTreeMap<H160, BTreeSet<H256>>>,
+}
+
+impl AccessListTracer {
+    fn account(&self, address: H160) {
+        self.accessed.borrow_mut().entry(address).or_default();
+    }
+
+    fn slot(&self, address: H160, key: H256) {
+        self.accessed.borrow_mut().entry(address).or_default().insert(key);
+    }
+}
+
 #[derive(Clone, Default, Debug)]
 pub struct ExpectedEmit {
     pub depth: usize,
@@ -40,6 +67,19 @@ pub struct Prank {
     pub depth: usize,
 }
 
+/// An active `broadcast` or `startBroadcast`
+#[derive(Clone, Default, Debug)]
+pub struct Broadcast {
+    /// Address of the contract that called broadcast
+    pub caller: H160,
+    /// Address the transactions are sent from
+    pub new_caller: H160,
+    /// Call depth at which broadcast was called
+    pub depth: usize,
+    /// Whether only the next call is broadcast
+    pub single_call: bool,
+}
+
 /// This struct implementation is copied from [upstream](https://github.com/rust-blockchain/evm/blob/5ecf36ce393380a89c6f1b09ef79f686fe043624/src/executor/stack/state.rs#L412) and modified to own the Backend type.
 ///
 /// We had to copy it so that we can modify the Stack's internal backend, because
@@ -48,6 +88,8 @@ pub struct Prank {
 #[derive(Clone)]
 pub struct MemoryStackStateOwned<'config, B> {
     pub backend: B,
+    /// Committed changes on top of the backend
+    pub journal: Journal,
     pub substate: MemoryStackSubstate<'config>,
     /// Tracing enabled
     pub trace_enabled: bool,
@@ -63,8 +105,14 @@ pub struct MemoryStackStateOwned<'config, B> {
     pub next_prank: Option<Prank>,
     /// StartPrank information
     pub prank: Option<Prank>,
+    /// Broadcast information
+    pub broadcast: Option<Broadcast>,
+    /// The calls and creations made while broadcasting
+    pub broadcastable_transactions: Vec<BroadcastableTransaction>,
     /// List of accesses done during a call
     pub accesses: Option<RecordAccess>,
+    /// Accounts and storage slots accessed since the access list recording was enabled
+    pub access_list: Option<AccessListTracer>,
     /// All logs accumulated (regardless of revert status)
     pub all_logs: Vec<String>,
     /// Expected events by end of the next call
@@ -75,13 +123,30 @@ pub struct MemoryStackStateOwned<'config, B> {
     pub debug_enabled: bool,
     /// An arena allocator of DebugNodes for debugging purposes
     pub debug_steps: Vec<DebugArena>,
+    /// The maximum number of steps recorded per debug arena, the oldest steps are dropped first
+    pub debug_step_limit: Option<usize>,
     /// Instruction pointers that maps an address to a mapping of pc to ic
     pub debug_instruction_pointers: Dip,
+    /// The program counters hit by the executed code, by the address of the code, if coverage
+    /// is recorded
+    pub coverage: Option<BTreeSet<(H160, usize)>>,
 }
 
 impl<'config, B: Backend> MemoryStackStateOwned<'config, B> {
     pub fn deposit(&mut self, address: H160, value: U256) {
-        self.substate.deposit(address, value, &self.backend);
+        self.substate.deposit(address, value, &self.journal.with_backend(&self.backend));
+    }
+
+    /// Commits all changes of the substate into a new layer of the journal, afterwards the
+    /// substate is empty so that cloning the state is cheap.
+    ///
+    /// Must only be called between transactions.
+    pub fn commit(&mut self) {
+        let metadata = self.substate.metadata().clone();
+        let substate = std::mem::replace(&mut self.substate, MemoryStackSubstate::new(metadata));
+        let (applies, _) = substate.deconstruct(&self.journal.with_backend(&self.backend));
+        let changes = applies.into_iter().map(JournalAccount::from_apply).collect::<Vec<_>>();
+        self.journal.commit(changes);
     }
 
     pub fn increment_call_index(&mut self) {
@@ -127,6 +192,7 @@ impl<'config, B: Backend> MemoryStackStateOwned<'config, B> {
     ) -> Self {
         Self {
             backend,
+            journal: Default::default(),
             substate: MemoryStackSubstate::new(metadata),
             trace_enabled,
             call_index: 0,
@@ -135,14 +201,19 @@ impl<'config, B: Backend> MemoryStackStateOwned<'config, B> {
             expected_revert: None,
             next_prank: None,
             prank: None,
+            broadcast: None,
+            broadcastable_transactions: Vec::new(),
             accesses: None,
+            access_list: None,
             all_logs: Default::default(),
             expected_emits: Default::default(),
             mocked_calls: Default::default(),
             expected_calls: Default::default(),
             debug_enabled,
             debug_steps: vec![Default::default()],
+            debug_step_limit: None,
             debug_instruction_pointers: (BTreeMap::new(), BTreeMap::new()),
+            coverage: None,
         }
     }
 }
@@ -180,24 +251,38 @@ impl<'config, B: Backend> Backend for MemoryStackStateOwned<'config, B> {
     }
 
     fn exists(&self, address: H160) -> bool {
-        self.substate.known_account(address).is_some() || self.backend.exists(address)
+        self.substate.known_account(address).is_some() ||
+            self.journal.with_backend(&self.backend).exists(address)
     }
 
     fn basic(&self, address: H160) -> Basic {
-        self.substate.known_basic(address).unwrap_or_else(|| self.backend.basic(address))
+        if let Some(access_list) = &self.access_list {
+            access_list.account(address);
+        }
+        self.substate
+            .known_basic(address)
+            .unwrap_or_else(|| self.journal.with_backend(&self.backend).basic(address))
     }
 
     fn code(&self, address: H160) -> Vec<u8> {
-        self.substate.known_code(address).unwrap_or_else(|| self.backend.code(address))
+        if let Some(access_list) = &self.access_list {
+            access_list.account(address);
+        }
+        self.substate
+            .known_code(address)
+            .unwrap_or_else(|| self.journal.with_backend(&self.backend).code(address))
     }
 
     fn storage(&self, address: H160, key: H256) -> H256 {
         if let Some(record_accesses) = &self.accesses {
             record_accesses.reads.borrow_mut().entry(address).or_insert_with(Vec::new).push(key);
         }
+        if let Some(access_list) = &self.access_list {
+            access_list.slot(address, key);
+        }
         self.substate
             .known_storage(address, key)
-            .unwrap_or_else(|| self.backend.storage(address, key))
+            .unwrap_or_else(|| self.journal.with_backend(&self.backend).storage(address, key))
     }
 
     fn original_storage(&self, address: H160, key: H256) -> Option<H256> {
@@ -205,7 +290,7 @@ impl<'config, B: Backend> Backend for MemoryStackStateOwned<'config, B> {
             return Some(value)
         }
 
-        self.backend.original_storage(address, key)
+        self.journal.with_backend(&self.backend).original_storage(address, key)
     }
 }
 
@@ -239,9 +324,10 @@ impl<'config, B: Backend> StackState<'config> for MemoryStackStateOwned<'config,
             return known_empty
         }
 
-        self.backend.basic(address).balance == U256::zero() &&
-            self.backend.basic(address).nonce == U256::zero() &&
-            self.backend.code(address).len() == 0
+        let backend = self.journal.with_backend(&self.backend);
+        backend.basic(address).balance == U256::zero() &&
+            backend.basic(address).nonce == U256::zero() &&
+            backend.code(address).len() == 0
     }
 
     fn deleted(&self, address: H160) -> bool {
@@ -249,6 +335,9 @@ impl<'config, B: Backend> StackState<'config> for MemoryStackStateOwned<'config,
     }
 
     fn is_cold(&self, address: H160) -> bool {
+        if let Some(access_list) = &self.access_list {
+            access_list.account(address);
+        }
         self.substate.is_cold(address)
     }
 
@@ -257,18 +346,21 @@ impl<'config, B: Backend> StackState<'config> for MemoryStackStateOwned<'config,
     }
 
     fn inc_nonce(&mut self, address: H160) {
-        self.substate.inc_nonce(address, &self.backend);
+        self.substate.inc_nonce(address, &self.journal.with_backend(&self.backend));
     }
 
     fn set_storage(&mut self, address: H160, key: H256, value: H256) {
         if let Some(record_accesses) = &self.accesses {
             record_accesses.writes.borrow_mut().entry(address).or_insert_with(Vec::new).push(key);
         }
+        if let Some(access_list) = &self.access_list {
+            access_list.slot(address, key);
+        }
         self.substate.set_storage(address, key, value)
     }
 
     fn reset_storage(&mut self, address: H160) {
-        self.substate.reset_storage(address, &self.backend);
+        self.substate.reset_storage(address, &self.journal.with_backend(&self.backend));
     }
 
     fn log(&mut self, address: H160, topics: Vec<H256>, data: Vec<u8>) {
@@ -280,18 +372,18 @@ impl<'config, B: Backend> StackState<'config> for MemoryStackStateOwned<'config,
     }
 
     fn set_code(&mut self, address: H160, code: Vec<u8>) {
-        self.substate.set_code(address, code, &self.backend)
+        self.substate.set_code(address, code, &self.journal.with_backend(&self.backend))
     }
 
     fn transfer(&mut self, transfer: Transfer) -> Result<(), ExitError> {
-        self.substate.transfer(transfer, &self.backend)
+        self.substate.transfer(transfer, &self.journal.with_backend(&self.backend))
     }
 
     fn reset_balance(&mut self, address: H160) {
-        self.substate.reset_balance(address, &self.backend)
+        self.substate.reset_balance(address, &self.journal.with_backend(&self.backend))
     }
 
     fn touch(&mut self, address: H160) {
-        self.substate.touch(address, &self.backend)
+        self.substate.touch(address, &self.journal.with_backend(&self.backend))
     }
 }
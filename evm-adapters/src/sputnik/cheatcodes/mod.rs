@@ -11,8 +11,11 @@ pub mod backend;
 
 pub mod debugger;
 
-use ethers::types::{Address, Selector, H256, U256};
+pub mod assertions;
+
+use ethers::types::{Address, Bytes, Selector, H256, U256};
 use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
 use sputnik::backend::{Backend, MemoryAccount, MemoryBackend};
 
 #[derive(Clone, Debug, Default)]
@@ -29,6 +32,28 @@ pub struct Cheatcodes {
     pub accounts: HashMap<Address, MemoryAccount>,
     /// The overriden tx.origin
     pub origin: Option<Address>,
+    /// The overridden chain id
+    pub chain_id: Option<U256>,
+}
+
+/// A call or creation of a script's `broadcast`, recorded during the simulation to be sent as a
+/// transaction to the chain it was made on
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BroadcastableTransaction {
+    /// The chain id at the time of the call, see the `chainId` cheatcode
+    pub chain_id: u64,
+    pub from: Address,
+    /// `None` for contract creations
+    pub to: Option<Address>,
+    pub value: U256,
+    /// The gas the transaction used in the simulation, including the intrinsic gas
+    #[serde(default)]
+    pub gas: U256,
+    pub data: Bytes,
+    /// The nonce of the sender in the simulation, the address of a creation depends on it
+    #[serde(default)]
+    pub simulated_nonce: U256,
 }
 
 /// Extension trait over [`Backend`] which provides additional methods for interacting with the
@@ -72,6 +97,24 @@ ethers::contract::abigen!(
             clearMockedCalls()
             expectCall(address,bytes)
             getCode(string)
+            deployCode(string)(address)
+            rpcUrl(string)(string)
+            getDeployment(string)(address)
+            hashTypedData(string)(bytes32)
+            chainId(uint256)
+            broadcast()
+            broadcast(address)
+            startBroadcast()
+            startBroadcast(address)
+            stopBroadcast()
+            assertEq(uint256,uint256)
+            assertEq(int256,int256)
+            assertEq(address,address)
+            assertEq(bytes32,bytes32)
+            assertEq(bytes,bytes)
+            assertEq(uint256[],uint256[])
+            assertEq(int256[],int256[])
+            assertEq(address[],address[])
     ]"#,
 );
 pub use hevm_mod::{HEVMCalls, HEVM_ABI};
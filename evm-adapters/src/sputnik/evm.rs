@@ -10,11 +10,14 @@ use sputnik::{
     },
     Config, CreateScheme, ExitReason, ExitRevert, Transfer,
 };
-use std::{collections::BTreeMap, marker::PhantomData};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    marker::PhantomData,
+};
 
 use eyre::Result;
 
-use super::SputnikExecutor;
+use super::{take_invalid_state, SputnikExecutor};
 
 pub type MemoryState = BTreeMap<Address, MemoryAccount>;
 
@@ -99,6 +102,18 @@ where
         self.executor.debug_calls()
     }
 
+    fn debug_enabled(&self) -> bool {
+        self.executor.debug_enabled()
+    }
+
+    fn set_coverage_enabled(&mut self, enabled: bool) -> bool {
+        self.executor.set_coverage_enabled(enabled)
+    }
+
+    fn coverage(&self) -> BTreeSet<(Address, usize)> {
+        self.executor.coverage()
+    }
+
     /// given an iterator of contract address to contract bytecode, initializes
     /// the state with the contract deployed at the specified address
     fn initialize_contracts<T: IntoIterator<Item = (Address, Bytes)>>(&mut self, contracts: T) {
@@ -148,6 +163,8 @@ where
         // The account's created contract address is pre-computed by using the account's nonce
         // before it executes the contract deployment transaction.
         let address = self.executor.create_address(CreateScheme::Legacy { caller: from });
+        // drop the error of state accessed outside of a transaction
+        take_invalid_state();
         let status =
             self.executor.transact_create(from, value, calldata.to_vec(), self.gas_limit, vec![]);
 
@@ -164,6 +181,10 @@ where
             .saturating_sub(refunded_gas)
             .saturating_sub(21000.into());
 
+        // the fork state the deployment ran with can't be trusted
+        if let Some(err) = take_invalid_state() {
+            eyre::bail!("deployment failed: {}", err)
+        }
         if Self::is_fail(&status) {
             tracing::trace!(?status, "failed");
             Err(eyre::eyre!("deployment reverted, reason: {:?}", status))
@@ -185,6 +206,8 @@ where
         let gas_used_before = self.executor.gas_used();
         let refunded_gas_before = self.executor.gas_refund();
 
+        // drop the error of state accessed outside of a transaction
+        take_invalid_state();
         let (status, retdata) =
             self.executor.transact_call(from, to, value, calldata.to_vec(), self.gas_limit, vec![]);
 
@@ -204,6 +227,10 @@ where
         // clear them
         self.executor.clear_logs();
 
+        // the fork state the call ran with can't be trusted
+        if let Some(err) = take_invalid_state() {
+            eyre::bail!("{}", err)
+        }
         Ok((retdata.into(), status, gas.as_u64(), logs))
     }
 }
@@ -362,7 +389,9 @@ mod tests {
     use super::*;
     use crate::{
         sputnik::helpers::vm,
-        test_helpers::{can_call_vm_directly, solidity_unit_test, COMPILED},
+        test_helpers::{
+            can_call_vm_directly, can_snapshot_and_revert, solidity_unit_test, COMPILED,
+        },
     };
     use ethers::utils::id;
     use sputnik::{ExitReason, ExitRevert, ExitSucceed};
@@ -374,6 +403,13 @@ mod tests {
         can_call_vm_directly(evm, compiled);
     }
 
+    #[test]
+    fn sputnik_can_snapshot_and_revert() {
+        let evm = vm();
+        let compiled = COMPILED.find("Greeter").expect("could not find contract");
+        can_snapshot_and_revert(evm, compiled);
+    }
+
     #[test]
     fn sputnik_solidity_unit_test() {
         let evm = vm();
@@ -13,6 +13,7 @@ use futures::{
 };
 use parking_lot::RwLock;
 use std::{
+    cell::RefCell,
     collections::{hash_map::Entry, BTreeMap, HashMap},
     pin::Pin,
     sync::{
@@ -21,7 +22,11 @@ use std::{
     },
 };
 
-use crate::blocking_provider::RuntimeOrHandle;
+use super::{
+    proof::{verify_account, verify_account_proof, verify_storage_proof},
+    DiskCache, StateOverride,
+};
+use crate::{blocking_provider::RuntimeOrHandle, rpc_metrics::RPC_METRICS};
 
 /// A basic in memory cache (address -> Account)
 pub type MemCache = BTreeMap<H160, MemoryAccount>;
@@ -43,6 +48,20 @@ pub fn new_shared_cache<T>(cache: T) -> SharedCache<T> {
     Arc::new(RwLock::new(cache))
 }
 
+thread_local! {
+    /// The error of the first invalid state a `SharedBackend` accessed on this thread
+    static INVALID_STATE: RefCell<Option<String>> = RefCell::new(None);
+}
+
+/// Returns and clears the error of the first state that failed its proof verification or that's
+/// missing offline, if a [`SharedBackend`] accessed it on this thread.
+///
+/// The [`Backend`] accessors can't fail, so the executor checks this after every transaction and
+/// fails it instead.
+pub fn take_invalid_state() -> Option<String> {
+    INVALID_STATE.with(|invalid_state| invalid_state.borrow_mut().take())
+}
+
 type AccountFuture<Err> =
     Pin<Box<dyn Future<Output = (Result<(U256, U256, Bytes), Err>, Address)> + Send>>;
 type StorageFuture<Err> = Pin<Box<dyn Future<Output = (Result<H256, Err>, Address, H256)> + Send>>;
@@ -92,6 +111,12 @@ struct BackendHandler<M: Middleware> {
     /// The block to fetch data from.
     // This is an `Option` so that we can have less code churn in the functions below
     block_id: Option<BlockId>,
+    /// If set, all fetched state is verified via `eth_getProof` against this state root
+    state_root: Option<H256>,
+    /// The error of the first failed state verification
+    invalid_state: InvalidState,
+    /// If set, only cached state is served and any state that would be fetched is an error
+    offline: bool,
 }
 
 impl<M> BackendHandler<M>
@@ -103,6 +128,9 @@ where
         cache: SharedCache<MemCache>,
         rx: Receiver<BackendRequest>,
         block_id: Option<BlockId>,
+        state_root: Option<H256>,
+        invalid_state: InvalidState,
+        offline: bool,
     ) -> Self {
         Self {
             provider,
@@ -112,6 +140,9 @@ where
             storage_requests: Default::default(),
             incoming: rx.fuse(),
             block_id,
+            state_root,
+            invalid_state,
+            offline,
         }
     }
 
@@ -131,6 +162,7 @@ where
                 drop(lock);
                 if let Some(basic) = basic {
                     let _ = sender.send(basic);
+                    RPC_METRICS.record_cache_hit();
                 } else {
                     self.request_account(addr, AccountListener::Basic(sender));
                 }
@@ -142,6 +174,7 @@ where
                 drop(lock);
                 if let Some(basic) = code {
                     let _ = sender.send(basic);
+                    RPC_METRICS.record_cache_hit();
                 } else {
                     self.request_account(addr, AccountListener::Code(sender));
                 }
@@ -160,6 +193,7 @@ where
 
                 if has_account {
                     let _ = sender.send(exists);
+                    RPC_METRICS.record_cache_hit();
                 } else {
                     self.request_account(addr, AccountListener::Exists(sender));
                 }
@@ -176,6 +210,7 @@ where
                     // account is already stored in the cache
                     if let Some(value) = value {
                         let _ = sender.send(value);
+                        RPC_METRICS.record_cache_hit();
                     } else {
                         // account present but not storage -> fetch storage
                         self.request_account_storage(addr, idx, sender);
@@ -187,6 +222,7 @@ where
                         self.account_requests.get(&addr).and_then(|(_, s)| s.get(&idx).copied())
                     {
                         let _ = sender.send(value);
+                        RPC_METRICS.record_cache_hit();
                     } else {
                         // fetch storage via provider
                         self.request_account_storage(addr, idx, sender);
@@ -206,13 +242,41 @@ where
         match self.storage_requests.entry((address, idx)) {
             Entry::Occupied(mut entry) => {
                 entry.get_mut().push(listener);
+                RPC_METRICS.record_coalesced();
             }
+            Entry::Vacant(_) if self.offline => self.invalid_state.set(format!(
+                "storage {:?} of {:?} is not in the fork cache and can't be fetched offline",
+                idx, address
+            )),
             Entry::Vacant(entry) => {
                 entry.insert(vec![listener]);
                 let provider = self.provider.clone();
                 let block_id = self.block_id;
+                let verify = self.state_root.map(|root| (root, self.invalid_state.clone()));
                 let fut = Box::pin(async move {
                     let storage = provider.get_storage_at(address, idx, block_id).await;
+                    if let (Ok(value), Some((root, invalid_state))) = (&storage, verify) {
+                        let res = async {
+                            let proof = provider
+                                .get_proof(address, vec![idx], block_id)
+                                .await
+                                .map_err(|err| eyre::eyre!("{}", err))?;
+                            let storage_proof =
+                                proof
+                                    .storage_proof
+                                    .iter()
+                                    .find(|p| p.key == idx)
+                                    .ok_or_else(|| eyre::eyre!("missing storage proof"))?;
+                            verify_account_proof(root, address, &proof)?;
+                            verify_storage_proof(proof.storage_hash, storage_proof, *value)
+                        };
+                        if let Err(err) = res.await {
+                            invalid_state.set(format!(
+                                "fork state verification failed for storage {:?} of {:?}: {}",
+                                idx, address, err
+                            ));
+                        }
+                    }
                     (storage, address, idx)
                 });
                 self.pending_requests.push(ProviderRequest::Storage(fut));
@@ -224,11 +288,27 @@ where
     fn get_account_req(&self, address: Address) -> ProviderRequest<M::Error> {
         let provider = self.provider.clone();
         let block_id = self.block_id;
+        let verify = self.state_root.map(|root| (root, self.invalid_state.clone()));
         let fut = Box::pin(async move {
             let balance = provider.get_balance(address, block_id);
             let nonce = provider.get_transaction_count(address, block_id);
             let code = provider.get_code(address, block_id);
             let resp = tokio::try_join!(balance, nonce, code);
+            if let (Ok((balance, nonce, code)), Some((root, invalid_state))) = (&resp, verify) {
+                let res = async {
+                    let proof = provider
+                        .get_proof(address, vec![], block_id)
+                        .await
+                        .map_err(|err| eyre::eyre!("{}", err))?;
+                    verify_account(root, address, &proof, *nonce, *balance, code)
+                };
+                if let Err(err) = res.await {
+                    invalid_state.set(format!(
+                        "fork state verification failed for account {:?}: {}",
+                        address, err
+                    ));
+                }
+            }
             (resp, address)
         });
         ProviderRequest::Account(fut)
@@ -239,7 +319,12 @@ where
         match self.account_requests.entry(address) {
             Entry::Occupied(mut entry) => {
                 entry.get_mut().0.push(listener);
+                RPC_METRICS.record_coalesced();
             }
+            Entry::Vacant(_) if self.offline => self.invalid_state.set(format!(
+                "account {:?} is not in the fork cache and can't be fetched offline",
+                address
+            )),
             Entry::Vacant(entry) => {
                 entry.insert((vec![listener], Default::default()));
                 self.pending_requests.push(self.get_account_req(address));
@@ -257,6 +342,11 @@ where
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         let pin = self.get_mut();
 
+        // stop serving state that can't be trusted, this drops all listeners
+        if pin.invalid_state.is_set() {
+            return Poll::Ready(())
+        }
+
         // receive new requests to delegate to the underlying provider
         while let Poll::Ready(Some(req)) = Pin::new(&mut pin.incoming).poll_next(cx) {
             pin.on_request(req)
@@ -268,6 +358,9 @@ where
             match &mut request {
                 ProviderRequest::Account(fut) => {
                     if let Poll::Ready((resp, addr)) = fut.poll_unpin(cx) {
+                        if pin.invalid_state.is_set() {
+                            return Poll::Ready(())
+                        }
                         let (balance, nonce, code) = resp.unwrap_or_else(|_| {
                             tracing::trace!("Failed to get account for {}", addr);
                             Default::default()
@@ -298,6 +391,9 @@ where
                 }
                 ProviderRequest::Storage(fut) => {
                     if let Poll::Ready((resp, addr, idx)) = fut.poll_unpin(cx) {
+                        if pin.invalid_state.is_set() {
+                            return Poll::Ready(())
+                        }
                         let value = resp.unwrap_or_else(|_| {
                             tracing::trace!("Failed to get storage for {} at {}", addr, idx);
                             Default::default()
@@ -379,11 +475,71 @@ impl SharedBackend {
         vicinity: MemoryVicinity,
         pin_block: Option<BlockId>,
     ) -> Self
+    where
+        M: Middleware + Unpin + 'static + Clone,
+    {
+        Self::spawn(provider, cache, vicinity, pin_block, None, false)
+    }
+
+    /// Same as [`SharedBackend::new()`] but all state that is fetched from remote is verified via
+    /// `eth_getProof` against the `state_root` of the `pin_block`.
+    ///
+    /// If a proof is invalid, the backend stops serving state and every transaction that accesses
+    /// state fails with the error, so that no execution can silently continue with state that
+    /// can't be trusted, see [`take_invalid_state`].
+    pub fn new_verified<M>(
+        provider: M,
+        cache: SharedCache<MemCache>,
+        vicinity: MemoryVicinity,
+        pin_block: BlockId,
+        state_root: H256,
+    ) -> Self
+    where
+        M: Middleware + Unpin + 'static + Clone,
+    {
+        Self::spawn(provider, cache, vicinity, Some(pin_block), Some(state_root), false)
+    }
+
+    /// Same as [`SharedBackend::new()`] but the `provider` is never used, only the state in the
+    /// `cache` is served.
+    ///
+    /// If state is accessed that isn't cached, the backend stops serving state and every
+    /// transaction that accesses state fails with the error, so that no execution can silently
+    /// continue with state that's missing, see [`take_invalid_state`].
+    pub fn new_offline<M>(
+        provider: M,
+        cache: SharedCache<MemCache>,
+        vicinity: MemoryVicinity,
+        pin_block: Option<BlockId>,
+    ) -> Self
+    where
+        M: Middleware + Unpin + 'static + Clone,
+    {
+        Self::spawn(provider, cache, vicinity, pin_block, None, true)
+    }
+
+    fn spawn<M>(
+        provider: M,
+        cache: SharedCache<MemCache>,
+        vicinity: MemoryVicinity,
+        pin_block: Option<BlockId>,
+        state_root: Option<H256>,
+        offline: bool,
+    ) -> Self
     where
         M: Middleware + Unpin + 'static + Clone,
     {
         let (tx, rx) = channel(1);
-        let handler = BackendHandler::new(provider, cache, rx, pin_block);
+        let invalid_state = InvalidState::default();
+        let handler = BackendHandler::new(
+            provider,
+            cache,
+            rx,
+            pin_block,
+            state_root,
+            invalid_state.clone(),
+            offline,
+        );
         // spawn the provider handler to background
         let rt = RuntimeOrHandle::new();
         std::thread::spawn(move || match rt {
@@ -391,7 +547,93 @@ impl SharedBackend {
             RuntimeOrHandle::Handle(handle) => handle.block_on(handler),
         });
 
-        Self { inner: SharedBackendInner { vicinity: Arc::new(vicinity), backend: tx } }
+        Self {
+            inner: SharedBackendInner {
+                vicinity: Arc::new(vicinity),
+                backend: tx,
+                disk_cache: None,
+                overrides: Default::default(),
+                invalid_state,
+            },
+        }
+    }
+
+    /// Persists the cache of this backend on disk once the last `SharedBackend` is dropped
+    #[must_use]
+    pub fn with_disk_cache(mut self, disk_cache: DiskCache) -> Self {
+        self.inner.disk_cache = Some(Arc::new(disk_cache));
+        self
+    }
+
+    /// Applies the `overrides` on top of the forked state.
+    ///
+    /// The overrides are never written to the cache, so they're not persisted on disk either.
+    #[must_use]
+    pub fn with_state_overrides(mut self, overrides: StateOverride) -> Self {
+        self.inner.overrides = Arc::new(overrides);
+        self
+    }
+
+    /// Fetches all `accounts` concurrently and waits until they're cached, so that the EVM does not
+    /// need to fetch them one by one once they're accessed.
+    pub fn prefetch_accounts(
+        &self,
+        accounts: impl IntoIterator<Item = Address>,
+    ) -> eyre::Result<()> {
+        self.prefetch_accounts_with_progress(accounts, |_, _| {})
+    }
+
+    /// Prefetches the `accounts` like [`Self::prefetch_accounts`], calling `progress` with the
+    /// number of fetched and all accounts after every fetched account
+    pub fn prefetch_accounts_with_progress(
+        &self,
+        accounts: impl IntoIterator<Item = Address>,
+        progress: impl Fn(usize, usize),
+    ) -> eyre::Result<()> {
+        let receivers = accounts
+            .into_iter()
+            .map(|address| {
+                let (sender, rx) = oneshot_channel();
+                let req = BackendRequest::Basic(address, sender);
+                self.inner.backend.clone().try_send(req).map_err(|e| eyre::eyre!("{:?}", e))?;
+                Ok(rx)
+            })
+            .collect::<eyre::Result<Vec<_>>>()?;
+        for (idx, rx) in receivers.iter().enumerate() {
+            rx.recv()?;
+            progress(idx + 1, receivers.len());
+        }
+        Ok(())
+    }
+
+    /// Fetches all storage `slots` concurrently and waits until they're cached
+    pub fn prefetch_storage(
+        &self,
+        slots: impl IntoIterator<Item = (Address, H256)>,
+    ) -> eyre::Result<()> {
+        let receivers = slots
+            .into_iter()
+            .map(|(address, index)| {
+                let (sender, rx) = oneshot_channel();
+                let req = BackendRequest::Storage(address, index, sender);
+                self.inner.backend.clone().try_send(req).map_err(|e| eyre::eyre!("{:?}", e))?;
+                Ok(rx)
+            })
+            .collect::<eyre::Result<Vec<_>>>()?;
+        for rx in receivers {
+            rx.recv()?;
+        }
+        Ok(())
+    }
+
+    /// Records the error of fetched state that failed its proof verification or state that's
+    /// missing offline, so the executor fails the current transaction
+    fn record_invalid_state(&self) {
+        if let Some(err) = self.inner.invalid_state.get() {
+            INVALID_STATE.with(|invalid_state| {
+                invalid_state.borrow_mut().get_or_insert(err);
+            })
+        }
     }
 
     fn do_get_exists(&self, address: H160) -> eyre::Result<bool> {
@@ -465,28 +707,45 @@ impl Backend for SharedBackend {
     }
 
     fn exists(&self, address: H160) -> bool {
+        if self.inner.overrides.contains_key(&address) {
+            return true
+        }
         self.do_get_exists(address).unwrap_or_else(|_| {
+            self.record_invalid_state();
             tracing::trace!("Failed to send/recv `exists` for {}", address);
             Default::default()
         })
     }
 
     fn basic(&self, address: H160) -> Basic {
-        self.do_get_basic(address).unwrap_or_else(|_| {
+        let basic = self.do_get_basic(address).unwrap_or_else(|_| {
+            self.record_invalid_state();
             tracing::trace!("Failed to send/recv `basic` for {}", address);
             Default::default()
-        })
+        });
+        match self.inner.overrides.get(&address) {
+            Some(acc) => acc.apply_basic(basic),
+            None => basic,
+        }
     }
 
     fn code(&self, address: H160) -> Vec<u8> {
+        if let Some(code) = self.inner.overrides.get(&address).and_then(|acc| acc.code.as_ref()) {
+            return code.to_vec()
+        }
         self.do_get_code(address).unwrap_or_else(|_| {
+            self.record_invalid_state();
             tracing::trace!("Failed to send/recv `code` for {}", address);
             Default::default()
         })
     }
 
     fn storage(&self, address: H160, index: TxHash) -> TxHash {
+        if let Some(value) = self.inner.overrides.get(&address).and_then(|acc| acc.storage(index)) {
+            return value
+        }
         self.do_get_storage(address, index).unwrap_or_else(|_| {
+            self.record_invalid_state();
             tracing::trace!("Failed to send/recv `storage` for {} at {}", address, index);
             Default::default()
         })
@@ -501,6 +760,33 @@ impl Backend for SharedBackend {
 struct SharedBackendInner {
     vicinity: Arc<MemoryVicinity>,
     backend: Sender<BackendRequest>,
+    /// Writes the cache to disk when the last clone is dropped
+    disk_cache: Option<Arc<DiskCache>>,
+    /// State overrides that take precedence over the cache
+    overrides: Arc<StateOverride>,
+    /// Set by the handler if fetched state failed its proof verification or state is missing
+    /// offline
+    invalid_state: InvalidState,
+}
+
+/// Holds the error of the first fetched state that failed its proof verification, or of the first
+/// state that's missing in the cache of an offline backend
+#[derive(Debug, Clone, Default)]
+struct InvalidState(Arc<RwLock<Option<String>>>);
+
+impl InvalidState {
+    fn set(&self, err: String) {
+        tracing::error!("{}", err);
+        self.0.write().get_or_insert(err);
+    }
+
+    fn get(&self) -> Option<String> {
+        self.0.read().clone()
+    }
+
+    fn is_set(&self) -> bool {
+        self.0.read().is_some()
+    }
 }
 
 #[cfg(test)]
@@ -552,4 +838,78 @@ mod tests {
         let mem_acc = cache.read().get(&address).unwrap().clone();
         assert_eq!(mem_acc.storage.len() as u64, max_slots);
     }
+
+    #[test]
+    fn can_prefetch() {
+        let provider = Provider::<Http>::try_from(
+            "https://mainnet.infura.io/v3/c60b0bb42f8a4c6481ecd229eddaca27",
+        )
+        .unwrap();
+        let rng: Address = "63091244180ae240c87d1f528f5f269134cb07b3".parse().unwrap();
+        let weth: Address = "c02aaa39b223fe8d0a0e5c4f27ead9083c756cc2".parse().unwrap();
+
+        let rt = Runtime::new().unwrap();
+        let vicinity = rt.block_on(vicinity(&provider, None, None, None)).unwrap();
+        let cache = new_shared_cache(MemCache::default());
+        let backend = SharedBackend::new(Arc::new(provider), cache.clone(), vicinity, None);
+
+        backend.prefetch_accounts([rng, weth]).unwrap();
+        assert!(cache.read().contains_key(&rng));
+        assert!(!cache.read()[&weth].code.is_empty());
+
+        let slots = (0..5u64).map(|i| (weth, H256::from_low_u64_be(i))).collect::<Vec<_>>();
+        backend.prefetch_storage(slots).unwrap();
+        assert_eq!(cache.read()[&weth].storage.len(), 5);
+    }
+
+    #[test]
+    fn can_verify_fork_state() {
+        let provider = Provider::<Http>::try_from(
+            "https://mainnet.infura.io/v3/c60b0bb42f8a4c6481ecd229eddaca27",
+        )
+        .unwrap();
+        let weth: Address = "c02aaa39b223fe8d0a0e5c4f27ead9083c756cc2".parse().unwrap();
+
+        let rt = Runtime::new().unwrap();
+        let vicinity = rt.block_on(vicinity(&provider, None, None, None)).unwrap();
+        let block =
+            rt.block_on(provider.get_block(vicinity.block_number.as_u64())).unwrap().unwrap();
+        let cache = new_shared_cache(MemCache::default());
+        let backend = SharedBackend::new_verified(
+            Arc::new(provider),
+            cache,
+            vicinity,
+            block.number.unwrap().as_u64().into(),
+            block.state_root,
+        );
+
+        assert!(!backend.code(weth).is_empty());
+        backend.storage(weth, H256::from_low_u64_be(0));
+        assert!(backend.inner.invalid_state.get().is_none());
+    }
+
+    #[test]
+    fn offline_backend_only_serves_cached_state() {
+        // never connected to
+        let provider = Provider::<Http>::try_from("http://localhost:1").unwrap();
+        let cached = Address::from_low_u64_be(1);
+        let missing = Address::from_low_u64_be(2);
+        let mut acc = MemoryAccount { nonce: 3.into(), code: vec![1], ..Default::default() };
+        acc.storage.insert(H256::from_low_u64_be(1), H256::from_low_u64_be(2));
+        let cache = new_shared_cache(MemCache::from([(cached, acc)]));
+
+        let vicinity = crate::evm_opts::Env::default().sputnik_state();
+        let backend = SharedBackend::new_offline(Arc::new(provider), cache, vicinity, None);
+        assert_eq!(backend.basic(cached).nonce, 3.into());
+        assert_eq!(backend.storage(cached, H256::from_low_u64_be(1)), H256::from_low_u64_be(2));
+
+        assert!(take_invalid_state().is_none());
+
+        assert_eq!(backend.basic(missing).nonce, 0.into());
+        let err = take_invalid_state().unwrap();
+        assert!(err.contains("is not in the fork cache"), "{}", err);
+        // the backend doesn't serve cached state either anymore
+        assert_eq!(backend.basic(cached).nonce, 0.into());
+        assert!(take_invalid_state().is_some());
+    }
 }
@@ -0,0 +1,245 @@
+//! Persists the state fetched by a forked backend on disk, so that it can be reused across runs
+use super::cache::{MemCache, SharedCache};
+use ethers::types::{Address, Bytes, H160, H256, U256};
+use serde::{Deserialize, Serialize};
+use sputnik::backend::{MemoryAccount, MemoryVicinity};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+};
+
+/// Returns the directory where all fork state is cached: `~/.foundry/cache`
+pub fn foundry_cache_dir() -> Option<PathBuf> {
+    dirs_next::home_dir().map(|p| p.join(".foundry").join("cache"))
+}
+
+/// Returns the file that holds the cached state of the block `block` of the chain `chain_id`:
+/// `~/.foundry/cache/<chain_id>/<block>.json`
+pub fn block_cache_file(chain_id: u64, block: u64) -> Option<PathBuf> {
+    foundry_cache_dir().map(|p| p.join(chain_id.to_string()).join(format!("{}.json", block)))
+}
+
+/// Returns the file that holds the environment of the block `block` of the chain `chain_id`:
+/// `~/.foundry/cache/<chain_id>/<block>.env.json`
+pub fn block_env_file(chain_id: u64, block: u64) -> Option<PathBuf> {
+    foundry_cache_dir().map(|p| p.join(chain_id.to_string()).join(format!("{}.env.json", block)))
+}
+
+/// Returns the ids of all chains whose state of the block `block` is cached
+pub fn cached_chain_ids(block: u64) -> Vec<u64> {
+    let dir = match foundry_cache_dir().and_then(|dir| std::fs::read_dir(dir).ok()) {
+        Some(dir) => dir,
+        None => return Vec::new(),
+    };
+    let mut chain_ids = dir
+        .filter_map(Result::ok)
+        .filter_map(|entry| entry.file_name().to_str()?.parse::<u64>().ok())
+        .filter(|chain_id| block_cache_file(*chain_id, block).map_or(false, |p| p.is_file()))
+        .collect::<Vec<_>>();
+    chain_ids.sort_unstable();
+    chain_ids
+}
+
+/// The serializable version of the block environment of a `MemoryVicinity`, the origin is not
+/// part of the block
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct CachedBlockEnv {
+    chain_id: U256,
+    block_number: U256,
+    block_coinbase: H160,
+    block_timestamp: U256,
+    block_difficulty: U256,
+    block_gas_limit: U256,
+    block_base_fee_per_gas: U256,
+    gas_price: U256,
+}
+
+/// Reads the cached block environment from `path`, executed by `origin`
+pub fn read_block_env(path: impl AsRef<Path>, origin: H160) -> eyre::Result<MemoryVicinity> {
+    let env: CachedBlockEnv = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+    Ok(MemoryVicinity {
+        gas_price: env.gas_price,
+        origin,
+        chain_id: env.chain_id,
+        block_hashes: Vec::new(),
+        block_number: env.block_number,
+        block_coinbase: env.block_coinbase,
+        block_timestamp: env.block_timestamp,
+        block_difficulty: env.block_difficulty,
+        block_gas_limit: env.block_gas_limit,
+        block_base_fee_per_gas: env.block_base_fee_per_gas,
+    })
+}
+
+/// Writes the block environment of the `vicinity` to `path`, creating all parent directories if
+/// necessary
+pub fn write_block_env(path: impl AsRef<Path>, vicinity: &MemoryVicinity) -> eyre::Result<()> {
+    let path = path.as_ref();
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let env = CachedBlockEnv {
+        chain_id: vicinity.chain_id,
+        block_number: vicinity.block_number,
+        block_coinbase: vicinity.block_coinbase,
+        block_timestamp: vicinity.block_timestamp,
+        block_difficulty: vicinity.block_difficulty,
+        block_gas_limit: vicinity.block_gas_limit,
+        block_base_fee_per_gas: vicinity.block_base_fee_per_gas,
+        gas_price: vicinity.gas_price,
+    };
+    std::fs::write(path, serde_json::to_string(&env)?)?;
+    Ok(())
+}
+
+/// The serializable version of a `MemoryAccount`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct CachedAccount {
+    nonce: U256,
+    balance: U256,
+    code: Bytes,
+    storage: BTreeMap<H256, H256>,
+}
+
+impl From<CachedAccount> for MemoryAccount {
+    fn from(acc: CachedAccount) -> Self {
+        MemoryAccount {
+            nonce: acc.nonce,
+            balance: acc.balance,
+            code: acc.code.to_vec(),
+            storage: acc.storage,
+        }
+    }
+}
+
+impl From<&MemoryAccount> for CachedAccount {
+    fn from(acc: &MemoryAccount) -> Self {
+        CachedAccount {
+            nonce: acc.nonce,
+            balance: acc.balance,
+            code: acc.code.clone().into(),
+            storage: acc.storage.clone(),
+        }
+    }
+}
+
+/// Reads the cached state from `path`
+pub fn read_cache(path: impl AsRef<Path>) -> eyre::Result<MemCache> {
+    let content = std::fs::read_to_string(path)?;
+    let accounts: BTreeMap<H160, CachedAccount> = serde_json::from_str(&content)?;
+    Ok(accounts.into_iter().map(|(addr, acc)| (addr, acc.into())).collect())
+}
+
+/// Writes the `cache` to `path`, creating all parent directories if necessary
+pub fn write_cache(path: impl AsRef<Path>, cache: &MemCache) -> eyre::Result<()> {
+    let path = path.as_ref();
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let accounts: BTreeMap<_, _> =
+        cache.iter().map(|(addr, acc)| (*addr, CachedAccount::from(acc))).collect();
+    std::fs::write(path, serde_json::to_string(&accounts)?)?;
+    Ok(())
+}
+
+/// A handle to a `SharedCache` that is written to disk once it is dropped.
+///
+/// Accounts that were set locally before the cache was handed to the backend (e.g. the faucet)
+/// are not part of the remote state and are excluded.
+#[derive(Debug)]
+pub struct DiskCache {
+    path: PathBuf,
+    cache: SharedCache<MemCache>,
+    local_accounts: BTreeSet<Address>,
+}
+
+impl DiskCache {
+    /// Loads the cache at `path` into the `cache`, any account already present in the `cache`
+    /// takes precedence
+    pub fn load(path: impl Into<PathBuf>, cache: SharedCache<MemCache>) -> Self {
+        let path = path.into();
+        let local_accounts = cache.read().keys().copied().collect::<BTreeSet<_>>();
+        if path.exists() {
+            match read_cache(&path) {
+                Ok(accounts) => {
+                    let mut lock = cache.write();
+                    for (addr, acc) in accounts {
+                        lock.entry(addr).or_insert(acc);
+                    }
+                }
+                Err(err) => {
+                    tracing::warn!(?err, "failed to read fork cache at {}", path.display())
+                }
+            }
+        }
+        Self { path, cache, local_accounts }
+    }
+
+    /// Writes the current state of the cache to disk
+    pub fn flush(&self) -> eyre::Result<()> {
+        let cache = self
+            .cache
+            .read()
+            .iter()
+            .filter(|(addr, _)| !self.local_accounts.contains(addr))
+            .map(|(addr, acc)| (*addr, acc.clone()))
+            .collect();
+        write_cache(&self.path, &cache)
+    }
+}
+
+impl Drop for DiskCache {
+    fn drop(&mut self) {
+        if let Err(err) = self.flush() {
+            tracing::warn!(?err, "failed to write fork cache to {}", self.path.display())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sputnik::new_shared_cache;
+
+    #[test]
+    fn can_persist_cache() {
+        let path = std::env::temp_dir().join("foundry-disk-cache-test").join("1.json");
+        let local: Address = Address::from_low_u64_be(1);
+        let remote: Address = Address::from_low_u64_be(2);
+
+        let cache = new_shared_cache(MemCache::default());
+        cache.write().insert(local, MemoryAccount { balance: U256::MAX, ..Default::default() });
+        let disk = DiskCache::load(&path, cache.clone());
+        let mut acc = MemoryAccount { nonce: 5.into(), code: vec![1, 2, 3], ..Default::default() };
+        acc.storage.insert(H256::from_low_u64_be(1), H256::from_low_u64_be(2));
+        cache.write().insert(remote, acc.clone());
+        drop(disk);
+
+        let stored = read_cache(&path).unwrap();
+        assert!(!stored.contains_key(&local));
+        assert_eq!(stored[&remote], acc);
+
+        let cache = new_shared_cache(MemCache::default());
+        let disk = DiskCache::load(&path, cache.clone());
+        assert_eq!(cache.read()[&remote], acc);
+        drop(disk);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn can_persist_block_env() {
+        let path = std::env::temp_dir().join("foundry-disk-cache-test").join("1.env.json");
+        let mut vicinity = crate::evm_opts::Env::default().sputnik_state();
+        vicinity.block_number = 14_000_000.into();
+        vicinity.block_base_fee_per_gas = 7.into();
+        write_block_env(&path, &vicinity).unwrap();
+
+        let origin = Address::from_low_u64_be(3);
+        let read = read_block_env(&path, origin).unwrap();
+        assert_eq!(read.origin, origin);
+        assert_eq!(read.block_number, vicinity.block_number);
+        assert_eq!(read.block_base_fee_per_gas, vicinity.block_base_fee_per_gas);
+        assert_eq!(read.chain_id, vicinity.chain_id);
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -1,4 +1,9 @@
 pub mod cache;
-pub use cache::{new_shared_cache, MemCache, SharedBackend, SharedCache};
+pub use cache::{new_shared_cache, take_invalid_state, MemCache, SharedBackend, SharedCache};
+pub mod disk_cache;
+pub use disk_cache::DiskCache;
+pub mod state_override;
+pub use state_override::{AccountOverride, StateOverride};
+pub mod proof;
 pub mod rpc;
 pub use rpc::ForkMemoryBackend;
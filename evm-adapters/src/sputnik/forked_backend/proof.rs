@@ -0,0 +1,250 @@
+//! Verification of `eth_getProof` (EIP-1186) Merkle-Patricia proofs against a state root
+use ethers::{
+    types::{Address, Bytes, EIP1186ProofResponse, StorageProof, H256, U256},
+    utils::{
+        keccak256,
+        rlp::{self, Rlp, RlpStream},
+    },
+};
+
+/// The root of an empty trie: `keccak256(rlp(""))`
+pub const EMPTY_TRIE_ROOT: H256 = H256([
+    0x56, 0xe8, 0x1f, 0x17, 0x1b, 0xcc, 0x55, 0xa6, 0xff, 0x83, 0x45, 0xe6, 0x92, 0xc0, 0xf8, 0x6e,
+    0x5b, 0x48, 0xe0, 0x1b, 0x99, 0x6c, 0xad, 0xc0, 0x01, 0x62, 0x2f, 0xb5, 0xe3, 0x63, 0xb4, 0x21,
+]);
+
+/// The hash of empty code: `keccak256("")`
+pub const EMPTY_CODE_HASH: H256 = H256([
+    0xc5, 0xd2, 0x46, 0x01, 0x86, 0xf7, 0x23, 0x3c, 0x92, 0x7e, 0x7d, 0xb2, 0xdc, 0xc7, 0x03, 0xc0,
+    0xe5, 0x00, 0xb6, 0x53, 0xca, 0x82, 0x27, 0x3b, 0x7b, 0xfa, 0xd8, 0x04, 0x5d, 0x85, 0xa4, 0x70,
+]);
+
+/// Reference to the next node in the trie
+enum NodeRef {
+    Hash(H256),
+    /// nodes smaller than 32 bytes are embedded in their parent
+    Inline(Vec<u8>),
+}
+
+impl NodeRef {
+    fn from_rlp(item: &Rlp) -> eyre::Result<Self> {
+        if item.is_list() {
+            Ok(NodeRef::Inline(item.as_raw().to_vec()))
+        } else {
+            let data = item.data()?;
+            eyre::ensure!(data.len() == 32, "invalid node reference of length {}", data.len());
+            Ok(NodeRef::Hash(H256::from_slice(data)))
+        }
+    }
+}
+
+/// Verifies the Merkle-Patricia `proof` of the `key` against the trie `root`.
+///
+/// Returns the value stored at `key` or `None` if the proof shows that `key` is not in the trie.
+pub fn verify_proof(root: H256, key: &[u8], proof: &[Bytes]) -> eyre::Result<Option<Vec<u8>>> {
+    if root == EMPTY_TRIE_ROOT {
+        return Ok(None)
+    }
+    let path = key.iter().flat_map(|b| [b >> 4, b & 0x0f]).collect::<Vec<_>>();
+    let mut path = path.as_slice();
+    let mut proof = proof.iter();
+    let mut next = NodeRef::Hash(root);
+
+    loop {
+        let node = match next {
+            NodeRef::Hash(hash) => {
+                let node = proof.next().ok_or_else(|| eyre::eyre!("incomplete proof"))?;
+                eyre::ensure!(
+                    H256(keccak256(node)) == hash,
+                    "proof node does not match its hash {:?}",
+                    hash
+                );
+                node.to_vec()
+            }
+            NodeRef::Inline(node) => node,
+        };
+        let node = Rlp::new(&node);
+        match node.item_count()? {
+            // branch node
+            17 => {
+                let (nibble, rest) = match path.split_first() {
+                    Some(split) => split,
+                    None => {
+                        let value = node.at(16)?.data()?;
+                        return Ok(if value.is_empty() { None } else { Some(value.to_vec()) })
+                    }
+                };
+                let child = node.at(*nibble as usize)?;
+                if child.is_empty() {
+                    return Ok(None)
+                }
+                path = rest;
+                next = NodeRef::from_rlp(&child)?;
+            }
+            // extension or leaf node
+            2 => {
+                let (partial, is_leaf) = decode_hex_prefix(node.at(0)?.data()?)?;
+                if is_leaf {
+                    return Ok(if path == partial.as_slice() {
+                        Some(node.at(1)?.data()?.to_vec())
+                    } else {
+                        None
+                    })
+                }
+                if !path.starts_with(&partial) {
+                    return Ok(None)
+                }
+                path = &path[partial.len()..];
+                next = NodeRef::from_rlp(&node.at(1)?)?;
+            }
+            n => eyre::bail!("invalid trie node with {} items", n),
+        }
+    }
+}
+
+/// Decodes the hex-prefix encoded path of a leaf or extension node, returns the nibbles and
+/// whether it's a leaf
+fn decode_hex_prefix(encoded: &[u8]) -> eyre::Result<(Vec<u8>, bool)> {
+    let (first, rest) = encoded.split_first().ok_or_else(|| eyre::eyre!("empty node path"))?;
+    let flag = first >> 4;
+    eyre::ensure!(flag <= 3, "invalid node path flag {}", flag);
+    let mut nibbles = Vec::with_capacity(rest.len() * 2 + 1);
+    // odd length paths store their first nibble in the prefix byte
+    if flag & 1 == 1 {
+        nibbles.push(first & 0x0f);
+    }
+    nibbles.extend(rest.iter().flat_map(|b| [b >> 4, b & 0x0f]));
+    Ok((nibbles, flag & 2 == 2))
+}
+
+/// Verifies that the account of the `proof` (nonce, balance, storage hash and code hash) is part
+/// of the state with the `state_root`
+pub fn verify_account_proof(
+    state_root: H256,
+    address: Address,
+    proof: &EIP1186ProofResponse,
+) -> eyre::Result<()> {
+    let nonce = U256::from(proof.nonce.as_u64());
+    let mut account = RlpStream::new_list(4);
+    account
+        .append(&nonce)
+        .append(&proof.balance)
+        .append(&proof.storage_hash)
+        .append(&proof.code_hash);
+    let expected = account.out().to_vec();
+
+    match verify_proof(state_root, &keccak256(address), &proof.account_proof)? {
+        Some(value) => {
+            eyre::ensure!(value == expected, "invalid account proof for {:?}", address)
+        }
+        None => {
+            let is_empty = nonce.is_zero() &&
+                proof.balance.is_zero() &&
+                proof.code_hash == EMPTY_CODE_HASH &&
+                (proof.storage_hash == EMPTY_TRIE_ROOT || proof.storage_hash.is_zero());
+            eyre::ensure!(is_empty, "account {:?} is not part of the state", address)
+        }
+    }
+    Ok(())
+}
+
+/// Verifies the account `proof` against the `state_root` and that it matches the fetched
+/// `nonce`, `balance` and `code`
+pub fn verify_account(
+    state_root: H256,
+    address: Address,
+    proof: &EIP1186ProofResponse,
+    nonce: U256,
+    balance: U256,
+    code: &[u8],
+) -> eyre::Result<()> {
+    verify_account_proof(state_root, address, proof)?;
+    eyre::ensure!(
+        U256::from(proof.nonce.as_u64()) == nonce && proof.balance == balance,
+        "account {:?} does not match its proof",
+        address
+    );
+    eyre::ensure!(
+        H256(keccak256(code)) == proof.code_hash,
+        "code of account {:?} does not match its proof",
+        address
+    );
+    Ok(())
+}
+
+/// Verifies that the storage slot in the `proof` is part of the storage trie with the
+/// `storage_hash` and that it holds `value`.
+pub fn verify_storage_proof(
+    storage_hash: H256,
+    proof: &StorageProof,
+    value: H256,
+) -> eyre::Result<()> {
+    let value = U256::from_big_endian(value.as_bytes());
+    eyre::ensure!(proof.value == value, "storage slot {:?} does not match its proof", proof.key);
+    match verify_proof(storage_hash, &keccak256(proof.key), &proof.proof)? {
+        Some(stored) => {
+            eyre::ensure!(
+                stored == rlp::encode(&value).to_vec(),
+                "invalid storage proof for slot {:?}",
+                proof.key
+            )
+        }
+        None => eyre::ensure!(value.is_zero(), "storage slot {:?} is not set", proof.key),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// hex-prefix encodes the full 64 nibble path of a leaf
+    fn leaf(key: &[u8], value: &[u8]) -> Vec<u8> {
+        let mut path = vec![0x20];
+        path.extend_from_slice(key);
+        let mut stream = RlpStream::new_list(2);
+        stream.append(&path).append(&value.to_vec());
+        stream.out().to_vec()
+    }
+
+    #[test]
+    fn constants_are_correct() {
+        assert_eq!(EMPTY_TRIE_ROOT, H256(keccak256(rlp::NULL_RLP)));
+        assert_eq!(EMPTY_CODE_HASH, H256(keccak256(b"")));
+    }
+
+    #[test]
+    fn can_verify_single_leaf() {
+        let key = keccak256(H256::from_low_u64_be(1));
+        let value = rlp::encode(&U256::from(1337)).to_vec();
+        let node = leaf(&key, &value);
+        let root = H256(keccak256(&node));
+        let proof = vec![Bytes::from(node)];
+
+        assert_eq!(verify_proof(root, &key, &proof).unwrap(), Some(value));
+
+        // a different key diverges at the leaf which proves its absence
+        let other = keccak256(H256::from_low_u64_be(2));
+        assert_eq!(verify_proof(root, &other, &proof).unwrap(), None);
+
+        // tampered proofs are rejected
+        let tampered = leaf(&key, &rlp::encode(&U256::from(1)));
+        assert!(verify_proof(root, &key, &[Bytes::from(tampered)]).is_err());
+        assert!(verify_proof(root, &key, &[]).is_err());
+    }
+
+    #[test]
+    fn can_verify_storage_proof() {
+        let slot = H256::from_low_u64_be(1);
+        let value = H256::from_low_u64_be(1337);
+        let node = leaf(&keccak256(slot), &rlp::encode(&U256::from(1337)));
+        let storage_hash = H256(keccak256(&node));
+        let mut proof =
+            StorageProof { key: slot, proof: vec![Bytes::from(node)], value: U256::from(1337) };
+        verify_storage_proof(storage_hash, &proof, value).unwrap();
+        assert!(verify_storage_proof(storage_hash, &proof, H256::zero()).is_err());
+
+        proof.value = 1.into();
+        assert!(verify_storage_proof(storage_hash, &proof, H256::from_low_u64_be(1)).is_err());
+    }
+}
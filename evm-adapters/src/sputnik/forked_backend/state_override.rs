@@ -0,0 +1,129 @@
+//! Geth-style state overrides that are applied on top of the (forked) state
+use ethers::types::{Address, Bytes, H256, U256};
+use serde::{Deserialize, Serialize};
+use sputnik::backend::{Basic, MemoryAccount};
+use std::{collections::BTreeMap, path::Path};
+
+/// The state override set, as accepted by geth's `eth_call`:
+///
+/// ```json
+/// {
+///   "0xd8dA6BF26964aF9D7eEd9e03E53415D37aA96045": {
+///     "balance": "0xde0b6b3a7640000",
+///     "nonce": "0x1",
+///     "code": "0x6080...",
+///     "stateDiff": {
+///       "0x0000000000000000000000000000000000000000000000000000000000000000": "0x...01"
+///     }
+///   }
+/// }
+/// ```
+pub type StateOverride = BTreeMap<Address, AccountOverride>;
+
+/// Reads the [`StateOverride`] set from the json file at `path`
+pub fn read_state_overrides(path: impl AsRef<Path>) -> eyre::Result<StateOverride> {
+    let path = path.as_ref();
+    let content = std::fs::read_to_string(path)
+        .map_err(|err| eyre::eyre!("failed to read state overrides {}: {}", path.display(), err))?;
+    serde_json::from_str(&content)
+        .map_err(|err| eyre::eyre!("invalid state overrides {}: {}", path.display(), err))
+}
+
+/// Overrides of a single account, all fields are optional
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountOverride {
+    /// the balance of the account
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub balance: Option<U256>,
+    /// the nonce of the account
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<U256>,
+    /// the runtime code of the account
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<Bytes>,
+    /// replaces the entire storage of the account, all other slots are empty
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state: Option<BTreeMap<H256, H256>>,
+    /// replaces only the given slots, all other slots are left untouched
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state_diff: Option<BTreeMap<H256, H256>>,
+}
+
+impl AccountOverride {
+    /// Applies the balance and nonce overrides to `basic`
+    pub fn apply_basic(&self, mut basic: Basic) -> Basic {
+        if let Some(balance) = self.balance {
+            basic.balance = balance;
+        }
+        if let Some(nonce) = self.nonce {
+            basic.nonce = nonce;
+        }
+        basic
+    }
+
+    /// Returns the overridden value of the storage slot `index`, if any
+    pub fn storage(&self, index: H256) -> Option<H256> {
+        if let Some(ref state) = self.state {
+            return Some(state.get(&index).copied().unwrap_or_default())
+        }
+        self.state_diff.as_ref()?.get(&index).copied()
+    }
+
+    /// Applies all overrides to the `account`
+    pub fn apply(&self, account: &mut MemoryAccount) {
+        let basic = self.apply_basic(Basic { balance: account.balance, nonce: account.nonce });
+        account.balance = basic.balance;
+        account.nonce = basic.nonce;
+        if let Some(ref code) = self.code {
+            account.code = code.to_vec();
+        }
+        if let Some(ref state) = self.state {
+            account.storage = state.clone();
+        }
+        if let Some(ref diff) = self.state_diff {
+            account.storage.extend(diff.iter().map(|(k, v)| (*k, *v)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_apply_geth_overrides() {
+        let overrides: StateOverride = serde_json::from_str(
+            r#"{
+                "0x0000000000000000000000000000000000000001": {
+                    "balance": "0x10",
+                    "code": "0x6001",
+                    "stateDiff": {
+                        "0x0000000000000000000000000000000000000000000000000000000000000001": "0x0000000000000000000000000000000000000000000000000000000000000002"
+                    }
+                },
+                "0x0000000000000000000000000000000000000002": {
+                    "nonce": "0x5",
+                    "state": {}
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let one = H256::from_low_u64_be(1);
+        let two = H256::from_low_u64_be(2);
+
+        let mut acc = MemoryAccount { nonce: 3.into(), ..Default::default() };
+        acc.storage.insert(two, two);
+        overrides[&Address::from_low_u64_be(1)].apply(&mut acc);
+        assert_eq!(acc.balance, 16.into());
+        assert_eq!(acc.nonce, 3.into());
+        assert_eq!(acc.code, vec![0x60, 0x01]);
+        assert_eq!(acc.storage[&one], two);
+        assert_eq!(acc.storage[&two], two);
+
+        let replaced = &overrides[&Address::from_low_u64_be(2)];
+        assert_eq!(replaced.storage(one), Some(H256::zero()));
+        assert_eq!(overrides[&Address::from_low_u64_be(1)].storage(two), None);
+    }
+}
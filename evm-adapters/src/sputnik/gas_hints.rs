@@ -0,0 +1,216 @@
+//! Finds patterns in the debug steps of an execution that waste gas
+
+use crate::sputnik::cheatcodes::debugger::{DebugArena, DebugNode, DebugStep};
+use ethers::types::{Address, H256};
+use sputnik::Opcode;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+/// The gas of a storage write that changes a slot from zero to a non-zero value
+const ZERO_TO_NONZERO_GAS: u64 = 20_000;
+
+/// The growth of the memory in a single step, in bytes, that's reported as a spike
+pub const MEMORY_SPIKE: usize = 4096;
+
+/// A pattern that wastes gas
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum GasHintKind {
+    /// The slot was read before in the same call, without a write in between
+    RepeatedSload(H256),
+    /// The step wrote zero-to-nonzero values multiple times, which happens in loops
+    ZeroToNonzeroInLoop(usize),
+    /// The step grew the memory by the number of bytes
+    MemorySpike(usize),
+    /// The call doesn't transfer value or change the state, so it could be a staticcall
+    CouldBeStaticcall,
+}
+
+impl fmt::Display for GasHintKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GasHintKind::RepeatedSload(slot) => {
+                write!(f, "storage slot {:?} is read again, cache it in memory", slot)
+            }
+            GasHintKind::ZeroToNonzeroInLoop(writes) => write!(
+                f,
+                "{} zero-to-nonzero storage writes in a loop, {} gas each",
+                writes, ZERO_TO_NONZERO_GAS
+            ),
+            GasHintKind::MemorySpike(bytes) => write!(f, "memory expanded by {} bytes", bytes),
+            GasHintKind::CouldBeStaticcall => {
+                write!(f, "the call doesn't change state, declare the function `view`")
+            }
+        }
+    }
+}
+
+/// A pattern that wastes gas and the step it was observed at
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GasHint {
+    pub kind: GasHintKind,
+    /// The address of the contract whose code was executing
+    pub address: Address,
+    /// Whether the creation code was executing
+    pub creation: bool,
+    /// The instruction counter of the step, to look up in the source map
+    pub ic: usize,
+}
+
+/// Returns the gas hints of every call in the arena, see [`DebugArena::calls`]
+pub fn gas_hints(arena: &DebugArena) -> Vec<GasHint> {
+    arena.calls().iter().flat_map(|nodes| call_hints(arena, nodes)).collect()
+}
+
+/// Returns the gas hints of the nodes of a call
+fn call_hints(arena: &DebugArena, nodes: &[&DebugNode]) -> Vec<GasHint> {
+    let (address, creation) = (nodes[0].address, nodes[0].creation);
+    let hint = |kind, step: &DebugStep| GasHint { kind, address, creation, ic: step.ic };
+    let steps = nodes.iter().flat_map(|node| &node.steps).collect::<Vec<_>>();
+
+    let mut hints = Vec::new();
+    let mut loaded = BTreeSet::new();
+    let mut reported = BTreeSet::new();
+    // the zero-to-nonzero writes of every step
+    let mut writes: BTreeMap<usize, (usize, &DebugStep)> = BTreeMap::new();
+    for (step, next) in steps.iter().zip(steps.iter().skip(1)) {
+        let top = step.stack.first().copied().unwrap_or_default();
+        match step.op.0 {
+            Opcode::SLOAD => {
+                if !loaded.insert(top) && reported.insert(top) {
+                    hints.push(hint(GasHintKind::RepeatedSload(top), step));
+                }
+            }
+            Opcode::SSTORE => {
+                loaded.remove(&top);
+                let gas = next.total_gas_used.saturating_sub(step.total_gas_used);
+                if gas >= ZERO_TO_NONZERO_GAS {
+                    writes.entry(step.pc).or_insert((0, *step)).0 += 1;
+                }
+            }
+            _ => {}
+        }
+        let growth = next.memory.len().saturating_sub(step.memory.len());
+        if growth >= MEMORY_SPIKE {
+            hints.push(hint(GasHintKind::MemorySpike(growth), step));
+        }
+    }
+    for (count, step) in writes.into_values() {
+        if count > 1 {
+            hints.push(hint(GasHintKind::ZeroToNonzeroInLoop(count), step));
+        }
+    }
+
+    for node in nodes {
+        let call = match node.steps.last() {
+            Some(call) if call.op.0 == Opcode::CALL => call,
+            _ => continue,
+        };
+        // the value is the third argument, calls to precompiles and accounts without code
+        // don't execute steps
+        let value = call.stack.get(2).copied().unwrap_or_default();
+        if value.is_zero() &&
+            !node.children.is_empty() &&
+            node.children.iter().all(|child| !changes_state(arena, *child))
+        {
+            hints.push(hint(GasHintKind::CouldBeStaticcall, call));
+        }
+    }
+    hints
+}
+
+/// Whether the node or one of its descendants changes the state
+fn changes_state(arena: &DebugArena, idx: usize) -> bool {
+    let node = &arena.arena[idx];
+    let changes = node.steps.iter().any(|step| {
+        // every cheatcode is considered to change the state
+        step.op.1.is_some() ||
+            matches!(
+                step.op.0,
+                Opcode::SSTORE |
+                    Opcode::LOG0 |
+                    Opcode::LOG1 |
+                    Opcode::LOG2 |
+                    Opcode::LOG3 |
+                    Opcode::LOG4 |
+                    Opcode::CREATE |
+                    Opcode::CREATE2 |
+                    Opcode::SUICIDE
+            ) ||
+            (step.op.0 == Opcode::CALL &&
+                !step.stack.get(2).copied().unwrap_or_default().is_zero())
+    });
+    changes || node.children.iter().any(|child| changes_state(arena, *child))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sputnik::cheatcodes::debugger::OpCode;
+
+    fn step(pc: usize, op: Opcode, stack: Vec<H256>, total_gas_used: u64) -> DebugStep {
+        DebugStep { pc, ic: pc, op: OpCode::from(op), stack, total_gas_used, ..Default::default() }
+    }
+
+    fn node(address: Address, depth: usize, trace_idx: usize, steps: Vec<DebugStep>) -> DebugNode {
+        DebugNode { trace_idx, ..DebugNode::new(address, depth, steps) }
+    }
+
+    #[test]
+    fn finds_gas_hints() {
+        let slot = H256::from_low_u64_be(1);
+        let other = H256::from_low_u64_be(2);
+        let caller = vec![
+            step(0, Opcode::SLOAD, vec![slot], 0),
+            step(1, Opcode::SLOAD, vec![other], 100),
+            step(2, Opcode::SLOAD, vec![slot], 200),
+            // a loop writing zero-to-nonzero values
+            step(3, Opcode::SSTORE, vec![other], 300),
+            step(4, Opcode::JUMP, vec![], 20_300),
+            step(3, Opcode::SSTORE, vec![slot], 20_400),
+            step(5, Opcode::SLOAD, vec![slot], 40_400),
+            // a call without value
+            step(6, Opcode::CALL, vec![H256::zero(), H256::zero(), H256::zero()], 40_500),
+        ];
+        let callee = vec![
+            step(0, Opcode::SLOAD, vec![slot], 41_000),
+            step(1, Opcode::RETURN, vec![], 41_100),
+        ];
+        let mut arena = DebugArena::default();
+        arena.push_node(0, node(Address::repeat_byte(1), 1, 1, caller));
+        arena.push_node(0, node(Address::repeat_byte(2), 2, 2, callee));
+
+        let kinds = gas_hints(&arena)
+            .into_iter()
+            .map(|hint| (hint.address, hint.ic, hint.kind))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec![
+                (Address::repeat_byte(1), 2, GasHintKind::RepeatedSload(slot)),
+                (Address::repeat_byte(1), 3, GasHintKind::ZeroToNonzeroInLoop(2)),
+                (Address::repeat_byte(1), 6, GasHintKind::CouldBeStaticcall),
+            ]
+        );
+    }
+
+    #[test]
+    fn separates_calls_to_the_same_contract() {
+        let slot = H256::from_low_u64_be(1);
+        let call = || {
+            vec![step(0, Opcode::SLOAD, vec![slot], 0), step(1, Opcode::RETURN, vec![], 100)]
+        };
+        let mut arena = DebugArena::default();
+        arena.push_node(0, node(Address::repeat_byte(1), 1, 1, call()));
+        arena.push_node(0, node(Address::repeat_byte(1), 1, 2, call()));
+        assert_eq!(gas_hints(&arena), vec![]);
+
+        // the nodes of a single call are joined
+        let mut arena = DebugArena::default();
+        arena.push_node(0, node(Address::repeat_byte(1), 1, 1, call()));
+        arena.push_node(0, node(Address::repeat_byte(1), 1, 1, call()));
+        let kinds = gas_hints(&arena).into_iter().map(|hint| hint.kind).collect::<Vec<_>>();
+        assert_eq!(kinds, vec![GasHintKind::RepeatedSload(slot)]);
+    }
+}
@@ -0,0 +1,625 @@
+//! A layered journal of committed state changes
+use ethers::{
+    types::{H160, H256, U256},
+    utils::keccak256,
+};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sputnik::backend::{Apply, Backend, Basic};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+};
+
+/// The number of layers past which a journal squashes its layers
+const MAX_LAYERS: usize = 64;
+
+/// A committed change of a single account
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum JournalAccount {
+    /// The account was deleted
+    Deleted,
+    /// The account was modified
+    #[serde(rename_all = "camelCase")]
+    Modified {
+        #[serde(with = "serde_basic")]
+        basic: Basic,
+        /// the new code, `None` if unchanged
+        #[serde(with = "serde_code")]
+        code: Option<Vec<u8>>,
+        /// the changed storage slots
+        storage: BTreeMap<H256, H256>,
+        /// whether all other storage slots were cleared
+        reset_storage: bool,
+    },
+}
+
+impl JournalAccount {
+    /// Converts a sputnik [`Apply`] into the address and change of the account
+    pub fn from_apply<I: IntoIterator<Item = (H256, H256)>>(apply: Apply<I>) -> (H160, Self) {
+        match apply {
+            Apply::Modify { address, basic, code, storage, reset_storage } => (
+                address,
+                JournalAccount::Modified {
+                    basic,
+                    code,
+                    storage: storage.into_iter().collect(),
+                    reset_storage,
+                },
+            ),
+            Apply::Delete { address } => (address, JournalAccount::Deleted),
+        }
+    }
+
+    /// Returns the change of the account made by applying `self` and then `next`
+    fn squash(self, next: Self) -> Self {
+        match (self, next) {
+            (_, JournalAccount::Deleted) => JournalAccount::Deleted,
+            (JournalAccount::Deleted, JournalAccount::Modified { basic, code, storage, .. }) => {
+                JournalAccount::Modified {
+                    basic,
+                    // the code and storage of the deleted account must not be read from below
+                    code: Some(code.unwrap_or_default()),
+                    storage,
+                    reset_storage: true,
+                }
+            }
+            (
+                JournalAccount::Modified {
+                    code: prev_code,
+                    storage: mut prev_storage,
+                    reset_storage: prev_reset,
+                    ..
+                },
+                JournalAccount::Modified { basic, code, storage, reset_storage },
+            ) => {
+                let storage = if reset_storage {
+                    storage
+                } else {
+                    prev_storage.extend(storage);
+                    prev_storage
+                };
+                JournalAccount::Modified {
+                    basic,
+                    code: code.or(prev_code),
+                    storage,
+                    reset_storage: reset_storage || prev_reset,
+                }
+            }
+        }
+    }
+}
+
+/// A layer of the journal and the number of commits up to and including it
+#[derive(Debug, Clone)]
+struct Layer {
+    changes: Arc<BTreeMap<H160, JournalAccount>>,
+    depth: usize,
+}
+
+/// A stack of immutable layers of state changes on top of a [`Backend`].
+///
+/// Every commit pushes a new layer, layers are never modified afterwards and are shared between
+/// all clones of the journal. This makes cloning and rolling back a journal O(1), so a state can
+/// be checkpointed once, e.g. after `setUp`, and restored cheaply for every test.
+///
+/// Reads walk the layers from the top, so once there are more than [`MAX_LAYERS`] the layers
+/// between the pinned depths are squashed into a single layer. The squashed layers are copied,
+/// clones of the journal keep sharing the original ones.
+#[derive(Debug, Clone, Default)]
+pub struct Journal {
+    layers: Vec<Layer>,
+    /// the depths that are never squashed, see [`Journal::pin`]
+    pins: BTreeSet<usize>,
+}
+
+impl Journal {
+    /// Returns the number of commits that created a layer
+    pub fn depth(&self) -> usize {
+        self.layers.last().map(|layer| layer.depth).unwrap_or_default()
+    }
+
+    /// Returns the depth and keeps it from being squashed, so the journal can be rolled back to
+    /// it and the changes made since can be taken with [`diff_since`](Self::diff_since)
+    pub fn pin(&mut self) -> usize {
+        let depth = self.depth();
+        self.pins.insert(depth);
+        depth
+    }
+
+    /// Pushes the `changes` as a new layer, empty changes don't create a layer
+    pub fn commit(&mut self, changes: impl IntoIterator<Item = (H160, JournalAccount)>) {
+        let layer = changes.into_iter().collect::<BTreeMap<_, _>>();
+        if !layer.is_empty() {
+            self.push(Arc::new(layer));
+        }
+    }
+
+    /// Drops all layers above `depth`
+    ///
+    /// Panics if the `depth` was squashed, i.e. it wasn't [pinned](Self::pin).
+    pub fn rollback(&mut self, depth: usize) {
+        let index = self.index(depth);
+        self.layers.truncate(index);
+        self.pins.retain(|pin| *pin <= depth);
+    }
+
+    /// Returns the layers above `depth`, which can be applied on top of other journals
+    ///
+    /// Panics if the `depth` was squashed, i.e. it wasn't [pinned](Self::pin).
+    pub fn diff_since(&self, depth: usize) -> JournalDiff {
+        let index = self.index(depth.min(self.depth()));
+        JournalDiff {
+            layers: self.layers[index..].iter().map(|layer| layer.changes.clone()).collect(),
+        }
+    }
+
+    /// Pushes the layers of the diff, they're shared with the journal the diff was taken from
+    pub fn apply(&mut self, diff: &JournalDiff) {
+        for layer in &diff.layers {
+            self.push(layer.clone());
+        }
+    }
+
+    /// Pushes the layer and squashes the layers if there are too many
+    fn push(&mut self, changes: Arc<BTreeMap<H160, JournalAccount>>) {
+        let depth = self.depth() + 1;
+        self.layers.push(Layer { changes, depth });
+        if self.layers.len() > MAX_LAYERS {
+            self.squash();
+        }
+    }
+
+    /// Squashes every run of layers between two pinned depths into a single layer
+    fn squash(&mut self) {
+        let mut squashed: Vec<Layer> = Vec::with_capacity(self.pins.len() + 1);
+        let mut run_start = 0;
+        for (index, layer) in self.layers.iter().enumerate() {
+            let run_end = index + 1 == self.layers.len() || self.pins.contains(&layer.depth);
+            if !run_end {
+                continue
+            }
+            let run = &self.layers[run_start..=index];
+            run_start = index + 1;
+            if run.len() == 1 {
+                squashed.push(layer.clone());
+                continue
+            }
+            let mut changes = BTreeMap::new();
+            for (address, change) in run.iter().flat_map(|layer| layer.changes.iter()) {
+                let change = match changes.remove(address) {
+                    Some(prev) => JournalAccount::squash(prev, change.clone()),
+                    None => change.clone(),
+                };
+                changes.insert(*address, change);
+            }
+            squashed.push(Layer { changes: Arc::new(changes), depth: layer.depth });
+        }
+        self.layers = squashed;
+    }
+
+    /// Returns the number of layers up to and including `depth`
+    fn index(&self, depth: usize) -> usize {
+        let index = self.layers.partition_point(|layer| layer.depth <= depth);
+        let squashed = if index == 0 { depth != 0 } else { self.layers[index - 1].depth != depth };
+        assert!(!squashed, "journal depth {} was squashed", depth);
+        index
+    }
+
+    /// Returns a [`Backend`] view of the journal on top of the `backend`
+    pub fn with_backend<'a, B>(&'a self, backend: &'a B) -> JournaledBackend<'a, B> {
+        JournaledBackend { journal: self, backend }
+    }
+
+    /// Returns the accounts and storage slots changed by any layer
+    pub fn touched(&self) -> BTreeMap<H160, BTreeSet<H256>> {
+        let mut touched: BTreeMap<H160, BTreeSet<H256>> = BTreeMap::new();
+        for (address, change) in self.layers.iter().flat_map(|layer| layer.changes.iter()) {
+            let slots = touched.entry(*address).or_default();
+            if let JournalAccount::Modified { storage, .. } = change {
+                slots.extend(storage.keys().copied());
+            }
+        }
+        touched
+    }
+
+    /// Returns the changes of the account from the most recent to the oldest layer
+    fn changes(&self, address: H160) -> impl Iterator<Item = &JournalAccount> {
+        self.layers.iter().rev().filter_map(move |layer| layer.changes.get(&address))
+    }
+}
+
+/// The layers of state changes taken from a [`Journal`], see [`Journal::diff_since`]
+///
+/// A diff serializes into the list of its layers, so it can be kept on disk and applied to the
+/// journals of later runs.
+#[derive(Debug, Clone, Default)]
+pub struct JournalDiff {
+    layers: Vec<Arc<BTreeMap<H160, JournalAccount>>>,
+}
+
+impl Serialize for JournalDiff {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.layers.iter().map(|layer| layer.as_ref()))
+    }
+}
+
+impl<'de> Deserialize<'de> for JournalDiff {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let layers = Vec::<BTreeMap<H160, JournalAccount>>::deserialize(deserializer)?;
+        Ok(Self { layers: layers.into_iter().map(Arc::new).collect() })
+    }
+}
+
+/// (De)serializes the balance and nonce of a sputnik [`Basic`]
+mod serde_basic {
+    use super::*;
+
+    #[derive(Serialize, Deserialize)]
+    struct BasicDef {
+        balance: U256,
+        nonce: U256,
+    }
+
+    pub fn serialize<S: Serializer>(basic: &Basic, serializer: S) -> Result<S::Ok, S::Error> {
+        BasicDef { balance: basic.balance, nonce: basic.nonce }.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Basic, D::Error> {
+        let BasicDef { balance, nonce } = BasicDef::deserialize(deserializer)?;
+        Ok(Basic { balance, nonce })
+    }
+}
+
+/// (De)serializes code as a `0x` prefixed hex string
+mod serde_code {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        code: &Option<Vec<u8>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        code.as_ref().map(|code| format!("0x{}", hex::encode(code))).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Vec<u8>>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|code| hex::decode(code.trim_start_matches("0x")))
+            .transpose()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// The state of a set of accounts and storage slots at a point in time
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StateSnapshot {
+    pub accounts: BTreeMap<H160, AccountSnapshot>,
+}
+
+/// The state of an account and some of its storage slots
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AccountSnapshot {
+    pub balance: U256,
+    pub nonce: U256,
+    pub code_hash: H256,
+    pub storage: BTreeMap<H256, H256>,
+}
+
+impl StateSnapshot {
+    /// Reads the state of the accounts and their storage slots from the backend
+    pub fn read<B: Backend>(accounts: &BTreeMap<H160, BTreeSet<H256>>, backend: &B) -> Self {
+        let accounts = accounts
+            .iter()
+            .map(|(address, slots)| {
+                let Basic { balance, nonce } = backend.basic(*address);
+                let account = AccountSnapshot {
+                    balance,
+                    nonce,
+                    code_hash: keccak256(backend.code(*address)).into(),
+                    storage: slots
+                        .iter()
+                        .map(|slot| (*slot, backend.storage(*address, *slot)))
+                        .collect(),
+                };
+                (*address, account)
+            })
+            .collect();
+        Self { accounts }
+    }
+
+    /// Reads the state of the same accounts and storage slots as this snapshot from the backend
+    pub fn reread<B: Backend>(&self, backend: &B) -> Self {
+        let accounts = self
+            .accounts
+            .iter()
+            .map(|(address, account)| (*address, account.storage.keys().copied().collect()))
+            .collect();
+        Self::read(&accounts, backend)
+    }
+
+    /// Returns a description of every account field and storage slot whose value differs in
+    /// the `other` snapshot
+    pub fn diff(&self, other: &Self) -> Vec<String> {
+        let mut diffs = Vec::new();
+        for (address, before) in &self.accounts {
+            let after = other.accounts.get(address).cloned().unwrap_or_default();
+            if before.balance != after.balance {
+                diffs.push(format!(
+                    "{:?}: balance changed from {} to {}",
+                    address, before.balance, after.balance
+                ));
+            }
+            if before.nonce != after.nonce {
+                diffs.push(format!(
+                    "{:?}: nonce changed from {} to {}",
+                    address, before.nonce, after.nonce
+                ));
+            }
+            if before.code_hash != after.code_hash {
+                diffs.push(format!("{:?}: code changed", address));
+            }
+            for (slot, value) in &before.storage {
+                let new_value = after.storage.get(slot).copied().unwrap_or_default();
+                if *value != new_value {
+                    diffs.push(format!(
+                        "{:?}: slot {:?} changed from {:?} to {:?}",
+                        address, slot, value, new_value
+                    ));
+                }
+            }
+        }
+        diffs
+    }
+}
+
+/// A [`Backend`] that reads from the layers of a [`Journal`] first and falls back to the inner
+/// backend
+#[derive(Debug)]
+pub struct JournaledBackend<'a, B> {
+    journal: &'a Journal,
+    backend: &'a B,
+}
+
+impl<'a, B: Backend> Backend for JournaledBackend<'a, B> {
+    fn gas_price(&self) -> U256 {
+        self.backend.gas_price()
+    }
+    fn origin(&self) -> H160 {
+        self.backend.origin()
+    }
+    fn block_hash(&self, number: U256) -> H256 {
+        self.backend.block_hash(number)
+    }
+    fn block_number(&self) -> U256 {
+        self.backend.block_number()
+    }
+    fn block_coinbase(&self) -> H160 {
+        self.backend.block_coinbase()
+    }
+    fn block_timestamp(&self) -> U256 {
+        self.backend.block_timestamp()
+    }
+    fn block_difficulty(&self) -> U256 {
+        self.backend.block_difficulty()
+    }
+    fn block_gas_limit(&self) -> U256 {
+        self.backend.block_gas_limit()
+    }
+    fn block_base_fee_per_gas(&self) -> U256 {
+        self.backend.block_base_fee_per_gas()
+    }
+    fn chain_id(&self) -> U256 {
+        self.backend.chain_id()
+    }
+
+    fn exists(&self, address: H160) -> bool {
+        match self.journal.changes(address).next() {
+            Some(JournalAccount::Deleted) => false,
+            Some(JournalAccount::Modified { .. }) => true,
+            None => self.backend.exists(address),
+        }
+    }
+
+    fn basic(&self, address: H160) -> Basic {
+        match self.journal.changes(address).next() {
+            Some(JournalAccount::Deleted) => Basic::default(),
+            Some(JournalAccount::Modified { basic, .. }) => basic.clone(),
+            None => self.backend.basic(address),
+        }
+    }
+
+    fn code(&self, address: H160) -> Vec<u8> {
+        for change in self.journal.changes(address) {
+            match change {
+                JournalAccount::Deleted => return Vec::new(),
+                JournalAccount::Modified { code: Some(code), .. } => return code.clone(),
+                JournalAccount::Modified { code: None, .. } => {}
+            }
+        }
+        self.backend.code(address)
+    }
+
+    fn storage(&self, address: H160, index: H256) -> H256 {
+        for change in self.journal.changes(address) {
+            match change {
+                JournalAccount::Deleted => return H256::zero(),
+                JournalAccount::Modified { storage, reset_storage, .. } => {
+                    if let Some(value) = storage.get(&index) {
+                        return *value
+                    }
+                    if *reset_storage {
+                        return H256::zero()
+                    }
+                }
+            }
+        }
+        self.backend.storage(address, index)
+    }
+
+    fn original_storage(&self, address: H160, index: H256) -> Option<H256> {
+        if self.journal.changes(address).next().is_none() {
+            return self.backend.original_storage(address, index)
+        }
+        // committed changes are the original state of the next transaction
+        Some(self.storage(address, index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sputnik::helpers::new_vicinity;
+    use sputnik::backend::MemoryBackend;
+
+    fn modified(storage: &[(u64, u64)], reset_storage: bool) -> JournalAccount {
+        JournalAccount::Modified {
+            basic: Basic { balance: 1.into(), nonce: 0.into() },
+            code: None,
+            storage: storage
+                .iter()
+                .map(|(k, v)| (H256::from_low_u64_be(*k), H256::from_low_u64_be(*v)))
+                .collect(),
+            reset_storage,
+        }
+    }
+
+    #[test]
+    fn reads_through_layers() {
+        let vicinity = new_vicinity();
+        let addr = H160::from_low_u64_be(1);
+        let mut backend = MemoryBackend::new(&vicinity, Default::default());
+        let account = backend.state_mut().entry(addr).or_insert_with(Default::default);
+        account.storage.insert(H256::from_low_u64_be(1), H256::from_low_u64_be(1));
+        account.storage.insert(H256::from_low_u64_be(2), H256::from_low_u64_be(2));
+        account.code = vec![1];
+
+        let mut journal = Journal::default();
+        journal.commit([(addr, modified(&[(1, 10)], false))]);
+        let snapshot = journal.clone();
+        journal.commit([(addr, modified(&[(3, 30)], true))]);
+        journal.commit(std::iter::empty());
+        assert_eq!(journal.depth(), 2);
+
+        let view = journal.with_backend(&backend);
+        assert_eq!(view.storage(addr, H256::from_low_u64_be(3)), H256::from_low_u64_be(30));
+        // cleared by the reset of the top layer
+        assert_eq!(view.storage(addr, H256::from_low_u64_be(1)), H256::zero());
+        assert_eq!(view.code(addr), vec![1]);
+        assert_eq!(view.basic(addr).balance, 1.into());
+
+        let view = snapshot.with_backend(&backend);
+        assert_eq!(view.storage(addr, H256::from_low_u64_be(1)), H256::from_low_u64_be(10));
+        assert_eq!(view.storage(addr, H256::from_low_u64_be(2)), H256::from_low_u64_be(2));
+
+        journal.rollback(1);
+        journal.commit([(addr, JournalAccount::Deleted)]);
+        let view = journal.with_backend(&backend);
+        assert!(!view.exists(addr));
+        assert_eq!(view.storage(addr, H256::from_low_u64_be(2)), H256::zero());
+        assert!(view.code(addr).is_empty());
+    }
+
+    #[test]
+    fn diffs_snapshots() {
+        let vicinity = new_vicinity();
+        let addr = H160::from_low_u64_be(1);
+        let backend = MemoryBackend::new(&vicinity, Default::default());
+        let mut journal = Journal::default();
+        journal.commit([(addr, modified(&[(1, 1)], false))]);
+        assert_eq!(journal.touched()[&addr], BTreeSet::from([H256::from_low_u64_be(1)]));
+
+        let before = StateSnapshot::read(&journal.touched(), &journal.with_backend(&backend));
+        assert!(before.diff(&before.reread(&journal.with_backend(&backend))).is_empty());
+
+        journal.commit([(addr, modified(&[(1, 2)], false))]);
+        let after = before.reread(&journal.with_backend(&backend));
+        let diffs = before.diff(&after);
+        assert_eq!(diffs.len(), 1);
+        assert!(diffs[0].contains("slot"));
+    }
+
+    #[test]
+    fn applies_diffs() {
+        let vicinity = new_vicinity();
+        let addr = H160::from_low_u64_be(1);
+        let backend = MemoryBackend::new(&vicinity, Default::default());
+        let mut fixture = Journal::default();
+        fixture.commit([(addr, modified(&[(1, 1)], false))]);
+        let depth = fixture.depth();
+        fixture.commit([(addr, modified(&[(2, 2)], false))]);
+        let diff = fixture.diff_since(depth);
+
+        let mut journal = Journal::default();
+        journal.commit([(addr, modified(&[(3, 3)], false))]);
+        journal.apply(&diff);
+        assert_eq!(journal.depth(), 2);
+        let view = journal.with_backend(&backend);
+        assert_eq!(view.storage(addr, H256::from_low_u64_be(1)), H256::zero());
+        assert_eq!(view.storage(addr, H256::from_low_u64_be(2)), H256::from_low_u64_be(2));
+        assert_eq!(view.storage(addr, H256::from_low_u64_be(3)), H256::from_low_u64_be(3));
+    }
+
+    #[test]
+    fn squashes_layers() {
+        let vicinity = new_vicinity();
+        let addr = H160::from_low_u64_be(1);
+        let deleted = H160::from_low_u64_be(2);
+        let mut backend = MemoryBackend::new(&vicinity, Default::default());
+        let account = backend.state_mut().entry(deleted).or_insert_with(Default::default);
+        account.storage.insert(H256::from_low_u64_be(1), H256::from_low_u64_be(1));
+        account.code = vec![1];
+
+        let mut journal = Journal::default();
+        journal.commit([(deleted, JournalAccount::Deleted)]);
+        journal.commit([(deleted, modified(&[(2, 2)], false))]);
+        let pinned = journal.pin();
+        for i in 0..MAX_LAYERS as u64 {
+            journal.commit([(addr, modified(&[(i, i + 1)], i == 1))]);
+        }
+        assert_eq!(journal.depth(), MAX_LAYERS + 2);
+        assert!(journal.layers.len() <= 3);
+
+        let view = journal.with_backend(&backend);
+        // cleared by the reset of the second layer after the pin
+        assert_eq!(view.storage(addr, H256::from_low_u64_be(0)), H256::zero());
+        assert_eq!(view.storage(addr, H256::from_low_u64_be(1)), H256::from_low_u64_be(2));
+        assert_eq!(view.storage(addr, H256::from_low_u64_be(63)), H256::from_low_u64_be(64));
+        // the deleted account doesn't read from the backend
+        assert_eq!(view.storage(deleted, H256::from_low_u64_be(1)), H256::zero());
+        assert_eq!(view.storage(deleted, H256::from_low_u64_be(2)), H256::from_low_u64_be(2));
+        assert!(view.code(deleted).is_empty());
+
+        // the layers after the pin were squashed into one, the last commit pushed another
+        assert_eq!(journal.diff_since(pinned).layers.len(), 2);
+        journal.rollback(pinned);
+        assert_eq!(journal.depth(), pinned);
+        assert!(!journal.with_backend(&backend).exists(addr));
+    }
+
+    #[test]
+    fn serializes_diffs() {
+        let vicinity = new_vicinity();
+        let addr = H160::from_low_u64_be(1);
+        let backend = MemoryBackend::new(&vicinity, Default::default());
+        let mut fixture = Journal::default();
+        let mut account = modified(&[(1, 1)], true);
+        if let JournalAccount::Modified { code, .. } = &mut account {
+            *code = Some(vec![0x60, 0x00]);
+        }
+        fixture.commit([(addr, account)]);
+        fixture.commit([(H160::from_low_u64_be(2), JournalAccount::Deleted)]);
+
+        let json = serde_json::to_string(&fixture.diff_since(0)).unwrap();
+        let diff: JournalDiff = serde_json::from_str(&json).unwrap();
+        let mut journal = Journal::default();
+        journal.apply(&diff);
+        assert_eq!(journal.depth(), 2);
+        let view = journal.with_backend(&backend);
+        assert_eq!(view.storage(addr, H256::from_low_u64_be(1)), H256::from_low_u64_be(1));
+        assert_eq!(view.code(addr), vec![0x60, 0x00]);
+        assert_eq!(view.basic(addr).balance, 1.into());
+        assert!(!view.exists(H160::from_low_u64_be(2)));
+    }
+}
@@ -5,6 +5,8 @@ mod forked_backend;
 pub use forked_backend::*;
 
 pub mod cheatcodes;
+pub mod gas_hints;
+pub mod journal;
 pub mod state;
 
 use ethers::{
@@ -13,6 +15,8 @@ use ethers::{
     types::{Address, H160, H256, U256},
 };
 
+use std::collections::BTreeSet;
+
 use sputnik::{
     backend::MemoryVicinity,
     executor::stack::{PrecompileFailure, PrecompileOutput, StackExecutor, StackState},
@@ -68,6 +72,17 @@ pub trait SputnikExecutor<S> {
     fn set_tracing_enabled(&mut self, enabled: bool) -> bool;
     fn tracing_enabled(&self) -> bool;
     fn debug_calls(&self) -> Vec<DebugArena>;
+    fn debug_enabled(&self) -> bool {
+        false
+    }
+    /// Turns on/off recording the program counters hit, returning whether it was on
+    fn set_coverage_enabled(&mut self, _enabled: bool) -> bool {
+        false
+    }
+    /// Returns the program counters hit since coverage was enabled, by the address of the code
+    fn coverage(&self) -> BTreeSet<(Address, usize)> {
+        Default::default()
+    }
     fn all_logs(&self) -> Vec<String>;
     fn gas_left(&self) -> U256;
     fn gas_used(&self) -> U256;
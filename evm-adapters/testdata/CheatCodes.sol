@@ -59,6 +59,33 @@ interface Hevm {
     function expectCall(address,bytes calldata) external;
 
     function getCode(string calldata) external returns (bytes memory);
+    // Deploys the creation code of a registered contract or of an artifact, like `getCode`
+    function deployCode(string calldata) external returns (address);
+
+    function rpcUrl(string calldata) external returns (string memory);
+    // Returns the address of the contract deployed on the current chain, from the deployments registry
+    function getDeployment(string calldata) external returns (address);
+    // Returns the EIP-712 digest of the typed data JSON, as signed by `eth_signTypedData_v4`
+    function hashTypedData(string calldata) external returns (bytes32);
+    // Sets block.chainid, the chain the calls of the following broadcasts are sent to. It can't be
+    // changed after a broadcast, since all chains would be simulated against the same state
+    function chainId(uint256) external;
+    // Records the *next* call or creation as a transaction of tx.origin, or of the input address, to be broadcast
+    function broadcast() external;
+    function broadcast(address) external;
+    // Records all subsequent calls and creations as transactions until `stopBroadcast` is called
+    function startBroadcast() external;
+    function startBroadcast(address) external;
+    function stopBroadcast() external;
+    // Reverts with a diff of the actual (first) and expected (second) value if they differ
+    function assertEq(uint256,uint256) external;
+    function assertEq(int256,int256) external;
+    function assertEq(address,address) external;
+    function assertEq(bytes32,bytes32) external;
+    function assertEq(bytes calldata,bytes calldata) external;
+    function assertEq(uint256[] calldata,uint256[] calldata) external;
+    function assertEq(int256[] calldata,int256[] calldata) external;
+    function assertEq(address[] calldata,address[] calldata) external;
 }
 
 contract HasStorage {
@@ -284,6 +311,63 @@ contract CheatCodes is DSTest {
         complexPrank.completePrankDual(prank);
     }
 
+    function testBroadcast() public {
+        Prank prank = new Prank();
+        address broadcaster = address(1337);
+        hevm.broadcast(broadcaster);
+        prank.bar(broadcaster);
+        prank.bar(address(this));
+    }
+
+    function testStartBroadcast() public {
+        address broadcaster = address(1337);
+        hevm.startBroadcast(broadcaster);
+        PrankConstructor prank = new PrankConstructor(broadcaster);
+        prank.bar(broadcaster);
+        hevm.stopBroadcast();
+        prank.bar(address(this));
+    }
+
+    function testFailBroadcastTwice() public {
+        hevm.startBroadcast();
+        hevm.broadcast();
+    }
+
+    function testChainId() public {
+        hevm.chainId(10);
+        assertEq(block.chainid, 10);
+    }
+
+    function testFailChainIdAfterBroadcast() public {
+        Prank prank = new Prank();
+        hevm.broadcast(address(1337));
+        prank.bar(address(1337));
+        hevm.chainId(10);
+    }
+
+    function testAssertEq() public {
+        hevm.assertEq(uint256(1), uint256(1));
+        hevm.assertEq(int256(-1), int256(-1));
+        hevm.assertEq(address(this), address(this));
+        hevm.assertEq(bytes32("gm"), bytes32("gm"));
+        hevm.assertEq(bytes("gm"), bytes("gm"));
+
+        uint256[] memory values = new uint256[](2);
+        values[0] = 1;
+        values[1] = 2;
+        hevm.assertEq(values, values);
+    }
+
+    function testFailAssertEqUint() public {
+        hevm.assertEq(uint256(1), uint256(2));
+    }
+
+    function testFailAssertEqArray() public {
+        uint256[] memory actual = new uint256[](2);
+        uint256[] memory expected = new uint256[](3);
+        hevm.assertEq(actual, expected);
+    }
+
     function testEtch() public {
         address rewriteCode = address(1337);
 
@@ -578,6 +662,37 @@ contract CheatCodes is DSTest {
         );
     }
 
+    function testDeployCode() public {
+        address deployed = hevm.deployCode("./testdata/Contract.json");
+        (bool success, bytes memory ret) = deployed.call(abi.encodeWithSignature("someFunc()"));
+        assertTrue(success);
+        assertEq(abi.decode(ret, (uint256)), 7);
+    }
+
+    function testFailDeployCodeUnknown() public {
+        hevm.deployCode("Unknown.huff:Unknown");
+    }
+
+    function deployCode(string memory name) public returns (address) {
+        return hevm.deployCode(name);
+    }
+
+    function testFailRpcUrlUnknown() public {
+        hevm.rpcUrl("unknown");
+    }
+
+    function rpcUrl(string memory name) public returns (string memory) {
+        return hevm.rpcUrl(name);
+    }
+
+    function testFailGetDeploymentUnknown() public {
+        hevm.getDeployment("Unknown");
+    }
+
+    function getDeployment(string memory name) public returns (address) {
+        return hevm.getDeployment(name);
+    }
+
     function getCode(address who) internal returns (bytes memory o_code) {
         assembly {
             // retrieve the size of the code, this needs assembly
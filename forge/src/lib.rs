@@ -1,12 +1,24 @@
 mod runner;
-pub use runner::{ContractRunner, TestKind, TestKindGas, TestResult};
+pub use runner::{
+    ContractRunner, CounterExample, CounterExampleParam, TestKind, TestKindGas, TestProgress,
+    TestResult,
+};
 
 mod multi_runner;
-pub use multi_runner::{MultiContractRunner, MultiContractRunnerBuilder};
+pub use multi_runner::{
+    DiscoveryReason, MultiContractRunner, MultiContractRunnerBuilder, ScopedSettings, TestDiscovery,
+};
+
+pub mod sources;
 
 pub trait TestFilter {
     fn matches_test(&self, test_name: &str) -> bool;
     fn matches_contract(&self, contract_name: &str) -> bool;
+
+    /// Returns whether the test function of the contract matches, by default whether both match
+    fn matches_contract_test(&self, contract_name: &str, test_name: &str) -> bool {
+        self.matches_contract(contract_name) && self.matches_test(test_name)
+    }
 }
 
 #[cfg(test)]
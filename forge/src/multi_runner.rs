@@ -1,6 +1,10 @@
-use crate::{runner::TestResult, ContractRunner, TestFilter};
+use crate::{
+    runner::{Fixture, FixtureKey, Fixtures, TestProgress, TestResult},
+    ContractRunner, TestFilter,
+};
 use evm_adapters::{
     evm_opts::{BackendKind, EvmOpts},
+    fuzz::FuzzBounds,
     sputnik::cheatcodes::{CONSOLE_ABI, HEVMCONSOLE_ABI, HEVM_ABI},
 };
 use sputnik::{backend::Backend, Config};
@@ -10,15 +14,79 @@ use ethers::solc::Artifact;
 use ethers::{
     abi::{Abi, Event, Function},
     prelude::ArtifactOutput,
-    solc::Project,
+    solc::{artifacts::CompactContract, Project, ProjectCompileOutput},
     types::{Address, H256, U256},
+    utils::keccak256,
 };
 
 use proptest::test_runner::TestRunner;
 
 use eyre::Result;
 use rayon::prelude::*;
-use std::collections::BTreeMap;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// Settings that replace the settings of the [`MultiContractRunner`] for the test contracts
+/// whose source file, relative to the project root, matches the `pattern`
+#[derive(Debug, Clone)]
+pub struct ScopedSettings {
+    /// The glob the source file must match
+    pub pattern: glob::Pattern,
+    /// The EVM options, the contracts of a scope share a backend
+    pub evm_opts: EvmOpts,
+    /// The EVM revision config, `None` to use the runner's config
+    pub evm_cfg: Option<Config>,
+    /// The fuzzer, `None` to use the runner's fuzzer
+    pub fuzzer: Option<TestRunner>,
+}
+
+/// The scheme used to tell the test contracts apart from the other contracts of the project
+///
+/// Without any base or glob, every contract with `test` functions is a test contract, otherwise
+/// only the contracts with `test` functions that inherit one of the bases or whose source file
+/// matches one of the globs are.
+#[derive(Debug, Clone, Default)]
+pub struct TestDiscovery {
+    /// The contracts, like `DSTest`, that test contracts inherit directly or transitively
+    pub bases: Vec<String>,
+    /// The globs the source files of test contracts match, relative to the project root
+    pub globs: Vec<glob::Pattern>,
+}
+
+impl TestDiscovery {
+    /// Whether every contract with `test` functions is a test contract
+    pub fn is_empty(&self) -> bool {
+        self.bases.is_empty() && self.globs.is_empty()
+    }
+}
+
+/// Why a contract was discovered as a test contract
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiscoveryReason {
+    /// The contract has `test` functions and no discovery scheme is configured
+    TestFunctions,
+    /// The contract inherits the base
+    Inherits(String),
+    /// The source file of the contract matches the glob
+    MatchesGlob(String),
+}
+
+impl fmt::Display for DiscoveryReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiscoveryReason::TestFunctions => write!(f, "has `test` functions"),
+            DiscoveryReason::Inherits(base) => write!(f, "inherits `{}`", base),
+            DiscoveryReason::MatchesGlob(glob) => write!(f, "matches `{}`", glob),
+        }
+    }
+}
 
 /// Builder used for instantiating the multi-contract runner
 #[derive(Debug, Default)]
@@ -32,56 +100,211 @@ pub struct MultiContractRunnerBuilder {
     pub initial_balance: U256,
     /// The EVM Configuration to use
     pub evm_cfg: Option<Config>,
+    /// Settings for the test contracts of path globs, the first matching scope applies
+    pub scoped: Vec<ScopedSettings>,
+    /// Directories of precompiled Hardhat or Truffle artifacts whose contracts are identified in
+    /// traces
+    pub external_artifacts: Vec<PathBuf>,
+    /// How the test contracts are discovered
+    pub discovery: TestDiscovery,
+    /// The `vyper` binary the `.vy` sources are compiled with, they're skipped if it isn't set
+    pub vyper: Option<PathBuf>,
+    /// Contracts compiled outside of forge, like with Huff, by name, which are identified in
+    /// traces and can be deployed with the `deployCode` cheatcode
+    pub bytecode_contracts: BTreeMap<String, CompactContract>,
+    /// The directory the corpus of every fuzz test is kept in, by test contract
+    pub fuzz_corpus: Option<PathBuf>,
+    /// The bounds of the fuzzed values of dynamic types
+    pub fuzz_bounds: FuzzBounds,
+    /// The directory the state changes of the fixtures are kept in across runs
+    pub fixture_cache: Option<PathBuf>,
 }
 
 impl MultiContractRunnerBuilder {
     /// Given an EVM, proceeds to return a runner which is able to execute all tests
     /// against that evm
-    pub fn build<A>(self, project: Project<A>, evm_opts: EvmOpts) -> Result<MultiContractRunner>
+    pub fn build<A>(self, mut project: Project<A>, evm_opts: EvmOpts) -> Result<MultiContractRunner>
     where
         // TODO: Can we remove the static? It's due to the `into_artifacts()` call below
         A: ArtifactOutput + 'static,
+        ProjectCompileOutput<A>: Clone,
     {
-        println!("compiling...");
+        self.prepare_project(&mut project)?;
         let output = project.compile()?;
         if output.has_compiler_errors() {
             // return the diagnostics error back to the user.
             eyre::bail!(output.to_string())
-        } else if output.is_unchanged() {
-            println!("no files changed, compilation skipped.");
-        } else {
-            println!("success.");
         }
+        self.build_with_output(&project, output, evm_opts)
+    }
+
+    /// Whether the runner needs the AST of every source, to resolve the scoped settings and
+    /// discovery globs or to share the fixtures
+    pub fn needs_source_units<A: ArtifactOutput>(&self, project: &Project<A>) -> Result<bool> {
+        if !self.scoped.is_empty() || !self.discovery.globs.is_empty() {
+            return Ok(true)
+        }
+        let re = Regex::new(r"function\s+fixture").unwrap();
+        Ok(project.sources()?.values().any(|source| re.is_match(&source.content)))
+    }
+
+    /// Prepares the project for [`Self::build_with_output()`]: if the runner needs the source
+    /// units, every source is compiled without the cache and emits its AST
+    pub fn prepare_project<A: ArtifactOutput>(&self, project: &mut Project<A>) -> Result<()> {
+        if self.needs_source_units(project)? {
+            project.cached = false;
+            project
+                .solc_config
+                .settings
+                .output_selection
+                .entry("*".to_string())
+                .or_default()
+                .insert("".to_string(), vec!["ast".to_string()]);
+        }
+        Ok(())
+    }
+
+    /// Returns a runner for the `output` of the project, which was compiled beforehand with the
+    /// project prepared by [`Self::prepare_project()`]
+    pub fn build_with_output<A>(
+        self,
+        project: &Project<A>,
+        output: ProjectCompileOutput<A>,
+        evm_opts: EvmOpts,
+    ) -> Result<MultiContractRunner>
+    where
+        A: ArtifactOutput + 'static,
+        ProjectCompileOutput<A>: Clone,
+    {
+        // the source units are only needed to resolve the scoped settings and discovery globs,
+        // and the declaring contracts of the fixtures to share them
+        let units = if self.needs_source_units(project)? {
+            source_units(output.clone())?
+        } else {
+            Default::default()
+        };
+
+        // the Yul and Vyper contracts are tested and identified alongside the Solidity ones
+        let compiled = crate::sources::compile_sources(project, self.vyper.as_deref())?;
 
         // This is just the contracts compiled, but we need to merge this with the read cached
         // artifacts
-        let contracts = output.into_artifacts();
+        let contracts = output
+            .into_artifacts()
+            .map(|(fname, contract)| (fname, contract.into_parts()))
+            .chain(compiled.iter().map(|source| {
+                let CompactContract { abi, bin, bin_runtime } = source.contract.clone();
+                let bin = bin.and_then(|bin| bin.into_bytes());
+                let bin_runtime = bin_runtime.and_then(|bin| bin.into_bytes());
+                (source.id(), (abi, bin, bin_runtime))
+            }))
+            .collect::<Vec<_>>();
+
+        let mut source_files = contract_sources(&units, &project.paths.root);
+        source_files.extend(compiled.into_iter().map(|source| (source.id(), source.path)));
+        let fixture_contracts = fixture_contracts(&units, &project.paths.root);
+        let bases = if self.discovery.bases.is_empty() {
+            Default::default()
+        } else {
+            contract_bases(project)?
+        };
+        self.build_from_parts(contracts, source_files, bases, fixture_contracts, evm_opts)
+    }
+
+    /// Returns a runner for contracts that were compiled beforehand, like the contracts of a
+    /// dapptools build, without compiling the project
+    ///
+    /// The contracts are keyed by `<source file>:<name>`.
+    pub fn build_precompiled(
+        self,
+        contracts: BTreeMap<String, CompactContract>,
+        evm_opts: EvmOpts,
+    ) -> Result<MultiContractRunner> {
+        let source_files = contracts
+            .keys()
+            .filter_map(|fname| Some((fname.clone(), PathBuf::from(fname.rsplit_once(':')?.0))))
+            .collect();
+        let contracts = contracts.into_iter().map(|(fname, contract)| {
+            let CompactContract { abi, bin, bin_runtime, .. } = contract;
+            let bin = bin.and_then(|bin| bin.into_bytes());
+            let bin_runtime = bin_runtime.and_then(|bin| bin.into_bytes());
+            (fname, (abi, bin, bin_runtime))
+        });
+        self.build_from_parts(
+            contracts,
+            source_files,
+            Default::default(),
+            Default::default(),
+            evm_opts,
+        )
+    }
+
+    /// Returns a runner for the abi, creation and runtime bytecode of the contracts
+    #[allow(clippy::type_complexity)]
+    fn build_from_parts(
+        mut self,
+        contracts: impl IntoIterator<
+            Item = (
+                String,
+                (Option<Abi>, Option<ethers::prelude::Bytes>, Option<ethers::prelude::Bytes>),
+            ),
+        >,
+        source_files: BTreeMap<String, PathBuf>,
+        bases: BTreeMap<String, Vec<String>>,
+        fixture_contracts: BTreeMap<String, BTreeMap<String, String>>,
+        mut evm_opts: EvmOpts,
+    ) -> Result<MultiContractRunner> {
         let mut known_contracts: BTreeMap<String, (Abi, Vec<u8>)> = Default::default();
         let mut deployable_contracts: BTreeMap<String, (Abi, ethers::prelude::Bytes)> =
             Default::default();
+        let mut discovered = BTreeMap::new();
 
-        for (fname, contract) in contracts {
-            let (maybe_abi, maybe_deploy_bytes, maybe_runtime_bytes) = contract.into_parts();
+        for (fname, (maybe_abi, maybe_deploy_bytes, maybe_runtime_bytes)) in contracts {
             if let (Some(abi), Some(bytecode)) = (maybe_abi, maybe_deploy_bytes) {
                 // skip deployment of abstract contracts
                 if bytecode.as_ref().is_empty() {
                     continue
                 }
 
+                let split = fname.split(':').collect::<Vec<&str>>();
+                let contract_name = if split.len() > 1 { split[1] } else { split[0] };
+
                 if abi.constructor.as_ref().map(|c| c.inputs.is_empty()).unwrap_or(true) &&
                     abi.functions().any(|func| func.name.starts_with("test"))
                 {
-                    deployable_contracts.insert(fname.clone(), (abi.clone(), bytecode.clone()));
+                    if let Some(reason) = self.discover(&fname, &source_files, &bases) {
+                        deployable_contracts.insert(fname.clone(), (abi.clone(), bytecode.clone()));
+                        discovered.insert(fname.clone(), reason);
+                    }
                 }
 
-                let split = fname.split(':').collect::<Vec<&str>>();
-                let contract_name = if split.len() > 1 { split[1] } else { split[0] };
                 if let Some(runtime_code) = maybe_runtime_bytes {
                     known_contracts.insert(contract_name.to_string(), (abi, runtime_code.to_vec()));
                 }
             }
         }
 
+        // compiled contracts take precedence over precompiled ones of the same name
+        foundry_utils::HardhatArtifact::extend_known_contracts(
+            &mut known_contracts,
+            &self.external_artifacts,
+        )?;
+
+        for (name, contract) in &self.bytecode_contracts {
+            let abi = contract.abi.clone().unwrap_or_default();
+            if let Some(code) = contract.bin.clone().and_then(|code| code.into_bytes()) {
+                // the scoped settings carry their own options
+                for opts in std::iter::once(&mut evm_opts)
+                    .chain(self.scoped.iter_mut().map(|s| &mut s.evm_opts))
+                {
+                    opts.bytecode_contracts.insert(name.clone(), code.to_vec());
+                }
+            }
+            if let Some(code) = contract.bin_runtime.clone().and_then(|code| code.into_bytes()) {
+                known_contracts.entry(name.clone()).or_insert((abi, code.to_vec()));
+            }
+        }
+
         // add forge+sputnik specific contracts
         known_contracts.insert("VM".to_string(), (HEVM_ABI.clone(), Vec::new()));
         known_contracts.insert("VM_CONSOLE".to_string(), (HEVMCONSOLE_ABI.clone(), Vec::new()));
@@ -90,16 +313,49 @@ impl MultiContractRunnerBuilder {
         let execution_info = foundry_utils::flatten_known_contracts(&known_contracts);
         Ok(MultiContractRunner {
             contracts: deployable_contracts,
+            discovered,
             known_contracts,
             identified_contracts: Default::default(),
             evm_opts,
             evm_cfg: self.evm_cfg.unwrap_or_else(Config::london),
             sender: self.sender,
             fuzzer: self.fuzzer,
+            fixtures: (0..=self.scoped.len()).map(|_| Default::default()).collect(),
+            fixture_contracts,
+            fixture_cache: self.fixture_cache,
+            scoped: self.scoped,
+            source_files,
+            fuzz_corpus: self.fuzz_corpus,
+            fuzz_bounds: self.fuzz_bounds,
             execution_info,
+            progress: None,
         })
     }
 
+    /// Returns why the contract with `test` functions is a test contract, `None` if it isn't one
+    fn discover(
+        &self,
+        fname: &str,
+        source_files: &BTreeMap<String, PathBuf>,
+        bases: &BTreeMap<String, Vec<String>>,
+    ) -> Option<DiscoveryReason> {
+        if self.discovery.is_empty() {
+            return Some(DiscoveryReason::TestFunctions)
+        }
+        let contract_name = fname.split(':').last()?;
+        if let Some(base) =
+            self.discovery.bases.iter().find(|base| inherits(contract_name, base, bases))
+        {
+            return Some(DiscoveryReason::Inherits(base.clone()))
+        }
+        let source = source_file(source_files, fname)?;
+        self.discovery
+            .globs
+            .iter()
+            .find(|glob| glob.matches_path(source))
+            .map(|glob| DiscoveryReason::MatchesGlob(glob.as_str().to_string()))
+    }
+
     #[must_use]
     pub fn sender(mut self, sender: Address) -> Self {
         self.sender = Some(sender);
@@ -118,11 +374,240 @@ impl MultiContractRunnerBuilder {
         self
     }
 
+    #[must_use]
+    pub fn fuzz_corpus(mut self, dir: Option<PathBuf>) -> Self {
+        self.fuzz_corpus = dir;
+        self
+    }
+
+    #[must_use]
+    pub fn fixture_cache(mut self, dir: Option<PathBuf>) -> Self {
+        self.fixture_cache = dir;
+        self
+    }
+
+    #[must_use]
+    pub fn fuzz_bounds(mut self, bounds: FuzzBounds) -> Self {
+        self.fuzz_bounds = bounds;
+        self
+    }
+
     #[must_use]
     pub fn evm_cfg(mut self, evm_cfg: Config) -> Self {
         self.evm_cfg = Some(evm_cfg);
         self
     }
+
+    #[must_use]
+    pub fn scoped(mut self, settings: ScopedSettings) -> Self {
+        self.scoped.push(settings);
+        self
+    }
+
+    #[must_use]
+    pub fn external_artifacts(mut self, dirs: Vec<PathBuf>) -> Self {
+        self.external_artifacts = dirs;
+        self
+    }
+
+    #[must_use]
+    pub fn vyper(mut self, vyper: Option<PathBuf>) -> Self {
+        self.vyper = vyper;
+        self
+    }
+
+    #[must_use]
+    pub fn bytecode_contracts(mut self, contracts: BTreeMap<String, CompactContract>) -> Self {
+        self.bytecode_contracts = contracts;
+        self
+    }
+
+    #[must_use]
+    pub fn discovery(mut self, discovery: TestDiscovery) -> Self {
+        self.discovery = discovery;
+        self
+    }
+}
+
+/// Returns the AST of every source unit of the compiler output by its file, the sources that
+/// weren't compiled because they're cached have none
+fn source_units<A: ArtifactOutput>(
+    output: ProjectCompileOutput<A>,
+) -> Result<BTreeMap<PathBuf, Value>> {
+    let sources = serde_json::to_value(&output.output().sources)?;
+    Ok(sources
+        .as_object()
+        .into_iter()
+        .flatten()
+        .map(|(file, source)| (PathBuf::from(file), source["ast"].clone()))
+        .collect())
+}
+
+/// Returns the source file, relative to the project root, of every contract declared in the
+/// source units by `<source file>:<name>`
+fn contract_sources(units: &BTreeMap<PathBuf, Value>, root: &Path) -> BTreeMap<String, PathBuf> {
+    let mut contracts = BTreeMap::new();
+    for (file, ast) in units {
+        let path = file.strip_prefix(root).map(Path::to_path_buf).unwrap_or_else(|_| file.clone());
+        for node in ast["nodes"].as_array().into_iter().flatten() {
+            if node["nodeType"] != "ContractDefinition" {
+                continue
+            }
+            if let Some(name) = node["name"].as_str() {
+                contracts.insert(format!("{}:{}", path.display(), name), path.clone());
+            }
+        }
+    }
+    contracts
+}
+
+/// Returns the source file of the contract by its `<source file>:<name>`, or by its name if only
+/// one source file declares a contract of that name
+///
+/// The artifacts are named after the contract only, so their source file is ambiguous if
+/// several source files declare a contract of the same name.
+fn source_file<'a>(source_files: &'a BTreeMap<String, PathBuf>, fname: &str) -> Option<&'a Path> {
+    if let Some(path) = source_files.get(fname) {
+        return Some(path.as_path())
+    }
+    let name = fname.split(':').last()?;
+    let mut declared = source_files
+        .iter()
+        .filter(|(id, _)| id.rsplit_once(':').map(|(_, declared)| declared) == Some(name));
+    let (_, path) = declared.next()?;
+    declared.next().is_none().then(|| path.as_path())
+}
+
+/// Returns the contract that declares each fixture of every contract declared in the source units,
+/// by the signature of the fixture
+///
+/// A fixture is declared by the most derived contract of the linearized inheritance that defines
+/// it, in the form of `<source file>:<name>`, so overridden fixtures aren't shared with the
+/// contracts that inherit the original.
+fn fixture_contracts(
+    units: &BTreeMap<PathBuf, Value>,
+    root: &Path,
+) -> BTreeMap<String, BTreeMap<String, String>> {
+    struct Definition {
+        id: String,
+        name: String,
+        bases: Vec<u64>,
+        fixtures: BTreeSet<String>,
+    }
+
+    let mut definitions = BTreeMap::new();
+    for (file, ast) in units {
+        let path = file.strip_prefix(root).unwrap_or(file.as_path());
+        for node in ast["nodes"].as_array().into_iter().flatten() {
+            let (ast_id, name) = match (node["id"].as_u64(), node["name"].as_str()) {
+                (Some(ast_id), Some(name)) if node["nodeType"] == "ContractDefinition" => {
+                    (ast_id, name)
+                }
+                _ => continue,
+            };
+            let fixtures = node["nodes"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter(|func| {
+                    func["nodeType"] == "FunctionDefinition" &&
+                        func["kind"] == "function" &&
+                        func["parameters"]["parameters"].as_array().map_or(true, Vec::is_empty)
+                })
+                .filter_map(|func| func["name"].as_str())
+                .filter(|name| name.starts_with("fixture"))
+                .map(|name| format!("{}()", name))
+                .collect();
+            let bases = node["linearizedBaseContracts"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_u64)
+                .collect();
+            definitions.insert(
+                ast_id,
+                Definition {
+                    id: format!("{}:{}", path.display(), name),
+                    name: name.to_string(),
+                    bases,
+                    fixtures,
+                },
+            );
+        }
+    }
+
+    let mut contracts = BTreeMap::new();
+    for definition in definitions.values() {
+        let mut declared = BTreeMap::new();
+        // the linearized bases start with the contract itself
+        for base in definition.bases.iter().filter_map(|base| definitions.get(base)) {
+            for signature in &base.fixtures {
+                declared.entry(signature.clone()).or_insert_with(|| base.id.clone());
+            }
+        }
+        if !declared.is_empty() {
+            contracts.insert(definition.name.clone(), declared);
+        }
+    }
+    contracts
+}
+
+/// Returns the contracts every contract declared in the sources of the project directly inherits
+fn contract_bases<A: ArtifactOutput>(
+    project: &Project<A>,
+) -> Result<BTreeMap<String, Vec<String>>> {
+    let re = Regex::new(
+        r"(?m)^[ \t]*(?:abstract[ \t]+)?contract[ \t]+([a-zA-Z0-9_$]+)\s+is\s+([^{]+)\{",
+    )
+    .unwrap();
+    let mut contracts = BTreeMap::new();
+    for (_, source) in project.sources()? {
+        for cap in re.captures_iter(&source.content) {
+            contracts.insert(cap[1].to_string(), parse_bases(&cap[2]));
+        }
+    }
+    Ok(contracts)
+}
+
+/// Returns the names of the contracts of an inheritance list like `A, B(1, 2), Lib.C`
+fn parse_bases(list: &str) -> Vec<String> {
+    let mut bases = Vec::new();
+    let mut depth = 0usize;
+    let mut current = String::new();
+    for c in list.chars().chain(std::iter::once(',')) {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                let name = current.trim().rsplit('.').next().unwrap_or_default().trim();
+                if !name.is_empty() {
+                    bases.push(name.to_string());
+                }
+                current.clear();
+            }
+            _ if depth == 0 => current.push(c),
+            _ => {}
+        }
+    }
+    bases
+}
+
+/// Whether the contract inherits the base, directly or through its own bases
+fn inherits(contract: &str, base: &str, bases: &BTreeMap<String, Vec<String>>) -> bool {
+    let mut visited = BTreeSet::new();
+    let mut stack = vec![contract];
+    while let Some(name) = stack.pop() {
+        if !visited.insert(name) {
+            continue
+        }
+        for parent in bases.get(name).into_iter().flatten() {
+            if parent == base {
+                return true
+            }
+            stack.push(parent);
+        }
+    }
+    false
 }
 
 /// A multi contract runner receives a set of contracts deployed in an EVM instance and proceeds
@@ -130,6 +615,8 @@ impl MultiContractRunnerBuilder {
 pub struct MultiContractRunner {
     /// Mapping of contract name to Abi and creation bytecode
     pub contracts: BTreeMap<String, (Abi, ethers::prelude::Bytes)>,
+    /// Why each of the `contracts` was discovered as a test contract
+    pub discovered: BTreeMap<String, DiscoveryReason>,
     /// Compiled contracts by name that have an Abi and runtime bytecode
     pub known_contracts: BTreeMap<String, (Abi, Vec<u8>)>,
     /// Identified contracts by test
@@ -144,6 +631,23 @@ pub struct MultiContractRunner {
     fuzzer: Option<TestRunner>,
     /// The address which will be used as the `from` field in all EVM calls
     sender: Option<Address>,
+    /// Settings for the test contracts of path globs
+    scoped: Vec<ScopedSettings>,
+    /// The source file of every contract by `<source file>:<name>`, relative to the project root
+    source_files: BTreeMap<String, PathBuf>,
+    /// The directory the corpus of every fuzz test is kept in, by test contract
+    fuzz_corpus: Option<PathBuf>,
+    /// The bounds of the fuzzed values of dynamic types
+    fuzz_bounds: FuzzBounds,
+    /// The state changes of the fixtures, shared by the test contracts of the same settings: the
+    /// unscoped ones first, then those of every scope
+    fixtures: Vec<Fixtures>,
+    /// The contract that declares each fixture by its signature, by test contract name
+    fixture_contracts: BTreeMap<String, BTreeMap<String, String>>,
+    /// The directory the state changes of the fixtures are kept in across runs
+    fixture_cache: Option<PathBuf>,
+    /// Receives the progress of the test run
+    pub progress: Option<Arc<dyn TestProgress>>,
 }
 
 impl MultiContractRunner {
@@ -154,33 +658,180 @@ impl MultiContractRunner {
         // TODO: Convert to iterator, ideally parallel one?
         let contracts = std::mem::take(&mut self.contracts);
 
-        let vicinity = self.evm_opts.vicinity()?;
-        let backend = self.evm_opts.backend(&vicinity)?;
+        // the contracts of a scope share the same backend
+        let mut scopes: BTreeMap<Option<usize>, Vec<_>> = BTreeMap::new();
+        for (name, contract) in contracts.iter().filter(|(name, _)| filter.matches_contract(name)) {
+            scopes.entry(self.scope(name)).or_default().push((name, contract));
+        }
 
-        let results = contracts
-            .par_iter()
-            .filter(|(name, _)| filter.matches_contract(name))
-            .map(|(name, (abi, deploy_code))| {
-                // unavoidable duplication here?
-                let result = match backend {
-                    BackendKind::Simple(ref backend) => {
-                        self.run_tests(name, abi, backend, deploy_code.clone(), filter)?
-                    }
-                    BackendKind::Shared(ref backend) => {
-                        self.run_tests(name, abi, backend, deploy_code.clone(), filter)?
-                    }
-                };
-                Ok((name.clone(), result))
-            })
-            .filter_map(|x: Result<_>| x.ok())
-            .filter_map(|(name, res)| if res.is_empty() { None } else { Some((name, res)) })
-            .collect::<BTreeMap<_, _>>();
+        let mut results = BTreeMap::new();
+        for (scope, scope_contracts) in scopes {
+            let fixtures = &self.fixtures[scope.map_or(0, |scope| scope + 1)];
+            let cache = self.fixture_cache(scope, &contracts);
+            if let Some((ref file, fingerprint)) = cache {
+                let mut fixtures = fixtures.lock().unwrap();
+                if fixtures.is_empty() {
+                    *fixtures = read_fixtures(file, fingerprint).unwrap_or_default();
+                }
+            }
+
+            let (evm_opts, _, _) = self.settings(scope);
+            let vicinity = evm_opts.vicinity()?;
+            let backend = evm_opts.backend_with_progress(&vicinity, |fetched, total| {
+                if let Some(ref progress) = self.progress {
+                    progress.fork_state_fetched(fetched, total)
+                }
+            })?;
+
+            results.extend(
+                scope_contracts
+                    .par_iter()
+                    .map(|(name, (abi, deploy_code))| {
+                        // unavoidable duplication here?
+                        let result = match backend {
+                            BackendKind::Simple(ref backend) => self.run_tests(
+                                name,
+                                abi,
+                                scope,
+                                backend,
+                                deploy_code.clone(),
+                                filter,
+                            )?,
+                            BackendKind::Shared(ref backend) => self.run_tests(
+                                name,
+                                abi,
+                                scope,
+                                backend,
+                                deploy_code.clone(),
+                                filter,
+                            )?,
+                        };
+                        Ok(((*name).clone(), result))
+                    })
+                    .filter_map(|x: Result<_>| x.ok())
+                    .filter_map(|(name, res)| if res.is_empty() { None } else { Some((name, res)) })
+                    .collect::<Vec<_>>(),
+            );
+
+            if let Some((file, fingerprint)) = cache {
+                if let Err(err) = write_fixtures(&file, fingerprint, &fixtures.lock().unwrap()) {
+                    tracing::warn!(?err, "failed to write the fixtures to {}", file.display());
+                }
+            }
+        }
 
         self.contracts = contracts;
 
         Ok(results)
     }
 
+    /// Sets the EVM revision config of the tests that are run from now on, except for the scoped
+    /// settings with their own config
+    ///
+    /// The state of the fixtures executed so far is discarded since it depends on the config.
+    pub fn set_evm_cfg(&mut self, evm_cfg: Config) {
+        self.evm_cfg = evm_cfg;
+        for fixtures in &mut self.fixtures {
+            fixtures.get_mut().unwrap().clear();
+        }
+    }
+
+    /// Records the debug steps of the tests that are run from now on, and the traces that the
+    /// contracts of the steps are identified by
+    pub fn enable_debug(&mut self) {
+        let scoped = self.scoped.iter_mut().map(|settings| &mut settings.evm_opts);
+        for evm_opts in std::iter::once(&mut self.evm_opts).chain(scoped) {
+            evm_opts.debug = true;
+            evm_opts.verbosity = evm_opts.verbosity.max(3);
+        }
+    }
+
+    /// Stops recording the debug steps of the tests, restoring the `verbosity` of the runner
+    /// before [`enable_debug`](Self::enable_debug)
+    pub fn disable_debug(&mut self, verbosity: u8) {
+        let scoped = self.scoped.iter_mut().map(|settings| &mut settings.evm_opts);
+        for evm_opts in std::iter::once(&mut self.evm_opts).chain(scoped) {
+            evm_opts.debug = false;
+            evm_opts.verbosity = verbosity;
+        }
+    }
+
+    /// Returns the test contracts matching the filter with why they were discovered and the
+    /// signatures of their test functions, without running them
+    pub fn list(
+        &self,
+        filter: &impl TestFilter,
+    ) -> BTreeMap<String, (DiscoveryReason, Vec<String>)> {
+        self.contracts
+            .iter()
+            .filter(|(name, _)| filter.matches_contract(name))
+            .map(|(name, (abi, _))| {
+                let tests = abi
+                    .functions()
+                    .filter(|func| func.name.starts_with("test"))
+                    .filter(|func| filter.matches_contract_test(name, &func.name))
+                    .map(|func| func.signature())
+                    .collect();
+                let reason =
+                    self.discovered.get(name).cloned().unwrap_or(DiscoveryReason::TestFunctions);
+                (name.clone(), (reason, tests))
+            })
+            .collect()
+    }
+
+    /// Returns the index of the first scoped settings whose glob matches the source file of the
+    /// contract
+    fn scope(&self, name: &str) -> Option<usize> {
+        let source = source_file(&self.source_files, name)?;
+        self.scoped.iter().position(|settings| settings.pattern.matches_path(source))
+    }
+
+    /// Returns the file the fixtures of the scope are kept in and the hash of the settings and
+    /// contracts their state changes depend on, `None` if they aren't kept or depend on the latest
+    /// block of a fork
+    fn fixture_cache(
+        &self,
+        scope: Option<usize>,
+        contracts: &BTreeMap<String, (Abi, ethers::prelude::Bytes)>,
+    ) -> Option<(PathBuf, H256)> {
+        let dir = self.fixture_cache.as_ref()?;
+        let (evm_opts, evm_cfg, _) = self.settings(scope);
+        if evm_opts.fork_url.is_some() && evm_opts.fork_block_number.is_none() {
+            return None
+        }
+        // the verbosity doesn't change the state
+        let mut evm_opts = evm_opts.clone();
+        evm_opts.verbosity = 0;
+        evm_opts.debug = false;
+        let mut fingerprint = serde_json::to_vec(&evm_opts).ok()?;
+        fingerprint.extend(format!("{:?}", evm_cfg).as_bytes());
+        for (name, (_, code)) in contracts {
+            fingerprint.extend(name.as_bytes());
+            fingerprint.extend(code.as_ref());
+        }
+        for (name, (_, code)) in &self.known_contracts {
+            fingerprint.extend(name.as_bytes());
+            fingerprint.extend(code);
+        }
+        let file = match scope {
+            Some(scope) => format!("fixtures-{}.json", scope),
+            None => "fixtures.json".to_string(),
+        };
+        Some((dir.join(file), keccak256(fingerprint).into()))
+    }
+
+    /// Returns the evm options, evm config and fuzzer of the scope
+    fn settings(&self, scope: Option<usize>) -> (&EvmOpts, &Config, Option<&TestRunner>) {
+        match scope.map(|idx| &self.scoped[idx]) {
+            Some(settings) => (
+                &settings.evm_opts,
+                settings.evm_cfg.as_ref().unwrap_or(&self.evm_cfg),
+                settings.fuzzer.as_ref().or(self.fuzzer.as_ref()),
+            ),
+            None => (&self.evm_opts, &self.evm_cfg, self.fuzzer.as_ref()),
+        }
+    }
+
     // The _name field is unused because we only want it for tracing
     #[tracing::instrument(
         name = "contract",
@@ -190,22 +841,84 @@ impl MultiContractRunner {
     )]
     fn run_tests<B: Backend + Clone + Send + Sync>(
         &self,
-        _name: &str,
+        name: &str,
         contract: &Abi,
+        scope: Option<usize>,
         backend: &B,
         deploy_code: ethers::prelude::Bytes,
         filter: &impl TestFilter,
     ) -> Result<BTreeMap<String, TestResult>> {
+        let (evm_opts, evm_cfg, fuzzer) = self.settings(scope);
         let runner = ContractRunner::new(
-            &self.evm_opts,
-            &self.evm_cfg,
+            evm_opts,
+            evm_cfg,
             backend,
             contract,
             deploy_code,
             self.sender,
             Some((&self.execution_info.0, &self.execution_info.1, &self.execution_info.2)),
+        )
+        .with_progress(self.progress.as_deref())
+        .with_corpus(self.fuzz_corpus.as_ref().map(|dir| dir.join(name)))
+        .with_fuzz_bounds(self.fuzz_bounds.clone())
+        .with_fixtures(
+            self.fixtures.get(scope.map_or(0, |scope| scope + 1)),
+            name.split(':')
+                .last()
+                .and_then(|name| self.fixture_contracts.get(name))
+                .cloned()
+                .unwrap_or_default(),
         );
-        runner.run_tests(filter, self.fuzzer.clone(), Some(&self.known_contracts))
+        let filter = ContractFilter { filter, contract: name };
+        runner.run_tests(&filter, fuzzer.cloned(), Some(&self.known_contracts))
+    }
+}
+
+/// The state changes of the fixtures of a scope kept on disk across runs
+#[derive(Debug, Serialize, Deserialize)]
+struct FixtureCache {
+    /// The hash of the settings and contracts the state changes depend on
+    fingerprint: H256,
+    fixtures: Vec<(FixtureKey, Fixture)>,
+}
+
+/// Reads the fixtures kept in the file, `None` if there are none or they were kept for another
+/// fingerprint
+fn read_fixtures(file: &Path, fingerprint: H256) -> Option<BTreeMap<FixtureKey, Fixture>> {
+    let cache: FixtureCache = serde_json::from_slice(&std::fs::read(file).ok()?).ok()?;
+    (cache.fingerprint == fingerprint).then(|| cache.fixtures.into_iter().collect())
+}
+
+/// Keeps the fixtures in the file for later runs with the same fingerprint
+fn write_fixtures(
+    file: &Path,
+    fingerprint: H256,
+    fixtures: &BTreeMap<FixtureKey, Fixture>,
+) -> Result<()> {
+    if fixtures.is_empty() {
+        return Ok(())
+    }
+    if let Some(parent) = file.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let fixtures = fixtures.iter().map(|(key, fixture)| (key.clone(), fixture.clone())).collect();
+    std::fs::write(file, serde_json::to_vec(&FixtureCache { fingerprint, fixtures })?)?;
+    Ok(())
+}
+
+/// Matches the test functions of a single contract with a [`TestFilter`]
+struct ContractFilter<'a, F> {
+    filter: &'a F,
+    contract: &'a str,
+}
+
+impl<'a, F: TestFilter> TestFilter for ContractFilter<'a, F> {
+    fn matches_test(&self, test_name: &str) -> bool {
+        self.filter.matches_contract_test(self.contract, test_name)
+    }
+
+    fn matches_contract(&self, contract_name: &str) -> bool {
+        self.filter.matches_contract(contract_name)
     }
 }
 
@@ -268,6 +981,88 @@ mod tests {
         assert!(results.get("BTests.json:BTests").is_some());
     }
 
+    #[test]
+    fn finds_contract_sources() {
+        let root = Path::new("/project");
+        let units = BTreeMap::from([(
+            root.join("src/Vault.sol"),
+            serde_json::json!({
+                "nodeType": "SourceUnit",
+                "nodes": [
+                    { "nodeType": "PragmaDirective", "literals": ["solidity", "^", "0.8", ".10"] },
+                    { "nodeType": "ContractDefinition", "name": "IVault", "abstract": false },
+                    { "nodeType": "ContractDefinition", "name": "Vault", "abstract": true }
+                ]
+            }),
+        )]);
+        let mut sources = contract_sources(&units, root);
+        assert_eq!(sources.len(), 2);
+        assert_eq!(sources["src/Vault.sol:Vault"], PathBuf::from("src/Vault.sol"));
+        assert_eq!(sources["src/Vault.sol:IVault"], PathBuf::from("src/Vault.sol"));
+        assert_eq!(source_file(&sources, "Vault.json:Vault"), Some(Path::new("src/Vault.sol")));
+
+        // a contract name declared in several source files is ambiguous
+        sources.insert("test/Vault.t.sol:Vault".to_string(), PathBuf::from("test/Vault.t.sol"));
+        assert_eq!(source_file(&sources, "Vault.json:Vault"), None);
+        assert_eq!(
+            source_file(&sources, "test/Vault.t.sol:Vault"),
+            Some(Path::new("test/Vault.t.sol"))
+        );
+    }
+
+    #[test]
+    fn finds_fixture_contracts() {
+        let root = Path::new("/project");
+        let fixture = |name: &str| {
+            serde_json::json!({
+                "nodeType": "FunctionDefinition",
+                "kind": "function",
+                "name": name,
+                "parameters": { "parameters": [] }
+            })
+        };
+        let contract = |id: u64, name: &str, bases: &[u64], nodes: Vec<Value>| {
+            serde_json::json!({
+                "nodeType": "ContractDefinition",
+                "id": id,
+                "name": name,
+                "linearizedBaseContracts": bases,
+                "nodes": nodes
+            })
+        };
+        let units = BTreeMap::from([(
+            root.join("test/Vault.t.sol"),
+            serde_json::json!({
+                "nodeType": "SourceUnit",
+                "nodes": [
+                    contract(1, "Base", &[1], vec![fixture("fixtureProtocol"), fixture("setUp")]),
+                    contract(2, "ATest", &[2, 1], vec![fixture("testA")]),
+                    contract(3, "BTest", &[3, 1], vec![fixture("fixtureProtocol")])
+                ]
+            }),
+        )]);
+        let contracts = fixture_contracts(&units, root);
+        assert_eq!(contracts["ATest"]["fixtureProtocol()"], "test/Vault.t.sol:Base");
+        assert_eq!(contracts["Base"]["fixtureProtocol()"], "test/Vault.t.sol:Base");
+        // overridden fixtures aren't shared
+        assert_eq!(contracts["BTest"]["fixtureProtocol()"], "test/Vault.t.sol:BTest");
+        assert_eq!(contracts["ATest"].len(), 1);
+    }
+
+    #[test]
+    fn can_parse_bases() {
+        assert_eq!(parse_bases("A, B(1, f(2)), Lib.C "), vec!["A", "B", "C"]);
+        assert_eq!(parse_bases("DSTest"), vec!["DSTest"]);
+
+        let bases = BTreeMap::from([
+            ("ATest".to_string(), vec!["Base".to_string()]),
+            ("Base".to_string(), vec!["DSTest".to_string()]),
+        ]);
+        assert!(inherits("ATest", "DSTest", &bases));
+        assert!(!inherits("Base", "ATest", &bases));
+        assert!(!inherits("DSTest", "DSTest", &bases));
+    }
+
     mod sputnik {
         use super::*;
         use std::collections::HashMap;
@@ -313,5 +1108,48 @@ mod tests {
         fn test_sputnik_abstract_contract() {
             test_abstract_contract();
         }
+
+        #[test]
+        fn test_sputnik_discovery() {
+            let runner = MultiContractRunnerBuilder::default()
+                .discovery(TestDiscovery {
+                    bases: vec!["Tests".to_string()],
+                    globs: vec![glob::Pattern::new("FooTest2.sol").unwrap()],
+                })
+                .build(project(), EVM_OPTS.clone())
+                .unwrap();
+            let listed = runner.list(&Filter::new(".*", ".*"));
+            assert_eq!(
+                listed.keys().map(String::as_str).collect::<Vec<_>>(),
+                vec!["ATests.json:ATests", "BTests.json:BTests", "FooBar.json:FooBar"]
+            );
+            assert_eq!(
+                listed["ATests.json:ATests"],
+                (DiscoveryReason::Inherits("Tests".to_string()), vec!["testFoo()".to_string()])
+            );
+            assert_eq!(
+                listed["FooBar.json:FooBar"].0,
+                DiscoveryReason::MatchesGlob("FooTest2.sol".to_string())
+            );
+        }
+
+        #[test]
+        fn test_sputnik_scoped_settings() {
+            let mut runner = MultiContractRunnerBuilder::default()
+                .scoped(ScopedSettings {
+                    pattern: glob::Pattern::new("Greet*.sol").unwrap(),
+                    evm_opts: EVM_OPTS.clone(),
+                    evm_cfg: Some(Config::berlin()),
+                    fuzzer: None,
+                })
+                .build(project(), EVM_OPTS.clone())
+                .unwrap();
+            assert_eq!(runner.scope("GmTest.json:GmTest"), Some(0));
+            assert_eq!(runner.scope("FooTest.json:FooTest"), None);
+
+            let results = runner.test(&Filter::new(".*", ".*")).unwrap();
+            assert!(results["GmTest.json:GmTest"]["testGm()"].success);
+            assert!(results["FooTest.json:FooTest"].iter().all(|(_, result)| result.success));
+        }
     }
 }
@@ -1,23 +1,29 @@
 use crate::TestFilter;
 use evm_adapters::{
+    chain_profile::{precompiles, system_contracts},
     evm_opts::EvmOpts,
-    sputnik::{helpers::TestSputnikVM, Executor, PRECOMPILES_MAP},
+    sputnik::{
+        cheatcodes::{cheatcode_handler::Checkpoint, BroadcastableTransaction},
+        helpers::TestSputnikVM,
+        journal::{JournalDiff, StateSnapshot},
+        Executor,
+    },
 };
 use rayon::iter::ParallelIterator;
 use sputnik::{backend::Backend, Config};
 
 use ethers::{
     abi::{Abi, Event, Function, Token},
-    types::{Address, Bytes, H256},
+    types::{transaction::eip2930::AccessList, Address, Bytes, H256, U256},
 };
 use evm_adapters::{
     call_tracing::CallTraceArena,
-    fuzz::{FuzzTestResult, FuzzedCases, FuzzedExecutor},
+    fuzz::{FuzzBounds, FuzzDictionary, FuzzTestResult, FuzzedCases, FuzzedExecutor, ValueOrigin},
     sputnik::cheatcodes::debugger::DebugArena,
     Evm, EvmError,
 };
 use eyre::Result;
-use std::{collections::BTreeMap, fmt, time::Instant};
+use std::{collections::BTreeMap, fmt, path::PathBuf, sync::Mutex, time::Instant};
 
 use proptest::test_runner::{TestError, TestRunner};
 use rayon::iter::IntoParallelRefIterator;
@@ -29,6 +35,48 @@ pub struct CounterExample {
     // Token does not implement Serde (lol), so we just serialize the calldata
     #[serde(skip)]
     pub args: Vec<Token>,
+    /// The decoded arguments by parameter
+    pub params: Vec<CounterExampleParam>,
+}
+
+impl CounterExample {
+    /// Decodes the arguments of the calldata of the function, `origins` are the likely origins of
+    /// the arguments
+    pub fn new(
+        func: &Function,
+        calldata: Bytes,
+        origins: &[Option<ValueOrigin>],
+    ) -> eyre::Result<Self> {
+        // skip the function selector when decoding
+        let args = func.decode_input(&calldata.as_ref()[4..])?;
+        let params = func
+            .inputs
+            .iter()
+            .zip(&args)
+            .enumerate()
+            .map(|(i, (param, arg))| CounterExampleParam {
+                name: if param.name.is_empty() { format!("#{}", i) } else { param.name.clone() },
+                kind: param.kind.to_string(),
+                value: foundry_utils::format_token(arg),
+                origin: origins.get(i).copied().flatten(),
+            })
+            .collect();
+        Ok(Self { calldata, args, params })
+    }
+}
+
+/// An argument of a [`CounterExample`]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CounterExampleParam {
+    /// The name of the parameter, or its position if it's unnamed
+    pub name: String,
+    /// The type of the parameter
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// The decoded value
+    pub value: String,
+    /// Where the value likely came from
+    pub origin: Option<ValueOrigin>,
 }
 
 impl fmt::Display for CounterExample {
@@ -75,6 +123,19 @@ pub struct TestResult {
     /// Debug Steps
     #[serde(skip)]
     pub debug_calls: Option<Vec<DebugArena>>,
+
+    /// The EIP-2930 access list of the test call, if recording the access list was enabled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub access_list: Option<AccessList>,
+
+    /// The calls and creations made while broadcasting, see the `broadcast` cheatcodes
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub broadcastable_transactions: Vec<BroadcastableTransaction>,
+
+    /// The accounts and storage slots written by `setUp` whose values differed when the test
+    /// started, if checking the isolation of tests was enabled
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub isolation_warnings: Vec<String>,
 }
 
 impl TestResult {
@@ -140,6 +201,60 @@ impl TestKind {
     }
 }
 
+/// The state of a test contract after `setUp`, shared by all tests of the contract
+#[derive(Debug, Clone)]
+pub struct SetupCheckpoint {
+    /// The address of the test contract
+    pub address: Address,
+    /// The logs of the deployment and `setUp`
+    pub logs: Vec<String>,
+    /// The state of the EVM after `setUp`
+    pub checkpoint: Checkpoint,
+    /// The accounts and storage slots written by `setUp` and their values, if checking the
+    /// isolation of tests is enabled
+    pub state: Option<StateSnapshot>,
+}
+
+/// Receives the progress of long running parts of a test run, e.g. to render progress bars
+pub trait TestProgress: fmt::Debug + Send + Sync {
+    /// Called after every prefetched account of a fork, with the number of fetched and all
+    /// accounts
+    fn fork_state_fetched(&self, _fetched: usize, _total: usize) {}
+
+    /// Called after every successful case of the fuzz test `test`, with the number of cases run
+    /// so far and the number of cases to run
+    fn fuzz_case(&self, _test: &str, _runs: u32, _total: u32) {}
+}
+
+/// The state changes of the fixtures executed so far
+///
+/// A fixture is a `fixture` function without arguments, like `fixtureProtocol()`, that's executed
+/// before `setUp`. Its state changes are computed once and applied to every test contract that
+/// inherits the fixture from the same declaring contract.
+pub type Fixtures = Mutex<BTreeMap<FixtureKey, Fixture>>;
+
+/// Identifies a fixture by the contract that declares it, in the form of `<source file>:<name>`,
+/// and its signature
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct FixtureKey {
+    pub contract: String,
+    pub signature: String,
+}
+
+/// The state changes of a fixture
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Fixture {
+    /// The address of the test contract the fixture was executed on, the state changes are only
+    /// applied to test contracts deployed at the same address
+    pub address: Address,
+    pub diff: JournalDiff,
+}
+
+/// Whether the function is a fixture, see [`Fixtures`]
+pub fn is_fixture(func: &Function) -> bool {
+    func.name.starts_with("fixture") && func.inputs.is_empty()
+}
+
 /// Type complexity wrapper around execution info
 type MaybeExecutionInfo<'a> =
     Option<(&'a BTreeMap<[u8; 4], Function>, &'a BTreeMap<H256, Event>, &'a Abi)>;
@@ -164,6 +279,18 @@ pub struct ContractRunner<'a, B> {
 
     /// Contract execution info, (functions, events, errors)
     pub execution_info: MaybeExecutionInfo<'a>,
+
+    /// Receives the progress of the fuzz tests
+    pub progress: Option<&'a dyn TestProgress>,
+    /// The directory the corpus of every fuzz test of the contract is kept in
+    pub corpus: Option<PathBuf>,
+    /// The bounds of the fuzzed values of dynamic types and of the run time of fuzz tests
+    pub fuzz_bounds: FuzzBounds,
+    /// The state changes of the fixtures executed so far, shared with other test contracts
+    pub fixtures: Option<&'a Fixtures>,
+    /// The contract that declares each fixture of the test contract by its signature, the
+    /// fixtures without a known declaring contract aren't shared
+    pub fixture_contracts: BTreeMap<String, String>,
 }
 
 impl<'a, B: Backend> ContractRunner<'a, B> {
@@ -184,8 +311,47 @@ impl<'a, B: Backend> ContractRunner<'a, B> {
             code,
             sender: sender.unwrap_or_default(),
             execution_info,
+            progress: None,
+            corpus: None,
+            fuzz_bounds: Default::default(),
+            fixtures: None,
+            fixture_contracts: Default::default(),
         }
     }
+
+    /// Reports the progress of the fuzz tests to `progress`
+    #[must_use]
+    pub fn with_progress(mut self, progress: Option<&'a dyn TestProgress>) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    /// Bounds the fuzzed values of dynamic types
+    #[must_use]
+    pub fn with_fuzz_bounds(mut self, bounds: FuzzBounds) -> Self {
+        self.fuzz_bounds = bounds;
+        self
+    }
+
+    /// Shares the state changes of the fixtures with the other runners of the `fixtures`, by the
+    /// contracts that declare them
+    #[must_use]
+    pub fn with_fixtures(
+        mut self,
+        fixtures: Option<&'a Fixtures>,
+        contracts: BTreeMap<String, String>,
+    ) -> Self {
+        self.fixtures = fixtures;
+        self.fixture_contracts = contracts;
+        self
+    }
+
+    /// Keeps the corpus of every fuzz test in the directory across runs
+    #[must_use]
+    pub fn with_corpus(mut self, corpus: Option<PathBuf>) -> Self {
+        self.corpus = corpus;
+        self
+    }
 }
 
 // Require that the backend is Cloneable. This allows us to use the `SharedBackend` from
@@ -194,22 +360,132 @@ impl<'a, B: Backend + Clone + Send + Sync> ContractRunner<'a, B> {
     /// Creates a new EVM and deploys the test contract inside the runner
     /// from the sending account.
     pub fn new_sputnik_evm(&'a self) -> eyre::Result<(Address, TestSputnikVM<'a, B>, Vec<String>)> {
-        // create the EVM, clone the backend.
+        let mut executor = self.new_executor();
+
+        // deploy an instance of the contract inside the runner in the EVM
+        let (addr, _, _, logs) =
+            executor.deploy(self.sender, self.code.clone(), 0u32.into()).expect("couldn't deploy");
+        executor.set_balance(addr, self.evm_opts.initial_balance);
+        let mut logs = logs;
+        logs.extend(self.apply_fixtures(&mut executor, addr)?);
+        Ok((addr, executor, logs))
+    }
+
+    /// Applies the state changes of the test contract's fixtures in the order of their names,
+    /// the fixtures no other test contract at the same address executed before are executed
+    fn apply_fixtures(
+        &self,
+        evm: &mut TestSputnikVM<'_, B>,
+        address: Address,
+    ) -> eyre::Result<Vec<String>> {
+        let mut fixtures =
+            self.contract.functions().filter(|func| is_fixture(func)).collect::<Vec<_>>();
+        fixtures.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut logs = Vec::new();
+        for func in fixtures {
+            let signature = func.signature();
+            let key = self.fixture_contracts.get(&signature).map(|contract| FixtureKey {
+                contract: contract.clone(),
+                signature: signature.clone(),
+            });
+            let cached = key.as_ref().and_then(|key| {
+                self.fixtures.and_then(|fixtures| fixtures.lock().unwrap().get(key).cloned())
+            });
+            // the state changes of a fixture refer to the address of the test contract it was
+            // executed on
+            if let Some(fixture) = cached.filter(|fixture| fixture.address == address) {
+                evm.apply_journal_diff(&fixture.diff);
+                continue
+            }
+
+            let depth = evm.journal_depth();
+            let calldata = func.selector().to_vec().into();
+            let (_, reason, _, fixture_logs) =
+                evm.call_raw(Address::zero(), address, calldata, 0.into(), false)?;
+            if is_fail(evm, reason.clone()) {
+                eyre::bail!("fixture `{}` failed: {:?}", signature, reason)
+            }
+            logs.extend(fixture_logs);
+            if let (Some(fixtures), Some(key)) = (self.fixtures, key) {
+                let fixture = Fixture { address, diff: evm.journal_diff(depth) };
+                fixtures.lock().unwrap().entry(key).or_insert(fixture);
+            }
+        }
+        Ok(logs)
+    }
+
+    /// Creates the EVM, clones the backend
+    fn new_executor(&'a self) -> TestSputnikVM<'a, B> {
         let mut executor = Executor::new_with_cheatcodes(
             self.backend.clone(),
-            self.evm_opts.env.gas_limit,
+            self.evm_opts.gas_limit(),
             self.evm_cfg,
-            &*PRECOMPILES_MAP,
+            precompiles(self.evm_opts.chain_profile),
             self.evm_opts.ffi,
             self.evm_opts.verbosity > 2,
             self.evm_opts.debug,
         );
+        executor.set_rpc_endpoints(self.evm_opts.rpc_endpoints.clone());
+        executor.set_bytecode_contracts(self.evm_opts.bytecode_contracts.clone());
+        executor.set_deployments(self.evm_opts.deployments.clone());
+        executor.set_debug_step_limit(self.evm_opts.debug_step_limit);
+        executor.initialize_contracts(system_contracts(self.evm_opts.chain_profile));
+        executor
+    }
 
-        // deploy an instance of the contract inside the runner in the EVM
-        let (addr, _, _, logs) =
-            executor.deploy(self.sender, self.code.clone(), 0u32.into()).expect("couldn't deploy");
-        executor.set_balance(addr, self.evm_opts.initial_balance);
-        Ok((addr, executor, logs))
+    /// Deploys the test contract and executes `setUp` once, returns the resulting state which is
+    /// restored for every test.
+    ///
+    /// Returns `None` if `setUp` fails, so that the failure is reported by every test.
+    pub fn setup_checkpoint(&'a self) -> Option<SetupCheckpoint> {
+        let (address, mut evm, mut logs) = self.new_sputnik_evm().ok()?;
+        // clear out the deployment trace
+        evm.reset_traces();
+        let (_, setup_logs) = evm.setup(address).ok()?;
+        logs.extend(setup_logs);
+        let state = self.evm_opts.check_isolation.then(|| evm.touched_state());
+        Some(SetupCheckpoint { address, logs, checkpoint: evm.checkpoint(), state })
+    }
+
+    /// Returns the differences between the state `setUp` left behind and the state the test
+    /// observes, which indicate that state leaked into the test
+    fn isolation_warnings(
+        &self,
+        evm: &TestSputnikVM<'_, B>,
+        checkpoint: Option<&SetupCheckpoint>,
+    ) -> Vec<String> {
+        checkpoint
+            .and_then(|checkpoint| checkpoint.state.as_ref())
+            .map(|state| state.diff(&evm.reread_state(state)))
+            .unwrap_or_default()
+    }
+
+    /// Returns the dictionary of the fuzz tests: the addresses, the constants of the code and the
+    /// storage of the test contract and of the accounts `setUp` touched
+    fn fuzz_dictionary(&self, evm: &mut TestSputnikVM<'_, B>, address: Address) -> FuzzDictionary {
+        let mut dictionary = FuzzDictionary::default();
+        dictionary.insert_address(self.sender);
+        let state = evm.touched_state();
+        for account in std::iter::once(address).chain(state.accounts.keys().copied()) {
+            dictionary.insert_address(account);
+            dictionary.insert_code(&evm.code(account));
+        }
+        for (slot, value) in state.accounts.values().flat_map(|account| &account.storage) {
+            dictionary.insert_value(U256::from_big_endian(slot.as_bytes()));
+            dictionary.insert_value(U256::from_big_endian(value.as_bytes()));
+        }
+        dictionary
+    }
+
+    /// Creates a new EVM with the state of the test contract after `setUp`
+    pub fn restore_sputnik_evm(
+        &'a self,
+        setup: &SetupCheckpoint,
+    ) -> (Address, TestSputnikVM<'a, B>, Vec<String>) {
+        let mut executor = self.new_executor();
+        executor.restore(&setup.checkpoint);
+        (setup.address, executor, setup.logs.clone())
     }
 
     /// Runs all tests for a contract whose names match the provided regular expression
@@ -222,6 +498,10 @@ impl<'a, B: Backend + Clone + Send + Sync> ContractRunner<'a, B> {
         tracing::info!("starting tests");
         let start = Instant::now();
         let needs_setup = self.contract.functions().any(|func| func.name == "setUp");
+        // `setUp` is executed once and its state is restored for every test, except when
+        // debugging, which needs the steps of `setUp` in every test
+        let checkpoint =
+            if needs_setup && !self.evm_opts.debug { self.setup_checkpoint() } else { None };
         let test_fns = self
             .contract
             .functions()
@@ -235,7 +515,8 @@ impl<'a, B: Backend + Clone + Send + Sync> ContractRunner<'a, B> {
             .par_iter()
             .filter(|func| func.inputs.is_empty())
             .map(|func| {
-                let result = self.run_test(func, needs_setup, known_contracts)?;
+                let result =
+                    self.run_test(func, needs_setup, checkpoint.as_ref(), known_contracts)?;
                 Ok((func.signature(), result))
             })
             .collect::<Result<BTreeMap<_, _>>>()?;
@@ -245,8 +526,13 @@ impl<'a, B: Backend + Clone + Send + Sync> ContractRunner<'a, B> {
                 .par_iter()
                 .filter(|func| !func.inputs.is_empty())
                 .map(|func| {
-                    let result =
-                        self.run_fuzz_test(func, needs_setup, fuzzer.clone(), known_contracts)?;
+                    let result = self.run_fuzz_test(
+                        func,
+                        needs_setup,
+                        checkpoint.as_ref(),
+                        fuzzer.clone(),
+                        known_contracts,
+                    )?;
                     Ok((func.signature(), result))
                 })
                 .collect::<Result<BTreeMap<_, _>>>()?;
@@ -271,6 +557,7 @@ impl<'a, B: Backend + Clone + Send + Sync> ContractRunner<'a, B> {
         &self,
         func: &Function,
         setup: bool,
+        checkpoint: Option<&SetupCheckpoint>,
         known_contracts: Option<&BTreeMap<String, (Abi, Vec<u8>)>>,
     ) -> Result<TestResult> {
         let start = Instant::now();
@@ -281,7 +568,17 @@ impl<'a, B: Backend + Clone + Send + Sync> ContractRunner<'a, B> {
         let should_fail = func.name.starts_with("testFail");
         tracing::debug!(func = ?func.signature(), should_fail, "unit-testing");
 
-        let (address, mut evm, init_logs) = self.new_sputnik_evm()?;
+        let (address, mut evm, init_logs) = match checkpoint {
+            Some(checkpoint) => self.restore_sputnik_evm(checkpoint),
+            None => {
+                let (address, mut evm, logs) = self.new_sputnik_evm()?;
+                // clear out the deployment trace
+                evm.reset_traces();
+                (address, evm, logs)
+            }
+        };
+
+        let isolation_warnings = self.isolation_warnings(&evm, checkpoint);
 
         let errors_abi = self.execution_info.as_ref().map(|(_, _, errors)| errors);
         let errors_abi = if let Some(ref abi) = errors_abi { abi } else { self.contract };
@@ -291,11 +588,9 @@ impl<'a, B: Backend + Clone + Send + Sync> ContractRunner<'a, B> {
         let mut traces: Option<Vec<CallTraceArena>> = None;
         let mut identified_contracts: Option<BTreeMap<Address, (String, Abi)>> = None;
 
-        // clear out the deployment trace
-        evm.reset_traces();
-
-        // call the setup function in each test to reset the test's state.
-        if setup {
+        // call the setup function in each test to reset the test's state, unless the state after
+        // `setUp` was restored
+        if setup && checkpoint.is_none() {
             tracing::trace!("setting up");
             let setup_logs = match evm.setup(address) {
                 Ok((_reason, setup_logs)) => setup_logs,
@@ -321,17 +616,24 @@ impl<'a, B: Backend + Clone + Send + Sync> ContractRunner<'a, B> {
                         kind: TestKind::Standard(0),
                         traces,
                         identified_contracts,
-                        debug_calls: if evm.state().debug_enabled {
+                        debug_calls: if evm.debug_enabled() {
                             Some(evm.debug_calls())
                         } else {
                             None
                         },
+                        access_list: None,
+                        broadcastable_transactions: Vec::new(),
+                        isolation_warnings,
                     })
                 }
             };
             logs.extend_from_slice(&setup_logs);
         }
 
+        if self.evm_opts.record_access_list {
+            evm.record_access_list();
+        }
+
         let (status, reason, gas_used, logs) = match evm.call::<(), _, _>(
             self.sender,
             address,
@@ -379,7 +681,13 @@ impl<'a, B: Backend + Clone + Send + Sync> ContractRunner<'a, B> {
             kind: TestKind::Standard(gas_used),
             traces,
             identified_contracts,
-            debug_calls: if evm.state().debug_enabled { Some(evm.debug_calls()) } else { None },
+            debug_calls: if evm.debug_enabled() { Some(evm.debug_calls()) } else { None },
+            access_list: self
+                .evm_opts
+                .record_access_list
+                .then(|| evm.access_list(self.sender, address)),
+            broadcastable_transactions: evm.broadcastable_transactions().to_vec(),
+            isolation_warnings,
         })
     }
 
@@ -388,6 +696,7 @@ impl<'a, B: Backend + Clone + Send + Sync> ContractRunner<'a, B> {
         &self,
         func: &Function,
         setup: bool,
+        checkpoint: Option<&SetupCheckpoint>,
         runner: TestRunner,
         known_contracts: Option<&BTreeMap<String, (Abi, Vec<u8>)>>,
     ) -> Result<TestResult> {
@@ -396,16 +705,24 @@ impl<'a, B: Backend + Clone + Send + Sync> ContractRunner<'a, B> {
         let should_fail = func.name.starts_with("testFail");
         tracing::debug!(func = ?func.signature(), should_fail, "fuzzing");
 
-        let (address, mut evm, init_logs) = self.new_sputnik_evm()?;
+        let (address, mut evm, init_logs) = match checkpoint {
+            Some(checkpoint) => self.restore_sputnik_evm(checkpoint),
+            None => {
+                let (address, mut evm, logs) = self.new_sputnik_evm()?;
+                // clear out the deployment trace
+                evm.reset_traces();
+                (address, evm, logs)
+            }
+        };
+
+        let isolation_warnings = self.isolation_warnings(&evm, checkpoint);
 
         let mut traces: Option<Vec<CallTraceArena>> = None;
         let mut identified_contracts: Option<BTreeMap<Address, (String, Abi)>> = None;
 
-        // clear out the deployment trace
-        evm.reset_traces();
-
-        // call the setup function in each test to reset the test's state.
-        if setup {
+        // call the setup function in each test to reset the test's state, unless the state after
+        // `setUp` was restored
+        if setup && checkpoint.is_none() {
             tracing::trace!("setting up");
             match evm.setup(address) {
                 Ok((_reason, _setup_logs)) => {}
@@ -430,11 +747,14 @@ impl<'a, B: Backend + Clone + Send + Sync> ContractRunner<'a, B> {
                         kind: TestKind::Fuzz(FuzzedCases::new(vec![])),
                         traces,
                         identified_contracts,
-                        debug_calls: if evm.state().debug_enabled {
+                        debug_calls: if evm.debug_enabled() {
                             Some(evm.debug_calls())
                         } else {
                             None
                         },
+                        access_list: None,
+                        broadcastable_transactions: Vec::new(),
+                        isolation_warnings,
                     })
                 }
             }
@@ -445,9 +765,20 @@ impl<'a, B: Backend + Clone + Send + Sync> ContractRunner<'a, B> {
         let prev = evm.set_tracing_enabled(false);
 
         // instantiate the fuzzed evm in line
-        let evm = FuzzedExecutor::new(&mut evm, runner, self.sender);
+        let total = runner.config().cases;
+        let signature = func.signature();
+        let corpus = self.corpus.as_ref().map(|dir| dir.join(format!("{}.json", signature)));
+        let dictionary = self.fuzz_dictionary(&mut evm, address);
+        let evm = FuzzedExecutor::new(&mut evm, runner, self.sender)
+            .with_corpus(corpus)
+            .with_dictionary(dictionary)
+            .with_bounds(self.fuzz_bounds.clone());
         let FuzzTestResult { cases, test_error } =
-            evm.fuzz(func, address, should_fail, Some(self.contract));
+            evm.fuzz_with_progress(func, address, should_fail, Some(self.contract), |runs| {
+                if let Some(progress) = self.progress {
+                    progress.fuzz_case(&signature, runs, total)
+                }
+            });
 
         let evm = evm.into_inner();
         if let Some(ref error) = test_error {
@@ -481,11 +812,8 @@ impl<'a, B: Backend + Clone + Send + Sync> ContractRunner<'a, B> {
         if let Some(err) = test_error {
             match err.test_error {
                 TestError::Fail(_, value) => {
-                    // skip the function selector when decoding
-                    let args = func.decode_input(&value.as_ref()[4..])?;
-                    let counter = CounterExample { calldata: value.clone(), args };
-                    counterexample = Some(counter);
                     tracing::info!("Found minimal failing case: {}", hex::encode(&value));
+                    counterexample = Some(CounterExample::new(func, value, &err.origins)?);
                 }
                 result => panic!("Unexpected test result: {:?}", result),
             }
@@ -507,7 +835,10 @@ impl<'a, B: Backend + Clone + Send + Sync> ContractRunner<'a, B> {
             kind: TestKind::Fuzz(cases),
             traces,
             identified_contracts,
-            debug_calls: if evm.state().debug_enabled { Some(evm.debug_calls()) } else { None },
+            debug_calls: if evm.debug_enabled() { Some(evm.debug_calls()) } else { None },
+            access_list: None,
+            broadcastable_transactions: Vec::new(),
+            isolation_warnings,
         })
     }
 
@@ -639,11 +970,38 @@ mod tests {
             cfg.failure_persistence = None;
             let fuzzer = TestRunner::new(cfg);
             let func = get_func("testStringFuzz(string)").unwrap();
-            let res = runner.run_fuzz_test(&func, true, fuzzer, None).unwrap();
+            let res = runner.run_fuzz_test(&func, true, None, fuzzer, None).unwrap();
             assert!(res.success);
             assert!(res.counterexample.is_none());
         }
 
+        #[test]
+        fn test_fuzzing_progress() {
+            #[derive(Debug, Default)]
+            struct Runs(std::sync::Mutex<Vec<(String, u32, u32)>>);
+
+            impl TestProgress for Runs {
+                fn fuzz_case(&self, test: &str, runs: u32, total: u32) {
+                    self.0.lock().unwrap().push((test.to_string(), runs, total));
+                }
+            }
+
+            let compiled = COMPILED.find("GreeterTest").expect("could not find contract");
+            let (_, code, _) = compiled.into_parts_or_default();
+            let progress = Runs::default();
+            let runner =
+                runner(compiled.abi.as_ref().unwrap(), code).with_progress(Some(&progress));
+
+            let cfg = FuzzConfig { cases: 10, failure_persistence: None, ..Default::default() };
+            let func = get_func("testStringFuzz(string)").unwrap();
+            let res = runner.run_fuzz_test(&func, true, None, TestRunner::new(cfg), None).unwrap();
+            assert!(res.success);
+
+            let runs = progress.0.into_inner().unwrap();
+            assert_eq!(runs.len(), 10);
+            assert_eq!(runs.last().unwrap(), &("testStringFuzz(string)".to_string(), 10, 10));
+        }
+
         #[test]
         fn test_fuzz_shrinking() {
             let compiled = COMPILED.find("GreeterTest").expect("could not find contract");
@@ -654,7 +1012,7 @@ mod tests {
             cfg.failure_persistence = None;
             let fuzzer = TestRunner::new(cfg);
             let func = get_func("function testShrinking(uint256 x, uint256 y) public").unwrap();
-            let res = runner.run_fuzz_test(&func, true, fuzzer, None).unwrap();
+            let res = runner.run_fuzz_test(&func, true, None, fuzzer, None).unwrap();
             assert!(!res.success);
 
             // get the counterexample with shrinking enabled by default
@@ -670,7 +1028,7 @@ mod tests {
             // we reduce the shrinking iters and observe a larger result
             cfg.max_shrink_iters = 5;
             let fuzzer = TestRunner::new(cfg);
-            let res = runner.run_fuzz_test(&func, true, fuzzer, None).unwrap();
+            let res = runner.run_fuzz_test(&func, true, None, fuzzer, None).unwrap();
             assert!(!res.success);
 
             // get the non-shrunk result
@@ -0,0 +1,202 @@
+//! Compiles the contracts of a project that aren't written in Solidity, `.yul` objects with solc
+//! and `.vy` files with a vyper binary, into the same shape as the Solidity artifacts
+
+use ethers::{
+    abi::Abi,
+    prelude::{ArtifactOutput, Bytes},
+    solc::{
+        artifacts::{BytecodeObject, CompactContract},
+        Project,
+    },
+};
+use eyre::{Result, WrapErr};
+use serde_json::{json, Value};
+use std::{
+    collections::BTreeMap,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+/// A contract compiled from a Yul or Vyper source
+#[derive(Debug, Clone)]
+pub struct CompiledSource {
+    /// The source file, relative to the project root
+    pub path: PathBuf,
+    /// The name of the Yul object, or the file stem of the Vyper source
+    pub name: String,
+    pub contract: CompactContract,
+}
+
+impl CompiledSource {
+    /// The identifier of the contract in the form of `<source file>:<name>`
+    pub fn id(&self) -> String {
+        format!("{}:{}", self.path.display(), self.name)
+    }
+}
+
+/// Compiles all `.yul` and `.vy` files in the sources of the project
+///
+/// The `.yul` files are compiled with the solc of the project, the `.vy` files with the `vyper`
+/// binary and are skipped if it isn't set.
+pub fn compile_sources<A: ArtifactOutput>(
+    project: &Project<A>,
+    vyper: Option<&Path>,
+) -> Result<Vec<CompiledSource>> {
+    let mut compiled = Vec::new();
+    for file in source_files(&project.paths.sources)? {
+        let path = file
+            .strip_prefix(&project.paths.root)
+            .map(Path::to_path_buf)
+            .unwrap_or_else(|_| file.clone());
+        match file.extension().and_then(|ext| ext.to_str()) {
+            Some("yul") => {
+                for (name, contract) in compile_yul(&project.solc.solc, &file)? {
+                    compiled.push(CompiledSource { path: path.clone(), name, contract });
+                }
+            }
+            Some("vy") => {
+                if let Some(vyper) = vyper {
+                    let name = file.file_stem().unwrap_or_default().to_string_lossy().to_string();
+                    let contract = compile_vyper(vyper, &file)?;
+                    compiled.push(CompiledSource { path, name, contract });
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(compiled)
+}
+
+/// Returns all `.yul` and `.vy` files in the directory and its subdirectories
+pub fn source_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    if !dir.is_dir() {
+        return Ok(files)
+    }
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            files.extend(source_files(&path)?);
+        } else if matches!(path.extension().and_then(|ext| ext.to_str()), Some("yul" | "vy")) {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Compiles the Yul objects of the file via solc's standard json interface
+///
+/// Yul has no ABI, the contracts are called through the interfaces declared in Solidity.
+pub fn compile_yul(solc: &Path, file: &Path) -> Result<BTreeMap<String, CompactContract>> {
+    let content = std::fs::read_to_string(file)
+        .wrap_err_with(|| format!("failed to read {}", file.display()))?;
+    let source = file.display().to_string();
+    let input = json!({
+        "language": "Yul",
+        "sources": { &source: { "content": content } },
+        "settings": {
+            "optimizer": { "enabled": true, "details": { "yul": true } },
+            "outputSelection": {
+                "*": { "*": ["evm.bytecode.object", "evm.deployedBytecode.object"] }
+            }
+        }
+    });
+
+    let mut child = Command::new(solc)
+        .arg("--standard-json")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .wrap_err_with(|| format!("failed to run {}", solc.display()))?;
+    child.stdin.take().expect("stdin is piped").write_all(input.to_string().as_bytes())?;
+    let output = child.wait_with_output()?;
+    let output: Value = serde_json::from_slice(&output.stdout)
+        .wrap_err_with(|| format!("failed to compile {}", file.display()))?;
+
+    let errors = output["errors"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|err| err["severity"] == "error")
+        .filter_map(|err| err["formattedMessage"].as_str())
+        .collect::<Vec<_>>();
+    if !errors.is_empty() {
+        eyre::bail!("failed to compile {}:\n{}", file.display(), errors.join("\n"))
+    }
+
+    let mut contracts = BTreeMap::new();
+    if let Some(objects) = output["contracts"][&source].as_object() {
+        for (name, object) in objects {
+            let bin = parse_bytecode(object["evm"]["bytecode"]["object"].as_str());
+            let bin_runtime = parse_bytecode(object["evm"]["deployedBytecode"]["object"].as_str());
+            contracts.insert(
+                name.clone(),
+                CompactContract { abi: Some(Abi::default()), bin, bin_runtime },
+            );
+        }
+    }
+    Ok(contracts)
+}
+
+/// Compiles the Vyper contract of the file with the `vyper` binary
+pub fn compile_vyper(vyper: &Path, file: &Path) -> Result<CompactContract> {
+    let output = Command::new(vyper)
+        .arg("-f")
+        .arg("abi,bytecode,bytecode_runtime")
+        .arg(file)
+        .output()
+        .wrap_err_with(|| format!("failed to run {}", vyper.display()))?;
+    if !output.status.success() {
+        eyre::bail!(
+            "failed to compile {}:\n{}",
+            file.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+    }
+
+    // vyper prints one line per output format, in the order of the formats
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut lines = stdout.lines();
+    let abi: Abi = serde_json::from_str(lines.next().unwrap_or_default())
+        .wrap_err_with(|| format!("failed to parse the abi of {}", file.display()))?;
+    let bin = parse_bytecode(lines.next());
+    let bin_runtime = parse_bytecode(lines.next());
+    Ok(CompactContract { abi: Some(abi), bin, bin_runtime })
+}
+
+fn parse_bytecode(code: Option<&str>) -> Option<BytecodeObject> {
+    let code = code?.trim();
+    let code = hex::decode(code.strip_prefix("0x").unwrap_or(code)).ok()?;
+    Some(BytecodeObject::Bytecode(Bytes::from(code)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_yul_and_vyper_sources() {
+        let dir = std::env::temp_dir().join("forge-non-solidity-sources");
+        std::fs::create_dir_all(dir.join("nested")).unwrap();
+        std::fs::write(dir.join("Token.vy"), "").unwrap();
+        std::fs::write(dir.join("nested/Counter.yul"), "").unwrap();
+        std::fs::write(dir.join("Greeter.sol"), "").unwrap();
+
+        let files = source_files(&dir).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(files, vec![dir.join("Token.vy"), dir.join("nested/Counter.yul")]);
+    }
+
+    #[test]
+    fn parses_bytecode() {
+        assert!(matches!(
+            parse_bytecode(Some("0x6080\n")),
+            Some(BytecodeObject::Bytecode(code)) if code.as_ref() == [0x60, 0x80]
+        ));
+        assert!(parse_bytecode(Some("not hex")).is_none());
+        assert!(parse_bytecode(None).is_none());
+    }
+}
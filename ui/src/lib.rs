@@ -61,14 +61,22 @@ pub struct Tui {
 
 impl Tui {
     /// Create a tui
+    ///
+    /// Calls without steps, like the calls whose steps were dropped by the step limit, are
+    /// skipped. Fails if no call has any steps.
     #[allow(unused_must_use)]
     pub fn new(
-        debug_arena: Vec<(Address, Vec<DebugStep>, bool)>,
+        mut debug_arena: Vec<(Address, Vec<DebugStep>, bool)>,
         current_step: usize,
         identified_contracts: BTreeMap<Address, (String, Abi)>,
         known_contracts: BTreeMap<String, ContractBytecodeSome>,
         source_code: BTreeMap<u32, String>,
     ) -> Result<Self> {
+        debug_arena.retain(|(_, steps, _)| !steps.is_empty());
+        let current_step = match debug_arena.first() {
+            Some((_, steps, _)) => current_step.min(steps.len() - 1),
+            None => eyre::bail!("no debug steps were recorded"),
+        };
         enable_raw_mode()?;
         let mut stdout = io::stdout();
         execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
@@ -0,0 +1,278 @@
+//! Hashing of [EIP-712](https://eips.ethereum.org/EIPS/eip-712) typed structured data
+
+use ethers_core::{
+    types::{Address, I256, U256},
+    utils::keccak256,
+};
+use eyre::Result;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The name of the domain type
+pub const EIP712_DOMAIN: &str = "EIP712Domain";
+
+/// The fields of the domain in their canonical order, used if the document does not declare the
+/// `EIP712Domain` type
+const DOMAIN_FIELDS: [(&str, &str); 5] = [
+    ("name", "string"),
+    ("version", "string"),
+    ("chainId", "uint256"),
+    ("verifyingContract", "address"),
+    ("salt", "bytes32"),
+];
+
+/// A member of a struct type
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct TypedField {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: String,
+}
+
+/// An EIP-712 typed data document, as used by `eth_signTypedData_v4`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TypedData {
+    pub types: BTreeMap<String, Vec<TypedField>>,
+    pub primary_type: String,
+    pub domain: serde_json::Map<String, Value>,
+    pub message: Value,
+}
+
+impl TypedData {
+    /// Parses the typed data from its JSON representation
+    pub fn from_json(json: &str) -> Result<Self> {
+        let mut data: Self = serde_json::from_str(json)?;
+        if !data.types.contains_key(EIP712_DOMAIN) {
+            let fields = DOMAIN_FIELDS
+                .iter()
+                .filter(|(name, _)| data.domain.contains_key(*name))
+                .map(|(name, ty)| TypedField { name: name.to_string(), ty: ty.to_string() })
+                .collect();
+            data.types.insert(EIP712_DOMAIN.to_string(), fields);
+        }
+        Ok(data)
+    }
+
+    /// The hash of the domain, `hashStruct(domain)`
+    pub fn domain_separator(&self) -> Result<[u8; 32]> {
+        self.hash_struct(EIP712_DOMAIN, &Value::Object(self.domain.clone()))
+    }
+
+    /// The hash of the message, `hashStruct(message)`
+    pub fn struct_hash(&self) -> Result<[u8; 32]> {
+        self.hash_struct(&self.primary_type, &self.message)
+    }
+
+    /// The digest to sign, `keccak256("\x19\x01" ‖ domainSeparator ‖ hashStruct(message))`
+    pub fn digest(&self) -> Result<[u8; 32]> {
+        let mut encoded = vec![0x19, 0x01];
+        encoded.extend_from_slice(&self.domain_separator()?);
+        encoded.extend_from_slice(&self.struct_hash()?);
+        Ok(keccak256(encoded))
+    }
+
+    /// Encodes the type like `Mail(Person from,Person to,string contents)Person(string name)`,
+    /// the referenced struct types are appended in alphabetical order
+    pub fn encode_type(&self, name: &str) -> Result<String> {
+        let mut deps = BTreeSet::new();
+        self.collect_deps(name, &mut deps)?;
+        deps.remove(name);
+
+        let mut encoded = String::new();
+        for ty in std::iter::once(name).chain(deps.iter().map(String::as_str)) {
+            let fields = self
+                .fields(ty)?
+                .iter()
+                .map(|field| format!("{} {}", field.ty, field.name))
+                .collect::<Vec<_>>()
+                .join(",");
+            encoded.push_str(&format!("{}({})", ty, fields));
+        }
+        Ok(encoded)
+    }
+
+    /// `keccak256(encodeType(name))`
+    pub fn type_hash(&self, name: &str) -> Result<[u8; 32]> {
+        Ok(keccak256(self.encode_type(name)?))
+    }
+
+    /// `keccak256(typeHash ‖ encodeData(value))`
+    pub fn hash_struct(&self, name: &str, value: &Value) -> Result<[u8; 32]> {
+        let mut encoded = self.type_hash(name)?.to_vec();
+        for field in self.fields(name)? {
+            let value = value
+                .get(&field.name)
+                .ok_or_else(|| eyre::eyre!("field `{}` of `{}` is missing", field.name, name))?;
+            encoded.extend_from_slice(&self.encode_value(&field.ty, value)?);
+        }
+        Ok(keccak256(encoded))
+    }
+
+    fn fields(&self, name: &str) -> Result<&Vec<TypedField>> {
+        self.types.get(name).ok_or_else(|| eyre::eyre!("type `{}` is not defined", name))
+    }
+
+    fn collect_deps(&self, name: &str, deps: &mut BTreeSet<String>) -> Result<()> {
+        if !deps.insert(name.to_string()) {
+            return Ok(())
+        }
+        for field in self.fields(name)? {
+            let base = base_type(&field.ty);
+            if self.types.contains_key(base) {
+                self.collect_deps(base, deps)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Encodes a single value into its 32 byte word
+    fn encode_value(&self, ty: &str, value: &Value) -> Result<[u8; 32]> {
+        if let Some(item_ty) = ty.strip_suffix(']').and_then(|ty| ty.rsplit_once('[')).map(|t| t.0)
+        {
+            let items =
+                value.as_array().ok_or_else(|| eyre::eyre!("expected an array of `{}`", ty))?;
+            let mut encoded = Vec::with_capacity(items.len() * 32);
+            for item in items {
+                encoded.extend_from_slice(&self.encode_value(item_ty, item)?);
+            }
+            return Ok(keccak256(encoded))
+        }
+        if self.types.contains_key(ty) {
+            return self.hash_struct(ty, value)
+        }
+
+        let mut word = [0u8; 32];
+        match ty {
+            "string" => word = keccak256(as_str(value)?),
+            "bytes" => word = keccak256(decode_hex(as_str(value)?)?),
+            "bool" => {
+                let b = match value {
+                    Value::Bool(b) => *b,
+                    Value::String(s) => s.parse()?,
+                    _ => eyre::bail!("expected a bool, got {}", value),
+                };
+                word[31] = b as u8;
+            }
+            "address" => {
+                let address = as_str(value)?.parse::<Address>()?;
+                word[12..].copy_from_slice(address.as_bytes());
+            }
+            ty if ty.starts_with("bytes") => {
+                let bytes = decode_hex(as_str(value)?)?;
+                if bytes.len() > 32 {
+                    eyre::bail!("`{}` value is longer than 32 bytes", ty)
+                }
+                word[..bytes.len()].copy_from_slice(&bytes);
+            }
+            ty if ty.starts_with("uint") => parse_uint(value)?.to_big_endian(&mut word),
+            ty if ty.starts_with("int") => parse_int(value)?.into_raw().to_big_endian(&mut word),
+            _ => eyre::bail!("unsupported type `{}`", ty),
+        }
+        Ok(word)
+    }
+}
+
+/// Strips all array dimensions of the type
+fn base_type(ty: &str) -> &str {
+    ty.split('[').next().unwrap_or(ty)
+}
+
+fn as_str(value: &Value) -> Result<&str> {
+    value.as_str().ok_or_else(|| eyre::eyre!("expected a string, got {}", value))
+}
+
+fn decode_hex(s: &str) -> Result<Vec<u8>> {
+    Ok(hex::decode(s.strip_prefix("0x").unwrap_or(s))?)
+}
+
+fn parse_uint(value: &Value) -> Result<U256> {
+    Ok(match value {
+        Value::Number(num) => U256::from_dec_str(&num.to_string())?,
+        Value::String(s) if s.starts_with("0x") => U256::from_str_radix(&s[2..], 16)?,
+        Value::String(s) => U256::from_dec_str(s)?,
+        _ => eyre::bail!("expected an unsigned integer, got {}", value),
+    })
+}
+
+fn parse_int(value: &Value) -> Result<I256> {
+    Ok(match value {
+        Value::Number(num) => I256::from_dec_str(&num.to_string())?,
+        Value::String(s) if s.starts_with("0x") => {
+            I256::from_raw(U256::from_str_radix(&s[2..], 16)?)
+        }
+        Value::String(s) => I256::from_dec_str(s)?,
+        _ => eyre::bail!("expected an integer, got {}", value),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the example of the EIP
+    const MAIL: &str = r#"{
+        "types": {
+            "EIP712Domain": [
+                { "name": "name", "type": "string" },
+                { "name": "version", "type": "string" },
+                { "name": "chainId", "type": "uint256" },
+                { "name": "verifyingContract", "type": "address" }
+            ],
+            "Person": [
+                { "name": "name", "type": "string" },
+                { "name": "wallet", "type": "address" }
+            ],
+            "Mail": [
+                { "name": "from", "type": "Person" },
+                { "name": "to", "type": "Person" },
+                { "name": "contents", "type": "string" }
+            ]
+        },
+        "primaryType": "Mail",
+        "domain": {
+            "name": "Ether Mail",
+            "version": "1",
+            "chainId": 1,
+            "verifyingContract": "0xCcCCccccCCCCcCCCCCCcCcCccCcCCCcCcccccccC"
+        },
+        "message": {
+            "from": { "name": "Cow", "wallet": "0xCD2a3d9F938E13CD947Ec05AbC7FE734Df8DD826" },
+            "to": { "name": "Bob", "wallet": "0xbBbBBBBbbBBBbbbBbbBbbbbBBbBbbbbBbBbbBBbB" },
+            "contents": "Hello, Bob!"
+        }
+    }"#;
+
+    #[test]
+    fn can_hash_typed_data() {
+        let data = TypedData::from_json(MAIL).unwrap();
+        assert_eq!(
+            data.encode_type("Mail").unwrap(),
+            "Mail(Person from,Person to,string contents)Person(string name,address wallet)"
+        );
+        assert_eq!(
+            hex::encode(data.domain_separator().unwrap()),
+            "f2cee375fa42b42143804025fc449deafd50cc031ca257e0b194a650a912090f"
+        );
+        assert_eq!(
+            hex::encode(data.struct_hash().unwrap()),
+            "c52c0ee5d84264471806290a3f2c4cecfc5490626bf912d01f240d7a274b371e"
+        );
+        assert_eq!(
+            hex::encode(data.digest().unwrap()),
+            "be609aee343fb3c4b28e1df9e632fca64fcfaede20f02e86244efddf30957bd2"
+        );
+    }
+
+    #[test]
+    fn can_infer_domain_type() {
+        let mut json: Value = serde_json::from_str(MAIL).unwrap();
+        json["types"].as_object_mut().unwrap().remove(EIP712_DOMAIN);
+        let data = TypedData::from_json(&json.to_string()).unwrap();
+        assert_eq!(
+            hex::encode(data.domain_separator().unwrap()),
+            "f2cee375fa42b42143804025fc449deafd50cc031ca257e0b194a650a912090f"
+        );
+    }
+}
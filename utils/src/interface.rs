@@ -0,0 +1,316 @@
+//! Generation of solidity interfaces from JSON ABIs
+//!
+//! The JSON ABI is used as is rather than as an [`ethers_core::abi::Abi`], which drops the names
+//! of the components of tuples that are needed to reconstruct the structs of the contract.
+
+use eyre::{Result, WrapErr};
+use serde::Deserialize;
+use serde_json::Value;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AbiItem {
+    #[serde(rename = "type", default = "function_type")]
+    ty: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    inputs: Vec<AbiParam>,
+    #[serde(default)]
+    outputs: Vec<AbiParam>,
+    state_mutability: Option<String>,
+    /// the mutability of ABIs of solc < 0.4.16
+    #[serde(default)]
+    constant: bool,
+    #[serde(default)]
+    payable: bool,
+    #[serde(default)]
+    anonymous: bool,
+}
+
+fn function_type() -> String {
+    "function".to_string()
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AbiParam {
+    #[serde(default)]
+    name: String,
+    #[serde(rename = "type")]
+    ty: String,
+    internal_type: Option<String>,
+    #[serde(default)]
+    components: Vec<AbiParam>,
+    #[serde(default)]
+    indexed: bool,
+}
+
+impl AbiParam {
+    /// Whether the param needs a data location in function signatures
+    fn is_reference(&self) -> bool {
+        self.ty == "bytes" || self.ty == "string" || self.ty.ends_with(']') || self.ty == "tuple"
+    }
+}
+
+/// The structs of the tuples of an ABI, in the order they're found
+#[derive(Default)]
+struct Structs {
+    /// the names and the members of the structs
+    structs: Vec<(String, Vec<String>)>,
+}
+
+impl Structs {
+    /// Returns the solidity type of the param, declaring the structs of its tuples
+    fn ty(&mut self, param: &AbiParam) -> String {
+        let suffix = match param.ty.strip_prefix("tuple") {
+            Some(suffix) => suffix,
+            None => return param.ty.clone(),
+        };
+        let members = param
+            .components
+            .iter()
+            .enumerate()
+            .map(|(idx, component)| {
+                let name = if component.name.is_empty() {
+                    format!("field{}", idx)
+                } else {
+                    component.name.clone()
+                };
+                format!("{} {};", self.ty(component), name)
+            })
+            .collect::<Vec<_>>();
+
+        // `struct Pool.Key[]`, the structs of tuples without an internal type are numbered and
+        // shared by the tuples with the same members
+        let name =
+            param.internal_type.as_deref().and_then(|ty| ty.strip_prefix("struct ")).map(|ty| {
+                let ty = ty.split('[').next().unwrap_or(ty);
+                ty.rsplit('.').next().unwrap_or(ty).to_string()
+            });
+        let name = match name {
+            Some(name) => name,
+            None => match self.structs.iter().find(|(_, existing)| *existing == members) {
+                Some((name, _)) => name.clone(),
+                None => format!("Struct{}", self.structs.len()),
+            },
+        };
+        if !self.structs.iter().any(|(existing, _)| *existing == name) {
+            self.structs.push((name.clone(), members));
+        }
+        format!("{}{}", name, suffix)
+    }
+
+    /// Formats the params of a function, with the data location of reference types
+    fn params(&mut self, params: &[AbiParam]) -> String {
+        params
+            .iter()
+            .map(|param| {
+                let mut ty = self.ty(param);
+                if param.is_reference() {
+                    ty.push_str(" memory");
+                }
+                if !param.name.is_empty() {
+                    ty = format!("{} {}", ty, param.name);
+                }
+                ty
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Formats the params of an event or an error
+    fn fields(&mut self, params: &[AbiParam]) -> String {
+        params
+            .iter()
+            .map(|param| {
+                let mut ty = self.ty(param);
+                if param.indexed {
+                    ty.push_str(" indexed");
+                }
+                if !param.name.is_empty() {
+                    ty = format!("{} {}", ty, param.name);
+                }
+                ty
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// Generates a solidity `interface` named `contract_name`, `Interface` if empty, from a JSON ABI
+/// or from an artifact with an `abi`
+///
+/// The structs of tuple params are reconstructed from their components and their internal types,
+/// enums and contracts are declared as their ABI types, like `uint8` and `address`.
+///
+/// Kudos to https://github.com/maxme/abi2solidity for the algorithm
+pub fn abi_to_solidity(abi: &Value, mut contract_name: &str) -> Result<String> {
+    let abi = abi.get("abi").unwrap_or(abi);
+    let items: Vec<AbiItem> = serde_json::from_value(abi.clone()).wrap_err("invalid JSON ABI")?;
+    if contract_name.trim().is_empty() {
+        contract_name = "Interface";
+    }
+
+    let mut structs = Structs::default();
+    let mut events = Vec::new();
+    let mut errors = Vec::new();
+    let mut functions = Vec::new();
+    for item in &items {
+        match item.ty.as_str() {
+            "event" => {
+                let anonymous = if item.anonymous { " anonymous" } else { "" };
+                events.push(format!(
+                    "event {}({}){};",
+                    item.name,
+                    structs.fields(&item.inputs),
+                    anonymous
+                ));
+            }
+            "error" => {
+                errors.push(format!("error {}({});", item.name, structs.fields(&item.inputs)))
+            }
+            "function" => {
+                let mutability = match item.state_mutability.as_deref() {
+                    Some("pure") => "pure",
+                    Some("view") => "view",
+                    Some("payable") => "payable",
+                    Some(_) => "",
+                    None if item.constant => "view",
+                    None if item.payable => "payable",
+                    None => "",
+                };
+                let mut func = format!("function {}({})", item.name, structs.params(&item.inputs));
+                if !mutability.is_empty() {
+                    func = format!("{} {}", func, mutability);
+                }
+                func = format!("{} external", func);
+                let outputs = structs.params(&item.outputs);
+                if !outputs.is_empty() {
+                    func = format!("{} returns ({})", func, outputs);
+                }
+                functions.push(format!("{};", func));
+            }
+            "receive" => functions.push("receive() external payable;".to_string()),
+            "fallback" => {
+                let payable = item.payable || item.state_mutability.as_deref() == Some("payable");
+                functions
+                    .push(format!("fallback() external{};", if payable { " payable" } else { "" }));
+            }
+            _ => {}
+        }
+    }
+
+    let structs = structs
+        .structs
+        .into_iter()
+        .map(|(name, members)| {
+            format!("struct {} {{\n        {}\n    }}", name, members.join("\n        "))
+        })
+        .collect::<Vec<_>>();
+    let sections = [structs, events, errors, functions]
+        .iter()
+        .filter(|section| !section.is_empty())
+        .map(|section| section.join("\n    "))
+        .collect::<Vec<_>>();
+    Ok(format!("interface {} {{\n    {}\n}}\n", contract_name, sections.join("\n\n    ")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reconstructs_structs() {
+        let abi = serde_json::json!([
+            {
+                "type": "function",
+                "name": "swap",
+                "stateMutability": "payable",
+                "inputs": [
+                    {
+                        "name": "key",
+                        "type": "tuple",
+                        "internalType": "struct IPoolManager.PoolKey",
+                        "components": [
+                            { "name": "token0", "type": "address", "internalType": "Currency" },
+                            { "name": "fee", "type": "uint24", "internalType": "uint24" }
+                        ]
+                    },
+                    {
+                        "name": "hops",
+                        "type": "tuple[]",
+                        "internalType": "struct Router.Hop[]",
+                        "components": [
+                            { "name": "pool", "type": "address", "internalType": "address" },
+                            {
+                                "name": "key",
+                                "type": "tuple",
+                                "internalType": "struct IPoolManager.PoolKey",
+                                "components": [
+                                    { "name": "token0", "type": "address" },
+                                    { "name": "fee", "type": "uint24" }
+                                ]
+                            }
+                        ]
+                    }
+                ],
+                "outputs": [
+                    { "name": "", "type": "tuple", "components": [
+                        { "name": "amount0", "type": "int256" },
+                        { "name": "", "type": "int256" }
+                    ]}
+                ]
+            },
+            {
+                "type": "event",
+                "name": "Swap",
+                "anonymous": false,
+                "inputs": [
+                    { "name": "sender", "type": "address", "indexed": true },
+                    { "name": "amount", "type": "uint256", "indexed": false }
+                ]
+            },
+            { "type": "error", "name": "Slippage", "inputs": [{ "name": "min", "type": "uint256" }] },
+            { "type": "receive", "stateMutability": "payable" },
+            { "type": "constructor", "inputs": [] }
+        ]);
+        assert_eq!(
+            abi_to_solidity(&abi, "IRouter").unwrap(),
+            r#"interface IRouter {
+    struct PoolKey {
+        address token0;
+        uint24 fee;
+    }
+    struct Hop {
+        address pool;
+        PoolKey key;
+    }
+    struct Struct2 {
+        int256 amount0;
+        int256 field1;
+    }
+
+    event Swap(address indexed sender, uint256 amount);
+
+    error Slippage(uint256 min);
+
+    function swap(PoolKey memory key, Hop[] memory hops) payable external returns (Struct2 memory);
+    receive() external payable;
+}
+"#
+        );
+    }
+
+    #[test]
+    fn reads_artifacts() {
+        let artifact = serde_json::json!({
+            "abi": [{ "type": "function", "name": "f", "inputs": [], "outputs": [], "stateMutability": "view" }],
+            "bytecode": "0x"
+        });
+        assert_eq!(
+            abi_to_solidity(&artifact, "").unwrap(),
+            "interface Interface {\n    function f() view external;\n}\n"
+        );
+    }
+}
@@ -4,18 +4,26 @@ use ethers_core::{
     abi::{
         self, parse_abi,
         token::{LenientTokenizer, StrictTokenizer, Tokenizer},
-        Abi, AbiParser, Event, Function, Param, ParamType, Token,
+        Abi, AbiParser, Event, Function, ParamType, Token,
     },
     types::*,
 };
 use ethers_etherscan::Client;
 use eyre::{Result, WrapErr};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::{
-    collections::{BTreeMap, HashSet},
+    collections::BTreeMap,
     env::VarError,
+    path::{Path, PathBuf},
 };
 
+pub mod eip712;
+pub mod interface;
+pub mod merkle;
+pub mod metadata;
+
+pub use interface::abi_to_solidity;
+
 const BASE_TX_COST: u64 = 21000;
 
 /// Helper trait for converting types to Functions. Helpful for allowing the `call`
@@ -65,6 +73,26 @@ pub fn remove_extra_costs(gas: U256, calldata: &[u8]) -> U256 {
     gas.saturating_sub(calldata_cost.into()).saturating_sub(BASE_TX_COST.into())
 }
 
+/// The canonical CREATE2 deployer, `0x4e59b44847b379578588920cA78FbF26c0B4956C`, which is
+/// deployed at the same address on most chains
+///
+/// Its calldata is the 32 byte salt followed by the init code.
+pub const DEFAULT_CREATE2_DEPLOYER: Address = H160([
+    0x4e, 0x59, 0xb4, 0x48, 0x47, 0xb3, 0x79, 0x57, 0x85, 0x88, 0x92, 0x0c, 0xa7, 0x8f, 0xbf, 0x26,
+    0xc0, 0xb4, 0x95, 0x6c,
+]);
+
+/// Returns the address of a contract created with CREATE2 by the `deployer`, which is
+/// `keccak256(0xff ‖ deployer ‖ salt ‖ keccak256(init_code))[12..]`
+pub fn create2_address(deployer: Address, salt: H256, init_code: &[u8]) -> Address {
+    let mut preimage = Vec::with_capacity(85);
+    preimage.push(0xff);
+    preimage.extend_from_slice(deployer.as_bytes());
+    preimage.extend_from_slice(salt.as_bytes());
+    preimage.extend_from_slice(&ethers_core::utils::keccak256(init_code));
+    Address::from_slice(&ethers_core::utils::keccak256(preimage)[12..])
+}
+
 /// Flattens a group of contracts into maps of all events and functions
 pub fn flatten_known_contracts(
     contracts: &BTreeMap<String, (Abi, Vec<u8>)>,
@@ -225,6 +253,11 @@ pub fn to_table(value: serde_json::Value) -> String {
 
 /// Given a function signature string, it tries to parse it as a `Function`
 pub fn get_func(sig: &str) -> Result<Function> {
+    // nested tuples are not supported by the human readable abi parser, so the signature is
+    // converted into a json abi
+    if sig.contains("((") || sig.contains(",(") || sig.contains("tuple(") {
+        return parse_signature(sig)
+    }
     // TODO: Make human readable ABI better / more minimal
     let abi = parse_abi(&[sig])?;
     // get the function
@@ -234,6 +267,108 @@ pub fn get_func(sig: &str) -> Result<Function> {
     Ok(func.clone())
 }
 
+/// Parses a function signature with optional outputs, like
+/// `f((uint256,address)[],bytes)(bool)` or `f((uint a, address b) data) returns (bool)`, into a
+/// [`Function`], tuples and arrays can be nested arbitrarily
+pub fn parse_signature(sig: &str) -> Result<Function> {
+    let sig = sig.trim();
+    let sig = sig.strip_prefix("function ").unwrap_or(sig).trim();
+    let open = sig.find('(').ok_or_else(|| eyre::eyre!("missing `(` in `{}`", sig))?;
+    let name = sig[..open].trim();
+    let (inputs, rest) = split_parens(&sig[open..])?;
+    // the outputs are the next parenthesized list, after `returns` or modifiers like `view`
+    let outputs = match rest.find('(') {
+        Some(idx) => split_parens(&rest[idx..])?.0,
+        None => "",
+    };
+
+    let abi = serde_json::json!([{
+        "type": "function",
+        "name": name,
+        "inputs": params_json(inputs)?,
+        "outputs": params_json(outputs)?,
+        "stateMutability": "nonpayable",
+    }]);
+    let abi: Abi = serde_json::from_value(abi)?;
+    let func = abi.functions().next().ok_or_else(|| eyre::eyre!("function name not found"))?;
+    Ok(func.clone())
+}
+
+/// Returns the content of the parentheses the string starts with and the rest after them
+fn split_parens(s: &str) -> Result<(&str, &str)> {
+    let mut depth = 0;
+    for (idx, c) in s.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok((&s[1..idx], &s[idx + 1..]))
+                }
+            }
+            _ => {}
+        }
+    }
+    eyre::bail!("unbalanced parentheses in `{}`", s)
+}
+
+/// Splits the parameter list at its top level commas
+fn split_params(s: &str) -> Vec<&str> {
+    let mut params = Vec::new();
+    let (mut depth, mut start) = (0, 0);
+    for (idx, c) in s.char_indices() {
+        match c {
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth -= 1,
+            ',' if depth == 0 => {
+                params.push(&s[start..idx]);
+                start = idx + 1;
+            }
+            _ => {}
+        }
+    }
+    params.push(&s[start..]);
+    params.into_iter().map(str::trim).filter(|param| !param.is_empty()).collect()
+}
+
+/// Converts a parameter list into the params of a json abi
+fn params_json(params: &str) -> Result<Vec<serde_json::Value>> {
+    split_params(params).into_iter().map(param_json).collect()
+}
+
+/// Converts a parameter like `(uint a, bool)[] memory data` into a param of a json abi
+fn param_json(param: &str) -> Result<serde_json::Value> {
+    let param = param.strip_prefix("tuple").filter(|p| p.starts_with('(')).unwrap_or(param);
+    let (ty, components, rest) = if param.starts_with('(') {
+        let (inner, rest) = split_parens(param)?;
+        // array suffixes directly follow the closing parenthesis
+        let suffix_len = rest.find(|c: char| c.is_whitespace()).unwrap_or(rest.len());
+        let (suffix, rest) = rest.split_at(suffix_len);
+        (format!("tuple{}", suffix), Some(params_json(inner)?), rest)
+    } else {
+        let ty_len = param.find(|c: char| c.is_whitespace()).unwrap_or(param.len());
+        let (ty, rest) = param.split_at(ty_len);
+        // `uint` and `int` are aliases of `uint256` and `int256`
+        let ty = match ty.find('[').map(|idx| ty.split_at(idx)).unwrap_or((ty, "")) {
+            ("uint", suffix) => format!("uint256{}", suffix),
+            ("int", suffix) => format!("int256{}", suffix),
+            _ => ty.to_string(),
+        };
+        (ty, None, rest)
+    };
+    let name = rest
+        .split_whitespace()
+        .filter(|word| !matches!(*word, "memory" | "calldata" | "storage" | "indexed"))
+        .last()
+        .unwrap_or_default();
+
+    let mut json = serde_json::json!({ "name": name, "type": ty });
+    if let Some(components) = components {
+        json["components"] = serde_json::Value::Array(components);
+    }
+    Ok(json)
+}
+
 // Given a function name, address, and args, tries to parse it as a `Function` by fetching the
 // abi from etherscan. If the address is a proxy, fetches the ABI of the implementation contract.
 pub async fn get_func_etherscan(
@@ -243,15 +378,7 @@ pub async fn get_func_etherscan(
     chain: Chain,
     etherscan_api_key: String,
 ) -> Result<Function> {
-    let client = Client::new(chain, etherscan_api_key)?;
-    let metadata = &client.contract_source_code(contract).await?.items[0];
-
-    let abi = if metadata.implementation.is_empty() {
-        serde_json::from_str(&metadata.abi)?
-    } else {
-        let implementation = metadata.implementation.parse::<Address>()?;
-        client.contract_abi(implementation).await?
-    };
+    let abi = fetch_abi_etherscan(contract, chain, etherscan_api_key).await?;
 
     let empty = vec![];
     let funcs = abi.functions.get(function_name).unwrap_or(&empty);
@@ -266,6 +393,55 @@ pub async fn get_func_etherscan(
     Err(eyre::eyre!("Function not found in abi"))
 }
 
+/// Fetches the ABI of a verified contract from Etherscan, following proxies to the ABI of their
+/// implementation
+pub async fn fetch_abi_etherscan(
+    contract: Address,
+    chain: Chain,
+    etherscan_api_key: String,
+) -> Result<Abi> {
+    Ok(fetch_contract_etherscan(contract, chain, etherscan_api_key).await?.1)
+}
+
+/// Fetches the name and ABI of a verified contract from Etherscan, following proxies to the ABI
+/// of their implementation
+pub async fn fetch_contract_etherscan(
+    contract: Address,
+    chain: Chain,
+    etherscan_api_key: String,
+) -> Result<(String, Abi)> {
+    let client = Client::new(chain, etherscan_api_key)?;
+    let metadata = &client.contract_source_code(contract).await?.items[0];
+    if metadata.abi.starts_with("Contract source code not verified") {
+        eyre::bail!("contract {:?} is not verified", contract)
+    }
+
+    let abi = if metadata.implementation.is_empty() {
+        serde_json::from_str(&metadata.abi)?
+    } else {
+        let implementation = metadata.implementation.parse::<Address>()?;
+        client.contract_abi(implementation).await?
+    };
+    Ok((metadata.contract_name.clone(), abi))
+}
+
+/// Decodes the log with the first matching event of the ABI, formatted like
+/// `Transfer(from: 0x.., to: 0x.., value: 1)`
+pub fn decode_log(abi: &Abi, log: &Log) -> Option<String> {
+    let topic = log.topics.first()?;
+    abi.events().filter(|event| !event.anonymous && event.signature() == *topic).find_map(|event| {
+        let raw = abi::RawLog { topics: log.topics.clone(), data: log.data.to_vec() };
+        let decoded = event.parse_log(raw).ok()?;
+        let params = decoded
+            .params
+            .iter()
+            .map(|param| format!("{}: {}", param.name, format_token(&param.value)))
+            .collect::<Vec<_>>()
+            .join(", ");
+        Some(format!("{}({})", event.name, params))
+    })
+}
+
 /// Parses string input as Token against the expected ParamType
 pub fn parse_tokens<'a, I: IntoIterator<Item = (&'a ParamType, &'a str)>>(
     params: I,
@@ -279,6 +455,11 @@ pub fn parse_tokens<'a, I: IntoIterator<Item = (&'a ParamType, &'a str)>>(
                 ParamType::Address => value.strip_prefix("0x").unwrap_or(value),
                 ParamType::Bytes => value.strip_prefix("0x").unwrap_or(value),
                 ParamType::FixedBytes(_size) => value.strip_prefix("0x").unwrap_or(value),
+                // allow amounts to be passed with a unit, like `1ether`
+                ParamType::Uint(_) if split_unit(value).is_some() => {
+                    let amount = parse_amount(value).map_err(|_| abi::Error::InvalidData)?;
+                    return Ok(Token::Uint(amount))
+                }
                 _ => value,
             };
             if lenient {
@@ -291,6 +472,31 @@ pub fn parse_tokens<'a, I: IntoIterator<Item = (&'a ParamType, &'a str)>>(
         .wrap_err("Failed to parse tokens")
 }
 
+/// Splits an amount like `1.5ether` or `10 gwei` into the number and the decimals of its unit
+fn split_unit(value: &str) -> Option<(&str, u32)> {
+    let value = value.trim();
+    let lower = value.to_lowercase();
+    [("ether", 18), ("eth", 18), ("gwei", 9), ("wei", 0)].iter().find_map(|(unit, decimals)| {
+        lower
+            .ends_with(unit)
+            .then(|| (value[..value.len() - unit.len()].trim(), *decimals))
+            .filter(|(number, _)| !number.is_empty())
+    })
+}
+
+/// Parses an amount of wei, which can be passed with a unit, like `1ether`, `1.5 ether` or
+/// `10gwei`, in decimal or in hex with a `0x` prefix
+pub fn parse_amount(value: &str) -> Result<U256> {
+    if let Some((number, decimals)) = split_unit(value) {
+        return Ok(ethers_core::utils::parse_units(number, decimals)?)
+    }
+    let value = value.trim();
+    Ok(match value.strip_prefix("0x") {
+        Some(hex) => U256::from_str_radix(hex, 16)?,
+        None => U256::from_dec_str(value)?,
+    })
+}
+
 /// Given a function and a vector of string arguments, it proceeds to convert the args to ethabi
 /// Tokens and then ABI encode them.
 pub fn encode_args(func: &Function, args: &[impl AsRef<str>]) -> Result<Vec<u8>> {
@@ -304,8 +510,30 @@ pub fn encode_args(func: &Function, args: &[impl AsRef<str>]) -> Result<Vec<u8>>
     Ok(func.encode_input(&tokens)?)
 }
 
+/// The api of 4byte.directory
+pub const FOURBYTE_API_URL: &str = "https://www.4byte.directory/api/v1";
+
 /// Fetches a function signature given the selector using 4byte.directory
 pub async fn fourbyte(selector: &str) -> Result<Vec<(String, i32)>> {
+    let selector = &selector.strip_prefix("0x").unwrap_or(selector);
+    if selector.len() < 8 {
+        return Err(eyre::eyre!("Invalid selector"))
+    }
+    fourbyte_lookup("signatures", &selector[..8]).await
+}
+
+/// Fetches the event signatures given the topic using 4byte.directory
+pub async fn fourbyte_event(topic: &str) -> Result<Vec<(String, i32)>> {
+    let topic = topic.strip_prefix("0x").unwrap_or(topic);
+    if topic.len() != 64 {
+        return Err(eyre::eyre!("Invalid topic"))
+    }
+    fourbyte_lookup("event-signatures", topic).await
+}
+
+/// Fetches the signatures of the hex signature from the `endpoint` of 4byte.directory, with their
+/// ids
+async fn fourbyte_lookup(endpoint: &str, hex_signature: &str) -> Result<Vec<(String, i32)>> {
     #[derive(Deserialize)]
     struct Decoded {
         text_signature: String,
@@ -317,13 +545,7 @@ pub async fn fourbyte(selector: &str) -> Result<Vec<(String, i32)>> {
         results: Vec<Decoded>,
     }
 
-    let selector = &selector.strip_prefix("0x").unwrap_or(selector);
-    if selector.len() < 8 {
-        return Err(eyre::eyre!("Invalid selector"))
-    }
-    let selector = &selector[..8];
-
-    let url = format!("https://www.4byte.directory/api/v1/signatures/?hex_signature={}", selector);
+    let url = format!("{}/{}/?hex_signature={}", FOURBYTE_API_URL, endpoint, hex_signature);
     let res = reqwest::get(url).await?;
     let api_response = res.json::<ApiResponse>().await?;
 
@@ -334,6 +556,28 @@ pub async fn fourbyte(selector: &str) -> Result<Vec<(String, i32)>> {
         .collect::<Vec<(String, i32)>>())
 }
 
+/// The outcome of importing signatures into 4byte.directory
+#[derive(Debug, Clone, Deserialize)]
+pub struct SignatureImport {
+    pub num_processed: u64,
+    pub num_imported: u64,
+    pub num_duplicates: u64,
+    pub num_ignored: u64,
+}
+
+/// Imports the signatures of the functions and events of the JSON ABI into 4byte.directory
+pub async fn fourbyte_import_abi(abi: &serde_json::Value) -> Result<SignatureImport> {
+    let resp = reqwest::Client::new()
+        .post(format!("{}/import-abi/", FOURBYTE_API_URL))
+        .json(&serde_json::json!({ "contract_abi": abi.to_string() }))
+        .send()
+        .await?;
+    if !resp.status().is_success() {
+        eyre::bail!("4byte.directory rejected the ABI: {}", resp.text().await?)
+    }
+    Ok(resp.json().await?)
+}
+
 pub async fn fourbyte_possible_sigs(calldata: &str, id: Option<String>) -> Result<Vec<String>> {
     let mut sigs = fourbyte(calldata).await?;
 
@@ -378,10 +622,13 @@ pub async fn fourbyte_possible_sigs(calldata: &str, id: Option<String>) -> Resul
 }
 
 pub fn abi_decode(sig: &str, calldata: &str, input: bool) -> Result<Vec<Token>> {
-    let func = IntoFunction::into(sig);
+    let func = get_func(sig)?;
     let calldata = calldata.strip_prefix("0x").unwrap_or(calldata);
     let calldata = hex::decode(calldata)?;
     let res = if input {
+        if calldata.len() < 4 {
+            eyre::bail!("calldata is shorter than a function selector")
+        }
         // need to strip the function selector
         func.decode_input(&calldata[4..])?
     } else {
@@ -471,156 +718,221 @@ pub fn etherscan_api_key() -> eyre::Result<String> {
     })
 }
 
-// Helper for generating solidity abi encoder v2 field names.
-const ASCII_LOWER: [char; 26] = [
-    'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l', 'm', 'n', 'o', 'p', 'q', 'r', 's',
-    't', 'u', 'v', 'w', 'x', 'y', 'z',
-];
-
-fn capitalize(s: &str) -> String {
-    let mut c = s.chars();
-    match c.next() {
-        None => String::new(),
-        Some(f) => f.to_uppercase().chain(c).collect(),
-    }
-}
-
-// Returns the function parameter formatted as a string, as well as inserts into the provided
-// `structs` set in order to create type definitions for any Abi Encoder v2 structs.
-fn format_param(param: &Param, structs: &mut HashSet<String>) -> String {
-    // check if it requires a memory tag
-    let is_memory = matches!(
-        param.kind,
-        ParamType::Array(_) |
-            ParamType::Bytes |
-            ParamType::String |
-            ParamType::FixedArray(_, _) |
-            ParamType::Tuple(_),
-    );
-
-    let (kind, v2_struct) = match param.kind {
-        // We need to do some extra work to parse ABI Encoder V2 types.
-        ParamType::Tuple(ref args) => {
-            let name = param.internal_type.clone().unwrap_or_else(|| capitalize(&param.name));
-            let name = if name.contains('.') {
-                name.split('.').nth(1).expect("could not get struct name").to_owned()
-            } else {
-                name
-            };
+/// A precompiled contract artifact in the format Hardhat writes to `artifacts/**/<Name>.json`
+///
+/// Truffle artifacts share the fields that are required, so they can be read as well.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HardhatArtifact {
+    #[serde(rename = "_format", default)]
+    pub format: String,
+    pub contract_name: String,
+    #[serde(default)]
+    pub source_name: String,
+    pub abi: Abi,
+    /// The creation bytecode, hex encoded
+    pub bytecode: String,
+    /// The runtime bytecode, hex encoded
+    pub deployed_bytecode: String,
+    #[serde(default)]
+    pub link_references: BTreeMap<String, serde_json::Value>,
+    #[serde(default)]
+    pub deployed_link_references: BTreeMap<String, serde_json::Value>,
+}
 
-            // NB: This does not take into account recursive ABI Encoder v2 structs. Left
-            // as future work.
-            let args = args
-                .iter()
-                .enumerate()
-                // Unfortunately Solidity does not support unnamed struct fields, so we
-                // just codegen ones alphabetically.
-                .map(|(i, x)| format!("{} {};", x, ASCII_LOWER[i]))
-                .collect::<Vec<_>>()
-                .join(" ");
-
-            let v2_struct = format!("struct {} {{ {} }}", name, args);
-            (name, Some(v2_struct))
-        }
-        // If not, just get the string of the param kind.
-        _ => (param.kind.to_string(), None),
-    };
+impl HardhatArtifact {
+    /// The format of Hardhat's artifacts
+    pub const FORMAT: &'static str = "hh-sol-artifact-1";
 
-    // add `memory` if required
-    let kind = if is_memory { format!("{} memory", kind) } else { kind };
+    /// The directory, relative to the project root, Hardhat writes its artifacts to
+    pub const DIR: &'static str = "artifacts";
 
-    // if there was a v2 struct, push it for later usage
-    if let Some(v2_struct) = v2_struct {
-        structs.insert(v2_struct);
+    /// Reads all artifacts in the directory and its subdirectories
+    ///
+    /// Json files that aren't artifacts, like Hardhat's debug files and build infos, are skipped.
+    pub fn read_all(dir: impl AsRef<Path>) -> Result<Vec<Self>> {
+        let dir = dir.as_ref();
+        let mut artifacts = Vec::new();
+        if !dir.is_dir() {
+            return Ok(artifacts)
+        }
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                if !path.ends_with("build-info") {
+                    artifacts.extend(Self::read_all(&path)?);
+                }
+                continue
+            }
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            if !name.ends_with(".json") || name.ends_with(".dbg.json") {
+                continue
+            }
+            let content = std::fs::read_to_string(&path)
+                .wrap_err_with(|| format!("failed to read {}", path.display()))?;
+            if let Ok(artifact) = serde_json::from_str::<Self>(&content) {
+                artifacts.push(artifact);
+            }
+        }
+        Ok(artifacts)
     }
 
-    if param.name.is_empty() {
-        kind
-    } else {
-        format!("{} {}", kind, param.name)
+    /// Adds the abi and runtime bytecode of all artifacts in the directories to the contracts by
+    /// name, contracts that are already known take precedence over the precompiled ones
+    pub fn extend_known_contracts(
+        known_contracts: &mut BTreeMap<String, (Abi, Vec<u8>)>,
+        dirs: &[PathBuf],
+    ) -> Result<()> {
+        for dir in dirs {
+            for artifact in Self::read_all(dir)? {
+                if let Some(runtime_code) = artifact.deployed_code() {
+                    known_contracts
+                        .entry(artifact.contract_name)
+                        .or_insert((artifact.abi, runtime_code));
+                }
+            }
+        }
+        Ok(())
     }
-}
-
-/// This function takes a contract [`Abi`] and a name and proceeds to generate a Solidity
-/// `interface` from that ABI. If the provided name is empty, then it defaults to `interface
-/// Interface`.
-///
-/// This is done by iterating over the functions and their ABI inputs/outputs, and generating
-/// function signatures/inputs/outputs according to the ABI.
-///
-/// Notes:
-/// * ABI Encoder V2 is not supported yet
-/// * Kudos to https://github.com/maxme/abi2solidity for the algorithm
-pub fn abi_to_solidity(contract_abi: &Abi, mut contract_name: &str) -> Result<String> {
-    let functions_iterator = contract_abi.functions();
-    if contract_name.trim().is_empty() {
-        contract_name = "Interface";
-    };
 
-    // instantiate an array of all ABI Encoder v2 structs
-    let mut structs = HashSet::new();
+    /// Returns the creation bytecode, `None` if it's empty or has unlinked libraries
+    pub fn code(&self) -> Option<Vec<u8>> {
+        decode_artifact_code(&self.bytecode).filter(|code| !code.is_empty())
+    }
 
-    let functions = functions_iterator
-        .map(|function| {
-            let inputs = function
-                .inputs
-                .iter()
-                .map(|param| format_param(param, &mut structs))
-                .collect::<Vec<String>>()
-                .join(", ");
-            let outputs = function
-                .outputs
-                .iter()
-                .map(|param| format_param(param, &mut structs))
-                .collect::<Vec<String>>()
-                .join(", ");
-
-            let mutability = match function.state_mutability {
-                abi::StateMutability::Pure => "pure",
-                abi::StateMutability::View => "view",
-                abi::StateMutability::Payable => "payable",
-                _ => "",
-            };
+    /// Returns the runtime bytecode, `None` if it's empty or has unlinked libraries
+    pub fn deployed_code(&self) -> Option<Vec<u8>> {
+        decode_artifact_code(&self.deployed_bytecode).filter(|code| !code.is_empty())
+    }
+}
 
-            let mut func = format!("function {}({})", function.name, inputs);
-            if !mutability.is_empty() {
-                func = format!("{} {}", func, mutability);
-            }
-            func = format!("{} external", func);
-            if !outputs.is_empty() {
-                func = format!("{} returns ({})", func, outputs);
-            }
-            format!("{};", func)
-        })
-        .collect::<Vec<_>>()
-        .join("\n    ");
-
-    Ok(if structs.is_empty() {
-        format!(
-            r#"interface {} {{
-    {}
-}}
-"#,
-            contract_name, functions
-        )
-    } else {
-        let structs = structs.into_iter().collect::<Vec<_>>().join("\n    ");
-        format!(
-            r#"interface {} {{
-    {}
-
-    {}
-}}
-"#,
-            contract_name, structs, functions
-        )
-    })
+/// Decodes the hex encoded bytecode of an artifact, with or without `0x` prefix, `None` if it has
+/// unlinked libraries
+pub fn decode_artifact_code(code: &str) -> Option<Vec<u8>> {
+    hex::decode(code.strip_prefix("0x").unwrap_or(code)).ok()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use ethers_core::abi::Abi;
+
+    #[test]
+    fn can_compute_create2_addresses() {
+        // the examples of EIP-1014
+        assert_eq!(
+            create2_address(Address::zero(), H256::zero(), &[0x00]),
+            "0x4D1A2e2bB4F88F0250f26Ffff098B0b30B26BF38".parse::<Address>().unwrap()
+        );
+        assert_eq!(
+            create2_address(
+                "0xdeadbeef00000000000000000000000000000000".parse().unwrap(),
+                H256::zero(),
+                &[0x00]
+            ),
+            "0xB928f69Bb1D91Cd65274e3c79d8986362984fDA3".parse::<Address>().unwrap()
+        );
+        assert_eq!(
+            format!("{:?}", DEFAULT_CREATE2_DEPLOYER),
+            "0x4e59b44847b379578588920ca78fbf26c0b4956c"
+        );
+    }
+
+    #[test]
+    fn can_decode_logs() {
+        let abi =
+            parse_abi(&["event Transfer(address indexed from, address indexed to, uint256 value)"])
+                .unwrap();
+        let event = abi.event("Transfer").unwrap();
+        let log = Log {
+            topics: vec![event.signature(), H256::zero(), H256::from_low_u64_be(1)],
+            data: abi::encode(&[Token::Uint(42.into())]).into(),
+            ..Default::default()
+        };
+        assert_eq!(
+            decode_log(&abi, &log).unwrap(),
+            "Transfer(from: 0x0000000000000000000000000000000000000000, to: 0x0000000000000000000000000000000000000001, value: 42)"
+        );
+
+        let unknown = Log { topics: vec![H256::zero()], ..Default::default() };
+        assert!(decode_log(&abi, &unknown).is_none());
+    }
+
+    #[test]
+    fn can_parse_nested_tuple_signatures() {
+        let func = get_func("f((uint a,(address,bool)[] b)[],bytes data)(bool)").unwrap();
+        assert_eq!(func.signature(), "f((uint256,(address,bool)[])[],bytes):(bool)");
+        assert_eq!(func.inputs[1].name, "data");
+
+        let func =
+            parse_signature("function g(tuple(uint256,string) memory x) returns (uint)").unwrap();
+        assert_eq!(func.signature(), "g((uint256,string)):(uint256)");
+
+        let calldata = hex::encode(
+            func.encode_input(&[Token::Tuple(vec![
+                Token::Uint(1.into()),
+                Token::String("gm".to_string()),
+            ])])
+            .unwrap(),
+        );
+        let decoded = abi_decode("g((uint256,string))", &calldata, true).unwrap();
+        assert_eq!(format_token(&decoded[0]), "(1, \"gm\")");
+    }
+
+    #[test]
+    fn can_parse_amounts_with_units() {
+        assert_eq!(parse_amount("1ether").unwrap(), U256::exp10(18));
+        assert_eq!(parse_amount("1.5 ether").unwrap(), U256::exp10(17) * 15);
+        assert_eq!(parse_amount("10gwei").unwrap(), U256::exp10(10));
+        assert_eq!(parse_amount("100wei").unwrap(), 100.into());
+        assert_eq!(parse_amount("100").unwrap(), 100.into());
+        assert_eq!(parse_amount("0x64").unwrap(), 100.into());
+        assert!(parse_amount("ether").is_err());
+
+        let func = AbiParser::default().parse_function("transfer(address,uint256)").unwrap();
+        let tokens = parse_tokens(
+            func.inputs
+                .iter()
+                .map(|input| &input.kind)
+                .zip(["0x0000000000000000000000000000000000000001", "1ether"]),
+            true,
+        )
+        .unwrap();
+        assert_eq!(tokens[1], Token::Uint(U256::exp10(18)));
+    }
+
+    #[test]
+    fn can_read_hardhat_artifacts() {
+        let dir = std::env::temp_dir().join("foundry-utils-hardhat-artifacts");
+        let contracts = dir.join("contracts/Greeter.sol");
+        std::fs::create_dir_all(&contracts).unwrap();
+        std::fs::create_dir_all(dir.join("build-info")).unwrap();
+        std::fs::write(
+            contracts.join("Greeter.json"),
+            r#"{
+                "_format": "hh-sol-artifact-1",
+                "contractName": "Greeter",
+                "sourceName": "contracts/Greeter.sol",
+                "abi": [],
+                "bytecode": "0x6080",
+                "deployedBytecode": "0x",
+                "linkReferences": {},
+                "deployedLinkReferences": {}
+            }"#,
+        )
+        .unwrap();
+        std::fs::write(contracts.join("Greeter.dbg.json"), r#"{"_format": "hh-sol-dbg-1"}"#)
+            .unwrap();
+        std::fs::write(dir.join("build-info/1234.json"), "{}").unwrap();
+
+        let artifacts = HardhatArtifact::read_all(&dir).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(artifacts.len(), 1);
+        let artifact = &artifacts[0];
+        assert_eq!(artifact.contract_name, "Greeter");
+        assert_eq!(artifact.source_name, "contracts/Greeter.sol");
+        assert_eq!(artifact.code(), Some(vec![0x60, 0x80]));
+        assert_eq!(artifact.deployed_code(), None);
+    }
 
     #[test]
     fn test_resolve_addr() {
@@ -683,7 +995,7 @@ mod tests {
     }
     #[test]
     fn abi2solidity() {
-        let contract_abi: Abi =
+        let contract_abi: serde_json::Value =
             serde_json::from_slice(&std::fs::read("testdata/interfaceTestABI.json").unwrap())
                 .unwrap();
         assert_eq!(
@@ -0,0 +1,182 @@
+//! Merkle trees of sorted pairs, as verified by OpenZeppelin's `MerkleProof`, and the leaves of
+//! airdrop claims
+
+use ethers_core::{
+    types::{Address, H256, U256},
+    utils::keccak256,
+};
+use eyre::{Context, Result};
+
+/// A claim of `amount` tokens by `account`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AirdropClaim {
+    pub account: Address,
+    pub amount: U256,
+}
+
+impl AirdropClaim {
+    /// The leaf of the claim, `keccak256(abi.encodePacked(account, amount))`
+    pub fn leaf(&self) -> H256 {
+        let mut packed = self.account.as_bytes().to_vec();
+        let mut amount = [0u8; 32];
+        self.amount.to_big_endian(&mut amount);
+        packed.extend_from_slice(&amount);
+        H256(keccak256(packed))
+    }
+
+    /// Parses the claims of a CSV with an `address,amount` row per claim, the amounts are parsed
+    /// like `--value` amounts and a header row is skipped
+    pub fn from_csv(csv: &str) -> Result<Vec<Self>> {
+        let mut claims = Vec::new();
+        for (idx, line) in csv.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue
+            }
+            let (account, amount) = line
+                .split_once(',')
+                .ok_or_else(|| eyre::eyre!("line {}: expected `address,amount`", idx + 1))?;
+            let account = match account.trim().parse::<Address>() {
+                Ok(account) => account,
+                Err(_) if claims.is_empty() => continue,
+                Err(err) => eyre::bail!("line {}: invalid address: {}", idx + 1, err),
+            };
+            let amount = crate::parse_amount(amount)
+                .wrap_err_with(|| format!("line {}: invalid amount", idx + 1))?;
+            claims.push(AirdropClaim { account, amount });
+        }
+        Ok(claims)
+    }
+}
+
+/// Hashes the pair in sorted order, so proofs don't need to record the side of the siblings
+pub fn hash_pair(a: H256, b: H256) -> H256 {
+    let (first, second) = if a <= b { (a, b) } else { (b, a) };
+    let mut packed = first.as_bytes().to_vec();
+    packed.extend_from_slice(second.as_bytes());
+    H256(keccak256(packed))
+}
+
+/// Returns whether the `proof` proves that the `leaf` is part of the tree with the `root`
+pub fn verify(root: H256, leaf: H256, proof: &[H256]) -> bool {
+    proof.iter().fold(leaf, |node, sibling| hash_pair(node, *sibling)) == root
+}
+
+/// A merkle tree of sorted pairs, an unpaired node is moved up to the next layer as is
+#[derive(Debug, Clone)]
+pub struct MerkleTree {
+    layers: Vec<Vec<H256>>,
+}
+
+impl MerkleTree {
+    /// Builds the tree of the leaves, in their order
+    pub fn new(leaves: Vec<H256>) -> Result<Self> {
+        if leaves.is_empty() {
+            eyre::bail!("a merkle tree needs at least one leaf")
+        }
+        let mut layers = vec![leaves];
+        while layers.last().unwrap().len() > 1 {
+            let layer = layers
+                .last()
+                .unwrap()
+                .chunks(2)
+                .map(|pair| if pair.len() == 2 { hash_pair(pair[0], pair[1]) } else { pair[0] })
+                .collect();
+            layers.push(layer);
+        }
+        Ok(Self { layers })
+    }
+
+    pub fn leaves(&self) -> &[H256] {
+        &self.layers[0]
+    }
+
+    pub fn root(&self) -> H256 {
+        self.layers.last().unwrap()[0]
+    }
+
+    /// The siblings of the leaf at the `index` from the bottom to the top of the tree, `None` if
+    /// there's no such leaf
+    pub fn proof(&self, mut index: usize) -> Option<Vec<H256>> {
+        if index >= self.leaves().len() {
+            return None
+        }
+        let mut proof = Vec::new();
+        for layer in &self.layers[..self.layers.len() - 1] {
+            if let Some(sibling) = layer.get(index ^ 1) {
+                proof.push(*sibling);
+            }
+            index /= 2;
+        }
+        Some(proof)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn claims() -> Vec<AirdropClaim> {
+        (1..=5u64)
+            .map(|n| AirdropClaim {
+                account: Address::from_low_u64_be(n),
+                amount: (n * 100).into(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn proves_every_leaf() {
+        let leaves = claims().iter().map(AirdropClaim::leaf).collect::<Vec<_>>();
+        let tree = MerkleTree::new(leaves.clone()).unwrap();
+        assert_eq!(
+            tree.root(),
+            hash_pair(
+                hash_pair(hash_pair(leaves[0], leaves[1]), hash_pair(leaves[2], leaves[3])),
+                leaves[4]
+            )
+        );
+        for (idx, leaf) in leaves.iter().enumerate() {
+            let proof = tree.proof(idx).unwrap();
+            assert!(verify(tree.root(), *leaf, &proof));
+            assert!(!verify(tree.root(), leaves[(idx + 1) % leaves.len()], &proof));
+        }
+        assert!(tree.proof(leaves.len()).is_none());
+    }
+
+    #[test]
+    fn single_leaf_is_root() {
+        let leaf = claims()[0].leaf();
+        let tree = MerkleTree::new(vec![leaf]).unwrap();
+        assert_eq!(tree.root(), leaf);
+        assert!(tree.proof(0).unwrap().is_empty());
+        assert!(MerkleTree::new(Vec::new()).is_err());
+    }
+
+    #[test]
+    fn encodes_packed_leaf() {
+        let claim = AirdropClaim { account: Address::repeat_byte(0x11), amount: 1u64.into() };
+        let mut packed = vec![0x11; 20];
+        packed.extend_from_slice(&[0; 31]);
+        packed.push(1);
+        assert_eq!(claim.leaf(), H256(keccak256(packed)));
+    }
+
+    #[test]
+    fn parses_csv() {
+        let csv = "account,amount\n\
+                   0x0000000000000000000000000000000000000001, 100\n\
+                   \n\
+                   0x0000000000000000000000000000000000000002,0x64\n\
+                   0x0000000000000000000000000000000000000003,1gwei\n";
+        let claims = AirdropClaim::from_csv(csv).unwrap();
+        assert_eq!(claims.len(), 3);
+        assert_eq!(claims[2].amount, U256::exp10(9));
+        assert_eq!(claims[0].amount, claims[1].amount);
+        assert_eq!(claims[1].account, Address::from_low_u64_be(2));
+
+        let err = AirdropClaim::from_csv("0x0000000000000000000000000000000000000001,1\nfoo,1")
+            .unwrap_err();
+        assert!(err.to_string().contains("line 2"));
+    }
+}
@@ -0,0 +1,84 @@
+//! The metadata solc emits for a contract and the hash of it that solc appends to the bytecode
+
+use eyre::Result;
+use serde_json::Value;
+
+/// The metadata of a contract in solc's json output
+#[derive(Debug, Clone, PartialEq)]
+pub struct SolcMetadata {
+    /// The metadata json, exactly as emitted by solc
+    pub raw: String,
+    pub json: Value,
+}
+
+impl SolcMetadata {
+    /// Reads the `metadata` field of a contract in solc's json output
+    ///
+    /// solc emits the metadata as a json string, which is parsed, an already parsed object is
+    /// accepted as well. Returns `None` if the field is missing or empty.
+    pub fn from_output(metadata: &Value) -> Result<Option<Self>> {
+        let raw = match metadata {
+            Value::Null => return Ok(None),
+            Value::String(raw) if raw.is_empty() => return Ok(None),
+            Value::String(raw) => raw.clone(),
+            metadata => serde_json::to_string(metadata)?,
+        };
+        let json = serde_json::from_str(&raw)?;
+        Ok(Some(Self { raw, json }))
+    }
+}
+
+/// Returns the IPFS hash of the metadata that solc appends CBOR encoded to the deployed bytecode
+///
+/// The hash is the base58 encoded multihash stored under the `ipfs` key.
+pub fn bytecode_metadata_hash(code: &[u8]) -> Option<String> {
+    // the last two bytes are the length of the CBOR encoded metadata
+    let len = code.len().checked_sub(2)?;
+    let cbor_len = u16::from_be_bytes([code[len], code[len + 1]]) as usize;
+    let cbor = &code[len.checked_sub(cbor_len)?..len];
+    // the text key `ipfs` followed by a byte string of 34 bytes
+    const IPFS_KEY: &[u8] = &[0x64, b'i', b'p', b'f', b's', 0x58, 0x22];
+    let start =
+        cbor.windows(IPFS_KEY.len()).position(|window| window == IPFS_KEY)? + IPFS_KEY.len();
+    let multihash = cbor.get(start..start + 34)?;
+    Some(bs58::encode(multihash).into_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn can_read_metadata() {
+        let raw = r#"{"compiler":{"version":"0.8.10+commit.fc410830"}}"#;
+        let metadata = SolcMetadata::from_output(&json!(raw)).unwrap().unwrap();
+        assert_eq!(metadata.raw, raw);
+        assert_eq!(metadata.json["compiler"]["version"], "0.8.10+commit.fc410830");
+
+        let parsed = SolcMetadata::from_output(&metadata.json).unwrap().unwrap();
+        assert_eq!(parsed, metadata);
+
+        assert_eq!(SolcMetadata::from_output(&Value::Null).unwrap(), None);
+        assert_eq!(SolcMetadata::from_output(&json!("")).unwrap(), None);
+        assert!(SolcMetadata::from_output(&json!("{")).is_err());
+    }
+
+    #[test]
+    fn can_read_bytecode_metadata_hash() {
+        // a2 64 "ipfs" 58 22 <multihash> 64 "solc" 43 <version>
+        let mut cbor = vec![0xa2, 0x64, b'i', b'p', b'f', b's', 0x58, 0x22, 0x12, 0x20];
+        cbor.extend(0u8..32);
+        cbor.extend([0x64, b's', b'o', b'l', b'c', 0x43, 0x00, 0x08, 0x0a]);
+        let mut code = vec![0x60, 0x80, 0x60, 0x40, 0x52, 0xfe];
+        code.extend(&cbor);
+        code.extend((cbor.len() as u16).to_be_bytes());
+
+        assert_eq!(
+            bytecode_metadata_hash(&code).as_deref(),
+            Some("QmNLfbof5rLekrACjeuLk9JmGZD2HDBHCU4z16iYKmx5SE")
+        );
+        assert_eq!(bytecode_metadata_hash(&[0x60, 0x80, 0x00, 0x40]), None);
+        assert_eq!(bytecode_metadata_hash(&[]), None);
+    }
+}